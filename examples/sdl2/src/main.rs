@@ -0,0 +1,139 @@
+// A minimal SDL2 frontend for Oxide-7.
+// Shows video, audio, joypad input and save states in one file.
+//
+// Usage, from this directory: cargo run --release -- [ROM NAME]
+//
+// Controls:
+// Arrow keys: D-pad, X: A, Z: B, D: X, C: Y, A: L, S: R, Space: Select, Return: Start
+// F1: save state, F2: load state, Escape: quit
+
+use std::{
+    env,
+    fs,
+    path::Path
+};
+
+use sdl2::{
+    audio::{
+        AudioCallback,
+        AudioSpecDesired
+    },
+    event::Event,
+    keyboard::Keycode,
+    pixels::PixelFormatEnum
+};
+
+use oxide7::{
+    Button,
+    FrameLimiter,
+    SNES,
+    SNESAudioHandler,
+    SNESBuilder
+};
+
+// SDL pulls audio from the callback on its own thread.
+struct Audio(SNESAudioHandler);
+
+impl AudioCallback for Audio {
+    type Channel = f32;
+
+    fn callback(&mut self, out: &mut [f32]) {
+        self.0.get_audio_packet(out);
+    }
+}
+
+fn main() {
+    let cart_path = env::args().nth(1).expect("Usage: oxide-7-sdl2-example [ROM NAME]");
+    let state_path = Path::new(&cart_path).with_extension("state");
+
+    // The save file goes next to the cart by default, and is written at the end of each frame that changes it.
+    let mut snes = match SNESBuilder::from_file(&cart_path).build() {
+        Ok(snes) => snes,
+        Err(e) => {
+            println!("Couldn't load {}: {}", cart_path, e);
+            return;
+        }
+    };
+
+    let sdl = sdl2::init().unwrap();
+
+    // Video: the frame is RGBA, 512x224 by default.
+    let width = snes.frame_width() as u32;
+    let height = (snes.frame_buffer_size() / (snes.frame_width() * 4)) as u32;
+    let window = sdl.video().unwrap()
+        .window(&format!("Oxide-7: {}", snes.rom_name().trim_end()), 512, 448)
+        .position_centered()
+        .resizable()
+        .build()
+        .unwrap();
+    let mut canvas = window.into_canvas().build().unwrap();
+    let texture_creator = canvas.texture_creator();
+    let mut texture = texture_creator.create_texture_streaming(PixelFormatEnum::RGBA32, width, height).unwrap();
+    let mut frame = vec![0; snes.frame_buffer_size()];
+
+    // Audio: stereo f32 samples, at whatever rate the device picks.
+    let desired = AudioSpecDesired {
+        freq:       Some(48_000),
+        channels:   Some(2),
+        samples:    Some(1024),
+    };
+    let audio_device = sdl.audio().unwrap()
+        .open_playback(None, &desired, |spec| Audio(snes.enable_audio(spec.freq as f64)))
+        .unwrap();
+    audio_device.resume();
+
+    let mut events = sdl.event_pump().unwrap();
+    // Run at 50Hz for PAL carts, and 60Hz for NTSC carts.
+    let mut limiter = FrameLimiter::for_region(snes.region());
+    'running: loop {
+        for event in events.poll_iter() {
+            match event {
+                Event::Quit {..} | Event::KeyDown {keycode: Some(Keycode::Escape), ..} => break 'running,
+                Event::KeyDown {keycode: Some(Keycode::F1), repeat: false, ..} => {
+                    match fs::write(&state_path, snes.save_state()) {
+                        Ok(()) => println!("Saved state to {}", state_path.display()),
+                        Err(e) => println!("Couldn't save state: {}", e),
+                    }
+                },
+                Event::KeyDown {keycode: Some(Keycode::F2), repeat: false, ..} => {
+                    let result = fs::read(&state_path)
+                        .map_err(|e| e.to_string())
+                        .and_then(|state| snes.load_state(&state));
+                    if let Err(e) = result {
+                        println!("Couldn't load state: {}", e);
+                    }
+                },
+                Event::KeyDown {keycode: Some(key), repeat: false, ..} => set_button(&mut snes, key, true),
+                Event::KeyUp {keycode: Some(key), ..} => set_button(&mut snes, key, false),
+                _ => {},
+            }
+        }
+
+        limiter.wait();
+        snes.frame(&mut frame);
+
+        texture.update(None, &frame, (width * 4) as usize).unwrap();
+        canvas.copy(&texture, None, None).unwrap();
+        canvas.present();
+    }
+}
+
+// Keyboard mapping for joypad 1.
+fn set_button(snes: &mut SNES, key: Keycode, pressed: bool) {
+    let button = match key {
+        Keycode::X      => Button::A,
+        Keycode::Z      => Button::B,
+        Keycode::D      => Button::X,
+        Keycode::C      => Button::Y,
+        Keycode::A      => Button::L,
+        Keycode::S      => Button::R,
+        Keycode::Space  => Button::Select,
+        Keycode::Return => Button::Start,
+        Keycode::Up     => Button::Up,
+        Keycode::Down   => Button::Down,
+        Keycode::Left   => Button::Left,
+        Keycode::Right  => Button::Right,
+        _ => return,
+    };
+    snes.set_button(button, pressed, 0);
+}
@@ -1,4 +1,7 @@
-use oxide7::SNES;
+use oxide7::{
+    SNES,
+    debug::SymbolTable
+};
 
 macro_rules! make24 {
     ($hi:expr, $lo:expr) => {
@@ -9,11 +12,15 @@ macro_rules! make24 {
     };
 }
 
-pub fn debug_mode(snes: &mut SNES) {
+pub fn debug_mode(snes: &mut SNES, symbol_path: Option<&str>) {
     // Debug mode.
     snes.start_frame();
     println!("Debug mode.");
     println!("Enter 'h' for help.");
+    let mut symbols = match symbol_path {
+        Some(path) => load_symbols(path).unwrap_or_default(),
+        None => SymbolTable::new(),
+    };
     let mut breaks = std::collections::BTreeSet::new();
     let mut stack_trace = Vec::new();
     loop {
@@ -21,21 +28,21 @@ pub fn debug_mode(snes: &mut SNES) {
         match std::io::stdin().read_line(&mut input) {
             Ok(_) => if input.starts_with("b:") {
                 // Add breakpoint
-                match u32::from_str_radix(&input[2..].trim(), 16) {
-                    Ok(num) => {
-                        println!("Inserted breakpoint at ${:06X}", num);
+                match symbols.resolve(&input[2..].trim()) {
+                    Some(num) => {
+                        println!("Inserted breakpoint at {}", symbols.format_addr(num));
                         breaks.insert(num);
                     },
-                    Err(e) => println!("Invalid breakpoint: {}", e),
+                    None => println!("Invalid breakpoint: {}", input[2..].trim()),
                 }
             } else if input.starts_with("c:") {
                 // Remove breakpoint
-                match u32::from_str_radix(&input[2..].trim(), 16) {
-                    Ok(num) => {
-                        println!("Cleared breakpoint at ${:06X}", num);
+                match symbols.resolve(&input[2..].trim()) {
+                    Some(num) => {
+                        println!("Cleared breakpoint at {}", symbols.format_addr(num));
                         breaks.remove(&num);
                     },
-                    Err(e) => println!("Invalid breakpoint: {}", e),
+                    None => println!("Invalid breakpoint: {}", input[2..].trim()),
                 }
             } else if input.starts_with("c") {
                 // Remove all breakpoints
@@ -47,10 +54,10 @@ pub fn debug_mode(snes: &mut SNES) {
                     let state = snes.get_state();
                     let loc = make24!(state.pb, state.pc);
                     if breaks.contains(&loc) {
-                        println!("Break at ${:06X}", loc);
+                        println!("Break at {}", symbols.format_addr(loc));
                         break;
                     } else {
-                        step_and_trace(snes, &mut stack_trace, false);
+                        step_and_trace(snes, &mut stack_trace, &symbols, false);
                     }
                 }
             } else if input.starts_with("s:") {
@@ -58,14 +65,14 @@ pub fn debug_mode(snes: &mut SNES) {
                 match usize::from_str_radix(&input[2..].trim(), 10) {
                     Ok(num) => {
                         for _ in 0..num {
-                            step_and_trace(snes, &mut stack_trace, true);
+                            step_and_trace(snes, &mut stack_trace, &symbols, true);
                         }
                     },
                     Err(e) => println!("Invalid number of steps: {}", e),
                 }
             } else if input.starts_with("s") {
                 // Step
-                step_and_trace(snes, &mut stack_trace, true);
+                step_and_trace(snes, &mut stack_trace, &symbols, true);
             } else if input.starts_with("p:") {
                 // Print cpu or mem state
                 print(&input[2..].trim(), snes);
@@ -74,10 +81,82 @@ pub fn debug_mode(snes: &mut SNES) {
                 println!("{}", snes.get_state().to_string());
             } else if input.starts_with("t") {
                 let trace = stack_trace.iter()
-                    .map(|n| format!("${:06X}", n))
+                    .map(|n| symbols.format_addr(*n))
                     .collect::<Vec<_>>()
                     .join("\n");
                 println!("{}", trace);
+            } else if input.starts_with("e:") {
+                // Export background map
+                match usize::from_str_radix(&input[2..].trim(), 10) {
+                    Ok(bg) => match snes.export_bg_map(bg.wrapping_sub(1)) {
+                        Some(image) => save_image(&image, &format!("bg{}.png", bg)),
+                        None => println!("BG{} isn't used in this mode", bg),
+                    },
+                    Err(e) => println!("Invalid background: {}", e),
+                }
+            } else if input.starts_with("o:") {
+                // Export object tables
+                match u8::from_str_radix(&input[2..].trim(), 10) {
+                    Ok(palette) => save_image(&snes.export_obj_tables(palette), &format!("obj{}.png", palette)),
+                    Err(e) => println!("Invalid palette: {}", e),
+                }
+            } else if input.starts_with("f") {
+                match snes.get_fx_cache_state() {
+                    Some(state) => print!("{}", state),
+                    None => println!("No SuperFX in this cart"),
+                }
+            } else if input.starts_with("d:") {
+                // Print HDMA state
+                match usize::from_str_radix(&input[2..].trim(), 10) {
+                    Ok(line) => match snes.get_hdma_states().get(line) {
+                        Some(states) => for (chan, state) in states.iter().enumerate() {
+                            println!("{}: {}", chan, state);
+                        },
+                        None => println!("No HDMA state for line {} in the last frame", line),
+                    },
+                    Err(e) => println!("Invalid line: {}", e),
+                }
+            } else if input.starts_with("j:") {
+                // Print sprites evaluated for a line
+                match usize::from_str_radix(&input[2..].trim(), 10) {
+                    Ok(line) => match snes.get_sprite_lines().get(line.wrapping_sub(1)) {
+                        Some(state) => println!("{}", state),
+                        None => println!("No sprite state for line {} in the last frame", line),
+                    },
+                    Err(e) => println!("Invalid line: {}", e),
+                }
+            } else if input.starts_with("y:r") {
+                // Reset cycle profile
+                snes.reset_cycle_profile();
+                println!("Cleared cycle profile");
+            } else if input.starts_with("y") {
+                // Print cycle profile
+                print!("{}", snes.get_cycle_profile());
+            } else if input.starts_with("a:r") {
+                // Reset DSP clamp counts
+                snes.reset_dsp_clamp_stats();
+                println!("Cleared DSP clamp counts");
+            } else if input.starts_with("a") {
+                // Print DSP clamp counts
+                println!("{}", snes.get_dsp_clamp_stats());
+            } else if input.starts_with("w:") {
+                // Explain a pixel
+                let coords = input[2..].trim().split(',')
+                    .map(|n| usize::from_str_radix(n.trim(), 10))
+                    .collect::<Result<Vec<_>, _>>();
+                match coords.as_deref() {
+                    Ok([x, y]) => match snes.explain_pixel(*x, *y) {
+                        Some(explanation) => println!("{}", explanation),
+                        None => println!("{},{} is off screen", x, y),
+                    },
+                    Ok(_) => println!("Expected x,y"),
+                    Err(e) => println!("Invalid position: {}", e),
+                }
+            } else if input.starts_with("l:") {
+                // Load symbols
+                if let Some(new_symbols) = load_symbols(&input[2..].trim()) {
+                    symbols = new_symbols;
+                }
             } else if input.starts_with("h") {
                 // Help
                 help();
@@ -128,8 +207,8 @@ fn print(s: &str, snes: &mut SNES) {
 }
 
 fn help() {
-    println!("b:x: New breakpoint at memory location x (hex).");
-    println!("c:x: Clear breakpoint at memory location x (hex).");
+    println!("b:x: New breakpoint at memory location x (hex or label).");
+    println!("c:x: Clear breakpoint at memory location x (hex or label).");
     println!("r: Keep running until a breakpoint is hit.");
     println!("s: Step a single instruction.");
     println!("s:x: Step multiple instructions (base 10).");
@@ -137,11 +216,42 @@ fn help() {
     println!("p: Print the current state of the CPU.");
     println!("p:x: Print x - if x is a number, print the contents of that address, otherwise print the register.");
     println!("p:x-y: Print the memory in the range x -> y.");
+    println!("e:x: Export BG x (1-4) map to bgx.png and bgx.pal.");
+    println!("o:x: Export the object tables using palette x (0-7) to objx.png and objx.pal.");
+    println!("f: Print the SuperFX instruction cache and hit/miss counts.");
+    println!("d:x: Print the state of each HDMA channel at line x (base 10) of the last frame.");
+    println!("j:x: Print the objects selected and dropped at line x (base 10) of the last frame.");
+    println!("y: Print the cycles taken by the last instruction, and the total for each opcode.");
+    println!("y:r: Clear the cycle counts for each opcode.");
+    println!("a: Print the number of times each stage of DSP mixing has clamped.");
+    println!("a:r: Clear the DSP clamp counts.");
+    println!("w:x,y: Explain the colour of the pixel at x,y (base 10), using the current PPU state.");
+    println!("l:x: Load labels from symbol file x (WLA-DX .sym).");
     println!("q: Quit execution.");
 }
 
+fn load_symbols(path: &str) -> Option<SymbolTable> {
+    match SymbolTable::load(path) {
+        Ok(symbols) => {
+            println!("Loaded {} labels from {}", symbols.len(), path);
+            Some(symbols)
+        },
+        Err(e) => {
+            println!("Couldn't load symbols: {}", e);
+            None
+        },
+    }
+}
+
+fn save_image(image: &oxide7::debug::IndexedImage, path: &str) {
+    match image.save(path) {
+        Ok(_) => println!("Saved {}x{} image to {}", image.width, image.height, path),
+        Err(e) => println!("Couldn't save image: {}", e),
+    }
+}
+
 // Step the CPU, and add the PC to the stack trace if it calls.
-fn step_and_trace(snes: &mut SNES, stack_trace: &mut Vec<u32>, print: bool) {
+fn step_and_trace(snes: &mut SNES, stack_trace: &mut Vec<u32>, symbols: &SymbolTable, print: bool) {
     let instr = snes.get_instr();
     match instr[0] {
         0x22 | 0x20 | 0xFC => {
@@ -156,6 +266,9 @@ fn step_and_trace(snes: &mut SNES, stack_trace: &mut Vec<u32>, print: bool) {
     if print {
         let state = snes.get_state();
         let pc = make24!(state.pb, state.pc);
+        if let Some(label) = symbols.label_at(pc) {
+            println!("{}:", label);
+        }
         println!("${:06X}: ${:02X} ({:02X} {:02X} {:02X})", pc, instr[0], instr[1], instr[2], instr[3]);
     }
 
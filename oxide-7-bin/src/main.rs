@@ -36,8 +36,18 @@ fn main() {
         (about: "Super Nintendo Entertainment System emulator.")
         (@arg CART: "The path to the game cart to use.")
         (@arg debug: -d "Enter debug mode.")
+        (@arg symbols: --sym +takes_value "Symbol file (WLA-DX .sym) with labels to use in debug mode.")
         (@arg save: -s +takes_value "Save file path.")
-        (@arg dsprom: -r +takes_value "DSP ROM path. Needed for DSP games (e.g. Super Mario Kart, Pilotwings)")
+        (@arg nopatch: --nopatch "Don't apply a .bps or .ips patch with the same name as the cart.")
+        (@arg dsprom: -r +takes_value "DSP ROM path, or a directory of DSP ROMs named dsp1.rom, dsp1b.rom, dsp2.rom etc. Needed for DSP games (e.g. Super Mario Kart, Pilotwings)")
+        (@arg blend: -b "Blend hires pixels together into a 256-wide frame.")
+        (@arg nospritelimit: -l "Remove the per-line sprite limit, to reduce flicker.")
+        (@arg fastboot: --fastboot "Skip the APU upload handshake waits, to load audio faster.")
+        (@arg dcblock: --dcblock "Filter out any DC offset from the audio output.")
+        (@arg widescreen: -w +takes_value "Experimental: draw this many extra background pixels on each side of the screen.")
+        (@arg dump: --dump +takes_value "Run without a window, and write a log of frame hashes, audio hashes and input to this file.")
+        (@arg frames: --frames +takes_value "Number of frames to run with --dump. Defaults to 3600.")
+        (@arg verifyrender: --verifyrender "Draw each frame on the main thread as well as the render thread, and report frames that differ.")
     );
 
     let cmd_args = app.get_matches();
@@ -47,16 +57,43 @@ fn main() {
         None => panic!("Usage: oxide7 [cart name]. Run with --help for more options."),
     };
 
-    let save_file_path = match cmd_args.value_of("save") {
-        Some(c) => c.to_string(),
-        None => make_save_name(&cart_path),
+    let mut builder = SNESBuilder::from_file(&cart_path)
+        .soft_patch(!cmd_args.is_present("nopatch"))
+        .sprite_limit(!cmd_args.is_present("nospritelimit"))
+        .fast_apu_boot(cmd_args.is_present("fastboot"))
+        .dc_block(cmd_args.is_present("dcblock"))
+        .hires_blending(cmd_args.is_present("blend"));
+    if let Some(save_path) = cmd_args.value_of("save") {
+        builder = builder.save_path(save_path);
+    }
+    if let Some(dsp_path) = cmd_args.value_of("dsprom") {
+        builder = builder.dsp_rom(dsp_path);
+    }
+    if let Some(extra) = cmd_args.value_of("widescreen") {
+        builder = builder.widescreen(extra.parse().expect("Widescreen must be a number of pixels."));
+    }
+
+    let mut snes = match builder.build() {
+        Ok(snes) => snes,
+        Err(e) => {
+            println!("Couldn't load {}: {}", cart_path, e);
+            ::std::process::exit(1);
+        }
     };
+    report_events(&mut snes);
+    if cmd_args.is_present("verifyrender") {
+        snes.set_render_verification(true);
+    }
 
-    let mut snes = SNES::new(&cart_path, &save_file_path, cmd_args.value_of("dsprom"));
+    let frame_width = snes.frame_width() as u32;
+    let frame_size = snes.frame_buffer_size();
 
-    if cmd_args.is_present("debug") {
+    if let Some(dump_path) = cmd_args.value_of("dump") {
+        let frames = cmd_args.value_of("frames").map_or(3600, |f| f.parse().expect("Frames must be a number."));
+        run_dump(&mut snes, dump_path, frames, frame_size);
+    } else if cmd_args.is_present("debug") {
         //#[cfg(feature = "debug")]
-        debug::debug_mode(&mut snes);
+        debug::debug_mode(&mut snes, cmd_args.value_of("symbols"));
     } else {
         let event_loop = EventLoop::new();
         let window = WindowBuilder::new()
@@ -107,7 +144,7 @@ fn main() {
         });
 
         let texture_extent = wgpu::Extent3d {
-            width: 512,
+            width: frame_width,
             height: 224,
             depth: 1
         };
@@ -230,8 +267,7 @@ fn main() {
             alpha_to_coverage_enabled: false,
         });
 
-        let mut last_frame_time = chrono::Utc::now();
-        let frame_time = chrono::Duration::nanoseconds(1_000_000_000 / 60);
+        let mut limiter = oxide7::FrameLimiter::for_region(snes.region());
     
         // AUDIO
         let audio_stream = make_audio_stream(&mut snes);
@@ -247,6 +283,9 @@ fn main() {
                     event: w,
                 } => match w {
                     WindowEvent::CloseRequested => {
+                        for feature in snes.compat_report() {
+                            println!("Unsupported feature used: {}", feature);
+                        }
                         ::std::process::exit(0);
                     },
                     WindowEvent::KeyboardInput {
@@ -283,67 +322,64 @@ fn main() {
                     WindowEvent::Focused(focused) => {
                         in_focus = focused;
                         if !in_focus {
-                            audio_stream.pause().expect("Couldn't pause audio stream");
+                            snes.pause();
                         } else {
-                            audio_stream.play().expect("Couldn't restart audio stream");
+                            snes.resume();
+                            limiter.reset();
                         }
                     },
                     _ => {}
                 },
                 Event::RedrawRequested(_) => {
-                    let now = chrono::Utc::now();
-                    if now.signed_duration_since(last_frame_time) >= frame_time {
-                        last_frame_time = now;
-
-                        let mut buf = device.create_buffer_mapped(&wgpu::BufferDescriptor {
-                            label: None,
-                            size: FRAME_BUFFER_SIZE as u64,
-                            usage: wgpu::BufferUsage::COPY_SRC | wgpu::BufferUsage::MAP_WRITE
+                    limiter.wait();
+
+                    let mut buf = device.create_buffer_mapped(&wgpu::BufferDescriptor {
+                        label: None,
+                        size: frame_size as u64,
+                        usage: wgpu::BufferUsage::COPY_SRC | wgpu::BufferUsage::MAP_WRITE
+                    });
+    
+                    snes.frame(&mut buf.data);
+    
+                    let tex_buffer = buf.finish();
+    
+                    let frame = swapchain.get_next_texture().expect("Timeout when acquiring next swapchain tex.");
+                    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {label: None});
+    
+                    encoder.copy_buffer_to_texture(
+                        wgpu::BufferCopyView {
+                            buffer: &tex_buffer,
+                            offset: 0,
+                            bytes_per_row: 4 * texture_extent.width,
+                            rows_per_image: 0
+                        },
+                        wgpu::TextureCopyView {
+                            texture: &texture,
+                            mip_level: 0,
+                            array_layer: 0,
+                            origin: wgpu::Origin3d::ZERO,
+                        },
+                        texture_extent
+                    );
+    
+                    {
+                        let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                            color_attachments: &[wgpu::RenderPassColorAttachmentDescriptor {
+                                attachment: &frame.view,
+                                resolve_target: None,
+                                load_op: wgpu::LoadOp::Clear,
+                                store_op: wgpu::StoreOp::Store,
+                                clear_color: wgpu::Color::WHITE,
+                            }],
+                            depth_stencil_attachment: None,
                         });
-        
-                        snes.frame(&mut buf.data);
-        
-                        let tex_buffer = buf.finish();
-        
-                        let frame = swapchain.get_next_texture().expect("Timeout when acquiring next swapchain tex.");
-                        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {label: None});
-        
-                        encoder.copy_buffer_to_texture(
-                            wgpu::BufferCopyView {
-                                buffer: &tex_buffer,
-                                offset: 0,
-                                bytes_per_row: 4 * texture_extent.width,
-                                rows_per_image: 0
-                            },
-                            wgpu::TextureCopyView {
-                                texture: &texture,
-                                mip_level: 0,
-                                array_layer: 0,
-                                origin: wgpu::Origin3d::ZERO,
-                            },
-                            texture_extent
-                        );
-        
-                        {
-                            let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                                color_attachments: &[wgpu::RenderPassColorAttachmentDescriptor {
-                                    attachment: &frame.view,
-                                    resolve_target: None,
-                                    load_op: wgpu::LoadOp::Clear,
-                                    store_op: wgpu::StoreOp::Store,
-                                    clear_color: wgpu::Color::WHITE,
-                                }],
-                                depth_stencil_attachment: None,
-                            });
-                            rpass.set_pipeline(&render_pipeline);
-                            rpass.set_bind_group(0, &bind_group, &[]);
-                            rpass.set_vertex_buffer(0, &vertex_buf, 0, 0);
-                            rpass.draw(0..4, 0..1);
-                        }
-        
-                        queue.submit(&[encoder.finish()]);
+                        rpass.set_pipeline(&render_pipeline);
+                        rpass.set_bind_group(0, &bind_group, &[]);
+                        rpass.set_vertex_buffer(0, &vertex_buf, 0, 0);
+                        rpass.draw(0..4, 0..1);
                     }
-                    
+    
+                    queue.submit(&[encoder.finish()]);
                 },
                 _ => {},
             }
@@ -352,11 +388,30 @@ fn main() {
     }
 }
 
-fn make_save_name(cart_name: &str) -> String {
-    match cart_name.find(".") {
-        Some(pos) => cart_name[0..pos].to_string() + ".sav",
-        None      => cart_name.to_string() + ".sav"
+// Run the SNES headless with no input, and log each frame.
+fn run_dump(snes: &mut SNES, dump_path: &str, frames: u64, frame_size: usize) {
+    let mut dump = FrameDump::new(snes, dump_path).expect("Couldn't create dump file");
+    let mut buf = vec![0_u8; frame_size];
+    while dump.frame_count() < frames {
+        dump.frame(snes, &mut buf).expect("Couldn't write to dump file");
     }
+    dump.flush().expect("Couldn't write to dump file");
+}
+
+// Print problems found when loading the cart, and each frame where the render thread
+// and the main thread drew something different.
+fn report_events(snes: &mut SNES) {
+    let events = snes.subscribe_events();
+    std::thread::spawn(move || {
+        for event in events.iter() {
+            match event {
+                oxide7::Event::BadChecksum {header, actual} => println!("Warning: bad checksum: header {:04X}, actual {:04X}", header, actual),
+                oxide7::Event::BadPatch(reason) => println!("Warning: couldn't apply patch: {}", reason),
+                oxide7::Event::RenderMismatch {threaded, single} => println!("Render mismatch: threaded {:016x}, single-threaded {:016x}", threaded, single),
+                _ => {},
+            }
+        }
+    });
 }
 
 fn make_audio_stream(snes: &mut SNES) -> cpal::Stream {
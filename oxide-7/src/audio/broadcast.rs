@@ -0,0 +1,35 @@
+// Distributes sample packets from the DSP to any number of audio consumers.
+use alloc::vec::Vec;
+
+use crate::sync::{
+    Arc, Mutex,
+    unbounded,
+    Receiver,
+    Sender
+};
+
+use super::SamplePacket;
+
+#[derive(Clone, Default)]
+pub struct SampleBroadcast {
+    senders:    Arc<Mutex<Vec<Sender<SamplePacket>>>>,
+}
+
+impl SampleBroadcast {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Create a new receiver, which will get all sample packets sent from this point onwards.
+    pub fn subscribe(&self) -> Receiver<SamplePacket> {
+        let (tx, rx) = unbounded();
+        self.senders.lock().push(tx);
+        rx
+    }
+
+    // Send a packet to every consumer.
+    // Consumers whose receiver has been dropped are removed.
+    pub fn send(&self, packet: SamplePacket) {
+        self.senders.lock().retain(|tx| tx.send(packet.clone()).is_ok());
+    }
+}
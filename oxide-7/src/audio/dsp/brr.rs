@@ -1,6 +1,10 @@
 // Decoding the bit rate reduction format.
 
 use bitflags::bitflags;
+use serde::{
+    Serialize,
+    Deserialize
+};
 
 use crate::mem::RAM;
 
@@ -64,6 +68,7 @@ impl BRRHead {
 }
 
 // A block of 16 BRR-decoded samples.
+#[derive(Serialize, Deserialize)]
 pub struct SampleBlock {
     samples:    [i16; 16],
     prev_0:     i16,
@@ -0,0 +1,26 @@
+// High-pass filter to remove DC offset from the mixer output.
+// A single pole, with the cutoff around 20Hz at 32kHz.
+
+use crate::audio::Stereo;
+
+const POLE: f32 = 0.996;
+
+#[derive(Default)]
+pub struct DCBlock {
+    prev_in:    Stereo<f32>,
+    prev_out:   Stereo<f32>,
+}
+
+impl DCBlock {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn apply(&mut self, frame: Stereo<f32>) -> Stereo<f32> {
+        for (i, sample) in frame.iter().enumerate() {
+            self.prev_out[i] = sample - self.prev_in[i] + (POLE * self.prev_out[i]);
+            self.prev_in[i] = *sample;
+        }
+        self.prev_out
+    }
+}
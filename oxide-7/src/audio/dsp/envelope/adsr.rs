@@ -1,8 +1,12 @@
 // ADSR envelope setup.
 use bitflags::bitflags;
+use serde::{
+    Serialize,
+    Deserialize
+};
 
 bitflags! {
-    #[derive(Default)]
+    #[derive(Default, Serialize, Deserialize)]
     pub struct ADSRSettings: u16 {
         const SUSTAIN_LEVEL = bits16![15, 14, 13];
         const RELEASE = bits16![12, 11, 10, 9, 8];
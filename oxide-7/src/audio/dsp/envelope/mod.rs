@@ -2,6 +2,11 @@
 mod adsr;
 mod gain;
 
+use serde::{
+    Serialize,
+    Deserialize
+};
+
 pub use gain::step_size;
 
 use adsr::*;
@@ -14,6 +19,7 @@ const BENT_STEP: i16 = 8;     // Bent line slow increase gain adjustment per ste
 
 const BENT_MAX: i16 = 1536;        // Point at which bent line switches from fast to slow increase.
 
+#[derive(Serialize, Deserialize)]
 pub struct Envelope {
     adsr:           ADSRSettings,
     sustain:        i16,
@@ -31,7 +37,7 @@ impl Envelope {
 
         let state = EnvelopeState::new(adsr_settings, gain_settings);
         let sustain = adsr_settings.sustain_level();
-        let initial_gain = state.initial_gain();
+        let initial_gain = state.initial_gain(sustain);
 
         Envelope {
             adsr:           adsr_settings,
@@ -52,6 +58,20 @@ impl Envelope {
     pub fn muted(&self) -> bool {
         self.state == EnvelopeState::Fade && self.gain <= 0
     }
+
+    // Simplified state, for visualisation.
+    pub fn phase(&self) -> super::EnvelopePhase {
+        use super::EnvelopePhase;
+        match self.state {
+            EnvelopeState::Attack(_) => EnvelopePhase::Attack,
+            EnvelopeState::Decay(_) => EnvelopePhase::Decay,
+            EnvelopeState::Sustain(_) => EnvelopePhase::Sustain,
+            EnvelopeState::Static(_) if self.adsr.contains(ADSRSettings::ENABLE) => EnvelopePhase::Sustain,
+            EnvelopeState::Fade if self.gain <= 0 => EnvelopePhase::Off,
+            EnvelopeState::Fade => EnvelopePhase::Release,
+            _ => EnvelopePhase::Gain,
+        }
+    }
 }
 
 impl Iterator for Envelope {
@@ -178,7 +198,7 @@ impl Iterator for Envelope {
 
 // States along with the step time for each change.
 // The associated values here are the step size; i.e. how many samples should be emitted before altering gain.
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub enum EnvelopeState {
     // ADSR
     Attack(usize),  // Increase of 1/64 per step.
@@ -207,8 +227,10 @@ impl EnvelopeState {
         }
     }
 
+    // Sustain and fade only follow other states, but if they come first
+    // they start from the level they would have reached.
     #[inline]
-    fn initial_gain(&self) -> i16 {
+    fn initial_gain(&self, sustain: i16) -> i16 {
         use EnvelopeState::*;
         match self {
             Attack(_) |
@@ -217,8 +239,9 @@ impl EnvelopeState {
             Decay(_) |
             LinearDecrease(_) |
             ExpDecrease(_) => MAX_GAIN,
+            Sustain(_) => sustain,
             Static(v) => *v,
-            _ => panic!("Don't initialise with state {:?}", self)
+            Fade => 0
         }
     }
 }
\ No newline at end of file
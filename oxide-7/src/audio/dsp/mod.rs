@@ -3,29 +3,56 @@
 // Clamp val between min and max.
 macro_rules! clamp {
     ($val:expr, $min:expr, $max:expr) => {
-        std::cmp::min($max, std::cmp::max($min, $val))
+        core::cmp::min($max, core::cmp::max($min, $val))
     };
 }
 
+// Clamp a mixed value to 16 bits. In debug builds, overflows are added to the count provided.
+macro_rules! clamp16 {
+    ($count:expr, $val:expr) => {{
+        let val = $val;
+        let clamped = clamp!(val, core::i16::MIN as i32, core::i16::MAX as i32);
+        #[cfg(feature = "debug")]
+        if clamped != val {
+            $count += 1;
+        }
+        clamped
+    }};
+}
+
 mod brr;
+mod dcblock;
 mod envelope;
 mod voice;
 
 use bitflags::bitflags;
-use crossbeam_channel::Sender;
-use dasp::frame::{
-    Frame,
-    Stereo
+use serde::{
+    Serialize,
+    Deserialize
 };
 
 pub use voice::*;
+use dcblock::DCBlock;
+
+use alloc::{
+    boxed::Box,
+    string::String,
+    vec::Vec
+};
 
 use crate::{
+    audio::Stereo,
     constants::timing,
-    mem::RAM
+    mem::RAM,
+    savestate::{
+        Stateful,
+        StateWriter,
+        StateReader
+    }
 };
 
 bitflags! {
+    #[derive(Serialize, Deserialize)]
     pub struct DSPFlags: u8 {
         const SOFT_RESET    = bit!(7);
         const MUTE          = bit!(6);
@@ -44,7 +71,7 @@ const SPC_SAMPLE_RATE: usize = 32_000;
 const SAMPLE_CYCLES: usize = timing::SPC_CLOCK_RATE / SPC_SAMPLE_RATE;
 const SAMPLE_BATCH_SIZE: usize = 64;
 
-#[derive(Default, Clone)]
+#[derive(Default, Clone, Serialize, Deserialize)]
 pub struct DSPRegisters {
 
     main_vol_left:  u8,
@@ -57,9 +84,8 @@ pub struct DSPRegisters {
     key_on:         u8,
     key_off:        u8,
 
-    endx:           u8,
-
     echo_feedback:  i8,
+    unused:         u8, // $1D: not used by the DSP, but can be written and read back.
     pitch_mod:      u8,
 
     noise_enable:   u8,
@@ -73,9 +99,10 @@ pub struct DSPRegisters {
 }
 
 pub struct DSP {
-    signal_tx:      Sender<super::SamplePacket>,
+    signal_tx:      super::SampleBroadcast,
     cycle_count:    usize,
     frames:         Vec<Stereo<f32>>,
+    sample_count:   u64,    // Samples generated since power on. Used for timestamps.
 
     echo_buffer_size:   u16,
 
@@ -86,26 +113,34 @@ pub struct DSP {
     noise_step:         Option<usize>,
     noise_count:        usize,
 
+    dc_block:           Option<DCBlock>,    // Filter the output. Raw output is bit-exact.
+
     regs:           DSPRegisters,
     voices:         [Voice; 8],
+
+    #[cfg(feature = "debug")]
+    clamp_stats:    crate::debug::DSPClampStats,
 }
 
 impl DSP {
-    pub fn new(signal_tx: Sender<super::SamplePacket>) -> Self {
+    pub fn new(signal_tx: super::SampleBroadcast) -> Self {
         DSP {
             signal_tx:      signal_tx,
             cycle_count:    0,
             frames:         Vec::with_capacity(SAMPLE_BATCH_SIZE),
+            sample_count:   0,
 
             echo_buffer_size:   0,
 
-            fir_buffer:         [Stereo::EQUILIBRIUM; 8],
+            fir_buffer:         [[0; 2]; 8],
             fir_buffer_index:   0,
 
             noise_level:        -0x4000,
             noise_step:         None,
             noise_count:        0,
 
+            dc_block:           None,
+
             regs:           DSPRegisters::default(),
             voices:         [
                 Voice::new(),
@@ -117,21 +152,34 @@ impl DSP {
                 Voice::new(),
                 Voice::new(),
             ],
+
+            #[cfg(feature = "debug")]
+            clamp_stats:    crate::debug::DSPClampStats::default(),
         }
     }
 
+    // Enable a high-pass filter on the output, to remove any DC offset.
+    pub fn set_dc_block(&mut self, enable: bool) {
+        self.dc_block = if enable {Some(DCBlock::new())} else {None};
+    }
+
     pub fn clock(&mut self, cycles: usize, ram: &mut RAM) {
         // Generate a new sample every 32 cycles.
         self.cycle_count += cycles;
         if self.cycle_count >= SAMPLE_CYCLES {
             self.generate_frame(ram);
             self.cycle_count -= SAMPLE_CYCLES;
+            self.sample_count += 1;
         }
 
         // Every 16384 cycles, send the batch of 512 samples over to the audio thread.
+        // The SPC runs in lockstep with the CPU, so the sample count gives the time in master cycles.
         if self.frames.len() >= SAMPLE_BATCH_SIZE {
-            let in_ = self.frames.drain(..).collect::<Box<[_]>>();
-            self.signal_tx.send(in_).unwrap();
+            let first_sample = self.sample_count - (self.frames.len() as u64);
+            self.signal_tx.send(super::SamplePacket {
+                timestamp:  (first_sample as f64 * super::MASTER_CYCLES_PER_SAMPLE) as u64,
+                samples:    self.frames.drain(..).collect::<Box<[_]>>(),
+            });
         }
     }
 
@@ -148,6 +196,7 @@ impl DSP {
             0x7C => self.read_endx(),
 
             0x0D => self.regs.echo_feedback as u8,
+            0x1D => self.regs.unused,
             0x2D => self.regs.pitch_mod,
             0x3D => self.regs.noise_enable,
             0x4D => self.regs.echo_enable,
@@ -172,9 +221,10 @@ impl DSP {
             0x4C => self.set_key_on(data, ram),
             0x5C => self.set_key_off(data),
             0x6C => self.set_flags(data),
-            0x7C => self.regs.endx = data,
+            0x7C => self.clear_endx(),
 
             0x0D => self.regs.echo_feedback = data as i8,
+            0x1D => self.regs.unused = data,
             0x2D => self.set_pitch_mod(data),
             0x3D => self.set_noise_enable(data),
             0x4D => self.set_echo_enable(data),
@@ -189,14 +239,59 @@ impl DSP {
             _ => {}
         }
     }
+
+    pub fn voice_states(&self) -> [VoiceState; 8] {
+        let mut states = [self.voices[0].get_state(); 8];
+        for (state, voice) in states.iter_mut().zip(self.voices.iter()).skip(1) {
+            *state = voice.get_state();
+        }
+        states
+    }
+}
+
+#[cfg(feature = "debug")]
+impl DSP {
+    pub fn clamp_stats(&self) -> crate::debug::DSPClampStats {
+        self.clamp_stats.clone()
+    }
+
+    pub fn reset_clamp_stats(&mut self) {
+        self.clamp_stats = crate::debug::DSPClampStats::default();
+    }
+}
+
+impl Stateful for DSP {
+    // Pending samples that haven't been sent out are dropped.
+    // The DC block filter isn't saved: it settles again within a few milliseconds.
+    fn save_state(&self, w: &mut StateWriter) {
+        w.write(&self.cycle_count);
+        w.write(&self.echo_buffer_size);
+        w.write(&self.fir_buffer);
+        w.write(&self.fir_buffer_index);
+        w.write(&self.noise_level);
+        w.write(&self.noise_step);
+        w.write(&self.noise_count);
+        w.write(&self.regs);
+        w.write(&self.voices);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), String> {
+        self.frames.clear();
+        r.read_into(&mut self.cycle_count)?;
+        r.read_into(&mut self.echo_buffer_size)?;
+        r.read_into(&mut self.fir_buffer)?;
+        r.read_into(&mut self.fir_buffer_index)?;
+        r.read_into(&mut self.noise_level)?;
+        r.read_into(&mut self.noise_step)?;
+        r.read_into(&mut self.noise_count)?;
+        r.read_into(&mut self.regs)?;
+        r.read_into(&mut self.voices)
+    }
 }
 
 impl DSP {
     // Generate a single left-right pair of audio samples.
     fn generate_frame(&mut self, ram: &mut RAM) {
-        const MIN: i32 = std::i16::MIN as i32;
-        const MAX: i32 = std::i16::MAX as i32;
-
         let mut main_left = 0;  // Main signal
         let mut main_right = 0;
         let mut prev = 0;       // Previous channel's sample for pitch modulation
@@ -207,15 +302,18 @@ impl DSP {
             if let Some(v) = voice.generate(&ram, prev, self.noise_level) {
                 prev = v;
 
+                // Volumes are signed: a negative volume inverts the phase, which some games use for surround.
                 let v_samp = v as i32;
-                let voice_left = clamp!((v_samp * voice.read_left_vol()) >> 6, MIN, MAX);
-                let voice_right = clamp!((v_samp * voice.read_right_vol()) >> 6, MIN, MAX);
-                main_left += voice_left;
-                main_right += voice_right;
+                let voice_left = (v_samp * voice.read_left_vol()) >> 6;
+                let voice_right = (v_samp * voice.read_right_vol()) >> 6;
+
+                // The sums are clamped as each voice is added, so the order of voices matters when they overflow.
+                main_left = clamp16!(self.clamp_stats.main_mix, main_left + voice_left);
+                main_right = clamp16!(self.clamp_stats.main_mix, main_right + voice_right);
 
                 if voice.is_echo_enabled() {
-                    echo_left += voice_left;
-                    echo_right += voice_right;
+                    echo_left = clamp16!(self.clamp_stats.echo_mix, echo_left + voice_left);
+                    echo_right = clamp16!(self.clamp_stats.echo_mix, echo_right + voice_right);
                 }
             } else {
                 prev = 0;
@@ -227,22 +325,38 @@ impl DSP {
         let echo = self.generate_echo(ram, echo_left as i16, echo_right as i16);
 
         let frame = if self.is_mute() {
-            Stereo::EQUILIBRIUM
+            [0.0; 2]
         } else {
-            let mut left = clamp!((main_left * (self.regs.main_vol_left as i32)) >> 7, MIN, MAX);
-            let mut right = clamp!((main_right * (self.regs.main_vol_right as i32)) >> 7, MIN, MAX);
-            left += clamp!(((echo[0] as i32) * (self.regs.echo_vol_left as i32)) >> 7, MIN, MAX);
-            right += clamp!(((echo[1] as i32) * (self.regs.echo_vol_right as i32)) >> 7, MIN, MAX);
-            left = clamp!(left, MIN, MAX);
-            right = clamp!(right, MIN, MAX);
+            // Master and echo volumes are signed. Each product is truncated to 16 bits before they are summed.
+            let main_out = [
+                (((main_left * (self.regs.main_vol_left as i8 as i32)) >> 7) as i16) as i32,
+                (((main_right * (self.regs.main_vol_right as i8 as i32)) >> 7) as i16) as i32
+            ];
+            let echo_out = [
+                ((((echo[0] as i32) * (self.regs.echo_vol_left as i8 as i32)) >> 7) as i16) as i32,
+                ((((echo[1] as i32) * (self.regs.echo_vol_right as i8 as i32)) >> 7) as i16) as i32
+            ];
+            let left = clamp16!(self.clamp_stats.output, main_out[0] + echo_out[0]);
+            let right = clamp16!(self.clamp_stats.output, main_out[1] + echo_out[1]);
             [(left as f32) / 32_768.0, (right as f32) / 32_768.0]
         };
 
+        let frame = match &mut self.dc_block {
+            Some(filter) => filter.apply(frame),
+            None => frame
+        };
+
         self.frames.push(frame);
     }
 
     // Generate a single echo frame based on the main output.
+    // The echo buffer is in audio RAM, so it overwrites anything else that is in the same region.
     fn generate_echo(&mut self, ram: &mut RAM, main_left: i16, main_right: i16) -> Stereo<i16> {
+        // A new buffer size only takes effect at the start of the buffer.
+        if self.regs.echo_internal_counter == 0 {
+            self.latch_echo_buffer_size();
+        }
+
         let echo_buffer_address = make16!(self.regs.echo_offset, 0).wrapping_add(self.regs.echo_internal_counter);
         let buffer_samples = (0..4).map(|i| ram.read(echo_buffer_address.wrapping_add(i) as u32)).collect::<Box<[_]>>();
         let buffer_sample_left = make16!(buffer_samples[1], buffer_samples[0]) as i16;
@@ -253,11 +367,12 @@ impl DSP {
         // Write new samples.
         if !self.regs.flags.contains(DSPFlags::ECHO_WRITES) {
             let feedback_vol = self.regs.echo_feedback as i32;
-            let feedback_left = (((echo_val[0] as i32) * feedback_vol) >> 7) as i16;
-            let feedback_right = (((echo_val[1] as i32) * feedback_vol) >> 7) as i16;
+            let feedback_left = ((((echo_val[0] as i32) * feedback_vol) >> 7) as i16) as i32;
+            let feedback_right = ((((echo_val[1] as i32) * feedback_vol) >> 7) as i16) as i32;
 
-            let buffer_input_left = main_left + feedback_left;
-            let buffer_input_right = main_right + feedback_right;
+            // The lowest bit is always cleared.
+            let buffer_input_left = (clamp16!(self.clamp_stats.echo_feedback, (main_left as i32) + feedback_left) & !1) as u16;
+            let buffer_input_right = (clamp16!(self.clamp_stats.echo_feedback, (main_right as i32) + feedback_right) & !1) as u16;
 
             ram.write(echo_buffer_address as u32, lo!(buffer_input_left));
             ram.write(echo_buffer_address.wrapping_add(1) as u32, hi!(buffer_input_left));
@@ -276,7 +391,7 @@ impl DSP {
     fn calculate_fir(&mut self, new_sample: Stereo<i16>) -> Stereo<i16> {
         self.fir_buffer[self.fir_buffer_index] = new_sample;
 
-        let out = (0..8).fold(Stereo::EQUILIBRIUM, |acc: Stereo<i32>, i| {
+        let out = (0..8).fold([0; 2], |acc: Stereo<i32>, i| {
             let index = self.fir_buffer_index.wrapping_sub(7 - i) & 7;
             let buffer_sample = self.fir_buffer[index];
             let fir_coef = self.regs.echo_fir_coefs[i] as i32;
@@ -288,8 +403,8 @@ impl DSP {
         //let left = out[0] + (new_sample[0] as i32) * (self.regs.echo_fir_coefs[7] as i32);
         //let right = out[1] + (new_sample[1] as i32) * (self.regs.echo_fir_coefs[7] as i32);
 
-        let left_clamped = clamp!(out[0], std::i16::MIN as i32, std::i16::MAX as i32) as i16;
-        let right_clamped = clamp!(out[1], std::i16::MIN as i32, std::i16::MAX as i32) as i16;
+        let left_clamped = clamp16!(self.clamp_stats.fir, out[0]) as i16;
+        let right_clamped = clamp16!(self.clamp_stats.fir, out[1]) as i16;
 
         self.fir_buffer_index = (self.fir_buffer_index + 1) & 7;
 
@@ -352,6 +467,13 @@ impl DSP {
         })
     }
 
+    // Writing any value to ENDX clears all the bits.
+    fn clear_endx(&mut self) {
+        for voice in self.voices.iter_mut() {
+            voice.clear_endx();
+        }
+    }
+
     fn set_flags(&mut self, val: u8) {
         self.regs.flags = DSPFlags::from_bits_truncate(val);
         if self.regs.flags.contains(DSPFlags::SOFT_RESET) {
@@ -402,13 +524,17 @@ impl DSP {
     }
 
     fn set_echo_delay(&mut self, val: u8) {
+        self.regs.echo_delay = val;
+    }
+
+    fn latch_echo_buffer_size(&mut self) {
         const ECHO_BUFFER_STEP_SIZE: u16 = 2048;
 
-        self.regs.echo_delay = val;
-        self.echo_buffer_size = if (val & 0xF) == 0 {
+        let delay = self.regs.echo_delay & 0xF;
+        self.echo_buffer_size = if delay == 0 {
             4
         } else {
-            ((val & 0xF) as u16) * ECHO_BUFFER_STEP_SIZE
+            (delay as u16) * ECHO_BUFFER_STEP_SIZE
         };
     }
 }
@@ -1,11 +1,51 @@
 // A single audio channel
 
+use serde::{
+    Serialize,
+    Deserialize
+};
+
 use super::{
     brr::SampleBlock,
     envelope::Envelope
 };
 use crate::mem::RAM;
 
+/// Phase of a voice's volume envelope.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub enum EnvelopePhase {
+    Attack,
+    Decay,
+    Sustain,
+    /// Using GAIN instead of ADSR.
+    Gain,
+    /// Fading out after key off.
+    Release,
+    /// Silent after key off.
+    Off,
+}
+
+/// Snapshot of a single DSP voice, for visualisation.
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct VoiceState {
+    pub envelope_phase: EnvelopePhase,
+    /// Envelope level (ENVX), 0-127.
+    pub envelope_level: u8,
+    /// Source number (SRCN), the index into the sample directory.
+    pub source_num:     u8,
+    /// Address in audio RAM of the BRR block currently playing.
+    pub source_addr:    u16,
+    /// Pitch (P). $1000 plays the sample at 32kHz.
+    pub pitch:          u16,
+    /// Current output level after the envelope (OUTX).
+    pub output:         i8,
+    pub left_vol:       i8,
+    pub right_vol:      i8,
+}
+
+#[derive(Serialize, Deserialize)]
 pub struct Voice {
     left_vol:   i8,
     right_vol:  i8,
@@ -26,6 +66,9 @@ pub struct Voice {
     envx:       u8,
     outx:       u8,
 
+    // $xA, $xB and $xE: not used by the DSP, but can be written and read back.
+    unused:     [u8; 3],
+
     // Internal: sample generation
     // TODO: separate this?
     loop_addr:      u16,
@@ -61,6 +104,8 @@ impl Voice {
             envx:       0,
             outx:       0,
 
+            unused:     [0; 3],
+
             loop_addr:      0,
             current_addr:   0,
 
@@ -87,7 +132,10 @@ impl Voice {
             0x7 => self.gain,
             0x8 => self.envx,
             0x9 => self.outx,
-            _ => panic!("Reading from DSP {:X}", addr),
+            0xA => self.unused[0],
+            0xB => self.unused[1],
+            0xE => self.unused[2],
+            _ => 0,     // $xC, $xD and $xF are global registers.
         }
     }
 
@@ -103,7 +151,10 @@ impl Voice {
             0x7 => self.gain = data,
             0x8 => self.envx = data,
             0x9 => self.outx = data,
-            _ => panic!("Writing to DSP {:X}", addr),
+            0xA => self.unused[0] = data,
+            0xB => self.unused[1] = data,
+            0xE => self.unused[2] = data,
+            _ => {},    // $xC, $xD and $xF are global registers.
         }
     }
 
@@ -155,6 +206,10 @@ impl Voice {
         self.endx
     }
 
+    pub fn clear_endx(&mut self) {
+        self.endx = false;
+    }
+
     pub fn read_left_vol(&self) -> i32 {
         self.left_vol as i32
     }
@@ -162,6 +217,19 @@ impl Voice {
     pub fn read_right_vol(&self) -> i32 {
         self.right_vol as i32
     }
+
+    pub fn get_state(&self) -> VoiceState {
+        VoiceState {
+            envelope_phase: self.envelope.phase(),
+            envelope_level: self.envx,
+            source_num:     self.src_num,
+            source_addr:    self.current_addr,
+            pitch:          self.pitch,
+            output:         self.outx as i8,
+            left_vol:       self.left_vol,
+            right_vol:      self.right_vol,
+        }
+    }
 }
 
 // Generator
@@ -199,8 +267,8 @@ impl Voice {
 
     // Mix sample using gaussian filter.
     fn generate_sample(&self, samples: [i32; 4]) -> i16 {
-        const MIN: i32 = std::i16::MIN as i32;
-        const MAX: i32 = std::i16::MAX as i32;
+        const MIN: i32 = core::i16::MIN as i32;
+        const MAX: i32 = core::i16::MAX as i32;
 
         let gauss_index = ((self.freq_counter >> 4) & 0xFF) as usize;
         let mut out = (samples[3] * GAUSS_TABLE[0xFF - gauss_index]) >> 10;
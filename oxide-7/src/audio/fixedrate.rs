@@ -0,0 +1,56 @@
+// Module that outputs samples at the native rate of 32_000, a fixed number at a time.
+// This is deterministic, so it can be used for headless and test runs.
+use alloc::collections::VecDeque;
+
+use crate::sync::Receiver;
+use super::Stereo;
+
+pub struct FixedRate {
+    receiver:   Receiver<super::SamplePacket>,
+    pending:    VecDeque<Stereo<f32>>,
+
+    pending_timestamp:  u64,    // Timestamp of the first pending sample.
+    pending_output:     usize,  // Number of samples output since the pending timestamp.
+    timestamp:          u64,    // Timestamp of the last sample output.
+}
+
+impl FixedRate {
+    pub fn new(receiver: Receiver<super::SamplePacket>) -> Self {
+        FixedRate {
+            receiver:   receiver,
+            pending:    VecDeque::new(),
+
+            pending_timestamp:  0,
+            pending_output:     0,
+            timestamp:          0,
+        }
+    }
+
+    // Fill the buffer with all the samples that are available, without blocking.
+    // If there aren't enough samples, the rest is filled with silence.
+    pub fn fill(&mut self, buffer: &mut [Stereo<f32>]) {
+        for packet in self.receiver.try_iter() {
+            if self.pending.is_empty() {
+                self.pending_timestamp = packet.timestamp;
+                self.pending_output = 0;
+            }
+            self.pending.extend(packet.samples.iter());
+        }
+
+        for o in buffer.iter_mut() {
+            *o = match self.pending.pop_front() {
+                Some(sample) => {
+                    self.timestamp = super::sample_timestamp(self.pending_timestamp, self.pending_output);
+                    self.pending_output += 1;
+                    sample
+                },
+                None => [0.0; 2]
+            };
+        }
+    }
+
+    // Get the emulated time that the last sample output was generated, in master cycles.
+    pub fn timestamp(&self) -> u64 {
+        self.timestamp
+    }
+}
@@ -1,14 +1,31 @@
 // Memory for the SPC-700.
 mod timer;
+#[cfg(test)]
+mod tests;
 
+use alloc::string::String;
 use bitflags::bitflags;
-use crossbeam_channel::Sender;
-
-use crate::mem::RAM;
+use serde::{
+    Serialize,
+    Deserialize
+};
+
+use crate::{
+    mem::RAM,
+    savestate::{
+        Stateful,
+        StateWriter,
+        StateReader
+    }
+};
 use timer::Timer;
-use super::dsp::DSP;
+use super::dsp::{
+    DSP,
+    VoiceState
+};
 
 bitflags! {
+    #[derive(Serialize, Deserialize)]
     struct SPCControl: u8 {
         const ROM_ENABLE =      bit!(7);
         const CLEAR_PORT_32 =   bit!(5);
@@ -60,7 +77,7 @@ pub struct SPCBus {
 }
 
 impl SPCBus {
-    pub fn new(signal_tx: Sender<super::SamplePacket>) -> Self {
+    pub fn new(signal_tx: super::SampleBroadcast) -> Self {
         SPCBus {
             ram:        RAM::new(SPC_RAM_SIZE),
 
@@ -86,6 +103,64 @@ impl SPCBus {
     pub fn write_port(&mut self, port_num: usize, data: u8) {
         self.ports_cpu_to_apu[port_num] = data;
     }
+
+    pub fn voice_states(&self) -> [VoiceState; 8] {
+        self.dsp.voice_states()
+    }
+
+    pub fn set_dc_block(&mut self, enable: bool) {
+        self.dsp.set_dc_block(enable);
+    }
+
+    pub fn ipl_rom_enabled(&self) -> bool {
+        self.control.contains(SPCControl::ROM_ENABLE)
+    }
+}
+
+impl Stateful for SPCBus {
+    fn save_state(&self, w: &mut StateWriter) {
+        w.write(&self.ram);
+        w.write(&self.control);
+        w.write(&self.dsp_reg_addr);
+        self.dsp.save_state(w);
+        w.write(&self.ports_cpu_to_apu);
+        w.write(&self.ports_apu_to_cpu);
+        w.write(&self.timer_0);
+        w.write(&self.timer_1);
+        w.write(&self.timer_2);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), String> {
+        r.read_into(&mut self.ram)?;
+        r.read_into(&mut self.control)?;
+        r.read_into(&mut self.dsp_reg_addr)?;
+        self.dsp.load_state(r)?;
+        r.read_into(&mut self.ports_cpu_to_apu)?;
+        r.read_into(&mut self.ports_apu_to_cpu)?;
+        r.read_into(&mut self.timer_0)?;
+        r.read_into(&mut self.timer_1)?;
+        r.read_into(&mut self.timer_2)
+    }
+}
+
+// Debug
+#[cfg(feature = "debug")]
+impl SPCBus {
+    pub fn get_ram(&self) -> &[u8] {
+        self.ram.as_slice()
+    }
+
+    pub fn get_ram_mut(&mut self) -> &mut [u8] {
+        self.ram.as_mut_slice()
+    }
+
+    pub fn get_dsp_clamp_stats(&self) -> crate::debug::DSPClampStats {
+        self.dsp.clamp_stats()
+    }
+
+    pub fn reset_dsp_clamp_stats(&mut self) {
+        self.dsp.reset_clamp_stats();
+    }
 }
 
 impl SPCMem for SPCBus {
@@ -107,6 +182,8 @@ impl SPCMem for SPCBus {
             0xFE => self.timer_1.read_counter(),
             0xFF => self.timer_2.read_counter(),
 
+            // The IPL ROM can be unmapped through $F1 after boot, to read the RAM underneath.
+            // Writes always go to RAM, and the ROM enable bit is part of the control register in savestates.
             0xFFC0..=0xFFFF if self.control.contains(SPCControl::ROM_ENABLE) => self.ipl_rom[(addr - 0xFFC0) as usize],
 
             _ => self.ram.read(addr.into())
@@ -118,7 +195,10 @@ impl SPCMem for SPCBus {
             0xF1 => self.set_control(data),
 
             0xF2 => self.dsp_reg_addr = data,
-            0xF3 => self.dsp.write(self.dsp_reg_addr & 0x7F, data, &self.ram),
+            // $80-$FF are read-only mirrors of the DSP registers.
+            0xF3 => if self.dsp_reg_addr < 0x80 {
+                self.dsp.write(self.dsp_reg_addr, data, &self.ram);
+            },
 
             0xF4 => self.ports_apu_to_cpu[0] = data,
             0xF5 => self.ports_apu_to_cpu[1] = data,
@@ -154,9 +234,17 @@ impl SPCBus {
     fn set_control(&mut self, data: u8) {
         let control = SPCControl::from_bits_truncate(data);
 
-        self.timer_0.reset();
-        self.timer_1.reset();
-        self.timer_2.reset();
+        // Timers are only reset when they are started.
+        let started = control - self.control;
+        if started.contains(SPCControl::ENABLE_TIMER_0) {
+            self.timer_0.reset();
+        }
+        if started.contains(SPCControl::ENABLE_TIMER_1) {
+            self.timer_1.reset();
+        }
+        if started.contains(SPCControl::ENABLE_TIMER_2) {
+            self.timer_2.reset();
+        }
 
         if control.contains(SPCControl::CLEAR_PORT_10) {
             //println!("APU reset ports 0 and 1");
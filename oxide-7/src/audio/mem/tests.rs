@@ -0,0 +1,300 @@
+// DSP register access through $F2 and $F3, DSP writes to audio RAM, and the timers.
+use super::*;
+use crate::audio::broadcast::SampleBroadcast;
+
+fn new_bus() -> SPCBus {
+    SPCBus::new(SampleBroadcast::new())
+}
+
+fn write_dsp(bus: &mut SPCBus, addr: u8, data: u8) {
+    bus.write(0xF2, addr);
+    bus.write(0xF3, data);
+}
+
+fn read_dsp(bus: &mut SPCBus, addr: u8) -> u8 {
+    bus.write(0xF2, addr);
+    bus.read(0xF3)
+}
+
+#[test]
+fn dsp_registers_read_back() {
+    let mut bus = new_bus();
+    // Voice registers, global registers and unused registers.
+    for addr in [0x00, 0x12, 0x27, 0x0A, 0x3B, 0x7E, 0x1D, 0x0C, 0x5D, 0x4F] {
+        write_dsp(&mut bus, addr, addr ^ 0xA5);
+        assert_eq!(read_dsp(&mut bus, addr), addr ^ 0xA5, "DSP register ${:02X}", addr);
+    }
+}
+
+#[test]
+fn dsp_upper_half_is_read_only_mirror() {
+    let mut bus = new_bus();
+    write_dsp(&mut bus, 0x0A, 0x12);
+    assert_eq!(read_dsp(&mut bus, 0x8A), 0x12);
+
+    write_dsp(&mut bus, 0x8A, 0x34);
+    assert_eq!(read_dsp(&mut bus, 0x0A), 0x12);
+    assert_eq!(read_dsp(&mut bus, 0x8A), 0x12);
+}
+
+#[test]
+fn endx_set_at_end_and_cleared_on_write() {
+    let mut bus = new_bus();
+    // Directory at $0200, with sample 0 at $0300: a single block with the end flag set.
+    for (i, b) in [0x00, 0x03, 0x00, 0x03].iter().enumerate() {
+        bus.write(0x0200 + i as u16, *b);
+    }
+    bus.write(0x0300, 0x01);
+
+    write_dsp(&mut bus, 0x5D, 0x02);
+    write_dsp(&mut bus, 0x02, 0x00);
+    write_dsp(&mut bus, 0x03, 0x10);
+    write_dsp(&mut bus, 0x7C, 0x00);
+    write_dsp(&mut bus, 0x4C, 0x01);
+    assert_eq!(read_dsp(&mut bus, 0x7C), 0x00);
+
+    // 16 samples at pitch $1000 plays the whole block.
+    for _ in 0..32 {
+        bus.clock(32);
+    }
+    assert_eq!(read_dsp(&mut bus, 0x7C), 0x01);
+
+    // Any value written clears all the bits.
+    write_dsp(&mut bus, 0x7C, 0xFF);
+    assert_eq!(read_dsp(&mut bus, 0x7C), 0x00);
+}
+
+// Run the DSP for a number of samples.
+fn run_samples(bus: &mut SPCBus, samples: usize) {
+    for _ in 0..samples {
+        bus.clock(32);
+    }
+}
+
+// Set up a 2kB echo buffer at $4000, over memory filled with $FF.
+fn echo_bus() -> SPCBus {
+    let mut bus = new_bus();
+    for addr in 0x4000..0x6000 {
+        bus.write(addr, 0xFF);
+    }
+    write_dsp(&mut bus, 0x6D, 0x40);
+    write_dsp(&mut bus, 0x7D, 0x01);
+    write_dsp(&mut bus, 0x6C, 0x00);
+    bus
+}
+
+#[test]
+fn echo_overwrites_ram() {
+    let mut bus = echo_bus();
+    run_samples(&mut bus, 16);
+    assert!((0x4000..0x4040).all(|addr| bus.read(addr) == 0));
+    assert!((0x4040..0x6000).all(|addr| bus.read(addr) == 0xFF));
+
+    // No more writes once echo writes are disabled.
+    write_dsp(&mut bus, 0x6C, 0x20);
+    run_samples(&mut bus, 16);
+    assert!((0x4040..0x6000).all(|addr| bus.read(addr) == 0xFF));
+}
+
+#[test]
+fn echo_delay_changes_at_buffer_start() {
+    let mut bus = echo_bus();
+    run_samples(&mut bus, 256);
+    write_dsp(&mut bus, 0x7D, 0x02);
+
+    // The rest of the 2kB buffer is written, then it goes back to the start with the new size.
+    run_samples(&mut bus, 256);
+    assert!((0x4000..0x4800).all(|addr| bus.read(addr) == 0));
+    assert!((0x4800..0x6000).all(|addr| bus.read(addr) == 0xFF));
+
+    run_samples(&mut bus, 1024);
+    assert!((0x4000..0x5000).all(|addr| bus.read(addr) == 0));
+    assert!((0x5000..0x6000).all(|addr| bus.read(addr) == 0xFF));
+}
+
+// Timer 0 with the given modulo, started from 0.
+fn timer_bus(modulo: u8) -> SPCBus {
+    let mut bus = new_bus();
+    bus.write(0xFA, modulo);
+    bus.write(0xF1, 0x01);
+    bus
+}
+
+#[test]
+fn timer_modulo() {
+    let mut bus = timer_bus(3);
+    bus.clock(128 * 3 - 1);
+    assert_eq!(bus.read(0xFD), 0);
+    bus.clock(1);
+    assert_eq!(bus.read(0xFD), 1);
+    // The counter is cleared when read.
+    assert_eq!(bus.read(0xFD), 0);
+}
+
+#[test]
+fn timer_modulo_zero_is_256() {
+    let mut bus = timer_bus(0);
+    bus.clock(128 * 255);
+    assert_eq!(bus.read(0xFD), 0);
+    bus.clock(128);
+    assert_eq!(bus.read(0xFD), 1);
+}
+
+#[test]
+fn timer_counter_is_4_bits() {
+    let mut bus = timer_bus(1);
+    bus.clock(128 * 17);
+    assert_eq!(bus.read(0xFD), 1);
+}
+
+#[test]
+fn timer_reset_only_when_started() {
+    let mut bus = timer_bus(2);
+    bus.clock(128);
+    // Still running: writing the control register again doesn't reset it.
+    bus.write(0xF1, 0x01);
+    bus.clock(128);
+    assert_eq!(bus.read(0xFD), 1);
+
+    bus.clock(128);
+    bus.write(0xF1, 0x00);
+    bus.write(0xF1, 0x01);
+    bus.clock(128);
+    assert_eq!(bus.read(0xFD), 0);
+}
+
+// Play a looping sample where every nibble is the same on the first few voices, one for each volume provided.
+// Returns the bus and the left output.
+fn constant_voices(data: u8, volumes: &[u8], main_vol: u8, dc_block: bool) -> (SPCBus, Vec<f32>) {
+    let broadcast = SampleBroadcast::new();
+    let rx = broadcast.subscribe();
+    let mut bus = SPCBus::new(broadcast);
+    bus.set_dc_block(dc_block);
+    // Directory at $0200, with sample 0 at $0300: a single looping block.
+    for (i, b) in [0x00, 0x03, 0x00, 0x03].iter().enumerate() {
+        bus.write(0x0200 + i as u16, *b);
+    }
+    bus.write(0x0300, 0xC3);
+    for addr in 0x0301..0x0309 {
+        bus.write(addr, data);
+    }
+
+    for (voice, vol) in volumes.iter().enumerate() {
+        let base = (voice as u8) << 4;
+        for (addr, data) in [(0x00, *vol), (0x01, *vol), (0x02, 0x00), (0x03, 0x10), (0x05, 0x00), (0x07, 0x7F)] {
+            write_dsp(&mut bus, base | addr, data);
+        }
+    }
+    let key_on = ((1_u16 << volumes.len()) - 1) as u8;
+    for (addr, data) in [(0x0C, main_vol), (0x1C, main_vol), (0x5D, 0x02), (0x6C, 0x20), (0x4C, key_on)] {
+        write_dsp(&mut bus, addr, data);
+    }
+    run_samples(&mut bus, 4096);
+    let output = rx.try_iter().flat_map(|packet| packet.samples.iter().map(|s| s[0]).collect::<Vec<_>>()).collect();
+    (bus, output)
+}
+
+// Play a looping sample with a constant value on voice 0, and get the left output.
+fn constant_output(dc_block: bool) -> Vec<f32> {
+    constant_voices(0x11, &[0x7F], 0x7F, dc_block).1
+}
+
+#[test]
+fn dc_block() {
+    let raw = constant_output(false);
+    let last = raw[raw.len() - 1];
+    assert!(last > 0.1);
+    assert!(raw[(raw.len() - 64)..].iter().all(|s| *s == last));
+
+    let filtered = constant_output(true);
+    assert_eq!(filtered.len(), raw.len());
+    assert!(filtered.iter().any(|s| *s > 0.1));
+    assert!(filtered[filtered.len() - 1].abs() < 0.001);
+}
+
+#[test]
+fn negative_volume_inverts_phase() {
+    let (_, positive) = constant_voices(0x11, &[0x7F], 0x7F, false);
+    assert!(positive[positive.len() - 1] > 0.1);
+
+    let (_, negative_voice) = constant_voices(0x11, &[0x81], 0x7F, false);
+    assert!(negative_voice[negative_voice.len() - 1] < -0.1);
+
+    let (_, negative_main) = constant_voices(0x11, &[0x7F], 0x81, false);
+    assert!(negative_main[negative_main.len() - 1] < -0.1);
+
+    // Both negative: back in phase.
+    let (_, surround) = constant_voices(0x11, &[0x81], 0x81, false);
+    assert!(surround[surround.len() - 1] > 0.1);
+}
+
+#[test]
+fn voice_mix_clamps_as_each_voice_is_added() {
+    // The first two voices overflow and are clamped, then the third is subtracted.
+    // Clamping only at the end would leave the output near the level of one voice.
+    let (_, output) = constant_voices(0x77, &[0x7F, 0x7F, 0x80], 0x7F, false);
+    let last = output[output.len() - 1];
+    assert!(last > 0.05 && last < 0.2, "output: {}", last);
+}
+
+#[cfg(feature = "debug")]
+#[test]
+fn clamp_stats() {
+    let (mut bus, _) = constant_voices(0x77, &[0x7F, 0x7F, 0x80], 0x7F, false);
+    let stats = bus.get_dsp_clamp_stats();
+    assert!(stats.main_mix > 0);
+    assert_eq!(stats.echo_mix, 0);
+    assert_eq!(stats.output, 0);
+
+    bus.reset_dsp_clamp_stats();
+    assert_eq!(bus.get_dsp_clamp_stats(), crate::debug::DSPClampStats::default());
+
+    let (bus, _) = constant_voices(0x11, &[0x7F], 0x7F, false);
+    assert_eq!(bus.get_dsp_clamp_stats(), crate::debug::DSPClampStats::default());
+}
+
+#[test]
+fn ipl_rom_toggle() {
+    let mut bus = new_bus();
+    // Writes go to the RAM underneath the ROM.
+    bus.write(0xFFC0, 0x12);
+    bus.write(0xFFFF, 0x34);
+    assert_eq!(bus.read(0xFFC0), 0xCD);
+    assert_eq!(bus.read(0xFFFF), 0xFF);
+
+    bus.write(0xF1, 0x00);
+    assert!(!bus.ipl_rom_enabled());
+    assert_eq!(bus.read(0xFFC0), 0x12);
+    assert_eq!(bus.read(0xFFFF), 0x34);
+
+    bus.write(0xF1, 0x80);
+    assert_eq!(bus.read(0xFFC0), 0xCD);
+    assert_eq!(bus.read(0xFFFF), 0xFF);
+}
+
+#[test]
+fn ipl_rom_toggle_savestate() {
+    use crate::savestate::*;
+
+    let mut bus = new_bus();
+    bus.write(0xFFC0, 0x12);
+    bus.write(0xF1, 0x01);
+
+    let mut w = StateWriter::new();
+    bus.save_state(&mut w);
+    let state = w.finish(&[0; THUMBNAIL_SIZE]);
+
+    let mut loaded = new_bus();
+    assert!(loaded.ipl_rom_enabled());
+    loaded.load_state(&mut StateReader::new(&state).unwrap()).unwrap();
+    assert!(!loaded.ipl_rom_enabled());
+    assert_eq!(loaded.read(0xFFC0), 0x12);
+
+    // Loading a state with the ROM enabled maps it back in.
+    let mut w = StateWriter::new();
+    new_bus().save_state(&mut w);
+    let state = w.finish(&[0; THUMBNAIL_SIZE]);
+    loaded.load_state(&mut StateReader::new(&state).unwrap()).unwrap();
+    assert!(loaded.ipl_rom_enabled());
+    assert_eq!(loaded.read(0xFFC0), 0xCD);
+}
@@ -1,5 +1,11 @@
 // Timers for SPC-700.
 
+use serde::{
+    Serialize,
+    Deserialize
+};
+
+#[derive(Serialize, Deserialize)]
 pub struct Timer {
     timer_mod:  u8,     // Timer modulo set by SPC
     counter:    u8,     // Counter
@@ -21,10 +27,11 @@ impl Timer {
         }
     }
 
+    // A modulo of 0 acts as 256, since the internal timer wraps before it matches.
     pub fn clock(&mut self, cycles: usize) {
         self.cycle_count += cycles;
 
-        if self.cycle_count >= self.period {
+        while self.cycle_count >= self.period {
             self.cycle_count -= self.period;
 
             self.timer = self.timer.wrapping_add(1);
@@ -41,7 +48,7 @@ impl Timer {
     }
 
     pub fn read_counter(&mut self) -> u8 {
-        std::mem::replace(&mut self.counter, 0)
+        core::mem::replace(&mut self.counter, 0)
     }
 
     pub fn reset(&mut self) {
@@ -1,62 +1,111 @@
 // APU
 // Consists of a CPU interface, the SPC-700 8-bit processor, and an 8-channel DSP.
 
+mod broadcast;
 mod dsp;
+mod fixedrate;
 mod mem;
+#[cfg(feature = "std")]
 mod resampler;
 mod spc;
 
-use crossbeam_channel::{
-    unbounded,
-    Receiver
+use alloc::{
+    boxed::Box,
+    string::String
 };
 
-use dasp::frame::Stereo;
-
-use crate::constants::timing;
+use crate::{
+    constants::timing,
+    events::EventBus,
+    sync::Receiver,
+    savestate::{
+        Stateful,
+        StateWriter,
+        StateReader
+    }
+};
 
+use broadcast::SampleBroadcast;
 use spc::SPC;
 use mem::SPCBus;
 
-pub type SamplePacket = Box<[Stereo<f32>]>;
+#[cfg(feature = "std")]
 pub use resampler::Resampler;
+pub use fixedrate::FixedRate;
+pub use dsp::{
+    EnvelopePhase,
+    VoiceState
+};
 
 const SPC_RATIO: f64 = (timing::SPC_CLOCK_RATE as f64) / timing::REAL_HZ; // Around 1/21
+const MASTER_CYCLES_PER_SAMPLE: f64 = 32.0 / SPC_RATIO;
+
+// A pair of left and right samples.
+pub type Stereo<S> = [S; 2];
+
+// A batch of samples from the DSP.
+#[derive(Clone)]
+pub struct SamplePacket {
+    pub timestamp:  u64,    // Emulated master cycle count when the first sample was generated.
+    pub samples:    Box<[Stereo<f32>]>,
+}
+
+// Get the timestamp of the nth sample in a packet.
+fn sample_timestamp(packet_timestamp: u64, n: usize) -> u64 {
+    packet_timestamp + ((n as f64) * MASTER_CYCLES_PER_SAMPLE) as u64
+}
 
 // The APU processes SPC instructions and generates audio.
 pub struct APU {
-    signal_rx:      Option<Receiver<SamplePacket>>, // Receiver that will be used on the audio thread.
+    broadcast:      SampleBroadcast,    // Hands out receivers that will be used on audio threads.
 
     spc:            SPC<SPCBus>,
     cycle_count:    f64,
+
+    fast_boot:      bool,   // Use HLE for the IPL ROM upload.
 }
 
 impl APU {
-    pub fn new() -> Self {
-        let (signal_tx, signal_rx) = unbounded();
-        let bus = SPCBus::new(signal_tx);
+    pub fn new(events: EventBus) -> Self {
+        let broadcast = SampleBroadcast::new();
+        let bus = SPCBus::new(broadcast.clone());
+        let mut spc = SPC::new(bus);
+        spc.set_event_bus(events);
 
         APU {
-            signal_rx:      Some(signal_rx),
+            broadcast:      broadcast,
 
-            spc:            SPC::new(bus),
-            cycle_count:    0.0
+            spc:            spc,
+            cycle_count:    0.0,
+
+            fast_boot:      false,
         }
     }
 
-    pub fn get_rx(&mut self) -> Option<Receiver<SamplePacket>> {
-        std::mem::replace(&mut self.signal_rx, None)
+    // Create a new receiver for audio samples.
+    // Any number of receivers can exist at once: each gets every sample packet.
+    pub fn new_rx(&mut self) -> Receiver<SamplePacket> {
+        self.broadcast.subscribe()
     }
 
     pub fn clock(&mut self, cycles: usize) {
         self.cycle_count += calc_cycles(cycles);
 
         while self.cycle_count > 0.0 {
-            let cycles_passed = self.spc.step() as f64;
+            let hle_cycles = if self.fast_boot {self.spc.ipl_hle_step()} else {None};
+            let cycles_passed = hle_cycles.unwrap_or_else(|| self.spc.step()) as f64;
             self.cycle_count -= cycles_passed;
         }
     }
 
+    pub fn set_fast_boot(&mut self, enable: bool) {
+        self.fast_boot = enable;
+    }
+
+    pub fn set_dc_block(&mut self, enable: bool) {
+        self.spc.set_dc_block(enable);
+    }
+
     pub fn read_port(&self, port_num: usize) -> u8 {
         self.spc.read_port(port_num)
     }
@@ -64,6 +113,43 @@ impl APU {
     pub fn write_port(&mut self, port_num: usize, data: u8) {
         self.spc.write_port(port_num, data);
     }
+
+    pub fn voice_states(&self) -> [VoiceState; 8] {
+        self.spc.voice_states()
+    }
+}
+
+impl Stateful for APU {
+    fn save_state(&self, w: &mut StateWriter) {
+        self.spc.save_state(w);
+        w.write(&self.cycle_count);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), String> {
+        self.spc.load_state(r)?;
+        r.read_into(&mut self.cycle_count)
+    }
+}
+
+// Debug
+#[cfg(feature = "debug")]
+impl APU {
+    // Get the whole of audio RAM. Note this bypasses the IPL ROM and I/O registers.
+    pub fn get_ram(&self) -> &[u8] {
+        self.spc.get_bus().get_ram()
+    }
+
+    pub fn get_ram_mut(&mut self) -> &mut [u8] {
+        self.spc.get_bus_mut().get_ram_mut()
+    }
+
+    pub fn get_dsp_clamp_stats(&self) -> crate::debug::DSPClampStats {
+        self.spc.get_bus().get_dsp_clamp_stats()
+    }
+
+    pub fn reset_dsp_clamp_stats(&mut self) {
+        self.spc.get_bus_mut().reset_dsp_clamp_stats();
+    }
 }
 
 // Convert master cycles into SPC cycles.
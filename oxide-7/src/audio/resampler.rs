@@ -1,5 +1,8 @@
 // Module that resamples from 32_000 to the output sample rate.
-use crossbeam_channel::Receiver;
+use crossbeam_channel::{
+    Receiver,
+    TryRecvError
+};
 use dasp::{
     frame::{Frame, Stereo},
     interpolate::sinc::Sinc,
@@ -21,6 +24,24 @@ impl Resampler {
             converter: Source::new(receiver).from_hz_to_hz(sinc, 32_000.0, target_sample_rate)
         }
     }
+
+    // Change the output sample rate, for example if the audio device changes.
+    // Any audio waiting to be output is dropped, so latency doesn't build up.
+    pub fn set_sample_rate(&mut self, target_sample_rate: f64) {
+        self.converter.set_hz_to_hz(32_000.0, target_sample_rate);
+        self.converter.source_mut().clear();
+    }
+
+    // Get the number of samples that weren't ready in time since the last call.
+    // Silence is output in their place.
+    pub fn take_underruns(&mut self) -> usize {
+        core::mem::take(&mut self.converter.source_mut().underruns)
+    }
+
+    // Get the emulated time that the last input sample was generated, in master cycles.
+    pub fn timestamp(&self) -> u64 {
+        self.converter.source().timestamp
+    }
 }
 
 impl Iterator for Resampler {
@@ -38,6 +59,9 @@ struct Source {
 
     current:    super::SamplePacket,
     n:          usize,
+
+    underruns:  usize,
+    timestamp:  u64,
 }
 
 impl Source {
@@ -45,24 +69,52 @@ impl Source {
         Source {
             receiver:   receiver,
 
-            current:    Box::new([]),
+            current:    super::SamplePacket {
+                timestamp:  0,
+                samples:    Box::new([]),
+            },
             n:          0,
+
+            underruns:  0,
+            timestamp:  0,
         }
     }
+
+    // Drop all audio waiting to be output.
+    fn clear(&mut self) {
+        self.current.samples = Box::new([]);
+        self.n = 0;
+        for _ in self.receiver.try_iter() {}
+    }
 }
 
 impl Signal for Source {
     type Frame = Stereo<f32>;
 
     fn next(&mut self) -> Self::Frame {
-        if self.n < self.current.len() {
-            let out = self.current[self.n];
+        if self.n < self.current.samples.len() {
+            let out = self.current.samples[self.n];
+            self.timestamp = super::sample_timestamp(self.current.timestamp, self.n);
             self.n += 1;
             out
         } else {
-            self.current = self.receiver.recv().unwrap();
-            self.n = 1;
-            self.current[0]
+            // Don't block the audio thread if the SNES is running behind: output silence instead.
+            loop {
+                match self.receiver.try_recv() {
+                    Ok(packet) if packet.samples.is_empty() => continue,
+                    Ok(packet) => {
+                        self.current = packet;
+                        self.timestamp = self.current.timestamp;
+                        self.n = 1;
+                        return self.current.samples[0];
+                    },
+                    Err(TryRecvError::Empty) => {
+                        self.underruns += 1;
+                        return Stereo::EQUILIBRIUM;
+                    },
+                    Err(TryRecvError::Disconnected) => return Stereo::EQUILIBRIUM,
+                }
+            }
         }
     }
 }
\ No newline at end of file
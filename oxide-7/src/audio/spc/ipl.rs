@@ -0,0 +1,102 @@
+// High-level emulation of the IPL ROM upload protocol, for fast boot.
+// The SPC responds to the CPU as soon as a port is written, instead of polling in the IPL loops.
+// All the state is kept in registers and RAM in the same way as the IPL ROM,
+// so this can take over from (or hand back to) the real ROM code at any of the wait points.
+
+use super::*;
+
+// Points in the IPL ROM where the SPC waits for the CPU.
+const IPL_START:        u16 = 0xFFC0;
+const WAIT_FOR_START:   u16 = 0xFFCF;   // Wait for $CC in port 0.
+const WAIT_FOR_FIRST:   u16 = 0xFFD6;   // Wait for index 0 in port 0.
+const WAIT_FOR_BYTE:    u16 = 0xFFDA;   // Wait for the index in Y in port 0.
+const START_BLOCK:      u16 = 0xFFEF;   // Read the address of the next block, or jump to it.
+
+impl SPC<SPCBus> {
+    // If the SPC is at a wait point in the IPL ROM, run it until it waits again.
+    // Returns the cycles passed, or None if the SPC should step normally.
+    pub fn ipl_hle_step(&mut self) -> Option<usize> {
+        if !self.bus.ipl_rom_enabled() {
+            return None;
+        }
+
+        match self.pc {
+            IPL_START => self.ipl_init(),
+            WAIT_FOR_START => if self.read_data(0xF4) == 0xCC {
+                self.ipl_start_block();
+            },
+            WAIT_FOR_FIRST => if self.read_data(0xF4) == 0 {
+                self.y = 0;
+                self.pc = WAIT_FOR_BYTE;
+            },
+            WAIT_FOR_BYTE => self.ipl_transfer_byte(),
+            START_BLOCK => self.ipl_start_block(),
+            _ => return None,
+        }
+
+        let cycles_passed = self.cycle_count;
+        self.cycle_count = 0;
+        Some(cycles_passed)
+    }
+}
+
+// Internal
+impl SPC<SPCBus> {
+    // Clear the zero page and signal to the CPU that the SPC is ready.
+    fn ipl_init(&mut self) {
+        for addr in 0x01..=0xEF {
+            self.bus.write(addr, 0);
+        }
+        self.sp = 0xEF;
+        self.a = 0;
+        self.x = 0;
+        self.write_data(0xF4, 0xAA);
+        self.write_data(0xF5, 0xBB);
+        self.pc = WAIT_FOR_START;
+    }
+
+    // Store the block address, and echo port 0.
+    // Port 1 is non-zero if a block follows, otherwise the SPC jumps to the address.
+    fn ipl_start_block(&mut self) {
+        let addr_lo = self.read_data(0xF6);
+        let addr_hi = self.read_data(0xF7);
+        self.write_data(0x00, addr_lo);
+        self.write_data(0x01, addr_hi);
+
+        let kick = self.read_data(0xF4);
+        let next = self.read_data(0xF5);
+        self.write_data(0xF4, kick);
+
+        self.a = next;
+        self.x = next;
+        self.y = next;
+        self.ps.set(PSFlags::Z, next == 0);
+        self.ps.set(PSFlags::N, test_bit!(next, 7, u8));
+        self.pc = if next != 0 {
+            WAIT_FOR_FIRST
+        } else {
+            make16!(addr_hi, addr_lo)
+        };
+    }
+
+    // Copy the byte in port 1 if port 0 has the next index.
+    // If port 0 has moved further ahead, a new block is starting.
+    fn ipl_transfer_byte(&mut self) {
+        let index = self.read_data(0xF4);
+        if index == self.y {
+            self.a = self.read_data(0xF5);
+            self.write_data(0xF4, self.y);
+
+            let base = make16!(self.read_data(0x01), self.read_data(0x00));
+            self.write_data(base.wrapping_add(self.y as u16), self.a);
+
+            self.y = self.y.wrapping_add(1);
+            if self.y == 0 {
+                let page = self.read_data(0x01).wrapping_add(1);
+                self.write_data(0x01, page);
+            }
+        } else if (self.y.wrapping_sub(index) as i8) < 0 {
+            self.ipl_start_block();
+        }
+    }
+}
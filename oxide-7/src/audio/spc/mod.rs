@@ -1,9 +1,25 @@
 // SPC-700 Audio processor
+mod ipl;
 mod types;
 #[cfg(test)]
 mod tests;
 
+use alloc::string::String;
+
 use super::mem::{SPCMem, SPCBus};
+use crate::{
+    events::{
+        Event,
+        EventBus,
+        Processor,
+        UnsupportedFeature
+    },
+    savestate::{
+        Stateful,
+        StateWriter,
+        StateReader
+    }
+};
 use types::*;
 
 pub struct SPC<B: SPCMem> {
@@ -18,6 +34,8 @@ pub struct SPC<B: SPCMem> {
     bus:    B,          // Memory
 
     cycle_count:    usize,  // Temp. cycle count for an instruction
+
+    events: EventBus,
 }
 
 impl<B: SPCMem> SPC<B> {
@@ -34,9 +52,16 @@ impl<B: SPCMem> SPC<B> {
             bus:    bus,
 
             cycle_count:    0,
+
+            events: EventBus::default(),
         }
     }
 
+    // Connect the SPC to the event bus, to report unsupported instructions.
+    pub fn set_event_bus(&mut self, events: EventBus) {
+        self.events = events;
+    }
+
     // Run a single instruction and return how many cycles passed.
     pub fn step(&mut self) -> usize {
         self.execute_instruction();
@@ -55,6 +80,48 @@ impl SPC<SPCBus> {
     pub fn write_port(&mut self, port_num: usize, data: u8) {
         self.bus.write_port(port_num, data);
     }
+
+    pub fn voice_states(&self) -> [crate::audio::VoiceState; 8] {
+        self.bus.voice_states()
+    }
+
+    pub fn set_dc_block(&mut self, enable: bool) {
+        self.bus.set_dc_block(enable);
+    }
+
+    #[cfg(feature = "debug")]
+    pub fn get_bus(&self) -> &SPCBus {
+        &self.bus
+    }
+
+    #[cfg(feature = "debug")]
+    pub fn get_bus_mut(&mut self) -> &mut SPCBus {
+        &mut self.bus
+    }
+}
+
+impl<B: SPCMem + Stateful> Stateful for SPC<B> {
+    fn save_state(&self, w: &mut StateWriter) {
+        w.write(&self.a);
+        w.write(&self.x);
+        w.write(&self.y);
+        w.write(&self.sp);
+        w.write(&self.pc);
+        w.write(&self.ps);
+
+        self.bus.save_state(w);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), String> {
+        r.read_into(&mut self.a)?;
+        r.read_into(&mut self.x)?;
+        r.read_into(&mut self.y)?;
+        r.read_into(&mut self.sp)?;
+        r.read_into(&mut self.pc)?;
+        r.read_into(&mut self.ps)?;
+
+        self.bus.load_state(r)
+    }
 }
 
 // Internal
@@ -1094,14 +1161,15 @@ impl<B: SPCMem> SPC<B> {
         self.clock_inc(SPC_OP);
     }
 
+    // TODO: SLEEP and STOP should halt the SPC. For now they are reported and treated as NOP.
     fn sleep(&mut self) {
-        // TODO
-        unimplemented!("Unsupported SPC instruction: SLEEP");
+        self.clock_inc(SPC_OP);
+        self.bad_opcode(0xEF);
     }
 
     fn stop(&mut self) {
-        // TODO
-        unimplemented!("Unsupported SPC instruction: STOP");
+        self.clock_inc(SPC_OP);
+        self.bad_opcode(0xFF);
     }
 
     fn brk(&mut self) {
@@ -1118,13 +1186,23 @@ impl<B: SPCMem> SPC<B> {
         let pc_hi = self.read_data(0xFFDF);
 
         self.pc = make16!(pc_hi, pc_lo);
-
-        panic!("BRK should not be called!");
     }
 }
 
 // Misc helper functions
 impl<B: SPCMem> SPC<B> {
+    fn bad_opcode(&mut self, opcode: u8) {
+        self.events.send(Event::BadOpcode {
+            processor:  Processor::SPC,
+            opcode:     opcode,
+            pc:         self.pc.wrapping_sub(1) as u32,
+        });
+        self.events.report_unsupported(UnsupportedFeature::Instruction {
+            processor:  Processor::SPC,
+            opcode:     opcode,
+        });
+    }
+
     #[inline]
     fn carry(&self) -> u16 {
         (self.ps & PSFlags::C).bits() as u16
@@ -1,9 +1,13 @@
 // Types and constants used internally in the SPC-700.
 
 use bitflags::bitflags;
+use serde::{
+    Serialize,
+    Deserialize
+};
 
 bitflags! {
-    #[derive(Default)]
+    #[derive(Default, Serialize, Deserialize)]
     pub struct PSFlags: u8 {
         const N = bit!(7);  // Negative
         const V = bit!(6);  // Overflow
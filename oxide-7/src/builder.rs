@@ -0,0 +1,232 @@
+// Configuration for creating a SNES.
+use crate::{
+    Error,
+    SNES,
+    constants::machine::Region,
+    events::EventBus,
+    expansion::ExpansionProvider,
+    mem::rom::create_cart_from_data
+};
+#[cfg(feature = "std")]
+use crate::mem::rom::create_cart;
+
+// Where the cart ROM comes from.
+enum CartSource<'a> {
+    #[cfg(feature = "std")]
+    File(&'a str),
+    Data(&'a [u8]),
+}
+
+/// Sets up a SNES, with options that are fixed when it is created or that should be set before it runs.
+/// Only the cart is required: everything else has a default.
+///
+/// ```no_run
+/// let snes = oxide7::SNESBuilder::from_file("game.sfc")
+///     .save_path("saves/game.sav")
+///     .dsp_rom("roms/dsp")
+///     .sprite_limit(false)
+///     .build()
+///     .unwrap();
+/// ```
+///
+/// Audio is set up after building, with `SNES::enable_audio` or `SNES::enable_headless_audio`.
+pub struct SNESBuilder<'a> {
+    cart:           CartSource<'a>,
+    save_path:      Option<&'a str>,
+    dsp_rom_path:   Option<&'a str>,
+    soft_patch:     bool,
+    provider:       Option<&'a mut dyn ExpansionProvider>,
+    allow_unsupported:  bool,
+
+    region:             Option<Region>,
+    region_flag:        Option<Region>,
+    sprite_limit:       bool,
+    power_on_garbage:   bool,
+    fast_apu_boot:      bool,
+    dc_block:           bool,
+    hires_blending:     bool,
+    widescreen:         usize,
+    overscan:           bool,
+}
+
+impl<'a> SNESBuilder<'a> {
+    /// Load the cart from a file.
+    /// If the archive feature is enabled, the file can be a zip archive containing the ROM.
+    /// By default, the save file is next to the cart, with the .sav extension.
+    #[cfg(feature = "std")]
+    pub fn from_file(cart_path: &'a str) -> Self {
+        Self::new(CartSource::File(cart_path))
+    }
+
+    /// Use ROM data that has already been loaded.
+    /// If the archive feature is enabled, the data can be a zip archive containing the ROM.
+    /// Set a save path if the cart has SRAM.
+    pub fn from_rom_data(rom_data: &'a [u8]) -> Self {
+        Self::new(CartSource::Data(rom_data))
+    }
+
+    /// Path of the file to keep the cart's save RAM in.
+    /// Without the std feature, save RAM is only kept in memory.
+    pub fn save_path(mut self, path: &'a str) -> Self {
+        self.save_path = Some(path);
+        self
+    }
+
+    /// Path of the DSP ROM, needed for DSP carts (e.g. Super Mario Kart, Pilotwings).
+    /// This can be a single ROM, or a directory of ROMs named dsp1, dsp1b, dsp2, dsp3 and dsp4
+    /// (with a .rom or .bin extension), where the one the game uses is picked automatically.
+    /// Without the std feature, DSP carts can't be run.
+    pub fn dsp_rom(mut self, path: &'a str) -> Self {
+        self.dsp_rom_path = Some(path);
+        self
+    }
+
+    /// Apply a .bps or .ips file next to the cart with the same name (for example game.bps for game.sfc)
+    /// as the ROM is loaded, leaving the files unchanged. If both exist, the .bps patch is used.
+    /// If the patch can't be applied, the ROM is loaded unpatched and a warning is raised.
+    /// This only applies to carts loaded from a file. Disabled by default.
+    pub fn soft_patch(mut self, enable: bool) -> Self {
+        self.soft_patch = enable;
+        self
+    }
+
+    /// Use a cart chip implemented outside of the crate.
+    /// If the provider doesn't support the cart, the built-in chip is used.
+    pub fn expansion(mut self, provider: &'a mut dyn ExpansionProvider) -> Self {
+        self.provider = Some(provider);
+        self
+    }
+
+    /// Load carts with an enhancement chip that isn't emulated. The game runs without the chip,
+    /// and an event is sent when it is first accessed. Enabled by default.
+    /// If disabled, building fails with `Error::UnsupportedChip` instead.
+    pub fn allow_unsupported_chips(mut self, allow: bool) -> Self {
+        self.allow_unsupported = allow;
+        self
+    }
+
+    /// Console region. By default, this is the region the cart was made for, from its header.
+    /// PAL consoles have 312 lines in each frame, and run at 50 frames per second.
+    pub fn region(mut self, region: Region) -> Self {
+        self.region = Some(region);
+        self
+    }
+
+    /// See `SNES::set_region_flag`. By default, the game sees the region set with `region`.
+    pub fn region_flag(mut self, region: Region) -> Self {
+        self.region_flag = Some(region);
+        self
+    }
+
+    /// See `SNES::set_sprite_limit`. Enabled by default.
+    pub fn sprite_limit(mut self, enable: bool) -> Self {
+        self.sprite_limit = enable;
+        self
+    }
+
+    /// See `SNES::set_power_on_garbage`. Disabled by default.
+    pub fn power_on_garbage(mut self, enable: bool) -> Self {
+        self.power_on_garbage = enable;
+        self
+    }
+
+    /// See `SNES::set_fast_apu_boot`. Disabled by default.
+    pub fn fast_apu_boot(mut self, enable: bool) -> Self {
+        self.fast_apu_boot = enable;
+        self
+    }
+
+    /// See `SNES::set_dc_block`. Disabled by default.
+    pub fn dc_block(mut self, enable: bool) -> Self {
+        self.dc_block = enable;
+        self
+    }
+
+    /// See `SNES::set_hires_blending`. Disabled by default.
+    pub fn hires_blending(mut self, enable: bool) -> Self {
+        self.hires_blending = enable;
+        self
+    }
+
+    /// See `SNES::set_widescreen`. 0 (disabled) by default.
+    pub fn widescreen(mut self, extra: usize) -> Self {
+        self.widescreen = extra;
+        self
+    }
+
+    /// See `SNES::set_overscan`. Disabled by default.
+    pub fn overscan(mut self, enable: bool) -> Self {
+        self.overscan = enable;
+        self
+    }
+
+    /// Load the cart and create the SNES.
+    /// Fails if the cart or save file can't be read, or if the cart can't be emulated.
+    pub fn build(self) -> Result<SNES, Error> {
+        let allow_unsupported = self.allow_unsupported;
+        let events = EventBus::new();
+        let cart = match self.cart {
+            #[cfg(feature = "std")]
+            CartSource::File(cart_path) => {
+                let save_path = self.save_path.map(String::from).unwrap_or_else(|| default_save_path(cart_path));
+                create_cart(cart_path, &save_path, self.dsp_rom_path, self.soft_patch, self.provider, events.clone())?
+            },
+            CartSource::Data(rom_data) => {
+                create_cart_from_data(rom_data.to_vec(), None, self.save_path.unwrap_or(""), self.dsp_rom_path, self.provider, events.clone())?
+            },
+        };
+        if let Some(chip) = cart.unsupported_chip().filter(|_| !allow_unsupported) {
+            return Err(Error::UnsupportedChip(chip));
+        }
+
+        let region = self.region.unwrap_or(cart.info().region);
+        let mut snes = SNES::with_cart(cart, events, region);
+        if let Some(region) = self.region_flag {
+            snes.set_region_flag(region);
+        }
+        snes.set_sprite_limit(self.sprite_limit);
+        if self.power_on_garbage {
+            snes.set_power_on_garbage(true);
+        }
+        snes.set_fast_apu_boot(self.fast_apu_boot);
+        snes.set_dc_block(self.dc_block);
+        snes.set_hires_blending(self.hires_blending);
+        if self.widescreen > 0 {
+            snes.set_widescreen(self.widescreen);
+        }
+        if self.overscan {
+            snes.set_overscan(true);
+        }
+        Ok(snes)
+    }
+}
+
+// Internal
+impl<'a> SNESBuilder<'a> {
+    fn new(cart: CartSource<'a>) -> Self {
+        SNESBuilder {
+            cart:           cart,
+            save_path:      None,
+            dsp_rom_path:   None,
+            soft_patch:     false,
+            provider:       None,
+            allow_unsupported:  true,
+
+            region:             None,
+            region_flag:        None,
+            sprite_limit:       true,
+            power_on_garbage:   false,
+            fast_apu_boot:      false,
+            dc_block:           false,
+            hires_blending:     false,
+            widescreen:         0,
+            overscan:           false,
+        }
+    }
+}
+
+// The cart path with the extension swapped for .sav.
+#[cfg(feature = "std")]
+fn default_save_path(cart_path: &str) -> String {
+    std::path::Path::new(cart_path).with_extension("sav").to_string_lossy().into_owned()
+}
@@ -1,5 +1,9 @@
 // Common utils
 use bitflags::bitflags;
+use serde::{
+    Serialize,
+    Deserialize
+};
 
 /**** BIT MACROS ****/
 // Single bit selection.
@@ -180,14 +184,24 @@ macro_rules! hi32 {
     };
 }
 
+/**** LOGGING ****/
+// Without std there's nowhere to print to, so messages are dropped.
+#[cfg(not(feature = "std"))]
+macro_rules! println {
+    ($($arg:tt)*) => {{
+        let _ = format_args!($($arg)*);
+    }};
+}
+
 // Interrupts that can be triggered from devices.
 bitflags! {
-    #[derive(Default)]
+    /// Interrupt signals. Expansion chips raise IRQ.
+    #[derive(Default, Serialize, Deserialize)]
     pub struct Interrupt: u8 {
-        const NMI   = bit!(0);  // Indicates that V-Blank and NMI have occurred.
+        const NMI   = bit!(0);  // Indicates that NMI was triggered.
         const IRQ   = bit!(1);  // Indicates that an IRQ was triggered.
 
-        const VBLANK = bit!(2); // Indicates that V-Blank without NMI happened.
+        const VBLANK = bit!(2); // Indicates that V-Blank started. This can come with or without NMI.
 
         const RESET = bit!(3);  // Indicates a RST was triggered.
 
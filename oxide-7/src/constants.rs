@@ -3,10 +3,11 @@
 // Screen parameters
 pub mod screen {
     pub const HORIZONTAL_DOTS: usize = 341;
-    pub const NUM_SCANLINES: usize = 262;
+    pub const NUM_SCANLINES: usize = 262;   // NTSC: see machine::MachineTiming.
 
     pub const H_RES: usize = 256;
     pub const V_RES: usize = 224;
+    pub const V_RES_OVERSCAN: usize = 239;
 }
 
 // Master cycle counts
@@ -32,11 +33,71 @@ pub mod timing {
     // Clock rate of CPU.
     pub const MASTER_HZ: usize = SCANLINE * screen::NUM_SCANLINES * 60; // Clock rate of emulated CPU.
     pub const REAL_HZ: f64 = 21_477_270.0;                              // Clock rate of real CPU.
+    #[allow(dead_code)]
+    pub const REAL_HZ_PAL: f64 = 21_281_370.0;                          // Clock rate of real PAL CPU.
 
     // Clock rate of SPC-700
     pub const SPC_CLOCK_RATE: usize = 1_024_000;
 }
 
+// Timing that depends on the console region and the cart.
+pub mod machine {
+    use super::{screen, timing};
+
+    /// Console region.
+    /// PAL consoles have 312 lines in each frame and run at 50 frames per second.
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize))]
+    #[allow(clippy::upper_case_acronyms)]
+    pub enum Region {
+        NTSC,
+        PAL,
+    }
+
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    pub struct MachineTiming {
+        pub region:             Region,
+        pub internal_op:        usize,  // Master cycles for a CPU internal operation.
+        pub slow_rom_access:    usize,  // Master cycles for a cart ROM access.
+        pub fast_rom_access:    usize,  // Master cycles for a cart ROM access with FastROM enabled ($420D).
+        pub num_scanlines:      usize,  // Lines in a frame, including V-Blank.
+        pub frame_rate:         usize,  // Frames per second.
+    }
+
+    impl MachineTiming {
+        pub const NTSC: MachineTiming = MachineTiming {
+            region:             Region::NTSC,
+            internal_op:        timing::INTERNAL_OP,
+            slow_rom_access:    timing::SLOW_MEM_ACCESS,
+            fast_rom_access:    timing::FAST_MEM_ACCESS,
+            num_scanlines:      screen::NUM_SCANLINES,
+            frame_rate:         60,
+        };
+
+        pub const PAL: MachineTiming = MachineTiming {
+            region:             Region::PAL,
+            internal_op:        timing::INTERNAL_OP,
+            slow_rom_access:    timing::SLOW_MEM_ACCESS,
+            fast_rom_access:    timing::FAST_MEM_ACCESS,
+            num_scanlines:      312,
+            frame_rate:         50,
+        };
+
+        pub fn new(region: Region) -> Self {
+            match region {
+                Region::NTSC => Self::NTSC,
+                Region::PAL => Self::PAL,
+            }
+        }
+
+        // Clock rate of the emulated CPU.
+        #[allow(dead_code)]
+        pub fn master_hz(&self) -> usize {
+            timing::SCANLINE * self.num_scanlines * self.frame_rate
+        }
+    }
+}
+
 // Interrupt vector locations. Each contains a 16-bit address.
 pub mod int {
     pub const COP_VECTOR: u32   = 0xFFE4;
@@ -3,18 +3,45 @@ mod types;
 #[cfg(test)]
 mod tests;
 
+use alloc::{
+    boxed::Box,
+    string::String,
+    vec::Vec
+};
+
 use types::*;
 
 use crate::{
     mem::{
         AddrBusA,
+        ExpansionPort,
+        IOPort,
         MemBus
     },
     common::Interrupt,
-    constants::int,
-    joypad::Button,
-    video::RenderTarget
+    constants::{
+        int,
+        machine::{
+            MachineTiming,
+            Region
+        }
+    },
+    events::{
+        Event,
+        EventBus,
+        Processor,
+        UnsupportedFeature
+    },
+    joypad::{Button, InputProvider, Peripheral, Port, PortMonitor},
+    savestate::{
+        Stateful,
+        StateWriter,
+        StateReader
+    },
+    video::{RenderTarget, VideoMemMonitor}
 };
+#[cfg(feature = "std")]
+use crate::savestate::StateComponent;
 
 // 65816
 pub struct CPU<B: MemBus> {
@@ -36,13 +63,22 @@ pub struct CPU<B: MemBus> {
     internal_op_cycles: usize,  // Number of cycles for an internal operation.
 
     // Memory
-    mem:    B
+    mem:    B,
+
+    events: EventBus,
+
+    #[cfg(feature = "debug")]
+    cycle_count:    u64,    // Cycles the CPU has been clocked for.
+    #[cfg(feature = "debug")]
+    instr_count:    u64,    // Instructions the CPU has executed.
+    #[cfg(feature = "debug")]
+    profile:        crate::debug::CycleProfile,
 }
 
 // Public
 impl<B: MemBus> CPU<B> {
     // Create and initialise new CPU.
-    pub fn new(mut bus: B, internal_op_cycles: usize) -> Self {
+    pub fn new(mut bus: B, timing: &MachineTiming) -> Self {
         let start_pc_lo = bus.read(int::RESET_VECTOR_EMU).0;
         let start_pc_hi = bus.read(int::RESET_VECTOR_EMU + 1).0;
 
@@ -60,12 +96,26 @@ impl<B: MemBus> CPU<B> {
             pe:     true,
             halt:   false,
             int:    Interrupt::default(),
-            internal_op_cycles: internal_op_cycles,
+            internal_op_cycles: timing.internal_op,
+
+            mem:    bus,
+
+            events: EventBus::default(),
 
-            mem:    bus
+            #[cfg(feature = "debug")]
+            cycle_count:    0,
+            #[cfg(feature = "debug")]
+            instr_count:    0,
+            #[cfg(feature = "debug")]
+            profile:        crate::debug::CycleProfile::new(),
         }
     }
 
+    // Connect the CPU to the event bus, to report unsupported instructions.
+    pub fn set_event_bus(&mut self, events: EventBus) {
+        self.events = events;
+    }
+
     // A single step of the CPU.
     // Executes an instruction and clocks other components.
     // Returns true if V-Blank occurred.
@@ -75,16 +125,18 @@ impl<B: MemBus> CPU<B> {
             self.int.remove(Interrupt::RESET);
             self.reset();
         } else if self.int.contains(Interrupt::NMI) {
-            self.trigger_interrupt(if self.pe {int::NMI_VECTOR_EMU} else {int::NMI_VECTOR});
+            // NMI can be triggered late in V-Blank, so only end the frame if V-Blank just started.
+            self.trigger_interrupt(if self.pe {int::NMI_VECTOR_EMU} else {int::NMI_VECTOR}, false);
+            let frame_end = self.int.contains(Interrupt::VBLANK);
             self.int.remove(Interrupt::NMI | Interrupt::VBLANK);
             self.halt = false;
-            return true;
+            return frame_end;
         } else if self.int.contains(Interrupt::VBLANK) {
             self.int.remove(Interrupt::VBLANK);
             return true;
         } else if self.int.contains(Interrupt::IRQ) {
             if !self.p.contains(PFlags::I) {
-                self.trigger_interrupt(if self.pe {int::IRQ_VECTOR_EMU} else {int::IRQ_VECTOR});
+                self.trigger_interrupt(if self.pe {int::IRQ_VECTOR_EMU} else {int::IRQ_VECTOR}, false);
             }
             self.int.remove(Interrupt::IRQ);
             self.halt = false;
@@ -113,18 +165,181 @@ impl CPU<AddrBusA> {
         self.mem.set_buttons(button, val, joypad);
     }
 
+    pub fn queue_buttons(&mut self, button: Button, val: bool, joypad: usize, cycle: u64) {
+        self.mem.queue_buttons(button, val, joypad, cycle);
+    }
+
+    pub fn set_input_queue(&mut self, enable: bool) {
+        self.mem.set_input_queue(enable);
+    }
+
+    pub fn held_buttons(&self, joypad: usize) -> u16 {
+        self.mem.held_buttons(joypad)
+    }
+
+    pub fn set_input_provider(&mut self, provider: Option<Box<dyn InputProvider>>) -> Option<Box<dyn InputProvider>> {
+        self.mem.set_input_provider(provider)
+    }
+
+    pub fn set_peripheral(&mut self, port: Port, device: Option<Box<dyn Peripheral>>) -> Option<Box<dyn Peripheral>> {
+        self.mem.set_peripheral(port, device)
+    }
+
+    pub fn set_port_monitor(&mut self, monitor: Option<Box<dyn PortMonitor>>) -> Option<Box<dyn PortMonitor>> {
+        self.mem.set_port_monitor(monitor)
+    }
+
+    pub fn set_video_mem_monitor(&mut self, monitor: Option<Box<dyn VideoMemMonitor>>) -> Option<Box<dyn VideoMemMonitor>> {
+        self.mem.set_video_mem_monitor(monitor)
+    }
+
+    // Save the state of one component. The bus saves everything apart from the registers.
+    #[cfg(feature = "std")]
+    pub fn save_component_state(&self, component: StateComponent, w: &mut StateWriter) {
+        match component {
+            StateComponent::CPU => self.save_registers(w),
+            _ => self.mem.save_component_state(component, w),
+        }
+    }
+
+    pub fn get_wram(&self) -> &[u8] {
+        self.mem.get_wram()
+    }
+
+    pub fn randomize_wram(&mut self, seed: u64) {
+        self.mem.randomize_wram(seed);
+    }
+
+    pub fn copy_vram(&self) -> Vec<u8> {
+        self.mem.copy_vram()
+    }
+
+    pub fn copy_cgram(&self) -> Vec<u8> {
+        self.mem.copy_cgram()
+    }
+
+    pub fn copy_oam(&self) -> Vec<u8> {
+        self.mem.copy_oam()
+    }
+
+    pub fn set_expansion_port(&mut self, device: Option<Box<dyn ExpansionPort>>) -> Option<Box<dyn ExpansionPort>> {
+        self.mem.set_expansion_port(device)
+    }
+
+    pub fn set_io_port(&mut self, device: Option<Box<dyn IOPort>>) -> Option<Box<dyn IOPort>> {
+        self.mem.set_io_port(device)
+    }
+
+    pub fn set_sprite_limit(&mut self, enable: bool) {
+        self.mem.set_sprite_limit(enable);
+    }
+
+    pub fn set_power_on_garbage(&mut self, enable: bool) {
+        self.mem.set_power_on_garbage(enable);
+    }
+
+    pub fn set_region_flag(&mut self, region: Region) {
+        self.mem.set_region_flag(region);
+    }
+
+    pub fn set_fast_apu_boot(&mut self, enable: bool) {
+        self.mem.set_fast_apu_boot(enable);
+    }
+
+    pub fn set_dc_block(&mut self, enable: bool) {
+        self.mem.set_dc_block(enable);
+    }
+
+    pub fn master_cycles(&self) -> u64 {
+        self.mem.master_cycles()
+    }
+
+    pub fn set_widescreen(&mut self, extra: usize) {
+        self.mem.set_widescreen(extra);
+    }
+
+    pub fn set_overscan(&mut self, enable: bool) {
+        self.mem.set_overscan(enable);
+    }
+
     // Call this before processing each frame.
     pub fn start_frame(&mut self, frame: RenderTarget) {
         self.mem.start_frame(frame);
     }
 
-    pub fn get_audio_rx(&mut self) -> Option<crossbeam_channel::Receiver<crate::audio::SamplePacket>> {
-        self.mem.get_audio_rx()
+    // Call this after each frame, before reading the frame buffer.
+    pub fn end_frame(&mut self) {
+        self.mem.end_frame();
+    }
+
+    #[cfg(feature = "std")]
+    pub fn set_skip_render(&mut self, skip: bool) {
+        self.mem.set_skip_render(skip);
+    }
+
+    #[cfg(feature = "std")]
+    pub fn set_render_verification(&mut self, enable: bool) {
+        self.mem.set_render_verification(enable);
+    }
+
+    pub fn new_audio_rx(&mut self) -> crate::sync::Receiver<crate::audio::SamplePacket> {
+        self.mem.new_audio_rx()
     }
 
     pub fn rom_name(&self) -> String {
         self.mem.rom_name()
     }
+
+    pub fn cart_info(&self) -> crate::mem::rom::CartInfo {
+        self.mem.cart_info()
+    }
+
+    pub fn voice_states(&self) -> [crate::audio::VoiceState; 8] {
+        self.mem.voice_states()
+    }
+}
+
+impl<B: MemBus + Stateful> Stateful for CPU<B> {
+    fn save_state(&self, w: &mut StateWriter) {
+        self.save_registers(w);
+        self.mem.save_state(w);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), String> {
+        r.read_into(&mut self.a)?;
+        r.read_into(&mut self.x)?;
+        r.read_into(&mut self.y)?;
+        r.read_into(&mut self.s)?;
+        r.read_into(&mut self.db)?;
+        r.read_into(&mut self.dp)?;
+        r.read_into(&mut self.pb)?;
+        r.read_into(&mut self.p)?;
+        r.read_into(&mut self.pc)?;
+
+        r.read_into(&mut self.pe)?;
+        r.read_into(&mut self.halt)?;
+        r.read_into(&mut self.int)?;
+
+        self.mem.load_state(r)
+    }
+}
+
+impl<B: MemBus> CPU<B> {
+    fn save_registers(&self, w: &mut StateWriter) {
+        w.write(&self.a);
+        w.write(&self.x);
+        w.write(&self.y);
+        w.write(&self.s);
+        w.write(&self.db);
+        w.write(&self.dp);
+        w.write(&self.pb);
+        w.write(&self.p);
+        w.write(&self.pc);
+
+        w.write(&self.pe);
+        w.write(&self.halt);
+        w.write(&self.int);
+    }
 }
 
 // Internal: High-level
@@ -134,8 +349,16 @@ impl<B: MemBus> CPU<B> {
         use DataAddrMode::*;
         use DataMode::*;
 
+        #[cfg(feature = "debug")]
+        self.mem.set_pc(make24!(self.pb, self.pc));
+        #[cfg(feature = "debug")]
+        let start_cycles = self.cycle_count;
+
         let instr = self.fetch();
 
+        #[cfg(test)]
+        self.mem.opcode_hook(instr);
+
         match instr {
             0x61 => self.adc(Mode(DirPtrXDbr)),
             0x63 => self.adc(Mode(Stack)),
@@ -431,10 +654,20 @@ impl<B: MemBus> CPU<B> {
             0xEB => self.xba(),
             0xFB => self.xce(),
         }
+
+        #[cfg(feature = "debug")]
+        {
+            self.instr_count += 1;
+            self.profile.record(instr, (self.cycle_count - start_cycles) as usize);
+        }
     }
 
     // Clock
     fn clock_inc(&mut self, cycles: usize) {
+        #[cfg(feature = "debug")]
+        {
+            self.cycle_count += cycles as u64;
+        }
         self.int.insert(self.mem.clock(cycles));
     }
 
@@ -751,12 +984,11 @@ impl<B: MemBus> CPU<B> {
     fn brk(&mut self) {
         self.pc = self.pc.wrapping_add(1);
 
-        if self.pe {
-            self.p.insert(PFlags::B);
-            self.trigger_interrupt(int::BRK_VECTOR_EMU);
+        self.trigger_interrupt(if self.pe {
+            int::BRK_VECTOR_EMU
         } else {
-            self.trigger_interrupt(int::BRK_VECTOR);
-        }
+            int::BRK_VECTOR
+        }, true);
     }
 
     fn cop(&mut self) {
@@ -766,7 +998,7 @@ impl<B: MemBus> CPU<B> {
             int::COP_VECTOR_EMU
         } else {
             int::COP_VECTOR
-        });
+        }, true);
     }
 
     fn rti(&mut self) {
@@ -828,9 +1060,18 @@ impl<B: MemBus> CPU<B> {
     }
 
     fn stp(&mut self) {
-        // TODO
+        // TODO: this should stop the CPU until reset. For now, halt like WAI and report it.
         self.clock_inc(self.internal_op_cycles * 2);
-        panic!("STP not implemented.");
+        self.halt = true;
+        self.events.send(Event::BadOpcode {
+            processor:  Processor::CPU,
+            opcode:     0xDB,
+            pc:         make24!(self.pb, self.pc.wrapping_sub(1)),
+        });
+        self.events.report_unsupported(UnsupportedFeature::Instruction {
+            processor:  Processor::CPU,
+            opcode:     0xDB,
+        });
     }
 
     fn wai(&mut self) {
@@ -914,6 +1155,11 @@ impl<B: MemBus> CPU<B> {
 
         self.x = self.x.wrapping_add(1);
         self.y = self.y.wrapping_add(1);
+        if self.is_x_set() {
+            // 8-bit index registers wrap within the page.
+            self.x &= 0xFF;
+            self.y &= 0xFF;
+        }
 
         self.a = self.a.wrapping_sub(1);
 
@@ -936,6 +1182,11 @@ impl<B: MemBus> CPU<B> {
 
         self.x = self.x.wrapping_sub(1);
         self.y = self.y.wrapping_sub(1);
+        if self.is_x_set() {
+            // 8-bit index registers wrap within the page.
+            self.x &= 0xFF;
+            self.y &= 0xFF;
+        }
 
         self.a = self.a.wrapping_sub(1);
 
@@ -1265,15 +1516,22 @@ impl<B: MemBus> CPU<B> {
         self.p.contains(PFlags::X)
     }
 
-    fn trigger_interrupt(&mut self, vector_addr: u32) {
+    // In emulation mode, the B flag is pushed set for software interrupts (BRK, COP),
+    // and clear for hardware interrupts (NMI, IRQ). It only exists on the stack.
+    fn trigger_interrupt(&mut self, vector_addr: u32, software: bool) {
         if !self.pe {
             self.stack_push(self.pb);
             self.pb = 0;
         }
 
+        let mut pushed_p = self.p;
+        if self.pe {
+            pushed_p.set(PFlags::B, software);
+        }
+
         self.stack_push(hi!(self.pc));
         self.stack_push(lo!(self.pc));
-        self.stack_push(self.p.bits());
+        self.stack_push(pushed_p.bits());
 
         let pc_lo = self.read_data(vector_addr);
         let pc_hi = self.read_data(vector_addr + 1);
@@ -1389,7 +1647,7 @@ impl<B: MemBus> CPU<B> {
             Imm => self.immediate(byte),
             Acc => self.get_acc(),
             Mode(m) => {
-                let addr = self.get_data_addr(m);
+                let addr = self.get_data_addr(m, false);
                 self.read_addr(addr, byte)
             },
             Known(_) => unreachable!() // In practice we never read from known addresses.
@@ -1404,7 +1662,7 @@ impl<B: MemBus> CPU<B> {
             Imm => unreachable!(),  // We can't write back to immediate data.
             Acc => (self.get_acc(), Acc),
             Mode(m) => {
-                let addr = self.get_data_addr(m);
+                let addr = self.get_data_addr(m, true);
                 (self.read_addr(addr, byte), Known(addr))
             },
             Known(_) => unreachable!() // In practice we never read from known addresses.
@@ -1419,7 +1677,7 @@ impl<B: MemBus> CPU<B> {
             Imm => unreachable!(),  // We can't write to immediate data.
             Acc => self.set_acc(data),
             Mode(m) => {
-                let addr = self.get_data_addr(m);
+                let addr = self.get_data_addr(m, true);
                 self.write_addr(data, addr, byte);
             },
             Known(a) => self.write_addr(data, a, byte)
@@ -1427,20 +1685,21 @@ impl<B: MemBus> CPU<B> {
     }
 
     // Get an address of data using the specified addressing mode.
-    fn get_data_addr(&mut self, addr_mode: DataAddrMode) -> Addr {
+    // Write is set for instructions that write to the address (including read-modify-write).
+    fn get_data_addr(&mut self, addr_mode: DataAddrMode, write: bool) -> Addr {
         use DataAddrMode::*;
 
         match addr_mode {
             Abs             => self.absolute(),
-            AbsX            => self.absolute_x(),
-            AbsY            => self.absolute_y(),
+            AbsX            => self.absolute_x(write),
+            AbsY            => self.absolute_y(write),
 
             Dir             => self.direct(),
             DirX            => self.direct_x(),
             DirY            => self.direct_y(),
             DirPtrDbr       => self.direct_ptr_dbr(),
             DirPtrXDbr      => self.direct_ptr_x_dbr(),
-            DirPtrDbrY      => self.direct_ptr_dbr_y(),
+            DirPtrDbrY      => self.direct_ptr_dbr_y(write),
             DirPtr          => self.direct_ptr(),
             DirPtrY         => self.direct_ptr_y(),
             
@@ -1464,6 +1723,14 @@ impl<B: MemBus> CPU<B> {
         }
     }
 
+    // Indexed modes take an extra cycle with 16-bit index registers, or when the index crosses a page.
+    // Instructions that write always take the extra cycle.
+    fn index_cycle(&mut self, base_addr: u32, addr: u32, write: bool) {
+        if write || !self.is_x_set() || ((base_addr ^ addr) & 0xFFFF00) != 0 {
+            self.clock_inc(self.internal_op_cycles);
+        }
+    }
+
     // Addressing modes:
 
     // #$vvvv
@@ -1486,31 +1753,27 @@ impl<B: MemBus> CPU<B> {
     }
 
     // $vvvv, X
-    fn absolute_x(&mut self) -> Addr {
+    fn absolute_x(&mut self, write: bool) -> Addr {
         let imm_lo = self.fetch();
         let imm_hi = self.fetch();
 
         let abs_addr = make24!(self.db, imm_hi, imm_lo);
         let addr = abs_addr.wrapping_add(self.x as u32);
 
-        if !self.is_x_set() || (self.is_x_set() && (abs_addr < addr)) {
-            self.clock_inc(self.internal_op_cycles);
-        }
+        self.index_cycle(abs_addr, addr, write);
 
         Addr::Full(addr)
     }
 
     // $vvvv, Y
-    fn absolute_y(&mut self) -> Addr {
+    fn absolute_y(&mut self, write: bool) -> Addr {
         let imm_lo = self.fetch();
         let imm_hi = self.fetch();
 
         let abs_addr = make24!(self.db, imm_hi, imm_lo);
         let addr = abs_addr.wrapping_add(self.y as u32);
 
-        if !self.is_x_set() || (self.is_x_set() && (abs_addr < addr)) {
-            self.clock_inc(self.internal_op_cycles);
-        }
+        self.index_cycle(abs_addr, addr, write);
 
         Addr::Full(addr)
     }
@@ -1617,7 +1880,7 @@ impl<B: MemBus> CPU<B> {
     }
 
     // ($vv), Y
-    fn direct_ptr_dbr_y(&mut self) -> Addr {
+    fn direct_ptr_dbr_y(&mut self, write: bool) -> Addr {
         let imm = self.fetch();
 
         let (ptr_lo, ptr_hi) = if self.pe && (lo!(self.dp) == 0) {
@@ -1637,9 +1900,7 @@ impl<B: MemBus> CPU<B> {
         let addr = make24!(self.db, addr_hi, addr_lo);
         let final_addr = addr.wrapping_add(self.y as u32);
 
-        if !self.is_x_set() || (self.is_x_set() && (addr < final_addr)) {
-            self.clock_inc(self.internal_op_cycles);
-        }
+        self.index_cycle(addr, final_addr, write);
 
         Addr::Full(final_addr)
     }
@@ -1817,6 +2078,22 @@ impl<B: MemBus> CPU<B> {
         self.mem.read(addr).0
     }
 
+    pub fn set_mem_at(&mut self, addr: u32, data: u8) {
+        self.mem.write(addr, data);
+    }
+
+    pub fn get_cycle_profile(&self) -> crate::debug::CycleProfile {
+        self.profile.clone()
+    }
+
+    pub fn reset_cycle_profile(&mut self) {
+        self.profile = crate::debug::CycleProfile::new();
+    }
+
+    pub fn get_instr_count(&self) -> u64 {
+        self.instr_count
+    }
+
     // Get the instruction at the current PC, with the next 3 bytes for context.
     pub fn get_instr(&mut self) -> [u8; 4] {
         [
@@ -1826,4 +2103,67 @@ impl<B: MemBus> CPU<B> {
             self.mem.read(make24!(self.pb, self.pc.wrapping_add(3))).0
         ]
     }
+}
+
+#[cfg(feature = "debug")]
+impl CPU<AddrBusA> {
+    pub fn get_aram(&self) -> &[u8] {
+        self.mem.get_aram()
+    }
+
+    pub fn get_aram_mut(&mut self) -> &mut [u8] {
+        self.mem.get_aram_mut()
+    }
+
+    pub fn get_dsp_clamp_stats(&self) -> crate::debug::DSPClampStats {
+        self.mem.get_dsp_clamp_stats()
+    }
+
+    pub fn reset_dsp_clamp_stats(&mut self) {
+        self.mem.reset_dsp_clamp_stats();
+    }
+
+    pub fn export_bg_map(&self, bg: usize) -> Option<crate::debug::IndexedImage> {
+        self.mem.export_bg_map(bg)
+    }
+
+    pub fn export_obj_tables(&self, palette: u8) -> crate::debug::IndexedImage {
+        self.mem.export_obj_tables(palette)
+    }
+
+    pub fn set_bg_priority_override(&mut self, bg: usize, priority: Option<bool>) {
+        self.mem.set_bg_priority_override(bg, priority);
+    }
+
+    pub fn explain_pixel(&self, x: usize, y: usize) -> Option<crate::debug::PixelExplanation> {
+        self.mem.explain_pixel(x, y)
+    }
+
+    pub fn get_fx_cache_state(&self) -> Option<crate::debug::FXCacheState> {
+        self.mem.get_fx_cache_state()
+    }
+
+    pub fn get_hdma_states(&self) -> Vec<[crate::debug::HDMAChannelState; 8]> {
+        self.mem.get_hdma_states()
+    }
+
+    pub fn get_dma_channels(&self) -> [crate::debug::DMAChannelRegs; 8] {
+        self.mem.get_dma_channels()
+    }
+
+    pub fn trigger_dma(&mut self, channels: u8) {
+        self.mem.trigger_dma(channels);
+    }
+
+    pub fn get_sprite_lines(&self) -> Vec<crate::debug::SpriteLineState> {
+        self.mem.get_sprite_lines()
+    }
+
+    pub fn get_hv(&self) -> (u16, u16) {
+        self.mem.get_hv()
+    }
+
+    pub fn set_hv(&mut self, dot: u16, scanline: u16) {
+        self.mem.set_hv(dot, scanline);
+    }
 }
\ No newline at end of file
@@ -2,6 +2,12 @@
 use super::*;
 use crate::mem::MemBus;
 
+// One cycle for each internal operation.
+const TEST_TIMING: MachineTiming = MachineTiming {
+    internal_op: 1,
+    ..MachineTiming::NTSC
+};
+
 struct TestBus {
 }
 
@@ -19,7 +25,7 @@ impl MemBus for TestBus {
 
 #[test]
 fn decimal_add_8bit() {
-    let mut cpu = CPU::new(TestBus{}, 1);
+    let mut cpu = CPU::new(TestBus{}, &TEST_TIMING);
     cpu.pe = false;
     cpu.set_p(0x20);
     cpu.a = 0x1234;
@@ -47,7 +53,7 @@ fn decimal_add_8bit() {
 
 #[test]
 fn decimal_add_16bit() {
-    let mut cpu = CPU::new(TestBus{}, 1);
+    let mut cpu = CPU::new(TestBus{}, &TEST_TIMING);
     cpu.pe = false;
     cpu.set_p(0x00);
     cpu.a = 0x1234;
@@ -74,7 +80,7 @@ fn decimal_add_16bit() {
 
 #[test]
 fn decimal_sub_8bit() {
-    let mut cpu = CPU::new(TestBus{}, 1);
+    let mut cpu = CPU::new(TestBus{}, &TEST_TIMING);
     cpu.pe = false;
     cpu.set_p(0x21);
     cpu.a = 0x1234;
@@ -102,7 +108,7 @@ fn decimal_sub_8bit() {
 
 #[test]
 fn decimal_sub_16bit() {
-    let mut cpu = CPU::new(TestBus{}, 1);
+    let mut cpu = CPU::new(TestBus{}, &TEST_TIMING);
     cpu.pe = false;
     cpu.set_p(0x00);
     cpu.a = 0x1234;
@@ -125,4 +131,572 @@ fn decimal_sub_16bit() {
 
     assert_eq!(cpu.a, 0x0000);
     assert_eq!(cpu.p.bits(), 0x03); // Carry, Zero
-}
\ No newline at end of file
+}
+std::thread_local! {
+    // The opcodes executed on this thread by any RAMBus CPU.
+    static EXECUTED: core::cell::RefCell<[bool; 256]> = const { core::cell::RefCell::new([false; 256]) };
+}
+
+// A bus with 64kB of RAM, mirrored across all banks.
+// Records which opcodes have been executed, and keeps track of how many cycles the CPU has been clocked for.
+// The IRQ line is held for as long as irq is set, like a level-triggered device.
+struct RAMBus {
+    data: Vec<u8>,
+    irq: bool,
+    cycles: usize
+}
+
+impl RAMBus {
+    fn new() -> Self {
+        RAMBus {
+            data: vec![0; 0x10000],
+            irq: false,
+            cycles: 0
+        }
+    }
+}
+
+impl MemBus for RAMBus {
+    fn read(&mut self, addr: u32) -> (u8, usize) {
+        (self.data[(addr & 0xFFFF) as usize], 0)
+    }
+    fn write(&mut self, addr: u32, data: u8) -> usize {
+        self.data[(addr & 0xFFFF) as usize] = data;
+        0
+    }
+    fn clock(&mut self, cycles: usize) -> Interrupt {
+        self.cycles += cycles;
+        if self.irq {Interrupt::IRQ} else {Interrupt::default()}
+    }
+    fn opcode_hook(&mut self, opcode: u8) {
+        EXECUTED.with(|e| e.borrow_mut()[opcode as usize] = true);
+    }
+}
+
+// Set up a CPU in emulation mode, with all interrupt vectors pointing to different places.
+fn interrupt_test_cpu() -> CPU<RAMBus> {
+    let mut bus = RAMBus::new();
+    let vectors = [
+        (int::COP_VECTOR, 0x8100_u16),
+        (int::BRK_VECTOR, 0x8200),
+        (int::NMI_VECTOR, 0x8300),
+        (int::IRQ_VECTOR, 0x8400),
+        (int::COP_VECTOR_EMU, 0x9100),
+        (int::NMI_VECTOR_EMU, 0x9300),
+        (int::RESET_VECTOR_EMU, 0x8000),
+        (int::BRK_VECTOR_EMU, 0x9400),
+    ];
+    for (vector, addr) in vectors.iter() {
+        bus.write(*vector, lo!(*addr));
+        bus.write(*vector + 1, hi!(*addr));
+    }
+
+    let mut cpu = CPU::new(bus, &TEST_TIMING);
+    cpu.s = 0x01FF;
+    cpu.pc = 0x8000;
+    cpu
+}
+
+#[test]
+fn emulation_brk() {
+    let mut cpu = interrupt_test_cpu();
+    cpu.set_p(0x08);    // Decimal mode.
+
+    cpu.brk();
+
+    assert_eq!(cpu.pc, 0x9400);
+    assert_eq!(cpu.s, 0x01FC);
+    // Return address skips the signature byte.
+    assert_eq!(cpu.mem.data[0x01FF], 0x80);
+    assert_eq!(cpu.mem.data[0x01FE], 0x01);
+    // B flag set, D flag pushed before being cleared.
+    assert_eq!(cpu.mem.data[0x01FD], 0x38);
+    assert!(cpu.p.contains(PFlags::I));
+    assert!(!cpu.p.contains(PFlags::D));
+}
+
+#[test]
+fn emulation_cop() {
+    let mut cpu = interrupt_test_cpu();
+    cpu.set_p(0x08);
+
+    cpu.cop();
+
+    assert_eq!(cpu.pc, 0x9100);
+    assert_eq!(cpu.s, 0x01FC);
+    assert_eq!(cpu.mem.data[0x01FD], 0x38);
+    assert!(cpu.p.contains(PFlags::I));
+    assert!(!cpu.p.contains(PFlags::D));
+}
+
+#[test]
+fn emulation_irq() {
+    let mut cpu = interrupt_test_cpu();
+    cpu.set_p(0x08);
+    cpu.int.insert(Interrupt::IRQ);
+
+    cpu.step();
+
+    // IRQ shares a vector with BRK.
+    assert_eq!(cpu.pc, 0x9400);
+    assert_eq!(cpu.s, 0x01FC);
+    // No signature byte to skip.
+    assert_eq!(cpu.mem.data[0x01FF], 0x80);
+    assert_eq!(cpu.mem.data[0x01FE], 0x00);
+    // B flag clear: this is how the handler tells IRQ and BRK apart.
+    assert_eq!(cpu.mem.data[0x01FD], 0x28);
+    assert!(cpu.p.contains(PFlags::I));
+    assert!(!cpu.p.contains(PFlags::D));
+    // B and X share a bit, but X is always set in emulation mode.
+    assert!(cpu.p.contains(PFlags::X));
+}
+
+#[test]
+fn emulation_irq_disabled() {
+    let mut cpu = interrupt_test_cpu();
+    cpu.set_p(0x04);
+    cpu.int.insert(Interrupt::IRQ);
+
+    cpu.step();
+
+    assert_eq!(cpu.pc, 0x8000);
+    assert_eq!(cpu.s, 0x01FF);
+    assert!(!cpu.int.contains(Interrupt::IRQ));
+}
+
+#[test]
+fn emulation_nmi() {
+    let mut cpu = interrupt_test_cpu();
+    cpu.set_p(0x0C);    // NMI ignores the I flag.
+    cpu.int.insert(Interrupt::NMI | Interrupt::VBLANK);
+
+    assert!(cpu.step());
+
+    assert_eq!(cpu.pc, 0x9300);
+    assert_eq!(cpu.s, 0x01FC);
+    assert_eq!(cpu.mem.data[0x01FD], 0x2C);
+    assert!(!cpu.p.contains(PFlags::D));
+}
+
+// NMI enabled part way through V-Blank doesn't end the frame.
+#[test]
+fn late_nmi() {
+    let mut cpu = interrupt_test_cpu();
+    cpu.int.insert(Interrupt::NMI);
+
+    assert!(!cpu.step());
+
+    assert_eq!(cpu.pc, 0x9300);
+    assert_eq!(cpu.s, 0x01FC);
+}
+
+#[test]
+fn emulation_rti() {
+    let mut cpu = interrupt_test_cpu();
+    cpu.set_p(0x09);
+
+    cpu.brk();
+    cpu.rti();
+
+    assert_eq!(cpu.pc, 0x8001);
+    assert_eq!(cpu.s, 0x01FF);
+    // B isn't a real flag: X is still set, as in any emulation mode status.
+    assert_eq!(cpu.p.bits(), 0x39);
+}
+
+#[test]
+fn native_brk() {
+    let mut cpu = interrupt_test_cpu();
+    cpu.pe = false;
+    cpu.set_p(0x08);    // Decimal mode, 16-bit registers.
+    cpu.pb = 0x12;
+
+    cpu.brk();
+
+    assert_eq!(cpu.pc, 0x8200);
+    assert_eq!(cpu.pb, 0);
+    assert_eq!(cpu.s, 0x01FB);
+    // Program bank is pushed first.
+    assert_eq!(cpu.mem.data[0x01FF], 0x12);
+    assert_eq!(cpu.mem.data[0x01FE], 0x80);
+    assert_eq!(cpu.mem.data[0x01FD], 0x01);
+    // No B flag in native mode: the status is pushed unchanged.
+    assert_eq!(cpu.mem.data[0x01FC], 0x08);
+    assert!(cpu.p.contains(PFlags::I));
+    assert!(!cpu.p.contains(PFlags::D));
+}
+
+#[test]
+fn native_irq() {
+    let mut cpu = interrupt_test_cpu();
+    cpu.pe = false;
+    cpu.set_p(0x18);    // 8-bit index registers share a bit with B.
+    cpu.int.insert(Interrupt::IRQ);
+
+    cpu.step();
+
+    assert_eq!(cpu.pc, 0x8400);
+    assert_eq!(cpu.s, 0x01FB);
+    assert_eq!(cpu.mem.data[0x01FC], 0x18);
+}
+
+// An IRQ that is held while interrupts are disabled is taken once they are enabled.
+#[test]
+fn irq_level_held() {
+    let mut cpu = interrupt_test_cpu();
+    cpu.set_p(0x04);
+    cpu.mem.data[0x8000] = 0xEA;    // NOP
+    cpu.mem.data[0x8001] = 0x58;    // CLI
+    cpu.mem.irq = true;
+
+    for _ in 0..4 {
+        cpu.step();
+    }
+
+    assert_eq!(cpu.pc, 0x9400);
+    assert_eq!(cpu.mem.data[0x01FE], 0x02);
+}
+
+// An IRQ that is acknowledged at the source before interrupts are enabled is never taken.
+#[test]
+fn irq_level_acknowledged() {
+    let mut cpu = interrupt_test_cpu();
+    cpu.set_p(0x04);
+    cpu.mem.data[0x8000] = 0xEA;    // NOP
+    cpu.mem.data[0x8001] = 0x58;    // CLI
+    cpu.mem.data[0x8002] = 0xEA;    // NOP
+    cpu.mem.irq = true;
+
+    cpu.step();
+    cpu.mem.irq = false;
+    for _ in 0..3 {
+        cpu.step();
+    }
+
+    assert_eq!(cpu.pc, 0x8003);
+    assert_eq!(cpu.s, 0x01FF);
+}
+
+// Set up a CPU in native mode with 16-bit registers, about to run a block move.
+fn block_move_test_cpu(opcode: u8, dst_bank: u8, src_bank: u8) -> CPU<RAMBus> {
+    let mut cpu = interrupt_test_cpu();
+    cpu.pe = false;
+    cpu.set_p(0x00);
+    cpu.mem.data[0x8000] = opcode;
+    cpu.mem.data[0x8001] = dst_bank;
+    cpu.mem.data[0x8002] = src_bank;
+    cpu
+}
+
+#[test]
+fn mvn_copy() {
+    let mut cpu = block_move_test_cpu(0x54, 0x7E, 0x7F);
+    cpu.mem.data[0x1000..0x1004].copy_from_slice(&[1, 2, 3, 4]);
+    cpu.a = 3;
+    cpu.x = 0x1000;
+    cpu.y = 0x2000;
+    cpu.db = 0x12;
+
+    // One byte is moved per step, with the PC rewound until A underflows.
+    for _ in 0..3 {
+        cpu.step();
+        assert_eq!(cpu.pc, 0x8000);
+        assert_eq!(cpu.db, 0x7E);
+    }
+    cpu.step();
+
+    assert_eq!(cpu.pc, 0x8003);
+    assert_eq!(cpu.a, 0xFFFF);
+    assert_eq!(cpu.x, 0x1004);
+    assert_eq!(cpu.y, 0x2004);
+    // The destination bank is left in DB.
+    assert_eq!(cpu.db, 0x7E);
+    assert_eq!(&cpu.mem.data[0x2000..0x2004], &[1, 2, 3, 4]);
+}
+
+#[test]
+fn mvp_copy() {
+    let mut cpu = block_move_test_cpu(0x44, 0x01, 0x02);
+    cpu.mem.data[0x1000..0x1004].copy_from_slice(&[1, 2, 3, 4]);
+    cpu.a = 3;
+    cpu.x = 0x1003;
+    cpu.y = 0x2003;
+
+    for _ in 0..4 {
+        cpu.step();
+    }
+
+    assert_eq!(cpu.pc, 0x8003);
+    assert_eq!(cpu.a, 0xFFFF);
+    assert_eq!(cpu.x, 0x0FFF);
+    assert_eq!(cpu.y, 0x1FFF);
+    assert_eq!(cpu.db, 0x01);
+    assert_eq!(&cpu.mem.data[0x2000..0x2004], &[1, 2, 3, 4]);
+}
+
+// With 8-bit index registers, X and Y wrap within the page.
+#[test]
+fn mvn_8bit_index() {
+    let mut cpu = block_move_test_cpu(0x54, 0x00, 0x00);
+    cpu.set_p(0x10);
+    cpu.mem.data[0x00FF] = 0xAA;
+    cpu.mem.data[0x0000] = 0xBB;
+    cpu.a = 1;
+    cpu.x = 0xFF;
+    cpu.y = 0x80;
+
+    cpu.step();
+    cpu.step();
+
+    assert_eq!(cpu.x, 0x01);
+    assert_eq!(cpu.y, 0x82);
+    assert_eq!(&cpu.mem.data[0x0080..0x0082], &[0xAA, 0xBB]);
+}
+
+#[test]
+fn mvp_8bit_index() {
+    let mut cpu = block_move_test_cpu(0x44, 0x00, 0x00);
+    cpu.set_p(0x10);
+    cpu.a = 0;
+    cpu.x = 0x00;
+    cpu.y = 0x00;
+
+    cpu.step();
+
+    assert_eq!(cpu.x, 0xFF);
+    assert_eq!(cpu.y, 0xFF);
+}
+
+// A block move can be interrupted between bytes, and resumes on return.
+#[test]
+fn mvn_interrupted() {
+    let mut cpu = block_move_test_cpu(0x54, 0x7E, 0x00);
+    cpu.a = 1;
+    cpu.x = 0x1000;
+    cpu.y = 0x2000;
+    cpu.pb = 0x00;
+
+    cpu.step();
+    cpu.int.insert(Interrupt::IRQ);
+    cpu.step();
+
+    // The return address points back at the move instruction.
+    assert_eq!(cpu.pc, 0x8400);
+    assert_eq!(cpu.mem.data[0x01FE], 0x80);
+    assert_eq!(cpu.mem.data[0x01FD], 0x00);
+    // DB isn't touched by the interrupt.
+    assert_eq!(cpu.db, 0x7E);
+
+    cpu.rti();
+    cpu.step();
+
+    assert_eq!(cpu.pc, 0x8003);
+    assert_eq!(cpu.a, 0xFFFF);
+}
+
+#[test]
+fn phb_plb() {
+    let mut cpu = interrupt_test_cpu();
+    cpu.pe = false;
+    cpu.set_p(0x00);
+    cpu.mem.data[0x8000] = 0x8B;    // PHB
+    cpu.mem.data[0x8001] = 0xAB;    // PLB
+    cpu.mem.data[0x8002] = 0xAB;    // PLB
+    cpu.mem.data[0x01FE] = 0x00;
+    cpu.db = 0x80;
+
+    cpu.step();
+    assert_eq!(cpu.s, 0x01FE);
+    assert_eq!(cpu.mem.data[0x01FF], 0x80);
+
+    cpu.db = 0;
+    cpu.step();
+    assert_eq!(cpu.db, 0x80);
+    assert_eq!(cpu.s, 0x01FF);
+    // PLB sets N and Z from the byte pulled.
+    assert!(cpu.p.contains(PFlags::N));
+    assert!(!cpu.p.contains(PFlags::Z));
+
+    cpu.s = 0x01FD;
+    cpu.step();
+    assert_eq!(cpu.db, 0x00);
+    assert!(!cpu.p.contains(PFlags::N));
+    assert!(cpu.p.contains(PFlags::Z));
+}
+
+#[test]
+fn phk() {
+    let mut cpu = interrupt_test_cpu();
+    cpu.pe = false;
+    cpu.set_p(0x02);
+    cpu.mem.data[0x8000] = 0x4B;    // PHK
+    cpu.pb = 0x00;
+    cpu.db = 0x34;
+
+    cpu.step();
+
+    assert_eq!(cpu.s, 0x01FE);
+    assert_eq!(cpu.mem.data[0x01FF], 0x00);
+    // Flags and DB are unchanged.
+    assert_eq!(cpu.p.bits(), 0x02);
+    assert_eq!(cpu.db, 0x34);
+}
+
+// Interrupts push PB in native mode but leave DB alone.
+#[test]
+fn native_interrupt_preserves_db() {
+    let mut cpu = interrupt_test_cpu();
+    cpu.pe = false;
+    cpu.set_p(0x00);
+    cpu.pb = 0x12;
+    cpu.db = 0x34;
+
+    cpu.brk();
+
+    assert_eq!(cpu.pb, 0);
+    assert_eq!(cpu.db, 0x34);
+
+    cpu.rti();
+
+    assert_eq!(cpu.pb, 0x12);
+    assert_eq!(cpu.db, 0x34);
+}
+
+// STP sends an event every time, but is only added to the unsupported list once.
+#[test]
+fn stp_reported() {
+    let mut cpu = interrupt_test_cpu();
+    let events = EventBus::new();
+    let rx = events.subscribe();
+    cpu.set_event_bus(events.clone());
+
+    cpu.stp();
+    cpu.stp();
+
+    let stp = UnsupportedFeature::Instruction {
+        processor:  Processor::CPU,
+        opcode:     0xDB,
+    };
+    assert_eq!(events.unsupported_features(), vec![stp]);
+    let received = rx.try_iter().collect::<Vec<_>>();
+    assert_eq!(received.iter().filter(|e| matches!(e, Event::BadOpcode {..})).count(), 2);
+    assert_eq!(received.iter().filter(|e| **e == Event::UnsupportedFeatureUsed(stp)).count(), 1);
+    assert!(cpu.halt);
+}
+
+// Run the instruction at $8000 in native mode, and return the number of internal cycles it took.
+// Memory accesses take no time on the RAMBus.
+fn internal_cycles(p: u8, x: u16, y: u16, code: &[u8]) -> usize {
+    let mut cpu = interrupt_test_cpu();
+    cpu.pe = false;
+    cpu.set_p(p);
+    cpu.x = x;
+    cpu.y = y;
+    cpu.mem.data[0x8000..(0x8000 + code.len())].copy_from_slice(code);
+    // Pointer for ($10),Y
+    cpu.mem.data[0x10] = 0xF0;
+    cpu.mem.data[0x11] = 0x10;
+
+    cpu.execute_instruction();
+    cpu.mem.cycles
+}
+
+#[test]
+fn abs_indexed_read_cycles() {
+    // 8-bit index: extra cycle only when crossing a page.
+    assert_eq!(internal_cycles(0x30, 0x08, 0, &[0xBD, 0xF0, 0x10]), 0);  // LDA $10F0,X
+    assert_eq!(internal_cycles(0x30, 0x10, 0, &[0xBD, 0xF0, 0x10]), 1);
+    assert_eq!(internal_cycles(0x30, 0, 0x08, &[0xB9, 0xF0, 0x10]), 0);  // LDA $10F0,Y
+    assert_eq!(internal_cycles(0x30, 0, 0x10, &[0xB9, 0xF0, 0x10]), 1);
+    assert_eq!(internal_cycles(0x30, 0, 0x08, &[0xBE, 0xF0, 0x10]), 0);  // LDX $10F0,Y
+    // 16-bit index: always an extra cycle.
+    assert_eq!(internal_cycles(0x20, 0x00, 0, &[0xBD, 0xF0, 0x10]), 1);
+    assert_eq!(internal_cycles(0x20, 0, 0x08, &[0xB9, 0xF0, 0x10]), 1);
+}
+
+#[test]
+fn abs_indexed_write_cycles() {
+    // Writes always take the extra cycle.
+    assert_eq!(internal_cycles(0x30, 0x08, 0, &[0x9D, 0xF0, 0x10]), 1);  // STA $10F0,X
+    assert_eq!(internal_cycles(0x30, 0x10, 0, &[0x9D, 0xF0, 0x10]), 1);
+    assert_eq!(internal_cycles(0x30, 0, 0x08, &[0x99, 0xF0, 0x10]), 1);  // STA $10F0,Y
+    assert_eq!(internal_cycles(0x30, 0x08, 0, &[0x9E, 0xF0, 0x10]), 1);  // STZ $10F0,X
+    assert_eq!(internal_cycles(0x20, 0x08, 0, &[0x9D, 0xF0, 0x10]), 1);
+    // Read-modify-write instructions take one more than the absolute mode.
+    let inc_abs = internal_cycles(0x30, 0x08, 0, &[0xEE, 0xF0, 0x10]);   // INC $10F0
+    assert_eq!(internal_cycles(0x30, 0x08, 0, &[0xFE, 0xF0, 0x10]), inc_abs + 1);    // INC $10F0,X
+}
+
+#[test]
+fn dir_ptr_indexed_cycles() {
+    // ($10),Y with the pointer set to $10F0.
+    assert_eq!(internal_cycles(0x30, 0, 0x08, &[0xB1, 0x10]), 0);    // LDA ($10),Y
+    assert_eq!(internal_cycles(0x30, 0, 0x10, &[0xB1, 0x10]), 1);
+    assert_eq!(internal_cycles(0x20, 0, 0x08, &[0xB1, 0x10]), 1);
+    assert_eq!(internal_cycles(0x30, 0, 0x08, &[0x91, 0x10]), 1);    // STA ($10),Y
+}
+
+// Opcodes that none of the tests above execute.
+// Remove opcodes from this list as tests are written for them.
+const UNTESTED_OPCODES: &[u8] = &[
+    0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0A, 0x0B, 0x0C, 0x0D, 0x0E, 0x0F,
+    0x10, 0x11, 0x12, 0x13, 0x14, 0x15, 0x16, 0x17, 0x18, 0x19, 0x1A, 0x1B, 0x1C, 0x1D, 0x1E, 0x1F,
+    0x20, 0x21, 0x22, 0x23, 0x24, 0x25, 0x26, 0x27, 0x28, 0x29, 0x2A, 0x2B, 0x2C, 0x2D, 0x2E, 0x2F,
+    0x30, 0x31, 0x32, 0x33, 0x34, 0x35, 0x36, 0x37, 0x38, 0x39, 0x3A, 0x3B, 0x3C, 0x3D, 0x3E, 0x3F,
+    0x40, 0x41, 0x42, 0x43, 0x45, 0x46, 0x47, 0x48, 0x49, 0x4A, 0x4C, 0x4D, 0x4E, 0x4F,
+    0x50, 0x51, 0x52, 0x53, 0x55, 0x56, 0x57, 0x59, 0x5A, 0x5B, 0x5C, 0x5D, 0x5E, 0x5F,
+    0x60, 0x61, 0x62, 0x63, 0x64, 0x65, 0x66, 0x67, 0x68, 0x69, 0x6A, 0x6B, 0x6C, 0x6D, 0x6E, 0x6F,
+    0x70, 0x71, 0x72, 0x73, 0x74, 0x75, 0x76, 0x77, 0x78, 0x79, 0x7A, 0x7B, 0x7C, 0x7D, 0x7E, 0x7F,
+    0x80, 0x81, 0x82, 0x83, 0x84, 0x85, 0x86, 0x87, 0x88, 0x89, 0x8A, 0x8C, 0x8D, 0x8E, 0x8F,
+    0x90, 0x92, 0x93, 0x94, 0x95, 0x96, 0x97, 0x98, 0x9A, 0x9B, 0x9C, 0x9F,
+    0xA0, 0xA1, 0xA2, 0xA3, 0xA4, 0xA5, 0xA6, 0xA7, 0xA8, 0xA9, 0xAA, 0xAC, 0xAD, 0xAE, 0xAF,
+    0xB0, 0xB2, 0xB3, 0xB4, 0xB5, 0xB6, 0xB7, 0xB8, 0xBA, 0xBB, 0xBC, 0xBF,
+    0xC0, 0xC1, 0xC2, 0xC3, 0xC4, 0xC5, 0xC6, 0xC7, 0xC8, 0xC9, 0xCA, 0xCB, 0xCC, 0xCD, 0xCE, 0xCF,
+    0xD0, 0xD1, 0xD2, 0xD3, 0xD4, 0xD5, 0xD6, 0xD7, 0xD8, 0xD9, 0xDA, 0xDB, 0xDC, 0xDD, 0xDE, 0xDF,
+    0xE0, 0xE1, 0xE2, 0xE3, 0xE4, 0xE5, 0xE6, 0xE7, 0xE8, 0xE9, 0xEB, 0xEC, 0xED, 0xEF,
+    0xF0, 0xF1, 0xF2, 0xF3, 0xF4, 0xF5, 0xF6, 0xF7, 0xF8, 0xF9, 0xFA, 0xFB, 0xFC, 0xFD, 0xFF,
+];
+
+// Run the tests that execute instructions, and check which opcodes they missed.
+// The tests are called directly so that the record only holds what they executed on this thread.
+#[test]
+fn opcode_coverage() {
+    let tests: &[fn()] = &[
+        emulation_brk,
+        emulation_cop,
+        emulation_irq,
+        emulation_irq_disabled,
+        emulation_nmi,
+        late_nmi,
+        emulation_rti,
+        native_brk,
+        native_irq,
+        irq_level_held,
+        irq_level_acknowledged,
+        mvn_copy,
+        mvp_copy,
+        mvn_8bit_index,
+        mvp_8bit_index,
+        mvn_interrupted,
+        phb_plb,
+        phk,
+        native_interrupt_preserves_db,
+        stp_reported,
+        abs_indexed_read_cycles,
+        abs_indexed_write_cycles,
+        dir_ptr_indexed_cycles,
+    ];
+    for test in tests {
+        test();
+    }
+
+    let missing = EXECUTED.with(|e| {
+        e.borrow().iter()
+            .enumerate()
+            .filter(|(_, executed)| !**executed)
+            .map(|(op, _)| op as u8)
+            .collect::<Vec<_>>()
+    });
+    let list = |ops: &[u8]| ops.iter().map(|op| format!("{:02X}", op)).collect::<Vec<_>>().join(", ");
+    assert_eq!(missing, UNTESTED_OPCODES, "Opcodes not executed by the tests: {}", list(&missing));
+}
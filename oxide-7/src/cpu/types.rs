@@ -1,11 +1,14 @@
 // Types used inside the CPU.
 
 use bitflags::bitflags;
-
+use serde::{
+    Serialize,
+    Deserialize
+};
 
 bitflags! {
     // Flags for status bits inside the CPU.
-    #[derive(Default)]
+    #[derive(Default, Serialize, Deserialize)]
     pub struct PFlags: u8 {
         const N = bit!(7);  // Negative
         const V = bit!(6);  // Overflow
@@ -16,7 +19,7 @@ bitflags! {
         const Z = bit!(1);  // Zero
         const C = bit!(0);  // Carry
 
-        const B = bit!(5);  // Break
+        const B = bit!(4);  // Break (emulation mode only, shares a bit with X)
     }
 }
 
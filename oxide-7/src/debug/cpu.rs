@@ -0,0 +1,89 @@
+// Disassembler for the 65816.
+
+// Each opcode's text. Lowercase letters are placeholders for operands:
+// d: direct page, a: absolute, l: long, i: 8-bit immediate,
+// m: immediate sized by the M flag, x: immediate sized by the X flag,
+// r: relative branch, w: long relative branch, b: bank.
+const OPCODES: [&str; 256] = [
+    "BRK i",        "ORA (d,X)",    "COP i",        "ORA d,S",      "TSB d",        "ORA d",        "ASL d",        "ORA [d]",
+    "PHP",          "ORA m",        "ASL A",        "PHD",          "TSB a",        "ORA a",        "ASL a",        "ORA l",
+    "BPL r",        "ORA (d),Y",    "ORA (d)",      "ORA (d,S),Y",  "TRB d",        "ORA d,X",      "ASL d,X",      "ORA [d],Y",
+    "CLC",          "ORA a,Y",      "INC A",        "TCS",          "TRB a",        "ORA a,X",      "ASL a,X",      "ORA l,X",
+    "JSR a",        "AND (d,X)",    "JSL l",        "AND d,S",      "BIT d",        "AND d",        "ROL d",        "AND [d]",
+    "PLP",          "AND m",        "ROL A",        "PLD",          "BIT a",        "AND a",        "ROL a",        "AND l",
+    "BMI r",        "AND (d),Y",    "AND (d)",      "AND (d,S),Y",  "BIT d,X",      "AND d,X",      "ROL d,X",      "AND [d],Y",
+    "SEC",          "AND a,Y",      "DEC A",        "TSC",          "BIT a,X",      "AND a,X",      "ROL a,X",      "AND l,X",
+    "RTI",          "EOR (d,X)",    "WDM i",        "EOR d,S",      "MVP b,b",      "EOR d",        "LSR d",        "EOR [d]",
+    "PHA",          "EOR m",        "LSR A",        "PHK",          "JMP a",        "EOR a",        "LSR a",        "EOR l",
+    "BVC r",        "EOR (d),Y",    "EOR (d)",      "EOR (d,S),Y",  "MVN b,b",      "EOR d,X",      "LSR d,X",      "EOR [d],Y",
+    "CLI",          "EOR a,Y",      "PHY",          "TCD",          "JML l",        "EOR a,X",      "LSR a,X",      "EOR l,X",
+    "RTS",          "ADC (d,X)",    "PER w",        "ADC d,S",      "STZ d",        "ADC d",        "ROR d",        "ADC [d]",
+    "PLA",          "ADC m",        "ROR A",        "RTL",          "JMP (a)",      "ADC a",        "ROR a",        "ADC l",
+    "BVS r",        "ADC (d),Y",    "ADC (d)",      "ADC (d,S),Y",  "STZ d,X",      "ADC d,X",      "ROR d,X",      "ADC [d],Y",
+    "SEI",          "ADC a,Y",      "PLY",          "TDC",          "JMP (a,X)",    "ADC a,X",      "ROR a,X",      "ADC l,X",
+    "BRA r",        "STA (d,X)",    "BRL w",        "STA d,S",      "STY d",        "STA d",        "STX d",        "STA [d]",
+    "DEY",          "BIT m",        "TXA",          "PHB",          "STY a",        "STA a",        "STX a",        "STA l",
+    "BCC r",        "STA (d),Y",    "STA (d)",      "STA (d,S),Y",  "STY d,X",      "STA d,X",      "STX d,Y",      "STA [d],Y",
+    "TYA",          "STA a,Y",      "TXS",          "TXY",          "STZ a",        "STA a,X",      "STZ a,X",      "STA l,X",
+    "LDY x",        "LDA (d,X)",    "LDX x",        "LDA d,S",      "LDY d",        "LDA d",        "LDX d",        "LDA [d]",
+    "TAY",          "LDA m",        "TAX",          "PLB",          "LDY a",        "LDA a",        "LDX a",        "LDA l",
+    "BCS r",        "LDA (d),Y",    "LDA (d)",      "LDA (d,S),Y",  "LDY d,X",      "LDA d,X",      "LDX d,Y",      "LDA [d],Y",
+    "CLV",          "LDA a,Y",      "TSX",          "TYX",          "LDY a,X",      "LDA a,X",      "LDX a,Y",      "LDA l,X",
+    "CPY x",        "CMP (d,X)",    "REP i",        "CMP d,S",      "CPY d",        "CMP d",        "DEC d",        "CMP [d]",
+    "INY",          "CMP m",        "DEX",          "WAI",          "CPY a",        "CMP a",        "DEC a",        "CMP l",
+    "BNE r",        "CMP (d),Y",    "CMP (d)",      "CMP (d,S),Y",  "PEI (d)",      "CMP d,X",      "DEC d,X",      "CMP [d],Y",
+    "CLD",          "CMP a,Y",      "PHX",          "STP",          "JML [a]",      "CMP a,X",      "DEC a,X",      "CMP l,X",
+    "CPX x",        "SBC (d,X)",    "SEP i",        "SBC d,S",      "CPX d",        "SBC d",        "INC d",        "SBC [d]",
+    "INX",          "SBC m",        "NOP",          "XBA",          "CPX a",        "SBC a",        "INC a",        "SBC l",
+    "BEQ r",        "SBC (d),Y",    "SBC (d)",      "SBC (d,S),Y",  "PEA a",        "SBC d,X",      "INC d,X",      "SBC [d],Y",
+    "SED",          "SBC a,Y",      "PLX",          "XCE",          "JSR (a,X)",    "SBC a,X",      "INC a,X",      "SBC l,X",
+];
+
+/// Disassemble a single 65816 instruction at the start of `bytes`.
+/// `pc` is the address of the first byte, used to calculate branch targets.
+/// `m` and `x` are the accumulator and index size flags, which set the size of immediate operands.
+///
+/// Returns the instruction text and the number of bytes used, or None if
+/// `bytes` ends before the instruction does.
+pub fn disassemble_cpu(pc: u16, bytes: &[u8], m: bool, x: bool) -> Option<(String, usize)> {
+    let opcode = *bytes.first()?;
+    let template = OPCODES[opcode as usize];
+
+    let mut len = 1;
+    let mut text = String::new();
+    for c in template.chars() {
+        let size = match c {
+            'd' | 'i' | 'r' | 'b' => 1,
+            'a' | 'w' => 2,
+            'l' => 3,
+            'm' => if m {1} else {2},
+            'x' => if x {1} else {2},
+            _ => {
+                text.push(c);
+                continue;
+            }
+        };
+        let data = (0..size).rev().try_fold(0_u32, |acc, i| Some((acc << 8) | (*bytes.get(len + i)? as u32)))?;
+        len += size;
+
+        match c {
+            'r' => {
+                let target = pc.wrapping_add(len as u16).wrapping_add((data as i8) as u16);
+                text.push_str(&format!("${:04X}", target));
+            },
+            'w' => {
+                let target = pc.wrapping_add(len as u16).wrapping_add(data as u16);
+                text.push_str(&format!("${:04X}", target));
+            },
+            'i' | 'm' | 'x' => text.push_str(&format!("#${:0width$X}", data, width = size * 2)),
+            _ => text.push_str(&format!("${:0width$X}", data, width = size * 2)),
+        }
+    }
+
+    // Block moves store the destination bank first.
+    if opcode == 0x44 || opcode == 0x54 {
+        text = format!("{} ${:02X},${:02X}", &template[..3], bytes[2], bytes[1]);
+    }
+
+    Some((text, len))
+}
@@ -0,0 +1,260 @@
+// Names and bit fields of the memory-mapped I/O registers.
+
+/// A memory-mapped I/O register.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct IORegister {
+    pub addr:   u16,
+    pub name:   &'static str,
+    /// Bit fields as (mask, description), from the high bits down.
+    /// Empty if the whole byte is a single value.
+    pub fields: &'static [(u8, &'static str)],
+}
+
+impl IORegister {
+    /// Describe a value written to or read from the register, field by field.
+    /// For example, $8F in INIDISP is `INIDISP: Force blank=1 Brightness=F`.
+    pub fn describe(&self, value: u8) -> String {
+        if self.fields.is_empty() {
+            return format!("{}: {:02X}", self.name, value);
+        }
+        let fields = self.fields.iter()
+            .map(|(mask, desc)| format!("{}={:X}", desc, (value & mask) >> mask.trailing_zeros()))
+            .collect::<Vec<_>>();
+        format!("{}: {}", self.name, fields.join(" "))
+    }
+}
+
+// Sorted by address.
+const REGISTERS: &[IORegister] = &[
+    // PPU
+    IORegister{addr: 0x2100, name: "INIDISP", fields: &[(0x80, "Force blank"), (0x0F, "Brightness")]},
+    IORegister{addr: 0x2101, name: "OBSEL", fields: &[(0xE0, "Object sizes"), (0x18, "Second name table offset"), (0x07, "Name base address")]},
+    IORegister{addr: 0x2102, name: "OAMADDL", fields: &[(0xFF, "OAM word address low")]},
+    IORegister{addr: 0x2103, name: "OAMADDH", fields: &[(0x80, "Priority rotation"), (0x01, "OAM word address high")]},
+    IORegister{addr: 0x2104, name: "OAMDATA", fields: &[]},
+    IORegister{addr: 0x2105, name: "BGMODE", fields: &[(0xF0, "16x16 tiles for BG4-1"), (0x08, "BG3 priority in mode 1"), (0x07, "Mode")]},
+    IORegister{addr: 0x2106, name: "MOSAIC", fields: &[(0xF0, "Mosaic size"), (0x0F, "Enable for BG4-1")]},
+    IORegister{addr: 0x2107, name: "BG1SC", fields: &[(0xFC, "Map address"), (0x02, "Vertical map size"), (0x01, "Horizontal map size")]},
+    IORegister{addr: 0x2108, name: "BG2SC", fields: &[(0xFC, "Map address"), (0x02, "Vertical map size"), (0x01, "Horizontal map size")]},
+    IORegister{addr: 0x2109, name: "BG3SC", fields: &[(0xFC, "Map address"), (0x02, "Vertical map size"), (0x01, "Horizontal map size")]},
+    IORegister{addr: 0x210A, name: "BG4SC", fields: &[(0xFC, "Map address"), (0x02, "Vertical map size"), (0x01, "Horizontal map size")]},
+    IORegister{addr: 0x210B, name: "BG12NBA", fields: &[(0xF0, "BG2 tile address"), (0x0F, "BG1 tile address")]},
+    IORegister{addr: 0x210C, name: "BG34NBA", fields: &[(0xF0, "BG4 tile address"), (0x0F, "BG3 tile address")]},
+    IORegister{addr: 0x210D, name: "BG1HOFS", fields: &[]},
+    IORegister{addr: 0x210E, name: "BG1VOFS", fields: &[]},
+    IORegister{addr: 0x210F, name: "BG2HOFS", fields: &[]},
+    IORegister{addr: 0x2110, name: "BG2VOFS", fields: &[]},
+    IORegister{addr: 0x2111, name: "BG3HOFS", fields: &[]},
+    IORegister{addr: 0x2112, name: "BG3VOFS", fields: &[]},
+    IORegister{addr: 0x2113, name: "BG4HOFS", fields: &[]},
+    IORegister{addr: 0x2114, name: "BG4VOFS", fields: &[]},
+    IORegister{addr: 0x2115, name: "VMAIN", fields: &[(0x80, "Increment after high byte"), (0x0C, "Address remapping"), (0x03, "Increment step")]},
+    IORegister{addr: 0x2116, name: "VMADDL", fields: &[]},
+    IORegister{addr: 0x2117, name: "VMADDH", fields: &[]},
+    IORegister{addr: 0x2118, name: "VMDATAL", fields: &[]},
+    IORegister{addr: 0x2119, name: "VMDATAH", fields: &[]},
+    IORegister{addr: 0x211A, name: "M7SEL", fields: &[(0xC0, "Screen over"), (0x02, "Vertical flip"), (0x01, "Horizontal flip")]},
+    IORegister{addr: 0x211B, name: "M7A", fields: &[]},
+    IORegister{addr: 0x211C, name: "M7B", fields: &[]},
+    IORegister{addr: 0x211D, name: "M7C", fields: &[]},
+    IORegister{addr: 0x211E, name: "M7D", fields: &[]},
+    IORegister{addr: 0x211F, name: "M7X", fields: &[]},
+    IORegister{addr: 0x2120, name: "M7Y", fields: &[]},
+    IORegister{addr: 0x2121, name: "CGADD", fields: &[]},
+    IORegister{addr: 0x2122, name: "CGDATA", fields: &[]},
+    IORegister{addr: 0x2123, name: "W12SEL", fields: &[(0xF0, "BG2 window enable and invert"), (0x0F, "BG1 window enable and invert")]},
+    IORegister{addr: 0x2124, name: "W34SEL", fields: &[(0xF0, "BG4 window enable and invert"), (0x0F, "BG3 window enable and invert")]},
+    IORegister{addr: 0x2125, name: "WOBJSEL", fields: &[(0xF0, "Colour window enable and invert"), (0x0F, "OBJ window enable and invert")]},
+    IORegister{addr: 0x2126, name: "WH0", fields: &[]},
+    IORegister{addr: 0x2127, name: "WH1", fields: &[]},
+    IORegister{addr: 0x2128, name: "WH2", fields: &[]},
+    IORegister{addr: 0x2129, name: "WH3", fields: &[]},
+    IORegister{addr: 0x212A, name: "WBGLOG", fields: &[(0xC0, "BG4 window logic"), (0x30, "BG3 window logic"), (0x0C, "BG2 window logic"), (0x03, "BG1 window logic")]},
+    IORegister{addr: 0x212B, name: "WOBJLOG", fields: &[(0x0C, "Colour window logic"), (0x03, "OBJ window logic")]},
+    IORegister{addr: 0x212C, name: "TM", fields: &[(0x10, "OBJ"), (0x08, "BG4"), (0x04, "BG3"), (0x02, "BG2"), (0x01, "BG1")]},
+    IORegister{addr: 0x212D, name: "TS", fields: &[(0x10, "OBJ"), (0x08, "BG4"), (0x04, "BG3"), (0x02, "BG2"), (0x01, "BG1")]},
+    IORegister{addr: 0x212E, name: "TMW", fields: &[(0x10, "OBJ"), (0x08, "BG4"), (0x04, "BG3"), (0x02, "BG2"), (0x01, "BG1")]},
+    IORegister{addr: 0x212F, name: "TSW", fields: &[(0x10, "OBJ"), (0x08, "BG4"), (0x04, "BG3"), (0x02, "BG2"), (0x01, "BG1")]},
+    IORegister{addr: 0x2130, name: "CGWSEL", fields: &[(0xC0, "Force main screen black"), (0x30, "Prevent colour math"), (0x02, "Add sub screen"), (0x01, "Direct colour")]},
+    IORegister{addr: 0x2131, name: "CGADSUB", fields: &[(0x80, "Subtract"), (0x40, "Half"), (0x20, "Backdrop"), (0x10, "OBJ"), (0x08, "BG4"), (0x04, "BG3"), (0x02, "BG2"), (0x01, "BG1")]},
+    IORegister{addr: 0x2132, name: "COLDATA", fields: &[(0x80, "Blue"), (0x40, "Green"), (0x20, "Red"), (0x1F, "Intensity")]},
+    IORegister{addr: 0x2133, name: "SETINI", fields: &[(0x80, "External sync"), (0x40, "EXTBG"), (0x08, "Pseudo hi-res"), (0x04, "Overscan"), (0x02, "OBJ interlace"), (0x01, "Screen interlace")]},
+    IORegister{addr: 0x2134, name: "MPYL", fields: &[]},
+    IORegister{addr: 0x2135, name: "MPYM", fields: &[]},
+    IORegister{addr: 0x2136, name: "MPYH", fields: &[]},
+    IORegister{addr: 0x2137, name: "SLHV", fields: &[]},
+    IORegister{addr: 0x2138, name: "RDOAM", fields: &[]},
+    IORegister{addr: 0x2139, name: "RDVRAML", fields: &[]},
+    IORegister{addr: 0x213A, name: "RDVRAMH", fields: &[]},
+    IORegister{addr: 0x213B, name: "RDCGRAM", fields: &[]},
+    IORegister{addr: 0x213C, name: "OPHCT", fields: &[]},
+    IORegister{addr: 0x213D, name: "OPVCT", fields: &[]},
+    IORegister{addr: 0x213E, name: "STAT77", fields: &[(0x80, "Time over"), (0x40, "Range over"), (0x20, "Master/slave"), (0x0F, "PPU1 version")]},
+    IORegister{addr: 0x213F, name: "STAT78", fields: &[(0x80, "Interlace field"), (0x40, "Counter latched"), (0x10, "PAL"), (0x0F, "PPU2 version")]},
+    // APU
+    IORegister{addr: 0x2140, name: "APUIO0", fields: &[]},
+    IORegister{addr: 0x2141, name: "APUIO1", fields: &[]},
+    IORegister{addr: 0x2142, name: "APUIO2", fields: &[]},
+    IORegister{addr: 0x2143, name: "APUIO3", fields: &[]},
+    // WRAM
+    IORegister{addr: 0x2180, name: "WMDATA", fields: &[]},
+    IORegister{addr: 0x2181, name: "WMADDL", fields: &[]},
+    IORegister{addr: 0x2182, name: "WMADDM", fields: &[]},
+    IORegister{addr: 0x2183, name: "WMADDH", fields: &[]},
+    // Joypads
+    IORegister{addr: 0x4016, name: "JOYSER0", fields: &[(0x01, "Latch (write) / port 1 data (read)")]},
+    IORegister{addr: 0x4017, name: "JOYSER1", fields: &[(0x1C, "Always set"), (0x03, "Port 2 data")]},
+    // CPU
+    IORegister{addr: 0x4200, name: "NMITIMEN", fields: &[(0x80, "NMI enable"), (0x20, "V-IRQ enable"), (0x10, "H-IRQ enable"), (0x01, "Auto joypad read")]},
+    IORegister{addr: 0x4201, name: "WRIO", fields: &[]},
+    IORegister{addr: 0x4202, name: "WRMPYA", fields: &[]},
+    IORegister{addr: 0x4203, name: "WRMPYB", fields: &[]},
+    IORegister{addr: 0x4204, name: "WRDIVL", fields: &[]},
+    IORegister{addr: 0x4205, name: "WRDIVH", fields: &[]},
+    IORegister{addr: 0x4206, name: "WRDIVB", fields: &[]},
+    IORegister{addr: 0x4207, name: "HTIMEL", fields: &[]},
+    IORegister{addr: 0x4208, name: "HTIMEH", fields: &[(0x01, "H timer high bit")]},
+    IORegister{addr: 0x4209, name: "VTIMEL", fields: &[]},
+    IORegister{addr: 0x420A, name: "VTIMEH", fields: &[(0x01, "V timer high bit")]},
+    IORegister{addr: 0x420B, name: "MDMAEN", fields: &[(0xFF, "Start channels 7-0")]},
+    IORegister{addr: 0x420C, name: "HDMAEN", fields: &[(0xFF, "Enable channels 7-0")]},
+    IORegister{addr: 0x420D, name: "MEMSEL", fields: &[(0x01, "Fast ROM")]},
+    IORegister{addr: 0x4210, name: "RDNMI", fields: &[(0x80, "NMI flag"), (0x0F, "CPU version")]},
+    IORegister{addr: 0x4211, name: "TIMEUP", fields: &[(0x80, "IRQ flag")]},
+    IORegister{addr: 0x4212, name: "HVBJOY", fields: &[(0x80, "V-blank"), (0x40, "H-blank"), (0x01, "Auto joypad read busy")]},
+    IORegister{addr: 0x4213, name: "RDIO", fields: &[]},
+    IORegister{addr: 0x4214, name: "RDDIVL", fields: &[]},
+    IORegister{addr: 0x4215, name: "RDDIVH", fields: &[]},
+    IORegister{addr: 0x4216, name: "RDMPYL", fields: &[]},
+    IORegister{addr: 0x4217, name: "RDMPYH", fields: &[]},
+    IORegister{addr: 0x4218, name: "JOY1L", fields: &[]},
+    IORegister{addr: 0x4219, name: "JOY1H", fields: &[]},
+    IORegister{addr: 0x421A, name: "JOY2L", fields: &[]},
+    IORegister{addr: 0x421B, name: "JOY2H", fields: &[]},
+    IORegister{addr: 0x421C, name: "JOY3L", fields: &[]},
+    IORegister{addr: 0x421D, name: "JOY3H", fields: &[]},
+    IORegister{addr: 0x421E, name: "JOY4L", fields: &[]},
+    IORegister{addr: 0x421F, name: "JOY4H", fields: &[]},
+    // DMA
+    IORegister{addr: 0x4300, name: "DMAP0", fields: &[(0x80, "B to A"), (0x40, "HDMA indirect"), (0x18, "A address step"), (0x07, "Transfer pattern")]},
+    IORegister{addr: 0x4301, name: "BBAD0", fields: &[]},
+    IORegister{addr: 0x4302, name: "A1T0L", fields: &[]},
+    IORegister{addr: 0x4303, name: "A1T0H", fields: &[]},
+    IORegister{addr: 0x4304, name: "A1B0", fields: &[]},
+    IORegister{addr: 0x4305, name: "DAS0L", fields: &[]},
+    IORegister{addr: 0x4306, name: "DAS0H", fields: &[]},
+    IORegister{addr: 0x4307, name: "DASB0", fields: &[]},
+    IORegister{addr: 0x4308, name: "A2A0L", fields: &[]},
+    IORegister{addr: 0x4309, name: "A2A0H", fields: &[]},
+    IORegister{addr: 0x430A, name: "NTRL0", fields: &[]},
+    IORegister{addr: 0x430B, name: "UNUSED0", fields: &[]},
+    IORegister{addr: 0x430F, name: "MIRR0", fields: &[]},
+    IORegister{addr: 0x4310, name: "DMAP1", fields: &[(0x80, "B to A"), (0x40, "HDMA indirect"), (0x18, "A address step"), (0x07, "Transfer pattern")]},
+    IORegister{addr: 0x4311, name: "BBAD1", fields: &[]},
+    IORegister{addr: 0x4312, name: "A1T1L", fields: &[]},
+    IORegister{addr: 0x4313, name: "A1T1H", fields: &[]},
+    IORegister{addr: 0x4314, name: "A1B1", fields: &[]},
+    IORegister{addr: 0x4315, name: "DAS1L", fields: &[]},
+    IORegister{addr: 0x4316, name: "DAS1H", fields: &[]},
+    IORegister{addr: 0x4317, name: "DASB1", fields: &[]},
+    IORegister{addr: 0x4318, name: "A2A1L", fields: &[]},
+    IORegister{addr: 0x4319, name: "A2A1H", fields: &[]},
+    IORegister{addr: 0x431A, name: "NTRL1", fields: &[]},
+    IORegister{addr: 0x431B, name: "UNUSED1", fields: &[]},
+    IORegister{addr: 0x431F, name: "MIRR1", fields: &[]},
+    IORegister{addr: 0x4320, name: "DMAP2", fields: &[(0x80, "B to A"), (0x40, "HDMA indirect"), (0x18, "A address step"), (0x07, "Transfer pattern")]},
+    IORegister{addr: 0x4321, name: "BBAD2", fields: &[]},
+    IORegister{addr: 0x4322, name: "A1T2L", fields: &[]},
+    IORegister{addr: 0x4323, name: "A1T2H", fields: &[]},
+    IORegister{addr: 0x4324, name: "A1B2", fields: &[]},
+    IORegister{addr: 0x4325, name: "DAS2L", fields: &[]},
+    IORegister{addr: 0x4326, name: "DAS2H", fields: &[]},
+    IORegister{addr: 0x4327, name: "DASB2", fields: &[]},
+    IORegister{addr: 0x4328, name: "A2A2L", fields: &[]},
+    IORegister{addr: 0x4329, name: "A2A2H", fields: &[]},
+    IORegister{addr: 0x432A, name: "NTRL2", fields: &[]},
+    IORegister{addr: 0x432B, name: "UNUSED2", fields: &[]},
+    IORegister{addr: 0x432F, name: "MIRR2", fields: &[]},
+    IORegister{addr: 0x4330, name: "DMAP3", fields: &[(0x80, "B to A"), (0x40, "HDMA indirect"), (0x18, "A address step"), (0x07, "Transfer pattern")]},
+    IORegister{addr: 0x4331, name: "BBAD3", fields: &[]},
+    IORegister{addr: 0x4332, name: "A1T3L", fields: &[]},
+    IORegister{addr: 0x4333, name: "A1T3H", fields: &[]},
+    IORegister{addr: 0x4334, name: "A1B3", fields: &[]},
+    IORegister{addr: 0x4335, name: "DAS3L", fields: &[]},
+    IORegister{addr: 0x4336, name: "DAS3H", fields: &[]},
+    IORegister{addr: 0x4337, name: "DASB3", fields: &[]},
+    IORegister{addr: 0x4338, name: "A2A3L", fields: &[]},
+    IORegister{addr: 0x4339, name: "A2A3H", fields: &[]},
+    IORegister{addr: 0x433A, name: "NTRL3", fields: &[]},
+    IORegister{addr: 0x433B, name: "UNUSED3", fields: &[]},
+    IORegister{addr: 0x433F, name: "MIRR3", fields: &[]},
+    IORegister{addr: 0x4340, name: "DMAP4", fields: &[(0x80, "B to A"), (0x40, "HDMA indirect"), (0x18, "A address step"), (0x07, "Transfer pattern")]},
+    IORegister{addr: 0x4341, name: "BBAD4", fields: &[]},
+    IORegister{addr: 0x4342, name: "A1T4L", fields: &[]},
+    IORegister{addr: 0x4343, name: "A1T4H", fields: &[]},
+    IORegister{addr: 0x4344, name: "A1B4", fields: &[]},
+    IORegister{addr: 0x4345, name: "DAS4L", fields: &[]},
+    IORegister{addr: 0x4346, name: "DAS4H", fields: &[]},
+    IORegister{addr: 0x4347, name: "DASB4", fields: &[]},
+    IORegister{addr: 0x4348, name: "A2A4L", fields: &[]},
+    IORegister{addr: 0x4349, name: "A2A4H", fields: &[]},
+    IORegister{addr: 0x434A, name: "NTRL4", fields: &[]},
+    IORegister{addr: 0x434B, name: "UNUSED4", fields: &[]},
+    IORegister{addr: 0x434F, name: "MIRR4", fields: &[]},
+    IORegister{addr: 0x4350, name: "DMAP5", fields: &[(0x80, "B to A"), (0x40, "HDMA indirect"), (0x18, "A address step"), (0x07, "Transfer pattern")]},
+    IORegister{addr: 0x4351, name: "BBAD5", fields: &[]},
+    IORegister{addr: 0x4352, name: "A1T5L", fields: &[]},
+    IORegister{addr: 0x4353, name: "A1T5H", fields: &[]},
+    IORegister{addr: 0x4354, name: "A1B5", fields: &[]},
+    IORegister{addr: 0x4355, name: "DAS5L", fields: &[]},
+    IORegister{addr: 0x4356, name: "DAS5H", fields: &[]},
+    IORegister{addr: 0x4357, name: "DASB5", fields: &[]},
+    IORegister{addr: 0x4358, name: "A2A5L", fields: &[]},
+    IORegister{addr: 0x4359, name: "A2A5H", fields: &[]},
+    IORegister{addr: 0x435A, name: "NTRL5", fields: &[]},
+    IORegister{addr: 0x435B, name: "UNUSED5", fields: &[]},
+    IORegister{addr: 0x435F, name: "MIRR5", fields: &[]},
+    IORegister{addr: 0x4360, name: "DMAP6", fields: &[(0x80, "B to A"), (0x40, "HDMA indirect"), (0x18, "A address step"), (0x07, "Transfer pattern")]},
+    IORegister{addr: 0x4361, name: "BBAD6", fields: &[]},
+    IORegister{addr: 0x4362, name: "A1T6L", fields: &[]},
+    IORegister{addr: 0x4363, name: "A1T6H", fields: &[]},
+    IORegister{addr: 0x4364, name: "A1B6", fields: &[]},
+    IORegister{addr: 0x4365, name: "DAS6L", fields: &[]},
+    IORegister{addr: 0x4366, name: "DAS6H", fields: &[]},
+    IORegister{addr: 0x4367, name: "DASB6", fields: &[]},
+    IORegister{addr: 0x4368, name: "A2A6L", fields: &[]},
+    IORegister{addr: 0x4369, name: "A2A6H", fields: &[]},
+    IORegister{addr: 0x436A, name: "NTRL6", fields: &[]},
+    IORegister{addr: 0x436B, name: "UNUSED6", fields: &[]},
+    IORegister{addr: 0x436F, name: "MIRR6", fields: &[]},
+    IORegister{addr: 0x4370, name: "DMAP7", fields: &[(0x80, "B to A"), (0x40, "HDMA indirect"), (0x18, "A address step"), (0x07, "Transfer pattern")]},
+    IORegister{addr: 0x4371, name: "BBAD7", fields: &[]},
+    IORegister{addr: 0x4372, name: "A1T7L", fields: &[]},
+    IORegister{addr: 0x4373, name: "A1T7H", fields: &[]},
+    IORegister{addr: 0x4374, name: "A1B7", fields: &[]},
+    IORegister{addr: 0x4375, name: "DAS7L", fields: &[]},
+    IORegister{addr: 0x4376, name: "DAS7H", fields: &[]},
+    IORegister{addr: 0x4377, name: "DASB7", fields: &[]},
+    IORegister{addr: 0x4378, name: "A2A7L", fields: &[]},
+    IORegister{addr: 0x4379, name: "A2A7H", fields: &[]},
+    IORegister{addr: 0x437A, name: "NTRL7", fields: &[]},
+    IORegister{addr: 0x437B, name: "UNUSED7", fields: &[]},
+    IORegister{addr: 0x437F, name: "MIRR7", fields: &[]},
+];
+
+/// Find the I/O register at an address.
+/// Registers are mirrored in banks $00-$3F and $80-$BF.
+pub fn io_register(addr: u32) -> Option<&'static IORegister> {
+    match hi24!(addr) {
+        0x00..=0x3F | 0x80..=0xBF => {
+            let addr = lo24!(addr);
+            REGISTERS.binary_search_by_key(&addr, |r| r.addr).ok().map(|i| &REGISTERS[i])
+        },
+        _ => None,
+    }
+}
+
+/// Get the name of the I/O register at an address, for example `io_register_name(0x2100) == Some("INIDISP")`.
+pub fn io_register_name(addr: u32) -> Option<&'static str> {
+    io_register(addr).map(|r| r.name)
+}
@@ -0,0 +1,433 @@
+// For stepping through the CPU.
+
+mod cpu;
+mod io;
+mod spc;
+mod superfx;
+mod symbols;
+mod trace;
+
+pub use cpu::disassemble_cpu;
+pub use io::{
+    IORegister,
+    io_register,
+    io_register_name
+};
+pub use spc::disassemble_spc;
+pub use superfx::disassemble_superfx;
+pub use symbols::SymbolTable;
+pub use trace::{
+    Divergence,
+    TraceEntry,
+    compare_trace,
+    trace_line
+};
+
+use crate::SNES;
+
+// Capture of CPU internal state.
+#[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct CPUState {
+    // Registers
+    pub a:      u16,    // Accumulator
+    pub x:      u16,    // X-Index
+    pub y:      u16,    // Y-Index
+    pub s:      u16,    // Stack Pointer
+    pub db:     u8,     // Data Bank
+    pub dp:     u16,    // Direct Page
+    pub pb:     u8,     // Program Bank
+    pub p:      u8,     // Processor Status
+    pub pc:     u16,    // Program Counter
+    pub pe:     bool,   // 6502 Emulator Processor Status
+}
+
+impl CPUState {
+    pub fn to_string(&self) -> String {
+        format!("a: ${:04X} x: ${:04X} y: ${:04X} sp: ${:04X} db: ${:02X} dp: ${:04X}\n\
+                pb: ${:02X} pc: ${:04X}\n\
+                nvmxdizc: {:08b} e: {}",
+                self.a, self.x, self.y, self.s, self.db, self.dp,
+                self.pb, self.pc,
+                self.p, self.pe)
+    }
+}
+
+/// Cycles spent on each CPU instruction, for profiling.
+/// Cycles are master cycles, and include memory access time but not DMA.
+#[derive(Clone)]
+pub struct CycleProfile {
+    pub last_opcode:    u8,
+    pub last_cycles:    usize,      // Cycles taken by the last instruction.
+    pub counts:         [u64; 256], // Number of times each opcode has run.
+    pub cycles:         [u64; 256], // Total cycles spent on each opcode.
+}
+
+impl CycleProfile {
+    pub fn new() -> Self {
+        CycleProfile {
+            last_opcode:    0,
+            last_cycles:    0,
+            counts:         [0; 256],
+            cycles:         [0; 256],
+        }
+    }
+
+    pub fn record(&mut self, opcode: u8, cycles: usize) {
+        self.last_opcode = opcode;
+        self.last_cycles = cycles;
+        self.counts[opcode as usize] += 1;
+        self.cycles[opcode as usize] += cycles as u64;
+    }
+
+    /// Opcodes that have run, as (opcode, count, total cycles), with the most expensive first.
+    pub fn by_total_cycles(&self) -> Vec<(u8, u64, u64)> {
+        let mut opcodes = (0..256).filter(|op| self.counts[*op] > 0)
+            .map(|op| (op as u8, self.counts[op], self.cycles[op]))
+            .collect::<Vec<_>>();
+        opcodes.sort_by_key(|op| std::cmp::Reverse(op.2));
+        opcodes
+    }
+}
+
+impl Default for CycleProfile {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl std::fmt::Display for CycleProfile {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        writeln!(f, "last: ${:02X} ({} cycles)", self.last_opcode, self.last_cycles)?;
+        for (opcode, count, cycles) in self.by_total_cycles() {
+            writeln!(f, "${:02X}: {} runs, {} cycles ({:.1} avg)", opcode, count, cycles, (cycles as f64) / (count as f64))?;
+        }
+        Ok(())
+    }
+}
+
+/// Number of times the DSP clamped a 16-bit sum since power on or the last reset, for each stage of mixing.
+/// Left and right are counted separately. Loud music clamps now and then, but a count that rises every sample
+/// points to a mixing bug.
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct DSPClampStats {
+    pub main_mix:       u64,    // Adding a voice to the main output.
+    pub echo_mix:       u64,    // Adding an echo enabled voice to the echo input.
+    pub fir:            u64,    // The echo FIR filter output.
+    pub echo_feedback:  u64,    // Adding the echo feedback to the echo input.
+    pub output:         u64,    // Adding the echo output to the main output, after master volume.
+}
+
+impl std::fmt::Display for DSPClampStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "main mix: {} echo mix: {} fir: {} echo feedback: {} output: {}",
+            self.main_mix, self.echo_mix, self.fir, self.echo_feedback, self.output)
+    }
+}
+
+/// Capture of the SuperFX instruction cache.
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct FXCacheState {
+    pub cbr:            u16,
+    pub lines:          Vec<Option<[u8; 16]>>,  // 32 lines of 16 bytes. None if the line isn't loaded.
+    pub hits:           usize,  // Fetches from the cache during the last complete frame.
+    pub misses:         usize,  // Fetches that had to load a cache line during the last complete frame.
+    pub frame_hits:     usize,  // Hits so far this frame.
+    pub frame_misses:   usize,  // Misses so far this frame.
+}
+
+impl std::fmt::Display for FXCacheState {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        writeln!(f, "cbr: ${:04X} hits: {} misses: {} (this frame: {} / {})",
+            self.cbr, self.hits, self.misses, self.frame_hits, self.frame_misses)?;
+        for (i, line) in self.lines.iter().enumerate() {
+            let addr = self.cbr.wrapping_add((i * 16) as u16);
+            match line {
+                Some(data) => {
+                    let hex = data.iter().map(|b| format!("{:02X}", b)).collect::<Vec<_>>().join(" ");
+                    writeln!(f, "${:04X}: {}", addr, hex)?;
+                },
+                None => writeln!(f, "${:04X}: --", addr)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// State of a single HDMA channel, captured at the end of a scanline's H-blank transfer.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct HDMAChannelState {
+    pub enabled:        bool,   // Set in $420C.
+    pub completed:      bool,   // Ran this frame, and reached the end of its table.
+    pub line_counter:   u8,     // Lines left in the current table entry.
+    pub repeat:         bool,   // Data is transferred every line of the current entry.
+    pub table_addr:     u32,    // Address of the next byte in the table.
+    pub indirect:       bool,
+    pub indirect_addr:  u32,    // Address of the next indirect data. Only used in indirect mode.
+}
+
+impl std::fmt::Display for HDMAChannelState {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let status = match (self.enabled, self.completed) {
+            (false, _) => "off",
+            (true, false) => "on",
+            (true, true) => "done",
+        };
+        write!(f, "{:4} lines: {:02X}{} table: ${:06X}", status, self.line_counter, if self.repeat {"r"} else {" "}, self.table_addr)?;
+        if self.indirect {
+            write!(f, " indirect: ${:06X}", self.indirect_addr)?;
+        }
+        Ok(())
+    }
+}
+
+/// Registers of a single DMA channel, $43x0-$43xB.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct DMAChannelRegs {
+    pub control:            u8,
+    pub b_bus_addr:         u8,     // Low byte of the B bus address: $21xx.
+    pub a_bus_addr:         u32,    // Including the bank.
+    pub count:              u16,    // Bytes left to transfer. Used as the indirect address for HDMA.
+    pub hdma_bank:          u8,     // Bank of the indirect HDMA data.
+    pub hdma_table_addr:    u16,
+    pub hdma_line_count:    u8,
+    pub unused:             u8,     // $43xB, mirrored at $43xF.
+}
+
+impl std::fmt::Display for DMAChannelRegs {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "ctrl: ${:02X} b: $21{:02X} a: ${:06X} count: ${:04X} hdma bank: ${:02X} table: ${:04X} lines: ${:02X}",
+            self.control, self.b_bus_addr, self.a_bus_addr, self.count,
+            self.hdma_bank, self.hdma_table_addr, self.hdma_line_count)
+    }
+}
+
+/// Objects evaluated for a single scanline.
+/// If the sprite limit is disabled, dropped objects are still drawn.
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct SpriteLineState {
+    pub selected:   Vec<u8>,    // OAM indices of the objects on the line, in OAM order.
+    pub dropped:    Vec<u8>,    // Objects on the line past the 32 sprite limit.
+}
+
+impl std::fmt::Display for SpriteLineState {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{:2} objects:", self.selected.len())?;
+        for i in &self.selected {
+            write!(f, " {:02X}", i)?;
+        }
+        if !self.dropped.is_empty() {
+            write!(f, " dropped:")?;
+            for i in &self.dropped {
+                write!(f, " {:02X}", i)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Decoded graphics: one palette index per pixel, with the palette.
+/// Index 0 is transparent.
+pub struct IndexedImage {
+    pub width:      usize,
+    pub height:     usize,
+    pub pixels:     Vec<u8>,
+    pub palette:    Vec<[u8; 3]>,   // All 256 colours of CGRAM.
+}
+
+impl IndexedImage {
+    /// Encode as an indexed PNG, with index 0 transparent.
+    pub fn to_png(&self) -> Result<Vec<u8>, String> {
+        let mut data = Vec::new();
+        {
+            let mut encoder = png::Encoder::new(&mut data, self.width as u32, self.height as u32);
+            encoder.set_color(png::ColorType::Indexed);
+            encoder.set_depth(png::BitDepth::Eight);
+            encoder.set_palette(self.palette_bytes());
+            encoder.set_trns(vec![0]);
+            let mut writer = encoder.write_header().map_err(|e| e.to_string())?;
+            writer.write_image_data(&self.pixels).map_err(|e| e.to_string())?;
+        }
+        Ok(data)
+    }
+
+    /// The palette as 256 raw RGB triplets (768 bytes), as read by most tile editors.
+    pub fn palette_bytes(&self) -> Vec<u8> {
+        self.palette.iter().flatten().cloned().collect()
+    }
+
+    /// Write the image to a PNG file, and the palette next to it with a .pal extension.
+    pub fn save(&self, png_path: &str) -> Result<(), String> {
+        std::fs::write(png_path, self.to_png()?).map_err(|e| e.to_string())?;
+        let pal_path = std::path::Path::new(png_path).with_extension("pal");
+        std::fs::write(pal_path, self.palette_bytes()).map_err(|e| e.to_string())
+    }
+}
+
+/// A layer that a pixel on screen can come from.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum PixelLayer {
+    BG1,
+    BG2,
+    BG3,
+    BG4,
+    Obj,
+    Backdrop,
+}
+
+/// The frontmost pixel of the main or sub screen.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct LayerPixel {
+    pub layer:          PixelLayer,
+    pub palette_index:  Option<u8>, // CGRAM index. None for direct colour.
+    pub colour:         [u8; 3],    // Before colour math and brightness.
+}
+
+impl std::fmt::Display for LayerPixel {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self.palette_index {
+            Some(index) => write!(f, "{:?} ${:02X}", self.layer, index)?,
+            None => write!(f, "{:?} direct", self.layer)?,
+        }
+        write!(f, " #{:02X}{:02X}{:02X}", self.colour[0], self.colour[1], self.colour[2])
+    }
+}
+
+/// Whether a layer shows through the window on each screen.
+/// False if the layer is disabled for the screen.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct LayerWindow {
+    pub main:   bool,
+    pub sub:    bool,
+}
+
+/// Colour math applied to the main screen pixel.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ColourMath {
+    pub subtract:   bool,
+    pub half:       bool,
+    pub operand:    [u8; 3],    // The sub screen pixel, or the fixed colour.
+}
+
+/// Why a pixel on screen is the colour it is.
+/// The pixel is composited again using the PPU state at the time of the call, so for lines
+/// that the game changes mid-frame (with HDMA for example) it should be called while the line is drawn.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct PixelExplanation {
+    pub mode:           u8,
+    pub hires:          bool,               // Mode 5, 6 or pseudo hi-res: the sub and main screen are shown side by side.
+    pub main:           LayerPixel,
+    pub sub:            Option<LayerPixel>, // None if the fixed colour is used: the sub screen is off or transparent.
+    pub windows:        [LayerWindow; 5],   // BG1-4, then objects.
+    pub colour_window:  bool,               // Inside the colour window.
+    pub clip_to_black:  bool,               // The main colour was clipped to black before colour math.
+    pub colour_math:    Option<ColourMath>, // None if the main layer doesn't take part, or colour math is prevented here.
+    pub colour:         [u8; 3],            // Final colour, after brightness. For hi-res this is the main screen half.
+}
+
+impl std::fmt::Display for PixelExplanation {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        writeln!(f, "mode {}{} colour: #{:02X}{:02X}{:02X}", self.mode, if self.hires {" hi-res"} else {""},
+            self.colour[0], self.colour[1], self.colour[2])?;
+        writeln!(f, "main: {}", self.main)?;
+        match &self.sub {
+            Some(pixel) => writeln!(f, "sub: {}", pixel)?,
+            None => writeln!(f, "sub: fixed colour")?,
+        }
+        for (name, window) in ["BG1", "BG2", "BG3", "BG4", "OBJ"].iter().zip(self.windows.iter()) {
+            writeln!(f, "{}: main {} sub {}", name,
+                if window.main {"shown"} else {"hidden"},
+                if window.sub {"shown"} else {"hidden"})?;
+        }
+        write!(f, "colour window: {} clip to black: {} colour math: ",
+            if self.colour_window {"inside"} else {"outside"}, self.clip_to_black)?;
+        match &self.colour_math {
+            Some(math) => write!(f, "{}{} #{:02X}{:02X}{:02X}",
+                if math.subtract {"subtract"} else {"add"}, if math.half {" half"} else {""},
+                math.operand[0], math.operand[1], math.operand[2]),
+            None => write!(f, "none"),
+        }
+    }
+}
+
+/// Composite a single pixel of the screen again, and find which layer and palette entry it came from,
+/// whether each layer passed the window, and what colour math was applied.
+/// x and y are the position in the frame, not including any widescreen margin.
+/// Returns None if the position is off screen.
+/// Debug overrides such as set_bg_priority_override aren't applied, and the sprite limit is always on.
+pub fn explain_pixel(snes: &SNES, x: usize, y: usize) -> Option<PixelExplanation> {
+    snes.explain_pixel(x, y)
+}
+
+/// A condition for run_until to stop at.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Condition {
+    /// The CPU is about to run the instruction at this address (program bank and PC).
+    PC(u32),
+    /// The byte at this address has this value.
+    /// Memory is read through the bus, so watching I/O registers can affect them.
+    Mem {
+        addr:   u32,
+        value:  u8,
+    },
+    /// The PPU is on this scanline.
+    Scanline(u16),
+}
+
+impl Condition {
+    fn is_met(&self, snes: &mut SNES) -> bool {
+        match self {
+            Condition::PC(addr) => {
+                let state = snes.get_state();
+                make24!(state.pb, state.pc) == *addr
+            },
+            Condition::Mem{addr, value} => snes.get_mem_at(*addr) == *value,
+            Condition::Scanline(line) => snes.get_hv_counter().1 == *line,
+        }
+    }
+}
+
+/// Step the SNES until the condition is met, for at most max_steps instructions.
+/// Returns the number of steps taken, or None if the condition wasn't met in time.
+/// The condition is checked before each step, so if it's already met no steps are taken.
+///
+/// For example, to run a ROM until it reaches its NMI handler at $008000:
+/// `run_until(&mut snes, Condition::PC(0x008000), 1_000_000)`
+pub fn run_until(snes: &mut SNES, condition: Condition, max_steps: usize) -> Option<usize> {
+    snes.start_frame();
+    for steps in 0..max_steps {
+        if condition.is_met(snes) {
+            return Some(steps);
+        }
+        if snes.step() {
+            snes.start_frame();
+        }
+    }
+    if condition.is_met(snes) {
+        Some(max_steps)
+    } else {
+        None
+    }
+}
+
+/// Format memory as a hexdump, with 16 bytes per line.
+/// `start` is the address of the first byte.
+pub fn hexdump(start: u16, data: &[u8]) -> String {
+    let mut out = String::new();
+    for (i, line) in data.chunks(16).enumerate() {
+        let addr = start.wrapping_add((i * 16) as u16);
+        let hex = line.iter().map(|b| format!("{:02X}", b)).collect::<Vec<_>>().join(" ");
+        let ascii = line.iter().map(|b| if b.is_ascii_graphic() {*b as char} else {'.'}).collect::<String>();
+        out.push_str(&format!("${:04X}: {:<47}  {}\n", addr, hex, ascii));
+    }
+    out
+}
@@ -0,0 +1,91 @@
+// Disassembler for the SPC-700.
+
+// Each opcode's text. Lowercase letters are placeholders for operands:
+// d: direct page, a: absolute, i: immediate, r: relative branch, m: memory bit, u: upper page.
+const OPCODES: [&str; 256] = [
+    "NOP",          "TCALL 0",      "SET1 d.0",     "BBS d.0, r",   "OR A, d",      "OR A, a",      "OR A, (X)",    "OR A, [d+X]",
+    "OR A, i",      "OR d, d",      "OR1 C, m",     "ASL d",        "ASL a",        "PUSH PSW",     "TSET1 a",      "BRK",
+    "BPL r",        "TCALL 1",      "CLR1 d.0",     "BBC d.0, r",   "OR A, d+X",    "OR A, a+X",    "OR A, a+Y",    "OR A, [d]+Y",
+    "OR d, i",      "OR (X), (Y)",  "DECW d",       "ASL d+X",      "ASL A",        "DEC X",        "CMP X, a",     "JMP [a+X]",
+    "CLRP",         "TCALL 2",      "SET1 d.1",     "BBS d.1, r",   "AND A, d",     "AND A, a",     "AND A, (X)",   "AND A, [d+X]",
+    "AND A, i",     "AND d, d",     "OR1 C, /m",    "ROL d",        "ROL a",        "PUSH A",       "CBNE d, r",    "BRA r",
+    "BMI r",        "TCALL 3",      "CLR1 d.1",     "BBC d.1, r",   "AND A, d+X",   "AND A, a+X",   "AND A, a+Y",   "AND A, [d]+Y",
+    "AND d, i",     "AND (X), (Y)", "INCW d",       "ROL d+X",      "ROL A",        "INC X",        "CMP X, d",     "CALL a",
+    "SETP",         "TCALL 4",      "SET1 d.2",     "BBS d.2, r",   "EOR A, d",     "EOR A, a",     "EOR A, (X)",   "EOR A, [d+X]",
+    "EOR A, i",     "EOR d, d",     "AND1 C, m",    "LSR d",        "LSR a",        "PUSH X",       "TCLR1 a",      "PCALL u",
+    "BVC r",        "TCALL 5",      "CLR1 d.2",     "BBC d.2, r",   "EOR A, d+X",   "EOR A, a+X",   "EOR A, a+Y",   "EOR A, [d]+Y",
+    "EOR d, i",     "EOR (X), (Y)", "CMPW YA, d",   "LSR d+X",      "LSR A",        "MOV X, A",     "CMP Y, a",     "JMP a",
+    "CLRC",         "TCALL 6",      "SET1 d.3",     "BBS d.3, r",   "CMP A, d",     "CMP A, a",     "CMP A, (X)",   "CMP A, [d+X]",
+    "CMP A, i",     "CMP d, d",     "AND1 C, /m",   "ROR d",        "ROR a",        "PUSH Y",       "DBNZ d, r",    "RET",
+    "BVS r",        "TCALL 7",      "CLR1 d.3",     "BBC d.3, r",   "CMP A, d+X",   "CMP A, a+X",   "CMP A, a+Y",   "CMP A, [d]+Y",
+    "CMP d, i",     "CMP (X), (Y)", "ADDW YA, d",   "ROR d+X",      "ROR A",        "MOV A, X",     "CMP Y, d",     "RETI",
+    "SETC",         "TCALL 8",      "SET1 d.4",     "BBS d.4, r",   "ADC A, d",     "ADC A, a",     "ADC A, (X)",   "ADC A, [d+X]",
+    "ADC A, i",     "ADC d, d",     "EOR1 C, m",    "DEC d",        "DEC a",        "MOV Y, i",     "POP PSW",      "MOV d, i",
+    "BCC r",        "TCALL 9",      "CLR1 d.4",     "BBC d.4, r",   "ADC A, d+X",   "ADC A, a+X",   "ADC A, a+Y",   "ADC A, [d]+Y",
+    "ADC d, i",     "ADC (X), (Y)", "SUBW YA, d",   "DEC d+X",      "DEC A",        "MOV X, SP",    "DIV YA, X",    "XCN A",
+    "EI",           "TCALL 10",     "SET1 d.5",     "BBS d.5, r",   "SBC A, d",     "SBC A, a",     "SBC A, (X)",   "SBC A, [d+X]",
+    "SBC A, i",     "SBC d, d",     "MOV1 C, m",    "INC d",        "INC a",        "CMP Y, i",     "POP A",        "MOV (X)+, A",
+    "BCS r",        "TCALL 11",     "CLR1 d.5",     "BBC d.5, r",   "SBC A, d+X",   "SBC A, a+X",   "SBC A, a+Y",   "SBC A, [d]+Y",
+    "SBC d, i",     "SBC (X), (Y)", "MOVW YA, d",   "INC d+X",      "INC A",        "MOV SP, X",    "DAS A",        "MOV A, (X)+",
+    "DI",           "TCALL 12",     "SET1 d.6",     "BBS d.6, r",   "MOV d, A",     "MOV a, A",     "MOV (X), A",   "MOV [d+X], A",
+    "CMP X, i",     "MOV a, X",     "MOV1 m, C",    "MOV d, Y",     "MOV a, Y",     "MOV X, i",     "POP X",        "MUL YA",
+    "BNE r",        "TCALL 13",     "CLR1 d.6",     "BBC d.6, r",   "MOV d+X, A",   "MOV a+X, A",   "MOV a+Y, A",   "MOV [d]+Y, A",
+    "MOV d, X",     "MOV d+Y, X",   "MOVW d, YA",   "MOV d+X, Y",   "DEC Y",        "MOV A, Y",     "CBNE d+X, r",  "DAA A",
+    "CLRV",         "TCALL 14",     "SET1 d.7",     "BBS d.7, r",   "MOV A, d",     "MOV A, a",     "MOV A, (X)",   "MOV A, [d+X]",
+    "MOV A, i",     "MOV X, a",     "NOT1 m",       "MOV Y, d",     "MOV Y, a",     "NOTC",         "POP Y",        "SLEEP",
+    "BEQ r",        "TCALL 15",     "CLR1 d.7",     "BBC d.7, r",   "MOV A, d+X",   "MOV A, a+X",   "MOV A, a+Y",   "MOV A, [d]+Y",
+    "MOV X, d",     "MOV X, d+Y",   "MOV d, d",     "MOV Y, d+X",   "INC Y",        "MOV Y, A",     "DBNZ Y, r",    "STOP",
+];
+
+/// Disassemble a single SPC-700 instruction at the start of `bytes`.
+/// `pc` is the address of the first byte, used to calculate branch targets.
+///
+/// Returns the instruction text and the number of bytes used, or None if
+/// `bytes` ends before the instruction does.
+pub fn disassemble_spc(pc: u16, bytes: &[u8]) -> Option<(String, usize)> {
+    let opcode = *bytes.first()?;
+    let template = OPCODES[opcode as usize];
+
+    // Read the operands in the order they are stored.
+    let mut len = 1;
+    let mut operands = Vec::new();
+    for c in template.chars().filter(|c| c.is_ascii_lowercase()) {
+        let size = if c == 'a' || c == 'm' {2} else {1};
+        let data = match size {
+            1 => *bytes.get(len)? as u16,
+            _ => make16!(*bytes.get(len + 1)?, *bytes.get(len)?),
+        };
+        operands.push(data);
+        len += size;
+    }
+
+    // Memory to memory and immediate to memory instructions store the destination last.
+    if (opcode & 0x1F == 0x09) || (opcode & 0x1F == 0x18) || (opcode == 0x8F) || (opcode == 0xFA) {
+        operands.reverse();
+    }
+
+    let mut text = String::new();
+    let mut operands = operands.into_iter();
+    for c in template.chars() {
+        if !c.is_ascii_lowercase() {
+            text.push(c);
+            continue;
+        }
+
+        let data = operands.next().unwrap();
+        match c {
+            'd' => text.push_str(&format!("${:02X}", data)),
+            'a' => text.push_str(&format!("!${:04X}", data)),
+            'i' => text.push_str(&format!("#${:02X}", data)),
+            'r' => {
+                let target = pc.wrapping_add(len as u16).wrapping_add((data as i8) as u16);
+                text.push_str(&format!("${:04X}", target));
+            },
+            'm' => text.push_str(&format!("${:04X}.{}", data & 0x1FFF, data >> 13)),
+            'u' => text.push_str(&format!("$FF{:02X}", data)),
+            _ => unreachable!(),
+        }
+    }
+
+    Some((text, len))
+}
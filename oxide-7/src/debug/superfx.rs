@@ -0,0 +1,133 @@
+// Disassembler for the SuperFX (GSU).
+
+const BRANCHES: [&str; 11] = ["BRA", "BGE", "BLT", "BNE", "BEQ", "BPL", "BMI", "BCC", "BCS", "BVC", "BVS"];
+
+/// Disassemble a single SuperFX instruction at the start of `bytes`.
+/// `pc` is the address of the first byte, used to calculate branch targets.
+///
+/// ALT1/ALT2/ALT3 prefixes are decoded into the full mnemonic (e.g. `3D 40` is `LDB (R0)`),
+/// and WITH followed by TO or FROM is decoded as MOVE or MOVES.
+///
+/// Returns the instruction text and the number of bytes used, or None if
+/// `bytes` ends before the instruction does.
+pub fn disassemble_superfx(pc: u16, bytes: &[u8]) -> Option<(String, usize)> {
+    let mut alt: usize = 0;
+    let mut i = 0;
+
+    // Prefixes.
+    let instr = loop {
+        let instr = *bytes.get(i)?;
+        i += 1;
+        match instr {
+            0x3D => alt = 1,
+            0x3E => alt = 2,
+            0x3F => alt = 3,
+            0x20..=0x2F => {
+                // WITH is only folded into the next instruction if it is a move.
+                let with = lo_nybble!(instr);
+                match bytes.get(i) {
+                    Some(next @ 0x10..=0x1F) => return Some((format!("MOVE R{}, R{}", lo_nybble!(next), with), i + 1)),
+                    Some(next @ 0xB0..=0xBF) => return Some((format!("MOVES R{}, R{}", with, lo_nybble!(next)), i + 1)),
+                    _ => return Some((format!("WITH R{}", with), i)),
+                }
+            },
+            _ => break instr,
+        }
+    };
+
+    let n = lo_nybble!(instr);
+    let text = match hi_nybble!(instr) {
+        0x0 => match n {
+            0x0 => "STOP".to_string(),
+            0x1 => "NOP".to_string(),
+            0x2 => "CACHE".to_string(),
+            0x3 => "LSR".to_string(),
+            0x4 => "ROL".to_string(),
+            _ => {
+                let offset = *bytes.get(i)? as i8;
+                i += 1;
+                let target = pc.wrapping_add(i as u16).wrapping_add(offset as u16);
+                format!("{} ${:04X}", BRANCHES[(n - 5) as usize], target)
+            },
+        },
+        0x1 => format!("TO R{}", n),
+        0x3 => match n {
+            0x0..=0xB => format!("{} (R{})", if alt & 1 == 0 {"STW"} else {"STB"}, n),
+            _ => "LOOP".to_string(),
+        },
+        0x4 => match n {
+            0x0..=0xB => format!("{} (R{})", if alt & 1 == 0 {"LDW"} else {"LDB"}, n),
+            0xC => if alt & 1 == 0 {"PLOT"} else {"RPIX"}.to_string(),
+            0xD => "SWAP".to_string(),
+            0xE => if alt & 1 == 0 {"COLOR"} else {"CMODE"}.to_string(),
+            _ => "NOT".to_string(),
+        },
+        0x5 => alt_op(alt, ["ADD", "ADC", "ADD", "ADC"], n),
+        0x6 => if alt == 3 {
+            format!("CMP R{}", n)
+        } else {
+            alt_op(alt, ["SUB", "SBC", "SUB", ""], n)
+        },
+        0x7 => if n == 0 {
+            "MERGE".to_string()
+        } else {
+            alt_op(alt, ["AND", "BIC", "AND", "BIC"], n)
+        },
+        0x8 => alt_op(alt, ["MULT", "UMULT", "MULT", "UMULT"], n),
+        0x9 => match n {
+            0x0 => "SBK".to_string(),
+            0x1..=0x4 => format!("LINK #{}", n),
+            0x5 => "SEX".to_string(),
+            0x6 => if alt & 1 == 0 {"ASR"} else {"DIV2"}.to_string(),
+            0x7 => "ROR".to_string(),
+            0x8..=0xD => format!("{} R{}", if alt & 1 == 0 {"JMP"} else {"LJMP"}, n),
+            0xE => "LOB".to_string(),
+            _ => if alt & 1 == 0 {"FMULT"} else {"LMULT"}.to_string(),
+        },
+        0xA => {
+            let data = *bytes.get(i)?;
+            i += 1;
+            match alt {
+                0 => format!("IBT R{}, #${:02X}", n, data),
+                2 => format!("SMS (${:04X}), R{}", (data as u16) << 1, n),
+                _ => format!("LMS R{}, (${:04X})", n, (data as u16) << 1),
+            }
+        },
+        0xB => format!("FROM R{}", n),
+        0xC => if n == 0 {
+            "HIB".to_string()
+        } else {
+            alt_op(alt, ["OR", "XOR", "OR", "XOR"], n)
+        },
+        0xD => if n == 0xF {
+            ["GETC", "GETC", "RAMB", "ROMB"][alt].to_string()
+        } else {
+            format!("INC R{}", n)
+        },
+        0xE => if n == 0xF {
+            ["GETB", "GETBH", "GETBL", "GETBS"][alt].to_string()
+        } else {
+            format!("DEC R{}", n)
+        },
+        _ => {
+            let data = make16!(*bytes.get(i + 1)?, *bytes.get(i)?);
+            i += 2;
+            match alt {
+                0 => format!("IWT R{}, #${:04X}", n, data),
+                2 => format!("SM (${:04X}), R{}", data, n),
+                _ => format!("LM R{}, (${:04X})", n, data),
+            }
+        },
+    };
+
+    Some((text, i))
+}
+
+// Instructions that use a register with ALT0/1, and an immediate with ALT2/3.
+fn alt_op(alt: usize, mnemonics: [&str; 4], n: u8) -> String {
+    if alt < 2 {
+        format!("{} R{}", mnemonics[alt], n)
+    } else {
+        format!("{} #{}", mnemonics[alt], n)
+    }
+}
@@ -0,0 +1,112 @@
+// Symbol files, for showing labels in place of addresses.
+use std::collections::BTreeMap;
+
+/// Labels for CPU addresses, loaded from a WLA-DX style symbol file (.sym).
+///
+/// Each label is on its own line as `BB:AAAA name`, where BB is the bank and AAAA the address, in hex.
+/// If the file has sections (e.g. `[labels]`), only the labels section is used.
+/// Comments start with `;`.
+#[derive(Clone, Default)]
+pub struct SymbolTable {
+    labels:     BTreeMap<u32, String>,
+    addresses:  BTreeMap<String, u32>,
+}
+
+impl SymbolTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Load a symbol file from disk.
+    pub fn load(path: &str) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        Self::parse(&text)
+    }
+
+    /// Parse the text of a symbol file.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut symbols = Self::new();
+        let mut in_labels = true;
+        for (line_num, line) in text.lines().enumerate() {
+            let line = line.split(';').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            if line.starts_with('[') {
+                in_labels = line.eq_ignore_ascii_case("[labels]");
+                continue;
+            }
+            if !in_labels {
+                continue;
+            }
+
+            let mut parts = line.split_whitespace();
+            let addr = parts.next().and_then(parse_addr)
+                .ok_or_else(|| format!("Invalid address on line {}: {}", line_num + 1, line))?;
+            let name = parts.next()
+                .ok_or_else(|| format!("Missing label on line {}: {}", line_num + 1, line))?;
+            symbols.insert(addr, name);
+        }
+        Ok(symbols)
+    }
+
+    /// Add a label. If the address already has a label, the first one is kept for display.
+    pub fn insert(&mut self, addr: u32, name: &str) {
+        self.labels.entry(addr).or_insert_with(|| name.to_string());
+        self.addresses.insert(name.to_string(), addr);
+    }
+
+    /// Number of labels.
+    pub fn len(&self) -> usize {
+        self.addresses.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.addresses.is_empty()
+    }
+
+    /// Find the address of a label.
+    pub fn address_of(&self, name: &str) -> Option<u32> {
+        self.addresses.get(name).copied()
+    }
+
+    /// Get the label at exactly this address.
+    pub fn label_at(&self, addr: u32) -> Option<&str> {
+        self.labels.get(&addr).map(|s| s.as_str())
+    }
+
+    /// Describe an address relative to the closest label before it in the same bank,
+    /// e.g. `NMI_Handler+$12`.
+    pub fn label_for(&self, addr: u32) -> Option<String> {
+        let (label_addr, name) = self.labels.range((addr & 0xFF_0000)..=addr).next_back()?;
+        match addr - label_addr {
+            0 => Some(name.clone()),
+            offset => Some(format!("{}+${:X}", name, offset)),
+        }
+    }
+
+    /// Format an address, with its label if there is one: `$008012 <NMI_Handler+$12>`.
+    pub fn format_addr(&self, addr: u32) -> String {
+        match self.label_for(addr) {
+            Some(label) => format!("${:06X} <{}>", addr, label),
+            None => format!("${:06X}", addr),
+        }
+    }
+
+    /// Parse an address as hex, or look it up as a label.
+    pub fn resolve(&self, s: &str) -> Option<u32> {
+        self.address_of(s).or_else(|| u32::from_str_radix(s.trim_start_matches('$'), 16).ok())
+    }
+}
+
+// Parse a `BB:AAAA` address.
+fn parse_addr(s: &str) -> Option<u32> {
+    let mut parts = s.split(':');
+    let bank = u8::from_str_radix(parts.next()?, 16).ok()?;
+    let addr = u16::from_str_radix(parts.next()?, 16).ok()?;
+    if parts.next().is_some() {
+        None
+    } else {
+        Some(make24!(bank, addr))
+    }
+}
@@ -0,0 +1,185 @@
+// Trace logs in the bsnes format, and comparing against logs from other emulators.
+
+use crate::SNES;
+use super::{
+    CPUState,
+    disassemble_cpu
+};
+
+// Steps to wait for the next instruction while halted, before giving up.
+const MAX_IDLE_STEPS: usize = 1_000_000;
+
+const FLAGS: &[u8; 8] = b"nvmxdizc";
+
+/// Format the CPU state before the next instruction as a line of a bsnes-style trace log:
+///
+/// `008000 sei                  A:0000 X:0000 Y:0000 S:01ff D:0000 B:00 nvMXdIzc V:  0 H: 184`
+///
+/// Set flags are uppercase. H is in master cycles, to the nearest dot.
+pub fn trace_line(snes: &mut SNES) -> String {
+    let state = snes.get_state();
+    let instr = snes.get_instr();
+    format_line(&state, &instr, snes.get_hv_counter())
+}
+
+fn format_line(state: &CPUState, instr: &[u8], (dot, line): (u16, u16)) -> String {
+    let m = state.pe || test_bit!(state.p, 5, u8);
+    let x = state.pe || test_bit!(state.p, 4, u8);
+    let disasm = disassemble_cpu(state.pc, instr, m, x).map(|(text, _)| text).unwrap_or_default();
+    let flags = FLAGS.iter().enumerate()
+        .map(|(i, c)| if test_bit!(state.p, 7 - i, u8) {c.to_ascii_uppercase() as char} else {*c as char})
+        .collect::<String>();
+    format!("{:06x} {:<20} A:{:04x} X:{:04x} Y:{:04x} S:{:04x} D:{:04x} B:{:02x} {} V:{:3} H:{:4}",
+        make24!(state.pb, state.pc), disasm.to_lowercase(),
+        state.a, state.x, state.y, state.s, state.dp, state.db,
+        flags, line, dot * 4)
+}
+
+/// CPU state read from a line of a trace log. Registers missing from the line are None.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct TraceEntry {
+    pub pc: u32,
+    pub a:  Option<u16>,
+    pub x:  Option<u16>,
+    pub y:  Option<u16>,
+    pub s:  Option<u16>,
+    pub d:  Option<u16>,
+    pub db: Option<u8>,
+    pub p:  Option<u8>,
+}
+
+impl TraceEntry {
+    /// Parse a line in the bsnes or Mesen format.
+    ///
+    /// The line must start with the 24-bit PC, either as `008000` or `00:8000`.
+    /// Registers are read from `A:`, `X:`, `Y:`, `S:`, `D:` and `B:` or `DB:` fields.
+    /// The status is read from a `P:` field, or from the flags written as letters (`nvMXdIzc`).
+    /// The case of hex digits doesn't matter, and anything else on the line is ignored.
+    ///
+    /// Returns None if the line doesn't start with a PC.
+    pub fn parse(line: &str) -> Option<Self> {
+        let mut tokens = line.split_whitespace();
+        let pc = tokens.next()?.trim_start_matches('$').replace(':', "");
+        if pc.len() != 6 {
+            return None;
+        }
+        let mut entry = TraceEntry {
+            pc: u32::from_str_radix(&pc, 16).ok()?,
+            ..Default::default()
+        };
+
+        for token in tokens {
+            if let Some(p) = parse_flags(token) {
+                entry.p = Some(p);
+                continue;
+            }
+            let (name, value) = match token.find(':') {
+                Some(i) => (&token[..i], &token[(i + 1)..]),
+                None => continue,
+            };
+            let word = || u16::from_str_radix(value, 16).ok();
+            match name.to_ascii_uppercase().as_str() {
+                "A" => entry.a = word(),
+                "X" => entry.x = word(),
+                "Y" => entry.y = word(),
+                "S" => entry.s = word(),
+                "D" => entry.d = word(),
+                "B" | "DB" => entry.db = u8::from_str_radix(value, 16).ok(),
+                "P" => entry.p = parse_flags(value).or_else(|| u8::from_str_radix(value, 16).ok()),
+                _ => {},
+            }
+        }
+        Some(entry)
+    }
+
+    // Names of the fields that don't match the CPU state.
+    fn diff(&self, state: &CPUState) -> Vec<&'static str> {
+        let fields = [
+            ("PC", Some(self.pc), make24!(state.pb, state.pc)),
+            ("A", self.a.map(u32::from), state.a as u32),
+            ("X", self.x.map(u32::from), state.x as u32),
+            ("Y", self.y.map(u32::from), state.y as u32),
+            ("S", self.s.map(u32::from), state.s as u32),
+            ("D", self.d.map(u32::from), state.dp as u32),
+            ("DB", self.db.map(u32::from), state.db as u32),
+            ("P", self.p.map(u32::from), state.p as u32),
+        ];
+        fields.iter()
+            .filter(|(_, expected, actual)| expected.is_some_and(|e| e != *actual))
+            .map(|(name, _, _)| *name)
+            .collect()
+    }
+}
+
+// Read the status flags written as letters, with set flags in uppercase.
+fn parse_flags(token: &str) -> Option<u8> {
+    let token = token.as_bytes();
+    if token.len() != 8 || !token.iter().zip(FLAGS.iter()).all(|(t, f)| t.eq_ignore_ascii_case(f)) {
+        return None;
+    }
+    Some(token.iter().fold(0, |acc, t| (acc << 1) | (t.is_ascii_uppercase() as u8)))
+}
+
+/// The first point where the emulator doesn't match a trace log.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Divergence {
+    pub line:       usize,              // Line in the log, starting from 1.
+    pub expected:   String,             // The line from the log.
+    pub actual:     String,             // The emulator's state, formatted with trace_line.
+    pub fields:     Vec<&'static str>,  // Registers that don't match.
+}
+
+impl std::fmt::Display for Divergence {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        writeln!(f, "diverged at line {} ({}):", self.line, self.fields.join(", "))?;
+        writeln!(f, "expected: {}", self.expected)?;
+        write!(f, "actual:   {}", self.actual)
+    }
+}
+
+/// Run the SNES in lockstep with a trace log from another emulator, one instruction per line,
+/// and report the first line where the CPU state doesn't match.
+/// Lines that can't be parsed by TraceEntry::parse are skipped. Only the PC and registers are compared:
+/// timing is ignored, as emulators count it differently.
+///
+/// Returns None if every line matched.
+pub fn compare_trace(snes: &mut SNES, log: &str) -> Option<Divergence> {
+    snes.start_frame();
+    for (i, line) in log.lines().enumerate() {
+        let entry = match TraceEntry::parse(line) {
+            Some(entry) => entry,
+            None => continue,
+        };
+
+        let (state, hv) = step_instruction(snes);
+        let fields = entry.diff(&state);
+        if !fields.is_empty() {
+            let addr = make24!(state.pb, state.pc);
+            let instr = (0..4).map(|i| snes.get_mem_at(addr.wrapping_add(i))).collect::<Vec<_>>();
+            return Some(Divergence {
+                line:       i + 1,
+                expected:   line.to_string(),
+                actual:     format_line(&state, &instr, hv),
+                fields:     fields,
+            });
+        }
+    }
+    None
+}
+
+// Step until the CPU has executed an instruction.
+// Returns the CPU state and PPU position from just before the instruction.
+fn step_instruction(snes: &mut SNES) -> (CPUState, (u16, u16)) {
+    let count = snes.instr_count();
+    for _ in 0..MAX_IDLE_STEPS {
+        let state = snes.get_state();
+        let hv = snes.get_hv_counter();
+        if snes.step() {
+            snes.start_frame();
+        }
+        if snes.instr_count() != count {
+            return (state, hv);
+        }
+    }
+    (snes.get_state(), snes.get_hv_counter())
+}
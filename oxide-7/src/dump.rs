@@ -0,0 +1,82 @@
+// Per-frame log of video and audio hashes, along with the input for each frame.
+use alloc::string::{
+    String,
+    ToString
+};
+use std::{
+    fs::File,
+    io::{
+        BufWriter,
+        Write
+    }
+};
+
+use crate::{
+    SNES,
+    SNESHeadlessAudio,
+    HEADLESS_SAMPLES_PER_FRAME
+};
+
+/// Writes a line to a log file for each frame the SNES runs.
+/// Each line contains the frame number, a hash of the video output, a hash of the audio output,
+/// and the buttons held on each joypad (see SNES::held_buttons).
+///
+/// Emulation is deterministic, so two versions of the emulator given the same ROM and input
+/// should produce identical logs. Comparing logs shows the first frame where the output changed,
+/// which can be used to bisect regressions without a GUI.
+pub struct FrameDump {
+    log:    BufWriter<File>,
+    audio:  SNESHeadlessAudio,
+    frame:  u64,
+}
+
+impl FrameDump {
+    /// Create a log file at the path specified, and start capturing audio from the SNES.
+    /// This should be created before the first frame, so all the audio is captured.
+    pub fn new(snes: &mut SNES, path: &str) -> Result<Self, String> {
+        let file = File::create(path).map_err(|e| e.to_string())?;
+        let mut log = BufWriter::new(file);
+        writeln!(log, "# oxide-7 frame dump: {}", snes.rom_name().trim()).map_err(|e| e.to_string())?;
+        writeln!(log, "# frame video audio joypad1 joypad2 joypad3 joypad4").map_err(|e| e.to_string())?;
+
+        Ok(FrameDump {
+            log:    log,
+            audio:  snes.enable_headless_audio(HEADLESS_SAMPLES_PER_FRAME),
+            frame:  0,
+        })
+    }
+
+    /// Run a frame, and log it.
+    /// Use this in place of SNES::frame.
+    pub fn frame(&mut self, snes: &mut SNES, frame: &mut [u8]) -> Result<(), String> {
+        snes.frame(frame);
+
+        let audio = self.audio.get_frame_audio();
+        let audio_bytes = audio.iter().flat_map(|s| s.to_bits().to_le_bytes().to_vec()).collect::<Vec<_>>();
+
+        write!(self.log, "{} {:016x} {:016x}", self.frame, fnv1a(frame), fnv1a(&audio_bytes)).map_err(|e| e.to_string())?;
+        for joypad in 0..4 {
+            write!(self.log, " {:04x}", snes.held_buttons(joypad)).map_err(|e| e.to_string())?;
+        }
+        writeln!(self.log).map_err(|e| e.to_string())?;
+
+        self.frame += 1;
+        Ok(())
+    }
+
+    /// Number of frames logged so far.
+    pub fn frame_count(&self) -> u64 {
+        self.frame
+    }
+
+    /// Write any buffered lines to the file.
+    /// This is also done when the FrameDump is dropped, but any errors are ignored.
+    pub fn flush(&mut self) -> Result<(), String> {
+        self.log.flush().map_err(|e| e.to_string())
+    }
+}
+
+// A fixed hash function, so logs from different builds can be compared.
+pub(crate) fn fnv1a(data: &[u8]) -> u64 {
+    data.iter().fold(0xcbf2_9ce4_8422_2325, |hash, b| (hash ^ (*b as u64)).wrapping_mul(0x0000_0100_0000_01B3))
+}
@@ -0,0 +1,51 @@
+// Errors from creating a SNES.
+use alloc::{
+    string::String,
+    vec::Vec
+};
+
+use crate::mem::rom::EnhancementChip;
+
+/// Reasons a cart couldn't be loaded.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Error {
+    /// A file couldn't be read or created: the cart, the save file, or the DSP ROM.
+    File {
+        path:   String,
+        reason: String,
+    },
+    /// The cart data is a zip archive that doesn't contain a ROM, or couldn't be read.
+    Archive(String),
+    /// The ROM doesn't have a valid header, so it may be corrupt or not a SNES ROM.
+    BadHeader,
+    /// The ROM header has a memory mapping mode that isn't emulated.
+    UnsupportedMapper(u8),
+    /// The cart has an enhancement chip that isn't emulated.
+    /// This is only returned if `SNESBuilder::allow_unsupported_chips` is disabled.
+    UnsupportedChip(EnhancementChip),
+    /// The cart has a DSP chip, but no DSP ROM was given.
+    NoDSPROM,
+    /// The cart has a DSP chip, and the DSP ROM directory given doesn't have a ROM for it.
+    /// The file names that were looked for are provided, without an extension.
+    DSPROMNotFound {
+        dir:        String,
+        expected:   Vec<String>,
+    },
+}
+
+impl core::fmt::Display for Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self {
+            Error::File {path, reason} => write!(f, "Couldn't open {}: {}", path, reason),
+            Error::Archive(reason) => write!(f, "Couldn't extract ROM from archive: {}", reason),
+            Error::BadHeader => write!(f, "Not a valid SNES ROM: no header found"),
+            Error::UnsupportedMapper(mapping) => write!(f, "Unsupported memory mapping: {:02X}", mapping),
+            Error::UnsupportedChip(chip) => write!(f, "Unsupported enhancement chip: {:?}", chip),
+            Error::NoDSPROM => write!(f, "This game needs a DSP ROM"),
+            Error::DSPROMNotFound {dir, expected} => write!(f, "No DSP ROM in {}: expected one of {}", dir, expected.join(", ")),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Error {}
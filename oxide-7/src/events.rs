@@ -0,0 +1,160 @@
+// Events that are sent out to the frontend.
+use alloc::{
+    string::String,
+    vec::Vec
+};
+
+use crate::sync::{
+    Arc, Mutex,
+    unbounded,
+    Receiver,
+    Sender
+};
+
+use crate::mem::rom::EnhancementChip;
+
+/// A processor inside the SNES.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Processor {
+    /// The main 65816 CPU.
+    CPU,
+    /// The SPC-700 audio processor.
+    SPC,
+}
+
+/// Hardware features that aren't emulated, or are only partly emulated.
+/// A game that uses any of these may not run correctly.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum UnsupportedFeature {
+    /// An enhancement chip in the cart.
+    Chip(EnhancementChip),
+    /// An instruction that should stop or halt a processor.
+    Instruction {
+        processor:  Processor,
+        opcode:     u8,
+    },
+    /// Interlaced screen video. Frames are drawn without interlacing.
+    Interlace,
+    /// The 239-line overscan mode. Only 224 lines are drawn, unless enabled with `SNES::set_overscan`.
+    Overscan,
+    /// SA-1 DMA and character conversion.
+    SA1DMA,
+    /// SA-1 variable-length bit processing.
+    SA1VariableLength,
+    /// The DSP-n serial port.
+    DSPSerial,
+}
+
+impl core::fmt::Display for UnsupportedFeature {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self {
+            UnsupportedFeature::Chip(chip) => write!(f, "{:?} enhancement chip", chip),
+            UnsupportedFeature::Instruction {processor, opcode} => write!(f, "{:?} instruction {:02X}", processor, opcode),
+            UnsupportedFeature::Interlace => write!(f, "Interlaced video"),
+            UnsupportedFeature::Overscan => write!(f, "Overscan (239-line) video"),
+            UnsupportedFeature::SA1DMA => write!(f, "SA-1 DMA"),
+            UnsupportedFeature::SA1VariableLength => write!(f, "SA-1 variable-length bit processing"),
+            UnsupportedFeature::DSPSerial => write!(f, "DSP serial port"),
+        }
+    }
+}
+
+/// Notable things that happen inside the SNES.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Event {
+    /// Save RAM was written out to the save file.
+    SramWritten,
+    /// Save RAM couldn't be written to the save file.
+    /// It is tried again the next time the game changes save RAM.
+    SramWriteFailed(String),
+    /// The checksum in the ROM header doesn't match the ROM data.
+    /// This could indicate a bad dump or a modified ROM.
+    /// This is sent at the start of the first frame, so subscribe before running it.
+    BadChecksum {
+        header: u16,
+        actual: u16,
+    },
+    /// The soft patch next to the cart couldn't be applied, so the ROM was loaded unpatched.
+    /// This is sent at the start of the first frame.
+    BadPatch(String),
+    /// A frame was completed.
+    FrameCompleted,
+    /// A savestate was loaded.
+    StateLoaded,
+    /// The frame drawn on the render thread was different to the one drawn on the calling thread.
+    /// The hashes of each frame are provided.
+    /// This is only sent if render verification is enabled.
+    RenderMismatch {
+        threaded:   u64,
+        single:     u64,
+    },
+    /// The game used a hardware feature that isn't emulated.
+    /// This is only sent the first time each feature is used.
+    UnsupportedFeatureUsed(UnsupportedFeature),
+    /// A processor tried to run an instruction that isn't supported.
+    BadOpcode {
+        processor:  Processor,
+        opcode:     u8,
+        pc:         u32,
+    },
+    /// The expansion port was read with no device attached. Open bus is returned.
+    /// This is only sent if the debug feature is enabled.
+    ExpansionPortRead {
+        addr:       u16,
+    },
+    /// The expansion port was written to with no device attached.
+    /// This is only sent if the debug feature is enabled.
+    ExpansionPortWritten {
+        addr:       u16,
+        data:       u8,
+    },
+    /// The CPU tried to write to ROM. The write is ignored.
+    /// This is only sent if the debug feature is enabled.
+    ROMWritten {
+        addr:       u32,
+        data:       u8,
+        pc:         u32,
+    },
+}
+
+// Distributes events to any number of subscribers.
+#[derive(Clone, Default)]
+pub struct EventBus {
+    senders:        Arc<Mutex<Vec<Sender<Event>>>>,
+    unsupported:    Arc<Mutex<Vec<UnsupportedFeature>>>,
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Create a new receiver, which will get all events sent from this point onwards.
+    pub fn subscribe(&self) -> Receiver<Event> {
+        let (tx, rx) = unbounded();
+        self.senders.lock().push(tx);
+        rx
+    }
+
+    // Send an event to every subscriber.
+    // Subscribers whose receiver has been dropped are removed.
+    pub fn send(&self, event: Event) {
+        self.senders.lock().retain(|tx| tx.send(event.clone()).is_ok());
+    }
+
+    // Record that the game used a feature that isn't emulated.
+    // Subscribers are only told the first time each feature is used.
+    pub fn report_unsupported(&self, feature: UnsupportedFeature) {
+        let mut unsupported = self.unsupported.lock();
+        if !unsupported.contains(&feature) {
+            unsupported.push(feature);
+            drop(unsupported);
+            self.send(Event::UnsupportedFeatureUsed(feature));
+        }
+    }
+
+    // All the unsupported features that have been used, in the order they were first used.
+    pub fn unsupported_features(&self) -> Vec<UnsupportedFeature> {
+        self.unsupported.lock().clone()
+    }
+}
@@ -0,0 +1,178 @@
+// Trig and wireframe maths for the CX4 commands.
+// Angles are in 1/512ths of a turn unless stated, and all calculations avoid the float
+// functions that aren't available without std.
+
+// Sine of the angle, scaled to 32767.
+pub fn sin(angle: u16) -> i16 {
+    SIN_TABLE[(angle & 0x1FF) as usize]
+}
+
+// Cosine of the angle, scaled to 32767.
+pub fn cos(angle: u16) -> i16 {
+    SIN_TABLE[(angle.wrapping_add(0x80) & 0x1FF) as usize]
+}
+
+// Angle of the vector (x, y).
+pub fn atan(x: i16, y: i16) -> i16 {
+    if x == 0 {
+        return if y > 0 {0x80} else {0x180};
+    }
+
+    // The largest first-quadrant angle with a tangent that doesn't exceed |y / x|.
+    let (abs_x, abs_y) = ((x as i32).abs(), (y as i32).abs());
+    let angle = (1..0x80)
+        .take_while(|a| (sin(*a) as i32) * abs_x <= (cos(*a) as i32) * abs_y)
+        .count() as i16;
+    let angle = if (x < 0) != (y < 0) {-angle} else {angle};
+    let angle = if x < 0 {angle + 0x100} else {angle};
+    angle & 0x1FF
+}
+
+// Length of the vector (x, y), rounded down.
+pub fn distance(x: i16, y: i16) -> i16 {
+    isqrt(length_squared(x, y)) as i16
+}
+
+// Scale the vector (x, y) to the length provided.
+// The results are slightly shortened, as on the real chip.
+pub fn set_length(x: i16, y: i16, length: i16) -> (i16, i16) {
+    let current = sqrt(length_squared(x, y));
+    if current == 0.0 {
+        return (0, 0);
+    }
+    let scale = (length as f64) / current;
+    (((x as f64) * scale * 0.98) as i16, ((y as f64) * scale * 0.99) as i16)
+}
+
+// Rotate a 3D point, then project it onto the screen with perspective.
+// Rotation angles are in 1/128ths of a turn.
+pub fn project(point: (i16, i16, i16), rotation: (i16, i16, i16), scale: i16) -> (i16, i16) {
+    let (x, y, z) = rotate((point.0 as f64, point.1 as f64, (point.2 as f64) - 149.0), rotation);
+    let divisor = 144.0 * (z + 149.0);
+    (
+        (x * (scale as f64) / divisor * 149.0) as i16,
+        (y * (scale as f64) / divisor * 149.0) as i16
+    )
+}
+
+// Rotate a 3D point, then scale it onto the screen without perspective.
+// Rotation angles are in 1/128ths of a turn. A scale of 256 is 1:1.
+pub fn transform(point: (i16, i16, i16), rotation: (i16, i16, i16), scale: i16) -> (i16, i16) {
+    let (x, y, _) = rotate((point.0 as f64, point.1 as f64, point.2 as f64), rotation);
+    (
+        (x * (scale as f64) / 256.0) as i16,
+        (y * (scale as f64) / 256.0) as i16
+    )
+}
+
+// Find the step to draw a line from (x1, y1) to (x2, y2) one pixel at a time.
+// Returns the step in 8.8 fixed point, and the number of pixels.
+pub fn line_step(x1: i16, y1: i16, x2: i16, y2: i16) -> (i16, i16, i16) {
+    let dx = x2.wrapping_sub(x1) as i32;
+    let dy = y2.wrapping_sub(y1) as i32;
+
+    if dx.abs() > dy.abs() {
+        let step_x = if dx < 0 {-256} else {256};
+        (step_x, (256 * dy / dx.abs()) as i16, (dx.abs() + 1) as i16)
+    } else if dy != 0 {
+        let step_y = if dy < 0 {-256} else {256};
+        ((256 * dx / dy.abs()) as i16, step_y, (dy.abs() + 1) as i16)
+    } else {
+        (0, 0, 0)
+    }
+}
+
+// Rotate around the X axis, then Y, then Z.
+fn rotate(point: (f64, f64, f64), rotation: (i16, i16, i16)) -> (f64, f64, f64) {
+    let (x, y, z) = point;
+
+    let (sin_x, cos_x) = sin_cos(rotation.0);
+    let y2 = y * cos_x - z * sin_x;
+    let z2 = y * sin_x + z * cos_x;
+
+    let (sin_y, cos_y) = sin_cos(rotation.1);
+    let x2 = x * cos_y + z2 * sin_y;
+    let z3 = z2 * cos_y - x * sin_y;
+
+    let (sin_z, cos_z) = sin_cos(rotation.2);
+    (x2 * cos_z - y2 * sin_z, x2 * sin_z + y2 * cos_z, z3)
+}
+
+// Sine and cosine of the negated angle, which is in 1/128ths of a turn.
+fn sin_cos(angle: i16) -> (f64, f64) {
+    let angle = (angle as u16).wrapping_neg().wrapping_mul(4);
+    ((sin(angle) as f64) / 32767.0, (cos(angle) as f64) / 32767.0)
+}
+
+fn length_squared(x: i16, y: i16) -> u64 {
+    let (x, y) = (x as i64, y as i64);
+    (x * x + y * y) as u64
+}
+
+// Integer square root, rounded down.
+fn isqrt(val: u64) -> u64 {
+    let mut remainder = val;
+    let mut root = 0;
+    let mut bit = 1 << 62;
+    while bit > val {
+        bit >>= 2;
+    }
+    while bit != 0 {
+        if remainder >= root + bit {
+            remainder -= root + bit;
+            root = (root >> 1) + bit;
+        } else {
+            root >>= 1;
+        }
+        bit >>= 2;
+    }
+    root
+}
+
+// Square root of an integer, refined from the integer root with Newton's method.
+fn sqrt(val: u64) -> f64 {
+    let mut root = isqrt(val) as f64;
+    if root == 0.0 {
+        return 0.0;
+    }
+    for _ in 0..3 {
+        root = (root + (val as f64) / root) / 2.0;
+    }
+    root
+}
+
+// sin(2 * pi * i / 512) * 32767, rounded towards zero.
+const SIN_TABLE: [i16; 512] = [
+    0, 402, 804, 1206, 1607, 2009, 2410, 2811, 3211, 3611, 4011, 4409, 4807, 5205, 5601, 5997,
+    6392, 6786, 7179, 7571, 7961, 8351, 8739, 9126, 9511, 9895, 10278, 10659, 11038, 11416, 11792, 12166,
+    12539, 12909, 13278, 13645, 14009, 14372, 14732, 15090, 15446, 15799, 16150, 16499, 16845, 17189, 17530, 17868,
+    18204, 18537, 18867, 19194, 19519, 19840, 20159, 20474, 20787, 21096, 21402, 21705, 22004, 22301, 22594, 22883,
+    23169, 23452, 23731, 24006, 24278, 24546, 24811, 25072, 25329, 25582, 25831, 26077, 26318, 26556, 26789, 27019,
+    27244, 27466, 27683, 27896, 28105, 28309, 28510, 28706, 28897, 29085, 29268, 29446, 29621, 29790, 29955, 30116,
+    30272, 30424, 30571, 30713, 30851, 30984, 31113, 31236, 31356, 31470, 31580, 31684, 31785, 31880, 31970, 32056,
+    32137, 32213, 32284, 32350, 32412, 32468, 32520, 32567, 32609, 32646, 32678, 32705, 32727, 32744, 32757, 32764,
+    32767, 32764, 32757, 32744, 32727, 32705, 32678, 32646, 32609, 32567, 32520, 32468, 32412, 32350, 32284, 32213,
+    32137, 32056, 31970, 31880, 31785, 31684, 31580, 31470, 31356, 31236, 31113, 30984, 30851, 30713, 30571, 30424,
+    30272, 30116, 29955, 29790, 29621, 29446, 29268, 29085, 28897, 28706, 28510, 28309, 28105, 27896, 27683, 27466,
+    27244, 27019, 26789, 26556, 26318, 26077, 25831, 25582, 25329, 25072, 24811, 24546, 24278, 24006, 23731, 23452,
+    23169, 22883, 22594, 22301, 22004, 21705, 21402, 21096, 20787, 20474, 20159, 19840, 19519, 19194, 18867, 18537,
+    18204, 17868, 17530, 17189, 16845, 16499, 16150, 15799, 15446, 15090, 14732, 14372, 14009, 13645, 13278, 12909,
+    12539, 12166, 11792, 11416, 11038, 10659, 10278, 9895, 9511, 9126, 8739, 8351, 7961, 7571, 7179, 6786,
+    6392, 5997, 5601, 5205, 4807, 4409, 4011, 3611, 3211, 2811, 2410, 2009, 1607, 1206, 804, 402,
+    0, -402, -804, -1206, -1607, -2009, -2410, -2811, -3211, -3611, -4011, -4409, -4807, -5205, -5601, -5997,
+    -6392, -6786, -7179, -7571, -7961, -8351, -8739, -9126, -9511, -9895, -10278, -10659, -11038, -11416, -11792, -12166,
+    -12539, -12909, -13278, -13645, -14009, -14372, -14732, -15090, -15446, -15799, -16150, -16499, -16845, -17189, -17530, -17868,
+    -18204, -18537, -18867, -19194, -19519, -19840, -20159, -20474, -20787, -21096, -21402, -21705, -22004, -22301, -22594, -22883,
+    -23169, -23452, -23731, -24006, -24278, -24546, -24811, -25072, -25329, -25582, -25831, -26077, -26318, -26556, -26789, -27019,
+    -27244, -27466, -27683, -27896, -28105, -28309, -28510, -28706, -28897, -29085, -29268, -29446, -29621, -29790, -29955, -30116,
+    -30272, -30424, -30571, -30713, -30851, -30984, -31113, -31236, -31356, -31470, -31580, -31684, -31785, -31880, -31970, -32056,
+    -32137, -32213, -32284, -32350, -32412, -32468, -32520, -32567, -32609, -32646, -32678, -32705, -32727, -32744, -32757, -32764,
+    -32767, -32764, -32757, -32744, -32727, -32705, -32678, -32646, -32609, -32567, -32520, -32468, -32412, -32350, -32284, -32213,
+    -32137, -32056, -31970, -31880, -31785, -31684, -31580, -31470, -31356, -31236, -31113, -30984, -30851, -30713, -30571, -30424,
+    -30272, -30116, -29955, -29790, -29621, -29446, -29268, -29085, -28897, -28706, -28510, -28309, -28105, -27896, -27683, -27466,
+    -27244, -27019, -26789, -26556, -26318, -26077, -25831, -25582, -25329, -25072, -24811, -24546, -24278, -24006, -23731, -23452,
+    -23169, -22883, -22594, -22301, -22004, -21705, -21402, -21096, -20787, -20474, -20159, -19840, -19519, -19194, -18867, -18537,
+    -18204, -17868, -17530, -17189, -16845, -16499, -16150, -15799, -15446, -15090, -14732, -14372, -14009, -13645, -13278, -12909,
+    -12539, -12166, -11792, -11416, -11038, -10659, -10278, -9895, -9511, -9126, -8739, -8351, -7961, -7571, -7179, -6786,
+    -6392, -5997, -5601, -5205, -4807, -4409, -4011, -3611, -3211, -2811, -2410, -2009, -1607, -1206, -804, -402,
+];
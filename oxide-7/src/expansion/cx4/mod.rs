@@ -0,0 +1,653 @@
+// Capcom CX4 (Hitachi HG51B169)
+// A maths coprocessor used for sprites and wireframes in Mega Man X2 and X3.
+// The chip runs a program from its own internal ROM, so each of its commands is emulated directly.
+mod maths;
+#[cfg(test)]
+mod tests;
+
+use alloc::{
+    string::String,
+    vec,
+    vec::Vec
+};
+
+use crate::{
+    common::Interrupt,
+    mem::rom::ROM,
+    savestate::{
+        Stateful,
+        StateWriter,
+        StateReader
+    }
+};
+
+use maths::*;
+use super::Expansion;
+
+const RAM_SIZE: usize = 0x2000;
+
+// Register offsets in RAM.
+const DMA_SRC: usize = 0x1F40;
+const DMA_LEN: usize = 0x1F43;
+const DMA_DST: usize = 0x1F45;
+const DMA_START: usize = 0x1F47;
+const SPRITE_FUNC: usize = 0x1F4D;
+const COMMAND: usize = 0x1F4F;
+const BUSY: usize = 0x1F5E;
+
+// Returned by command $5C.
+const TEST_PATTERN: [u8; 48] = [
+    0x00, 0x00, 0x00, 0xFF, 0xFF, 0xFF, 0x00, 0xFF, 0x00, 0x00, 0x00, 0xFF,
+    0xFF, 0xFF, 0x00, 0x00, 0xFF, 0xFF, 0x00, 0x00, 0x80, 0xFF, 0xFF, 0x7F,
+    0x00, 0x80, 0x00, 0xFF, 0x7F, 0x00, 0xFF, 0x7F, 0xFF, 0x7F, 0xFF, 0xFF,
+    0x00, 0x00, 0x01, 0xFF, 0xFF, 0xFE, 0x00, 0x01, 0x00, 0xFF, 0xFE, 0x00
+];
+
+pub struct CX4 {
+    rom:    ROM,
+    ram:    Vec<u8>,    // Mapped to $6000-$7FFF. The registers are at the top.
+}
+
+impl CX4 {
+    pub fn new(rom: ROM) -> Self {
+        CX4 {
+            rom:    rom,
+            ram:    vec![0; RAM_SIZE],
+        }
+    }
+
+    // Commands complete instantly, so the chip is never busy.
+    fn read_reg(&self, addr: u16) -> u8 {
+        let offset = (addr - 0x6000) as usize;
+        if offset == BUSY {
+            0
+        } else {
+            self.ram[offset]
+        }
+    }
+
+    fn write_reg(&mut self, addr: u16, data: u8) {
+        let offset = (addr - 0x6000) as usize;
+        self.ram[offset] = data;
+        match offset {
+            DMA_START => self.dma(),
+            COMMAND => self.command(data),
+            _ => {}
+        }
+    }
+
+    // Copy from ROM to RAM.
+    fn dma(&mut self) {
+        let src = rom_offset(self.read_24(DMA_SRC));
+        let len = self.read_word(DMA_LEN) as usize;
+        let dst = self.read_word(DMA_DST) as usize;
+        for i in 0..len {
+            self.ram[(dst + i) % RAM_SIZE] = self.rom_byte(src + i);
+        }
+    }
+
+    fn command(&mut self, command: u8) {
+        // With sprite function $0E, commands that are multiples of 4 below $40 are a test.
+        if self.ram[SPRITE_FUNC] == 0x0E && command < 0x40 && (command & 3) == 0 {
+            self.ram[0x1F80] = command >> 2;
+            return;
+        }
+
+        match command {
+            0x00 => self.sprite_function(),
+            0x01 => {
+                self.ram[0x300..0xC00].iter_mut().for_each(|b| *b = 0);
+                self.draw_wireframe();
+            },
+            0x05 => self.propulsion(),
+            0x0D => self.set_vector_length(),
+            0x10 => self.polar_to_rect(),
+            0x13 => self.polar_to_rect_scaled(),
+            0x15 => self.pythagorean(),
+            0x1F => self.atan(),
+            0x22 => self.trapezoid(),
+            0x25 => self.multiply(),
+            0x2D => self.transform_coords(),
+            0x40 => self.sum(),
+            0x54 => self.square(),
+            0x5C => self.ram[..TEST_PATTERN.len()].copy_from_slice(&TEST_PATTERN),
+            0x89 => self.ram[0x1F80..0x1F83].copy_from_slice(&[0x36, 0x43, 0x05]),
+            _ => {}
+        }
+    }
+
+    fn sprite_function(&mut self) {
+        match self.ram[SPRITE_FUNC] {
+            0x00 => self.build_oam(),
+            0x03 => self.scale_rotate(0),
+            0x05 => self.transform_lines(),
+            0x07 => self.scale_rotate(64),
+            0x08 => self.draw_wireframe(),
+            0x0B => self.disintegrate(),
+            0x0C => self.bitplane_wave(),
+            _ => {}
+        }
+    }
+}
+
+// Sprite functions
+impl CX4 {
+    // Build OAM for a list of sprites at $6220. Each sprite points to a list of tiles in ROM.
+    // The low table is written to $6000 and the high table to $6200,
+    // starting at the OAM index in $6626.
+    fn build_oam(&mut self) {
+        let first = self.ram[0x626] as usize;
+        let mut oam = first * 4;
+        for entry in (oam..0x200).step_by(4) {
+            self.ram[entry + 1] = 0xE0;
+        }
+
+        let global_x = self.read_word(0x621);
+        let global_y = self.read_word(0x623);
+        let mut oam_hi = 0x200 + (first >> 2);
+        let mut hi_shift = (first & 3) * 2;
+        let mut oam_left = 128_usize.saturating_sub(first);
+
+        for sprite in 0..(self.ram[0x620] as usize) {
+            if oam_left == 0 {
+                break;
+            }
+            let src = 0x220 + sprite * 16;
+            let sprite_x = self.read_word(src).wrapping_sub(global_x) as i16;
+            let sprite_y = self.read_word(src + 2).wrapping_sub(global_y) as i16;
+            let name = self.ram[src + 5];
+            let attr = self.ram[src + 4] | self.ram[src + 6];
+
+            let mut tiles = rom_offset(self.read_24(src + 7));
+            let tile_count = self.rom_byte(tiles) as usize;
+            tiles += 1;
+
+            if tile_count == 0 {
+                // A single tile at the sprite position.
+                self.ram[oam] = sprite_x as u8;
+                self.ram[oam + 1] = sprite_y as u8;
+                self.ram[oam + 2] = name;
+                self.ram[oam + 3] = attr;
+                let hi_bits = if (sprite_x & 0x100) != 0 {3} else {2};
+                self.ram[oam_hi] = (self.ram[oam_hi] & !(3 << hi_shift)) | (hi_bits << hi_shift);
+
+                oam += 4;
+                oam_left -= 1;
+                hi_shift = (hi_shift + 2) & 6;
+                if hi_shift == 0 {
+                    oam_hi += 1;
+                }
+                continue;
+            }
+
+            for tile in (0..tile_count).map(|t| tiles + t * 4) {
+                if oam_left == 0 {
+                    break;
+                }
+                let tile_attr = self.rom_byte(tile);
+                let size = if (tile_attr & 0x20) != 0 {16} else {8};
+
+                let mut x = self.rom_byte(tile + 1) as i8 as i16;
+                if (attr & 0x40) != 0 {
+                    x = -x - size;
+                }
+                let x = x.wrapping_add(sprite_x);
+                let mut y = self.rom_byte(tile + 2) as i8 as i16;
+                if (attr & 0x80) != 0 {
+                    y = -y - size;
+                }
+                let y = y.wrapping_add(sprite_y);
+
+                if (-16..=272).contains(&x) && (-16..=224).contains(&y) {
+                    self.ram[oam] = x as u8;
+                    self.ram[oam + 1] = y as u8;
+                    self.ram[oam + 2] = name.wrapping_add(self.rom_byte(tile + 3));
+                    self.ram[oam + 3] = attr ^ (tile_attr & 0xC0);
+                    let hi_bits = (if (x & 0x100) != 0 {1} else {0}) | (if size == 16 {2} else {0});
+                    self.ram[oam_hi] = (self.ram[oam_hi] & !(3 << hi_shift)) | (hi_bits << hi_shift);
+
+                    oam += 4;
+                    oam_left -= 1;
+                    hi_shift = (hi_shift + 2) & 6;
+                    if hi_shift == 0 {
+                        oam_hi += 1;
+                    }
+                }
+            }
+        }
+    }
+
+    // Scale and rotate a 4bpp bitmap at $6600, and write it out as 4bpp tiles at $6000.
+    // Each output row of tiles is followed by the padding provided, in bytes.
+    fn scale_rotate(&mut self, row_padding: usize) {
+        let x_scale = self.read_word(0x1F8F);
+        let x_scale = if (x_scale & 0x8000) != 0 {0x7FFF} else {x_scale as i32};
+        let y_scale = self.read_word(0x1F92);
+        let y_scale = if (y_scale & 0x8000) != 0 {0x7FFF} else {y_scale as i32};
+
+        // Transformation matrix, in 4.12 fixed point.
+        let angle = self.read_word(0x1F80);
+        let (a, b, c, d) = match angle {
+            0 => (x_scale, 0, 0, y_scale),
+            128 => (0, -y_scale, x_scale, 0),
+            256 => (-x_scale, 0, 0, -y_scale),
+            384 => (0, y_scale, -x_scale, 0),
+            _ => (
+                ((cos(angle) as i32) * x_scale) >> 15,
+                -(((sin(angle) as i32) * y_scale) >> 15),
+                ((sin(angle) as i32) * x_scale) >> 15,
+                ((cos(angle) as i32) * y_scale) >> 15
+            )
+        };
+        let (a, b, c, d) = (a as i16 as i32, b as i16 as i32, c as i16 as i32, d as i16 as i32);
+
+        let width = (self.ram[0x1F89] & !7) as usize;
+        let height = (self.ram[0x1F8C] & !7) as usize;
+        let clear_len = core::cmp::min((width + row_padding / 4) * height / 2, RAM_SIZE);
+        self.ram[..clear_len].iter_mut().for_each(|b| *b = 0);
+
+        // Find the source position of the top-left output pixel, rotating around the centre.
+        let centre_x = self.read_word(0x1F83) as i16 as i32;
+        let centre_y = self.read_word(0x1F86) as i16 as i32;
+        let mut line_x = (centre_x << 12).wrapping_sub(centre_x * a).wrapping_sub(centre_y * b);
+        let mut line_y = (centre_y << 12).wrapping_sub(centre_x * c).wrapping_sub(centre_y * d);
+
+        let mut out = 0_isize;
+        for _ in 0..height {
+            let mut x = line_x as u32;
+            let mut y = line_y as u32;
+            let mut bit = 0x80;
+
+            for _ in 0..width {
+                let (src_x, src_y) = ((x >> 12) as usize, (y >> 12) as usize);
+                let pixel = if src_x >= width || src_y >= height {
+                    0
+                } else {
+                    let src = src_y * width + src_x;
+                    let byte = self.ram[0x600 + (src >> 1)];
+                    if (src & 1) != 0 {byte >> 4} else {byte}
+                };
+                self.plot_bitplane_pixel(out, bit, pixel);
+
+                bit >>= 1;
+                if bit == 0 {
+                    bit = 0x80;
+                    out += 32;
+                }
+                x = x.wrapping_add(a as u32);
+                y = y.wrapping_add(c as u32);
+            }
+
+            out += 2 + row_padding as isize;
+            if (out & 0x10) != 0 {
+                out &= !0x10;
+            } else {
+                out -= (width * 4 + row_padding) as isize;
+            }
+            line_x = line_x.wrapping_add(b);
+            line_y = line_y.wrapping_add(d);
+        }
+    }
+
+    // Project a list of 3D points at $6000 onto the screen,
+    // then find the lines between pairs of them listed at $6B00.
+    fn transform_lines(&mut self) {
+        let rotation = (self.ram[0x1F83] as i16, self.ram[0x1F86] as i16, self.ram[0x1F89] as i16);
+        let scale = self.ram[0x1F8C] as i16;
+
+        for point in (0..self.read_word(0x1F80) as usize).map(|p| p * 0x10) {
+            if point + 0x10 > RAM_SIZE {
+                break;
+            }
+            let (x, y) = project((
+                self.read_word(point + 1) as i16,
+                self.read_word(point + 5) as i16,
+                self.read_word(point + 9) as i16
+            ), rotation, scale);
+            self.write_word(point + 1, x.wrapping_add(0x80) as u16);
+            self.write_word(point + 5, y.wrapping_add(0x50) as u16);
+        }
+
+        for line in [0x600, 0x608] {
+            self.write_word(line, 23);
+            self.write_word(line + 2, 0x60);
+            self.write_word(line + 5, 0x40);
+        }
+
+        for line in 0..(self.read_word(0xB00) as usize) {
+            let ends = 0xB02 + line * 2;
+            let out = 0x600 + line * 8;
+            if out + 8 > RAM_SIZE {
+                break;
+            }
+            let start = (self.ram[ends] as usize) << 4;
+            let end = (self.ram[ends + 1] as usize) << 4;
+            let (step_x, step_y, len) = line_step(
+                self.read_word(start + 1) as i16,
+                self.read_word(start + 5) as i16,
+                self.read_word(end + 1) as i16,
+                self.read_word(end + 5) as i16
+            );
+            self.write_word(out, if len == 0 {1} else {len as u16});
+            self.write_word(out + 2, step_x as u16);
+            self.write_word(out + 5, step_y as u16);
+        }
+    }
+
+    // Draw a 3D wireframe model from ROM into a 2bpp bitmap at $6300.
+    fn draw_wireframe(&mut self) {
+        let points_bank = self.ram[0x1F82];
+        let mut line = rom_offset(self.read_24(0x1F80));
+
+        for _ in 0..self.ram[0x295] {
+            // A start point of $FFFF continues from the last line that ended at a point.
+            let start = if self.rom_byte(line) == 0xFF && self.rom_byte(line + 1) == 0xFF {
+                let mut prev = line.saturating_sub(5);
+                while prev >= 5 && self.rom_byte(prev + 2) == 0xFF && self.rom_byte(prev + 3) == 0xFF {
+                    prev -= 5;
+                }
+                make24!(points_bank, self.rom_byte(prev + 2), self.rom_byte(prev + 3))
+            } else {
+                make24!(points_bank, self.rom_byte(line), self.rom_byte(line + 1))
+            };
+            let end = make24!(points_bank, self.rom_byte(line + 2), self.rom_byte(line + 3));
+            let colour = self.rom_byte(line + 4);
+
+            let start = self.read_rom_point(rom_offset(start));
+            let end = self.read_rom_point(rom_offset(end));
+            self.draw_line(start, end, colour);
+
+            line += 5;
+        }
+    }
+
+    // Break a 4bpp bitmap at $6600 into pixels, and scale them out from a point.
+    fn disintegrate(&mut self) {
+        let width = self.ram[0x1F89] as usize;
+        let height = self.ram[0x1F8C] as usize;
+        let centre_x = self.read_word(0x1F80) as i16 as i32;
+        let centre_y = self.read_word(0x1F83) as i16 as i32;
+        let scale_x = self.read_word(0x1F86) as i16 as i32;
+        let scale_y = self.read_word(0x1F8F) as i16 as i32;
+        let start_x = (centre_x << 8).wrapping_sub(centre_x * scale_x) as u32;
+        let start_y = (centre_y << 8).wrapping_sub(centre_y * scale_y) as u32;
+
+        let clear_len = core::cmp::min(width * height / 2, RAM_SIZE);
+        self.ram[..clear_len].iter_mut().for_each(|b| *b = 0);
+
+        let mut src = 0x600;
+        let mut y = start_y;
+        for _ in 0..height {
+            let mut x = start_x;
+            for j in 0..width {
+                let (dst_x, dst_y) = ((x >> 8) as usize, (y >> 8) as usize);
+                if dst_x < width && dst_y < height && (dst_y * width + dst_x) < RAM_SIZE && src < RAM_SIZE {
+                    let pixel = if (j & 1) != 0 {self.ram[src] >> 4} else {self.ram[src]};
+                    let out = (y >> 11) as usize * width * 4 + (x >> 11) as usize * 32 + (dst_y & 7) * 2;
+                    self.plot_bitplane_pixel(out as isize, 0x80 >> (dst_x & 7), pixel);
+                }
+                if (j & 1) != 0 {
+                    src += 1;
+                }
+                x = x.wrapping_add(scale_x as u32);
+            }
+            y = y.wrapping_add(scale_y as u32);
+        }
+    }
+
+    // Distort 2bpp tiles at $6000 with a wave of heights at $6B00.
+    fn bitplane_wave(&mut self) {
+        let mut dst = 0;
+        let mut wave = self.ram[0x1F83] as usize;
+        let mut mask1: u16 = 0xC0C0;
+        let mut mask2: u16 = 0x3F3F;
+
+        for _ in 0..0x10 {
+            for pattern in [0xA00, 0xA10] {
+                loop {
+                    let top = -((self.ram[wave + 0xB00] as i8) as i16) - 16;
+
+                    for i in 0..40 {
+                        let height = top + (i as i16);
+                        let addr = dst + (i / 8) * 0x200 + (i % 8) * 2;
+                        let mut data = self.read_word(addr) & mask2;
+                        if height >= 8 {
+                            data |= mask1 & 0xFF00;
+                        } else if height >= 0 {
+                            data |= mask1 & self.read_word(pattern + (height as usize) * 2);
+                        }
+                        self.write_word(addr, data);
+                    }
+
+                    wave = (wave + 1) & 0x7F;
+                    mask1 = mask1.rotate_right(2);
+                    mask2 = mask2.rotate_right(2);
+                    if mask1 == 0xC0C0 {
+                        break;
+                    }
+                }
+                dst += 16;
+            }
+        }
+    }
+}
+
+// Maths commands
+impl CX4 {
+    fn propulsion(&mut self) {
+        let divisor = self.read_word(0x1F83) as i32;
+        let result = if divisor == 0 {
+            0x10000
+        } else {
+            (0x10000 / divisor).wrapping_mul(self.read_word(0x1F81) as i32) >> 8
+        };
+        self.write_word(0x1F80, result as u16);
+    }
+
+    fn set_vector_length(&mut self) {
+        let (x, y) = set_length(self.read_word(0x1F80) as i16, self.read_word(0x1F83) as i16, self.read_word(0x1F86) as i16);
+        self.write_word(0x1F89, x as u16);
+        self.write_word(0x1F8C, y as u16);
+    }
+
+    fn polar_to_rect(&mut self) {
+        let radius = self.read_word(0x1F83) as i16 as i32;
+        let angle = self.read_word(0x1F80);
+        let x = radius.wrapping_mul(cos(angle) as i32).wrapping_mul(2) >> 16;
+        let y = radius.wrapping_mul(sin(angle) as i32).wrapping_mul(2) >> 16;
+        self.write_24(0x1F86, x as u32);
+        self.write_24(0x1F89, (y - (y >> 6)) as u32);
+    }
+
+    // The radius is unsigned, and the result has 8 more bits of precision.
+    fn polar_to_rect_scaled(&mut self) {
+        let radius = self.read_word(0x1F83) as i32;
+        let angle = self.read_word(0x1F80);
+        let x = radius.wrapping_mul(cos(angle) as i32).wrapping_mul(2) >> 8;
+        let y = radius.wrapping_mul(sin(angle) as i32).wrapping_mul(2) >> 8;
+        self.write_24(0x1F86, x as u32);
+        self.write_24(0x1F89, y as u32);
+    }
+
+    fn pythagorean(&mut self) {
+        let dist = distance(self.read_word(0x1F80) as i16, self.read_word(0x1F83) as i16);
+        self.write_word(0x1F80, dist as u16);
+    }
+
+    fn atan(&mut self) {
+        let angle = atan(self.read_word(0x1F80) as i16, self.read_word(0x1F83) as i16);
+        self.write_word(0x1F86, angle as u16);
+    }
+
+    // Find the left and right edges of a trapezoid for each of 225 lines,
+    // and write them to $6800 and $6900, for use in a HDMA window table.
+    fn trapezoid(&mut self) {
+        let tan = |angle: u16| {
+            let (sin, cos) = (sin(angle) as i32, cos(angle) as i32);
+            if cos == 0 {i32::MIN} else {(sin << 16) / cos}
+        };
+        let left_tan = tan(self.read_word(0x1F8C));
+        let right_tan = tan(self.read_word(0x1F8F));
+        let offset = self.read_word(0x1F86).wrapping_sub(self.read_word(0x1F80)) as i32;
+        let width = self.read_word(0x1F93) as i32;
+        let mut y = self.read_word(0x1F83).wrapping_sub(self.read_word(0x1F89)) as i16;
+
+        for line in 0..225 {
+            let (left, right) = if y >= 0 {
+                let left = (left_tan.wrapping_mul(y as i32) >> 16).wrapping_add(offset) as i16;
+                let right = (right_tan.wrapping_mul(y as i32) >> 16).wrapping_add(offset).wrapping_add(width) as i16;
+                match (left, right) {
+                    (l, r) if l < 0 && r < 0 => (1, 0),
+                    (l, r) if l < 0 => (0, r),
+                    (l, r) if r < 0 => (l, 0),
+                    (l, r) => (l, r)
+                }
+            } else {
+                (1, 0)
+            };
+            let (left, right) = match (left, right) {
+                (l, r) if l > 255 && r > 255 => (255, 254),
+                (l, r) if l > 255 => (255, r),
+                (l, r) if r > 255 => (l, 255),
+                (l, r) => (l, r)
+            };
+
+            self.ram[0x800 + line] = left as u8;
+            self.ram[0x900 + line] = right as u8;
+            y = y.wrapping_add(1);
+        }
+    }
+
+    // 24-bit multiply. Only the low 24 bits of the result are kept.
+    fn multiply(&mut self) {
+        let result = self.read_24(0x1F80).wrapping_mul(self.read_24(0x1F83));
+        self.write_24(0x1F80, result);
+    }
+
+    fn transform_coords(&mut self) {
+        let point = (self.read_word(0x1F81) as i16, self.read_word(0x1F84) as i16, self.read_word(0x1F87) as i16);
+        let rotation = (self.ram[0x1F89] as i16, self.ram[0x1F8A] as i16, self.ram[0x1F8B] as i16);
+        let (x, y) = transform(point, rotation, self.read_word(0x1F90) as i16);
+        self.write_word(0x1F80, x as u16);
+        self.write_word(0x1F83, y as u16);
+    }
+
+    fn sum(&mut self) {
+        let sum = self.ram[..0x800].iter().fold(0_u16, |sum, b| sum.wrapping_add(*b as u16));
+        self.write_word(0x1F80, sum);
+    }
+
+    // Square a signed 24-bit value, to a 48-bit result.
+    fn square(&mut self) {
+        let val = ((self.read_24(0x1F80) << 8) as i32 >> 8) as i64;
+        let result = (val * val) as u64;
+        self.write_24(0x1F83, result as u32);
+        self.write_24(0x1F86, (result >> 24) as u32);
+    }
+}
+
+// Internal
+impl CX4 {
+    fn read_word(&self, offset: usize) -> u16 {
+        make16!(self.ram[(offset + 1) % RAM_SIZE], self.ram[offset % RAM_SIZE])
+    }
+
+    fn write_word(&mut self, offset: usize, data: u16) {
+        self.ram[offset % RAM_SIZE] = lo!(data);
+        self.ram[(offset + 1) % RAM_SIZE] = hi!(data);
+    }
+
+    fn read_24(&self, offset: usize) -> u32 {
+        make24!(self.ram[offset + 2], self.ram[offset + 1], self.ram[offset])
+    }
+
+    fn write_24(&mut self, offset: usize, data: u32) {
+        self.ram[offset] = data as u8;
+        self.ram[offset + 1] = (data >> 8) as u8;
+        self.ram[offset + 2] = (data >> 16) as u8;
+    }
+
+    fn rom_byte(&self, offset: usize) -> u8 {
+        let data = self.rom.data();
+        data[offset % data.len()]
+    }
+
+    // 3D points in ROM are big-endian.
+    fn read_rom_point(&self, offset: usize) -> (i16, i16, i16) {
+        let coord = |i: usize| make16!(self.rom_byte(offset + i), self.rom_byte(offset + i + 1)) as i16;
+        (coord(0), coord(2), coord(4))
+    }
+
+    // Draw a line between two 3D points into the 2bpp bitmap at $6300.
+    fn draw_line(&mut self, start: (i16, i16, i16), end: (i16, i16, i16), colour: u8) {
+        let rotation = (self.ram[0x1F86] as i16, self.ram[0x1F87] as i16, self.ram[0x1F88] as i16);
+        let scale = self.ram[0x1F90] as i16;
+        let (x1, y1) = transform(start, rotation, scale);
+        let (x2, y2) = transform(end, rotation, scale);
+        let (x1, y1) = ((x1 as i32) + 48, (y1 as i32) + 48);
+        let (x2, y2) = ((x2 as i32) + 48, (y2 as i32) + 48);
+        let (step_x, step_y, len) = line_step(x1 as i16, y1 as i16, x2 as i16, y2 as i16);
+
+        // Position in 8.8 fixed point.
+        let (mut x, mut y) = (x1 << 8, y1 << 8);
+        for _ in 0..(if len == 0 {1} else {len}) {
+            if x > 0xFF && y > 0xFF && x < 0x6000 && y < 0x6000 {
+                let (pixel_x, pixel_y) = ((x >> 8) as usize, (y >> 8) as usize);
+                let addr = 0x300 + (pixel_y >> 3) * 0xC0 + (pixel_x >> 3) * 0x10 + (pixel_y & 7) * 2;
+                let bit = 0x80 >> (pixel_x & 7);
+                self.ram[addr] = (self.ram[addr] & !bit) | if test_bit!(colour, 0, u8) {bit} else {0};
+                self.ram[addr + 1] = (self.ram[addr + 1] & !bit) | if test_bit!(colour, 1, u8) {bit} else {0};
+            }
+            x += step_x as i32;
+            y += step_y as i32;
+        }
+    }
+
+    // Set a pixel in 4bpp tile data. Pixels outside of RAM are dropped.
+    fn plot_bitplane_pixel(&mut self, offset: isize, bit: u8, pixel: u8) {
+        if offset < 0 {
+            return;
+        }
+        for (plane, plane_offset) in [0, 1, 16, 17].iter().enumerate() {
+            if test_bit!(pixel, plane, u8) {
+                if let Some(b) = self.ram.get_mut(offset as usize + plane_offset) {
+                    *b |= bit;
+                }
+            }
+        }
+    }
+}
+
+// Convert a 24-bit LoROM address to an offset into the ROM.
+fn rom_offset(addr: u32) -> usize {
+    (((addr >> 1) & 0x3F_8000) | (addr & 0x7FFF)) as usize
+}
+
+impl Expansion for CX4 {
+    fn read(&mut self, bank: u8, addr: u16) -> u8 {
+        match (bank % 0x80, addr) {
+            (0x00..=0x3F, 0x6000..=0x7FFF) => self.read_reg(addr),
+            _ => self.rom_byte(rom_offset(make24!(bank % 0x40, addr)))
+        }
+    }
+
+    fn write(&mut self, bank: u8, addr: u16, data: u8) {
+        if let (0x00..=0x3F, 0x6000..=0x7FFF) = (bank, addr) {
+            self.write_reg(addr, data);
+        }
+    }
+
+    fn clock(&mut self, _cycles: usize) -> Interrupt {
+        Interrupt::default()
+    }
+}
+
+impl Stateful for CX4 {
+    fn save_state(&self, w: &mut StateWriter) {
+        w.write(&self.ram);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), String> {
+        r.read_into(&mut self.ram)
+    }
+}
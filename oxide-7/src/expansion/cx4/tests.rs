@@ -0,0 +1,186 @@
+// The maths routines, and commands sent through the registers.
+use super::*;
+
+fn new_cx4() -> CX4 {
+    let rom = (0..0x10000).map(|i| i as u8).collect();
+    CX4::new(ROM::new(rom, 0x8000))
+}
+
+fn write_24(cx4: &mut CX4, addr: u16, data: u32) {
+    cx4.write(0, addr, data as u8);
+    cx4.write(0, addr + 1, (data >> 8) as u8);
+    cx4.write(0, addr + 2, (data >> 16) as u8);
+}
+
+fn read_24(cx4: &mut CX4, addr: u16) -> u32 {
+    make24!(cx4.read(0, addr + 2), cx4.read(0, addr + 1), cx4.read(0, addr))
+}
+
+#[test]
+fn sin_cos() {
+    assert_eq!(sin(0), 0);
+    assert_eq!(sin(0x80), 32767);
+    assert_eq!(sin(0x180), -32767);
+    assert_eq!(cos(0), 32767);
+    assert_eq!(cos(0x100), -32767);
+    assert_eq!(cos(0x200), cos(0));
+}
+
+#[test]
+fn atan_quadrants() {
+    assert_eq!(atan(1, 0), 0x000);
+    assert_eq!(atan(0, 1), 0x080);
+    assert_eq!(atan(-1, 0), 0x100);
+    assert_eq!(atan(0, -1), 0x180);
+    assert_eq!(atan(100, 100), 0x040);
+    assert_eq!(atan(-100, -100), 0x140);
+    assert_eq!(atan(100, -100), 0x1C0);
+    // tan(30 degrees) is 0.577: 42.67 steps, rounded down.
+    assert_eq!(atan(1000, 577), 42);
+}
+
+#[test]
+fn distances() {
+    assert_eq!(distance(3, 4), 5);
+    assert_eq!(distance(-3, 4), 5);
+    assert_eq!(distance(1, 1), 1);
+    assert_eq!(distance(-300, -400), 500);
+    assert_eq!(set_length(3, 4, 10), (5, 7));
+    assert_eq!(set_length(0, 0, 10), (0, 0));
+}
+
+#[test]
+fn line_steps() {
+    assert_eq!(line_step(0, 0, 10, 5), (256, 128, 11));
+    assert_eq!(line_step(0, 0, -3, 6), (-128, 256, 7));
+    assert_eq!(line_step(5, 5, 5, 5), (0, 0, 0));
+}
+
+#[test]
+fn transform_rotation() {
+    assert_eq!(transform((10, -20, 5), (0, 0, 0), 256), (10, -20));
+    assert_eq!(transform((10, -20, 5), (0, 0, 0), 512), (20, -40));
+    // A quarter turn around each axis.
+    assert_eq!(transform((10, 0, 0), (0, 0, 32), 256), (0, -10));
+    assert_eq!(transform((0, 10, 0), (32, 0, 0), 256), (0, 0));
+    assert_eq!(transform((0, 0, 10), (0, 32, 0), 256), (-10, 0));
+}
+
+#[test]
+fn project_perspective() {
+    // The scale is divided by 144 at a depth of 149.
+    assert_eq!(project((149, -149, 149), (0, 0, 0), 144), (149, -149));
+    // Points further away are smaller.
+    assert_eq!(project((149, -149, 298), (0, 0, 0), 144), (74, -74));
+}
+
+#[test]
+fn multiply_command() {
+    let mut cx4 = new_cx4();
+    write_24(&mut cx4, 0x7F80, 0x001234);
+    write_24(&mut cx4, 0x7F83, 0x000100);
+    cx4.write(0, 0x7F4F, 0x25);
+    assert_eq!(read_24(&mut cx4, 0x7F80), 0x123400);
+}
+
+#[test]
+fn square_command() {
+    let mut cx4 = new_cx4();
+    write_24(&mut cx4, 0x7F80, 0xFFF000);   // -4096
+    cx4.write(0, 0x7F4F, 0x54);
+    assert_eq!(read_24(&mut cx4, 0x7F83), 0x000000);
+    assert_eq!(read_24(&mut cx4, 0x7F86), 0x000001);
+}
+
+#[test]
+fn atan_command() {
+    let mut cx4 = new_cx4();
+    write_24(&mut cx4, 0x7F80, 0xFFFF);     // x = -1
+    write_24(&mut cx4, 0x7F83, 0);          // y = 0
+    cx4.write(0, 0x7F4F, 0x1F);
+    assert_eq!(make16!(cx4.read(0, 0x7F87), cx4.read(0, 0x7F86)), 0x100);
+}
+
+#[test]
+fn polar_command() {
+    let mut cx4 = new_cx4();
+    write_24(&mut cx4, 0x7F80, 0);          // Angle 0
+    write_24(&mut cx4, 0x7F83, 0x100);      // Radius 256
+    cx4.write(0, 0x7F4F, 0x13);
+    assert_eq!(read_24(&mut cx4, 0x7F86), 0xFFFE);
+    assert_eq!(read_24(&mut cx4, 0x7F89), 0);
+}
+
+#[test]
+fn test_commands() {
+    let mut cx4 = new_cx4();
+    cx4.write(0, 0x7F4F, 0x89);
+    assert_eq!(read_24(&mut cx4, 0x7F80), 0x054336);
+
+    cx4.write(0, 0x7F4F, 0x5C);
+    assert_eq!((0..48).map(|i| cx4.read(0, 0x6000 + i)).collect::<Vec<_>>(), TEST_PATTERN.to_vec());
+
+    cx4.write(0, 0x7F4D, 0x0E);
+    cx4.write(0, 0x7F4F, 0x28);
+    assert_eq!(cx4.read(0, 0x7F80), 0x0A);
+}
+
+#[test]
+fn sum_command() {
+    let mut cx4 = new_cx4();
+    for i in 0..0x800 {
+        cx4.write(0, 0x6000 + i, 0xFF);
+    }
+    cx4.write(0, 0x7F4F, 0x40);
+    assert_eq!(make16!(cx4.read(0, 0x7F81), cx4.read(0, 0x7F80)), 0xF800);
+}
+
+#[test]
+fn dma_from_rom() {
+    let mut cx4 = new_cx4();
+    write_24(&mut cx4, 0x7F40, 0x018010);   // ROM offset $8010
+    cx4.write(0, 0x7F43, 4);
+    cx4.write(0, 0x7F44, 0);
+    cx4.write(0, 0x7F45, 0x00);
+    cx4.write(0, 0x7F46, 0x02);
+    cx4.write(0, 0x7F47, 0);
+    assert_eq!((0..4).map(|i| cx4.read(0, 0x6200 + i)).collect::<Vec<_>>(), vec![0x10, 0x11, 0x12, 0x13]);
+}
+
+#[test]
+fn memory_map() {
+    let mut cx4 = new_cx4();
+    // ROM is mirrored in the upper banks, and can't be written.
+    assert_eq!(cx4.read(0x01, 0x8012), 0x12);
+    assert_eq!(cx4.read(0x81, 0x8012), 0x12);
+    assert_eq!(cx4.read(0x41, 0x0012), 0x12);
+    cx4.write(0x01, 0x8012, 0);
+    assert_eq!(cx4.read(0x01, 0x8012), 0x12);
+
+    // The chip is never busy.
+    cx4.write(0, 0x7F5E, 0xFF);
+    assert_eq!(cx4.read(0, 0x7F5E), 0);
+    cx4.write(0x3F, 0x6123, 0x45);
+    assert_eq!(cx4.read(0x80, 0x6123), 0x45);
+}
+
+#[test]
+fn build_oam_single_tile() {
+    let mut rom = vec![0; 0x10000];
+    rom[0x100] = 0;     // No tile list: one tile at the sprite position.
+    let mut cx4 = CX4::new(ROM::new(rom, 0x8000));
+    cx4.write(0, 0x6620, 1);    // One sprite
+    cx4.write(0, 0x6626, 0);    // From OAM index 0
+    cx4.write(0, 0x6220, 0x10); // X
+    cx4.write(0, 0x6222, 0x20); // Y
+    cx4.write(0, 0x6224, 0x30); // Attributes
+    cx4.write(0, 0x6225, 0x40); // Name
+    write_24(&mut cx4, 0x6227, 0x008100);
+    cx4.write(0, 0x7F4D, 0x00);
+    cx4.write(0, 0x7F4F, 0x00);
+
+    assert_eq!((0..4).map(|i| cx4.read(0, 0x6000 + i)).collect::<Vec<_>>(), vec![0x10, 0x20, 0x40, 0x30]);
+    assert_eq!(cx4.read(0, 0x6200) & 3, 2);
+    // Unused entries are moved off screen.
+    assert_eq!(cx4.read(0, 0x6005), 0xE0);
+}
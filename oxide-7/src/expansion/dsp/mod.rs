@@ -2,9 +2,29 @@
 
 mod types;
 
+use alloc::{
+    string::String,
+    vec::Vec
+};
+use serde::{
+    Serialize,
+    Deserialize
+};
+
+use crate::{
+    events::{
+        EventBus,
+        UnsupportedFeature
+    },
+    savestate::{
+        Stateful,
+        StateWriter,
+        StateReader
+    }
+};
 use types::*;
 
-#[derive(Default)]
+#[derive(Default, Serialize, Deserialize)]
 pub struct DSP {
     // Registers
     dp:     u8,     // Data RAM pointer
@@ -31,13 +51,18 @@ pub struct DSP {
     _so:     u16,   // Serial I/O data
 
     // Memory
+    #[serde(skip)]
     prog_rom:   Vec<u8>, // 2048 * 24-bit instructions
+    #[serde(skip)]
     data_rom:   Vec<u8>, // 1024 * 16-bit ro-data
     ram:        Vec<u8>, // 256 * 16-bit data
 
     // Clocking
     pub cycle_fill: f64,
     wait_for_rqm_clear: bool,
+
+    #[serde(skip)]
+    pub events: EventBus,
 }
 
 impl DSP {
@@ -75,6 +100,8 @@ impl DSP {
 
             cycle_fill: 0.0,
             wait_for_rqm_clear: true,
+
+            events: EventBus::default(),
         }
     }
 
@@ -172,6 +199,21 @@ impl DSP {
     }
 }
 
+impl Stateful for DSP {
+    fn save_state(&self, w: &mut StateWriter) {
+        w.write(self);
+    }
+
+    // The ROMs aren't stored, so keep hold of the current ones.
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), String> {
+        let mut state: DSP = r.read()?;
+        core::mem::swap(&mut state.prog_rom, &mut self.prog_rom);
+        core::mem::swap(&mut state.data_rom, &mut self.data_rom);
+        *self = state;
+        Ok(())
+    }
+}
+
 // Instruction decoding.
 impl DSP {
     fn alu_instr(&mut self, instr: Instruction) {
@@ -270,10 +312,8 @@ impl DSP {
             0x0B3 if lo_nybble!(self.dp) == 0xF => self.jump(instr),
             0x0B4 if lo_nybble!(self.dp) != 0xF => self.jump(instr),
 
-            0x0B4 => panic!("Trying to use serial ACK"), // TODO: check these
-            0x0B6 => panic!("Trying to use serial ACK"),
-            0x0B8 => panic!("Trying to use serial ACK"),
-            0x0BA => panic!("Trying to use serial ACK"),
+            // The serial port isn't connected to anything. TODO: check these
+            0x0B4 | 0x0B6 | 0x0B8 | 0x0BA => self.events.report_unsupported(UnsupportedFeature::DSPSerial),
 
             0x0BC if !self.sr.contains(StatusFlags::RQM) => self.jump(instr),
             0x0BE if self.sr.contains(StatusFlags::RQM) => {
@@ -352,14 +392,14 @@ impl DSP {
             let result = (self.acc_b as u32) + (p as u32);
             let result16 = lo32!(result);
             self.flag_b.set_sz(result16);
-            self.flag_b.set(AccFlags::C, result > (std::u16::MAX as u32));
+            self.flag_b.set(AccFlags::C, result > (core::u16::MAX as u32));
             self.flag_b.set_add_ovf(self.acc_b, p, result16);
             self.acc_b = result16;
         } else {
             let result = (self.acc_a as u32) + (p as u32);
             let result16 = lo32!(result);
             self.flag_a.set_sz(result16);
-            self.flag_a.set(AccFlags::C, result > (std::u16::MAX as u32));
+            self.flag_a.set(AccFlags::C, result > (core::u16::MAX as u32));
             self.flag_a.set_add_ovf(self.acc_a, p, result16);
             self.acc_a = result16;
         }
@@ -388,14 +428,14 @@ impl DSP {
             let result = (self.acc_b as u32) + (p as u32) + self.flag_a.carry();
             let result16 = lo32!(result);
             self.flag_b.set_sz(result16);
-            self.flag_b.set(AccFlags::C, result > (std::u16::MAX as u32));
+            self.flag_b.set(AccFlags::C, result > (core::u16::MAX as u32));
             self.flag_b.set_add_ovf(self.acc_b, p, result16);
             self.acc_b = result16;
         } else {
             let result = (self.acc_a as u32) + (p as u32) + self.flag_b.carry();
             let result16 = lo32!(result);
             self.flag_a.set_sz(result16);
-            self.flag_a.set(AccFlags::C, result > (std::u16::MAX as u32));
+            self.flag_a.set(AccFlags::C, result > (core::u16::MAX as u32));
             self.flag_a.set_add_ovf(self.acc_a, p, result16);
             self.acc_a = result16;
         }
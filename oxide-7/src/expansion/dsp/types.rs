@@ -1,7 +1,11 @@
 use bitflags::bitflags;
+use serde::{
+    Serialize,
+    Deserialize
+};
 
 bitflags! {
-    #[derive(Default)]
+    #[derive(Default, Serialize, Deserialize)]
     pub struct AccFlags: u8 {
         const S0 = bit!(5);     // Sign
         const Z = bit!(4);      // Zero
@@ -59,7 +63,7 @@ impl AccFlags {
 }
 
 bitflags! {
-    #[derive(Default)]
+    #[derive(Default, Serialize, Deserialize)]
     pub struct StatusFlags: u16 {
         const RQM = bit!(15, u16);   // Request for master
         const USF1 = bit!(14, u16);  // User flag
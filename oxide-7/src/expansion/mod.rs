@@ -1,21 +1,84 @@
 // Expansion chips found in certain carts.
 
+mod cx4;
 mod dsp;
 mod sa1;
+mod sdd1;
+mod spc7110;
 mod superfx;
 
-use crate::common::Interrupt;
+use alloc::{
+    boxed::Box,
+    string::String
+};
 
+use crate::{
+    common::Interrupt,
+    events::EventBus,
+    mem::rom::{
+        CartInfo,
+        ROM,
+        SRAM
+    },
+    savestate::Stateful
+};
+
+pub use cx4::CX4;
 pub use dsp::DSP;
 pub use sa1::SA1;
+pub use sdd1::SDD1;
+pub use spc7110::{
+    SPC7110,
+    RealTimeClock
+};
 pub use superfx::SuperFX;
 
-pub trait Expansion {
+/// A coprocessor or other chip inside the cartridge.
+/// Chips can be implemented outside the crate, and added to a cart with an ExpansionProvider.
+///
+/// The chip must also implement Stateful, to be included in savestates.
+pub trait Expansion: Stateful {
+    /// Read from the chip. Bank and address are the CPU address, mapped by the cart.
     fn read(&mut self, bank: u8, addr: u16) -> u8;
+    /// Write to the chip. Bank and address are the CPU address, mapped by the cart.
     fn write(&mut self, bank: u8, addr: u16, data: u8);
 
+    /// Run the chip for the number of master cycles provided.
+    /// IRQ is level-triggered: it should be returned on every call while the chip's
+    /// interrupt is pending, until it is acknowledged through the chip's own registers.
+    /// Other sources can assert IRQ at the same time.
     fn clock(&mut self, cycles: usize) -> Interrupt;
-    fn flush(&mut self) {}
+    /// Flush any save data to disk.
+    /// Returns true if anything was written, or an error if the save file couldn't be written.
+    fn flush(&mut self) -> Result<bool, String> {
+        Ok(false)
+    }
+    /// Called at the start of each frame.
+    fn start_frame(&mut self) {}
+    // Connect the chip to the event bus, to report features that aren't emulated.
+    fn set_event_bus(&mut self, _events: EventBus) {}
+    /// Called when the CPU writes to the DMA channel registers at $4300-$437F.
+    /// Chips that intercept DMA transfers from the cart can use this to follow the setup.
+    fn dma_reg_write(&mut self, _addr: u16, _data: u8) {}
+
+    // Get the state of the SuperFX instruction cache.
+    #[cfg(feature = "debug")]
+    fn fx_cache_state(&self) -> Option<crate::debug::FXCacheState> {
+        None
+    }
+}
+
+/// Creates chips outside of the crate, in place of the cart's built-in chip.
+///
+/// A chip created here is given the ROM and SRAM, and receives all cart accesses:
+/// $2200-$FFFF in banks $00-$3F and $80-$BF, and all of banks $40-$7D and $C0-$FF.
+/// Writes are given the bank with the top bit cleared.
+pub trait ExpansionProvider {
+    /// Return true to create the chip for this cart. Otherwise the cart is loaded as normal.
+    fn provides(&self, info: &CartInfo) -> bool;
+
+    /// Create the chip. The ROM is mapped with the bank size of the cart's header format.
+    fn create(&mut self, info: &CartInfo, rom: ROM, sram: Box<dyn SRAM>) -> Box<dyn Expansion>;
 }
 
 impl Expansion for DSP {
@@ -48,4 +111,8 @@ impl Expansion for DSP {
 
         Interrupt::default()
     }
+
+    fn set_event_bus(&mut self, events: EventBus) {
+        self.events = events;
+    }
 }
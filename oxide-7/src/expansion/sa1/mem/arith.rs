@@ -1,5 +1,11 @@
 // Arithmetic unit
 
+use serde::{
+    Serialize,
+    Deserialize
+};
+
+#[derive(Serialize, Deserialize)]
 enum ArithMode {
     Multiply,
     Divide,
@@ -21,6 +27,7 @@ impl From<u8> for ArithMode {
 }
 
 
+#[derive(Serialize, Deserialize)]
 pub struct Arithmetic {
     mode:       ArithMode,
 
@@ -3,6 +3,11 @@
 mod arith;
 mod timer;
 
+use alloc::{
+    boxed::Box,
+    string::String
+};
+
 use arith::Arithmetic;
 use timer::Timer;
 //use varproc::VarLengthProc;
@@ -10,10 +15,19 @@ use timer::Timer;
 use crate::{
     constants::int,
     common::Interrupt,
+    events::{
+        EventBus,
+        UnsupportedFeature
+    },
     mem::{
         MemBus,
         RAM,
         rom::{ROM, SRAM}
+    },
+    savestate::{
+        Stateful,
+        StateWriter,
+        StateReader
     }
 };
 
@@ -25,9 +39,13 @@ const IRQ_VECTOR_LO: u16 = int::IRQ_VECTOR as u16;
 const IRQ_VECTOR_HI: u16 = (int::IRQ_VECTOR as u16) + 1;
 
 use bitflags::bitflags;
+use serde::{
+    Serialize,
+    Deserialize
+};
 
 bitflags! {
-    #[derive(Default)]
+    #[derive(Default, Serialize, Deserialize)]
     struct SNESControl: u8 {
         const IRQ = bit!(7);                // IRQ from SA-1
         const IRQ_VEC = bit!(6);            // IRQ Vector for SNES
@@ -38,7 +56,7 @@ bitflags! {
 }
 
 bitflags! {
-    #[derive(Default)]
+    #[derive(Default, Serialize, Deserialize)]
     struct SA1Control: u8 {
         const IRQ = bit!(7);                // IRQ from SNES
         const WAIT = bit!(6);               // Wait from SNES
@@ -49,7 +67,7 @@ bitflags! {
 }
 
 bitflags! {
-    #[derive(Default)]
+    #[derive(Default, Serialize, Deserialize)]
     struct SNESCPUInt: u8 {
         const SA1_IRQ = bit!(7);
         const DMA_IRQ = bit!(5);
@@ -57,7 +75,7 @@ bitflags! {
 }
 
 bitflags! {
-    #[derive(Default)]
+    #[derive(Default, Serialize, Deserialize)]
     struct SA1CPUInt: u8 {
         const SNES_IRQ = bit!(7);
         const TIMER_IRQ = bit!(6);
@@ -67,7 +85,7 @@ bitflags! {
 }
 
 bitflags! {
-    #[derive(Default)]
+    #[derive(Default, Serialize, Deserialize)]
     struct DMAControl: u8 {
         const ENABLE = bit!(7);
         const PRIORITY = bit!(6);
@@ -79,7 +97,7 @@ bitflags! {
 }
 
 bitflags! {
-    #[derive(Default)]
+    #[derive(Default, Serialize, Deserialize)]
     struct CDMA: u8 {
         const TERMINATE = bit!(7);
         const VIRTUAL_VRAM_WIDTH = bits![4, 3, 2];
@@ -87,7 +105,6 @@ bitflags! {
     }
 }
 
-
 pub struct SA1Bus {
     // Memory
     rom:    ROM,
@@ -138,6 +155,7 @@ pub struct SA1Bus {
     cycle_count:    usize,
     reset_latch: bool,
     wait_latch: bool,
+    events:     EventBus,
 }
 
 impl SA1Bus {
@@ -186,6 +204,7 @@ impl SA1Bus {
             cycle_count:    0,
             reset_latch:    true,
             wait_latch: false,
+            events:     EventBus::default(),
         }
     }
 
@@ -245,11 +264,15 @@ impl SA1Bus {
     }
 
     pub fn get_cycle_count(&mut self) -> usize {
-        std::mem::replace(&mut self.cycle_count, 0)
+        core::mem::replace(&mut self.cycle_count, 0)
+    }
+
+    pub fn flush(&mut self) -> Result<bool, String> {
+        self.bwram.flush()
     }
 
-    pub fn flush(&mut self) {
-        self.bwram.flush();
+    pub fn set_event_bus(&mut self, events: EventBus) {
+        self.events = events;
     }
 }
 
@@ -347,6 +370,93 @@ impl MemBus for SA1Bus {
     }
 }
 
+// The ROM and mapping type are fixed, so aren't stored.
+impl Stateful for SA1Bus {
+    fn save_state(&self, w: &mut StateWriter) {
+        self.bwram.save_state(w);
+
+        w.write(&self.iram);
+        w.write(&self.rom_bank_c);
+        w.write(&self.rom_bank_d);
+        w.write(&self.rom_bank_e);
+        w.write(&self.rom_bank_f);
+        w.write(&self.bwram_bitmap);
+
+        w.write(&self.dma_control);
+        w.write(&self.cdma_params);
+        w.write(&self.dma_src_addr);
+        w.write(&self.dma_dst_addr);
+        w.write(&self.dma_counter);
+        w.write(&self.dma_bitmap_regs);
+
+        w.write(&self.sa1_cpu_control);
+        w.write(&self.sa1_int_enable);
+        w.write(&self.sa1_int_pending);
+        w.write(&self.sa1_rst_vector);
+        w.write(&self.sa1_nmi_vector);
+        w.write(&self.sa1_irq_vector);
+        w.write(&self.sa1_bw_map);
+        w.write(&self.sa1_bw_write_enable);
+        w.write(&self.sa1_iram_write);
+
+        w.write(&self.snes_cpu_control);
+        w.write(&self.snes_int_enable);
+        w.write(&self.snes_nmi_vector);
+        w.write(&self.snes_irq_vector);
+        w.write(&self.snes_bw_map);
+        w.write(&self.snes_bw_write_enable);
+        w.write(&self.snes_iram_write);
+
+        w.write(&self.timer);
+        w.write(&self.arith);
+        w.write(&self.cycle_count);
+        w.write(&self.reset_latch);
+        w.write(&self.wait_latch);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), String> {
+        self.bwram.load_state(r)?;
+
+        r.read_into(&mut self.iram)?;
+        r.read_into(&mut self.rom_bank_c)?;
+        r.read_into(&mut self.rom_bank_d)?;
+        r.read_into(&mut self.rom_bank_e)?;
+        r.read_into(&mut self.rom_bank_f)?;
+        r.read_into(&mut self.bwram_bitmap)?;
+
+        r.read_into(&mut self.dma_control)?;
+        r.read_into(&mut self.cdma_params)?;
+        r.read_into(&mut self.dma_src_addr)?;
+        r.read_into(&mut self.dma_dst_addr)?;
+        r.read_into(&mut self.dma_counter)?;
+        r.read_into(&mut self.dma_bitmap_regs)?;
+
+        r.read_into(&mut self.sa1_cpu_control)?;
+        r.read_into(&mut self.sa1_int_enable)?;
+        r.read_into(&mut self.sa1_int_pending)?;
+        r.read_into(&mut self.sa1_rst_vector)?;
+        r.read_into(&mut self.sa1_nmi_vector)?;
+        r.read_into(&mut self.sa1_irq_vector)?;
+        r.read_into(&mut self.sa1_bw_map)?;
+        r.read_into(&mut self.sa1_bw_write_enable)?;
+        r.read_into(&mut self.sa1_iram_write)?;
+
+        r.read_into(&mut self.snes_cpu_control)?;
+        r.read_into(&mut self.snes_int_enable)?;
+        r.read_into(&mut self.snes_nmi_vector)?;
+        r.read_into(&mut self.snes_irq_vector)?;
+        r.read_into(&mut self.snes_bw_map)?;
+        r.read_into(&mut self.snes_bw_write_enable)?;
+        r.read_into(&mut self.snes_iram_write)?;
+
+        r.read_into(&mut self.timer)?;
+        r.read_into(&mut self.arith)?;
+        r.read_into(&mut self.cycle_count)?;
+        r.read_into(&mut self.reset_latch)?;
+        r.read_into(&mut self.wait_latch)
+    }
+}
+
 // Internal: SNES side
 impl SA1Bus {
     fn read_snes_port(&mut self, addr: u16) -> u8 {
@@ -446,8 +556,10 @@ impl SA1Bus {
             0x230A => self.arith.read_result_4(),
             0x230B => self.arith.read_ovf(),
 
-            0x230C => 0,    // Var-length port
-            0x230D => 0,    // Var-length port
+            0x230C | 0x230D => {    // Var-length port
+                self.events.report_unsupported(UnsupportedFeature::SA1VariableLength);
+                0
+            },
 
             _ => 0,
         }
@@ -632,6 +744,9 @@ impl SA1Bus {
     }
 
     fn dma_transfer(&mut self) {
+        if self.dma_counter > 0 {
+            self.events.report_unsupported(UnsupportedFeature::SA1DMA);
+        }
         for _i in 0..self.dma_counter {
             // transfer
         }
@@ -1,9 +1,13 @@
 // SA1 Timer
 
 use bitflags::bitflags;
+use serde::{
+    Serialize,
+    Deserialize
+};
 
 bitflags! {
-    #[derive(Default)]
+    #[derive(Default, Serialize, Deserialize)]
     struct TimerControl: u8 {
         const TIMER_MODE = bit!(7); // 1 = linear timer
         const VEN = bit!(1);        // V-Enable
@@ -11,6 +15,7 @@ bitflags! {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct Timer {
     timer_control:  TimerControl,
 
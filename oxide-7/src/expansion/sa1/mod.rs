@@ -2,15 +2,33 @@
 
 mod mem;
 
+use alloc::{
+    boxed::Box,
+    string::String
+};
+
 use super::Expansion;
 use crate::{
     common::Interrupt,
+    constants::machine::MachineTiming,
     cpu::CPU,
-    mem::rom::{ROM, SRAM}
+    events::EventBus,
+    mem::rom::{ROM, SRAM},
+    savestate::{
+        Stateful,
+        StateWriter,
+        StateReader
+    }
 };
 
 use mem::SA1Bus;
 
+// Internal operations take 2 master cycles at the SA-1's clock rate.
+const SA1_TIMING: MachineTiming = MachineTiming {
+    internal_op: 2,
+    ..MachineTiming::NTSC
+};
+
 pub struct SA1 {
     cpu: CPU<SA1Bus>,
     cycle_count: isize,
@@ -20,7 +38,7 @@ impl SA1 {
     pub fn new(rom: ROM, lo_rom: bool, sram: Box<dyn SRAM>) -> Self {
         let mem = SA1Bus::new(rom, lo_rom, sram);
         Self {
-            cpu: CPU::new(mem, 2),
+            cpu: CPU::new(mem, &SA1_TIMING),
             cycle_count: 0,
         }
     }
@@ -46,7 +64,24 @@ impl Expansion for SA1 {
         self.cpu.get_bus().check_snes_interrupts()
     }
 
-    fn flush(&mut self) {
-        self.cpu.get_bus().flush();
+    fn flush(&mut self) -> Result<bool, String> {
+        self.cpu.get_bus().flush()
+    }
+
+    fn set_event_bus(&mut self, events: EventBus) {
+        self.cpu.get_bus().set_event_bus(events.clone());
+        self.cpu.set_event_bus(events);
+    }
+}
+
+impl Stateful for SA1 {
+    fn save_state(&self, w: &mut StateWriter) {
+        self.cpu.save_state(w);
+        w.write(&self.cycle_count);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), String> {
+        self.cpu.load_state(r)?;
+        r.read_into(&mut self.cycle_count)
     }
 }
\ No newline at end of file
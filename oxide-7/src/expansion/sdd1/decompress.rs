@@ -0,0 +1,273 @@
+// The S-DD1 decompressor.
+// Compressed data is an arithmetic-coded bitstream, split into Golomb-coded runs.
+// Based on the algorithm reverse engineered by Andreas Naive.
+
+// For each estimation state: the Golomb code length, and the next state after an MPS or LPS run.
+struct State {
+    code_number:    usize,
+    next_if_mps:    u8,
+    next_if_lps:    u8,
+}
+
+const fn state(code_number: usize, next_if_mps: u8, next_if_lps: u8) -> State {
+    State {
+        code_number:    code_number,
+        next_if_mps:    next_if_mps,
+        next_if_lps:    next_if_lps,
+    }
+}
+
+const EVOLUTION_TABLE: [State; 33] = [
+    state(0, 25, 25),
+    state(0,  2,  1),
+    state(0,  3,  1),
+    state(0,  4,  2),
+    state(0,  5,  3),
+    state(1,  6,  4),
+    state(1,  7,  5),
+    state(1,  8,  6),
+    state(1,  9,  7),
+    state(2, 10,  8),
+    state(2, 11,  9),
+    state(2, 12, 10),
+    state(2, 13, 11),
+    state(3, 14, 12),
+    state(3, 15, 13),
+    state(3, 16, 14),
+    state(3, 17, 15),
+    state(4, 18, 16),
+    state(4, 19, 17),
+    state(5, 20, 18),
+    state(5, 21, 19),
+    state(6, 22, 20),
+    state(6, 23, 21),
+    state(7, 24, 22),
+    state(7, 24, 23),
+    state(0, 26,  1),
+    state(1, 27,  2),
+    state(2, 28,  4),
+    state(3, 29,  8),
+    state(4, 30, 12),
+    state(5, 31, 16),
+    state(6, 32, 18),
+    state(7, 24, 22),
+];
+
+// Reads Golomb code words from the compressed data.
+#[derive(Default, Clone)]
+struct InputManager {
+    offset:     u32,
+    bit_count:  u8,
+}
+
+impl InputManager {
+    // The first 4 bits are the header.
+    fn init(&mut self, offset: u32) {
+        self.offset = offset;
+        self.bit_count = 4;
+    }
+
+    // Returns the next code word, aligned to the top bit.
+    fn get_code_word<F: Fn(u32) -> u8>(&mut self, code_length: u8, read: &F) -> u8 {
+        let mut code_word = read(self.offset) << self.bit_count;
+        self.bit_count += 1;
+
+        if test_bit!(code_word, 7, u8) {
+            code_word |= ((read(self.offset.wrapping_add(1)) as u16) >> (9 - self.bit_count)) as u8;
+            self.bit_count += code_length;
+        }
+
+        if test_bit!(self.bit_count, 3, u8) {
+            self.offset = self.offset.wrapping_add(1);
+            self.bit_count &= 7;
+        }
+
+        code_word
+    }
+}
+
+// A code word with the top bit clear is a full run of 2^N MPS.
+// Otherwise the next N bits hold the run length before a single LPS, inverted and reversed.
+pub fn run_count(code_word: u8, code_number: u8) -> u8 {
+    let bits = !code_word & ((1 << code_number) - 1);
+    let mut count = 0;
+    for i in 0..code_number {
+        if test_bit!(bits, i, u8) {
+            count |= bit!(code_number - 1 - i);
+        }
+    }
+    count
+}
+
+// Generates the bits for one Golomb code length.
+#[derive(Default, Clone)]
+struct BitGenerator {
+    mps_count:  u8,
+    lps_index:  bool,
+}
+
+impl BitGenerator {
+    // Returns the bit (1 for LPS), and true if it was the last in the run.
+    fn get_bit<F: Fn(u32) -> u8>(&mut self, code_number: u8, input: &mut InputManager, read: &F) -> (bool, bool) {
+        if self.mps_count == 0 && !self.lps_index {
+            let code_word = input.get_code_word(code_number, read);
+            if test_bit!(code_word, 7, u8) {
+                self.lps_index = true;
+                self.mps_count = run_count(code_word >> (code_number ^ 7), code_number);
+            } else {
+                self.mps_count = 1 << code_number;
+            }
+        }
+
+        let bit = if self.mps_count > 0 {
+            self.mps_count -= 1;
+            false
+        } else {
+            self.lps_index = false;
+            true
+        };
+
+        (bit, self.mps_count == 0 && !self.lps_index)
+    }
+}
+
+#[derive(Default, Clone, Copy)]
+struct ContextInfo {
+    status: u8,
+    mps:    bool,
+}
+
+#[derive(Default, Clone)]
+pub struct Decompressor {
+    input:          InputManager,
+    generators:     [BitGenerator; 8],
+    contexts:       [ContextInfo; 32],
+
+    // Context model
+    bitplanes_info:     u8,
+    context_bits_info:  u8,
+    bit_number:         u8,
+    current_bitplane:   u8,
+    prev_bitplane_bits: [u16; 8],
+
+    // Output
+    r0: u8,
+    r1: u8,
+    r2: u8,
+}
+
+impl Decompressor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Start decompressing from the address provided. The header is in the top 4 bits of the first byte.
+    pub fn init<F: Fn(u32) -> u8>(&mut self, offset: u32, read: &F) {
+        let header = read(offset);
+
+        self.input.init(offset);
+        self.generators = Default::default();
+        self.contexts = [ContextInfo::default(); 32];
+
+        self.bitplanes_info = header & 0xC0;
+        self.context_bits_info = header & 0x30;
+        self.bit_number = 0;
+        self.current_bitplane = match self.bitplanes_info {
+            0x00 => 1,
+            0x40 => 7,
+            0x80 => 3,
+            _ => 0
+        };
+        self.prev_bitplane_bits = [0; 8];
+
+        self.r0 = 1;
+    }
+
+    // Get the next byte of decompressed data.
+    // In the 2, 4 and 8 bitplane modes, bytes come out in pairs of interleaved bitplanes.
+    // In mode 7, each byte is a whole 8-bit pixel.
+    pub fn read<F: Fn(u32) -> u8>(&mut self, read: &F) -> u8 {
+        if self.bitplanes_info == 0xC0 {
+            self.r1 = 0;
+            for i in 0..8 {
+                if self.get_context_bit(read) {
+                    self.r1 |= bit!(i);
+                }
+            }
+            self.r1
+        } else if self.r0 == 0 {
+            self.r0 = 0xFF;
+            self.r2
+        } else {
+            self.r1 = 0;
+            self.r2 = 0;
+            for i in (0..8).rev() {
+                if self.get_context_bit(read) {
+                    self.r1 |= bit!(i);
+                }
+                if self.get_context_bit(read) {
+                    self.r2 |= bit!(i);
+                }
+            }
+            self.r0 = 0;
+            self.r1
+        }
+    }
+}
+
+// Internal
+impl Decompressor {
+    // Find the context of the next bit from the previous bits in the same bitplane.
+    fn get_context_bit<F: Fn(u32) -> u8>(&mut self, read: &F) -> bool {
+        match self.bitplanes_info {
+            0x00 => self.current_bitplane ^= 1,
+            0x40 => {
+                self.current_bitplane ^= 1;
+                if (self.bit_number & 0x7F) == 0 {
+                    self.current_bitplane = (self.current_bitplane + 2) & 7;
+                }
+            },
+            0x80 => {
+                self.current_bitplane ^= 1;
+                if (self.bit_number & 0x7F) == 0 {
+                    self.current_bitplane ^= 2;
+                }
+            },
+            _ => self.current_bitplane = self.bit_number & 7,
+        }
+
+        let context_bits = self.prev_bitplane_bits[self.current_bitplane as usize];
+        let context = ((self.current_bitplane & 1) << 4) | match self.context_bits_info {
+            0x00 => ((context_bits & 0x01C0) >> 5) | (context_bits & 1),
+            0x10 => ((context_bits & 0x0180) >> 5) | (context_bits & 1),
+            0x20 => ((context_bits & 0x00C0) >> 5) | (context_bits & 1),
+            _    => ((context_bits & 0x0180) >> 5) | (context_bits & 3),
+        } as u8;
+
+        let bit = self.get_probable_bit(context as usize, read);
+        self.prev_bitplane_bits[self.current_bitplane as usize] = (context_bits << 1) | (bit as u16);
+        self.bit_number = self.bit_number.wrapping_add(1);
+        bit
+    }
+
+    // Decode a bit, and update the probability estimate for its context.
+    fn get_probable_bit<F: Fn(u32) -> u8>(&mut self, context: usize, read: &F) -> bool {
+        let info = self.contexts[context];
+        let state = &EVOLUTION_TABLE[info.status as usize];
+
+        let (lps, end_of_run) = self.generators[state.code_number].get_bit(state.code_number as u8, &mut self.input, read);
+
+        if end_of_run {
+            if lps {
+                if info.status < 2 {
+                    self.contexts[context].mps = !info.mps;
+                }
+                self.contexts[context].status = state.next_if_lps;
+            } else {
+                self.contexts[context].status = state.next_if_mps;
+            }
+        }
+
+        lps != info.mps
+    }
+}
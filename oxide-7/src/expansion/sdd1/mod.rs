@@ -0,0 +1,197 @@
+// Nintendo S-DD1
+// A graphics decompression chip used in Star Ocean and Street Fighter Alpha 2.
+// The chip watches the CPU's DMA setup, and replaces reads from compressed data
+// with the decompressed stream while a DMA is running.
+mod decompress;
+#[cfg(test)]
+mod tests;
+
+use alloc::string::String;
+use serde::{
+    Serialize,
+    Deserialize
+};
+
+use crate::{
+    common::Interrupt,
+    mem::rom::ROM,
+    savestate::{
+        Stateful,
+        StateWriter,
+        StateReader
+    }
+};
+
+use decompress::Decompressor;
+use super::Expansion;
+
+const PAGE_SIZE: usize = 0x10_0000;
+
+// Maps ROM into banks $C0-$FF in 1MB pages. Larger carts need more than 4MB.
+struct MemoryMapper {
+    rom:    ROM,
+    banks:  [u8; 4],    // $4804-$4807: ROM page for $C0-$CF, $D0-$DF, $E0-$EF and $F0-$FF.
+}
+
+impl MemoryMapper {
+    fn read_rom(&self, offset: usize) -> u8 {
+        let data = self.rom.data();
+        data[offset % data.len()]
+    }
+
+    // Banks $C0-$FF. This is also where the decompressor reads from.
+    fn read(&self, addr: u32) -> u8 {
+        let page = self.banks[((addr >> 20) & 3) as usize] & 0xF;
+        self.read_rom((page as usize) * PAGE_SIZE + (addr & 0xF_FFFF) as usize)
+    }
+
+    // Banks $00-$3F and $80-$BF are LoROM.
+    // If bit 7 of $4805 or $4807 is set, that page is used for $20-$3F or $A0-$BF respectively.
+    fn read_lo(&self, bank: u8, addr: u16) -> u8 {
+        let offset = ((bank % 0x40) as usize) * 0x8000 + (addr % 0x8000) as usize;
+        let select = if bank < 0x80 {self.banks[1]} else {self.banks[3]};
+        if test_bit!(bank, 5, u8) && test_bit!(select, 7, u8) {
+            self.read_rom(((select & 0xF) as usize) * PAGE_SIZE + (offset % PAGE_SIZE))
+        } else {
+            self.read_rom(offset)
+        }
+    }
+}
+
+// The source address and size of each DMA channel, copied when the CPU writes them.
+#[derive(Default, Clone, Copy, Serialize, Deserialize)]
+struct DMAChannel {
+    a_bus_addr: u16,
+    a_bus_bank: u8,
+    count:      u16,
+}
+
+impl DMAChannel {
+    fn src_addr(&self) -> u32 {
+        make24!(self.a_bus_bank, self.a_bus_addr)
+    }
+}
+
+pub struct SDD1 {
+    mapper:             MemoryMapper,
+
+    dma_enable:         u8, // $4800: DMA channels that can be decompressed.
+    decompress_enable:  u8, // $4801: DMA channels to decompress on the next transfer. Cleared when they finish.
+    channels:           [DMAChannel; 8],
+
+    decompressor:       Decompressor,
+    decompressing:      bool,   // True from the first read of a transfer until the last.
+}
+
+impl SDD1 {
+    pub fn new(rom: ROM) -> Self {
+        SDD1 {
+            mapper:             MemoryMapper {
+                rom:    rom,
+                banks:  [0, 1, 2, 3],
+            },
+
+            dma_enable:         0,
+            decompress_enable:  0,
+            channels:           [DMAChannel::default(); 8],
+
+            decompressor:       Decompressor::new(),
+            decompressing:      false,
+        }
+    }
+
+    fn read_reg(&self, addr: u16) -> u8 {
+        match addr {
+            0x4800 => self.dma_enable,
+            0x4801 => self.decompress_enable,
+            0x4804..=0x4807 => self.mapper.banks[(addr - 0x4804) as usize],
+            _ => 0
+        }
+    }
+
+    fn write_reg(&mut self, addr: u16, data: u8) {
+        match addr {
+            0x4800 => self.dma_enable = data,
+            0x4801 => self.decompress_enable = data,
+            0x4804..=0x4807 => self.mapper.banks[(addr - 0x4804) as usize] = data & 0x8F,
+            _ => {}
+        }
+    }
+
+    // Reads from a channel's source address are decompressed if it is enabled.
+    // S-DD1 transfers always use a fixed address, so this only needs to check the start.
+    fn read_hi(&mut self, addr: u32) -> u8 {
+        let active = self.dma_enable & self.decompress_enable;
+        for chan in 0..8 {
+            if test_bit!(active, chan, u8) && self.channels[chan].src_addr() == addr {
+                let mapper = &self.mapper;
+                if !self.decompressing {
+                    self.decompressor.init(addr, &|addr| mapper.read(addr));
+                    self.decompressing = true;
+                }
+                let data = self.decompressor.read(&|addr| mapper.read(addr));
+
+                let channel = &mut self.channels[chan];
+                channel.count = channel.count.wrapping_sub(1);
+                if channel.count == 0 {
+                    self.decompressing = false;
+                    self.decompress_enable &= !bit!(chan);
+                }
+                return data;
+            }
+        }
+
+        self.mapper.read(addr)
+    }
+}
+
+impl Expansion for SDD1 {
+    fn read(&mut self, bank: u8, addr: u16) -> u8 {
+        match (bank, addr) {
+            (0x00..=0x3F | 0x80..=0xBF, 0x4800..=0x4807) => self.read_reg(addr),
+            (0x00..=0x3F | 0x80..=0xBF, _) => self.mapper.read_lo(bank, addr),
+            _ => self.read_hi(make24!(bank, addr))
+        }
+    }
+
+    fn write(&mut self, bank: u8, addr: u16, data: u8) {
+        if let (0x00..=0x3F, 0x4800..=0x4807) = (bank, addr) {
+            self.write_reg(addr, data);
+        }
+    }
+
+    fn clock(&mut self, _cycles: usize) -> Interrupt {
+        Interrupt::default()
+    }
+
+    fn dma_reg_write(&mut self, addr: u16, data: u8) {
+        let channel = &mut self.channels[((addr >> 4) & 7) as usize];
+        match addr & 0xF {
+            0x2 => channel.a_bus_addr = set_lo!(channel.a_bus_addr, data),
+            0x3 => channel.a_bus_addr = set_hi!(channel.a_bus_addr, data),
+            0x4 => channel.a_bus_bank = data,
+            0x5 => channel.count = set_lo!(channel.count, data),
+            0x6 => channel.count = set_hi!(channel.count, data),
+            _ => {}
+        }
+    }
+}
+
+// DMA transfers run to completion, so a savestate is never taken mid-decompression.
+impl Stateful for SDD1 {
+    fn save_state(&self, w: &mut StateWriter) {
+        w.write(&self.mapper.banks);
+        w.write(&self.dma_enable);
+        w.write(&self.decompress_enable);
+        w.write(&self.channels);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), String> {
+        r.read_into(&mut self.mapper.banks)?;
+        r.read_into(&mut self.dma_enable)?;
+        r.read_into(&mut self.decompress_enable)?;
+        r.read_into(&mut self.channels)?;
+        self.decompressing = false;
+        Ok(())
+    }
+}
@@ -0,0 +1,100 @@
+// The decompressor, memory mapping, and DMA interception.
+use super::*;
+
+// 8MB of ROM. Each 64kB bank is filled with its number, apart from the data provided at the start.
+fn new_sdd1(data: &[u8]) -> SDD1 {
+    let mut rom: Vec<u8> = (0..0x80_0000).map(|i| (i >> 16) as u8).collect();
+    rom[..data.len()].copy_from_slice(data);
+    SDD1::new(ROM::new(rom, 0x8000))
+}
+
+fn decompress(data: &[u8], len: usize) -> Vec<u8> {
+    let read = |addr: u32| data.get(addr as usize).cloned().unwrap_or(0);
+    let mut decompressor = Decompressor::new();
+    decompressor.init(0, &read);
+    (0..len).map(|_| decompressor.read(&read)).collect()
+}
+
+// Set up a DMA from $C0:0000.
+fn setup_dma(sdd1: &mut SDD1, chan: u16, count: u16) {
+    let base = 0x4300 | (chan << 4);
+    sdd1.dma_reg_write(base + 2, 0x00);
+    sdd1.dma_reg_write(base + 3, 0x00);
+    sdd1.dma_reg_write(base + 4, 0xC0);
+    sdd1.dma_reg_write(base + 5, lo!(count));
+    sdd1.dma_reg_write(base + 6, hi!(count));
+}
+
+#[test]
+fn run_count_table() {
+    let expected = [
+        0x00, 0x00, 0x01, 0x00, 0x03, 0x01, 0x02, 0x00,
+        0x07, 0x03, 0x05, 0x01, 0x06, 0x02, 0x04, 0x00
+    ];
+    for (i, count) in expected.iter().enumerate().skip(1) {
+        let code_number = 7 - (i as u8).leading_zeros() as u8;
+        assert_eq!(decompress::run_count(i as u8, code_number), *count, "index {}", i);
+    }
+}
+
+#[test]
+fn zero_stream() {
+    for header in [0x00, 0x40, 0x80, 0xC0, 0x30].iter() {
+        let data = [*header, 0, 0, 0];
+        assert_eq!(decompress(&data, 64), vec![0; 64], "header {:X}", header);
+    }
+}
+
+#[test]
+fn mode7_pixel() {
+    // Header: 8 bits per pixel. The first code word is an immediate LPS, which flips the MPS of the even bitplanes.
+    assert_eq!(decompress(&[0xC8], 1), vec![0x55]);
+}
+
+#[test]
+fn mmc_banks() {
+    let mut sdd1 = new_sdd1(&[]);
+    assert_eq!(sdd1.read(0xC0, 0x0000), 0x00);
+    assert_eq!(sdd1.read(0xD0, 0x0000), 0x10);
+    assert_eq!(sdd1.read(0xFF, 0xFFFF), 0x3F);
+
+    sdd1.write(0x00, 0x4807, 0x05);
+    assert_eq!(sdd1.read(0x00, 0x4807), 0x05);
+    assert_eq!(sdd1.read(0xF2, 0x0000), 0x52);
+
+    // LoROM banks are remapped only when bit 7 is set.
+    assert_eq!(sdd1.read(0xA0, 0x8000), 0x10);
+    sdd1.write(0x00, 0x4807, 0x85);
+    assert_eq!(sdd1.read(0xA0, 0x8000), 0x50);
+    assert_eq!(sdd1.read(0x20, 0x8000), 0x10);
+    assert_eq!(sdd1.read(0x80, 0x8000), 0x00);
+}
+
+#[test]
+fn dma_decompression() {
+    let mut sdd1 = new_sdd1(&[0xC8]);
+    setup_dma(&mut sdd1, 2, 2);
+    sdd1.write(0x00, 0x4800, 0x04);
+    sdd1.write(0x00, 0x4801, 0x04);
+
+    assert_eq!(sdd1.read(0xC0, 0x0000), 0x55);
+    sdd1.read(0xC0, 0x0000);
+
+    // The channel is disabled when the transfer is done.
+    assert_eq!(sdd1.read(0x00, 0x4801), 0x00);
+    assert_eq!(sdd1.read(0xC0, 0x0000), 0xC8);
+}
+
+#[test]
+fn dma_not_enabled() {
+    let mut sdd1 = new_sdd1(&[0xC8]);
+    setup_dma(&mut sdd1, 0, 2);
+    sdd1.write(0x00, 0x4800, 0x01);
+
+    assert_eq!(sdd1.read(0xC0, 0x0000), 0xC8);
+
+    // Other addresses are not intercepted.
+    sdd1.write(0x00, 0x4801, 0x01);
+    assert_eq!(sdd1.read(0xC0, 0x0001), 0x00);
+    assert_eq!(sdd1.read(0x00, 0x4801), 0x01);
+}
@@ -0,0 +1,242 @@
+// The SPC7110 decompressor.
+// Compressed data is an arithmetic-coded bitstream of 1, 2 or 4 bit pixels.
+// Each pixel is coded as an index into a most-recently-used list of colours, predicted from its neighbours.
+// Based on the algorithm reverse engineered by neviksti and byuu.
+use serde::{
+    Serialize,
+    Deserialize
+};
+
+const MPS: u8 = 0;
+const LPS: u8 = 1;
+const HALF: u8 = 0x55;
+const MAX: u16 = 0xFF;
+
+// For each model state: the probability of the LPS, and the next state after rescaling from an MPS or LPS.
+struct ModelState {
+    probability:    u8,
+    next:           [u8; 2],
+}
+
+const fn state(probability: u8, next_if_mps: u8, next_if_lps: u8) -> ModelState {
+    ModelState {
+        probability:    probability,
+        next:           [next_if_mps, next_if_lps],
+    }
+}
+
+const EVOLUTION_TABLE: [ModelState; 53] = [
+    state(0x5A,  1,  1), state(0x25,  2,  6), state(0x11,  3,  8),
+    state(0x08,  4, 10), state(0x03,  5, 12), state(0x01,  5, 15),
+
+    state(0x5A,  7,  7), state(0x3F,  8, 19), state(0x2C,  9, 21),
+    state(0x20, 10, 22), state(0x17, 11, 23), state(0x11, 12, 25),
+    state(0x0C, 13, 26), state(0x09, 14, 28), state(0x07, 15, 29),
+    state(0x05, 16, 31), state(0x04, 17, 32), state(0x03, 18, 34),
+    state(0x02,  5, 35),
+
+    state(0x5A, 20, 20), state(0x48, 21, 39), state(0x3A, 22, 40),
+    state(0x2E, 23, 42), state(0x26, 24, 44), state(0x1F, 25, 45),
+    state(0x19, 26, 46), state(0x15, 27, 25), state(0x11, 28, 26),
+    state(0x0E, 29, 26), state(0x0B, 30, 27), state(0x09, 31, 28),
+    state(0x08, 32, 29), state(0x07, 33, 30), state(0x05, 34, 31),
+    state(0x04, 35, 33), state(0x04, 36, 33), state(0x03, 37, 34),
+    state(0x02, 38, 35), state(0x02,  5, 36),
+
+    state(0x58, 40, 39), state(0x4D, 41, 47), state(0x43, 42, 48),
+    state(0x3B, 43, 49), state(0x34, 44, 50), state(0x2E, 45, 51),
+    state(0x29, 46, 44), state(0x25, 24, 45),
+
+    state(0x56, 48, 47), state(0x4F, 49, 47), state(0x47, 50, 48),
+    state(0x41, 51, 49), state(0x3C, 52, 50), state(0x37, 43, 51),
+];
+
+const INITIAL_COLOUR_MAP: u64 = 0xFEDC_BA98_7654_3210;
+
+#[derive(Default, Clone, Copy, Serialize, Deserialize)]
+struct Context {
+    prediction: u8,     // Index into the evolution table.
+    swap:       bool,   // Exchange the roles of the MPS and LPS.
+}
+
+// Unpack big-endian packed pixels: the odd bits are returned in the lower half, the even bits in the upper half.
+fn deinterleave(data: u64, bits: u32) -> u32 {
+    let data = data & ((1 << bits) - 1);
+    let data = 0x5555_5555_5555_5555 & ((data << bits) | (data >> 1));
+    let data = 0x3333_3333_3333_3333 & (data | (data >> 1));
+    let data = 0x0F0F_0F0F_0F0F_0F0F & (data | (data >> 2));
+    let data = 0x00FF_00FF_00FF_00FF & (data | (data >> 4));
+    let data = 0x0000_FFFF_0000_FFFF & (data | (data >> 8));
+    (data | (data >> 16)) as u32
+}
+
+// Move a colour in the list of nybbles to the front.
+fn move_to_front(list: u64, colour: u64) -> u64 {
+    for n in (0..64).step_by(4) {
+        if ((list >> n) & 0xF) == colour {
+            let mask = !0xF_u64 << n;
+            return (list & mask) + ((list << 4) & !mask) + colour;
+        }
+    }
+    list
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Decompressor {
+    contexts:   [[Context; 15]; 5], // Not all of these are used.
+
+    bpp:        usize,
+    offset:     u32,    // Data ROM offset of the next input byte.
+    bits:       u8,     // Bits left in the low byte of the input.
+    range:      u16,
+    input:      u16,
+    output:     u8,
+    pixels:     u64,
+    colour_map: u64,    // Most recently used colours.
+    result:     u32,    // 8 pixels, in bitplane format.
+}
+
+impl Decompressor {
+    pub fn new() -> Self {
+        Decompressor {
+            contexts:   [[Context::default(); 15]; 5],
+
+            bpp:        1,
+            offset:     0,
+            bits:       8,
+            range:      MAX + 1,
+            input:      0,
+            output:     0,
+            pixels:     0,
+            colour_map: INITIAL_COLOUR_MAP,
+            result:     0,
+        }
+    }
+
+    // Start decompressing from the data ROM offset. Mode 0, 1 and 2 are 1, 2 and 4 bits per pixel.
+    pub fn init<F: Fn(u32) -> u8>(&mut self, mode: u8, offset: u32, read: &F) {
+        self.contexts = [[Context::default(); 15]; 5];
+        self.bpp = 1 << mode;
+        self.offset = offset;
+        self.bits = 8;
+        self.range = MAX + 1;
+        let hi = self.next_byte(read);
+        let lo = self.next_byte(read);
+        self.input = make16!(hi, lo);
+        self.output = 0;
+        self.pixels = 0;
+        self.colour_map = INITIAL_COLOUR_MAP;
+    }
+
+    pub fn bpp(&self) -> usize {
+        self.bpp
+    }
+
+    // The last 8 pixels decoded. For 2bpp this is two bitplanes, for 4bpp it is four.
+    pub fn result(&self) -> u32 {
+        self.result
+    }
+
+    // Decode the next 8 pixels.
+    pub fn decode<F: Fn(u32) -> u8>(&mut self, read: &F) {
+        for pixel in 0..8 {
+            let mut map = self.colour_map;
+            let mut diff = 0;
+
+            // Find which of the neighbouring pixels match, and use them as the most likely colours.
+            if self.bpp > 1 {
+                let (pa, pb, pc) = if self.bpp == 2 {
+                    ((self.pixels >> 2) & 3, (self.pixels >> 14) & 3, (self.pixels >> 16) & 3)
+                } else {
+                    (self.pixels & 0xF, (self.pixels >> 28) & 0xF, (self.pixels >> 32) & 0xF)
+                };
+
+                if pa != pb || pb != pc {
+                    let matched = pa ^ pb ^ pc;
+                    diff = if matched == pa {
+                        2
+                    } else if matched == pb {
+                        1
+                    } else if matched == pc {
+                        3
+                    } else {
+                        4
+                    };
+                }
+
+                self.colour_map = move_to_front(self.colour_map, pa);
+
+                map = move_to_front(map, pc);
+                map = move_to_front(map, pb);
+                map = move_to_front(map, pa);
+            }
+
+            for plane in 0..self.bpp {
+                let bit = if self.bpp > 1 {1 << plane} else {1 << (pixel & 3)};
+                let history = (bit - 1) & (self.output as usize);
+                let set = match self.bpp {
+                    1 => (pixel >= 4) as usize,
+                    2 => diff,
+                    _ if plane >= 2 && history <= 1 => diff,
+                    _ => 0
+                };
+
+                let context = &mut self.contexts[set][bit + history - 1];
+                let model = &EVOLUTION_TABLE[context.prediction as usize];
+                let lps_offset = self.range - (model.probability as u16);
+                let symbol = if self.input >= (lps_offset << 8) {LPS} else {MPS};
+
+                self.output = (self.output << 1) | (symbol ^ (context.swap as u8));
+
+                if symbol == MPS {
+                    self.range = lps_offset;
+                } else {
+                    self.range -= lps_offset;
+                    self.input -= lps_offset << 8;
+                }
+
+                // Rescale the range back to at least half.
+                while self.range <= MAX / 2 {
+                    context.prediction = model.next[symbol as usize];
+
+                    self.range <<= 1;
+                    self.input <<= 1;
+
+                    self.bits -= 1;
+                    if self.bits == 0 {
+                        self.bits = 8;
+                        let data = read(self.offset);
+                        self.offset = self.offset.wrapping_add(1);
+                        self.input += data as u16;
+                    }
+                }
+
+                if symbol == LPS && model.probability > HALF {
+                    context.swap = !context.swap;
+                }
+            }
+
+            let mut index = (self.output as u64) & ((1 << self.bpp) - 1);
+            if self.bpp == 1 {
+                index ^= (self.pixels >> 15) & 1;
+            }
+
+            self.pixels = (self.pixels << self.bpp) | ((map >> (4 * index)) & 0xF);
+        }
+
+        self.result = match self.bpp {
+            1 => self.pixels as u32,
+            2 => deinterleave(self.pixels, 16),
+            _ => deinterleave(deinterleave(self.pixels, 32) as u64, 32),
+        };
+    }
+}
+
+// Internal
+impl Decompressor {
+    fn next_byte<F: Fn(u32) -> u8>(&mut self, read: &F) -> u8 {
+        let data = read(self.offset);
+        self.offset = self.offset.wrapping_add(1);
+        data
+    }
+}
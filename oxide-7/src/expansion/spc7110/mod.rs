@@ -0,0 +1,538 @@
+// Epson SPC7110
+// A data ROM mapper and decompression chip, used in Far East of Eden Zero and the Momotaro Dentetsu games.
+// The cart has a 1MB program ROM, followed by a data ROM that is read through the chip's ports
+// or mapped into banks $D0-$FF. Far East of Eden Zero also has a real-time clock.
+mod decompress;
+mod rtc;
+#[cfg(test)]
+mod tests;
+
+use alloc::{
+    boxed::Box,
+    string::String
+};
+use serde::{
+    Serialize,
+    Deserialize
+};
+
+use crate::{
+    common::Interrupt,
+    mem::rom::{
+        ROM,
+        SRAM
+    },
+    savestate::{
+        Stateful,
+        StateWriter,
+        StateReader
+    }
+};
+
+use decompress::Decompressor;
+use super::Expansion;
+pub use rtc::RealTimeClock;
+
+const PAGE_SIZE: usize = 0x10_0000;
+const PROGRAM_ROM_SIZE: usize = PAGE_SIZE;
+
+const SRAM_ENABLE_BIT: u8 = 7;
+
+// Maps the program ROM and the data ROM into the CPU address space.
+struct MemoryMapper {
+    rom:            ROM,
+    sram_enable:    u8,         // $4830
+    banks:          [u8; 3],    // $4831-$4833: data ROM page for $D0-$DF, $E0-$EF and $F0-$FF.
+    data_rom_size:  u8,         // $4834
+}
+
+impl MemoryMapper {
+    // The first 1MB is program ROM, and the rest is data ROM.
+    fn read_program_rom(&self, offset: usize) -> u8 {
+        let data = self.rom.data();
+        let program_rom = &data[..core::cmp::min(data.len(), PROGRAM_ROM_SIZE)];
+        program_rom[offset % program_rom.len()]
+    }
+
+    // The data ROM size in $4834 is 1, 2, 4 or 8MB. Reads past 4MB return 0 unless it is 8MB.
+    fn read_data_rom(&self, offset: u32) -> u8 {
+        let size_select = self.data_rom_size & 3;
+        if size_select != 3 && (offset & 0x40_0000) != 0 {
+            return 0;
+        }
+
+        let data_rom = self.rom.data().get(PROGRAM_ROM_SIZE..).unwrap_or(&[]);
+        if data_rom.is_empty() {
+            return 0;
+        }
+        let mask = (PAGE_SIZE << size_select) - 1;
+        data_rom[((offset as usize) & mask) % data_rom.len()]
+    }
+
+    // Banks $00-$3F and $80-$BF are HiROM. They mirror $C0-$FF, where each 1MB is a page of ROM.
+    // The first page is always program ROM.
+    fn read(&self, addr: u32) -> u8 {
+        let offset = addr & 0xF_FFFF;
+        match (addr >> 20) & 3 {
+            0 => self.read_program_rom(offset as usize),
+            page => {
+                let data_page = (self.banks[(page - 1) as usize] & 7) as u32;
+                self.read_data_rom(data_page * (PAGE_SIZE as u32) + offset)
+            }
+        }
+    }
+
+    fn sram_enabled(&self) -> bool {
+        test_bit!(self.sram_enable, SRAM_ENABLE_BIT, u8)
+    }
+}
+
+// Decompresses data ROM, and returns it a tile at a time through $4800.
+#[derive(Clone, Serialize, Deserialize)]
+struct DecompressionUnit {
+    table:      u32,    // $4801-$4803: data ROM address of the table of compressed data.
+    index:      u8,     // $4804: index into the table. Each entry is the mode and a big-endian data ROM address.
+    skip:       u16,    // $4805-$4806: rows to skip at the start.
+    stride:     u8,     // $4807: rows to advance after each row.
+    count:      u16,    // $4809-$480A: decremented on each read.
+    control:    u8,     // $480B: bit 0 enables the stride, and bit 1 the skip.
+    ready:      bool,   // $480C bit 7
+
+    mode:       u8,
+    addr:       u32,
+    tile:       [u8; 32],
+    tile_offset:    usize,
+
+    decompressor:   Decompressor,
+}
+
+impl DecompressionUnit {
+    fn new() -> Self {
+        DecompressionUnit {
+            table:      0,
+            index:      0,
+            skip:       0,
+            stride:     0,
+            count:      0,
+            control:    0,
+            ready:      false,
+
+            mode:       0,
+            addr:       0,
+            tile:       [0; 32],
+            tile_offset:    0,
+
+            decompressor:   Decompressor::new(),
+        }
+    }
+
+    // Read the mode and address from the table entry.
+    fn load_table_entry(&mut self, mapper: &MemoryMapper) {
+        let entry = self.table.wrapping_add((self.index as u32) * 4);
+        self.mode = mapper.read_data_rom(entry);
+        self.addr = make24!(
+            mapper.read_data_rom(entry + 1),
+            make16!(mapper.read_data_rom(entry + 2), mapper.read_data_rom(entry + 3))
+        );
+    }
+
+    // Decompression is instant, so the data is ready as soon as it starts. Mode 3 is invalid.
+    fn start(&mut self, mapper: &MemoryMapper) {
+        self.ready = false;
+        if self.mode == 3 {
+            return;
+        }
+
+        let read = |offset| mapper.read_data_rom(offset);
+        self.decompressor.init(self.mode, self.addr, &read);
+        self.decompressor.decode(&read);
+
+        let skip = if test_bit!(self.control, 1, u8) {self.skip} else {0};
+        for _ in 0..skip {
+            self.decompressor.decode(&read);
+        }
+
+        self.ready = true;
+        self.tile_offset = 0;
+    }
+
+    // The next byte of the tile, in SNES bitplane format. A new tile is decoded at the start of each one.
+    fn read(&mut self, mapper: &MemoryMapper) -> u8 {
+        if !self.ready {
+            return 0;
+        }
+
+        let bpp = self.decompressor.bpp();
+        if self.tile_offset == 0 {
+            let read = |offset| mapper.read_data_rom(offset);
+            for row in 0..8 {
+                let result = self.decompressor.result();
+                match bpp {
+                    1 => self.tile[row] = result as u8,
+                    2 => {
+                        self.tile[row * 2] = result as u8;
+                        self.tile[row * 2 + 1] = (result >> 8) as u8;
+                    },
+                    _ => {
+                        self.tile[row * 2] = result as u8;
+                        self.tile[row * 2 + 1] = (result >> 8) as u8;
+                        self.tile[row * 2 + 16] = (result >> 16) as u8;
+                        self.tile[row * 2 + 17] = (result >> 24) as u8;
+                    }
+                }
+
+                let rows = if test_bit!(self.control, 0, u8) {self.stride} else {1};
+                for _ in 0..rows {
+                    self.decompressor.decode(&read);
+                }
+            }
+        }
+
+        let data = self.tile[self.tile_offset];
+        self.tile_offset = (self.tile_offset + 1) & (8 * bpp - 1);
+        data
+    }
+
+    fn read_reg(&mut self, addr: u16, mapper: &MemoryMapper) -> u8 {
+        match addr {
+            0x4800 => {
+                self.count = self.count.wrapping_sub(1);
+                self.read(mapper)
+            },
+            0x4801 => lo24!(self.table, u8),
+            0x4802 => mid24!(self.table),
+            0x4803 => hi24!(self.table),
+            0x4804 => self.index,
+            0x4805 => lo!(self.skip),
+            0x4806 => hi!(self.skip),
+            0x4807 => self.stride,
+            0x4809 => lo!(self.count),
+            0x480A => hi!(self.count),
+            0x480B => self.control,
+            0x480C => (self.ready as u8) << 7,
+            _ => 0
+        }
+    }
+
+    fn write_reg(&mut self, addr: u16, data: u8, mapper: &MemoryMapper) {
+        match addr {
+            0x4801 => self.table = set_lo24!(self.table, data),
+            0x4802 => self.table = set_mid24!(self.table, data),
+            0x4803 => self.table = set_hi24!(self.table, data),
+            0x4804 => {
+                self.index = data;
+                self.load_table_entry(mapper);
+            },
+            0x4805 => self.skip = set_lo!(self.skip, data),
+            0x4806 => {
+                self.skip = set_hi!(self.skip, data);
+                self.start(mapper);
+            },
+            0x4807 => self.stride = data,
+            0x4809 => self.count = set_lo!(self.count, data),
+            0x480A => self.count = set_hi!(self.count, data),
+            0x480B => self.control = data & 3,
+            _ => {}
+        }
+    }
+}
+
+// Reads data ROM directly, from an offset that can be moved after each read.
+#[derive(Default, Clone, Serialize, Deserialize)]
+struct DataPort {
+    data:       u8,     // $4810
+    offset:     u32,    // $4811-$4813
+    adjust:     u16,    // $4814-$4815
+    stride:     u16,    // $4816-$4817
+    control:    u8,     // $4818
+}
+
+impl DataPort {
+    // Bit 1 of the control adds the adjust to the offset. Bit 3 makes the adjust signed.
+    fn adjust(&self) -> u32 {
+        if test_bit!(self.control, 3, u8) {
+            self.adjust as i16 as u32
+        } else {
+            self.adjust as u32
+        }
+    }
+
+    fn fetch(&mut self, mapper: &MemoryMapper) {
+        let adjust = if test_bit!(self.control, 1, u8) {self.adjust()} else {0};
+        self.data = mapper.read_data_rom(self.offset.wrapping_add(adjust) & 0xFF_FFFF);
+    }
+
+    // After $4810 is read, the stride (or 1) is added to the offset, or to the adjust if bit 4 of the control is set.
+    // Bit 0 of the control uses the stride, and bit 2 makes it signed.
+    fn step(&mut self, mapper: &MemoryMapper) {
+        let stride = match self.control & 0x5 {
+            0x1 => self.stride as u32,
+            0x5 => self.stride as i16 as u32,
+            _ => 1
+        };
+        if test_bit!(self.control, 4, u8) {
+            self.adjust = self.adjust.wrapping_add(stride as u16);
+        } else {
+            self.offset = self.offset.wrapping_add(stride) & 0xFF_FFFF;
+        }
+        self.fetch(mapper);
+    }
+
+    // Bits 5-6 of the control choose the register that adds the adjust to the offset when accessed.
+    fn apply_adjust(&mut self, trigger: u8, mapper: &MemoryMapper) {
+        if (self.control >> 5) == trigger {
+            self.offset = self.offset.wrapping_add(self.adjust()) & 0xFF_FFFF;
+            self.fetch(mapper);
+        }
+    }
+
+    fn read_reg(&mut self, addr: u16, mapper: &MemoryMapper) -> u8 {
+        match addr {
+            0x4810 => {
+                let data = self.data;
+                self.step(mapper);
+                data
+            },
+            0x4811 => lo24!(self.offset, u8),
+            0x4812 => mid24!(self.offset),
+            0x4813 => hi24!(self.offset),
+            0x4814 => lo!(self.adjust),
+            0x4815 => hi!(self.adjust),
+            0x4816 => lo!(self.stride),
+            0x4817 => hi!(self.stride),
+            0x4818 => self.control,
+            0x481A => {
+                self.apply_adjust(3, mapper);
+                0
+            },
+            _ => 0
+        }
+    }
+
+    fn write_reg(&mut self, addr: u16, data: u8, mapper: &MemoryMapper) {
+        match addr {
+            0x4811 => self.offset = set_lo24!(self.offset, data),
+            0x4812 => self.offset = set_mid24!(self.offset, data),
+            0x4813 => {
+                self.offset = set_hi24!(self.offset, data);
+                self.fetch(mapper);
+            },
+            0x4814 => {
+                self.adjust = set_lo!(self.adjust, data);
+                self.apply_adjust(1, mapper);
+            },
+            0x4815 => {
+                self.adjust = set_hi!(self.adjust, data);
+                if test_bit!(self.control, 1, u8) {
+                    self.fetch(mapper);
+                }
+                self.apply_adjust(2, mapper);
+            },
+            0x4816 => self.stride = set_lo!(self.stride, data),
+            0x4817 => self.stride = set_hi!(self.stride, data),
+            0x4818 => {
+                self.control = data & 0x7F;
+                self.fetch(mapper);
+            },
+            _ => {}
+        }
+    }
+}
+
+// Multiplies and divides. Results are ready instantly.
+#[derive(Default, Clone, Serialize, Deserialize)]
+struct Arithmetic {
+    dividend:   u32,    // $4820-$4823. The multiplicand is the lower 16 bits.
+    multiplier: u16,    // $4824-$4825
+    divisor:    u16,    // $4826-$4827
+    result:     u32,    // $4828-$482B
+    remainder:  u16,    // $482C-$482D
+    signed:     bool,   // $482E bit 0
+}
+
+impl Arithmetic {
+    fn multiply(&mut self) {
+        self.result = if self.signed {
+            ((self.dividend as i16 as i32) * (self.multiplier as i16 as i32)) as u32
+        } else {
+            (self.dividend as u16 as u32) * (self.multiplier as u32)
+        };
+    }
+
+    // Division by zero returns a quotient of 0, and the dividend as the remainder.
+    fn divide(&mut self) {
+        if self.divisor == 0 {
+            self.result = 0;
+            self.remainder = self.dividend as u16;
+        } else if self.signed {
+            let dividend = self.dividend as i32;
+            let divisor = self.divisor as i16 as i32;
+            self.result = dividend.wrapping_div(divisor) as u32;
+            self.remainder = dividend.wrapping_rem(divisor) as u16;
+        } else {
+            let divisor = self.divisor as u32;
+            self.result = self.dividend / divisor;
+            self.remainder = (self.dividend % divisor) as u16;
+        }
+    }
+
+    fn read_reg(&self, addr: u16) -> u8 {
+        match addr {
+            0x4820..=0x4823 => (self.dividend >> ((addr - 0x4820) * 8)) as u8,
+            0x4824 => lo!(self.multiplier),
+            0x4825 => hi!(self.multiplier),
+            0x4826 => lo!(self.divisor),
+            0x4827 => hi!(self.divisor),
+            0x4828..=0x482B => (self.result >> ((addr - 0x4828) * 8)) as u8,
+            0x482C => lo!(self.remainder),
+            0x482D => hi!(self.remainder),
+            0x482E => self.signed as u8,
+            _ => 0      // $482F: never busy.
+        }
+    }
+
+    fn write_reg(&mut self, addr: u16, data: u8) {
+        match addr {
+            0x4820..=0x4823 => {
+                let shift = (addr - 0x4820) * 8;
+                self.dividend = (self.dividend & !(0xFF << shift)) | ((data as u32) << shift);
+            },
+            0x4824 => self.multiplier = set_lo!(self.multiplier, data),
+            0x4825 => {
+                self.multiplier = set_hi!(self.multiplier, data);
+                self.multiply();
+            },
+            0x4826 => self.divisor = set_lo!(self.divisor, data),
+            0x4827 => {
+                self.divisor = set_hi!(self.divisor, data);
+                self.divide();
+            },
+            0x482E => self.signed = test_bit!(data, 0, u8),
+            _ => {}
+        }
+    }
+}
+
+pub struct SPC7110 {
+    mapper:     MemoryMapper,
+    sram:       Box<dyn SRAM>,  // Mapped to $6000-$7FFF, when enabled.
+    rtc:        Option<RealTimeClock>,
+
+    dcu:        DecompressionUnit,
+    data_port:  DataPort,
+    alu:        Arithmetic,
+}
+
+impl SPC7110 {
+    pub fn new(rom: ROM, sram: Box<dyn SRAM>, rtc: Option<RealTimeClock>) -> Self {
+        SPC7110 {
+            mapper:     MemoryMapper {
+                rom:            rom,
+                sram_enable:    0,
+                banks:          [0, 1, 2],
+                data_rom_size:  0,
+            },
+            sram:       sram,
+            rtc:        rtc,
+
+            dcu:        DecompressionUnit::new(),
+            data_port:  DataPort::default(),
+            alu:        Arithmetic::default(),
+        }
+    }
+
+    fn read_reg(&mut self, addr: u16) -> u8 {
+        match addr {
+            0x4800..=0x480F => self.dcu.read_reg(addr, &self.mapper),
+            0x4810..=0x481F => self.data_port.read_reg(addr, &self.mapper),
+            0x4820..=0x482F => self.alu.read_reg(addr),
+            0x4830 => self.mapper.sram_enable,
+            0x4831..=0x4833 => self.mapper.banks[(addr - 0x4831) as usize],
+            0x4834 => self.mapper.data_rom_size,
+            0x4840..=0x4842 => self.rtc.as_mut().map_or(0, |rtc| rtc.read(addr)),
+            _ => 0
+        }
+    }
+
+    fn write_reg(&mut self, addr: u16, data: u8) {
+        match addr {
+            0x4800..=0x480F => self.dcu.write_reg(addr, data, &self.mapper),
+            0x4810..=0x481F => self.data_port.write_reg(addr, data, &self.mapper),
+            0x4820..=0x482F => self.alu.write_reg(addr, data),
+            0x4830 => self.mapper.sram_enable = data & 0x87,
+            0x4831..=0x4833 => self.mapper.banks[(addr - 0x4831) as usize] = data & 7,
+            0x4834 => self.mapper.data_rom_size = data & 7,
+            0x4840..=0x4842 => if let Some(rtc) = self.rtc.as_mut() {
+                rtc.write(addr, data);
+            },
+            _ => {}
+        }
+    }
+}
+
+impl Expansion for SPC7110 {
+    fn read(&mut self, bank: u8, addr: u16) -> u8 {
+        match (bank, addr) {
+            (0x00..=0x3F | 0x80..=0xBF, 0x4800..=0x48FF) => self.read_reg(addr),
+            (0x00..=0x3F | 0x80..=0xBF, 0x6000..=0x7FFF) if self.mapper.sram_enabled() => self.sram.read((addr - 0x6000) as u32),
+            (0x00..=0x3F | 0x80..=0xBF, 0x8000..=0xFFFF) |
+            (0xC0..=0xFF, _) => self.mapper.read(make24!(bank, addr)),
+            // Decompressed data is also mirrored here.
+            (0x50 | 0x58, _) => self.dcu.read(&self.mapper),
+            _ => 0
+        }
+    }
+
+    fn write(&mut self, bank: u8, addr: u16, data: u8) {
+        match (bank, addr) {
+            (0x00..=0x3F, 0x4800..=0x48FF) => self.write_reg(addr, data),
+            (0x00..=0x3F, 0x6000..=0x7FFF) if self.mapper.sram_enabled() => self.sram.write((addr - 0x6000) as u32, data),
+            _ => {}
+        }
+    }
+
+    fn clock(&mut self, cycles: usize) -> Interrupt {
+        if let Some(rtc) = self.rtc.as_mut() {
+            rtc.clock(cycles);
+        }
+        Interrupt::default()
+    }
+
+    fn flush(&mut self) -> Result<bool, String> {
+        let sram_written = self.sram.flush()?;
+        let rtc_written = if let Some(rtc) = self.rtc.as_mut() {
+            rtc.flush()?
+        } else {
+            false
+        };
+        Ok(sram_written || rtc_written)
+    }
+}
+
+impl Stateful for SPC7110 {
+    fn save_state(&self, w: &mut StateWriter) {
+        w.write(&self.mapper.sram_enable);
+        w.write(&self.mapper.banks);
+        w.write(&self.mapper.data_rom_size);
+        self.sram.save_state(w);
+        if let Some(rtc) = self.rtc.as_ref() {
+            rtc.save_state(w);
+        }
+        w.write(&self.dcu);
+        w.write(&self.data_port);
+        w.write(&self.alu);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), String> {
+        r.read_into(&mut self.mapper.sram_enable)?;
+        r.read_into(&mut self.mapper.banks)?;
+        r.read_into(&mut self.mapper.data_rom_size)?;
+        self.sram.load_state(r)?;
+        if let Some(rtc) = self.rtc.as_mut() {
+            rtc.load_state(r)?;
+        }
+        r.read_into(&mut self.dcu)?;
+        r.read_into(&mut self.data_port)?;
+        r.read_into(&mut self.alu)
+    }
+}
@@ -0,0 +1,368 @@
+// Epson RTC-4513 real-time clock, used by Far East of Eden Zero.
+// The CPU talks to it serially through $4840-$4842. The time is kept in 16 BCD nybbles.
+// While the game runs, the clock follows emulated time. It is saved next to the SRAM with a timestamp,
+// so the time that passed while the game wasn't running is added when it is loaded.
+use alloc::string::String;
+#[cfg(feature = "std")]
+use alloc::{
+    string::ToString,
+    vec::Vec
+};
+#[cfg(feature = "std")]
+use std::{
+    path::Path,
+    time::{
+        SystemTime,
+        UNIX_EPOCH
+    }
+};
+use serde::{
+    Serialize,
+    Deserialize
+};
+
+use crate::{
+    constants::timing::MASTER_HZ,
+    savestate::{
+        Stateful,
+        StateWriter,
+        StateReader
+    }
+};
+
+// Register indices.
+const SECONDS: usize = 0;
+const MINUTES: usize = 2;
+const HOURS: usize = 4;
+const DAY: usize = 6;
+const MONTH: usize = 8;
+const YEAR: usize = 10;
+const WEEKDAY: usize = 12;
+const CONTROL_D: usize = 13;
+const CONTROL_F: usize = 15;
+
+const HOLD_BIT: u8 = 0;     // Control D
+const ADJUST_BIT: u8 = 3;   // Control D: round to the nearest minute.
+const RESET_BIT: u8 = 0;    // Control F
+const STOP_BIT: u8 = 1;     // Control F
+const HOUR_24_BIT: u8 = 2;  // Control F
+const PM_BIT: u8 = 2;       // Hours tens
+
+// Commands written after the chip is enabled.
+const LINEAR_MODE: u8 = 0x03;   // Select an index, then write registers in order.
+const INDEXED_MODE: u8 = 0x0C;  // Each write selects an index.
+
+// Saturday 1st January 2000, 00:00:00.
+const Y2K_TIMESTAMP: u64 = 946_684_800;
+const Y2K_WEEKDAY: u8 = 6;
+
+// The save file is the registers, then a little-endian UNIX timestamp of when it was written.
+#[cfg(feature = "std")]
+const SAVE_FILE_SIZE: usize = 16 + 8;
+
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+enum State {
+    Inactive,
+    ModeSelect,
+    IndexSelect,
+    Write,
+}
+
+pub struct RealTimeClock {
+    #[cfg(feature = "std")]
+    save_path:      String,
+
+    regs:           [u8; 16],
+    enable:         u8,     // $4840
+    ready:          bool,   // $4842 bit 7: set after each transfer, cleared when read.
+    state:          State,
+    linear:         bool,
+    index:          usize,
+
+    cycle_count:    usize,  // Master cycles since the last second.
+    dirty:          bool,   // Changed since the last save.
+}
+
+impl RealTimeClock {
+    // The clock is saved to a .rtc file next to the save file. If there isn't one, it is created with the current time.
+    #[cfg(feature = "std")]
+    pub fn new(save_path: &str) -> Result<Self, String> {
+        let save_path = Path::new(save_path).with_extension("rtc").to_string_lossy().into_owned();
+        let mut rtc = RealTimeClock::with_path(save_path);
+
+        match std::fs::read(&rtc.save_path) {
+            Ok(data) if data.len() == SAVE_FILE_SIZE => {
+                rtc.regs.copy_from_slice(&data[..16]);
+                let mut timestamp = [0; 8];
+                timestamp.copy_from_slice(&data[16..]);
+                rtc.advance(now().saturating_sub(u64::from_le_bytes(timestamp)));
+            },
+            Ok(_) => return Err(format!("{} is not a valid RTC file.", rtc.save_path)),
+            Err(_) => {
+                rtc.set_time(now());
+                rtc.save()?;
+            }
+        }
+
+        Ok(rtc)
+    }
+
+    // Without std there is no save file or host clock, so the clock starts at the year 2000.
+    #[cfg(not(feature = "std"))]
+    pub fn new(_save_path: &str) -> Result<Self, String> {
+        let mut rtc = RealTimeClock::with_path(String::new());
+        rtc.set_time(Y2K_TIMESTAMP);
+        Ok(rtc)
+    }
+
+    pub fn read(&mut self, addr: u16) -> u8 {
+        match addr {
+            0x4840 => self.enable,
+            0x4841 => match self.state {
+                State::Inactive | State::ModeSelect => 0,
+                _ => {
+                    self.ready = true;
+                    let data = self.regs[self.index];
+                    self.index = (self.index + 1) & 0xF;
+                    data
+                }
+            },
+            0x4842 => {
+                let status = if self.ready {bit!(7)} else {0};
+                self.ready = false;
+                status
+            },
+            _ => 0
+        }
+    }
+
+    pub fn write(&mut self, addr: u16, data: u8) {
+        match addr {
+            0x4840 => {
+                self.enable = data;
+                if test_bit!(data, 0, u8) {
+                    self.ready = true;
+                    self.state = State::ModeSelect;
+                } else {
+                    self.state = State::Inactive;
+                }
+            },
+            0x4841 => match self.state {
+                State::ModeSelect if data == LINEAR_MODE || data == INDEXED_MODE => {
+                    self.ready = true;
+                    self.state = State::IndexSelect;
+                    self.linear = data == LINEAR_MODE;
+                    self.index = 0;
+                },
+                State::IndexSelect => {
+                    self.ready = true;
+                    self.index = (data & 0xF) as usize;
+                    if self.linear {
+                        self.state = State::Write;
+                    }
+                },
+                State::Write => {
+                    self.ready = true;
+                    self.write_reg(data & 0xF);
+                    self.index = (self.index + 1) & 0xF;
+                },
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+
+    // Count the seconds in emulated time. The clock is saved when the minute changes.
+    pub fn clock(&mut self, cycles: usize) {
+        self.cycle_count += cycles;
+        while self.cycle_count >= MASTER_HZ {
+            self.cycle_count -= MASTER_HZ;
+            let minute = self.regs[MINUTES];
+            self.advance(1);
+            self.dirty = self.dirty || self.regs[MINUTES] != minute;
+        }
+    }
+
+    // Returns true if the clock was written.
+    pub fn flush(&mut self) -> Result<bool, String> {
+        if !self.dirty {
+            return Ok(false);
+        }
+        self.dirty = false;
+        self.save()?;
+        Ok(true)
+    }
+}
+
+// Loading a state marks the clock to be saved, so the save file matches the game.
+impl Stateful for RealTimeClock {
+    fn save_state(&self, w: &mut StateWriter) {
+        w.write(&self.regs);
+        w.write(&self.enable);
+        w.write(&self.ready);
+        w.write(&self.state);
+        w.write(&self.linear);
+        w.write(&self.index);
+        w.write(&self.cycle_count);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), String> {
+        r.read_into(&mut self.regs)?;
+        r.read_into(&mut self.enable)?;
+        r.read_into(&mut self.ready)?;
+        r.read_into(&mut self.state)?;
+        r.read_into(&mut self.linear)?;
+        r.read_into(&mut self.index)?;
+        r.read_into(&mut self.cycle_count)?;
+        self.index &= 0xF;
+        self.dirty = true;
+        Ok(())
+    }
+}
+
+// Internal
+impl RealTimeClock {
+    fn with_path(_save_path: String) -> Self {
+        RealTimeClock {
+            #[cfg(feature = "std")]
+            save_path:      _save_path,
+
+            regs:           [0; 16],
+            enable:         0,
+            ready:          false,
+            state:          State::Inactive,
+            linear:         false,
+            index:          0,
+
+            cycle_count:    0,
+            dirty:          false,
+        }
+    }
+
+    #[cfg(feature = "std")]
+    fn save(&self) -> Result<(), String> {
+        let mut data = Vec::with_capacity(SAVE_FILE_SIZE);
+        data.extend_from_slice(&self.regs);
+        data.extend_from_slice(&now().to_le_bytes());
+        std::fs::write(&self.save_path, data).map_err(|e| e.to_string())
+    }
+
+    #[cfg(not(feature = "std"))]
+    fn save(&self) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn write_reg(&mut self, data: u8) {
+        match self.index {
+            CONTROL_D if test_bit!(data, ADJUST_BIT, u8) => {
+                let seconds = self.get(SECONDS, 0x7);
+                self.set(SECONDS, 0x7, 0);
+                if seconds >= 30 {
+                    self.advance(60);
+                }
+            },
+            CONTROL_F if test_bit!(data, RESET_BIT, u8) => self.set(SECONDS, 0x7, 0),
+            _ => {}
+        }
+        self.regs[self.index] = data;
+        self.dirty = true;
+    }
+
+    // 24-hour mode is set, and the time is the UTC time of the timestamp.
+    fn set_time(&mut self, timestamp: u64) {
+        self.regs = [0; 16];
+        self.regs[CONTROL_F] = bit!(HOUR_24_BIT);
+        self.set(DAY, 0x3, 1);
+        self.set(MONTH, 0x1, 1);
+        self.regs[WEEKDAY] = Y2K_WEEKDAY;
+        self.advance(timestamp.saturating_sub(Y2K_TIMESTAMP));
+    }
+
+    // Move the clock forward, unless it is held or stopped.
+    fn advance(&mut self, seconds: u64) {
+        if test_bit!(self.regs[CONTROL_D], HOLD_BIT, u8) ||
+            test_bit!(self.regs[CONTROL_F], RESET_BIT, u8) ||
+            test_bit!(self.regs[CONTROL_F], STOP_BIT, u8) {
+            return;
+        }
+
+        let seconds = self.get(SECONDS, 0x7) + seconds;
+        let minutes = self.get(MINUTES, 0x7) + seconds / 60;
+        let hours = self.hours() + minutes / 60;
+        self.set(SECONDS, 0x7, seconds % 60);
+        self.set(MINUTES, 0x7, minutes % 60);
+        self.set_hours(hours % 24);
+        for _ in 0..(hours / 24) {
+            self.next_day();
+        }
+    }
+
+    fn next_day(&mut self) {
+        self.regs[WEEKDAY] = (self.regs[WEEKDAY] + 1) % 7;
+
+        let day = self.get(DAY, 0x3) + 1;
+        if day <= days_in_month(self.get(MONTH, 0x1), self.get(YEAR, 0xF)) {
+            self.set(DAY, 0x3, day);
+            return;
+        }
+        self.set(DAY, 0x3, 1);
+
+        let month = self.get(MONTH, 0x1) + 1;
+        if month <= 12 {
+            self.set(MONTH, 0x1, month);
+            return;
+        }
+        self.set(MONTH, 0x1, 1);
+        self.set(YEAR, 0xF, (self.get(YEAR, 0xF) + 1) % 100);
+    }
+
+    // Hours from 0-23, in either mode.
+    fn hours(&self) -> u64 {
+        let hours = self.get(HOURS, 0x3);
+        if test_bit!(self.regs[CONTROL_F], HOUR_24_BIT, u8) {
+            hours
+        } else if test_bit!(self.regs[HOURS + 1], PM_BIT, u8) {
+            (hours % 12) + 12
+        } else {
+            hours % 12
+        }
+    }
+
+    fn set_hours(&mut self, hours: u64) {
+        if test_bit!(self.regs[CONTROL_F], HOUR_24_BIT, u8) {
+            self.regs[HOURS + 1] &= !bit!(PM_BIT);
+            self.set(HOURS, 0x3, hours);
+        } else if hours >= 12 {
+            self.regs[HOURS + 1] |= bit!(PM_BIT);
+            self.set(HOURS, 0x3, hours - 12);
+        } else {
+            self.regs[HOURS + 1] &= !bit!(PM_BIT);
+            self.set(HOURS, 0x3, hours);
+        }
+    }
+
+    // Read a two-digit BCD value. The mask is for the tens digit, which can share its nybble with flags.
+    fn get(&self, index: usize, tens_mask: u8) -> u64 {
+        ((self.regs[index] & 0xF) as u64) + ((self.regs[index + 1] & tens_mask) as u64) * 10
+    }
+
+    fn set(&mut self, index: usize, tens_mask: u8, value: u64) {
+        self.regs[index] = (value % 10) as u8;
+        self.regs[index + 1] = (self.regs[index + 1] & !tens_mask) | ((value / 10) as u8);
+    }
+}
+
+// Two-digit years are from 2000 to 2099.
+fn days_in_month(month: u64, year: u64) -> u64 {
+    match month {
+        4 | 6 | 9 | 11 => 30,
+        2 if (year & 3) == 0 => 29,
+        2 => 28,
+        _ => 31
+    }
+}
+
+#[cfg(feature = "std")]
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
@@ -0,0 +1,281 @@
+// Memory mapping, the data port, ALU, decompression and the real-time clock.
+use super::*;
+use crate::constants::timing::MASTER_HZ;
+
+// SRAM without a save file.
+struct TestSRAM(Vec<u8>);
+
+impl Stateful for TestSRAM {
+    fn save_state(&self, _w: &mut StateWriter) {}
+
+    fn load_state(&mut self, _r: &mut StateReader) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+impl SRAM for TestSRAM {
+    fn read(&self, addr: u32) -> u8 {
+        self.0[addr as usize]
+    }
+
+    fn write(&mut self, addr: u32, data: u8) {
+        self.0[addr as usize] = data;
+    }
+
+    fn flush(&mut self) -> Result<bool, String> {
+        Ok(false)
+    }
+}
+
+// 1MB of program ROM and 2MB of data ROM. Each 64kB bank is filled with its number, apart from the data provided
+// at the start of the data ROM.
+fn new_spc7110(data: &[u8]) -> SPC7110 {
+    let mut rom: Vec<u8> = (0..0x30_0000).map(|i| (i >> 16) as u8).collect();
+    rom[PROGRAM_ROM_SIZE..(PROGRAM_ROM_SIZE + data.len())].copy_from_slice(data);
+    SPC7110::new(ROM::new(rom, 0x10000), Box::new(TestSRAM(vec![0; 0x2000])), None)
+}
+
+fn write16(spc7110: &mut SPC7110, addr: u16, data: u16) {
+    spc7110.write(0x00, addr, lo!(data));
+    spc7110.write(0x00, addr + 1, hi!(data));
+}
+
+fn read32(spc7110: &mut SPC7110, addr: u16) -> u32 {
+    (0..4).fold(0, |acc, i| acc | ((spc7110.read(0x00, addr + i) as u32) << (i * 8)))
+}
+
+fn new_rtc(name: &str) -> (RealTimeClock, std::path::PathBuf) {
+    let save_path = std::env::temp_dir().join(name).with_extension("sav");
+    let rtc_path = save_path.with_extension("rtc");
+    let _ = std::fs::remove_file(&rtc_path);
+    (RealTimeClock::new(save_path.to_str().unwrap()).unwrap(), rtc_path)
+}
+
+// Write registers in linear mode.
+fn write_rtc(rtc: &mut RealTimeClock, index: u8, data: &[u8]) {
+    rtc.write(0x4840, 0x01);
+    rtc.write(0x4841, 0x03);
+    rtc.write(0x4841, index);
+    for d in data {
+        rtc.write(0x4841, *d);
+    }
+    rtc.write(0x4840, 0x00);
+}
+
+// Read all registers in indexed mode.
+fn read_rtc(rtc: &mut RealTimeClock) -> Vec<u8> {
+    rtc.write(0x4840, 0x01);
+    rtc.write(0x4841, 0x0C);
+    rtc.write(0x4841, 0x00);
+    let regs = (0..16).map(|_| rtc.read(0x4841)).collect();
+    rtc.write(0x4840, 0x00);
+    regs
+}
+
+#[test]
+fn rom_banks() {
+    let mut spc7110 = new_spc7110(&[]);
+    assert_eq!(spc7110.read(0xC5, 0x0000), 0x05);
+    assert_eq!(spc7110.read(0x85, 0x8000), 0x05);
+    assert_eq!(spc7110.read(0xD0, 0x0000), 0x10);
+
+    // Pages past the data ROM size set in $4834 are mirrored.
+    assert_eq!(spc7110.read(0xE0, 0x0000), 0x10);
+    spc7110.write(0x00, 0x4834, 0x01);
+    assert_eq!(spc7110.read(0xE0, 0x0000), 0x20);
+    assert_eq!(spc7110.read(0xF0, 0x0000), 0x10);
+
+    spc7110.write(0x00, 0x4831, 0x01);
+    assert_eq!(spc7110.read(0x00, 0x4831), 0x01);
+    assert_eq!(spc7110.read(0xD3, 0x0000), 0x23);
+    assert_eq!(spc7110.read(0x13, 0x8000), 0x23);
+}
+
+#[test]
+fn sram_enable() {
+    let mut spc7110 = new_spc7110(&[]);
+    spc7110.write(0x00, 0x6000, 0x12);
+    assert_eq!(spc7110.read(0x00, 0x6000), 0x00);
+
+    spc7110.write(0x00, 0x4830, 0x80);
+    spc7110.write(0x00, 0x6000, 0x12);
+    assert_eq!(spc7110.read(0x80, 0x6000), 0x12);
+}
+
+#[test]
+fn data_port() {
+    let mut spc7110 = new_spc7110(&[0x11, 0x22, 0x33, 0x44, 0x55]);
+    spc7110.write(0x00, 0x4811, 0x00);
+    spc7110.write(0x00, 0x4812, 0x00);
+    spc7110.write(0x00, 0x4813, 0x00);
+    assert_eq!(spc7110.read(0x00, 0x4810), 0x11);
+    assert_eq!(spc7110.read(0x00, 0x4810), 0x22);
+    assert_eq!(spc7110.read(0x00, 0x4811), 0x02);
+
+    // Step by the stride.
+    write16(&mut spc7110, 0x4816, 2);
+    spc7110.write(0x00, 0x4818, 0x01);
+    assert_eq!(spc7110.read(0x00, 0x4810), 0x33);
+    assert_eq!(spc7110.read(0x00, 0x4810), 0x55);
+
+    // Read from the offset plus the adjust, and step the adjust.
+    spc7110.write(0x00, 0x4811, 0x00);
+    write16(&mut spc7110, 0x4814, 1);
+    spc7110.write(0x00, 0x4818, 0x12);
+    assert_eq!(spc7110.read(0x00, 0x4810), 0x22);
+    assert_eq!(spc7110.read(0x00, 0x4810), 0x33);
+    assert_eq!(spc7110.read(0x00, 0x4814), 0x03);
+    assert_eq!(spc7110.read(0x00, 0x4811), 0x00);
+
+    // Add the adjust to the offset when $481A is read.
+    spc7110.write(0x00, 0x4818, 0x60);
+    spc7110.read(0x00, 0x481A);
+    assert_eq!(spc7110.read(0x00, 0x4811), 0x03);
+    assert_eq!(spc7110.read(0x00, 0x4810), 0x44);
+}
+
+#[test]
+fn alu() {
+    let mut spc7110 = new_spc7110(&[]);
+    write16(&mut spc7110, 0x4820, 300);
+    write16(&mut spc7110, 0x4824, 500);
+    assert_eq!(read32(&mut spc7110, 0x4828), 150_000);
+
+    spc7110.write(0x00, 0x482E, 0x01);
+    write16(&mut spc7110, 0x4824, -2_i16 as u16);
+    assert_eq!(read32(&mut spc7110, 0x4828), -600_i32 as u32);
+
+    // 32-bit by 16-bit division.
+    spc7110.write(0x00, 0x482E, 0x00);
+    write16(&mut spc7110, 0x4820, 0x0001);
+    write16(&mut spc7110, 0x4822, 0x0001);
+    write16(&mut spc7110, 0x4826, 0x1000);
+    assert_eq!(read32(&mut spc7110, 0x4828), 0x10);
+    assert_eq!(spc7110.read(0x00, 0x482C), 0x01);
+
+    spc7110.write(0x00, 0x482E, 0x01);
+    write16(&mut spc7110, 0x4820, -7_i16 as u16);
+    write16(&mut spc7110, 0x4822, 0xFFFF);
+    write16(&mut spc7110, 0x4826, 2);
+    assert_eq!(read32(&mut spc7110, 0x4828), -3_i32 as u32);
+    assert_eq!(spc7110.read(0x00, 0x482C), 0xFF);
+
+    // Division by zero.
+    write16(&mut spc7110, 0x4826, 0);
+    assert_eq!(read32(&mut spc7110, 0x4828), 0);
+    assert_eq!(spc7110.read(0x00, 0x482C), lo!(-7_i16 as u16));
+}
+
+#[test]
+fn decompress_zero_stream() {
+    let read = |_| 0;
+    for mode in 0..3 {
+        let mut decompressor = Decompressor::new();
+        decompressor.init(mode, 0, &read);
+        for _ in 0..16 {
+            decompressor.decode(&read);
+            assert_eq!(decompressor.result(), 0, "mode {}", mode);
+        }
+    }
+}
+
+#[test]
+fn decompress_first_pixel() {
+    // With all bits set, the first symbol is an LPS, so the first pixel is colour 1.
+    let read = |_| 0xFF;
+    let mut decompressor = Decompressor::new();
+    decompressor.init(0, 0, &read);
+    decompressor.decode(&read);
+    assert_eq!(decompressor.result() & 0x80, 0x80);
+}
+
+#[test]
+fn decompression_unit() {
+    // Table at $10, with an entry for 2bpp data at $20.
+    let mut data = vec![0; 0x30];
+    data[0x14..0x18].copy_from_slice(&[0x01, 0x00, 0x00, 0x20]);
+    let mut spc7110 = new_spc7110(&data);
+
+    spc7110.write(0x00, 0x4801, 0x10);
+    spc7110.write(0x00, 0x4804, 0x01);
+    write16(&mut spc7110, 0x4809, 0x20);
+    write16(&mut spc7110, 0x4805, 0);
+    assert_eq!(spc7110.read(0x00, 0x480C), 0x80);
+
+    for _ in 0..0x20 {
+        assert_eq!(spc7110.read(0x00, 0x4800), 0x00);
+    }
+    assert_eq!(spc7110.read(0x00, 0x4809), 0x00);
+    assert_eq!(spc7110.read(0x50, 0x0000), 0x00);
+    assert_eq!(spc7110.dcu.tile_offset, 1);
+
+    // Mode 3 is invalid.
+    data[0x14] = 0x03;
+    let mut spc7110 = new_spc7110(&data);
+    spc7110.write(0x00, 0x4801, 0x10);
+    spc7110.write(0x00, 0x4804, 0x01);
+    write16(&mut spc7110, 0x4805, 0);
+    assert_eq!(spc7110.read(0x00, 0x480C), 0x00);
+}
+
+#[test]
+fn rtc_rollover() {
+    let (mut rtc, rtc_path) = new_rtc("oxide7-rtc-rollover");
+
+    // 23:59:59, Friday 31st December 2099, in 24-hour mode.
+    write_rtc(&mut rtc, 0, &[9, 5, 9, 5, 3, 2, 1, 3, 2, 1, 9, 9, 5, 0, 0, 4]);
+    rtc.clock(MASTER_HZ - 1);
+    assert_eq!(read_rtc(&mut rtc)[0], 9);
+    rtc.clock(1);
+    assert_eq!(read_rtc(&mut rtc), vec![0, 0, 0, 0, 0, 0, 1, 0, 1, 0, 0, 0, 6, 0, 0, 4]);
+
+    // 11:59:59 AM, 28th February 2004, in 12-hour mode.
+    write_rtc(&mut rtc, 0, &[9, 5, 9, 5, 1, 1, 8, 2, 2, 0, 4, 0, 6, 0, 0, 0]);
+    rtc.clock(MASTER_HZ);
+    assert_eq!(&read_rtc(&mut rtc)[..8], &[0, 0, 0, 0, 0, 4, 8, 2]);
+
+    // Leap day.
+    write_rtc(&mut rtc, 0, &[9, 5, 9, 5, 1, 5]);
+    rtc.clock(MASTER_HZ);
+    assert_eq!(&read_rtc(&mut rtc)[..10], &[0, 0, 0, 0, 0, 0, 9, 2, 2, 0]);
+
+    let _ = std::fs::remove_file(&rtc_path);
+}
+
+#[test]
+fn rtc_stop() {
+    let (mut rtc, rtc_path) = new_rtc("oxide7-rtc-stop");
+    write_rtc(&mut rtc, 0, &[0, 3]);
+    write_rtc(&mut rtc, 15, &[0x6]);
+    rtc.clock(MASTER_HZ * 2);
+    assert_eq!(&read_rtc(&mut rtc)[..2], &[0, 3]);
+
+    // Round to the nearest minute.
+    write_rtc(&mut rtc, 15, &[0x4]);
+    write_rtc(&mut rtc, 13, &[0x8]);
+    assert_eq!(&read_rtc(&mut rtc)[..2], &[0, 0]);
+
+    let _ = std::fs::remove_file(&rtc_path);
+}
+
+#[test]
+fn rtc_save_file() {
+    let (mut rtc, rtc_path) = new_rtc("oxide7-rtc-save");
+    assert!(rtc_path.is_file());
+    assert_eq!(rtc.flush(), Ok(false));
+
+    // The clock is stopped, so the time is the same when it is loaded again.
+    write_rtc(&mut rtc, 0, &[1, 2, 3, 4, 5, 1, 6, 1, 7, 0, 8, 9, 3, 0, 0, 6]);
+    assert_eq!(rtc.flush(), Ok(true));
+    let regs = read_rtc(&mut rtc);
+    drop(rtc);
+
+    let mut rtc = RealTimeClock::new(rtc_path.with_extension("sav").to_str().unwrap()).unwrap();
+    assert_eq!(read_rtc(&mut rtc), regs);
+
+    // Bad save files are rejected.
+    std::fs::write(&rtc_path, [0; 4]).unwrap();
+    assert!(RealTimeClock::new(rtc_path.with_extension("sav").to_str().unwrap()).is_err());
+
+    let _ = std::fs::remove_file(&rtc_path);
+}
@@ -1,6 +1,11 @@
 // Code cache for SuperFX.
 
-#[derive(Clone, Copy)]
+use serde::{
+    Serialize,
+    Deserialize
+};
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
 struct CacheLine {
     data: [u8; 16],
 }
@@ -37,9 +42,22 @@ pub enum CacheResult {
     OutsideCache    // The requested data is outside the cache.
 }
 
+// Cache hits and misses, for debugging.
+#[derive(Clone, Copy, Default)]
+pub struct CacheStats {
+    pub hits:   usize,
+    pub misses: usize,
+}
+
+#[derive(Serialize, Deserialize)]
 pub struct InstructionCache {
     lines:  [CacheLine; 32],
     cbr:    u16,
+
+    #[serde(skip)]
+    stats:      CacheStats, // Current frame.
+    #[serde(skip)]
+    last_stats: CacheStats, // Last complete frame.
 }
 
 impl InstructionCache {
@@ -47,7 +65,21 @@ impl InstructionCache {
         Self {
             lines:  [CacheLine::new(); 32],
             cbr:    0,
+
+            stats:      CacheStats::default(),
+            last_stats: CacheStats::default(),
+        }
+    }
+
+    // Try read from cache for an instruction fetch, and record the hit or miss.
+    pub fn try_fetch(&mut self, addr: u16) -> CacheResult {
+        let result = self.try_read(addr);
+        match result {
+            CacheResult::InCache(_) => self.stats.hits += 1,
+            CacheResult::Request => self.stats.misses += 1,
+            CacheResult::OutsideCache => {},
         }
+        result
     }
 
     // Try read from cache.
@@ -98,4 +130,24 @@ impl InstructionCache {
     pub fn get_cbr(&self) -> u16 {
         self.cbr
     }
+
+    // Store the stats for the frame that just ended, and start counting again.
+    pub fn start_frame(&mut self) {
+        self.last_stats = core::mem::take(&mut self.stats);
+    }
+}
+
+// Debug
+#[cfg(feature = "debug")]
+impl InstructionCache {
+    pub fn get_state(&self) -> crate::debug::FXCacheState {
+        crate::debug::FXCacheState {
+            cbr:            self.cbr,
+            lines:          self.lines.iter().map(|l| if l.is_loaded() {Some(l.data)} else {None}).collect(),
+            hits:           self.last_stats.hits,
+            misses:         self.last_stats.misses,
+            frame_hits:     self.stats.hits,
+            frame_misses:   self.stats.misses,
+        }
+    }
 }
\ No newline at end of file
@@ -1,14 +1,26 @@
-use crate::mem::{
-    rom::{ROM, SRAM},
-    RAM,
+use alloc::{
+    boxed::Box,
+    string::String
 };
 
+use crate::{
+    mem::{
+        rom::{ROM, SRAM},
+        RAM,
+    },
+    savestate::{
+        Stateful,
+        StateWriter,
+        StateReader
+    }
+};
 
 pub struct FXMem {
     // Memory
     rom:    ROM,
     ram:    RAM,
     sram:   Box<dyn SRAM>,
+    battery:    bool,   // Game pak RAM is battery-backed, and stored in SRAM.
 
     // Access flags
     ron:    bool,
@@ -16,11 +28,12 @@ pub struct FXMem {
 }
 
 impl FXMem {
-    pub fn new(rom: ROM, sram: Box<dyn SRAM>) -> Self {
+    pub fn new(rom: ROM, sram: Box<dyn SRAM>, battery: bool) -> Self {
         FXMem {
             rom:    rom,
             ram:    RAM::new(128 * 1024),
             sram:   sram,
+            battery:    battery,
 
             ron:    false
         }
@@ -30,28 +43,24 @@ impl FXMem {
         self.ron = val;
     }
 
-    pub fn snes_read(&mut self, bank: u8, addr: u16) -> u8 {
-        use crate::constants::int;
-        const COP_VECTOR: (u16, u16) = (int::COP_VECTOR as u16, (int::COP_VECTOR + 1) as u16);
-        const BRK_VECTOR: (u16, u16) = (int::BRK_VECTOR as u16, (int::BRK_VECTOR + 1) as u16);
-        const NMI_VECTOR: (u16, u16) = (int::NMI_VECTOR as u16, (int::NMI_VECTOR + 1) as u16);
-        const IRQ_VECTOR: (u16, u16) = (int::IRQ_VECTOR as u16, (int::IRQ_VECTOR + 1) as u16);
+    // While the GSU is running and owns the ROM bus, the SNES CPU reads a fixed pattern
+    // in place of ROM. The interrupt vectors in the pattern point to handlers in WRAM.
+    pub fn snes_read(&mut self, bank: u8, addr: u16, gsu_running: bool) -> u8 {
+        const ROM_CONFLICT_DATA: [u8; 16] = [
+            0x00, 0x01, 0x00, 0x01, 0x04, 0x01, 0x00, 0x01,
+            0x00, 0x01, 0x08, 0x01, 0x00, 0x01, 0x0C, 0x01
+        ];
+        let rom_conflict = gsu_running && self.ron;
 
         match bank % 0x80 {
-            0x00 if self.ron && addr == COP_VECTOR.0 => 0x04,
-            0x00 if self.ron && addr == COP_VECTOR.1 => 0x01,
-            0x00 if self.ron && addr == BRK_VECTOR.0 => 0x00,
-            0x00 if self.ron && addr == BRK_VECTOR.1 => 0x01,
-            0x00 if self.ron && addr == NMI_VECTOR.0 => 0x08,
-            0x00 if self.ron && addr == NMI_VECTOR.1 => 0x01,
-            0x00 if self.ron && addr == IRQ_VECTOR.0 => 0x0C,
-            0x00 if self.ron && addr == IRQ_VECTOR.1 => 0x01,
+            0x00..=0x3F if addr >= 0x8000 && rom_conflict => ROM_CONFLICT_DATA[(addr & 0xF) as usize],
             0x00..=0x3F if addr >= 0x8000 => self.rom.read(bank, addr - 0x8000),
-            0x00..=0x3F if addr >= 0x6000 => self.ram.read((addr - 0x6000) as u32),
+            0x00..=0x3F if addr >= 0x6000 => self.read_ram((addr - 0x6000) as u32),
+            0x40..=0x5F if rom_conflict => ROM_CONFLICT_DATA[(addr & 0xF) as usize],
             0x40..=0x5F => self.read_hi(bank - 0x40, addr),
             0x70..=0x71 => {
                 let bank_addr = ((bank as u32) % 0x10) * 0x10000;
-                self.ram.read(bank_addr + (addr as u32))
+                self.read_ram(bank_addr + (addr as u32))
             },
             0x78..=0x79 => {
                 let bank_addr = ((bank as u32) % 0x8) * 0x10000;
@@ -63,10 +72,10 @@ impl FXMem {
 
     pub fn snes_write(&mut self, bank: u8, addr: u16, data: u8) {
         match bank % 0x80 {
-            0x00..=0x3F if addr >= 0x6000 => self.ram.write((addr - 0x6000) as u32, data),
+            0x00..=0x3F if addr >= 0x6000 => self.write_ram((addr - 0x6000) as u32, data),
             0x70..=0x71 => {
                 let bank_addr = ((bank as u32) % 0x10) * 0x10000;
-                self.ram.write(bank_addr + (addr as u32), data)
+                self.write_ram(bank_addr + (addr as u32), data)
             },
             0x78..=0x79 => {
                 let bank_addr = ((bank as u32) % 0x8) * 0x10000;
@@ -82,7 +91,7 @@ impl FXMem {
             0x40..=0x5F => self.read_hi(bank - 0x40, addr),
             0x70..=0x71 => {
                 let bank_addr = ((bank as u32) - 0x70) * 0x10000;
-                self.ram.read(bank_addr + (addr as u32))
+                self.read_ram(bank_addr + (addr as u32))
             },
             _ => 0,
         }
@@ -92,18 +101,39 @@ impl FXMem {
         match bank {
             0x70..=0x71 => {
                 let bank_addr = ((bank as u32) - 0x70) * 0x10000;
-                self.ram.write(bank_addr + (addr as u32), data);
+                self.write_ram(bank_addr + (addr as u32), data);
             },
             _ => {},
         }
     }
 
-    pub fn flush(&mut self) {
-        self.sram.flush();
+    pub fn is_battery_backed(&self) -> bool {
+        self.battery
+    }
+
+    pub fn flush(&mut self) -> Result<bool, String> {
+        self.sram.flush()
     }
 }
 
 impl FXMem {
+    // Game pak RAM.
+    fn read_ram(&self, addr: u32) -> u8 {
+        if self.battery {
+            self.sram.read(addr)
+        } else {
+            self.ram.read(addr)
+        }
+    }
+
+    fn write_ram(&mut self, addr: u32, data: u8) {
+        if self.battery {
+            self.sram.write(addr, data);
+        } else {
+            self.ram.write(addr, data);
+        }
+    }
+
     fn read_hi(&mut self, bank: u8, addr: u16) -> u8 {
         let mapped_bank = if addr >= 0x8000 {
             (bank * 2) + 1
@@ -113,4 +143,18 @@ impl FXMem {
 
         self.rom.read(mapped_bank, addr % 0x8000)
     }
+}
+
+impl Stateful for FXMem {
+    fn save_state(&self, w: &mut StateWriter) {
+        self.sram.save_state(w);
+        w.write(&self.ram);
+        w.write(&self.ron);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), String> {
+        self.sram.load_state(r)?;
+        r.read_into(&mut self.ram)?;
+        r.read_into(&mut self.ron)
+    }
 }
\ No newline at end of file
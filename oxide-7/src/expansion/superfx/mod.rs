@@ -3,12 +3,27 @@ mod mem;
 mod icache;
 mod pixelcache;
 mod writecache;
+#[cfg(test)]
+mod tests;
 
+use alloc::{
+    boxed::Box,
+    string::String
+};
 use bitflags::bitflags;
+use serde::{
+    Serialize,
+    Deserialize
+};
 
 use crate::{
     common::Interrupt,
-    mem::rom::{ROM, SRAM}
+    mem::rom::{ROM, SRAM},
+    savestate::{
+        Stateful,
+        StateWriter,
+        StateReader
+    }
 };
 
 use mem::FXMem;
@@ -18,7 +33,7 @@ use writecache::*;
 use super::Expansion;
 
 bitflags! {
-    #[derive(Default)]
+    #[derive(Default, Serialize, Deserialize)]
     struct FXFlags: u16 {
         const IRQ = bit!(15, u16);  // Interrupt
         const B = bit!(12, u16);    // Prefix
@@ -36,7 +51,7 @@ bitflags! {
 }
 
 bitflags! {
-    #[derive(Default)]
+    #[derive(Default, Serialize, Deserialize)]
     struct Config: u8 {
         const IRQ = bit!(7);
         const MS0 = bit!(5);
@@ -84,7 +99,7 @@ pub struct SuperFX {
 }
 
 impl SuperFX {
-    pub fn new(rom: ROM, sram: Box<dyn SRAM>) -> Self {
+    pub fn new(rom: ROM, sram: Box<dyn SRAM>, battery: bool) -> Self {
         SuperFX {
             regs:       [0; 16],
             regs_latch: 0,
@@ -106,7 +121,7 @@ impl SuperFX {
             clock_select:   false,
 
             cache:          InstructionCache::new(),
-            mem:            FXMem::new(rom, sram),
+            mem:            FXMem::new(rom, sram, battery),
             pixel_cache:    PixelCache::new(),
             write_cache:    WriteCache::new(),
 
@@ -123,7 +138,7 @@ impl Expansion for SuperFX {
     fn read(&mut self, bank: u8, addr: u16) -> u8 {
         match bank % 0x80 {
             0x00..=0x3F if addr <= 0x3500 => self.read_reg(addr),
-            _ => self.mem.snes_read(bank, addr)
+            _ => self.mem.snes_read(bank, addr, self.flags.contains(FXFlags::GO))
         }
     }
 
@@ -143,22 +158,80 @@ impl Expansion for SuperFX {
             while self.cycle_count <= 0 && self.flags.contains(FXFlags::GO) {
                 self.step();
             }
+        }
 
-            if self.flags.contains(FXFlags::IRQ) {
-                Interrupt::IRQ
-            } else {
-                Interrupt::default()
-            }
+        // The IRQ stays asserted until it is acknowledged by reading $3031.
+        if self.flags.contains(FXFlags::IRQ) && !self.cfg.contains(Config::IRQ) {
+            Interrupt::IRQ
         } else {
             Interrupt::default()
         }
     }
 
-    fn flush(&mut self) {
-        if test_bit!(self.backup, 0, u8) {
-            self.mem.flush();
+    fn flush(&mut self) -> Result<bool, String> {
+        if self.mem.is_battery_backed() || test_bit!(self.backup, 0, u8) {
+            self.mem.flush()
+        } else {
+            Ok(false)
         }
     }
+
+    fn start_frame(&mut self) {
+        self.cache.start_frame();
+    }
+
+    #[cfg(feature = "debug")]
+    fn fx_cache_state(&self) -> Option<crate::debug::FXCacheState> {
+        Some(self.cache.get_state())
+    }
+}
+
+impl Stateful for SuperFX {
+    fn save_state(&self, w: &mut StateWriter) {
+        w.write(&self.regs);
+        w.write(&self.regs_latch);
+        w.write(&self.pc_next);
+        w.write(&self.pb_next);
+        w.write(&self.flags);
+        w.write(&self.pb);
+        w.write(&self.romb);
+        w.write(&self.ramb);
+        w.write(&self.backup);
+        w.write(&self.cfg);
+        w.write(&self.last_ram_addr);
+        w.write(&self.src);
+        w.write(&self.dst);
+        w.write(&self.version);
+        w.write(&self.clock_select);
+        w.write(&self.cache);
+        self.mem.save_state(w);
+        w.write(&self.pixel_cache);
+        w.write(&self.write_cache);
+        w.write(&self.cycle_count);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), String> {
+        r.read_into(&mut self.regs)?;
+        r.read_into(&mut self.regs_latch)?;
+        r.read_into(&mut self.pc_next)?;
+        r.read_into(&mut self.pb_next)?;
+        r.read_into(&mut self.flags)?;
+        r.read_into(&mut self.pb)?;
+        r.read_into(&mut self.romb)?;
+        r.read_into(&mut self.ramb)?;
+        r.read_into(&mut self.backup)?;
+        r.read_into(&mut self.cfg)?;
+        r.read_into(&mut self.last_ram_addr)?;
+        r.read_into(&mut self.src)?;
+        r.read_into(&mut self.dst)?;
+        r.read_into(&mut self.version)?;
+        r.read_into(&mut self.clock_select)?;
+        r.read_into(&mut self.cache)?;
+        self.mem.load_state(r)?;
+        r.read_into(&mut self.pixel_cache)?;
+        r.read_into(&mut self.write_cache)?;
+        r.read_into(&mut self.cycle_count)
+    }
 }
 
 // Registers
@@ -208,8 +281,8 @@ impl SuperFX {
             0x3033 => self.backup = data,
             0x3034 => {
                 //println!("Setting PB to {:X}", self.pb);
-                self.pb = data;
-                self.pb_next = data;
+                self.pb = data & 0x7F;
+                self.pb_next = data & 0x7F;
             },
             0x3037 => self.cfg = Config::from_bits_truncate(data),
             0x3039 => {
@@ -365,10 +438,9 @@ impl SuperFX {
 // Special
 impl SuperFX {
     fn stop(&mut self) {
+        // The IRQ flag is always set: the config mask only stops it reaching the SNES CPU.
         self.flags.remove(FXFlags::GO);
-        if !self.cfg.contains(Config::IRQ) {
-            self.flags.insert(FXFlags::IRQ);
-        }
+        self.flags.insert(FXFlags::IRQ);
         //println!("FX STOP!");
         self.reset_prefix();
     }
@@ -696,11 +768,9 @@ impl SuperFX {
             if self.pixel_cache.needs_flush() {
                 self.flush_pixel_buffer();
             }
+            // The buffer now holds the tile this pixel is in.
             self.fill_pixel_buffer(x, y);
-            let ok = self.pixel_cache.try_plot(x, y);
-            if !ok {
-                panic!("FX Plot");
-            }
+            self.pixel_cache.try_plot(x, y);
         }
         self.regs[PLOT_X_REG] = self.regs[PLOT_X_REG].wrapping_add(1);
     }
@@ -887,7 +957,7 @@ impl SuperFX {
 
     fn asr(&mut self) {
         let s = self.regs[self.src];
-        let result = if self.flags.contains(FXFlags::ALT1) && s == std::u16::MAX {
+        let result = if self.flags.contains(FXFlags::ALT1) && s == core::u16::MAX {
             0
         } else {
             ((s as i16) >> 1) as u16
@@ -951,8 +1021,7 @@ impl SuperFX {
             0 => self.signed_mult(lo!(self.regs[n as usize])),
             1 => self.unsigned_mult(lo!(self.regs[n as usize])),
             2 => self.signed_mult(n & 0xF),
-            3 => self.unsigned_mult(n & 0xF),
-            _ => unimplemented!(),
+            _ => self.unsigned_mult(n & 0xF),
         }
 
         if !self.cfg.contains(Config::MS0) || self.clock_select {
@@ -1048,7 +1117,7 @@ impl SuperFX {
     }
 
     fn fetch(&mut self) -> u8 {
-        let data = match self.cache.try_read(self.regs[PC_REG]) {
+        let data = match self.cache.try_fetch(self.regs[PC_REG]) {
             CacheResult::InCache(data) => {
                 self.clock_inc(1);
                 data
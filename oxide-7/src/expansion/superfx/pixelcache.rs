@@ -1,8 +1,12 @@
 // Cache for holding bitmap pixel values.
 use bitflags::bitflags;
+use serde::{
+    Serialize,
+    Deserialize
+};
 
 bitflags! {
-    #[derive(Default)]
+    #[derive(Default, Serialize, Deserialize)]
     struct ScreenMode: u8 {
         const HT1 = bit!(5);
         const RON = bit!(4);
@@ -13,7 +17,7 @@ bitflags! {
 }
 
 bitflags! {
-    #[derive(Default)]
+    #[derive(Default, Serialize, Deserialize)]
     struct PlotOption: u8 {
         const OBJ_MODE = bit!(4);
         const FREEZE_HI = bit!(3);
@@ -23,23 +27,25 @@ bitflags! {
     }
 }
 
-#[derive(PartialEq)]
+#[derive(PartialEq, Serialize, Deserialize)]
 enum BPP {
     _2,
     _4,
     _8,
 }
 
+// Mode 2 is unused, and acts as 4bpp.
 impl From<ScreenMode> for BPP {
     fn from(val: ScreenMode) -> Self {
         match (val & ScreenMode::MD).bits() {
             0 => BPP::_2,
-            1 => BPP::_4,
+            1 | 2 => BPP::_4,
             _ => BPP::_8
         }
     }
 }
 
+#[derive(Serialize, Deserialize)]
 enum ScreenHeight {
     _128,
     _160,
@@ -63,7 +69,7 @@ impl From<ScreenMode> for ScreenHeight {
 
 // Represents a row of 8 pixels.
 // Each pixel is 2, 4, or 8 bits.
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 struct CacheLine {
     data:   [u8; 8],
     bitp:   u8, // Bit-pending flags
@@ -120,6 +126,7 @@ impl CacheLine {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct PixelCache {
     primary:    CacheLine,
     secondary:  CacheLine,
@@ -165,18 +172,16 @@ impl PixelCache {
         self.screen_mode.contains(ScreenMode::RON)
     }
 
+    // With the high nybble option, the high nybble of the source is written to the low nybble of COLR.
+    // Otherwise with freeze high, only the low nybble is written.
     pub fn set_colr(&mut self, data: u8) {
-        let to_write = if self.por.contains(PlotOption::HI_NYBBLE) {
-            let hi = hi_nybble!(data);
-            (hi << 4) | hi
+        self.colr = if self.por.contains(PlotOption::HI_NYBBLE) {
+            (self.colr & 0xF0) | hi_nybble!(data)
+        } else if self.por.contains(PlotOption::FREEZE_HI) {
+            (self.colr & 0xF0) | lo_nybble!(data)
         } else {
             data
         };
-        self.colr = if self.por.contains(PlotOption::FREEZE_HI) {
-            (self.colr & 0xF0) | (to_write & 0xF)
-        } else {
-            to_write
-        };
     }
 
     pub fn set_por(&mut self, data: u8) {
@@ -301,7 +306,11 @@ impl PixelCache {
         self.primary.bitp = 0;
     }
 
+    // Transparency is checked on COLR before dithering.
     fn do_plot(&mut self, x: u8, y: u8) {
+        if !self.is_opaque() {
+            return;
+        }
         let colour = if self.por.contains(PlotOption::DITHER) && self.bpp != BPP::_8 {
             if test_bit!(x ^ y, 0, u8) {
                 hi_nybble!(self.colr)
@@ -316,13 +325,13 @@ impl PixelCache {
             BPP::_4 => colour & 0xF,
             BPP::_8 => colour,
         };
-        if self.should_plot(masked_colour) {
-            self.primary.write_pix(x, masked_colour);
-        }
+        self.primary.write_pix(x, masked_colour);
     }
 
-    fn should_plot(&self, col: u8) -> bool {
-        let mask = if self.por.contains(PlotOption::FREEZE_HI) {0xF} else {0xFF};
-        self.por.contains(PlotOption::TRANSPARENT) || (col & mask) != 0
+    // Colour 0 is transparent unless the transparent option is set.
+    // Only the low nybble is checked, except in 8bpp mode without freeze high.
+    fn is_opaque(&self) -> bool {
+        let mask = if self.bpp == BPP::_8 && !self.por.contains(PlotOption::FREEZE_HI) {0xFF} else {0xF};
+        self.por.contains(PlotOption::TRANSPARENT) || (self.colr & mask) != 0
     }
-}
\ No newline at end of file
+}
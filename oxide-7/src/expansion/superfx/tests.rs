@@ -0,0 +1,115 @@
+// Plotting through the pixel cache, in each colour depth and with each POR option.
+use super::*;
+
+const TRANSPARENT: u8 = 0x01;
+const DITHER: u8 = 0x02;
+const HI_NYBBLE: u8 = 0x04;
+const FREEZE_HI: u8 = 0x08;
+const OBJ_MODE: u8 = 0x10;
+
+fn new_cache(scmr: u8, por: u8) -> PixelCache {
+    let mut cache = PixelCache::new();
+    cache.set_screen_base(0);
+    cache.set_screen_mode(scmr);
+    cache.set_por(por);
+    cache
+}
+
+// Plot a colour at (x, 0) and return the pixel, or None if it was transparent.
+fn plot(cache: &mut PixelCache, x: u8, colr: u8) -> Option<u8> {
+    cache.set_colr(colr);
+    cache.fill(x, 0, &[0xFF; 8]);
+    assert!(cache.try_plot(x, 0));
+    let pixel = cache.read_pixel(x % 8);
+    // The fill sets every bit.
+    if pixel == 0xFF {None} else {Some(pixel)}
+}
+
+#[test]
+fn colour_depth() {
+    assert_eq!(new_cache(0x00, 0).bpp(), 2);
+    assert_eq!(new_cache(0x01, 0).bpp(), 4);
+    // Mode 2 is unused and acts as 4bpp.
+    assert_eq!(new_cache(0x02, 0).bpp(), 4);
+    assert_eq!(new_cache(0x03, 0).bpp(), 8);
+    assert_eq!(new_cache(0x03, 0).flush_bitplane_pairs(), 4);
+}
+
+#[test]
+fn plot_masks_colour() {
+    assert_eq!(plot(&mut new_cache(0x00, 0), 0, 0xF7), Some(0x03));
+    assert_eq!(plot(&mut new_cache(0x01, 0), 0, 0xF7), Some(0x07));
+    assert_eq!(plot(&mut new_cache(0x03, 0), 0, 0xF7), Some(0xF7));
+}
+
+#[test]
+fn transparency() {
+    // Only the low nybble of COLR is checked, even in 2bpp mode.
+    assert_eq!(plot(&mut new_cache(0x00, 0), 0, 0x10), None);
+    assert_eq!(plot(&mut new_cache(0x00, 0), 0, 0x04), Some(0x00));
+    assert_eq!(plot(&mut new_cache(0x01, 0), 0, 0x10), None);
+    assert_eq!(plot(&mut new_cache(0x00, TRANSPARENT), 0, 0x00), Some(0x00));
+    // 8bpp checks the whole byte, unless the high nybble is frozen.
+    assert_eq!(plot(&mut new_cache(0x03, 0), 0, 0x10), Some(0x10));
+    assert_eq!(plot(&mut new_cache(0x03, FREEZE_HI), 0, 0x10), None);
+}
+
+#[test]
+fn dither() {
+    // Odd pixels use the high nybble.
+    let mut cache = new_cache(0x01, DITHER);
+    assert_eq!(plot(&mut cache, 0, 0x9A), Some(0x0A));
+    assert_eq!(plot(&mut cache, 1, 0x9A), Some(0x09));
+    // The transparency check happens before dithering.
+    assert_eq!(plot(&mut cache, 1, 0x01), Some(0x00));
+    assert_eq!(plot(&mut cache, 0, 0x90), None);
+    // No dithering in 8bpp mode.
+    assert_eq!(plot(&mut new_cache(0x03, DITHER), 1, 0x9A), Some(0x9A));
+}
+
+#[test]
+fn colr_options() {
+    let mut cache = new_cache(0x03, 0);
+    assert_eq!(plot(&mut cache, 0, 0x5F), Some(0x5F));
+    // The high nybble of the source is written to the low nybble.
+    cache.set_por(HI_NYBBLE);
+    assert_eq!(plot(&mut cache, 0, 0xA3), Some(0x5A));
+    // Only the low nybble is written.
+    cache.set_por(FREEZE_HI);
+    assert_eq!(plot(&mut cache, 0, 0x3C), Some(0x5C));
+    cache.set_por(0);
+    assert_eq!(plot(&mut cache, 0, 0x3C), Some(0x3C));
+}
+
+#[test]
+fn flush_8bpp() {
+    let mut cache = new_cache(0x03, 0);
+    cache.set_screen_base(0x01);
+    cache.fill(8, 9, &[0; 8]);
+    cache.set_colr(0x81);
+    cache.try_plot(8, 9);
+    cache.set_colr(0x02);
+    cache.try_plot(15, 9);
+    // Move the line to the secondary cache.
+    cache.fill(16, 9, &[0; 8]);
+
+    assert!(cache.needs_flush());
+    let mut buffer = [[0; 2]; 4];
+    // Tile 0x11, 64 bytes per tile, second row.
+    assert_eq!(cache.flush(&mut buffer), 0x70_0400 + (0x11 * 0x40) + 2);
+    assert_eq!(buffer, [[0x80, 0x01], [0x00, 0x00], [0x00, 0x00], [0x00, 0x80]]);
+    assert!(!cache.needs_flush());
+}
+
+#[test]
+fn obj_mode() {
+    // Set with HT0 and HT1, or the POR bit.
+    for (scmr, por) in [(0x24, 0), (0x00, OBJ_MODE)] {
+        let mut cache = new_cache(scmr, por);
+        // Each 128x128 quadrant is 256 tiles, in rows of 16.
+        assert_eq!(cache.calc_tile_addr(0x88, 0x10), 0x70_0000 + ((0x100 + 0x20 + 0x1) * 0x10));
+        assert_eq!(cache.calc_tile_addr(0x08, 0x80), 0x70_0000 + ((0x200 + 0x1) * 0x10));
+    }
+    // 192 lines high: 24 tiles per column.
+    assert_eq!(new_cache(0x20, 0).calc_tile_addr(0x08, 0x10), 0x70_0000 + ((0x18 + 0x2) * 0x10));
+}
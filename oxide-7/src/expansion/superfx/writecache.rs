@@ -1,11 +1,17 @@
 // Cache for writing data back to RAM.
 
+use serde::{
+    Serialize,
+    Deserialize
+};
+
 pub enum WritebackData {
     Byte(u8),
     Word(u8, u8),   // lo, hi
     None
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct WriteCache {
     data_lo:    Option<u8>,
     data_hi:    Option<u8>,
@@ -0,0 +1,65 @@
+// Automatic frame skip: rendering is skipped when frames take longer than the frontend's time budget.
+use alloc::vec::Vec;
+use std::time::{
+    Duration,
+    Instant
+};
+
+pub struct FrameSkip {
+    budget:     Option<Duration>,   // Time allowed to run each frame.
+    max_skip:   usize,              // Most frames that can be skipped in a row.
+
+    frame:      u64,        // Frames run since power on.
+    start:      Instant,    // When the current frame started.
+    debt:       Duration,   // Time over budget that hasn't been made up yet.
+    skip_count: usize,      // Frames skipped in a row.
+    skipped:    Vec<u64>,   // Frames skipped since the last call to take_skipped.
+}
+
+impl FrameSkip {
+    pub fn new() -> Self {
+        FrameSkip {
+            budget:     None,
+            max_skip:   0,
+
+            frame:      0,
+            start:      Instant::now(),
+            debt:       Duration::from_secs(0),
+            skip_count: 0,
+            skipped:    Vec::new(),
+        }
+    }
+
+    pub fn set_budget(&mut self, budget: Option<Duration>, max_skip: usize) {
+        self.budget = budget;
+        self.max_skip = max_skip;
+        self.debt = Duration::from_secs(0);
+        self.skip_count = 0;
+    }
+
+    // Call before running a frame. Returns true if the frame shouldn't be rendered.
+    pub fn start_frame(&mut self) -> bool {
+        let skip = self.budget.is_some() && (self.debt > Duration::from_secs(0)) && (self.skip_count < self.max_skip);
+        if skip {
+            self.skip_count += 1;
+            self.skipped.push(self.frame);
+        } else {
+            self.skip_count = 0;
+        }
+        self.start = Instant::now();
+        skip
+    }
+
+    pub fn end_frame(&mut self) {
+        if let Some(budget) = self.budget {
+            // The debt is capped so a single slow frame (e.g. when loading) doesn't cause a long run of skips.
+            let max_debt = budget * (self.max_skip as u32);
+            self.debt = core::cmp::min((self.debt + self.start.elapsed()).saturating_sub(budget), max_debt);
+        }
+        self.frame += 1;
+    }
+
+    pub fn take_skipped(&mut self) -> Vec<u64> {
+        core::mem::take(&mut self.skipped)
+    }
+}
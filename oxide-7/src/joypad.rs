@@ -1,7 +1,18 @@
 // Joypad registers
 
+use alloc::{
+    boxed::Box,
+    string::String,
+    vec::Vec
+};
 use bitflags::bitflags;
 
+use crate::savestate::{
+    Stateful,
+    StateWriter,
+    StateReader
+};
+
 bitflags! {
     // Flags for buttons.
     #[derive(Default)]
@@ -21,9 +32,126 @@ bitflags! {
     }
 }
 
+impl From<crate::Button> for Button {
+    fn from(button: crate::Button) -> Self {
+        use crate::Button as B;
+        match button {
+            B::Up       => Button::UP,
+            B::Down     => Button::DOWN,
+            B::Left     => Button::LEFT,
+            B::Right    => Button::RIGHT,
+            B::A        => Button::A,
+            B::B        => Button::B,
+            B::X        => Button::X,
+            B::Y        => Button::Y,
+            B::Start    => Button::START,
+            B::Select   => Button::SELECT,
+            B::L        => Button::L,
+            B::R        => Button::R
+        }
+    }
+}
+
+/// Provides joypad input, as an alternative to setting buttons with SNES::set_button.
+/// The provider is queried at the moment the emulated joypads are latched,
+/// so input is never applied part way through a read.
+pub trait InputProvider {
+    /// Return true if the button is held on the joypad (0-3).
+    fn is_pressed(&mut self, joypad: usize, button: crate::Button) -> bool;
+}
+
+/// A device plugged into one of the two controller ports, in place of a standard joypad.
+/// This allows devices such as mice, multitaps, or more exotic hardware to be implemented outside the crate.
+/// Automatic joypad reading ($4218-$421F) also goes through the device.
+pub trait Peripheral {
+    /// Called when the CPU writes to $4016.
+    /// Bit 0 (OUT0) is the latch line shared by both ports.
+    /// A frontend can also use this to drive feedback such as rumble.
+    fn write(&mut self, _data: u8) {}
+
+    /// Clock the next bit out of the device, on a read of $4016 (port 1) or $4017 (port 2).
+    /// Bits 0 and 1 are data lines D0 and D1. Other bits are ignored.
+    fn read(&mut self) -> u8;
+}
+
+/// One of the two controller ports on the front of the console.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Port {
+    /// Port 1, read through $4016. Signals from this port have port number 0.
+    One,
+    /// Port 2, read through $4017. Signals from this port have port number 1.
+    Two,
+}
+
+impl Port {
+    fn index(self) -> usize {
+        match self {
+            Port::One => 0,
+            Port::Two => 1,
+        }
+    }
+}
+
+/// An empty controller port.
+/// A standard joypad shifts out 1s after its 16 bits, so games can tell when nothing is plugged in
+/// by reading more bits: with this device, every bit is 0.
+pub struct Unplugged;
+
+impl Peripheral for Unplugged {
+    fn read(&mut self) -> u8 {
+        0
+    }
+}
+
+/// A raw signal on the controller ports.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PortSignal {
+    /// The CPU wrote to $4016. Bit 0 (OUT0) is the latch line.
+    Output(u8),
+    /// A port (0 or 1) was clocked by a read of $4016 or $4017.
+    /// The data is the level of lines D0 and D1 that was returned.
+    Clock {
+        port:   usize,
+        data:   u8,
+    },
+    /// The CPU changed the IOBit line of a port (0 or 1) through WRIO ($4201).
+    IOBit {
+        port:   usize,
+        high:   bool,
+    },
+    /// Automatic joypad reading clocked 16 bits out of a port (0 or 1).
+    /// D0 and D1 are the bits read from each data line, first bit in the top.
+    AutoRead {
+        port:   usize,
+        d0:     u16,
+        d1:     u16,
+    },
+}
+
+/// Receives every signal on the controller ports.
+/// This can be used to log the protocol of unknown devices while prototyping a Peripheral.
+pub trait PortMonitor {
+    fn signal(&mut self, signal: PortSignal);
+}
+
+// A button press or release waiting for the joypads to be read.
+#[derive(Clone, Copy)]
+struct QueuedInput {
+    cycle:      u64,    // Master cycle count when the input can be applied.
+    joypad:     usize,
+    button:     Button,
+    pressed:    bool,
+}
+
 // The Joypads
 pub struct JoypadMem {
     joypads: [Joypad; 4],   // "External" joypads.
+    queue: Vec<QueuedInput>,
+    queue_input: bool,      // Queue buttons that are set, instead of applying them immediately.
+    provider: Option<Box<dyn InputProvider>>,
+    peripherals: [Option<Box<dyn Peripheral>>; 2],  // Devices plugged into the ports in place of joypads 1 and 2.
+    monitor: Option<Box<dyn PortMonitor>>,
+    io_bits: u8,            // Reg 4201 bits 6 and 7
 
     joypad_regs: [u8; 8],   // Regs 4218-421F
 
@@ -36,6 +164,12 @@ impl JoypadMem {
     pub fn new() -> Self {
         JoypadMem {
             joypads:        [Joypad::new(); 4],
+            queue:          Vec::new(),
+            queue_input:    false,
+            provider:       None,
+            peripherals:    [None, None],
+            monitor:        None,
+            io_bits:        0xC0,
 
             joypad_regs:    [0; 8],
 
@@ -45,9 +179,66 @@ impl JoypadMem {
         }
     }
 
-    // Set buttons externally.
-    pub fn set_buttons(&mut self, button: Button, val: bool, joypad: usize) {
-        self.joypads[joypad].set_buttons(button, val);
+    // Set buttons externally. If input is queued, the buttons are set the next time the joypads are read.
+    pub fn set_buttons(&mut self, button: Button, val: bool, joypad: usize, cycle: u64) {
+        if self.queue_input {
+            self.queue_buttons(button, val, joypad, cycle);
+        } else {
+            self.joypads[joypad].set_buttons(button, val);
+        }
+    }
+
+    // Set buttons the first time the joypads are read at or after the cycle specified.
+    pub fn queue_buttons(&mut self, button: Button, val: bool, joypad: usize, cycle: u64) {
+        self.queue.push(QueuedInput {
+            cycle:      cycle,
+            joypad:     joypad,
+            button:     button,
+            pressed:    val,
+        });
+    }
+
+    // When disabled, any queued input is applied immediately.
+    pub fn set_input_queue(&mut self, enable: bool) {
+        self.queue_input = enable;
+        if !enable {
+            self.apply_queue(u64::MAX);
+        }
+    }
+
+    // Get the buttons currently held on the joypad, in the format the SNES reads them.
+    pub fn held_buttons(&self, joypad: usize) -> u16 {
+        self.joypads[joypad].buttons.bits()
+    }
+
+    // Set a provider to query for buttons, instead of setting them externally.
+    pub fn set_input_provider(&mut self, provider: Option<Box<dyn InputProvider>>) -> Option<Box<dyn InputProvider>> {
+        core::mem::replace(&mut self.provider, provider)
+    }
+
+    // Plug a device into a controller port.
+    pub fn set_peripheral(&mut self, port: Port, device: Option<Box<dyn Peripheral>>) -> Option<Box<dyn Peripheral>> {
+        core::mem::replace(&mut self.peripherals[port.index()], device)
+    }
+
+    // Set a monitor to receive all signals on the ports.
+    pub fn set_port_monitor(&mut self, monitor: Option<Box<dyn PortMonitor>>) -> Option<Box<dyn PortMonitor>> {
+        core::mem::replace(&mut self.monitor, monitor)
+    }
+
+    // Called when WRIO is written. Bits 6 and 7 go to the IOBit line of ports 1 and 2.
+    pub fn write_io(&mut self, data: u8) {
+        let io_bits = data & 0xC0;
+        let changed = io_bits ^ self.io_bits;
+        self.io_bits = io_bits;
+        for port in 0..2 {
+            if test_bit!(changed, 6 + port, u8) {
+                self.notify(PortSignal::IOBit {
+                    port:   port,
+                    high:   test_bit!(io_bits, 6 + port, u8),
+                });
+            }
+        }
     }
 
     // Set new-style joypad reading.
@@ -61,9 +252,11 @@ impl JoypadMem {
     }
 
     // Called at V-blank.
-    pub fn prepare_read(&mut self) {
+    pub fn prepare_read(&mut self, cycle: u64) {
         if self.counter {
             self.ready = false;
+            self.apply_queue(cycle);
+            self.poll_provider();
 
             // TODO: clock the following.
             for (i, j) in self.joypads.iter_mut().enumerate() {
@@ -74,17 +267,52 @@ impl JoypadMem {
                 self.joypad_regs[reg_base + 1] = regs.1;
             }
 
+            // Peripherals are read serially, with D1 going into the registers for joypads 3 and 4.
+            for (i, p) in self.peripherals.iter_mut().enumerate() {
+                if let Some(device) = p.as_mut() {
+                    device.write(1);
+                    device.write(0);
+                    let (mut d0, mut d1) = (0_u16, 0_u16);
+                    for _ in 0..16 {
+                        let data = device.read();
+                        d0 = (d0 << 1) | ((data & 1) as u16);
+                        d1 = (d1 << 1) | (((data >> 1) & 1) as u16);
+                    }
+                    let reg_base = i * 2;
+                    self.joypad_regs[reg_base] = lo!(d0);
+                    self.joypad_regs[reg_base + 1] = hi!(d0);
+                    self.joypad_regs[reg_base + 4] = lo!(d1);
+                    self.joypad_regs[reg_base + 5] = hi!(d1);
+                }
+            }
+
+            for port in 0..2 {
+                let reg_base = port * 2;
+                self.notify(PortSignal::AutoRead {
+                    port:   port,
+                    d0:     make16!(self.joypad_regs[reg_base + 1], self.joypad_regs[reg_base]),
+                    d1:     make16!(self.joypad_regs[reg_base + 5], self.joypad_regs[reg_base + 4]),
+                });
+            }
+
             self.ready = true;
         }
     }
 
     // Call to latch all joypads.
-    pub fn latch_all(&mut self, data: u8) {
+    pub fn latch_all(&mut self, data: u8, cycle: u64) {
+        self.notify(PortSignal::Output(data));
+        for device in self.peripherals.iter_mut().flatten() {
+            device.write(data);
+        }
+
         // Write 1 to strobe.
         if test_bit!(data, 0, u8) {
             self.strobe = true;
         } else {
             if self.strobe {
+                self.apply_queue(cycle);
+                self.poll_provider();
                 for j in self.joypads.iter_mut() {
                     j.latch();
                 }
@@ -93,12 +321,32 @@ impl JoypadMem {
         }
     }
 
-    // Read joypad register.
+    // Read $4016 (port 0) or $4017 (port 1). Only the data lines are driven: the rest of
+    // $4016 and the top 3 bits of $4017 are open bus. Bits 2-4 of $4017 are grounded, and read as 1.
+    pub fn read_port(&mut self, port: usize, open_bus: u8) -> u8 {
+        let data = match self.peripherals[port].as_mut() {
+            Some(device) => device.read() & 0x3,
+            None => {
+                // While the latch is high the shift register keeps reloading, so B is read every time.
+                if self.strobe {
+                    self.joypads[port].latch();
+                }
+                self.joypads[port].shift_bit()
+            },
+        };
+        self.notify(PortSignal::Clock {
+            port:   port,
+            data:   data,
+        });
+        match port {
+            0 => (open_bus & 0xFC) | data,
+            _ => (open_bus & 0xE0) | 0x1C | data,
+        }
+    }
+
+    // Read auto joypad register.
     pub fn read(&mut self, addr: u16) -> u8 {
         match addr {
-            0x4016 => self.joypads[0].shift_bit(),  // Shift the next one too?
-            0x4017 => self.joypads[1].shift_bit(),
-
             0x4218 => self.joypad_regs[0],
             0x4219 => self.joypad_regs[1],
             0x421A => self.joypad_regs[2],
@@ -113,6 +361,63 @@ impl JoypadMem {
     }
 }
 
+// Internal
+impl JoypadMem {
+    fn notify(&mut self, signal: PortSignal) {
+        if let Some(monitor) = self.monitor.as_mut() {
+            monitor.signal(signal);
+        }
+    }
+
+    // Apply queued input that is due, in the order it was queued.
+    fn apply_queue(&mut self, cycle: u64) {
+        let joypads = &mut self.joypads;
+        self.queue.retain(|input| if input.cycle <= cycle {
+            joypads[input.joypad].set_buttons(input.button, input.pressed);
+            false
+        } else {
+            true
+        });
+    }
+
+    // Update the buttons held on all joypads from the provider, if there is one.
+    fn poll_provider(&mut self) {
+        if let Some(provider) = self.provider.as_mut() {
+            for (i, j) in self.joypads.iter_mut().enumerate() {
+                for button in crate::Button::iter() {
+                    j.set_buttons(button.into(), provider.is_pressed(i, button));
+                }
+            }
+        }
+    }
+}
+
+// The buttons currently held are set externally, so they aren't stored.
+// Neither is the state of any peripherals.
+impl Stateful for JoypadMem {
+    fn save_state(&self, w: &mut StateWriter) {
+        for j in self.joypads.iter() {
+            w.write(&j.register);
+        }
+        w.write(&self.joypad_regs);
+        w.write(&self.counter);
+        w.write(&self.ready);
+        w.write(&self.strobe);
+        w.write(&self.io_bits);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), String> {
+        for j in self.joypads.iter_mut() {
+            r.read_into(&mut j.register)?;
+        }
+        r.read_into(&mut self.joypad_regs)?;
+        r.read_into(&mut self.counter)?;
+        r.read_into(&mut self.ready)?;
+        r.read_into(&mut self.strobe)?;
+        r.read_into(&mut self.io_bits)
+    }
+}
+
 // A single joypad
 #[derive(Clone, Copy)]
 struct Joypad {
@@ -1,6 +1,12 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg_attr(not(feature = "std"), macro_use)]
+extern crate alloc;
+
 #[macro_use]
 mod common;
 mod constants;
+mod sync;
 
 mod cpu;
 mod joypad;
@@ -8,23 +14,117 @@ mod mem;
 mod video;
 mod audio;
 mod expansion;
+mod events;
+mod rng;
+mod savestate;
+mod watch;
+mod builder;
+mod error;
 
+#[cfg(feature = "std")]
+mod dump;
+#[cfg(feature = "std")]
+mod frameskip;
+#[cfg(feature = "std")]
+mod limiter;
+#[cfg(feature = "std")]
+mod rewind;
 #[cfg(feature = "debug")]
 pub mod debug;
 
+use audio::FixedRate;
+#[cfg(feature = "std")]
 use audio::Resampler;
+use constants::machine::MachineTiming;
 use cpu::CPU;
-use mem::AddrBusA;
+use events::EventBus;
+use mem::{
+    AddrBusA,
+    rom::Cart
+};
+#[cfg(feature = "std")]
+use frameskip::FrameSkip;
+#[cfg(feature = "std")]
+use rewind::Rewind;
 use video::RenderTarget;
 
-use std::sync::{
+pub use joypad::{
+    InputProvider,
+    Peripheral,
+    Port,
+    PortMonitor,
+    PortSignal,
+    Unplugged
+};
+pub use audio::{
+    EnvelopePhase,
+    VoiceState
+};
+pub use builder::SNESBuilder;
+pub use common::Interrupt;
+pub use constants::machine::Region;
+pub use error::Error;
+pub use expansion::{
+    Expansion,
+    ExpansionProvider
+};
+pub use events::{
+    Event,
+    Processor,
+    UnsupportedFeature
+};
+pub use mem::{
+    ExpansionPort,
+    IOPort
+};
+pub use mem::rom::{
+    CartInfo,
+    EnhancementChip,
+    ROM,
+    ROMFormat,
+    SRAM
+};
+pub use video::VideoMemMonitor;
+#[cfg(feature = "std")]
+pub use dump::FrameDump;
+#[cfg(feature = "std")]
+pub use limiter::FrameLimiter;
+pub use watch::RamWatch;
+pub use savestate::{
+    StateComponent,
+    Stateful,
+    StateWriter,
+    StateReader,
+    THUMBNAIL_WIDTH,
+    THUMBNAIL_HEIGHT,
+    THUMBNAIL_SIZE,
+    read_thumbnail
+};
+
+use alloc::{
+    boxed::Box,
+    string::String,
+    vec::Vec
+};
+use core::sync::atomic::{
+    AtomicBool, Ordering
+};
+use sync::{
     Arc, Mutex
 };
 
 /// Size of destination buffer in bytes (R8G8B8A8 format).
+/// Frames are 224 lines: in the 239-line overscan mode, the extra lines are cut off unless enabled with SNES::set_overscan.
 pub const FRAME_BUFFER_SIZE: usize = 512 * 224 * 4;
+/// Size of destination buffer in bytes when hires blending is enabled (R8G8B8A8 format).
+pub const BLENDED_FRAME_BUFFER_SIZE: usize = 256 * 224 * 4;
+/// Sample rate of audio output by the SNES, before resampling.
+pub const NATIVE_SAMPLE_RATE: usize = 32_000;
+/// Default number of stereo samples per frame for headless audio.
+pub const HEADLESS_SAMPLES_PER_FRAME: usize = 534;
 
 /// Joypad buttons.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum Button {
     Up,
     Down,
@@ -40,90 +140,738 @@ pub enum Button {
     R
 }
 
+impl Button {
+    const ALL: [Button; 12] = [
+        Button::Up, Button::Down, Button::Left, Button::Right,
+        Button::A, Button::B, Button::X, Button::Y,
+        Button::Start, Button::Select, Button::L, Button::R
+    ];
+
+    /// Iterate over all of the buttons on a joypad.
+    pub fn iter() -> impl Iterator<Item = Button> {
+        Self::ALL.iter().cloned()
+    }
+
+    /// The name of the button, as accepted by from_str.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Button::Up      => "Up",
+            Button::Down    => "Down",
+            Button::Left    => "Left",
+            Button::Right   => "Right",
+            Button::A       => "A",
+            Button::B       => "B",
+            Button::X       => "X",
+            Button::Y       => "Y",
+            Button::Start   => "Start",
+            Button::Select  => "Select",
+            Button::L       => "L",
+            Button::R       => "R"
+        }
+    }
+}
+
+impl core::fmt::Display for Button {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// Parse a button from its name. This is case-insensitive.
+impl core::str::FromStr for Button {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::iter()
+            .find(|b| b.name().eq_ignore_ascii_case(s))
+            .ok_or_else(|| format!("Unknown button: {}", s))
+    }
+}
+
 /// A SNES.
 pub struct SNES {
     cpu:    CPU<AddrBusA>,    // CPU, along with mem bus and devices
 
-    frame:  RenderTarget
+    frames: [RenderTarget; 2],  // The front frame is read out while the back frame is drawn
+    front:  usize,              // Index of the front frame
+    blend:  bool,           // Blend hires pixel pairs into a 256-wide frame
+    widescreen: usize,      // Extra background pixels drawn on each side of the frame
+    overscan:   bool,       // Frames are 239 lines tall
+    frame_timestamp:    u64,    // Master cycle count at the end of the last frame
+    #[cfg(feature = "std")]
+    frame_skip: FrameSkip,
+    #[cfg(feature = "std")]
+    rewind:     Rewind,
+
+    paused: Arc<AtomicBool>, // Shared with audio handlers
+    region: Region,
+
+    load_events:    Vec<Event>, // Problems found when loading the cart, sent with the first frame
+    events:     EventBus,
 }
 
 impl SNES {
     /// Construct a new SNES with a cartridge inserted.
-    pub fn new(cart_path: &str, save_path: &str, dsp_rom_path: Option<&str>) -> Self {
-        let bus = AddrBusA::new(cart_path, save_path, dsp_rom_path);
-        let cpu = CPU::new(bus, constants::timing::INTERNAL_OP);
+    /// Returns an error if the cart can't be read or emulated.
+    /// If the archive feature is enabled, the cartridge can be a zip file containing the ROM.
+    /// The DSP ROM path can be a single ROM, or a directory of ROMs named dsp1, dsp1b, dsp2, dsp3 and dsp4
+    /// (with a .rom or .bin extension), where the one the game uses is picked automatically.
+    #[cfg(feature = "std")]
+    pub fn new(cart_path: &str, save_path: &str, dsp_rom_path: Option<&str>) -> Result<Self, Error> {
+        Self::builder_for(SNESBuilder::from_file(cart_path), save_path, dsp_rom_path).build()
+    }
 
-        SNES {
-            cpu: cpu,
+    /// Construct a new SNES with a cartridge inserted, and soft patch the ROM if there is a patch next to it.
+    /// A .bps or .ips file with the same name as the cart (for example game.bps for game.sfc) is applied
+    /// to the ROM as it is loaded, leaving the files unchanged. If both exist, the .bps patch is used.
+    /// If the patch can't be applied, the ROM is loaded unpatched and a warning is raised.
+    #[cfg(feature = "std")]
+    pub fn new_soft_patched(cart_path: &str, save_path: &str, dsp_rom_path: Option<&str>) -> Result<Self, Error> {
+        Self::builder_for(SNESBuilder::from_file(cart_path), save_path, dsp_rom_path)
+            .soft_patch(true)
+            .build()
+    }
 
-            frame: Arc::new(Mutex::new(Box::new([0; FRAME_BUFFER_SIZE])))
-        }
+    /// Construct a new SNES from ROM data that has already been loaded.
+    /// If the archive feature is enabled, the data can be a zip file containing the ROM.
+    /// Without the std feature, save RAM is only kept in memory and DSP carts can't be run.
+    pub fn from_rom_data(rom_data: &[u8], save_path: &str, dsp_rom_path: Option<&str>) -> Result<Self, Error> {
+        Self::builder_for(SNESBuilder::from_rom_data(rom_data), save_path, dsp_rom_path).build()
+    }
+
+    /// Construct a new SNES from ROM data, with a cart chip implemented outside of the crate.
+    /// If the provider doesn't support the cart, it is loaded as with from_rom_data.
+    pub fn from_rom_data_with_expansion(rom_data: &[u8], save_path: &str, dsp_rom_path: Option<&str>, provider: &mut dyn ExpansionProvider) -> Result<Self, Error> {
+        Self::builder_for(SNESBuilder::from_rom_data(rom_data), save_path, dsp_rom_path)
+            .expansion(provider)
+            .build()
     }
 
     /// Call at 60fps.
+    /// If the SNES is paused, this will just return the last frame.
     pub fn frame(&mut self, frame: &mut [u8]) {
-        // frame update?
-        self.cpu.start_frame(self.frame.clone());
+        if !self.is_paused() {
+            self.run_frame();
+        }
 
-        // When NMI is triggered, disable rendering of new frames.
-        while !self.cpu.step() {}
-        //self.cpu.enable_rendering(false);
+        self.copy_frame(frame);
+    }
+
+    /// Run n frames, and only copy out the last one.
+    /// This is faster than calling frame() n times, when the intermediate frames aren't needed.
+    /// If the SNES is paused, this will just return the last frame.
+    pub fn run_frames_collect_last(&mut self, n: usize, frame: &mut [u8]) {
+        if !self.is_paused() {
+            for _ in 0..n {
+                self.run_frame();
+            }
+        }
+
+        self.copy_frame(frame);
+    }
+
+    /// Stop the SNES from advancing.
+    /// While paused, audio handlers output silence.
+    pub fn pause(&mut self) {
+        self.paused.store(true, Ordering::Release);
+    }
+
+    /// Continue running after a call to pause.
+    pub fn resume(&mut self) {
+        self.paused.store(false, Ordering::Release);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::Acquire)
+    }
+
+    /// Average each pair of adjacent hires pixels into a single pixel.
+    /// When enabled, the buffer passed to frame() should be BLENDED_FRAME_BUFFER_SIZE bytes (256x224).
+    pub fn set_hires_blending(&mut self, enable: bool) {
+        self.blend = enable;
+    }
+
+    /// Experimental: draw the backgrounds past the left and right edges of the screen, for widescreen hacks.
+    /// `extra` pixels are added to each side: for example 48 gives a 352-wide frame.
+    /// Only modes 0-4 are extended, without sprites, windows or colour math. Set to 0 to disable.
+    /// The frame buffer size changes: use frame_width() and frame_buffer_size() to find the new size.
+    pub fn set_widescreen(&mut self, extra: usize) {
+        self.widescreen = extra;
+        self.resize_frames();
+        self.cpu.set_widescreen(extra);
+    }
+
+    /// Output the extra lines of the 239-line overscan mode. Disabled by default.
+    /// When enabled, frames are 239 lines tall, and the bottom 15 lines are black when the game shows 224 lines.
+    /// The frame buffer size changes: use frame_height() and frame_buffer_size() to find the new size.
+    pub fn set_overscan(&mut self, enable: bool) {
+        self.overscan = enable;
+        self.resize_frames();
+        self.cpu.set_overscan(enable);
+    }
+
+    /// Width in pixels of the frame output by frame().
+    /// This is 512 by default, or 256 with hires blending, and increases with widescreen.
+    pub fn frame_width(&self) -> usize {
+        let width = constants::screen::H_RES + (self.widescreen * 2);
+        if self.blend {width} else {width * 2}
+    }
+
+    /// Height in lines of the frame output by frame().
+    /// This is 224, or 239 with overscan.
+    pub fn frame_height(&self) -> usize {
+        if self.overscan {constants::screen::V_RES_OVERSCAN} else {constants::screen::V_RES}
+    }
+
+    /// Size in bytes of the buffer to pass to frame() (R8G8B8A8 format).
+    pub fn frame_buffer_size(&self) -> usize {
+        self.frame_width() * self.frame_height() * 4
+    }
 
-        let frame_in = self.frame.lock().unwrap();
-        frame.copy_from_slice(&(*frame_in));
+    /// Emulated time at the end of the last frame produced, in master cycles since power on.
+    /// Compare with the audio handler timestamp to measure A/V desync.
+    pub fn frame_timestamp(&self) -> u64 {
+        self.frame_timestamp
+    }
+
+    /// Enable or disable the limit of 32 sprites per line.
+    /// This is enabled by default, as on hardware.
+    /// Disabling it removes flicker in crowded scenes, but some games rely on the limit to hide sprites.
+    pub fn set_sprite_limit(&mut self, enable: bool) {
+        self.cpu.set_sprite_limit(enable);
+    }
+
+    /// Draw each frame on the calling thread as well as on the render thread, and compare the two.
+    /// Frames that differ are reported with Event::RenderMismatch.
+    /// This roughly doubles the time spent rendering, so it is meant for checking the threaded renderer.
+    #[cfg(feature = "std")]
+    pub fn set_render_verification(&mut self, enable: bool) {
+        self.cpu.set_render_verification(enable);
+    }
+
+    /// Emulate the PPU state at power on, instead of starting with clean video memory.
+    /// VRAM, CGRAM, OAM and the PPU registers are filled with random values and forced blank is off,
+    /// so the first frame shows garbage until the game initialises the PPU.
+    /// Also, as on hardware, the first line after forced blank is released has no sprites,
+    /// and reading VRAM, OAM or CGRAM during active display returns what the PPU is accessing:
+    /// VRAM reads return 0, OAM reads return the Y coordinate of the object being checked for the next line,
+    /// and CGRAM reads while pixels are output return the backdrop colour.
+    /// Call this before the first frame, and after `randomize_wram` if that is used:
+    /// the garbage comes from the same seed.
+    pub fn set_power_on_garbage(&mut self, enable: bool) {
+        self.cpu.set_power_on_garbage(enable);
+    }
+
+    /// Automatically skip rendering frames when the SNES can't keep up, for slow devices.
+    /// `budget` is the time that each frame is allowed to take to run (for example 16ms at 60fps).
+    /// When frames go over budget, the following frames are emulated without being drawn until the
+    /// time is made up, but no more than `max_skip` in a row. Skipped frames leave the previous frame in the buffer.
+    /// Set the budget to None to draw every frame (the default).
+    #[cfg(feature = "std")]
+    pub fn set_frame_budget(&mut self, budget: Option<std::time::Duration>, max_skip: usize) {
+        self.frame_skip.set_budget(budget, max_skip);
+    }
+
+    /// Take the numbers of the frames that weren't drawn since the last call.
+    /// Frames are numbered from 0 at power on.
+    #[cfg(feature = "std")]
+    pub fn take_skipped_frames(&mut self) -> Vec<u64> {
+        self.frame_skip.take_skipped()
+    }
+
+    /// Enable or disable fast boot of the APU.
+    /// When enabled, the IPL ROM's upload protocol is emulated at a high level, so the APU
+    /// responds to the CPU immediately instead of polling in the ROM loops. This shortens the time
+    /// games spend uploading audio programs, which helps when fast-forwarding (e.g. TAS verification).
+    /// Audio programs are uploaded exactly as before, but the timing of the handshake changes,
+    /// so this is disabled by default.
+    pub fn set_fast_apu_boot(&mut self, enable: bool) {
+        self.cpu.set_fast_apu_boot(enable);
+    }
+
+    /// Change the region the game sees in the PPU status register ($213F bit 4), without changing the timing.
+    /// By default this matches the region the SNES was created with.
+    /// Region-locked games check this, so overriding it lets them run on a console of the other region.
+    pub fn set_region_flag(&mut self, region: Region) {
+        self.cpu.set_region_flag(region);
+    }
+
+    /// Enable or disable a high-pass filter at the end of the audio mixer.
+    /// This removes any DC offset from the output, so long recordings don't drift.
+    /// It is disabled by default, so the output is exactly what the DSP generates.
+    pub fn set_dc_block(&mut self, enable: bool) {
+        self.cpu.set_dc_block(enable);
     }
 
     /// Call this at the start to enable audio.
     /// It creates a SNESAudioHandler that can be sent to the audio thread.
+    /// This can be called more than once: each handler receives all of the audio.
+    #[cfg(feature = "std")]
     pub fn enable_audio(&mut self, sample_rate: f64) -> SNESAudioHandler {
-        let rx = self.cpu.get_audio_rx().expect("Audio already enabled!");
+        let rx = self.cpu.new_audio_rx();
 
         SNESAudioHandler {
-            resampler: Resampler::new(rx, sample_rate),
+            resampler:  Resampler::new(rx, sample_rate),
+            paused:     self.paused.clone(),
+        }
+    }
+
+    /// Enable audio without an audio device or thread.
+    /// It creates a SNESHeadlessAudio that outputs a fixed number of samples each frame,
+    /// at the native sample rate. The output is the same each time the SNES is given the same input.
+    pub fn enable_headless_audio(&mut self, samples_per_frame: usize) -> SNESHeadlessAudio {
+        let rx = self.cpu.new_audio_rx();
+
+        SNESHeadlessAudio {
+            source:             FixedRate::new(rx),
+            samples_per_frame:  samples_per_frame,
         }
     }
 
     /// Sets a button on the specified joypad.
+    /// If the input queue is enabled, the button is set the next time the joypads are read.
     pub fn set_button(&mut self, button: Button, val: bool, joypad: usize) {
-        use joypad::Button as JB;
-        self.cpu.set_buttons(match button {
-            Button::Up      => JB::UP,
-            Button::Down    => JB::DOWN,
-            Button::Left    => JB::LEFT,
-            Button::Right   => JB::RIGHT,
-            Button::A       => JB::A,
-            Button::B       => JB::B,
-            Button::X       => JB::X,
-            Button::Y       => JB::Y,
-            Button::Start   => JB::START,
-            Button::Select  => JB::SELECT,
-            Button::L       => JB::L,
-            Button::R       => JB::R
-        }, val, joypad);
+        self.cpu.set_buttons(button.into(), val, joypad);
+    }
+
+    /// Queue buttons set with set_button, and only apply them when the game next reads the joypads
+    /// (at the auto-joypad read in V-blank, or when it latches them through $4016).
+    /// This means input set from another thread can never change part way through a frame,
+    /// so recorded input always replays the same way. Disabled by default.
+    ///
+    /// Disabling the queue applies any input still waiting.
+    pub fn set_input_queue(&mut self, enable: bool) {
+        self.cpu.set_input_queue(enable);
+    }
+
+    /// Set a button on the specified joypad the first time the joypads are read
+    /// at or after the master cycle specified (see frame_timestamp).
+    /// This works whether or not the input queue is enabled, and is useful for replaying input with exact timing.
+    pub fn queue_button(&mut self, button: Button, val: bool, joypad: usize, cycle: u64) {
+        self.cpu.queue_buttons(button.into(), val, joypad, cycle);
+    }
+
+    /// Get the buttons held on the specified joypad (0-3), as a bitmask in the order the SNES reads them.
+    /// Bit 15 is B, down to bit 4 which is R. A set bit means the button is held.
+    /// If an input provider is set, this is the input from the last time the joypads were read.
+    pub fn held_buttons(&self, joypad: usize) -> u16 {
+        self.cpu.held_buttons(joypad)
+    }
+
+    /// Query the provider for input whenever the emulated joypads are read,
+    /// instead of using set_button. Buttons set with set_button are ignored while a provider is set.
+    /// Returns the previous provider, if there was one.
+    pub fn set_input_provider(&mut self, provider: Box<dyn InputProvider>) -> Option<Box<dyn InputProvider>> {
+        self.cpu.set_input_provider(Some(provider))
+    }
+
+    /// Remove the input provider, and go back to using set_button.
+    pub fn remove_input_provider(&mut self) -> Option<Box<dyn InputProvider>> {
+        self.cpu.set_input_provider(None)
+    }
+
+    /// Plug a device into a controller port, in place of the joypad.
+    /// Returns the previous device, if there was one.
+    pub fn set_peripheral(&mut self, port: Port, device: Box<dyn Peripheral>) -> Option<Box<dyn Peripheral>> {
+        self.cpu.set_peripheral(port, Some(device))
+    }
+
+    /// Remove the device from a controller port, and go back to using the joypad.
+    pub fn remove_peripheral(&mut self, port: Port) -> Option<Box<dyn Peripheral>> {
+        self.cpu.set_peripheral(port, None)
+    }
+
+    /// Receive every latch, clock and IOBit signal on the controller ports, along with the data returned.
+    /// This is useful for logging the protocol of devices that aren't emulated.
+    /// Returns the previous monitor, if there was one.
+    pub fn set_port_monitor(&mut self, monitor: Box<dyn PortMonitor>) -> Option<Box<dyn PortMonitor>> {
+        self.cpu.set_port_monitor(Some(monitor))
+    }
+
+    /// Stop monitoring the controller ports.
+    pub fn remove_port_monitor(&mut self) -> Option<Box<dyn PortMonitor>> {
+        self.cpu.set_port_monitor(None)
+    }
+
+    /// Receive every write to VRAM, OAM and CGRAM, with the scanline it happened on.
+    /// This is useful for graphics debuggers and for extracting assets as they are uploaded.
+    /// Returns the previous monitor, if there was one.
+    pub fn set_video_mem_monitor(&mut self, monitor: Box<dyn VideoMemMonitor>) -> Option<Box<dyn VideoMemMonitor>> {
+        self.cpu.set_video_mem_monitor(Some(monitor))
+    }
+
+    /// Stop monitoring video memory.
+    pub fn remove_video_mem_monitor(&mut self) -> Option<Box<dyn VideoMemMonitor>> {
+        self.cpu.set_video_mem_monitor(None)
+    }
+
+    /// Attach a device to the expansion port on the bottom of the SNES.
+    /// Without a device, reads from the expansion port return open bus.
+    /// Returns the previous device, if there was one.
+    pub fn set_expansion_port(&mut self, device: Box<dyn ExpansionPort>) -> Option<Box<dyn ExpansionPort>> {
+        self.cpu.set_expansion_port(Some(device))
+    }
+
+    /// Remove the device attached to the expansion port.
+    pub fn remove_expansion_port(&mut self) -> Option<Box<dyn ExpansionPort>> {
+        self.cpu.set_expansion_port(None)
+    }
+
+    /// Attach a device to the programmable I/O port pins ($4201 / $4213).
+    /// Returns the previous device, if there was one.
+    pub fn set_io_port(&mut self, device: Box<dyn IOPort>) -> Option<Box<dyn IOPort>> {
+        self.cpu.set_io_port(Some(device))
+    }
+
+    /// Remove the device attached to the programmable I/O port.
+    pub fn remove_io_port(&mut self) -> Option<Box<dyn IOPort>> {
+        self.cpu.set_io_port(None)
     }
 
     /// Get the name of the ROM currently running.
     pub fn rom_name(&self) -> String {
         self.cpu.rom_name()
     }
+
+    /// The console region: this sets the timing, and the frame rate to run at.
+    /// By default, it is the region the cart was made for.
+    pub fn region(&self) -> Region {
+        self.region
+    }
+
+    /// Get information about the inserted cartridge, including the format it was loaded from.
+    pub fn cart_info(&self) -> CartInfo {
+        self.cpu.cart_info()
+    }
+
+    /// Get the state of each of the 8 DSP voices, for visualising the music.
+    /// Call this after each frame to follow the envelopes and pitch over time.
+    pub fn voice_states(&self) -> [VoiceState; 8] {
+        self.cpu.voice_states()
+    }
+
+    /// Read-only view of work RAM (128kB, $7E0000-$7FFFFF).
+    /// Call this between frames.
+    pub fn wram(&self) -> &[u8] {
+        self.cpu.get_wram()
+    }
+
+    /// Read the current values of the addresses in the watch.
+    /// Call this between frames. This is much cheaper than reading each address through the bus.
+    pub fn update_watch(&self, watch: &mut RamWatch) {
+        watch.update(self.cpu.get_wram());
+    }
+
+    /// Fill work RAM with random values, as on real hardware at power on, instead of zeroes.
+    /// Call this before the first frame. The same seed always gives the same RAM contents,
+    /// and the random state is stored in savestates, so replays and loaded states stay in sync.
+    pub fn randomize_wram(&mut self, seed: u64) {
+        self.cpu.randomize_wram(seed);
+    }
+
+    /// Copy of VRAM (64kB).
+    pub fn vram(&self) -> Vec<u8> {
+        self.cpu.copy_vram()
+    }
+
+    /// Copy of CGRAM (palette memory, 512 bytes).
+    pub fn cgram(&self) -> Vec<u8> {
+        self.cpu.copy_cgram()
+    }
+
+    /// Copy of OAM (sprite memory, 544 bytes).
+    /// This uses the layout the SNES uses: a 512 byte low table, followed by a 32 byte high table.
+    pub fn oam(&self) -> Vec<u8> {
+        self.cpu.copy_oam()
+    }
+
+    /// Subscribe to events from the SNES.
+    /// Any number of subscribers can exist at once: each receives every event sent after subscribing.
+    pub fn subscribe_events(&mut self) -> sync::Receiver<Event> {
+        self.events.subscribe()
+    }
+
+    /// List the hardware features the game has used that aren't emulated, in the order they were first used.
+    /// If a game misbehaves, this can help explain why.
+    pub fn compat_report(&self) -> Vec<UnsupportedFeature> {
+        self.events.unsupported_features()
+    }
+
+    /// Save the full state of the SNES.
+    /// The state includes a thumbnail of the current frame, which can be extracted with read_thumbnail.
+    /// If the compress feature is enabled, the state is compressed with zstd.
+    #[cfg(feature = "std")]
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut writer = StateWriter::new();
+        writer.write(&self.rom_name());
+        self.cpu.save_state(&mut writer);
+
+        let thumbnail = savestate::make_thumbnail(&self.frames[self.front].lock(), self.widescreen);
+        writer.finish(&thumbnail)
+    }
+
+    /// Load a state created by save_state.
+    /// The state must have been saved while running the same ROM.
+    /// If the state can't be loaded, the SNES is left as it was.
+    #[cfg(feature = "std")]
+    pub fn load_state(&mut self, state: &[u8]) -> Result<(), String> {
+        let mut reader = StateReader::new(state)?;
+        let rom_name: String = reader.read()?;
+        if rom_name != self.rom_name() {
+            return Err(format!("Savestate is for a different ROM: {}", rom_name));
+        }
+        self.load_machine_state(&mut reader)?;
+
+        self.events.send(Event::StateLoaded);
+        Ok(())
+    }
+
+    /// Export the state of a single component, without a header or thumbnail.
+    /// The data is the same as that component's part of a savestate.
+    /// Exporting each component from two builds at the same point and comparing the bytes
+    /// shows which part of the system has diverged.
+    #[cfg(feature = "std")]
+    pub fn export_component_state(&self, component: StateComponent) -> Vec<u8> {
+        let mut writer = StateWriter::new();
+        self.cpu.save_component_state(component, &mut writer);
+        writer.into_data()
+    }
+
+    /// Save a point that can be rewound to. Call this every frame (or every few frames) while running.
+    /// Only the difference to the previous point is kept, so each point takes much less memory than a savestate.
+    /// When the rewind buffer is full, the oldest point is dropped.
+    #[cfg(feature = "std")]
+    pub fn push_rewind_point(&mut self) {
+        let mut writer = StateWriter::new();
+        self.cpu.save_state(&mut writer);
+        self.rewind.push(writer.into_data());
+    }
+
+    /// Go back to an earlier rewind point, dropping the newest points.
+    /// rewind(0) goes back to the newest point, and each extra frame goes back one more point.
+    /// Returns the number of points gone back, which is less than asked for if the buffer runs out.
+    /// The point that is rewound to is kept, so it can be rewound to again.
+    #[cfg(feature = "std")]
+    pub fn rewind(&mut self, frames: usize) -> Result<usize, String> {
+        let (dropped, state) = self.rewind.rewind(frames).ok_or_else(|| "No rewind points.".to_string())?;
+        self.load_machine_state(&mut StateReader::from_data(state))?;
+
+        self.events.send(Event::StateLoaded);
+        Ok(dropped)
+    }
+
+    /// Set the most rewind points that are kept. The default is 600: 10 seconds if a point is saved every frame.
+    #[cfg(feature = "std")]
+    pub fn set_rewind_capacity(&mut self, points: usize) {
+        self.rewind.set_capacity(points);
+    }
+
+    /// Number of points that can be rewound to.
+    #[cfg(feature = "std")]
+    pub fn rewind_len(&self) -> usize {
+        self.rewind.len()
+    }
+}
+
+// Internal
+impl SNES {
+    // Builder for the older constructors, which take the save and DSP ROM paths directly.
+    fn builder_for<'a>(builder: SNESBuilder<'a>, save_path: &'a str, dsp_rom_path: Option<&'a str>) -> SNESBuilder<'a> {
+        let builder = builder.save_path(save_path);
+        match dsp_rom_path {
+            Some(path) => builder.dsp_rom(path),
+            None => builder,
+        }
+    }
+
+    pub(crate) fn with_cart(cart: Box<Cart>, events: EventBus, region: Region) -> Self {
+        let info = cart.info();
+        let mut load_events = Vec::new();
+        if let Some(error) = info.patch_error {
+            load_events.push(Event::BadPatch(error));
+        }
+        if info.checksum != info.header_checksum {
+            load_events.push(Event::BadChecksum {
+                header: info.header_checksum,
+                actual: info.checksum,
+            });
+        }
+
+        let timing = MachineTiming::new(region);
+        let bus = AddrBusA::new(cart, events.clone(), &timing);
+        let mut cpu = CPU::new(bus, &timing);
+        cpu.set_event_bus(events.clone());
+
+        SNES {
+            cpu: cpu,
+
+            frames: [
+                Arc::new(Mutex::new(Box::new([0; FRAME_BUFFER_SIZE]))),
+                Arc::new(Mutex::new(Box::new([0; FRAME_BUFFER_SIZE])))
+            ],
+            front: 0,
+            blend: false,
+            widescreen: 0,
+            overscan:   false,
+            frame_timestamp:    0,
+            #[cfg(feature = "std")]
+            frame_skip: FrameSkip::new(),
+            #[cfg(feature = "std")]
+            rewind:     Rewind::new(),
+
+            paused: Arc::new(AtomicBool::new(false)),
+            region: region,
+
+            load_events:    load_events,
+            events:     events,
+        }
+    }
+
+    // Components are loaded one at a time, so if a bad state fails part way through,
+    // the SNES is put back to how it was before.
+    #[cfg(feature = "std")]
+    fn load_machine_state(&mut self, reader: &mut StateReader) -> Result<(), String> {
+        let mut backup = StateWriter::new();
+        self.cpu.save_state(&mut backup);
+        if let Err(e) = self.cpu.load_state(reader) {
+            // The backup was just saved, so it always loads.
+            let _ = self.cpu.load_state(&mut StateReader::from_data(backup.into_data()));
+            return Err(e);
+        }
+
+        self.frame_timestamp = self.cpu.master_cycles();
+        Ok(())
+    }
+
+    fn run_frame(&mut self) {
+        // Nobody can subscribe until the SNES is created, so problems from loading are sent now.
+        for event in core::mem::take(&mut self.load_events) {
+            self.events.send(event);
+        }
+
+        let skip = self.start_frame_skip();
+
+        // Draw into the back frame, so the front frame can be read out while it is drawn.
+        let back = self.front ^ 1;
+        self.cpu.start_frame(self.frames[back].clone());
+
+        // When NMI is triggered, disable rendering of new frames.
+        while !self.cpu.step() {}
+        //self.cpu.enable_rendering(false);
+        // Fence: make sure the render thread has drawn the whole frame before it is read out.
+        self.cpu.end_frame();
+        self.frame_timestamp = self.cpu.master_cycles();
+
+        // Swap at NMI. Nothing is drawn in skipped frames, so the last frame stays in front.
+        if !skip {
+            self.front = back;
+        }
+
+        #[cfg(feature = "std")]
+        self.frame_skip.end_frame();
+
+        self.events.send(Event::FrameCompleted);
+    }
+
+    // Returns true if this frame won't be drawn.
+    #[cfg(feature = "std")]
+    fn start_frame_skip(&mut self) -> bool {
+        let skip = self.frame_skip.start_frame();
+        self.cpu.set_skip_render(skip);
+        skip
+    }
+
+    #[cfg(not(feature = "std"))]
+    fn start_frame_skip(&mut self) -> bool {
+        false
+    }
+
+    // Make new frames, after the frame size changes.
+    fn resize_frames(&mut self) {
+        let size = (constants::screen::H_RES + (self.widescreen * 2)) * 2 * self.frame_height() * 4;
+        for frame in &self.frames {
+            *frame.lock() = vec![0; size].into_boxed_slice();
+        }
+    }
+
+    fn copy_frame(&self, frame: &mut [u8]) {
+        let frame_in = self.frames[self.front].lock();
+        if self.blend {
+            video::blend_hires(&frame_in, frame);
+        } else {
+            frame.copy_from_slice(&(*frame_in));
+        }
+    }
 }
 
 /// Created by a SNES.
 /// Call to receive the 
+#[cfg(feature = "std")]
 pub struct SNESAudioHandler {
-    resampler:    Resampler,
+    resampler:  Resampler,
+    paused:     Arc<AtomicBool>,
 }
 
+#[cfg(feature = "std")]
 impl SNESAudioHandler {
     /// Fill the provided buffer with samples.
     /// The format is PCM interleaved stereo.
+    /// If the SNES is paused, the buffer is filled with silence.
     pub fn get_audio_packet(&mut self, buffer: &mut [f32]) {
+        if self.paused.load(Ordering::Acquire) {
+            for o in buffer.iter_mut() {
+                *o = 0.0;
+            }
+            return;
+        }
+
         for (o_frame, i_frame) in buffer.chunks_exact_mut(2).zip(&mut self.resampler) {
             for (o, i) in o_frame.iter_mut().zip(i_frame.iter()) {
                 *o = *i;
             }
         }
     }
+
+    /// Change the output sample rate, for example if the host switches audio devices.
+    /// Any audio waiting to be output is dropped, to avoid building up latency.
+    pub fn set_sample_rate(&mut self, sample_rate: f64) {
+        self.resampler.set_sample_rate(sample_rate);
+    }
+
+    /// Get the number of samples since the last call that weren't ready in time.
+    /// Silence is output in their place, rather than waiting for the SNES.
+    pub fn take_underruns(&mut self) -> usize {
+        self.resampler.take_underruns()
+    }
+
+    /// Emulated time when the last sample output was generated, in master cycles since power on.
+    /// This doesn't advance when silence is output in place of late samples.
+    pub fn timestamp(&self) -> u64 {
+        self.resampler.timestamp()
+    }
+}
+
+/// Created by a SNES, for running without an audio device.
+/// Outputs audio at NATIVE_SAMPLE_RATE, without resampling.
+pub struct SNESHeadlessAudio {
+    source:             FixedRate,
+    samples_per_frame:  usize,
+}
+
+impl SNESHeadlessAudio {
+    /// Get the audio for a single frame. Call this after each call to SNES::frame.
+    /// The format is PCM interleaved stereo, with the number of samples per frame specified on creation.
+    /// If the SNES hasn't generated enough samples, the rest is filled with silence.
+    pub fn get_frame_audio(&mut self) -> Vec<f32> {
+        let mut samples = vec![[0.0; 2]; self.samples_per_frame];
+        self.source.fill(&mut samples);
+        samples.iter().flat_map(|s| s.iter().cloned()).collect()
+    }
+
+    /// Emulated time when the last sample output was generated, in master cycles since power on.
+    pub fn timestamp(&self) -> u64 {
+        self.source.timestamp()
+    }
 }
 
 // Debug
@@ -139,13 +887,30 @@ impl SNES {
         self.cpu.get_mem_at(addr)
     }
 
+    /// Write to a memory address, as the CPU would. This includes I/O registers.
+    pub fn set_mem_at(&mut self, addr: u32, data: u8) {
+        self.cpu.set_mem_at(addr, data);
+    }
+
     // Get the instruction at the current PC, with the next 3 bytes for context.
     pub fn get_instr(&mut self) -> [u8; 4] {
         self.cpu.get_instr()
     }
 
+    // When stepping, the front frame is drawn into so show_frame can show the frame so far.
     pub fn start_frame(&mut self) {
-        self.cpu.start_frame(self.frame.clone());
+        self.cpu.start_frame(self.frames[self.front].clone());
+    }
+
+    /// Get the cycles taken by the last CPU instruction, and the total for each opcode since power on
+    /// or the last call to reset_cycle_profile.
+    pub fn get_cycle_profile(&self) -> crate::debug::CycleProfile {
+        self.cpu.get_cycle_profile()
+    }
+
+    /// Clear the per-opcode totals, to profile a specific part of a game.
+    pub fn reset_cycle_profile(&mut self) {
+        self.cpu.reset_cycle_profile();
     }
 
     // Step the device by one CPU cycle.
@@ -153,8 +918,114 @@ impl SNES {
         self.cpu.step()
     }
 
+    /// Number of instructions the CPU has executed since power on.
+    /// Steps that enter an interrupt or wait while halted don't count.
+    pub fn instr_count(&self) -> u64 {
+        self.cpu.get_instr_count()
+    }
+
     pub fn show_frame(&mut self, frame: &mut [u8]) {
-        let frame_in = self.frame.lock().unwrap();
-        frame.copy_from_slice(&(*frame_in));
+        self.copy_frame(frame);
+    }
+
+    // Read a range of audio RAM directly, without going through the SPC.
+    // The range is cut off at the end of ARAM.
+    pub fn read_aram(&self, range: core::ops::Range<usize>) -> &[u8] {
+        let aram = self.cpu.get_aram();
+        let end = core::cmp::min(range.end, aram.len());
+        let start = core::cmp::min(range.start, end);
+        &aram[start..end]
     }
-}
\ No newline at end of file
+
+    // Format a range of audio RAM as a hexdump.
+    pub fn dump_aram(&self, range: core::ops::Range<usize>) -> String {
+        crate::debug::hexdump(range.start as u16, self.read_aram(range))
+    }
+
+    // Write data into audio RAM directly, starting at the address provided.
+    // The address wraps around at the end of ARAM.
+    pub fn write_aram(&mut self, start: u16, data: &[u8]) {
+        let aram = self.cpu.get_aram_mut();
+        let len = aram.len();
+        for (i, d) in data.iter().enumerate() {
+            aram[(start as usize + i) % len] = *d;
+        }
+    }
+
+    /// Get the number of times the DSP has clamped each stage of mixing since power on
+    /// or the last call to reset_dsp_clamp_stats.
+    pub fn get_dsp_clamp_stats(&self) -> crate::debug::DSPClampStats {
+        self.cpu.get_dsp_clamp_stats()
+    }
+
+    /// Clear the DSP clamp counts, to check a specific part of a game.
+    pub fn reset_dsp_clamp_stats(&mut self) {
+        self.cpu.reset_dsp_clamp_stats();
+    }
+
+    // Get the current position of the PPU, as (dot, scanline).
+    pub fn get_hv_counter(&self) -> (u16, u16) {
+        self.cpu.get_hv()
+    }
+
+    // Move the PPU to the start of the dot and scanline provided.
+    // Useful for setting up precise mid-frame conditions, such as IRQ timing or HDMA.
+    // Moving to a different scanline does not render or skip any lines.
+    pub fn set_hv_counter(&mut self, dot: u16, scanline: u16) {
+        self.cpu.set_hv(dot, scanline);
+    }
+
+    // Decode an entire background map using the current mode, tiles and palettes.
+    // bg is 0-3 for BG1-4. Mode 7 BG1 is the full 1024x1024 map.
+    // Returns None if the background isn't used in the current mode.
+    pub fn export_bg_map(&self, bg: usize) -> Option<crate::debug::IndexedImage> {
+        self.cpu.export_bg_map(bg)
+    }
+
+    // Decode both object name tables using one of the 8 object palettes.
+    pub fn export_obj_tables(&self, palette: u8) -> crate::debug::IndexedImage {
+        self.cpu.export_obj_tables(palette)
+    }
+
+    /// Force the priority bit of every tile in a background (0-3 for BG1-4) on or off,
+    /// to find which layer is causing an artifact, or to preview a change to the tile map.
+    /// Set to None to use the priority bits in the tile map again. Mode 7 isn't affected.
+    pub fn set_bg_priority_override(&mut self, bg: usize, priority: Option<bool>) {
+        self.cpu.set_bg_priority_override(bg, priority);
+    }
+
+    /// Composite a single pixel again with the current PPU state, and explain where its colour came from.
+    /// Returns None if the position is off screen.
+    pub fn explain_pixel(&self, x: usize, y: usize) -> Option<crate::debug::PixelExplanation> {
+        self.cpu.explain_pixel(x, y)
+    }
+
+    /// Get the SuperFX instruction cache contents and hit/miss stats.
+    /// Returns None if the cart doesn't contain a SuperFX.
+    pub fn get_fx_cache_state(&self) -> Option<crate::debug::FXCacheState> {
+        self.cpu.get_fx_cache_state()
+    }
+
+    /// Get the state of all 8 HDMA channels, for each line of the last complete frame.
+    /// Entry n is the state after the H-blank transfer at the end of scanline n.
+    pub fn get_hdma_states(&self) -> Vec<[crate::debug::HDMAChannelState; 8]> {
+        self.cpu.get_hdma_states()
+    }
+
+    /// Get the registers of all 8 DMA channels.
+    pub fn get_dma_channels(&self) -> [crate::debug::DMAChannelRegs; 8] {
+        self.cpu.get_dma_channels()
+    }
+
+    /// Run a general purpose DMA on each channel set in the mask, as if it was written to $420B.
+    /// The rest of the system is clocked while the transfer runs, and it returns when complete.
+    pub fn trigger_dma(&mut self, channels: u8) {
+        self.cpu.trigger_dma(channels);
+    }
+
+    /// Get the objects evaluated for each visible line of the last complete frame.
+    /// Entry n is for screen line n + 1. Objects past the per-line limit are listed as dropped.
+    pub fn get_sprite_lines(&self) -> Vec<crate::debug::SpriteLineState> {
+        self.cpu.get_sprite_lines()
+    }
+}
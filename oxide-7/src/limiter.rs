@@ -0,0 +1,93 @@
+// Frame pacing for frontends: keep the emulator running at the speed of the real console.
+use std::{
+    thread,
+    time::{
+        Duration,
+        Instant
+    }
+};
+
+use crate::constants::{
+    machine::{
+        MachineTiming,
+        Region
+    },
+    timing
+};
+
+// Sleeping can overshoot, so the last part of each wait is spent spinning.
+const SPIN_TIME: Duration = Duration::from_millis(2);
+
+/// Paces a frontend's main loop to the frame rate of the real console.
+///
+/// Call `wait` once per frame. Most of the wait is spent asleep, and the end of it is spent spinning,
+/// so frames start at exact times without using a whole CPU core.
+pub struct FrameLimiter {
+    frame_time: Duration,
+    next_frame: Instant,    // When the next frame should start.
+}
+
+impl FrameLimiter {
+    /// Run at the NTSC frame rate: around 60.1 frames per second.
+    pub fn ntsc() -> Self {
+        Self::new(frame_time(&MachineTiming::NTSC, timing::REAL_HZ))
+    }
+
+    /// Run at the PAL frame rate: around 50.0 frames per second.
+    pub fn pal() -> Self {
+        Self::new(frame_time(&MachineTiming::PAL, timing::REAL_HZ_PAL))
+    }
+
+    /// Run at the frame rate of a console region, for example `SNES::region`.
+    pub fn for_region(region: Region) -> Self {
+        match region {
+            Region::NTSC => Self::ntsc(),
+            Region::PAL => Self::pal(),
+        }
+    }
+
+    /// Run with a custom time for each frame, for example to run at double speed.
+    pub fn new(frame_time: Duration) -> Self {
+        FrameLimiter {
+            frame_time: frame_time,
+            next_frame: Instant::now() + frame_time,
+        }
+    }
+
+    /// The time each frame takes.
+    pub fn frame_time(&self) -> Duration {
+        self.frame_time
+    }
+
+    /// Wait until the next frame should start.
+    /// A late frame is made up by not waiting for the next one. If the loop falls more than
+    /// a frame behind (for example when the window was dragged), the timing restarts instead.
+    pub fn wait(&mut self) {
+        let now = Instant::now();
+        if now > self.next_frame + self.frame_time {
+            self.next_frame = now + self.frame_time;
+            return;
+        }
+
+        if let Some(remaining) = self.next_frame.checked_duration_since(now) {
+            if remaining > SPIN_TIME {
+                thread::sleep(remaining - SPIN_TIME);
+            }
+            while Instant::now() < self.next_frame {
+                core::hint::spin_loop();
+            }
+        }
+        self.next_frame += self.frame_time;
+    }
+
+    /// Restart the timing from now. Call this after the loop was paused.
+    pub fn reset(&mut self) {
+        self.next_frame = Instant::now() + self.frame_time;
+    }
+}
+
+// Time taken by a frame on the real console, with a master clock of hz.
+fn frame_time(machine: &MachineTiming, hz: f64) -> Duration {
+    let cycles = timing::SCANLINE * machine.num_scanlines;
+    Duration::from_secs_f64((cycles as f64) / hz)
+}
@@ -1,89 +1,398 @@
-// Address Buses A and B, and DMA operation.
+// Address Bus A, and the devices attached to it.
+use alloc::{
+    boxed::Box,
+    string::String,
+    vec::Vec
+};
+
 use crate::{
     common::Interrupt,
-    constants::timing::*,
-    video::{PPU, PPUSignal, RenderTarget},
-    audio::APU,
-    joypad::{JoypadMem, Button}
+    constants::{
+        machine::{
+            MachineTiming,
+            Region
+        },
+        timing::*
+    },
+    video::{PPUSignal, RenderTarget, VideoMemMonitor},
+    events::{Event, EventBus},
+    joypad::{JoypadMem, Button, InputProvider, Peripheral, Port, PortMonitor},
+    rng::Rng,
+    savestate::{Stateful, StateWriter, StateReader}
 };
+#[cfg(feature = "std")]
+use crate::savestate::StateComponent;
 
 use super::{
+    ExpansionPort,
+    IOPort,
     MemBus,
-    RAM,
+    busb::AddrBusB,
     dma::{
-        DMAChannel,
-        DMAControl
+        DMABus,
+        DMAController,
+        DMASignal
     },
-    rom::*
+    math::MathUnit,
+    rom::*,
+    wram::WorkRAM
 };
 
 // A bus to attach to the CPU (Address Bus A).
+// DMA sits between the CPU and the rest of the devices,
+// as it has its own view of the bus and needs to respond to H-blank.
 pub struct AddrBusA {
-    // Devices
-    bus_b:      AddrBusB,
-    joypads:    JoypadMem,
-
-    // Memory
-    cart:       Box<Cart>,
-    wram:       RAM,
-
-    // Stored values
-    wram_addr:      u32,
-    mult_operand:   u8,
-    div_operand:    u16,
-    div_result:     u16,    // TODO: do these need to be timed properly?
-    mult_result:    u16,
-
-    // DMA
-    hdma_enable:    u8,
-    hdma_active:    u8,
-    dma_channels:   Vec<DMAChannel>,
+    dma:        DMAController,
+    devices:    Devices,
 }
 
 impl AddrBusA {
-    pub fn new(cart_path: &str, save_path: &str, dsp_rom_path: Option<&str>) -> Self {
-        // Open ROM file.
-        let cart = create_cart(cart_path, save_path, dsp_rom_path);
-
+    pub fn new(cart: Box<Cart>, events: EventBus, timing: &MachineTiming) -> Self {
         Self {
-            bus_b:      AddrBusB::new(),
-            joypads:    JoypadMem::new(),
-            
-            cart:       cart,
-            wram:       RAM::new(0x20000),
-
-            hdma_enable:    0,
-            hdma_active:    0,
-            dma_channels:   vec![DMAChannel::new(); 8],
-
-            wram_addr:      0,
-            mult_operand:   0xFF,
-            div_operand:    0xFFFF,
-            div_result:     0,
-            mult_result:    0,
+            dma:        DMAController::new(),
+            devices:    Devices::new(cart, events, timing),
         }
     }
 
     // Set buttons on the specified joypad.
     pub fn set_buttons(&mut self, button: Button, val: bool, joypad: usize) {
-        self.joypads.set_buttons(button, val, joypad);
+        self.devices.joypads.set_buttons(button, val, joypad, self.devices.master_cycles);
+    }
+
+    pub fn queue_buttons(&mut self, button: Button, val: bool, joypad: usize, cycle: u64) {
+        self.devices.joypads.queue_buttons(button, val, joypad, cycle);
+    }
+
+    pub fn set_input_queue(&mut self, enable: bool) {
+        self.devices.joypads.set_input_queue(enable);
+    }
+
+    pub fn held_buttons(&self, joypad: usize) -> u16 {
+        self.devices.joypads.held_buttons(joypad)
+    }
+
+    pub fn set_input_provider(&mut self, provider: Option<Box<dyn InputProvider>>) -> Option<Box<dyn InputProvider>> {
+        self.devices.joypads.set_input_provider(provider)
+    }
+
+    pub fn set_peripheral(&mut self, port: Port, device: Option<Box<dyn Peripheral>>) -> Option<Box<dyn Peripheral>> {
+        self.devices.joypads.set_peripheral(port, device)
+    }
+
+    pub fn set_port_monitor(&mut self, monitor: Option<Box<dyn PortMonitor>>) -> Option<Box<dyn PortMonitor>> {
+        self.devices.joypads.set_port_monitor(monitor)
+    }
+
+    pub fn set_video_mem_monitor(&mut self, monitor: Option<Box<dyn VideoMemMonitor>>) -> Option<Box<dyn VideoMemMonitor>> {
+        self.devices.bus_b.ppu.set_mem_monitor(monitor)
+    }
+
+    pub fn get_wram(&self) -> &[u8] {
+        self.devices.wram.as_slice()
+    }
+
+    // Reseed the RNG, and use it to fill WRAM.
+    pub fn randomize_wram(&mut self, seed: u64) {
+        self.devices.rng = Rng::new(seed);
+        self.devices.rng.fill(self.devices.wram.as_mut_slice());
+    }
+
+    pub fn copy_vram(&self) -> Vec<u8> {
+        self.devices.bus_b.ppu.copy_vram()
+    }
+
+    pub fn copy_cgram(&self) -> Vec<u8> {
+        self.devices.bus_b.ppu.copy_cgram()
+    }
+
+    pub fn copy_oam(&self) -> Vec<u8> {
+        self.devices.bus_b.ppu.copy_oam()
+    }
+
+    pub fn set_expansion_port(&mut self, device: Option<Box<dyn ExpansionPort>>) -> Option<Box<dyn ExpansionPort>> {
+        core::mem::replace(&mut self.devices.bus_b.exp_port, device)
+    }
+
+    pub fn set_io_port(&mut self, device: Option<Box<dyn IOPort>>) -> Option<Box<dyn IOPort>> {
+        core::mem::replace(&mut self.devices.bus_b.io_port, device)
+    }
+
+    pub fn set_sprite_limit(&mut self, enable: bool) {
+        self.devices.bus_b.ppu.set_sprite_limit(enable);
+    }
+
+    // Uses the RNG, so this should be called after randomize_wram.
+    pub fn set_power_on_garbage(&mut self, enable: bool) {
+        self.devices.bus_b.ppu.set_power_on_garbage(enable, &mut self.devices.rng);
+    }
+
+    pub fn set_region_flag(&mut self, region: Region) {
+        self.devices.bus_b.ppu.set_region_flag(region);
+    }
+
+    pub fn set_fast_apu_boot(&mut self, enable: bool) {
+        self.devices.bus_b.apu.set_fast_boot(enable);
+    }
+
+    pub fn set_dc_block(&mut self, enable: bool) {
+        self.devices.bus_b.apu.set_dc_block(enable);
+    }
+
+    pub fn master_cycles(&self) -> u64 {
+        self.devices.master_cycles
+    }
+
+    pub fn set_widescreen(&mut self, extra: usize) {
+        self.devices.bus_b.ppu.set_widescreen(extra);
+    }
+
+    pub fn set_overscan(&mut self, enable: bool) {
+        self.devices.bus_b.ppu.set_overscan_output(enable);
+    }
+
+    #[cfg(feature = "std")]
+    pub fn set_skip_render(&mut self, skip: bool) {
+        self.devices.bus_b.ppu.set_skip_render(skip);
     }
 
     pub fn start_frame(&mut self, frame: RenderTarget) {
-        self.bus_b.ppu.start_frame(frame);
-        self.cart.flush();
+        self.devices.bus_b.ppu.start_frame(frame);
+        match self.devices.cart.flush() {
+            Ok(true) => self.devices.events.send(Event::SramWritten),
+            Ok(false) => {},
+            Err(e) => self.devices.events.send(Event::SramWriteFailed(e)),
+        }
+    }
+
+    // Called when the frame is done: the frame buffer can't be read until the renderer is finished with it.
+    pub fn end_frame(&mut self) {
+        if let Some((threaded, single)) = self.devices.bus_b.ppu.end_frame() {
+            self.devices.events.send(Event::RenderMismatch {threaded, single});
+        }
     }
 
-    pub fn get_audio_rx(&mut self) -> Option<crossbeam_channel::Receiver<crate::audio::SamplePacket>> {
-        self.bus_b.apu.get_rx()
+    #[cfg(feature = "std")]
+    pub fn set_render_verification(&mut self, enable: bool) {
+        self.devices.bus_b.ppu.set_render_verification(enable);
+    }
+
+    pub fn new_audio_rx(&mut self) -> crate::sync::Receiver<crate::audio::SamplePacket> {
+        self.devices.bus_b.apu.new_rx()
     }
 
     pub fn rom_name(&self) -> String {
-        self.cart.name()
+        self.devices.cart.name()
+    }
+
+    pub fn cart_info(&self) -> CartInfo {
+        self.devices.cart.info()
+    }
+
+    pub fn voice_states(&self) -> [crate::audio::VoiceState; 8] {
+        self.devices.bus_b.apu.voice_states()
     }
 }
 
 impl MemBus for AddrBusA {
+    fn read(&mut self, addr: u32) -> (u8, usize) {
+        let (data, cycles) = match dma_reg(addr) {
+            Some(reg @ 0x4300..=0x437F) => (self.dma.read(reg), FAST_MEM_ACCESS),
+            _ => self.devices.read(addr)
+        };
+        self.devices.open_bus = data;
+        (data, cycles)
+    }
+
+    fn write(&mut self, addr: u32, data: u8) -> usize {
+        match dma_reg(addr) {
+            Some(reg) => {
+                if let 0x4300..=0x437F = reg {
+                    self.devices.cart.write_dma_reg(reg, data);
+                }
+                self.dma.write(&mut self.devices, reg, data);
+                FAST_MEM_ACCESS
+            },
+            None => self.devices.write(addr, data)
+        }
+    }
+
+    fn clock(&mut self, cycles: usize) -> Interrupt {
+        self.dma.clock(&mut self.devices, cycles)
+    }
+
+    #[cfg(feature = "debug")]
+    fn set_pc(&mut self, pc: u32) {
+        self.devices.cart.set_pc(pc);
+    }
+}
+
+// The order that components are written in is the savestate format: keep it the same when saving and loading.
+impl Stateful for AddrBusA {
+    fn save_state(&self, w: &mut StateWriter) {
+        self.devices.bus_b.save_state(w);
+        self.devices.joypads.save_state(w);
+        self.devices.cart.save_state(w);
+        self.devices.wram.save_state(w);
+        self.devices.math.save_state(w);
+        self.dma.save_state(w);
+        w.write(&self.devices.rng);
+        w.write(&self.devices.open_bus);
+        w.write(&self.devices.master_cycles);
+        w.write(&self.devices.cart_lead);
+        w.write(&self.devices.cart_int);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), String> {
+        self.devices.bus_b.load_state(r)?;
+        self.devices.joypads.load_state(r)?;
+        self.devices.cart.load_state(r)?;
+        self.devices.wram.load_state(r)?;
+        self.devices.math.load_state(r)?;
+        self.dma.load_state(r)?;
+        r.read_into(&mut self.devices.rng)?;
+        r.read_into(&mut self.devices.open_bus)?;
+        r.read_into(&mut self.devices.master_cycles)?;
+        r.read_into(&mut self.devices.cart_lead)?;
+        r.read_into(&mut self.devices.cart_int)
+    }
+}
+
+#[cfg(feature = "std")]
+impl AddrBusA {
+    // Save the state of one component, in the same format as in a full savestate.
+    pub fn save_component_state(&self, component: StateComponent, w: &mut StateWriter) {
+        match component {
+            StateComponent::CPU => {},  // The registers are saved by the CPU.
+            StateComponent::PPU => self.devices.bus_b.ppu.save_state(w),
+            StateComponent::APU => self.devices.bus_b.apu.save_state(w),
+            StateComponent::Cart => self.devices.cart.save_state(w),
+            StateComponent::WRAM => self.devices.wram.save_state(w),
+            StateComponent::DMA => self.dma.save_state(w),
+            StateComponent::Joypads => self.devices.joypads.save_state(w),
+            StateComponent::Math => self.devices.math.save_state(w),
+        }
+    }
+}
+
+// Debug
+#[cfg(feature = "debug")]
+impl AddrBusA {
+    pub fn get_aram(&self) -> &[u8] {
+        self.devices.bus_b.apu.get_ram()
+    }
+
+    pub fn get_aram_mut(&mut self) -> &mut [u8] {
+        self.devices.bus_b.apu.get_ram_mut()
+    }
+
+    pub fn get_dsp_clamp_stats(&self) -> crate::debug::DSPClampStats {
+        self.devices.bus_b.apu.get_dsp_clamp_stats()
+    }
+
+    pub fn reset_dsp_clamp_stats(&mut self) {
+        self.devices.bus_b.apu.reset_dsp_clamp_stats();
+    }
+
+    pub fn export_bg_map(&self, bg: usize) -> Option<crate::debug::IndexedImage> {
+        self.devices.bus_b.ppu.export_bg_map(bg)
+    }
+
+    pub fn export_obj_tables(&self, palette: u8) -> crate::debug::IndexedImage {
+        self.devices.bus_b.ppu.export_obj_tables(palette)
+    }
+
+    pub fn set_bg_priority_override(&mut self, bg: usize, priority: Option<bool>) {
+        self.devices.bus_b.ppu.set_bg_priority_override(bg, priority);
+    }
+
+    pub fn explain_pixel(&self, x: usize, y: usize) -> Option<crate::debug::PixelExplanation> {
+        self.devices.bus_b.ppu.explain_pixel(x, y)
+    }
+
+    pub fn get_fx_cache_state(&self) -> Option<crate::debug::FXCacheState> {
+        self.devices.cart.fx_cache_state()
+    }
+
+    pub fn get_hdma_states(&self) -> Vec<[crate::debug::HDMAChannelState; 8]> {
+        self.dma.hdma_states()
+    }
+
+    pub fn get_dma_channels(&self) -> [crate::debug::DMAChannelRegs; 8] {
+        self.dma.channel_regs()
+    }
+
+    pub fn trigger_dma(&mut self, channels: u8) {
+        self.dma.write(&mut self.devices, 0x420B, channels);
+    }
+
+    pub fn get_sprite_lines(&self) -> Vec<crate::debug::SpriteLineState> {
+        self.devices.bus_b.ppu.sprite_line_states()
+    }
+
+    pub fn get_hv(&self) -> (u16, u16) {
+        self.devices.bus_b.ppu.get_hv()
+    }
+
+    pub fn set_hv(&mut self, dot: u16, scanline: u16) {
+        self.devices.bus_b.ppu.set_hv(dot, scanline);
+    }
+}
+
+// Find if the address is a DMA register: $420B, $420C or $4300-$437F in the system banks.
+fn dma_reg(addr: u32) -> Option<u16> {
+    match hi24!(addr) {
+        0x00..=0x3F | 0x80..=0xBF => match lo24!(addr) {
+            reg @ 0x420B..=0x420C => Some(reg),
+            reg @ 0x4300..=0x437F => Some(reg),
+            _ => None
+        },
+        _ => None
+    }
+}
+
+// Everything attached to address bus A, apart from the DMA controller.
+struct Devices {
+    bus_b:      AddrBusB,
+    joypads:    JoypadMem,
+
+    // Memory
+    cart:       Box<Cart>,
+    wram:       WorkRAM,
+
+    // Cart chip sync
+    cart_lead:  usize,      // Master cycles the cart has been clocked ahead of the rest of the system.
+    cart_int:   Interrupt,  // Interrupts raised by the cart while it was clocked ahead.
+
+    math:       MathUnit,
+
+    events:         EventBus,
+    master_cycles:  u64,    // Total cycles since power on. Used for timestamps.
+    rng:            Rng,
+    open_bus:       u8,     // The last value read by the CPU. Returned in bits that aren't driven by the device read.
+}
+
+impl Devices {
+    fn new(mut cart: Box<Cart>, events: EventBus, timing: &MachineTiming) -> Self {
+        cart.set_timing(timing);
+        Self {
+            bus_b:      AddrBusB::new(events.clone(), timing),
+            joypads:    JoypadMem::new(),
+
+            cart:       cart,
+            wram:       WorkRAM::new(),
+
+            cart_lead:  0,
+            cart_int:   Interrupt::default(),
+
+            math:       MathUnit::new(),
+
+            events:         events,
+            master_cycles:  0,
+            rng:            Rng::new(0),
+            open_bus:       0,
+        }
+    }
+
     fn read(&mut self, addr: u32) -> (u8, usize) {
         let bank = hi24!(addr);
         let offset = lo24!(addr);
@@ -92,23 +401,17 @@ impl MemBus for AddrBusA {
             0x00..=0x3F | 0x80..=0xBF => match offset {
                 0x0000..=0x1FFF => (self.wram.read(offset as u32), SLOW_MEM_ACCESS),
 
-                0x2100..=0x2143 => (self.bus_b.read(lo!(offset)), FAST_MEM_ACCESS),
-                0x2180          => self.read_wram(),
-                0x2100..=0x21FF => (0, FAST_MEM_ACCESS),
-                0x2200..=0x23FF => (self.cart.read_exp(offset), FAST_MEM_ACCESS),
-                0x3000..=0x3FFF => (self.cart.read_exp(offset), FAST_MEM_ACCESS),   // Extensions
+                0x2100..=0x217F => (self.bus_b.read(lo!(offset), self.open_bus), FAST_MEM_ACCESS),
+                0x2180          => (self.wram.read_port(), FAST_MEM_ACCESS),
+                0x2184..=0x21FF => (self.bus_b.read(lo!(offset), self.open_bus), FAST_MEM_ACCESS),  // Expansion port
+                0x2100..=0x21FF => (self.open_bus, FAST_MEM_ACCESS),    // WRAM address registers are write-only.
+                0x2200..=0x23FF => (self.read_cart_reg(offset), FAST_MEM_ACCESS),
+                0x3000..=0x3FFF => (self.read_cart_reg(offset), FAST_MEM_ACCESS),   // Extensions
 
+                0x4016 | 0x4017 => (self.joypads.read_port((offset - 0x4016) as usize, self.open_bus), XSLOW_MEM_ACCESS),
                 0x4000..=0x41FF => (self.joypads.read(offset), XSLOW_MEM_ACCESS),
                 0x4210..=0x421F => (self.read_reg(offset), FAST_MEM_ACCESS),
-
-                0x4300..=0x430A => (self.dma_channels[0].read((addr as u8) & 0xF), FAST_MEM_ACCESS),
-                0x4310..=0x431A => (self.dma_channels[1].read((addr as u8) & 0xF), FAST_MEM_ACCESS),
-                0x4320..=0x432A => (self.dma_channels[2].read((addr as u8) & 0xF), FAST_MEM_ACCESS),
-                0x4330..=0x433A => (self.dma_channels[3].read((addr as u8) & 0xF), FAST_MEM_ACCESS),
-                0x4340..=0x434A => (self.dma_channels[4].read((addr as u8) & 0xF), FAST_MEM_ACCESS),
-                0x4350..=0x435A => (self.dma_channels[5].read((addr as u8) & 0xF), FAST_MEM_ACCESS),
-                0x4360..=0x436A => (self.dma_channels[6].read((addr as u8) & 0xF), FAST_MEM_ACCESS),
-                0x4370..=0x437A => (self.dma_channels[7].read((addr as u8) & 0xF), FAST_MEM_ACCESS),
+                0x4800..=0x48FF => (self.cart.read_io(offset).unwrap_or(0), FAST_MEM_ACCESS),
 
                 0x6000..=0xFFFF => self.cart.read(bank, offset),
                 _               => (0, FAST_MEM_ACCESS),                            // Unmapped
@@ -127,27 +430,19 @@ impl MemBus for AddrBusA {
                 0x0000..=0x1FFF => {self.wram.write(offset as u32, data); SLOW_MEM_ACCESS},
 
                 0x2100..=0x2143 => {self.bus_b.write(lo!(offset), data); FAST_MEM_ACCESS},
-                0x2180          => self.write_wram(data),
-                0x2181          => {self.wram_addr = set_lo24!(self.wram_addr, data); FAST_MEM_ACCESS},
-                0x2182          => {self.wram_addr = set_mid24!(self.wram_addr, data); FAST_MEM_ACCESS},
-                0x2183          => {self.wram_addr = set_hi24!(self.wram_addr, data & 1); FAST_MEM_ACCESS},
+                0x2180          => {self.wram.write_port(data); FAST_MEM_ACCESS},
+                0x2181..=0x2183 => {self.wram.write_port_addr(offset, data); FAST_MEM_ACCESS},
+                0x2184..=0x21FF => {self.bus_b.write(lo!(offset), data); FAST_MEM_ACCESS},   // Expansion port
                 0x2100..=0x21FF => FAST_MEM_ACCESS,
-                0x2200..=0x23FF => {self.cart.write_exp(offset, data); FAST_MEM_ACCESS}
-                0x3000..=0x3FFF => {self.cart.write_exp(offset, data); FAST_MEM_ACCESS}, // Extensions
+                0x2200..=0x23FF => {self.write_cart_reg(offset, data); FAST_MEM_ACCESS}
+                0x3000..=0x3FFF => {self.write_cart_reg(offset, data); FAST_MEM_ACCESS}, // Extensions
 
-                0x4016          => {self.joypads.latch_all(data); XSLOW_MEM_ACCESS},
+                0x4016          => {self.joypads.latch_all(data, self.master_cycles); XSLOW_MEM_ACCESS},
                 0x4000..=0x41FF => XSLOW_MEM_ACCESS,
 
-                0x4200..=0x420d => {self.write_reg(offset, data); FAST_MEM_ACCESS},
-
-                0x4300..=0x430A => {self.dma_channels[0].write((addr as u8) & 0xF, data); FAST_MEM_ACCESS},
-                0x4310..=0x431A => {self.dma_channels[1].write((addr as u8) & 0xF, data); FAST_MEM_ACCESS},
-                0x4320..=0x432A => {self.dma_channels[2].write((addr as u8) & 0xF, data); FAST_MEM_ACCESS},
-                0x4330..=0x433A => {self.dma_channels[3].write((addr as u8) & 0xF, data); FAST_MEM_ACCESS},
-                0x4340..=0x434A => {self.dma_channels[4].write((addr as u8) & 0xF, data); FAST_MEM_ACCESS},
-                0x4350..=0x435A => {self.dma_channels[5].write((addr as u8) & 0xF, data); FAST_MEM_ACCESS},
-                0x4360..=0x436A => {self.dma_channels[6].write((addr as u8) & 0xF, data); FAST_MEM_ACCESS},
-                0x4370..=0x437A => {self.dma_channels[7].write((addr as u8) & 0xF, data); FAST_MEM_ACCESS},
+                0x4200..=0x420A => {self.write_reg(offset, data); FAST_MEM_ACCESS},
+                0x420D          => {self.write_reg(offset, data); FAST_MEM_ACCESS},
+                0x4800..=0x48FF => {self.cart.write_io(offset, data); FAST_MEM_ACCESS},
 
                 0x6000..=0xFFFF => self.cart.write(bank, offset, data),
                 _               => FAST_MEM_ACCESS,  // Unmapped
@@ -157,62 +452,56 @@ impl MemBus for AddrBusA {
         }
     }
 
-    // Clock the PPU and APU, and handle any signals coming from the PPU.
-    fn clock(&mut self, cycles: usize) -> Interrupt {
+    // Clock the PPU, APU and cart, and handle any signals coming from the PPU.
+    fn clock(&mut self, cycles: usize) -> (Interrupt, DMASignal) {
+        self.master_cycles += cycles as u64;
         self.bus_b.clock_apu(cycles);
+        let cart_i = self.clock_cart(cycles);
 
-        let cart_i = self.cart.clock(cycles);
-
-        let v_i = match self.bus_b.ppu.clock(cycles) {
+        let (v_i, signal) = match self.bus_b.ppu.clock(cycles) {
             PPUSignal::Int(i) => {
-                if i.intersects(Interrupt::NMI | Interrupt::VBLANK) {
-                    self.joypads.prepare_read();
+                if i.contains(Interrupt::VBLANK) {
+                    self.joypads.prepare_read(self.master_cycles);
                 }
-                i
+                (i, DMASignal::None)
             }
-            PPUSignal::HBlank => {
-                if self.hdma_active != 0 {
-                    self.hdma_transfer();
-                }
-                Interrupt::default()
-            },
-            PPUSignal::Delay => {
-                self.bus_b.clock_apu(PAUSE_LEN);
-                match self.bus_b.ppu.clock(PAUSE_LEN) {
-                    PPUSignal::Int(Interrupt::IRQ) => Interrupt::IRQ, // This is the only one that should happen here.
-                    PPUSignal::None => Interrupt::default(),
-                    _ => unreachable!(),
-                }
-            },
+            PPUSignal::HBlank => (Interrupt::default(), DMASignal::HBlank),
+            // Only the CPU is paused: everything else keeps running, and its signals are handled as normal.
+            PPUSignal::Delay => self.clock(PAUSE_LEN),
             PPUSignal::FrameStart => {
-                self.hdma_active = self.hdma_enable;
-                for chan in 0..8 {
-                    if test_bit!(self.hdma_active, chan, u8) {
-                        self.dma_channels[chan].start_hdma();
-                    }
-                }
-                Interrupt::default()
+                self.cart.start_frame();
+                (Interrupt::default(), DMASignal::FrameStart)
             },
-            PPUSignal::None => Interrupt::default()
+            PPUSignal::None => (Interrupt::default(), DMASignal::None)
         };
 
-        cart_i | v_i
+        (cart_i | v_i, signal)
     }
-}
 
-// Internal
-impl AddrBusA {
-    // WRAM access from special register.
-    fn read_wram(&mut self) -> (u8, usize) {
-        let data = self.wram.read(self.wram_addr);
-        self.wram_addr = self.wram_addr.wrapping_add(1) & 0x1FFFF;
-        (data, FAST_MEM_ACCESS)
+    // Clock the cart, but not for time it has already run ahead.
+    fn clock_cart(&mut self, cycles: usize) -> Interrupt {
+        let cart_cycles = cycles.saturating_sub(self.cart_lead);
+        self.cart_lead -= cycles - cart_cycles;
+        self.cart.clock(cart_cycles) | core::mem::take(&mut self.cart_int)
     }
 
-    fn write_wram(&mut self, data: u8) -> usize {
-        self.wram.write(self.wram_addr, data);
-        self.wram_addr = self.wram_addr.wrapping_add(1) & 0x1FFFF;
-        FAST_MEM_ACCESS
+    // Cart chips such as the SuperFX run alongside the CPU.
+    // Before the CPU accesses their registers, catch them up to the end of the access,
+    // so polling loops see the chip's state at the right time.
+    fn sync_cart(&mut self, cycles: usize) {
+        let cart_i = self.cart.clock(cycles);
+        self.cart_int.insert(cart_i);
+        self.cart_lead += cycles;
+    }
+
+    fn read_cart_reg(&mut self, addr: u16) -> u8 {
+        self.sync_cart(FAST_MEM_ACCESS);
+        self.cart.read_exp(addr)
+    }
+
+    fn write_cart_reg(&mut self, addr: u16, data: u8) {
+        self.sync_cart(FAST_MEM_ACCESS);
+        self.cart.write_exp(addr, data);
     }
 
     // Internal status registers.
@@ -221,13 +510,10 @@ impl AddrBusA {
             0x4210 => self.bus_b.ppu.get_nmi_flag(),
             0x4211 => self.bus_b.ppu.get_irq_flag(),
             0x4212 => self.bus_b.ppu.get_status() | self.joypads.is_ready(), // PPU status
-            0x4213 => 0, // IO port read
-            0x4214 => lo!(self.div_result),
-            0x4215 => hi!(self.div_result),
-            0x4216 => lo!(self.mult_result),
-            0x4217 => hi!(self.mult_result),
+            0x4213 => self.bus_b.read_rdio(),
+            0x4214..=0x4217 => self.math.read(addr),
             0x4218..=0x421F => self.joypads.read(addr),
-            _ => unreachable!(),
+            _ => self.open_bus,
         }
     }
 
@@ -237,165 +523,35 @@ impl AddrBusA {
                 self.bus_b.ppu.set_int_enable(data);
                 self.joypads.enable_counter(data);
             },
-            0x4201 => {}, // IO port write
-            0x4202 => self.mult_operand = data,
-            0x4203 => self.mult_result = (self.mult_operand as u16) * (data as u16),
-            0x4204 => self.div_operand = set_lo!(self.div_operand, data),
-            0x4205 => self.div_operand = set_hi!(self.div_operand, data),
-            0x4206 => if data == 0 {
-                self.div_result = 0xFFFF;
-                self.mult_result = 0xC;
-            } else {
-                let divisor = data as u16;
-                self.div_result = self.div_operand / divisor;
-                self.mult_result = self.div_operand % divisor;
+            0x4201 => {
+                self.bus_b.write_wrio(data);
+                self.joypads.write_io(data);
             },
+            0x4202..=0x4206 => self.math.write(addr, data),
             0x4207 => self.bus_b.ppu.set_h_timer_lo(data),
             0x4208 => self.bus_b.ppu.set_h_timer_hi(data),
             0x4209 => self.bus_b.ppu.set_v_timer_lo(data),
             0x420a => self.bus_b.ppu.set_v_timer_hi(data),
-            0x420b => self.dma_transfer(data),
-            0x420c => self.hdma_enable = data,
             0x420d => self.cart.set_rom_speed(data),
-            _ => unreachable!(),
-        }
-    }
-
-    // DMA
-    // Keeps cycling until the transfer is done. This pauses the CPU operation.
-    fn dma_transfer(&mut self, channels: u8) {
-        for chan in 0..8 {
-            if test_bit!(channels, chan, u8) {
-                for i in 0..self.dma_channels[chan].get_count() {
-                    let src_addr = self.dma_channels[chan].get_src_addr(i);
-                    let dst_addr = self.dma_channels[chan].get_dst_addr(i);
-
-                    let data = self.read(src_addr).0;
-                    self.write(dst_addr, data);
-
-                    self.clock(8);  // TODO: interrupt?
-                }
-            }
-        }
-    }
-
-    // Transfers a single block of HDMA data. Called during H-blank.
-    fn hdma_transfer(&mut self) {
-        for chan in 0..8 {
-            if test_bit!(self.hdma_active, chan, u8) && test_bit!(self.hdma_enable, chan, u8) {
-
-                if self.dma_channels[chan].hdma_step_line() {
-                    if self.dma_channels[chan].should_repeat() {
-                        self.hdma_line(chan);
-                    }
-                } else {
-                    // New instruction.
-                    let instr = self.read(self.dma_channels[chan].get_hdma_table_addr()).0;
-                    if self.dma_channels[chan].hdma_init_instr(instr) {
-                        // Setup indirect address if necessary.
-                        if self.dma_channels[chan].control.contains(DMAControl::HDMA_INDIRECT) {
-                            let table_addr = self.dma_channels[chan].get_hdma_table_addr();
-                            let lo = self.read(table_addr).0;
-                            let hi = self.read(table_addr.wrapping_add(1)).0;
-                            self.dma_channels[chan].set_indirect_table_addr(make16!(hi, lo));
-                        }
-
-                        self.hdma_line(chan);
-                    } else {
-                        self.hdma_active ^= bit!(chan);
-                    }
-                }
-            }
+            _ => {},
         }
     }
-
-    // A single HDMA line transfer.
-    fn hdma_line(&mut self, chan: usize) {
-        let src_addr = self.dma_channels[chan].get_data_addr();
-        // Get bytes to write.
-        match (self.dma_channels[chan].control & DMAControl::TRANSFER_MODE).bits() {
-            0 => {
-                let data = self.read(src_addr).0;
-                self.bus_b.write(self.dma_channels[chan].b_bus_addr, data);
-            },
-            1 => for i in 0..2 {
-                let data = self.read(src_addr + i).0;
-                self.bus_b.write(self.dma_channels[chan].b_bus_addr + i as u8, data);
-            },
-            2 | 6 => for i in 0..2 {
-                let data = self.read(src_addr + i).0;
-                self.bus_b.write(self.dma_channels[chan].b_bus_addr, data);
-            },
-            3 | 7 => for i in 0..4 {
-                let data = self.read(src_addr + i).0;
-                self.bus_b.write(self.dma_channels[chan].b_bus_addr + ((i / 2) as u8), data);
-            },
-            4 => for i in 0..4 {
-                let data = self.read(src_addr + i).0;
-                self.bus_b.write(self.dma_channels[chan].b_bus_addr + i as u8, data);
-            },
-            5 => for i in 0..4 {
-                let data = self.read(src_addr + i).0;
-                self.bus_b.write(self.dma_channels[chan].b_bus_addr + ((i % 2) as u8), data);
-            },
-            _ => unreachable!()
-        }
-
-        self.clock(self.dma_channels[chan].get_cycles());
-    }
 }
 
-// Address Bus B, used for hardware registers.
-struct AddrBusB {
-    ppu:        PPU,
-    apu:        APU,
-
-    open_bus:   u8
-}
-
-impl AddrBusB {
-    fn new() -> Self {
-        AddrBusB {
-            ppu: PPU::new(),
-            apu: APU::new(),
-
-            open_bus:   0,
-        }
+impl DMABus for Devices {
+    fn read(&mut self, addr: u32) -> u8 {
+        Devices::read(self, addr).0
     }
 
-    fn read(&mut self, addr: u8) -> u8 {
-        match addr {
-            0x37        => self.ppu.latch_hv(),
-            0x34..=0x3F => self.ppu.read_mem(addr),
-            0x40..=0x7F => match addr % 4 {
-                0   => self.apu.read_port(0),
-                1   => self.apu.read_port(1),
-                2   => self.apu.read_port(2),
-                3   => self.apu.read_port(3),
-                _   => unreachable!(),
-            },
-            _ => self.open_bus//unreachable!("Reading from open bus: {:X}", addr)
-        }
+    fn write(&mut self, addr: u32, data: u8) {
+        Devices::write(self, addr, data);
     }
 
-    fn write(&mut self, addr: u8, data: u8) {
-        match addr {
-            0x00..=0x33 => self.ppu.write_mem(addr, data),
-            0x40..=0x7F => match addr % 4 {
-                0   => self.apu.write_port(0, data),
-                1   => self.apu.write_port(1, data),
-                2   => self.apu.write_port(2, data),
-                3   => self.apu.write_port(3, data),
-                _   => unreachable!(),
-            },
-            0x34..=0x3F => {},
-            _ => {}//panic!("Tried to write silly shit: {:X} to {:X}", data, addr),
-        }
-        self.open_bus = data;
+    fn write_b(&mut self, addr: u8, data: u8) {
+        self.bus_b.write(addr, data);
     }
 
-    // Clock the APU (SPC and DSP)
-    fn clock_apu(&mut self, cycles: usize) {
-        self.apu.clock(cycles);
+    fn clock(&mut self, cycles: usize) -> (Interrupt, DMASignal) {
+        Devices::clock(self, cycles)
     }
-}
\ No newline at end of file
+}
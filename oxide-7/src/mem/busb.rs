@@ -0,0 +1,178 @@
+// Address Bus B: PPU, APU and expansion port.
+use alloc::{
+    boxed::Box,
+    string::String
+};
+
+use crate::{
+    constants::machine::MachineTiming,
+    video::PPU,
+    audio::APU,
+    events::{EventBus, UnsupportedFeature},
+    savestate::{Stateful, StateWriter, StateReader}
+};
+#[cfg(feature = "debug")]
+use crate::events::Event;
+
+use super::{
+    ExpansionPort,
+    IOPort
+};
+
+// Address Bus B, used for hardware registers.
+pub struct AddrBusB {
+    pub ppu:        PPU,
+    pub apu:        APU,
+
+    pub exp_port:   Option<Box<dyn ExpansionPort>>,
+    pub io_port:    Option<Box<dyn IOPort>>,
+    events:         EventBus,
+
+    wrio:           u8, // Value written to $4201.
+    io_pins:        u8, // Level of the I/O port pins when they were last checked.
+}
+
+impl AddrBusB {
+    pub fn new(events: EventBus, timing: &MachineTiming) -> Self {
+        AddrBusB {
+            ppu: PPU::new(timing),
+            apu: APU::new(events.clone()),
+
+            exp_port:   None,
+            io_port:    None,
+            events:     events,
+
+            wrio:       0xFF,
+            io_pins:    0xFF,
+        }
+    }
+
+    // Registers that don't drive all of the data bus return open bus: the last value the CPU read.
+    pub fn read(&mut self, addr: u8, open_bus: u8) -> u8 {
+        match addr {
+            0x37        => self.read_slhv(open_bus),
+            0x00..=0x3F => self.ppu.read_mem(addr, open_bus),
+            0x40..=0x7F => match addr % 4 {
+                0   => self.apu.read_port(0),
+                1   => self.apu.read_port(1),
+                2   => self.apu.read_port(2),
+                3   => self.apu.read_port(3),
+                _   => unreachable!(),
+            },
+            0x84..=0xFF => self.read_exp_port(addr, open_bus),
+            _ => open_bus
+        }
+    }
+
+    pub fn write(&mut self, addr: u8, data: u8) {
+        match addr {
+            0x33        => {
+                self.check_setini(data);
+                self.ppu.write_mem(addr, data);
+            },
+            0x00..=0x32 => self.ppu.write_mem(addr, data),
+            0x40..=0x7F => match addr % 4 {
+                0   => self.apu.write_port(0, data),
+                1   => self.apu.write_port(1, data),
+                2   => self.apu.write_port(2, data),
+                3   => self.apu.write_port(3, data),
+                _   => unreachable!(),
+            },
+            0x34..=0x3F => {},
+            0x84..=0xFF => self.write_exp_port(addr, data),
+            _ => {}//panic!("Tried to write silly shit: {:X} to {:X}", data, addr),
+        }
+    }
+
+    // Screen interlace isn't emulated, and overscan lines aren't output unless enabled: report if the game uses them.
+    fn check_setini(&self, data: u8) {
+        if test_bit!(data, 0, u8) {
+            self.events.report_unsupported(UnsupportedFeature::Interlace);
+        }
+        if test_bit!(data, 2, u8) && !self.ppu.overscan_output() {
+            self.events.report_unsupported(UnsupportedFeature::Overscan);
+        }
+    }
+
+    // Clock the APU (SPC and DSP)
+    pub fn clock_apu(&mut self, cycles: usize) {
+        self.apu.clock(cycles);
+    }
+
+    // Reading $2137 only latches the H/V counters if WRIO bit 7 is set.
+    // Nothing drives the data bus, so it returns open bus.
+    fn read_slhv(&mut self, open_bus: u8) -> u8 {
+        if test_bit!(self.wrio, 7, u8) {
+            self.ppu.latch_hv();
+        }
+        open_bus
+    }
+
+    pub fn write_wrio(&mut self, data: u8) {
+        self.wrio = data;
+        if let Some(device) = self.io_port.as_mut() {
+            device.write(data);
+        }
+        self.update_io_pins();
+    }
+
+    pub fn read_rdio(&mut self) -> u8 {
+        self.update_io_pins()
+    }
+
+    // Find the current level of the I/O pins.
+    // The H/V counters are latched when pin 7 goes from high to low.
+    fn update_io_pins(&mut self) -> u8 {
+        let device_pins = self.io_port.as_mut().map_or(0xFF, |device| device.read());
+        let pins = self.wrio & device_pins;
+        if test_bit!(self.io_pins, 7, u8) && !test_bit!(pins, 7, u8) {
+            self.ppu.latch_hv();
+        }
+        self.io_pins = pins;
+        pins
+    }
+
+    // With nothing attached to the expansion port, reads return open bus.
+    // Accesses without a device are reported with the debug feature.
+    fn read_exp_port(&mut self, addr: u8, open_bus: u8) -> u8 {
+        match self.exp_port.as_mut() {
+            Some(device) => device.read(addr).unwrap_or(open_bus),
+            None => {
+                #[cfg(feature = "debug")]
+                self.events.send(Event::ExpansionPortRead {
+                    addr: make16!(0x21, addr),
+                });
+                open_bus
+            }
+        }
+    }
+
+    fn write_exp_port(&mut self, addr: u8, data: u8) {
+        match self.exp_port.as_mut() {
+            Some(device) => device.write(addr, data),
+            None => {
+                #[cfg(feature = "debug")]
+                self.events.send(Event::ExpansionPortWritten {
+                    addr: make16!(0x21, addr),
+                    data: data,
+                });
+            }
+        }
+    }
+}
+
+impl Stateful for AddrBusB {
+    fn save_state(&self, w: &mut StateWriter) {
+        self.ppu.save_state(w);
+        self.apu.save_state(w);
+        w.write(&self.wrio);
+        w.write(&self.io_pins);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), String> {
+        self.ppu.load_state(r)?;
+        self.apu.load_state(r)?;
+        r.read_into(&mut self.wrio)?;
+        r.read_into(&mut self.io_pins)
+    }
+}
\ No newline at end of file
@@ -0,0 +1,515 @@
+// DMA controller and channels.
+#[cfg(test)]
+mod tests;
+
+use alloc::{
+    string::String,
+    vec::Vec
+};
+use bitflags::bitflags;
+use serde::{
+    Serialize,
+    Deserialize
+};
+
+use crate::{
+    common::Interrupt,
+    savestate::{
+        Stateful,
+        StateWriter,
+        StateReader
+    }
+};
+
+bitflags! {
+    #[derive(Default, Serialize, Deserialize)]
+    pub struct DMAControl: u8 {
+        const TRANSFER_DIR  = bit!(7);
+        const HDMA_INDIRECT = bit!(6);
+        const ADDR_DEC      = bit!(4);
+        const FIXED_ADDR    = bit!(3);
+        const TRANSFER_MODE = bits![2, 1, 0];
+    }
+}
+
+// Signals from the rest of the system that the DMA controller responds to.
+#[derive(Debug, PartialEq)]
+pub enum DMASignal {
+    None,
+    HBlank,     // H-Blank period entered: transfer HDMA data.
+    FrameStart, // Frame begin: reset HDMA.
+}
+
+// The rest of the system, as seen by the DMA controller.
+pub trait DMABus {
+    // Read and write address bus A. B bus registers appear at $2100-$21FF.
+    fn read(&mut self, addr: u32) -> u8;
+    fn write(&mut self, addr: u32, data: u8);
+    // Write directly to address bus B.
+    fn write_b(&mut self, addr: u8, data: u8);
+    // Advance the rest of the system.
+    fn clock(&mut self, cycles: usize) -> (Interrupt, DMASignal);
+}
+
+// Registers $420B, $420C and $4300-$437F.
+// HDMA transfers are triggered by the PPU, so the rest of the system is clocked through here.
+pub struct DMAController {
+    hdma_enable:    u8,
+    hdma_active:    u8,
+    channels:       Vec<DMAChannel>,
+
+    #[cfg(feature = "debug")]
+    hdma_started:   u8, // Channels that were active at the start of the frame.
+    #[cfg(feature = "debug")]
+    hdma_lines:     Vec<[crate::debug::HDMAChannelState; 8]>,   // Captured each line of the current frame.
+    #[cfg(feature = "debug")]
+    hdma_last_frame:    Vec<[crate::debug::HDMAChannelState; 8]>,
+}
+
+impl DMAController {
+    pub fn new() -> Self {
+        DMAController {
+            hdma_enable:    0,
+            hdma_active:    0,
+            channels:       vec![DMAChannel::new(); 8],
+
+            #[cfg(feature = "debug")]
+            hdma_started:   0,
+            #[cfg(feature = "debug")]
+            hdma_lines:     Vec::new(),
+            #[cfg(feature = "debug")]
+            hdma_last_frame:    Vec::new(),
+        }
+    }
+
+    pub fn read(&self, addr: u16) -> u8 {
+        let chan = ((addr >> 4) & 0x7) as usize;
+        match lo_nybble!(addr) {
+            reg @ (0x0..=0xB | 0xF) => self.channels[chan].read(reg as u8),
+            _ => 0
+        }
+    }
+
+    // Writing to $420B starts a DMA transfer, which runs until it is complete.
+    pub fn write<B: DMABus>(&mut self, bus: &mut B, addr: u16, data: u8) {
+        match addr {
+            0x420B => self.dma_transfer(bus, data),
+            0x420C => self.hdma_enable = data,
+            0x4300..=0x437F => {
+                let chan = ((addr >> 4) & 0x7) as usize;
+                let reg = lo_nybble!(addr) as u8;
+                if reg <= 0xB || reg == 0xF {
+                    self.channels[chan].write(reg, data);
+                }
+            },
+            _ => unreachable!()
+        }
+    }
+
+    // Clock the rest of the system, and run HDMA if needed.
+    pub fn clock<B: DMABus>(&mut self, bus: &mut B, cycles: usize) -> Interrupt {
+        let (i, signal) = bus.clock(cycles);
+        match signal {
+            DMASignal::HBlank => {
+                if self.hdma_active != 0 {
+                    self.hdma_transfer(bus);
+                }
+                #[cfg(feature = "debug")]
+                self.capture_hdma_line();
+            },
+            DMASignal::FrameStart => {
+                #[cfg(feature = "debug")]
+                self.start_hdma_capture();
+                self.hdma_active = self.hdma_enable;
+                for chan in 0..8 {
+                    if test_bit!(self.hdma_active, chan, u8) {
+                        self.channels[chan].start_hdma();
+                    }
+                }
+            },
+            DMASignal::None => {}
+        }
+        i
+    }
+}
+
+impl Stateful for DMAController {
+    fn save_state(&self, w: &mut StateWriter) {
+        w.write(&self.hdma_enable);
+        w.write(&self.hdma_active);
+        w.write(&self.channels);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), String> {
+        r.read_into(&mut self.hdma_enable)?;
+        r.read_into(&mut self.hdma_active)?;
+        r.read_into(&mut self.channels)
+    }
+}
+
+// Internal
+impl DMAController {
+    // Keeps cycling until the transfer is done. This pauses the CPU operation.
+    fn dma_transfer<B: DMABus>(&mut self, bus: &mut B, channels: u8) {
+        for chan in 0..8 {
+            if test_bit!(channels, chan, u8) {
+                for i in 0..self.channels[chan].get_count() {
+                    let src_addr = self.channels[chan].get_src_addr(i);
+                    let dst_addr = self.channels[chan].get_dst_addr(i);
+
+                    let data = bus.read(src_addr);
+                    bus.write(dst_addr, data);
+
+                    self.clock(bus, 8);  // TODO: interrupt?
+                }
+            }
+        }
+    }
+
+    // Transfers a single block of HDMA data. Called during H-blank.
+    fn hdma_transfer<B: DMABus>(&mut self, bus: &mut B) {
+        for chan in 0..8 {
+            if test_bit!(self.hdma_active, chan, u8) && test_bit!(self.hdma_enable, chan, u8) {
+
+                if self.channels[chan].hdma_step_line() {
+                    if self.channels[chan].should_repeat() {
+                        self.hdma_line(bus, chan);
+                    }
+                } else {
+                    // New instruction.
+                    let instr = bus.read(self.channels[chan].get_hdma_table_addr());
+                    if self.channels[chan].hdma_init_instr(instr) {
+                        // Setup indirect address if necessary.
+                        if self.channels[chan].control.contains(DMAControl::HDMA_INDIRECT) {
+                            let table_addr = self.channels[chan].get_hdma_table_addr();
+                            let lo = bus.read(table_addr);
+                            let hi = bus.read(table_addr.wrapping_add(1));
+                            self.channels[chan].set_indirect_table_addr(make16!(hi, lo));
+                        }
+
+                        self.hdma_line(bus, chan);
+                    } else {
+                        self.hdma_active ^= bit!(chan);
+                    }
+                }
+            }
+        }
+    }
+
+    // A single HDMA line transfer.
+    fn hdma_line<B: DMABus>(&mut self, bus: &mut B, chan: usize) {
+        let src_addr = self.channels[chan].get_data_addr();
+        let b_bus_addr = self.channels[chan].b_bus_addr;
+        // Get bytes to write.
+        match (self.channels[chan].control & DMAControl::TRANSFER_MODE).bits() {
+            0 => {
+                let data = bus.read(src_addr);
+                bus.write_b(b_bus_addr, data);
+            },
+            1 => for i in 0..2 {
+                let data = bus.read(src_addr + i);
+                bus.write_b(b_bus_addr + i as u8, data);
+            },
+            2 | 6 => for i in 0..2 {
+                let data = bus.read(src_addr + i);
+                bus.write_b(b_bus_addr, data);
+            },
+            3 | 7 => for i in 0..4 {
+                let data = bus.read(src_addr + i);
+                bus.write_b(b_bus_addr + ((i / 2) as u8), data);
+            },
+            4 => for i in 0..4 {
+                let data = bus.read(src_addr + i);
+                bus.write_b(b_bus_addr + i as u8, data);
+            },
+            5 => for i in 0..4 {
+                let data = bus.read(src_addr + i);
+                bus.write_b(b_bus_addr + ((i % 2) as u8), data);
+            },
+            _ => unreachable!()
+        }
+
+        self.clock(bus, self.channels[chan].get_cycles());
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct DMAChannel {
+    pub control:    DMAControl,
+    pub b_bus_addr: u8,
+    a_bus_addr:     u16,
+    a_bus_bank:     u8,
+    
+    count:      u16,
+    hdma_bank:  u8,
+
+    hdma_table_addr:    u16,
+    hdma_line_count:    u8,
+    hdma_repeat:        bool,
+
+    unused:             u8, // $43xB, mirrored at $43xF. Not used by the DMA, but readable and writable.
+
+    bytes_per_cycle:    u16,
+}
+
+impl DMAChannel {
+    pub fn new() -> Self {
+        DMAChannel {
+            control:        DMAControl::default(),
+            b_bus_addr:     0,
+            a_bus_addr:     0,
+            a_bus_bank:     0,
+            
+            count:      0,
+            hdma_bank:  0,
+
+            hdma_table_addr:    0xFF,
+            hdma_line_count:    0,
+            hdma_repeat:        false,
+
+            unused:             0xFF,
+
+            bytes_per_cycle:    0
+        }
+    }
+
+    pub fn read(&self, addr: u8) -> u8 {
+        match addr {
+            0x0 => self.control.bits(),
+            0x1 => self.b_bus_addr,
+            0x2 => lo!(self.a_bus_addr),
+            0x3 => hi!(self.a_bus_addr),
+            0x4 => self.a_bus_bank,
+            0x5 => lo!(self.count),
+            0x6 => hi!(self.count),
+            0x7 => self.hdma_bank,
+            0x8 => lo!(self.hdma_table_addr),
+            0x9 => hi!(self.hdma_table_addr),
+            0xA => self.hdma_line_count,
+            0xB | 0xF => self.unused,
+            _ => unreachable!()
+        }
+    }
+
+    pub fn write(&mut self, addr: u8, data: u8) {
+        match addr {
+            0x0 => {
+                self.control = DMAControl::from_bits_truncate(data);
+                self.bytes_per_cycle = match (self.control & DMAControl::TRANSFER_MODE).bits() {
+                    0 => 1,
+                    1 | 2 | 6 => 2,
+                    3 | 4 | 5 | 7 => 4,
+                    _ => unreachable!()
+                };
+            },
+            0x1 => self.b_bus_addr = data,
+            0x2 => self.a_bus_addr = set_lo!(self.a_bus_addr, data),
+            0x3 => self.a_bus_addr = set_hi!(self.a_bus_addr, data),
+            0x4 => self.a_bus_bank = data,
+            0x5 => self.count = set_lo!(self.count, data),
+            0x6 => self.count = set_hi!(self.count, data),
+            0x7 => self.hdma_bank = data,
+            0x8 => self.hdma_table_addr = set_lo!(self.hdma_table_addr, data),
+            0x9 => self.hdma_table_addr = set_hi!(self.hdma_table_addr, data),
+            0xA => self.hdma_line_count = data,
+            0xB | 0xF => self.unused = data,
+            _ => unreachable!()
+        }
+    }
+
+    // Get the source addr for DMA transfer, for each byte.
+    pub fn get_src_addr(&mut self, i: usize) -> u32 {
+        if self.control.contains(DMAControl::TRANSFER_DIR) {
+            self.get_b_bus_addr(i as u8)
+        } else {
+            self.get_a_bus_addr()
+        }
+    }
+
+    // Get the destination addr for DMA transfer, for each byte.
+    pub fn get_dst_addr(&mut self, i: usize) -> u32 {
+        if self.control.contains(DMAControl::TRANSFER_DIR) {
+            self.get_a_bus_addr()
+        } else {
+            self.get_b_bus_addr(i as u8)
+        }
+    }
+
+    // Cycles for a single transfer block.
+    pub fn get_cycles(&self) -> usize {
+        match (self.control & DMAControl::TRANSFER_MODE).bits() {
+            0 => 8,
+            1 | 2 | 6 => 16,
+            3 | 4 | 5 | 7 => 32,
+            _ => unreachable!()
+        }
+    }
+
+    // Return the number of bytes to transfer.
+    pub fn get_count(&mut self) -> usize {
+        let count = core::mem::replace(&mut self.count, 0);
+        if count == 0 {
+            0x10000
+        } else {
+            count as usize
+        }
+    }
+
+    // HDMA
+    pub fn start_hdma(&mut self) {
+        self.hdma_table_addr = self.a_bus_addr;
+        self.hdma_line_count = 0;
+    }
+
+    // Call this for every instruction in the table. Return false if the HDMA table is finished.
+    pub fn hdma_init_instr(&mut self, line_count: u8) -> bool {
+        if line_count == 0 {
+            false
+        } else {
+            let repeat_line_count = line_count.wrapping_sub(1);
+            self.hdma_line_count = repeat_line_count & 0x7F;
+            self.hdma_repeat = test_bit!(repeat_line_count, 7, u8);
+            self.hdma_table_addr = self.hdma_table_addr.wrapping_add(1);
+            true
+        }
+    }
+
+    // Call this every line. Returns false when ready for next instruction.
+    pub fn hdma_step_line(&mut self) -> bool {
+        if self.hdma_line_count == 0 {
+            false
+        } else {
+            self.hdma_line_count = self.hdma_line_count.wrapping_sub(1) & 0x7F;
+            true
+        }
+    }
+
+    pub fn get_hdma_table_addr(&self) -> u32 {
+        make24!(self.a_bus_bank, self.hdma_table_addr)
+    }
+
+    // Check if the instruction should repeat or run once and skip.
+    pub fn should_repeat(&self) -> bool {
+        self.hdma_repeat
+    }
+
+    // Set address for indirect data, and inc table address.
+    pub fn set_indirect_table_addr(&mut self, addr: u16) {
+        self.count = addr;
+        self.hdma_table_addr = self.hdma_table_addr.wrapping_add(2);   // Addresses are 2 bytes.
+    }
+
+    // Get address of data to use, and increment.
+    pub fn get_data_addr(&mut self) -> u32 {
+        if self.control.contains(DMAControl::HDMA_INDIRECT) {
+            let addr = make24!(self.hdma_bank, self.count);
+            self.count = self.count.wrapping_add(self.bytes_per_cycle);
+            addr
+        } else {
+            let addr = self.get_hdma_table_addr();
+            self.hdma_table_addr = self.hdma_table_addr.wrapping_add(self.bytes_per_cycle);
+            addr
+        }
+    }
+}
+
+// Internal
+impl DMAChannel {
+    // Get the A-bus address and increment or decrement as specified.
+    fn get_a_bus_addr(&mut self) -> u32 {
+        let ret = make24!(self.a_bus_bank, self.a_bus_addr);
+
+        if !self.control.contains(DMAControl::FIXED_ADDR) {
+            if !self.control.contains(DMAControl::ADDR_DEC) {
+                self.a_bus_addr = self.a_bus_addr.wrapping_add(1);
+            } else {
+                self.a_bus_addr = self.a_bus_addr.wrapping_sub(1);
+            }
+        }
+
+        ret
+    }
+
+    // Get the B-bus address, modified by the current byte of the transfer and the transfer mode.
+    fn get_b_bus_addr(&mut self, i: u8) -> u32 {
+        match (self.control & DMAControl::TRANSFER_MODE).bits() {
+            0 | 2 | 6 => make24!(0, 0x21, self.b_bus_addr),
+            1 | 5 => make24!(0, 0x21, self.b_bus_addr + (i % 2)),
+            4 => make24!(0, 0x21, self.b_bus_addr + (i % 4)),
+            3 | 7 => make24!(0, 0x21, self.b_bus_addr + ((i / 2) % 2)),
+            _ => unreachable!()
+        }
+    }
+}
+
+// Debug
+#[cfg(feature = "debug")]
+impl DMAController {
+    // Registers of each channel.
+    pub fn channel_regs(&self) -> [crate::debug::DMAChannelRegs; 8] {
+        let mut regs = [crate::debug::DMAChannelRegs::default(); 8];
+        for (chan, regs) in self.channels.iter().zip(regs.iter_mut()) {
+            *regs = chan.regs();
+        }
+        regs
+    }
+
+    // HDMA state of each channel, for each line of the last complete frame.
+    pub fn hdma_states(&self) -> Vec<[crate::debug::HDMAChannelState; 8]> {
+        self.hdma_last_frame.clone()
+    }
+
+    fn start_hdma_capture(&mut self) {
+        self.hdma_last_frame = core::mem::take(&mut self.hdma_lines);
+        self.hdma_started = self.hdma_enable;
+    }
+
+    fn capture_hdma_line(&mut self) {
+        let mut states = [crate::debug::HDMAChannelState::default(); 8];
+        for (chan, state) in states.iter_mut().enumerate() {
+            let completed = test_bit!(self.hdma_started, chan, u8) && !test_bit!(self.hdma_active, chan, u8);
+            *state = self.channels[chan].hdma_state(test_bit!(self.hdma_enable, chan, u8), completed);
+        }
+        self.hdma_lines.push(states);
+    }
+}
+
+#[cfg(feature = "debug")]
+impl DMAChannel {
+    fn regs(&self) -> crate::debug::DMAChannelRegs {
+        crate::debug::DMAChannelRegs {
+            control:            self.control.bits(),
+            b_bus_addr:         self.b_bus_addr,
+            a_bus_addr:         make24!(self.a_bus_bank, self.a_bus_addr),
+            count:              self.count,
+            hdma_bank:          self.hdma_bank,
+            hdma_table_addr:    self.hdma_table_addr,
+            hdma_line_count:    self.hdma_line_count,
+            unused:             self.unused,
+        }
+    }
+
+    fn hdma_state(&self, enabled: bool, completed: bool) -> crate::debug::HDMAChannelState {
+        crate::debug::HDMAChannelState {
+            enabled:        enabled,
+            completed:      completed,
+            line_counter:   self.hdma_line_count,
+            repeat:         self.hdma_repeat,
+            table_addr:     self.get_hdma_table_addr(),
+            indirect:       self.control.contains(DMAControl::HDMA_INDIRECT),
+            indirect_addr:  make24!(self.hdma_bank, self.count),
+        }
+    }
+}
+
+impl DMAChannel {
+    #[allow(dead_code)]
+    pub fn print_dma(&self) {
+        println!("CTRL: {:b}, B_ADDR: {:X}, A_ADDR: {:X}_{:X}, count: {:X}", self.control.bits(), self.b_bus_addr, self.a_bus_bank, self.a_bus_addr, self.count);
+    }
+
+    #[allow(dead_code)]
+    pub fn print_hdma(&self) {
+        println!("CTRL: {:b}, B_ADDR: {:X}, A_ADDR: {:X}, bank: {:X}, line count: {:X}, table addr: {:X}, indirect addr: {:X}", self.control.bits(), self.b_bus_addr, self.a_bus_addr, self.hdma_bank, self.hdma_line_count, self.hdma_table_addr, self.count);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,252 @@
+// DMA tests, run against a fake bus.
+use super::*;
+
+// A-bus memory is 64KB, mirrored across all banks.
+// Accesses to $2100-$21FF go to the B bus, and are recorded.
+struct FakeBus {
+    mem:        Vec<u8>,
+    b_writes:   Vec<(u8, u8)>,
+    b_reads:    Vec<u8>,
+    cycles:     usize,
+    signal:     Option<DMASignal>,  // Returned by the next clock.
+}
+
+impl FakeBus {
+    fn new() -> Self {
+        FakeBus {
+            mem:        vec![0; 0x10000],
+            b_writes:   Vec::new(),
+            b_reads:    Vec::new(),
+            cycles:     0,
+            signal:     None,
+        }
+    }
+}
+
+impl DMABus for FakeBus {
+    fn read(&mut self, addr: u32) -> u8 {
+        let addr = lo24!(addr);
+        if hi!(addr) == 0x21 {
+            self.b_reads.push(lo!(addr));
+            lo!(addr)
+        } else {
+            self.mem[addr as usize]
+        }
+    }
+
+    fn write(&mut self, addr: u32, data: u8) {
+        let addr = lo24!(addr);
+        if hi!(addr) == 0x21 {
+            self.write_b(lo!(addr), data);
+        } else {
+            self.mem[addr as usize] = data;
+        }
+    }
+
+    fn write_b(&mut self, addr: u8, data: u8) {
+        self.b_writes.push((addr, data));
+    }
+
+    fn clock(&mut self, cycles: usize) -> (Interrupt, DMASignal) {
+        self.cycles += cycles;
+        (Interrupt::default(), self.signal.take().unwrap_or(DMASignal::None))
+    }
+}
+
+// Set up a channel's registers.
+fn setup_channel(dma: &mut DMAController, bus: &mut FakeBus, chan: u16, regs: &[u8]) {
+    for (i, data) in regs.iter().enumerate() {
+        dma.write(bus, 0x4300 + (chan << 4) + (i as u16), *data);
+    }
+}
+
+#[test]
+fn registers() {
+    let mut dma = DMAController::new();
+    let mut bus = FakeBus::new();
+    setup_channel(&mut dma, &mut bus, 3, &[0x01, 0x18, 0x00, 0x80, 0x7E, 0x34, 0x12, 0x7F, 0x56, 0x78, 0x9A]);
+
+    let regs = (0x4330..=0x433A).map(|addr| dma.read(addr)).collect::<Vec<_>>();
+    assert_eq!(regs, vec![0x01, 0x18, 0x00, 0x80, 0x7E, 0x34, 0x12, 0x7F, 0x56, 0x78, 0x9A]);
+    // Unused register, mirrored at $43xF.
+    assert_eq!(dma.read(0x433B), 0xFF);
+    dma.write(&mut bus, 0x433B, 0x5A);
+    assert_eq!(dma.read(0x433B), 0x5A);
+    assert_eq!(dma.read(0x433F), 0x5A);
+    dma.write(&mut bus, 0x433F, 0xA5);
+    assert_eq!(dma.read(0x433B), 0xA5);
+    assert_eq!(dma.read(0x432B), 0xFF);
+    // Not mapped.
+    assert_eq!(dma.read(0x433C), 0);
+}
+
+// Mode 1 writes to two B-bus registers in turn.
+#[test]
+fn general_dma_a_to_b() {
+    let mut dma = DMAController::new();
+    let mut bus = FakeBus::new();
+    bus.mem[0x8000..0x8004].copy_from_slice(&[0x11, 0x22, 0x33, 0x44]);
+    setup_channel(&mut dma, &mut bus, 0, &[0x01, 0x18, 0x00, 0x80, 0x00, 0x04, 0x00]);
+
+    dma.write(&mut bus, 0x420B, 0x01);
+
+    assert_eq!(bus.b_writes, vec![(0x18, 0x11), (0x19, 0x22), (0x18, 0x33), (0x19, 0x44)]);
+    assert_eq!(bus.cycles, 4 * 8);
+    // The A-bus address moves on, and the count is used up.
+    assert_eq!(dma.read(0x4302), 0x04);
+    assert_eq!(dma.read(0x4305), 0x00);
+    assert_eq!(dma.read(0x4306), 0x00);
+}
+
+#[test]
+fn general_dma_b_to_a_decrement() {
+    let mut dma = DMAController::new();
+    let mut bus = FakeBus::new();
+    setup_channel(&mut dma, &mut bus, 1, &[0x90, 0x39, 0x03, 0x10, 0x00, 0x03, 0x00]);
+
+    dma.write(&mut bus, 0x420B, 0x02);
+
+    assert_eq!(bus.b_reads, vec![0x39, 0x39, 0x39]);
+    assert_eq!(&bus.mem[0x1001..=0x1003], &[0x39, 0x39, 0x39]);
+    assert_eq!(dma.read(0x4312), 0x00);
+}
+
+#[test]
+fn general_dma_fixed_addr() {
+    let mut dma = DMAController::new();
+    let mut bus = FakeBus::new();
+    bus.mem[0x9000] = 0xAB;
+    setup_channel(&mut dma, &mut bus, 2, &[0x08, 0x22, 0x00, 0x90, 0x00, 0x03, 0x00]);
+
+    dma.write(&mut bus, 0x420B, 0x04);
+
+    assert_eq!(bus.b_writes, vec![(0x22, 0xAB); 3]);
+    assert_eq!(dma.read(0x4322), 0x00);
+    assert_eq!(dma.read(0x4323), 0x90);
+}
+
+// Channels run in order, lowest first.
+#[test]
+fn general_dma_multiple_channels() {
+    let mut dma = DMAController::new();
+    let mut bus = FakeBus::new();
+    bus.mem[0x8000] = 0x01;
+    bus.mem[0x8100] = 0x02;
+    setup_channel(&mut dma, &mut bus, 5, &[0x00, 0x04, 0x00, 0x81, 0x00, 0x01, 0x00]);
+    setup_channel(&mut dma, &mut bus, 2, &[0x00, 0x18, 0x00, 0x80, 0x00, 0x01, 0x00]);
+
+    dma.write(&mut bus, 0x420B, 0x24);
+
+    assert_eq!(bus.b_writes, vec![(0x18, 0x01), (0x04, 0x02)]);
+}
+
+// An HDMA table with a one-line entry, a repeated entry and an end marker.
+#[test]
+fn hdma_direct() {
+    let mut dma = DMAController::new();
+    let mut bus = FakeBus::new();
+    bus.mem[0x8000..0x8006].copy_from_slice(&[0x01, 0x10, 0x82, 0x20, 0x21, 0x00]);
+    setup_channel(&mut dma, &mut bus, 0, &[0x00, 0x21, 0x00, 0x80, 0x00]);
+    dma.write(&mut bus, 0x420C, 0x01);
+
+    bus.signal = Some(DMASignal::FrameStart);
+    dma.clock(&mut bus, 1);
+    for _ in 0..4 {
+        bus.signal = Some(DMASignal::HBlank);
+        dma.clock(&mut bus, 1);
+    }
+
+    assert_eq!(bus.b_writes, vec![(0x21, 0x10), (0x21, 0x20), (0x21, 0x21)]);
+    // The channel stops at the end of the table.
+    bus.b_writes.clear();
+    bus.signal = Some(DMASignal::HBlank);
+    dma.clock(&mut bus, 1);
+    assert!(bus.b_writes.is_empty());
+}
+
+// Without the repeat flag, data is only written on the first line of the entry.
+#[test]
+fn hdma_no_repeat() {
+    let mut dma = DMAController::new();
+    let mut bus = FakeBus::new();
+    bus.mem[0x8000..0x8005].copy_from_slice(&[0x03, 0x11, 0x22, 0x01, 0x33]);
+    setup_channel(&mut dma, &mut bus, 0, &[0x02, 0x0D, 0x00, 0x80, 0x00]);
+    dma.write(&mut bus, 0x420C, 0x01);
+
+    bus.signal = Some(DMASignal::FrameStart);
+    dma.clock(&mut bus, 1);
+    for _ in 0..4 {
+        bus.signal = Some(DMASignal::HBlank);
+        dma.clock(&mut bus, 1);
+    }
+
+    assert_eq!(bus.b_writes, vec![(0x0D, 0x11), (0x0D, 0x22), (0x0D, 0x33), (0x0D, 0x00)]);
+}
+
+#[test]
+fn hdma_indirect() {
+    let mut dma = DMAController::new();
+    let mut bus = FakeBus::new();
+    bus.mem[0x8000..0x8004].copy_from_slice(&[0x81, 0x00, 0x90, 0x00]);
+    bus.mem[0x9000..0x9002].copy_from_slice(&[0x55, 0x66]);
+    setup_channel(&mut dma, &mut bus, 4, &[0x41, 0x26, 0x00, 0x80, 0x00, 0x00, 0x00, 0x00]);
+    dma.write(&mut bus, 0x420C, 0x10);
+
+    bus.signal = Some(DMASignal::FrameStart);
+    dma.clock(&mut bus, 1);
+    bus.signal = Some(DMASignal::HBlank);
+    dma.clock(&mut bus, 1);
+
+    assert_eq!(bus.b_writes, vec![(0x26, 0x55), (0x27, 0x66)]);
+    // The indirect address is stored in the count registers.
+    assert_eq!(dma.read(0x4345), 0x02);
+    assert_eq!(dma.read(0x4346), 0x90);
+}
+
+// HDMA only starts on the next frame after it is enabled.
+#[test]
+fn hdma_waits_for_frame() {
+    let mut dma = DMAController::new();
+    let mut bus = FakeBus::new();
+    bus.mem[0x8000..0x8002].copy_from_slice(&[0x01, 0x10]);
+    setup_channel(&mut dma, &mut bus, 0, &[0x00, 0x21, 0x00, 0x80, 0x00]);
+    dma.write(&mut bus, 0x420C, 0x01);
+
+    bus.signal = Some(DMASignal::HBlank);
+    dma.clock(&mut bus, 1);
+    assert!(bus.b_writes.is_empty());
+
+    bus.signal = Some(DMASignal::FrameStart);
+    dma.clock(&mut bus, 1);
+    bus.signal = Some(DMASignal::HBlank);
+    dma.clock(&mut bus, 1);
+    assert_eq!(bus.b_writes, vec![(0x21, 0x10)]);
+}
+
+#[cfg(feature = "debug")]
+#[test]
+fn hdma_states() {
+    let mut dma = DMAController::new();
+    let mut bus = FakeBus::new();
+    bus.mem[0x8000..0x8004].copy_from_slice(&[0x82, 0x10, 0x11, 0x00]);
+    setup_channel(&mut dma, &mut bus, 1, &[0x00, 0x21, 0x00, 0x80, 0x00]);
+    dma.write(&mut bus, 0x420C, 0x02);
+
+    bus.signal = Some(DMASignal::FrameStart);
+    dma.clock(&mut bus, 1);
+    for _ in 0..3 {
+        bus.signal = Some(DMASignal::HBlank);
+        dma.clock(&mut bus, 1);
+    }
+    // States are available once the frame is complete.
+    assert!(dma.hdma_states().is_empty());
+    bus.signal = Some(DMASignal::FrameStart);
+    dma.clock(&mut bus, 1);
+
+    let states = dma.hdma_states().iter().map(|line| line[1]).collect::<Vec<_>>();
+    assert_eq!(states.len(), 3);
+    assert_eq!((states[0].line_counter, states[0].repeat, states[0].table_addr), (1, true, 0x8002));
+    assert_eq!((states[1].line_counter, states[1].completed), (0, false));
+    assert!(states[2].enabled && states[2].completed);
+    assert!(!dma.hdma_states()[0][0].enabled);
+}
@@ -0,0 +1,12 @@
+// The expansion port on the bottom of the SNES.
+
+/// A device attached to the expansion port on the bottom of the SNES.
+/// The device is mapped into B-bus addresses $2184-$21FF, and can be accessed by the CPU or with DMA.
+pub trait ExpansionPort {
+    /// Read from the device. The address is the low byte of the B-bus address ($84-$FF).
+    /// Return None if the device doesn't drive the data bus: the read will return open bus.
+    fn read(&mut self, addr: u8) -> Option<u8>;
+
+    /// Write to the device. The address is the low byte of the B-bus address ($84-$FF).
+    fn write(&mut self, addr: u8, data: u8);
+}
@@ -0,0 +1,16 @@
+// The programmable I/O port ($4201 / $4213).
+
+/// A device wired to the programmable I/O port pins.
+/// On the SNES, pin 6 goes to controller port 1 and pin 7 goes to controller port 2.
+/// Pin 7 also latches the PPU H/V counters when it goes low, which is how light guns work.
+pub trait IOPort {
+    /// Called when the CPU writes to WRIO ($4201).
+    fn write(&mut self, _data: u8) {}
+
+    /// Return the level the device drives on each pin.
+    /// The pins are open collector: a pin only reads high if neither the CPU nor the device pulls it low.
+    /// This is checked whenever WRIO is written or RDIO ($4213) is read.
+    fn read(&mut self) -> u8 {
+        0xFF
+    }
+}
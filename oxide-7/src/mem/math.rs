@@ -0,0 +1,70 @@
+// Multiplication and division registers ($4202-$4206, $4214-$4217).
+use alloc::string::String;
+
+use crate::savestate::{
+    Stateful,
+    StateWriter,
+    StateReader
+};
+
+pub struct MathUnit {
+    mult_operand:   u8,
+    div_operand:    u16,
+    div_result:     u16,    // TODO: do these need to be timed properly?
+    mult_result:    u16,    // Also holds the remainder after division.
+}
+
+impl MathUnit {
+    pub fn new() -> Self {
+        MathUnit {
+            mult_operand:   0xFF,
+            div_operand:    0xFFFF,
+            div_result:     0,
+            mult_result:    0,
+        }
+    }
+
+    pub fn read(&self, addr: u16) -> u8 {
+        match addr {
+            0x4214 => lo!(self.div_result),
+            0x4215 => hi!(self.div_result),
+            0x4216 => lo!(self.mult_result),
+            0x4217 => hi!(self.mult_result),
+            _ => unreachable!(),
+        }
+    }
+
+    pub fn write(&mut self, addr: u16, data: u8) {
+        match addr {
+            0x4202 => self.mult_operand = data,
+            0x4203 => self.mult_result = (self.mult_operand as u16) * (data as u16),
+            0x4204 => self.div_operand = set_lo!(self.div_operand, data),
+            0x4205 => self.div_operand = set_hi!(self.div_operand, data),
+            0x4206 => if data == 0 {
+                self.div_result = 0xFFFF;
+                self.mult_result = 0xC;
+            } else {
+                let divisor = data as u16;
+                self.div_result = self.div_operand / divisor;
+                self.mult_result = self.div_operand % divisor;
+            },
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl Stateful for MathUnit {
+    fn save_state(&self, w: &mut StateWriter) {
+        w.write(&self.mult_operand);
+        w.write(&self.div_operand);
+        w.write(&self.div_result);
+        w.write(&self.mult_result);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), String> {
+        r.read_into(&mut self.mult_operand)?;
+        r.read_into(&mut self.div_operand)?;
+        r.read_into(&mut self.div_result)?;
+        r.read_into(&mut self.mult_result)
+    }
+}
@@ -1,9 +1,23 @@
 // Memory
 mod bus;
+mod busb;
 mod dma;
+mod expport;
+mod ioport;
+mod math;
 pub mod rom;
+mod wram;
+
+use alloc::vec::Vec;
 
 pub use bus::AddrBusA;
+pub use expport::ExpansionPort;
+pub use ioport::IOPort;
+
+use serde::{
+    Serialize,
+    Deserialize
+};
 
 use crate::common::Interrupt;
 
@@ -12,10 +26,18 @@ pub trait MemBus {
     fn read(&mut self, addr: u32) -> (u8, usize);
     fn write(&mut self, addr: u32, data: u8) -> usize;
     fn clock(&mut self, cycles: usize) -> Interrupt;
+
+    // Called with each opcode the CPU executes. Used to track test coverage.
+    #[cfg(test)]
+    fn opcode_hook(&mut self, _opcode: u8) {}
+
+    // Called with the address of each instruction before the CPU executes it.
+    #[cfg(feature = "debug")]
+    fn set_pc(&mut self, _pc: u32) {}
 }
 
 /// Random access memory.
-#[derive(Default)]
+#[derive(Default, Serialize, Deserialize)]
 pub struct RAM {
     data: Vec<u8>
 }
@@ -41,6 +63,14 @@ impl RAM {
             read_head: start
         }
     }
+
+    pub fn as_slice(&self) -> &[u8] {
+        &self.data
+    }
+
+    pub fn as_mut_slice(&mut self) -> &mut [u8] {
+        &mut self.data
+    }
 }
 
 /// An iterator over RAM.
@@ -0,0 +1,44 @@
+// Loading ROMs from inside zip archives.
+
+use std::io::{
+    Cursor,
+    Read
+};
+
+use zip::ZipArchive;
+
+const ZIP_MAGIC: &[u8; 4] = b"PK\x03\x04";
+
+// File extensions that are recognised as ROMs.
+const ROM_EXTENSIONS: [&str; 4] = ["sfc", "smc", "swc", "fig"];
+
+// Check if the data provided is a zip archive.
+pub fn is_zip(data: &[u8]) -> bool {
+    data.starts_with(ZIP_MAGIC)
+}
+
+// Extract the first ROM file found inside the archive.
+pub fn extract_rom(data: &[u8]) -> Result<Vec<u8>, String> {
+    let mut archive = ZipArchive::new(Cursor::new(data)).map_err(|e| e.to_string())?;
+
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i).map_err(|e| e.to_string())?;
+        if file.is_file() && is_rom_name(file.name()) {
+            let mut rom_data = Vec::with_capacity(file.size() as usize);
+            file.read_to_end(&mut rom_data).map_err(|e| e.to_string())?;
+            return Ok(rom_data);
+        }
+    }
+
+    Err("No ROM found in archive.".to_string())
+}
+
+fn is_rom_name(name: &str) -> bool {
+    match name.rfind('.') {
+        Some(i) => {
+            let ext = &name[(i + 1)..];
+            ROM_EXTENSIONS.iter().any(|rom_ext| ext.eq_ignore_ascii_case(rom_ext))
+        },
+        None => false
+    }
+}
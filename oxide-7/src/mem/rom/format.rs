@@ -0,0 +1,65 @@
+// Older copier formats: 512-byte copier headers and interleaved dumps.
+
+use alloc::vec::Vec;
+
+const COPIER_HEADER_SIZE: usize = 512;
+
+// Bytes 4 and 5 of the header in .fig files.
+const FIG_EMULATION_BYTES: [[u8; 2]; 8] = [
+    [0x77, 0x83], [0x47, 0x83], [0xF7, 0x83], [0xFD, 0x82],
+    [0xDD, 0x82], [0xDD, 0x02], [0x11, 0x02], [0x00, 0x80]
+];
+
+/// The format that the ROM file was stored in.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum ROMFormat {
+    /// No copier header (usually .sfc).
+    Plain,
+    /// Super Magicom / Super Wild Card header (usually .smc or .swc).
+    SMC,
+    /// Pro Fighter header (.fig).
+    FIG,
+    /// Unrecognised 512-byte copier header.
+    Copier,
+}
+
+// Remove the copier header if there is one, and return the format it indicates.
+// ROMs are a multiple of 1kB, so a header is present if there are 512 extra bytes.
+pub fn strip_copier_header(mut rom_data: Vec<u8>) -> (Vec<u8>, ROMFormat) {
+    if rom_data.len() % 1024 != COPIER_HEADER_SIZE {
+        return (rom_data, ROMFormat::Plain);
+    }
+
+    let format = {
+        let header = &rom_data[0..COPIER_HEADER_SIZE];
+        if header[8] == 0xAA && header[9] == 0xBB && header[10] == 0x04 {
+            ROMFormat::SMC
+        } else if FIG_EMULATION_BYTES.iter().any(|b| b[..] == header[4..6]) {
+            ROMFormat::FIG
+        } else {
+            ROMFormat::Copier
+        }
+    };
+
+    rom_data.drain(0..COPIER_HEADER_SIZE);
+    (rom_data, format)
+}
+
+// Un-shuffle an interleaved HiROM dump.
+// Interleaved dumps store the lower half of each 64kB bank in the second half of the file,
+// and the upper half of each bank in the first half of the file.
+pub fn deinterleave(rom_data: &[u8]) -> Vec<u8> {
+    const HALF_BANK: usize = 0x8000;
+
+    let num_banks = rom_data.len() / (HALF_BANK * 2);
+    let mut out = Vec::with_capacity(rom_data.len());
+    for bank in 0..num_banks {
+        let lo = (num_banks + bank) * HALF_BANK;
+        let hi = bank * HALF_BANK;
+        out.extend_from_slice(&rom_data[lo..(lo + HALF_BANK)]);
+        out.extend_from_slice(&rom_data[hi..(hi + HALF_BANK)]);
+    }
+    out.extend_from_slice(&rom_data[(num_banks * HALF_BANK * 2)..]);
+    out
+}
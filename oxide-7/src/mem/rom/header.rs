@@ -1,17 +1,12 @@
 // ROM header. Contains metadata about the ROM.
 
-use std::{
-    io::{
-        BufReader,
-        Read,
-        Seek,
-        SeekFrom
-    },
-    fs::File
-};
+use alloc::string::String;
+
+use crate::constants::machine::Region;
 
 const SA1_MAPPING_MASK: u8 = 0xEB;
 const ROM_MAPPING_MASK: u8 = 0xED;
+const SPC7110_MAPPING: u8 = 0x3A;
 
 pub struct ROMHeader {
     data: [u8; 50]
@@ -25,47 +20,100 @@ impl ROMHeader {
     }
 
     /// Set the header to the LOROM position, and check if it is a lorom header.
-    pub fn try_lo(&mut self, reader: &mut BufReader<File>) -> bool {
-        const LO_ROM_HEADER_START: u64 = 0x7FB0;
+    pub fn try_lo(&mut self, rom: &[u8]) -> bool {
+        const LO_ROM_HEADER_START: usize = 0x7FB0;
         const LO_ROM: u8 = 0x20;
         const LO_ROM_SA1: u8 = 0x23;
 
-        reader.seek(SeekFrom::Start(LO_ROM_HEADER_START)).expect("Couldn't seek to cartridge header.");
-        reader.read_exact(&mut self.data).expect("Couldn't read cartridge header.");
+        if !self.read_from(rom, LO_ROM_HEADER_START) {
+            return false;
+        }
 
         (self.rom_mapping() & ROM_MAPPING_MASK) == LO_ROM || 
         (self.rom_mapping() & SA1_MAPPING_MASK) == LO_ROM_SA1
     }
 
     /// Set the header to the EXHIROM position, and check if it is a exhirom header.
-    pub fn try_exhi(&mut self, reader: &mut BufReader<File>) -> bool {
+    pub fn try_exhi(&mut self, rom: &[u8]) -> bool {
         // TODO: also check 0x40FFB0
-        const EXHI_ROM_HEADER_START: u64 = 0xFFB0;
+        const EXHI_ROM_HEADER_START: usize = 0xFFB0;
         const EXHI_ROM: u8 = 0x25;
 
-        reader.seek(SeekFrom::Start(EXHI_ROM_HEADER_START)).expect("Couldn't seek to cartridge header.");
-        reader.read_exact(&mut self.data).expect("Couldn't read cartridge header.");
+        if !self.read_from(rom, EXHI_ROM_HEADER_START) {
+            return false;
+        }
 
         (self.rom_mapping() & ROM_MAPPING_MASK) == EXHI_ROM
     }
 
     /// Set the header to the HIROM position, and check if it is a hirom header.
-    pub fn try_hi(&mut self, reader: &mut BufReader<File>) -> bool {
-        const HI_ROM_HEADER_START: u64 = 0xFFB0;
+    pub fn try_hi(&mut self, rom: &[u8]) -> bool {
+        const HI_ROM_HEADER_START: usize = 0xFFB0;
         const HI_ROM: u8 = 0x21;
 
-        reader.seek(SeekFrom::Start(HI_ROM_HEADER_START)).expect("Couldn't seek to cartridge header.");
-        reader.read_exact(&mut self.data).expect("Couldn't read cartridge header.");
+        if !self.read_from(rom, HI_ROM_HEADER_START) {
+            return false;
+        }
+
+        (self.rom_mapping() & ROM_MAPPING_MASK) == HI_ROM
+    }
+
+    /// Check if the ROM is an interleaved HIROM dump.
+    /// In these, the HIROM header appears where the LOROM header should be.
+    pub fn try_interleaved_hi(&mut self, rom: &[u8]) -> bool {
+        const INTERLEAVED_HEADER_START: usize = 0x7FB0;
+        const HI_ROM: u8 = 0x21;
+
+        if !self.read_from(rom, INTERLEAVED_HEADER_START) {
+            return false;
+        }
 
         (self.rom_mapping() & ROM_MAPPING_MASK) == HI_ROM
     }
 
+    /// Set the header to the HIROM position, and check if it is an SPC7110 header.
+    /// These have their own mapping mode, which is only used with the SPC7110.
+    pub fn try_spc7110(&mut self, rom: &[u8]) -> bool {
+        const SPC7110_HEADER_START: usize = 0xFFB0;
+
+        if !self.read_from(rom, SPC7110_HEADER_START) {
+            return false;
+        }
+
+        self.enhancement_chip() == Some(EnhancementChip::SPC7110)
+    }
+
+    /// Look for a header with a mapping mode that isn't known, at the LOROM or HIROM position.
+    /// This tells carts that aren't supported apart from data that isn't a SNES ROM.
+    pub fn find_mapping(&mut self, rom: &[u8]) -> Option<u8> {
+        const MAPPING_MASK: u8 = 0xE0;
+        const MAPPING: u8 = 0x20;
+
+        for start in [0x7FB0, 0xFFB0].iter() {
+            if self.read_from(rom, *start) && ((self.rom_mapping() & MAPPING_MASK) == MAPPING) {
+                return Some(self.rom_mapping());
+            }
+        }
+        None
+    }
+
+    // Copy the header from the ROM at the position specified.
+    // Returns false if the ROM is too small.
+    fn read_from(&mut self, rom: &[u8], start: usize) -> bool {
+        if let Some(header) = rom.get(start..(start + self.data.len())) {
+            self.data.copy_from_slice(header);
+            true
+        } else {
+            false
+        }
+    }
+
     // Header metadata.
 
     /// Name of the game.
+    /// Any bytes that aren't valid UTF-8 are replaced.
     pub fn rom_name(&self) -> String {
-        use std::str::FromStr;
-        String::from_str(std::str::from_utf8(&self.data[0x10..0x25]).unwrap()).unwrap()
+        String::from_utf8_lossy(&self.data[0x10..0x25]).into_owned()
     }
 
     /// Mapping type.
@@ -83,18 +131,35 @@ impl ROMHeader {
         0x400 << self.data[0x27]
     }
 
+    /// Enhancement chip in the cart.
+    /// Custom chips are told apart by the subtype in the extended header.
+    /// The SPC7110 also has its own mapping mode.
+    pub fn enhancement_chip(&self) -> Option<EnhancementChip> {
+        const SPC7110_SUBTYPE: u8 = 0x00;
+        const CX4_SUBTYPE: u8 = 0x10;
+
+        match self.rom_type().enhancement_chip() {
+            Some(EnhancementChip::Custom) if self.data[0x0F] == SPC7110_SUBTYPE && self.rom_mapping() == SPC7110_MAPPING => Some(EnhancementChip::SPC7110),
+            Some(EnhancementChip::Custom) if self.data[0x0F] == CX4_SUBTYPE => Some(EnhancementChip::CX4),
+            chip => chip,
+        }
+    }
+
     /// SRAM size in bytes.
+    /// For SuperFX carts this is the size of the game pak RAM, if it is battery-backed.
     pub fn sram_size(&self) -> usize {
         if self.rom_type().enhancement_chip() == Some(EnhancementChip::SuperFX) {
-            if self.is_extended() {
+            if !self.rom_type().has_sram() {
+                0
+            } else if self.is_extended() {
                 let expansion_ram_size = 0x400 << self.data[0x0D];
-                std::cmp::max(expansion_ram_size, 1024 * 32)
+                core::cmp::max(expansion_ram_size, 1024 * 32)
             } else {
                 1024 * 32
             }
         } else if self.rom_type().has_sram() {
             let indicated_size = 0x400 << self.data[0x28];
-            std::cmp::min(indicated_size, 1024 * 512)
+            core::cmp::min(indicated_size, 1024 * 512)
         } else {
             0
         }
@@ -109,14 +174,56 @@ impl ROMHeader {
     pub fn is_extended(&self) -> bool {
         self.data[0x2A] == 0x33
     }
+
+    /// The console region the cart was made for, from the destination code.
+    /// Europe, Australia and parts of Asia are PAL.
+    pub fn region(&self) -> Region {
+        match self.data[0x29] {
+            0x02..=0x0C | 0x11 => Region::PAL,
+            _ => Region::NTSC,
+        }
+    }
+
+    /// Checksum of the ROM, as written in the header.
+    pub fn checksum(&self) -> u16 {
+        make16!(self.data[0x2F], self.data[0x2E])
+    }
+}
+
+/// Calculate the actual checksum of the ROM.
+/// This is the sum of all bytes. If the ROM size is not a power of two,
+/// the remainder is mirrored to fill the space up to the next power of two.
+pub fn calc_checksum(rom: &[u8]) -> u16 {
+    mirrored_sum(rom) as u16
+}
+
+fn mirrored_sum(data: &[u8]) -> u32 {
+    if data.is_empty() {
+        return 0;
+    }
+
+    let sum = |d: &[u8]| d.iter().fold(0_u32, |acc, b| acc.wrapping_add(*b as u32));
+    if data.len().is_power_of_two() {
+        sum(data)
+    } else {
+        let base_size = data.len().next_power_of_two() / 2;
+        let rest = &data[base_size..];
+        let mirrors = base_size / rest.len().next_power_of_two();
+        sum(&data[..base_size]).wrapping_add(mirrored_sum(rest).wrapping_mul(mirrors as u32))
+    }
 }
 
-#[derive(Debug, PartialEq)]
+/// Enhancement chips found in cartridges.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum EnhancementChip {
     DSP,
     SuperFX,
     OBC1,
     SA1,
+    SDD1,
+    SPC7110,
+    CX4,
     Other,
     Custom,
     Unknown
@@ -136,9 +243,16 @@ impl From<u8> for ROMType {
 }
 
 impl ROMType {
+    // True if the cart has battery-backed RAM.
     pub fn has_sram(self) -> bool {
         let lower_nybble = lo_nybble!(self.rom_type);
-        lower_nybble == 2 || lower_nybble == 5 || lower_nybble == 6
+        // $1A is used by some SuperFX carts with a battery.
+        lower_nybble == 2 || lower_nybble == 5 || lower_nybble == 6 || lower_nybble == 9 || self.rom_type == 0x1A
+    }
+
+    // True if the cart has a real-time clock as well as battery-backed RAM.
+    pub fn has_rtc(self) -> bool {
+        lo_nybble!(self.rom_type) == 9
     }
 
     pub fn enhancement_chip(self) -> Option<EnhancementChip> {
@@ -149,6 +263,7 @@ impl ROMType {
                 0x1 => SuperFX,
                 0x2 => OBC1,
                 0x3 => SA1,
+                0x4 => SDD1,
                 0xE => Other,
                 0xF => Custom,
                 _ => Unknown
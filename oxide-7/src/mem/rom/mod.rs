@@ -1,26 +1,62 @@
 // ROM types
+#[cfg(feature = "archive")]
+mod archive;
+mod format;
 mod header;
+mod patch;
 mod sram;
+#[cfg(test)]
+mod tests;
 
+use alloc::{
+    boxed::Box,
+    string::String,
+    vec::Vec
+};
+#[cfg(feature = "std")]
 use std::{
     io::{
         BufReader,
-        Read,
-        Seek,
-        SeekFrom
+        Read
     },
-    fs::File
+    fs::File,
+    path::{
+        Path,
+        PathBuf
+    }
 };
 
 use crate::{
+    Error,
     common::Interrupt,
-    constants::timing,
-    expansion::*
+    constants::{
+        machine::{
+            MachineTiming,
+            Region
+        },
+        timing
+    },
+    events::{
+        EventBus,
+        UnsupportedFeature
+    },
+    expansion::*,
+    savestate::{
+        Stateful,
+        StateWriter,
+        StateReader
+    }
 };
+#[cfg(feature = "debug")]
+use crate::events::Event;
 
+use format::*;
 use header::*;
+use patch::*;
 use sram::*;
 pub use sram::SRAM;
+pub use format::ROMFormat;
+pub use header::EnhancementChip;
 
 const LOROM_LARGE_SIZE: usize = 1 << 21;
 const LOROM_RAM_BANK_SIZE: u32 = 0x8000;
@@ -28,87 +64,300 @@ const HIROM_RAM_BANK_SIZE: u32 = 0x2000;
 
 const SPEED_BIT: u8 = 0;
 
-pub fn create_cart(cart_path: &str, save_path: &str, dsp_path: Option<&str>) -> Box<Cart> {
-    let rom_file = File::open(cart_path).expect(&format!("Couldn't open file {}", cart_path));
+// With soft patching, a .bps or .ips file next to the cart with the same name is applied to the ROM.
+#[cfg(feature = "std")]
+pub fn create_cart(cart_path: &str, save_path: &str, dsp_path: Option<&str>, soft_patch: bool, provider: Option<&mut dyn ExpansionProvider>, events: EventBus) -> Result<Box<Cart>, Error> {
+    let rom_file = File::open(cart_path).map_err(|e| file_error(cart_path, e))?;
     //let rom_size = rom_file.metadata().expect("Couldn't get metadata for file.").len();
 
     let mut reader = BufReader::new(rom_file);
+    let mut rom_data = Vec::new();
+    reader.read_to_end(&mut rom_data).map_err(|e| file_error(cart_path, e))?;
+
+    let patch = if soft_patch {find_soft_patch(cart_path)} else {None};
+    create_cart_from_data(rom_data, patch.as_deref(), save_path, dsp_path, provider, events)
+}
+
+// BPS patches are preferred, as they are checked against the ROM.
+#[cfg(feature = "std")]
+fn find_soft_patch(cart_path: &str) -> Option<Vec<u8>> {
+    ["bps", "ips"].iter()
+        .map(|ext| Path::new(cart_path).with_extension(ext))
+        .find_map(|patch_path| std::fs::read(patch_path).ok())
+}
+
+#[cfg(feature = "std")]
+fn file_error(path: &str, error: std::io::Error) -> Error {
+    Error::File {
+        path:   String::from(path),
+        reason: error.to_string(),
+    }
+}
+
+// The ROM data can be a zip archive if the archive feature is enabled.
+// Copier headers are removed, and interleaved ROMs are restored to their normal layout.
+// The patch is applied after the copier header is removed. If it can't be applied, the ROM is loaded unpatched.
+// If the provider supports the cart, its chip is used in place of the built-in one.
+pub fn create_cart_from_data(rom_data: Vec<u8>, patch: Option<&[u8]>, save_path: &str, dsp_path: Option<&str>, provider: Option<&mut dyn ExpansionProvider>, events: EventBus) -> Result<Box<Cart>, Error> {
+    #[cfg(feature = "archive")]
+    let rom_data = if archive::is_zip(&rom_data) {
+        archive::extract_rom(&rom_data).map_err(Error::Archive)?
+    } else {
+        rom_data
+    };
+
+    let (rom_data, format) = strip_copier_header(rom_data);
+
+    let (rom_data, patched, patch_error) = match patch.map(|patch| apply_patch(&rom_data, patch)) {
+        Some(Ok(patched_data)) => (patched_data, true, None),
+        Some(Err(e)) => (rom_data, false, Some(e)),
+        None => (rom_data, false, None),
+    };
+
     let mut header = ROMHeader::new();
 
-    let cart = if header.try_lo(&mut reader) {
-        let sram = create_sram(save_path, header.sram_size()).expect("Couldn't make save file.");
+    let interleaved = !header.try_lo(&rom_data) &&
+        !header.try_exhi(&rom_data) &&
+        !header.try_hi(&rom_data) &&
+        !header.try_spc7110(&rom_data) &&
+        header.try_interleaved_hi(&rom_data);
+    let rom_data = if interleaved {
+        deinterleave(&rom_data)
+    } else {
+        rom_data
+    };
+    let rom_size = rom_data.len();
+    let checksum = calc_checksum(&rom_data);
+
+    let cart = if header.try_lo(&rom_data) {
+        let sram = create_sram(save_path, header.sram_size()).map_err(|e| save_error(save_path, e))?;
         let name = header.rom_name();
 
         if header.rom_size() > LOROM_LARGE_SIZE {
             println!("LOROM Large {:X}: {}", header.rom_mapping(), name);
-            Cart::new_lorom_large(reader, sram)
+            Cart::new_lorom_large(rom_data, sram)
         } else {
             println!("LOROM {:X}: {}", header.rom_mapping(), name);
-            Cart::new_lorom(reader, sram)
-        }.named(name)
-            .fast_rom(header.fast_rom())
+            Cart::new_lorom(rom_data, sram)
+        }.fast_rom(header.fast_rom())
 
-    } else if header.try_exhi(&mut reader) {
-        let sram = create_sram(save_path, header.sram_size()).expect("Couldn't make save file.");
+    } else if header.try_exhi(&rom_data) {
+        let sram = create_sram(save_path, header.sram_size()).map_err(|e| save_error(save_path, e))?;
         let name = header.rom_name();
 
         println!("EXHIROM {:X}: {}", header.rom_mapping(), name);
-        Cart::new_exhirom(reader, sram)
-            .named(name)
+        Cart::new_exhirom(rom_data, sram)
             .fast_rom(header.fast_rom())
 
-    } else if header.try_hi(&mut reader) {
-        let sram = create_sram(save_path, header.sram_size()).expect("Couldn't make save file.");
+    } else if header.try_hi(&rom_data) {
+        let sram = create_sram(save_path, header.sram_size()).map_err(|e| save_error(save_path, e))?;
         let name = header.rom_name();
 
         println!("HIROM {:X}: {}", header.rom_mapping(), name);
-        Cart::new_hirom(reader, sram)
-            .named(name)
+        Cart::new_hirom(rom_data, sram)
+            .fast_rom(header.fast_rom())
+
+    } else if header.try_spc7110(&rom_data) {
+        let sram = create_sram(save_path, header.sram_size()).map_err(|e| save_error(save_path, e))?;
+        let name = header.rom_name();
+
+        println!("SPC7110 {:X}: {}", header.rom_mapping(), name);
+        Cart::new_hirom(rom_data, sram)
             .fast_rom(header.fast_rom())
 
+    } else if let Some(mapping) = header.find_mapping(&rom_data) {
+        return Err(Error::UnsupportedMapper(mapping));
     } else {
-        panic!("Unrecognised ROM: {:X}", header.rom_mapping());
+        return Err(Error::BadHeader);
+    };
+
+    let mut info = CartInfo {
+        name:           header.rom_name(),
+        chip:           header.enhancement_chip(),
+        format:         format,
+        interleaved:    interleaved,
+        rom_size:       rom_size,
+        sram_size:      header.sram_size(),
+        region:         header.region(),
+        header_checksum:    header.checksum(),
+        checksum:           checksum,
+        patched:            patched,
+        patch_error:        patch_error,
+        dsp_rom:            None,
     };
 
-    let cart_with_ext = match header.rom_type().enhancement_chip() {
-        Some(EnhancementChip::DSP) => {
-            let dsp_path = dsp_path.expect("Must specify DSP ROM path!");
-            let dsp_rom_file = File::open(dsp_path).expect(&format!("Couldn't open DSP ROM file {}", dsp_path));
-            let mut dsp_reader = BufReader::new(dsp_rom_file);
-            let mut buffer = vec![0; 0x2000];
-            dsp_reader.read_exact(&mut buffer).expect("Couldn't read into DSP ROM");
-            cart.with_dsp(Box::new(DSP::new(&buffer)))
+    let cart_with_ext = match provider {
+        Some(provider) if provider.provides(&info) => cart.with_custom(provider, &info),
+        _ => match header.enhancement_chip() {
+            Some(EnhancementChip::DSP) => match read_dsp_rom(dsp_path, &info.name)? {
+                Some((dsp_rom_path, dsp_rom)) => {
+                    info.dsp_rom = Some(dsp_rom_path);
+                    cart.with_dsp(Box::new(DSP::new(&dsp_rom)))
+                },
+                None => cart.with_unsupported(EnhancementChip::DSP)
+            },
+            Some(EnhancementChip::SA1) => cart.with_sa1(),
+            Some(EnhancementChip::SuperFX) => cart.with_superfx(header.rom_type().has_sram()),
+            Some(EnhancementChip::SDD1) => cart.with_sdd1(),
+            Some(EnhancementChip::SPC7110) => {
+                let rtc = if header.rom_type().has_rtc() {
+                    Some(RealTimeClock::new(save_path).map_err(|e| save_error(save_path, e))?)
+                } else {
+                    None
+                };
+                cart.with_spc7110(rtc)
+            },
+            Some(EnhancementChip::CX4) => cart.with_cx4(),
+            Some(e) => cart.with_unsupported(e),
+            None => cart,
         },
-        Some(EnhancementChip::SA1) => cart.with_sa1(),
-        Some(EnhancementChip::SuperFX) => cart.with_superfx(),
-        Some(e) => panic!("Unsupported enhancement chip {:?}", e),
-        None => cart,
     };
 
-    cart_with_ext.build()
+    Ok(cart_with_ext.with_events(events).with_info(info).build())
+}
+
+fn save_error(save_path: &str, reason: String) -> Error {
+    Error::File {
+        path:   String::from(save_path),
+        reason: reason,
+    }
+}
+
+// The DSP program and data ROM is dumped separately from the cart.
+// The path can be a single ROM, or a directory of ROMs where the one for the game is picked by name.
+// Returns the path of the ROM that was loaded, along with the data.
+#[cfg(feature = "std")]
+fn read_dsp_rom(dsp_path: Option<&str>, game_name: &str) -> Result<Option<(String, Vec<u8>)>, Error> {
+    let dsp_path = dsp_path.ok_or(Error::NoDSPROM)?;
+    let dsp_path = if Path::new(dsp_path).is_dir() {
+        match find_dsp_rom(Path::new(dsp_path), game_name) {
+            Some(path) => path,
+            None => return Err(Error::DSPROMNotFound {
+                dir:        String::from(dsp_path),
+                expected:   dsp_rom_names(game_name).iter().map(|name| String::from(*name)).collect(),
+            }),
+        }
+    } else {
+        PathBuf::from(dsp_path)
+    };
+    let dsp_path = dsp_path.to_string_lossy().into_owned();
+
+    let dsp_rom_file = File::open(&dsp_path).map_err(|e| file_error(&dsp_path, e))?;
+    let mut dsp_reader = BufReader::new(dsp_rom_file);
+    let mut buffer = vec![0; 0x2000];
+    dsp_reader.read_exact(&mut buffer).map_err(|e| file_error(&dsp_path, e))?;
+    Ok(Some((dsp_path, buffer)))
+}
+
+// Without a file system the DSP ROM can't be loaded, so the chip is treated as unsupported.
+#[cfg(not(feature = "std"))]
+fn read_dsp_rom(_dsp_path: Option<&str>, _game_name: &str) -> Result<Option<(String, Vec<u8>)>, Error> {
+    Ok(None)
+}
+
+// Look for a ROM named after the DSP the game uses, for example dsp1b.rom or dsp2.bin.
+#[cfg(feature = "std")]
+fn find_dsp_rom(dir: &Path, game_name: &str) -> Option<PathBuf> {
+    dsp_rom_names(game_name).iter()
+        .flat_map(|name| ["rom", "bin"].iter().map(move |ext| dir.join(name).with_extension(ext)))
+        .find(|path| path.is_file())
+}
+
+// The cart header doesn't say which DSP is inside, so it is picked from the name of the game.
+// Most DSP-1 games run on either revision, but Pilotwings needs the original DSP-1.
+// Names are in order of preference.
+#[cfg(feature = "std")]
+fn dsp_rom_names(game_name: &str) -> &'static [&'static str] {
+    let name = game_name.trim_end();
+    match name {
+        "DUNGEON MASTER" => &["dsp2"],
+        "TOP GEAR 3000" | "PLANETS CHAMP TG3000" => &["dsp4"],
+        "PILOTWINGS" => &["dsp1", "dsp1b"],
+        // SD Gundam GX: the middle of the name is in katakana.
+        _ if name.starts_with("SD") && name.ends_with("GX") => &["dsp3"],
+        _ => &["dsp1b", "dsp1"],
+    }
+}
+
+/// Information about the cartridge, and the file it was loaded from.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct CartInfo {
+    /// Name of the game, from the ROM header.
+    pub name:           String,
+    /// The enhancement chip in the cartridge, if any.
+    pub chip:           Option<EnhancementChip>,
+    /// The copier format detected when loading.
+    pub format:         ROMFormat,
+    /// True if the ROM was stored in interleaved format.
+    pub interleaved:    bool,
+    /// ROM size in bytes, without any copier header.
+    pub rom_size:       usize,
+    /// SRAM size in bytes.
+    pub sram_size:      usize,
+    /// The console region the cart was made for.
+    pub region:         Region,
+    /// Checksum written in the ROM header.
+    pub header_checksum:    u16,
+    /// Checksum calculated from the ROM data.
+    /// If this doesn't match header_checksum, the ROM may be a bad dump or modified.
+    pub checksum:           u16,
+    /// True if a soft patch was applied to the ROM.
+    pub patched:            bool,
+    /// Why the soft patch couldn't be applied, if there was one.
+    pub patch_error:        Option<String>,
+    /// Path of the DSP ROM that was loaded, if the cart has a DSP.
+    pub dsp_rom:            Option<String>,
+}
+
+// The ROM and mappings are fixed, so only the RAM, expansion chip and speed are stored.
+impl Stateful for Cart {
+    fn save_state(&self, w: &mut StateWriter) {
+        self.ram.save_state(w);
+        if let Some(e) = self.expansion.as_ref() {
+            e.save_state(w);
+        }
+        w.write(&self.rom_speed);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), String> {
+        self.ram.load_state(r)?;
+        if let Some(e) = self.expansion.as_mut() {
+            e.load_state(r)?;
+        }
+        r.read_into(&mut self.rom_speed)
+    }
 }
 
-// ROM.
+/// Cartridge ROM, split into banks.
 pub struct ROM {
     data:       Vec<u8>,
     bank_size:  usize
 }
 
 impl ROM {
-    fn new(mut cart_file: BufReader<File>, bank_size: usize) -> Self {
-        // read and store
-        let mut buffer = Vec::new();
-        cart_file.seek(SeekFrom::Start(0)).expect("couldn't seek in file");
-        cart_file.read_to_end(&mut buffer).expect("couldn't read file");
+    /// Create ROM from the raw data. The bank size is 0x8000 for LoROM and 0x10000 for HiROM.
+    pub fn new(data: Vec<u8>, bank_size: usize) -> Self {
         ROM {
-            data:       buffer,
+            data:       data,
             bank_size:  bank_size
         }
     }
 
+    /// Read a byte. The bank and address are offsets into the ROM, not CPU addresses.
+    /// Reads past the end of the ROM are mirrored.
     pub fn read(&mut self, bank: u8, addr: u16) -> u8 {
-        let bank_offset = (bank as usize) * self.bank_size;
-        self.data[bank_offset + (addr as usize)]
+        let offset = (bank as usize) * self.bank_size + (addr as usize);
+        if offset < self.data.len() {
+            self.data[offset]
+        } else {
+            self.data[offset % self.data.len()]
+        }
+    }
+
+    /// The raw ROM data.
+    pub fn data(&self) -> &[u8] {
+        &self.data
     }
 }
 
@@ -124,7 +373,11 @@ enum CartMappingMode {
     Hi,
     ExHi,
     SA,
-    SuperFX
+    SuperFX,
+    SDD1,
+    SPC7110,
+    CX4,
+    Custom
 }
 
 type CartMappingFn = fn(u8, u16) -> CartDevice;
@@ -148,6 +401,12 @@ impl CartMapping {
     }
 }
 
+// 8kB of SRAM is visible at $6000-$7FFF in each bank. Smaller SRAM is mirrored.
+fn hirom_sram(bank: u8, addr: u16) -> CartDevice {
+    let ram_bank = ((bank % 0x10) as u32) * HIROM_RAM_BANK_SIZE;
+    CartDevice::RAM(ram_bank + (addr as u32 - 0x6000))
+}
+
 struct CartBuilder {
     mappings:       Vec<CartMapping>,
     mapping_mode:   CartMappingMode,
@@ -155,10 +414,13 @@ struct CartBuilder {
     rom:            Option<ROM>,
     ram:            Option<Box<dyn SRAM>>,
     expansion:      Option<Box<dyn Expansion>>,
+    io_regs:        bool,
 
     fast_rom:       bool,
 
-    name:           String
+    info:           Option<CartInfo>,
+    events:         EventBus,
+    unsupported:    Option<EnhancementChip>,
 }
 
 impl CartBuilder {
@@ -170,15 +432,29 @@ impl CartBuilder {
             rom:            None,
             ram:            None,
             expansion:      None,
+            io_regs:        false,
 
             fast_rom:       false,
 
-            name:           String::new()
+            info:           None,
+            events:         EventBus::default(),
+            unsupported:    None,
         }
     }
 
-    fn named(mut self, name: String) -> Self {
-        self.name = name;
+    fn with_info(mut self, info: CartInfo) -> Self {
+        self.info = Some(info);
+        self
+    }
+
+    fn with_events(mut self, events: EventBus) -> Self {
+        self.events = events;
+        self
+    }
+
+    // The cart has a chip that isn't emulated. Accesses to it will be reported.
+    fn with_unsupported(mut self, chip: EnhancementChip) -> Self {
+        self.unsupported = Some(chip);
         self
     }
 
@@ -222,8 +498,9 @@ impl CartBuilder {
         self
     }
 
-    fn with_superfx(mut self) -> Self {
-        let super_fx = Box::new(SuperFX::new(self.rom.take().unwrap(), self.ram.take().unwrap()));
+    // If the cart has a battery, the game pak RAM is stored in the save file.
+    fn with_superfx(mut self, battery: bool) -> Self {
+        let super_fx = Box::new(SuperFX::new(self.rom.take().unwrap(), self.ram.take().unwrap(), battery));
         self.expansion = Some(super_fx);
 
         self.mapping_mode = CartMappingMode::SuperFX;
@@ -231,6 +508,49 @@ impl CartBuilder {
         self
     }
 
+    // The chip has registers at $4800, and decompresses ROM reads during DMA.
+    fn with_sdd1(mut self) -> Self {
+        let sdd1 = Box::new(SDD1::new(self.rom.take().unwrap()));
+        self.expansion = Some(sdd1);
+        self.io_regs = true;
+
+        self.mapping_mode = CartMappingMode::SDD1;
+
+        self
+    }
+
+    // The chip has registers at $4800, and maps the data ROM and SRAM.
+    // The real-time clock is saved next to the save file.
+    fn with_spc7110(mut self, rtc: Option<RealTimeClock>) -> Self {
+        let spc7110 = Box::new(SPC7110::new(self.rom.take().unwrap(), self.ram.take().unwrap(), rtc));
+        self.expansion = Some(spc7110);
+        self.io_regs = true;
+
+        self.mapping_mode = CartMappingMode::SPC7110;
+
+        self
+    }
+
+    // The chip reads from ROM, so all ROM accesses go through it.
+    fn with_cx4(mut self) -> Self {
+        let cx4 = Box::new(CX4::new(self.rom.take().unwrap()));
+        self.expansion = Some(cx4);
+
+        self.mapping_mode = CartMappingMode::CX4;
+
+        self
+    }
+
+    // The chip is created outside the crate, and handles all cart accesses.
+    fn with_custom(mut self, provider: &mut dyn ExpansionProvider, info: &CartInfo) -> Self {
+        let chip = provider.create(info, self.rom.take().unwrap(), self.ram.take().unwrap());
+        self.expansion = Some(chip);
+
+        self.mapping_mode = CartMappingMode::Custom;
+
+        self
+    }
+
     fn build(mut self) -> Box<Cart> {
         use CartMappingMode::*;
         // Map ROM
@@ -270,6 +590,13 @@ impl CartBuilder {
                 self.mappings.push(CartMapping::new(0x40, 0x6F, 0, |bank, addr| CartDevice::Expansion(bank, addr)));
                 self.mappings.push(CartMapping::new(0xC0, 0xFF, 0, |bank, addr| CartDevice::Expansion(bank, addr)));
             },
+            Custom => {
+                self.mappings.push(CartMapping::new(0x00, 0x3F, 0x2200, CartDevice::Expansion));
+                self.mappings.push(CartMapping::new(0x80, 0xBF, 0x2200, CartDevice::Expansion));
+
+                self.mappings.push(CartMapping::new(0x40, 0x7F, 0, CartDevice::Expansion));
+                self.mappings.push(CartMapping::new(0xC0, 0xFF, 0, CartDevice::Expansion));
+            },
             SuperFX => {
                 self.mappings.push(CartMapping::new(0x00, 0x3F, 0x3000, |bank, addr| CartDevice::Expansion(bank, addr)));
                 self.mappings.push(CartMapping::new(0x80, 0xBF, 0x3000, |bank, addr| CartDevice::Expansion(bank, addr)));
@@ -277,21 +604,41 @@ impl CartBuilder {
                 self.mappings.push(CartMapping::new(0x40, 0x5F, 0, |bank, addr| CartDevice::Expansion(bank, addr)));
                 self.mappings.push(CartMapping::new(0xC0, 0xDF, 0, |bank, addr| CartDevice::Expansion(bank, addr)));
             },
+            SDD1 => {
+                self.mappings.push(CartMapping::new(0x00, 0x3F, 0x8000, CartDevice::Expansion));
+                self.mappings.push(CartMapping::new(0x80, 0xBF, 0x8000, CartDevice::Expansion));
+
+                self.mappings.push(CartMapping::new(0xC0, 0xFF, 0, CartDevice::Expansion));
+            },
+            SPC7110 => {
+                self.mappings.push(CartMapping::new(0x00, 0x3F, 0x6000, CartDevice::Expansion));
+                self.mappings.push(CartMapping::new(0x80, 0xBF, 0x6000, CartDevice::Expansion));
+
+                self.mappings.push(CartMapping::new(0x50, 0x50, 0, CartDevice::Expansion));
+                self.mappings.push(CartMapping::new(0x58, 0x58, 0, CartDevice::Expansion));
+                self.mappings.push(CartMapping::new(0xC0, 0xFF, 0, CartDevice::Expansion));
+            },
+            CX4 => {
+                self.mappings.push(CartMapping::new(0x00, 0x3F, 0x6000, CartDevice::Expansion));
+                self.mappings.push(CartMapping::new(0x80, 0xBF, 0x6000, CartDevice::Expansion));
+
+                self.mappings.push(CartMapping::new(0x40, 0x6F, 0, CartDevice::Expansion));
+                self.mappings.push(CartMapping::new(0xC0, 0xFF, 0, CartDevice::Expansion));
+            },
         }
 
         // SRAM
         match self.mapping_mode {
-            Lo | LoLarge => {
+            Lo | LoLarge | SDD1 => {
                 self.mappings.push(CartMapping::new(0x70, 0x7F, 0, |bank, addr| {
                     let ram_bank = ((bank - 0x70) as u32) * LOROM_RAM_BANK_SIZE;
                     CartDevice::RAM(ram_bank + addr as u32)
                 }));
             },
             Hi | ExHi => {
-                self.mappings.push(CartMapping::new(0x20, 0x3F, 0x6000, |bank, addr| {
-                    let ram_bank = ((bank % 0x10) as u32) * HIROM_RAM_BANK_SIZE;
-                    CartDevice::RAM(ram_bank + (addr as u32 - 0x6000))
-                }));
+                // Writes are mapped with the top bank bit cleared, but reads need the upper mirror too.
+                self.mappings.push(CartMapping::new(0x20, 0x3F, 0x6000, hirom_sram));
+                self.mappings.push(CartMapping::new(0xA0, 0xBF, 0x6000, hirom_sram));
             },
             SuperFX => {
                 self.mappings.push(CartMapping::new(0x60, 0x7F, 0, |bank, addr| CartDevice::Expansion(bank, addr)));
@@ -300,16 +647,28 @@ impl CartBuilder {
             _ => {}
         }
 
+        if let Some(e) = self.expansion.as_mut() {
+            e.set_event_bus(self.events.clone());
+        }
+
         Box::new(Cart {
             mappings:   self.mappings,
             rom:        self.rom,
             ram:        self.ram.unwrap_or(Box::new(EmptySRAM::new())),
             expansion:  self.expansion,
+            io_regs:    self.io_regs,
 
             fast_rom:   self.fast_rom,
             rom_speed:  timing::SLOW_MEM_ACCESS,
+            slow_rom_access:    timing::SLOW_MEM_ACCESS,
+            fast_rom_access:    timing::FAST_MEM_ACCESS,
 
-            name:       self.name,
+            info:       self.info.expect("Cart info must be set"),
+            events:     self.events,
+            unsupported:    self.unsupported,
+
+            #[cfg(feature = "debug")]
+            pc:         0,
         })
     }
 }
@@ -320,41 +679,49 @@ pub struct Cart {
     rom:        Option<ROM>,
     ram:        Box<dyn SRAM>,
     expansion:  Option<Box<dyn Expansion>>,
+    io_regs:    bool,   // The chip has registers at $4800-$48FF.
 
     fast_rom:   bool,
     rom_speed:  usize,
+    slow_rom_access:    usize,
+    fast_rom_access:    usize,
+
+    info:       CartInfo,
+    events:     EventBus,
+    unsupported:    Option<EnhancementChip>,    // Set until the first access to an unsupported chip.
 
-    name:       String
+    #[cfg(feature = "debug")]
+    pc:         u32,    // Address of the instruction currently executing, for reporting.
 }
 
 impl Cart {
-    fn new_lorom(cart_file: BufReader<File>, ram: Box<dyn SRAM>) -> CartBuilder {
+    fn new_lorom(rom_data: Vec<u8>, ram: Box<dyn SRAM>) -> CartBuilder {
         let mut builder = CartBuilder::new(CartMappingMode::Lo);
-        builder.rom = Some(ROM::new(cart_file, 0x8000));
+        builder.rom = Some(ROM::new(rom_data, 0x8000));
         builder.ram = Some(ram);
 
         builder
     }
 
-    fn new_lorom_large(cart_file: BufReader<File>, ram: Box<dyn SRAM>) -> CartBuilder {
+    fn new_lorom_large(rom_data: Vec<u8>, ram: Box<dyn SRAM>) -> CartBuilder {
         let mut builder = CartBuilder::new(CartMappingMode::LoLarge);
-        builder.rom = Some(ROM::new(cart_file, 0x8000));
+        builder.rom = Some(ROM::new(rom_data, 0x8000));
         builder.ram = Some(ram);
 
         builder
     }
 
-    fn new_hirom(cart_file: BufReader<File>, ram: Box<dyn SRAM>) -> CartBuilder {
+    fn new_hirom(rom_data: Vec<u8>, ram: Box<dyn SRAM>) -> CartBuilder {
         let mut builder = CartBuilder::new(CartMappingMode::Hi);
-        builder.rom = Some(ROM::new(cart_file, 0x10000));
+        builder.rom = Some(ROM::new(rom_data, 0x10000));
         builder.ram = Some(ram);
 
         builder
     }
 
-    fn new_exhirom(cart_file: BufReader<File>, ram: Box<dyn SRAM>) -> CartBuilder {
+    fn new_exhirom(rom_data: Vec<u8>, ram: Box<dyn SRAM>) -> CartBuilder {
         let mut builder = CartBuilder::new(CartMappingMode::ExHi);
-        builder.rom = Some(ROM::new(cart_file, 0x10000));
+        builder.rom = Some(ROM::new(rom_data, 0x10000));
         builder.ram = Some(ram);
 
         builder
@@ -379,57 +746,118 @@ impl Cart {
             }
         }
 
+        self.unsupported_access();
         (0, timing::SLOW_MEM_ACCESS)
     }
 
     pub fn write(&mut self, bank: u8, addr: u16, data: u8) -> usize {
         let internal_bank = bank % 0x80;
+        let mut mapped = false;
 
         for mapping in self.mappings.iter() {
             if (internal_bank >= mapping.start_bank) &&
                 (internal_bank <= mapping.end_bank) &&
                 (addr >= mapping.start_addr) {
                 match (mapping.addr_mapping)(internal_bank, addr) {
-                    CartDevice::ROM(_,_) => {},
+                    CartDevice::ROM(_,_) => if !mapped {
+                        self.rom_written(bank, addr, data);
+                    },
                     CartDevice::RAM(addr) => self.ram.write(addr, data),
                     CartDevice::Expansion(bank, addr) => {
                         //println!("Writing {:X} to {:X}", data, addr);
                         self.expansion.as_mut().map_or((), |e| e.write(bank, addr, data));
                     },
                 }
+                mapped = true;
             }
         }
 
+        if !mapped {
+            self.unsupported_access();
+        }
         timing::SLOW_MEM_ACCESS
     }
 
     // Read from expansion port slot.
     pub fn read_exp(&mut self, addr: u16) -> u8 {
+        self.unsupported_access();
         self.expansion.as_mut().map_or(0, |e| e.read(0, addr))
     }
 
     // Write to expansion port slot.
     pub fn write_exp(&mut self, addr: u16, data: u8) {
+        self.unsupported_access();
         self.expansion.as_mut().map_or((), |e| e.write(0, addr, data));
     }
 
-    pub fn flush(&mut self) {
-        self.ram.flush();
+    // Read from chip registers at $4800-$48FF. Returns None if the cart doesn't have any.
+    pub fn read_io(&mut self, addr: u16) -> Option<u8> {
+        if self.io_regs {
+            self.expansion.as_mut().map(|e| e.read(0, addr))
+        } else {
+            None
+        }
+    }
+
+    // Write to chip registers at $4800-$48FF.
+    pub fn write_io(&mut self, addr: u16, data: u8) {
+        match self.expansion.as_mut() {
+            Some(e) if self.io_regs => e.write(0, addr, data),
+            _ => {}
+        }
+    }
+
+    // Let the chip see the DMA channel setup.
+    pub fn write_dma_reg(&mut self, addr: u16, data: u8) {
         if let Some(e) = self.expansion.as_mut() {
-            e.flush();
+            e.dma_reg_write(addr, data);
         }
     }
 
+    // Returns true if any save data was written.
+    pub fn flush(&mut self) -> Result<bool, String> {
+        let ram_written = self.ram.flush()?;
+        let exp_written = if let Some(e) = self.expansion.as_mut() {
+            e.flush()?
+        } else {
+            false
+        };
+        Ok(ram_written || exp_written)
+    }
+
     pub fn set_rom_speed(&mut self, data: u8) {
         self.rom_speed = if self.fast_rom && test_bit!(data, SPEED_BIT, u8) {
-            timing::FAST_MEM_ACCESS
+            self.fast_rom_access
         } else {
-            timing::SLOW_MEM_ACCESS
+            self.slow_rom_access
         }
     }
 
+    // Use the ROM access times of the console that the cart is plugged into.
+    pub fn set_timing(&mut self, timing: &MachineTiming) {
+        self.slow_rom_access = timing.slow_rom_access;
+        self.fast_rom_access = timing.fast_rom_access;
+        self.rom_speed = timing.slow_rom_access;
+    }
+
     pub fn name(&self) -> String {
-        self.name.clone()
+        self.info.name.clone()
+    }
+
+    pub fn info(&self) -> CartInfo {
+        self.info.clone()
+    }
+
+    // The enhancement chip in the cart, if it isn't emulated.
+    // This is only known until the chip is first accessed.
+    pub fn unsupported_chip(&self) -> Option<EnhancementChip> {
+        self.unsupported
+    }
+
+    pub fn start_frame(&mut self) {
+        if let Some(ex) = self.expansion.as_mut() {
+            ex.start_frame();
+        }
     }
 
     pub fn clock(&mut self, cycles: usize) -> Interrupt {
@@ -440,3 +868,39 @@ impl Cart {
         }
     }
 }
+
+// Internal
+impl Cart {
+    // Report the first access to a chip that isn't emulated.
+    fn unsupported_access(&mut self) {
+        if let Some(chip) = self.unsupported.take() {
+            self.events.report_unsupported(UnsupportedFeature::Chip(chip));
+        }
+    }
+
+    // Writes to ROM are ignored. With the debug feature they are reported,
+    // as they usually point to an emulation bug elsewhere.
+    #[cfg(feature = "debug")]
+    fn rom_written(&self, bank: u8, addr: u16, data: u8) {
+        self.events.send(Event::ROMWritten {
+            addr:   make24!(bank, addr),
+            data:   data,
+            pc:     self.pc,
+        });
+    }
+
+    #[cfg(not(feature = "debug"))]
+    fn rom_written(&self, _bank: u8, _addr: u16, _data: u8) {}
+}
+
+// Debug
+#[cfg(feature = "debug")]
+impl Cart {
+    pub fn set_pc(&mut self, pc: u32) {
+        self.pc = pc;
+    }
+
+    pub fn fx_cache_state(&self) -> Option<crate::debug::FXCacheState> {
+        self.expansion.as_ref().and_then(|e| e.fx_cache_state())
+    }
+}
@@ -0,0 +1,217 @@
+// Soft patches: IPS and BPS files that modify the ROM as it is loaded.
+
+#[cfg(test)]
+mod tests;
+
+use alloc::{
+    string::String,
+    vec::Vec
+};
+
+const IPS_MAGIC: &[u8] = b"PATCH";
+const IPS_EOF: &[u8] = b"EOF";
+const BPS_MAGIC: &[u8] = b"BPS1";
+const BPS_FOOTER_SIZE: usize = 12;
+
+// Apply an IPS or BPS patch to the ROM, and return the patched ROM.
+// The ROM should have any copier header removed.
+pub fn apply_patch(rom_data: &[u8], patch: &[u8]) -> Result<Vec<u8>, String> {
+    if patch.starts_with(IPS_MAGIC) {
+        apply_ips(rom_data, patch)
+    } else if patch.starts_with(BPS_MAGIC) {
+        apply_bps(rom_data, patch)
+    } else {
+        Err("Unrecognised patch format".into())
+    }
+}
+
+// IPS patches are a list of records, each containing an offset and data to write there.
+// There are no checksums, so they can't be checked against the ROM.
+fn apply_ips(rom_data: &[u8], patch: &[u8]) -> Result<Vec<u8>, String> {
+    let mut out = rom_data.to_vec();
+    let mut reader = PatchReader::new(&patch[IPS_MAGIC.len()..]);
+
+    loop {
+        if reader.remaining() == IPS_EOF {
+            break;
+        }
+        let offset = reader.read_be(3)?;
+        if offset == 0x454F46 && reader.remaining().len() == 3 {
+            // "EOF", followed by the size to truncate the ROM to.
+            out.truncate(reader.read_be(3)?);
+            break;
+        }
+        let size = reader.read_be(2)?;
+        if size == 0 {
+            // Run-length encoded record.
+            let size = reader.read_be(2)?;
+            let value = reader.read_byte()?;
+            extend_to(&mut out, offset + size);
+            out[offset..(offset + size)].iter_mut().for_each(|b| *b = value);
+        } else {
+            let data = reader.read_bytes(size)?;
+            extend_to(&mut out, offset + size);
+            out[offset..(offset + size)].copy_from_slice(data);
+        }
+    }
+
+    Ok(out)
+}
+
+// Patches can write past the end of the ROM to make it bigger.
+fn extend_to(rom_data: &mut Vec<u8>, size: usize) {
+    if rom_data.len() < size {
+        rom_data.resize(size, 0);
+    }
+}
+
+// BPS patches contain the checksums of the source and target ROM,
+// so patching the wrong ROM is detected.
+fn apply_bps(rom_data: &[u8], patch: &[u8]) -> Result<Vec<u8>, String> {
+    if patch.len() < BPS_MAGIC.len() + BPS_FOOTER_SIZE {
+        return Err("BPS patch is too short".into());
+    }
+    let footer_start = patch.len() - BPS_FOOTER_SIZE;
+    let footer = &patch[footer_start..];
+    let source_crc = u32::from_le_bytes([footer[0], footer[1], footer[2], footer[3]]);
+    let target_crc = u32::from_le_bytes([footer[4], footer[5], footer[6], footer[7]]);
+    let patch_crc = u32::from_le_bytes([footer[8], footer[9], footer[10], footer[11]]);
+
+    if crc32(&patch[..(patch.len() - 4)]) != patch_crc {
+        return Err("BPS patch is corrupt".into());
+    }
+    if crc32(rom_data) != source_crc {
+        return Err("BPS patch is for a different ROM".into());
+    }
+
+    let mut reader = PatchReader::new(&patch[BPS_MAGIC.len()..footer_start]);
+    let source_size = reader.read_varint()?;
+    let target_size = reader.read_varint()?;
+    let metadata_size = reader.read_varint()?;
+    reader.read_bytes(metadata_size)?;
+    if source_size != rom_data.len() {
+        return Err("BPS patch is for a different ROM".into());
+    }
+
+    let mut out = Vec::with_capacity(target_size);
+    let mut source_offset = 0;
+    let mut target_offset = 0;
+    while !reader.remaining().is_empty() {
+        let action = reader.read_varint()?;
+        let length = (action >> 2) + 1;
+        match action & 3 {
+            // Source read: copy from the same position in the source.
+            0 => {
+                let start = out.len();
+                let data = rom_data.get(start..(start + length)).ok_or("BPS source read out of range")?;
+                out.extend_from_slice(data);
+            },
+            // Target read: copy from the patch.
+            1 => out.extend_from_slice(reader.read_bytes(length)?),
+            // Source copy: copy from a relative position in the source.
+            2 => {
+                source_offset = reader.read_offset(source_offset)?;
+                let data = rom_data.get(source_offset..(source_offset + length)).ok_or("BPS source copy out of range")?;
+                out.extend_from_slice(data);
+                source_offset += length;
+            },
+            // Target copy: copy from a relative position in the output. This can overlap with what is written.
+            _ => {
+                target_offset = reader.read_offset(target_offset)?;
+                if target_offset >= out.len() {
+                    return Err("BPS target copy out of range".into());
+                }
+                for _ in 0..length {
+                    let data = out[target_offset];
+                    out.push(data);
+                    target_offset += 1;
+                }
+            },
+        }
+        if out.len() > target_size {
+            return Err("BPS patch writes past the end of the ROM".into());
+        }
+    }
+
+    if out.len() != target_size || crc32(&out) != target_crc {
+        return Err("BPS patch produced the wrong ROM".into());
+    }
+    Ok(out)
+}
+
+struct PatchReader<'a> {
+    data:   &'a [u8],
+}
+
+impl<'a> PatchReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        PatchReader {
+            data: data
+        }
+    }
+
+    fn remaining(&self) -> &'a [u8] {
+        self.data
+    }
+
+    fn read_bytes(&mut self, count: usize) -> Result<&'a [u8], String> {
+        if count > self.data.len() {
+            return Err("Patch ended unexpectedly".into());
+        }
+        let (bytes, rest) = self.data.split_at(count);
+        self.data = rest;
+        Ok(bytes)
+    }
+
+    fn read_byte(&mut self) -> Result<u8, String> {
+        Ok(self.read_bytes(1)?[0])
+    }
+
+    // Big-endian, used by IPS.
+    fn read_be(&mut self, count: usize) -> Result<usize, String> {
+        Ok(self.read_bytes(count)?.iter().fold(0, |acc, b| (acc << 8) | (*b as usize)))
+    }
+
+    // Variable-length number, used by BPS.
+    // Each byte has 7 bits of data, and the top bit is set on the last byte.
+    fn read_varint(&mut self) -> Result<usize, String> {
+        let mut data = 0_usize;
+        let mut shift = 1_usize;
+        loop {
+            let byte = self.read_byte()?;
+            data = shift.checked_mul((byte & 0x7F) as usize)
+                .and_then(|d| data.checked_add(d))
+                .ok_or("BPS number is too large")?;
+            if test_bit!(byte, 7, u8) {
+                return Ok(data);
+            }
+            shift = shift.checked_shl(7).ok_or("BPS number is too large")?;
+            data = data.checked_add(shift).ok_or("BPS number is too large")?;
+        }
+    }
+
+    // Signed offset from the current position, used by BPS copy actions.
+    // Bit 0 is the sign.
+    fn read_offset(&mut self, current: usize) -> Result<usize, String> {
+        let data = self.read_varint()?;
+        let delta = data >> 1;
+        let new = if (data & 1) != 0 {
+            current.checked_sub(delta)
+        } else {
+            current.checked_add(delta)
+        };
+        new.ok_or_else(|| "BPS copy offset out of range".into())
+    }
+}
+
+// CRC-32, as used in zip and PNG files.
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFF_u32;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if test_bit!(crc, 0, u32) {(crc >> 1) ^ 0xEDB8_8320} else {crc >> 1};
+        }
+    }
+    !crc
+}
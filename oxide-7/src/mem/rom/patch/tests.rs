@@ -0,0 +1,106 @@
+// IPS and BPS patching.
+use super::*;
+use alloc::vec;
+
+fn ips(records: &[u8]) -> Vec<u8> {
+    let mut patch = IPS_MAGIC.to_vec();
+    patch.extend_from_slice(records);
+    patch.extend_from_slice(IPS_EOF);
+    patch
+}
+
+#[test]
+fn ips_records() {
+    let patch = ips(&[
+        0x00, 0x00, 0x02, 0x00, 0x03, 0x11, 0x22, 0x33,   // Write 3 bytes at $02.
+        0x00, 0x00, 0x08, 0x00, 0x00, 0x00, 0x04, 0xAA,   // Fill 4 bytes at $08.
+    ]);
+    let patched = apply_patch(&[0; 16], &patch).unwrap();
+    assert_eq!(patched, vec![0, 0, 0x11, 0x22, 0x33, 0, 0, 0, 0xAA, 0xAA, 0xAA, 0xAA, 0, 0, 0, 0]);
+}
+
+#[test]
+fn ips_resize() {
+    let patch = ips(&[0x00, 0x00, 0x0F, 0x00, 0x02, 0x11, 0x22]);
+    assert_eq!(apply_patch(&[0; 16], &patch).unwrap().len(), 17);
+
+    // Truncate to 8 bytes after EOF.
+    let mut patch = ips(&[]);
+    patch.extend_from_slice(&[0x00, 0x00, 0x08]);
+    assert_eq!(apply_patch(&[0; 16], &patch).unwrap().len(), 8);
+}
+
+#[test]
+fn ips_truncated_patch() {
+    let patch = ips(&[0x00, 0x00, 0x02, 0x00, 0x03, 0x11]);
+    assert!(apply_patch(&[0; 16], &patch[..(patch.len() - 3)]).is_err());
+}
+
+#[test]
+fn crc() {
+    assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+}
+
+fn encode(mut n: usize, out: &mut Vec<u8>) {
+    loop {
+        let x = (n & 0x7F) as u8;
+        n >>= 7;
+        if n == 0 {
+            out.push(x | 0x80);
+            return;
+        }
+        out.push(x);
+        n -= 1;
+    }
+}
+
+fn bps(source: &[u8], target: &[u8], actions: &[u8]) -> Vec<u8> {
+    let mut patch = BPS_MAGIC.to_vec();
+    encode(source.len(), &mut patch);
+    encode(target.len(), &mut patch);
+    encode(0, &mut patch);
+    patch.extend_from_slice(actions);
+    patch.extend_from_slice(&crc32(source).to_le_bytes());
+    patch.extend_from_slice(&crc32(target).to_le_bytes());
+    let patch_crc = crc32(&patch);
+    patch.extend_from_slice(&patch_crc.to_le_bytes());
+    patch
+}
+
+// Each type of action.
+fn bps_actions() -> Vec<u8> {
+    let mut actions = Vec::new();
+    encode(3 << 2, &mut actions);           // Source read 4 bytes.
+    encode((1 << 2) | 1, &mut actions);     // Target read 2 bytes.
+    actions.extend_from_slice(&[0xAA, 0xBB]);
+    encode((2 << 2) | 2, &mut actions);     // Source copy 3 bytes from $0A.
+    encode(10 << 1, &mut actions);
+    encode((3 << 2) | 3, &mut actions);     // Target copy 4 bytes from $04.
+    encode(4 << 1, &mut actions);
+    actions
+}
+
+#[test]
+fn bps_actions_applied() {
+    let source = (0..16).collect::<Vec<u8>>();
+    let target = [0, 1, 2, 3, 0xAA, 0xBB, 10, 11, 12, 0xAA, 0xBB, 10, 11];
+    let patch = bps(&source, &target, &bps_actions());
+    assert_eq!(apply_patch(&source, &patch).unwrap(), target.to_vec());
+}
+
+#[test]
+fn bps_wrong_source() {
+    let source = (0..16).collect::<Vec<u8>>();
+    let target = [0, 1, 2, 3, 0xAA, 0xBB, 10, 11, 12, 0xAA, 0xBB, 10, 11];
+    let patch = bps(&source, &target, &bps_actions());
+    assert!(apply_patch(&[0; 16], &patch).is_err());
+
+    let mut corrupt = patch.clone();
+    corrupt[10] ^= 1;
+    assert!(apply_patch(&source, &corrupt).is_err());
+}
+
+#[test]
+fn unknown_format() {
+    assert!(apply_patch(&[0; 16], b"NOT A PATCH").is_err());
+}
@@ -1,3 +1,11 @@
+use alloc::{
+    boxed::Box,
+    string::String,
+    vec::Vec
+};
+#[cfg(feature = "std")]
+use alloc::string::ToString;
+#[cfg(feature = "std")]
 use std::{
     io::{
         BufReader,
@@ -13,16 +21,30 @@ use std::{
     }
 };
 
+use crate::savestate::{
+    Stateful,
+    StateWriter,
+    StateReader
+};
 use super::super::RAM;
 
+const PAGE_SIZE: usize = 0x400; // Changes are tracked for each 1kB page.
+
+// Somewhere to write the save data. This is a file, except in tests.
+#[cfg(feature = "std")]
+pub trait SaveFile: Write + Seek {}
+#[cfg(feature = "std")]
+impl<T: Write + Seek> SaveFile for T {}
+
 /// Save RAM.
 /// This may or may not exist based on the cartridge.
-pub trait SRAM {
+pub trait SRAM: Stateful {
     fn read(&self, addr: u32) -> u8;
     fn write(&mut self, addr: u32, data: u8);
 
     /// Flush the SRAM to the save file specified.
-    fn flush(&mut self);
+    /// Returns true if any data was written, or an error if the save file couldn't be written.
+    fn flush(&mut self) -> Result<bool, String>;
 }
 
 pub fn create_sram(file_name: &str, size: usize) -> Result<Box<dyn SRAM>, String> {
@@ -35,16 +57,20 @@ pub fn create_sram(file_name: &str, size: usize) -> Result<Box<dyn SRAM>, String
 }
 
 /// Used in cartridges that have SRAM.
+/// Without std there is no save file, so the data is only kept in memory.
+/// Only the pages that have changed are written to the save file.
 pub struct SizedSRAM {
-    save_file:  BufWriter<File>,
+    #[cfg(feature = "std")]
+    save_file:  Box<dyn SaveFile>,
     ram:        RAM,
 
     mask:       u32,  // Mask when reading/writing
 
-    dirty:      bool,
+    dirty_pages:    Vec<bool>,  // Pages changed since the last flush.
 }
 
 impl SizedSRAM {
+    #[cfg(feature = "std")]
     fn new(file_name: &str, size: usize) -> Result<Self, String> {
         let mut ram = RAM::new(size);
 
@@ -61,13 +87,30 @@ impl SizedSRAM {
             .open(file_name)
             .map_err(|e| e.to_string())?;
 
-        Ok(SizedSRAM {
-            save_file:  BufWriter::new(file),
+        Ok(SizedSRAM::with_save_file(Box::new(BufWriter::new(file)), ram))
+    }
+
+    #[cfg(feature = "std")]
+    pub(super) fn with_save_file(save_file: Box<dyn SaveFile>, ram: RAM) -> Self {
+        let size = ram.data.len();
+        SizedSRAM {
+            save_file:  save_file,
             ram:        ram,
 
             mask:       (size - 1) as u32,
 
-            dirty:      false
+            dirty_pages:    vec![false; num_pages(size)],
+        }
+    }
+
+    #[cfg(not(feature = "std"))]
+    fn new(_file_name: &str, size: usize) -> Result<Self, String> {
+        Ok(SizedSRAM {
+            ram:        RAM::new(size),
+
+            mask:       (size - 1) as u32,
+
+            dirty_pages:    vec![false; num_pages(size)],
         })
     }
 }
@@ -78,22 +121,84 @@ impl SRAM for SizedSRAM {
         self.ram.read(addr & self.mask)
     }
 
+    // Many games write the same data over and over, so the page only changes if the data does.
     fn write(&mut self, addr: u32, data: u8) {
-        self.ram.write(addr & self.mask, data);
-        self.dirty = true;
+        let addr = addr & self.mask;
+        if self.ram.read(addr) != data {
+            self.ram.write(addr, data);
+            self.dirty_pages[(addr as usize) / PAGE_SIZE] = true;
+        }
+    }
+
+    // Each run of changed pages is written in one go.
+    // The pages stay dirty until they have all been written, so if it fails they are written on the next flush.
+    #[cfg(feature = "std")]
+    fn flush(&mut self) -> Result<bool, String> {
+        let mut runs = Vec::new();
+        let mut page = 0;
+        while page < self.dirty_pages.len() {
+            if !self.dirty_pages[page] {
+                page += 1;
+                continue;
+            }
+
+            let start_page = page;
+            while page < self.dirty_pages.len() && self.dirty_pages[page] {
+                page += 1;
+            }
+            runs.push(start_page..page);
+        }
+
+        if runs.is_empty() {
+            return Ok(false);
+        }
+
+        for pages in &runs {
+            let start = pages.start * PAGE_SIZE;
+            let end = core::cmp::min(pages.end * PAGE_SIZE, self.ram.data.len());
+            self.save_file.seek(SeekFrom::Start(start as u64)).map_err(|e| e.to_string())?;
+            self.save_file.write_all(&self.ram.data[start..end]).map_err(|e| e.to_string())?;
+        }
+        self.save_file.flush().map_err(|e| e.to_string())?;
+
+        for pages in runs {
+            self.dirty_pages[pages].fill(false);
+        }
+        Ok(true)
+    }
+
+    #[cfg(not(feature = "std"))]
+    fn flush(&mut self) -> Result<bool, String> {
+        Ok(false)
     }
+}
 
-    fn flush(&mut self) {
-        if self.dirty {
-            self.save_file.seek(SeekFrom::Start(0)).expect("Couldn't seek to start of save file!");
+// All of SRAM is stored, so the state is always the same size. Rewind points rely on this.
+impl Stateful for SizedSRAM {
+    fn save_state(&self, w: &mut StateWriter) {
+        w.write(&self.ram.data);
+    }
 
-            self.save_file.write_all(&self.ram.data).expect("Couldn't write to save file!");
+    // Pages where the loaded data differs will be written to the save file on the next flush.
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), String> {
+        let data: Vec<u8> = r.read()?;
+        if data.len() != self.ram.data.len() {
+            return Err(format!("SRAM is {} bytes, but the state has {}.", self.ram.data.len(), data.len()));
+        }
 
-            self.dirty = false;
+        let changed_pages = self.ram.data.chunks(PAGE_SIZE).zip(data.chunks(PAGE_SIZE)).map(|(old, new)| old != new);
+        for (dirty, changed) in self.dirty_pages.iter_mut().zip(changed_pages) {
+            *dirty = *dirty || changed;
         }
+        self.ram.data = data;
+        Ok(())
     }
 }
 
+fn num_pages(size: usize) -> usize {
+    size.div_ceil(PAGE_SIZE)
+}
+
 /// Used in cartridges that don't have SRAM.
 pub struct EmptySRAM {}
 
@@ -110,5 +215,15 @@ impl SRAM for EmptySRAM {
 
     fn write(&mut self, _: u32, _: u8) {}
 
-    fn flush(&mut self) {}
+    fn flush(&mut self) -> Result<bool, String> {
+        Ok(false)
+    }
+}
+
+impl Stateful for EmptySRAM {
+    fn save_state(&self, _: &mut StateWriter) {}
+
+    fn load_state(&mut self, _: &mut StateReader) -> Result<(), String> {
+        Ok(())
+    }
 }
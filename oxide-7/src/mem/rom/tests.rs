@@ -0,0 +1,62 @@
+use super::*;
+
+use alloc::rc::Rc;
+use core::cell::{
+    Cell,
+    RefCell
+};
+use std::io::{
+    self,
+    Cursor,
+    Seek,
+    SeekFrom,
+    Write
+};
+
+// A save file in memory, which fails every write while fail is set.
+struct TestSaveFile {
+    data:   Rc<RefCell<Cursor<Vec<u8>>>>,
+    fail:   Rc<Cell<bool>>,
+}
+
+impl Write for TestSaveFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.fail.get() {
+            Err(io::Error::other("disk full"))
+        } else {
+            self.data.borrow_mut().write(buf)
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Seek for TestSaveFile {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.data.borrow_mut().seek(pos)
+    }
+}
+
+// If a flush fails, the pages are written by the next flush, even if the game doesn't change them again.
+#[test]
+fn sram_failed_flush_keeps_pages_dirty() {
+    let data = Rc::new(RefCell::new(Cursor::new(vec![0; 0x2000])));
+    let fail = Rc::new(Cell::new(true));
+    let save_file = TestSaveFile {
+        data:   data.clone(),
+        fail:   fail.clone(),
+    };
+    let mut sram = SizedSRAM::with_save_file(Box::new(save_file), crate::mem::RAM::new(0x2000));
+
+    sram.write(0x0010, 0x12);
+    sram.write(0x1C00, 0x34);
+    assert!(sram.flush().is_err());
+
+    fail.set(false);
+    assert_eq!(sram.flush(), Ok(true));
+    assert_eq!(data.borrow().get_ref()[0x0010], 0x12);
+    assert_eq!(data.borrow().get_ref()[0x1C00], 0x34);
+    assert_eq!(sram.flush(), Ok(false));
+}
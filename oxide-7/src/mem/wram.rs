@@ -0,0 +1,76 @@
+// Work RAM, and the port for accessing it through address bus B ($2180-$2183).
+use alloc::string::String;
+
+use crate::savestate::{
+    Stateful,
+    StateWriter,
+    StateReader
+};
+
+use super::RAM;
+
+const WRAM_SIZE: usize = 0x20000;
+
+pub struct WorkRAM {
+    ram:        RAM,
+    port_addr:  u32,    // 17-bit address used by the port.
+}
+
+impl WorkRAM {
+    pub fn new() -> Self {
+        WorkRAM {
+            ram:        RAM::new(WRAM_SIZE),
+            port_addr:  0,
+        }
+    }
+
+    pub fn read(&self, addr: u32) -> u8 {
+        self.ram.read(addr)
+    }
+
+    pub fn write(&mut self, addr: u32, data: u8) {
+        self.ram.write(addr, data);
+    }
+
+    // Access through $2180. The address increments after each access.
+    pub fn read_port(&mut self) -> u8 {
+        let data = self.ram.read(self.port_addr);
+        self.port_addr = self.port_addr.wrapping_add(1) & 0x1FFFF;
+        data
+    }
+
+    pub fn write_port(&mut self, data: u8) {
+        self.ram.write(self.port_addr, data);
+        self.port_addr = self.port_addr.wrapping_add(1) & 0x1FFFF;
+    }
+
+    // Set the port address through $2181-$2183.
+    pub fn write_port_addr(&mut self, addr: u16, data: u8) {
+        match addr {
+            0x2181 => self.port_addr = set_lo24!(self.port_addr, data),
+            0x2182 => self.port_addr = set_mid24!(self.port_addr, data),
+            0x2183 => self.port_addr = set_hi24!(self.port_addr, data & 1),
+            _ => unreachable!()
+        }
+    }
+
+    pub fn as_slice(&self) -> &[u8] {
+        self.ram.as_slice()
+    }
+
+    pub fn as_mut_slice(&mut self) -> &mut [u8] {
+        self.ram.as_mut_slice()
+    }
+}
+
+impl Stateful for WorkRAM {
+    fn save_state(&self, w: &mut StateWriter) {
+        w.write(&self.ram);
+        w.write(&self.port_addr);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), String> {
+        r.read_into(&mut self.ram)?;
+        r.read_into(&mut self.port_addr)
+    }
+}
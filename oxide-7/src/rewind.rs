@@ -0,0 +1,131 @@
+// Rewind: a ring buffer of states, saved at points chosen by the frontend (usually every frame).
+// The newest state is kept whole. Each older state is kept as the difference to the state after it,
+// so the oldest states can be dropped without decoding anything.
+// Most of the state doesn't change from one frame to the next, so the differences are mostly zeros,
+// and runs of zeros are shortened.
+use alloc::{
+    collections::VecDeque,
+    vec::Vec
+};
+
+const DEFAULT_CAPACITY: usize = 600;    // 10 seconds, at one point each frame.
+
+pub struct Rewind {
+    capacity:   usize,
+    newest:     Option<Vec<u8>>,
+    deltas:     VecDeque<Vec<u8>>,  // Oldest first. Each one recreates the state before the next.
+}
+
+impl Rewind {
+    pub fn new() -> Self {
+        Rewind {
+            capacity:   DEFAULT_CAPACITY,
+            newest:     None,
+            deltas:     VecDeque::new(),
+        }
+    }
+
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        self.trim();
+    }
+
+    // Number of points that can be rewound to.
+    pub fn len(&self) -> usize {
+        self.deltas.len() + if self.newest.is_some() {1} else {0}
+    }
+
+    pub fn push(&mut self, state: Vec<u8>) {
+        if let Some(newest) = self.newest.take() {
+            self.deltas.push_back(encode_delta(&newest, &state));
+        }
+        self.newest = Some(state);
+        self.trim();
+    }
+
+    // Drop up to n of the newest points, and get the state that is the newest after that.
+    // Returns the number of points dropped along with the state, or None if there are no points.
+    pub fn rewind(&mut self, n: usize) -> Option<(usize, Vec<u8>)> {
+        let mut state = self.newest.take()?;
+        let mut dropped = 0;
+        while dropped < n {
+            match self.deltas.pop_back() {
+                Some(delta) => state = decode_delta(&state, &delta),
+                None => break,
+            }
+            dropped += 1;
+        }
+        self.newest = Some(state.clone());
+        Some((dropped, state))
+    }
+
+    fn trim(&mut self) {
+        while self.len() > self.capacity {
+            if self.deltas.pop_front().is_none() {
+                self.newest = None;
+            }
+        }
+    }
+}
+
+// Make a delta that recreates the old state from the new one.
+// The delta is the old state XOR the new state, stored as the length of the old state followed by
+// a list of (zeros to skip, bytes that changed) runs.
+fn encode_delta(old: &[u8], new: &[u8]) -> Vec<u8> {
+    let mut delta = Vec::new();
+    write_len(&mut delta, old.len());
+
+    let diff = old.iter().enumerate().map(|(i, o)| o ^ new.get(i).copied().unwrap_or(0)).collect::<Vec<_>>();
+    let mut pos = 0;
+    while pos < diff.len() {
+        let zeros = diff[pos..].iter().take_while(|d| **d == 0).count();
+        let changed = diff[(pos + zeros)..].iter().take_while(|d| **d != 0).count();
+        write_len(&mut delta, zeros);
+        write_len(&mut delta, changed);
+        delta.extend_from_slice(&diff[(pos + zeros)..(pos + zeros + changed)]);
+        pos += zeros + changed;
+    }
+    delta
+}
+
+fn decode_delta(new: &[u8], delta: &[u8]) -> Vec<u8> {
+    let mut delta_pos = 0;
+    let len = read_len(delta, &mut delta_pos);
+    let mut old = new.to_vec();
+    old.resize(len, 0);
+
+    let mut pos = 0;
+    while delta_pos < delta.len() {
+        pos += read_len(delta, &mut delta_pos);
+        let changed = read_len(delta, &mut delta_pos);
+        for (o, d) in old[pos..(pos + changed)].iter_mut().zip(&delta[delta_pos..(delta_pos + changed)]) {
+            *o ^= *d;
+        }
+        pos += changed;
+        delta_pos += changed;
+    }
+    old
+}
+
+// Lengths are stored 7 bits at a time, with the top bit set if more bytes follow.
+fn write_len(out: &mut Vec<u8>, mut len: usize) {
+    while len >= 0x80 {
+        out.push((len as u8) | 0x80);
+        len >>= 7;
+    }
+    out.push(len as u8);
+}
+
+fn read_len(data: &[u8], pos: &mut usize) -> usize {
+    let mut len = 0;
+    let mut shift = 0;
+    loop {
+        let byte = data[*pos];
+        *pos += 1;
+        len |= ((byte & 0x7F) as usize) << shift;
+        if (byte & 0x80) == 0 {
+            return len;
+        }
+        shift += 7;
+    }
+}
@@ -0,0 +1,247 @@
+// Savestates.
+// Each component writes its state in order, and reads it back in the same order.
+// Values are serialized with bincode, which needs std. Without std the components
+// still implement Stateful, but the SNES can't save or load states.
+#![cfg_attr(not(feature = "std"), allow(dead_code))]
+
+use alloc::{
+    string::{
+        String,
+        ToString
+    },
+    vec::Vec
+};
+use serde::{
+    Serialize,
+    de::DeserializeOwned
+};
+
+/// Width of the thumbnail image stored in a savestate.
+pub const THUMBNAIL_WIDTH: usize = 128;
+/// Height of the thumbnail image stored in a savestate.
+pub const THUMBNAIL_HEIGHT: usize = 112;
+/// Size of the thumbnail image stored in a savestate in bytes (R8G8B8A8 format).
+pub const THUMBNAIL_SIZE: usize = THUMBNAIL_WIDTH * THUMBNAIL_HEIGHT * 4;
+
+const MAGIC: &[u8; 4] = b"OX7S";
+const VERSION: u8 = 1;
+
+const HEADER_SIZE: usize = 6;
+const COMPRESSED: u8 = bit!(0);
+
+/// A component that can be saved into and loaded from a savestate.
+/// Values must be read back in the same order they were written.
+pub trait Stateful {
+    fn save_state(&self, w: &mut StateWriter);
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), String>;
+}
+
+/// A part of the system that can be exported on its own with `SNES::export_component_state`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StateComponent {
+    /// CPU registers.
+    CPU,
+    /// Video registers, VRAM, OAM and CGRAM.
+    PPU,
+    /// SPC700, DSP and audio RAM.
+    APU,
+    /// Save RAM and enhancement chip.
+    Cart,
+    /// Work RAM.
+    WRAM,
+    /// DMA and HDMA channels.
+    DMA,
+    /// Joypads and auto-read.
+    Joypads,
+    /// Multiply and divide unit.
+    Math
+}
+
+impl StateComponent {
+    const ALL: [StateComponent; 8] = [
+        StateComponent::CPU, StateComponent::PPU, StateComponent::APU, StateComponent::Cart,
+        StateComponent::WRAM, StateComponent::DMA, StateComponent::Joypads, StateComponent::Math
+    ];
+
+    /// Iterate over all of the components.
+    pub fn iter() -> impl Iterator<Item = StateComponent> {
+        Self::ALL.iter().cloned()
+    }
+
+    /// The name of the component, in lower case. Useful for naming export files.
+    pub fn name(&self) -> &'static str {
+        match self {
+            StateComponent::CPU     => "cpu",
+            StateComponent::PPU     => "ppu",
+            StateComponent::APU     => "apu",
+            StateComponent::Cart    => "cart",
+            StateComponent::WRAM    => "wram",
+            StateComponent::DMA     => "dma",
+            StateComponent::Joypads => "joypads",
+            StateComponent::Math    => "math"
+        }
+    }
+}
+
+/// Collects the state of each component, in order.
+pub struct StateWriter {
+    data:   Vec<u8>,
+}
+
+impl StateWriter {
+    pub(crate) fn new() -> Self {
+        StateWriter {
+            data:   Vec::new(),
+        }
+    }
+
+    /// Append a value to the state.
+    #[cfg(feature = "std")]
+    pub fn write<T: Serialize>(&mut self, val: &T) {
+        bincode::serialize_into(&mut self.data, val).expect("Couldn't serialize state!");
+    }
+
+    #[cfg(not(feature = "std"))]
+    pub fn write<T: Serialize>(&mut self, _val: &T) {}
+
+    // Get the raw state, without a header or compression.
+    pub(crate) fn into_data(self) -> Vec<u8> {
+        self.data
+    }
+
+    // Pack the state along with a thumbnail.
+    pub(crate) fn finish(self, thumbnail: &[u8]) -> Vec<u8> {
+        let (flags, body) = compress(self.data);
+
+        let mut out = Vec::with_capacity(HEADER_SIZE + THUMBNAIL_SIZE + body.len());
+        out.extend_from_slice(MAGIC);
+        out.push(VERSION);
+        out.push(flags);
+        out.extend_from_slice(thumbnail);
+        out.extend_from_slice(&body);
+        out
+    }
+}
+
+/// Reads back the state of each component, in the order it was written.
+pub struct StateReader {
+    data:   Vec<u8>,
+    pos:    usize,
+}
+
+impl StateReader {
+    // Unpack a state created by StateWriter.
+    pub(crate) fn new(state: &[u8]) -> Result<Self, String> {
+        let flags = read_header(state)?;
+        let body = &state[(HEADER_SIZE + THUMBNAIL_SIZE)..];
+
+        Ok(StateReader {
+            data:   decompress(flags, body)?,
+            pos:    0,
+        })
+    }
+
+    // Read a raw state created by StateWriter::into_data.
+    pub(crate) fn from_data(data: Vec<u8>) -> Self {
+        StateReader {
+            data:   data,
+            pos:    0,
+        }
+    }
+
+    /// Read the next value from the state.
+    #[cfg(feature = "std")]
+    pub fn read<T: DeserializeOwned>(&mut self) -> Result<T, String> {
+        let mut remaining = &self.data[self.pos..];
+        let start_len = remaining.len();
+        let val = bincode::deserialize_from(&mut remaining).map_err(|e| e.to_string())?;
+        self.pos += start_len - remaining.len();
+        Ok(val)
+    }
+
+    #[cfg(not(feature = "std"))]
+    pub fn read<T: DeserializeOwned>(&mut self) -> Result<T, String> {
+        Err("Savestates need the std feature.".to_string())
+    }
+
+    /// Read the next value, and copy it into the destination.
+    pub fn read_into<T: DeserializeOwned>(&mut self, dest: &mut T) -> Result<(), String> {
+        *dest = self.read()?;
+        Ok(())
+    }
+}
+
+/// Get the thumbnail image from a savestate, without loading the state.
+/// The image is THUMBNAIL_WIDTH x THUMBNAIL_HEIGHT, in R8G8B8A8 format.
+pub fn read_thumbnail(state: &[u8]) -> Result<&[u8], String> {
+    read_header(state)?;
+    Ok(&state[HEADER_SIZE..(HEADER_SIZE + THUMBNAIL_SIZE)])
+}
+
+// Shrink the 512x224 screen in a frame down to a thumbnail, by averaging each block of 4x2 pixels.
+// Frames with widescreen have extra pixels on each side of the line, which are cropped off.
+pub fn make_thumbnail(frame: &[u8], widescreen: usize) -> Vec<u8> {
+    const SCREEN_WIDTH: usize = 512;
+    const BLOCK_WIDTH: usize = SCREEN_WIDTH / THUMBNAIL_WIDTH;
+    const BLOCK_HEIGHT: usize = 224 / THUMBNAIL_HEIGHT;
+    let frame_width = SCREEN_WIDTH + (widescreen * 4);
+
+    let mut thumbnail = vec![0; THUMBNAIL_SIZE];
+    for (i, out) in thumbnail.chunks_exact_mut(4).enumerate() {
+        let x = (i % THUMBNAIL_WIDTH) * BLOCK_WIDTH + (widescreen * 2);
+        let y = (i / THUMBNAIL_WIDTH) * BLOCK_HEIGHT;
+        let mut sum = [0_usize; 4];
+        for block_y in y..(y + BLOCK_HEIGHT) {
+            for block_x in x..(x + BLOCK_WIDTH) {
+                let offset = (block_y * frame_width + block_x) * 4;
+                for (s, p) in sum.iter_mut().zip(&frame[offset..(offset + 4)]) {
+                    *s += *p as usize;
+                }
+            }
+        }
+        for (o, s) in out.iter_mut().zip(sum.iter()) {
+            *o = (*s / (BLOCK_WIDTH * BLOCK_HEIGHT)) as u8;
+        }
+    }
+    thumbnail
+}
+
+// Internal
+fn read_header(state: &[u8]) -> Result<u8, String> {
+    if state.len() < HEADER_SIZE + THUMBNAIL_SIZE || &state[0..4] != MAGIC {
+        return Err("Not a savestate.".to_string());
+    }
+    if state[4] != VERSION {
+        return Err(format!("Unsupported savestate version {}.", state[4]));
+    }
+    Ok(state[5])
+}
+
+#[cfg(feature = "compress")]
+fn compress(data: Vec<u8>) -> (u8, Vec<u8>) {
+    let compressed = zstd::encode_all(data.as_slice(), 0).expect("Couldn't compress state!");
+    (COMPRESSED, compressed)
+}
+
+#[cfg(not(feature = "compress"))]
+fn compress(data: Vec<u8>) -> (u8, Vec<u8>) {
+    (0, data)
+}
+
+#[cfg(feature = "compress")]
+fn decompress(flags: u8, body: &[u8]) -> Result<Vec<u8>, String> {
+    if (flags & COMPRESSED) != 0 {
+        zstd::decode_all(body).map_err(|e| e.to_string())
+    } else {
+        Ok(body.to_vec())
+    }
+}
+
+#[cfg(not(feature = "compress"))]
+fn decompress(flags: u8, body: &[u8]) -> Result<Vec<u8>, String> {
+    if (flags & COMPRESSED) != 0 {
+        Err("Savestate is compressed: build with the compress feature to load it.".to_string())
+    } else {
+        Ok(body.to_vec())
+    }
+}
@@ -0,0 +1,122 @@
+// Sharing data and sending messages between parts of the emulator.
+// With std, these are the real thread-safe types, so data can be shared with the render and audio threads.
+// Without std everything runs on a single thread, so simpler stand-ins with the same interface are used.
+
+pub use alloc::sync::Arc;
+
+#[cfg(feature = "std")]
+pub use threaded::Mutex;
+#[cfg(feature = "std")]
+pub use crossbeam_channel::{
+    unbounded,
+    Receiver,
+    Sender
+};
+
+#[cfg(not(feature = "std"))]
+pub use single::*;
+
+#[cfg(feature = "std")]
+mod threaded {
+    use std::sync::{
+        MutexGuard,
+        PoisonError
+    };
+
+    // A mutex that can still be locked after a thread panicked while holding it.
+    // The data is emulator state, which is left whole between writes, so the emulator
+    // carries on rather than panicking on every thread that shares it.
+    #[derive(Default)]
+    pub struct Mutex<T> {
+        inner: std::sync::Mutex<T>
+    }
+
+    impl<T> Mutex<T> {
+        pub fn new(data: T) -> Self {
+            Mutex {
+                inner: std::sync::Mutex::new(data)
+            }
+        }
+
+        pub fn lock(&self) -> MutexGuard<'_, T> {
+            self.inner.lock().unwrap_or_else(PoisonError::into_inner)
+        }
+    }
+}
+
+#[cfg(not(feature = "std"))]
+mod single {
+    use core::cell::{
+        RefCell,
+        RefMut
+    };
+    use alloc::{
+        collections::VecDeque,
+        sync::{
+            Arc,
+            Weak
+        }
+    };
+
+    // A RefCell with the interface of a mutex.
+    #[derive(Default)]
+    pub struct Mutex<T> {
+        data: RefCell<T>
+    }
+
+    impl<T> Mutex<T> {
+        pub fn new(data: T) -> Self {
+            Mutex {
+                data: RefCell::new(data)
+            }
+        }
+
+        pub fn lock(&self) -> RefMut<'_, T> {
+            self.data.borrow_mut()
+        }
+    }
+
+    type Queue<T> = Mutex<VecDeque<T>>;
+
+    // Create a channel with no limit on the number of messages waiting.
+    pub fn unbounded<T>() -> (Sender<T>, Receiver<T>) {
+        let queue = Arc::new(Mutex::new(VecDeque::new()));
+        let sender = Sender {
+            queue: Arc::downgrade(&queue)
+        };
+        (sender, Receiver {queue: queue})
+    }
+
+    pub struct Sender<T> {
+        queue: Weak<Queue<T>>
+    }
+
+    impl<T> Sender<T> {
+        // Fails if the receiver has been dropped.
+        pub fn send(&self, msg: T) -> Result<(), T> {
+            match self.queue.upgrade() {
+                Some(queue) => {
+                    queue.lock().push_back(msg);
+                    Ok(())
+                },
+                None => Err(msg)
+            }
+        }
+    }
+
+    pub struct Receiver<T> {
+        queue: Arc<Queue<T>>
+    }
+
+    impl<T> Receiver<T> {
+        // Get the next message, if there is one.
+        pub fn try_recv(&self) -> Option<T> {
+            self.queue.lock().pop_front()
+        }
+
+        // Iterate over all the messages that are waiting.
+        pub fn try_iter(&self) -> impl Iterator<Item = T> + '_ {
+            core::iter::from_fn(move || self.try_recv())
+        }
+    }
+}
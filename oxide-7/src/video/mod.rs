@@ -3,29 +3,73 @@
 
 mod ram;
 mod render;
+#[cfg(test)]
+mod tests;
 
-use std::sync::{
-    Arc,
-    Mutex
+use alloc::{
+    boxed::Box,
+    string::String,
+    vec::Vec
 };
 
 use bitflags::bitflags;
+use serde::{
+    Serialize,
+    Deserialize
+};
 
 use crate::{
     common::Interrupt,
+    rng::Rng,
+    sync::{
+        Arc,
+        Mutex
+    },
     constants::{
+        machine::{
+            MachineTiming,
+            Region
+        },
         timing,
         screen
     },
+    savestate::{
+        Stateful,
+        StateWriter,
+        StateReader
+    }
 };
 
-use ram::VideoMem;
-pub use render::RenderTarget;
+#[cfg(feature = "debug")]
+use crate::debug::IndexedImage;
+
+use ram::{
+    PortWrite,
+    VideoMem
+};
+pub use render::{
+    RenderTarget,
+    blend_hires
+};
 
 type VRamRef = Arc<Mutex<VideoMem>>;
 
+/// Receives every write that reaches video memory through the PPU data ports, including writes by DMA.
+/// Addresses are byte addresses into each memory, and the scanline is the line the write happened on.
+/// Writes to VRAM outside of V-Blank and forced blank are ignored by the PPU, so they aren't included.
+pub trait VideoMemMonitor {
+    /// A byte was written to VRAM, through $2118 or $2119.
+    fn vram_write(&mut self, _addr: u16, _data: u8, _scanline: u16) {}
+    /// A byte was written to OAM, through $2104. Addresses $200-$21F are the high table.
+    /// Bytes in the low table are written in pairs, when the second byte of each pair is written.
+    fn oam_write(&mut self, _addr: u16, _data: u8, _scanline: u16) {}
+    /// A byte was written to CGRAM, through $2122.
+    /// Bytes are written in pairs, when the second byte of each colour is written.
+    fn cgram_write(&mut self, _addr: u16, _data: u8, _scanline: u16) {}
+}
+
 bitflags! {
-    #[derive(Default)]
+    #[derive(Default, Serialize, Deserialize)]
     struct IntEnable: u8 {
         const ENABLE_NMI    = bit!(7);
         const ENABLE_IRQ_Y  = bit!(5);
@@ -41,7 +85,7 @@ impl IntEnable {
 }
 
 bitflags! {
-    #[derive(Default)]
+    #[derive(Default, Serialize, Deserialize)]
     struct PPUStatus: u8 {
         const V_BLANK = bit!(7);
         const H_BLANK = bit!(6);
@@ -49,7 +93,7 @@ bitflags! {
 }
 
 // Signal from the PPU.
-#[derive(PartialEq)]
+#[derive(Debug, PartialEq)]
 pub enum PPUSignal {
     None,           // No signal.
     Int(Interrupt), // Interrupt(s) or VBlank triggered:
@@ -62,7 +106,7 @@ pub enum PPUSignal {
 }
 
 // PPU internal state
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
 enum PPUState {
     HBlankLeft,         // The left side of the screen, before drawing begins.
     DrawingBeforePause, // Drawing the line.
@@ -103,13 +147,31 @@ pub struct PPU {
     h_cycle:        usize,  // Cycle into line to fire IRQ on.
     v_timer:        u16,    // $4209-a, for triggering IRQ.
     h_irq_latch:    bool,   // Latched if the horizontal IRQ is triggered.
-
-    renderer:       render::RenderThread,
+    nmi_pending:    bool,   // Set if NMI was enabled during V-Blank.
+    overscan:       bool,   // Copy of $2133 bit 2: V-Blank starts after line 239 instead of 224.
+    output_overscan:    bool,   // Draw lines 225-239 into the frame.
+    num_scanlines:  usize,  // Lines in a frame, including V-Blank.
+    pal:            bool,   // Region reported to the game in $213F.
+
+    renderer:       render::LineRenderer,
+    skip_render:    bool,   // Don't draw anything this frame.
+    mem_monitor:    Option<Box<dyn VideoMemMonitor>>,
+
+    blank_accuracy: bool,   // Emulate power-on garbage, sprites missing after forced blank, and data port reads during active display.
+    line_blank:     bool,   // Forced blank was on during this line, so no sprites are evaluated for the next.
+
+    #[cfg(feature = "debug")]
+    sprite_lines:       Vec<crate::debug::SpriteLineState>, // Captured each line of the current frame.
+    #[cfg(feature = "debug")]
+    sprite_last_frame:  Vec<crate::debug::SpriteLineState>,
 }
 
 impl PPU {
-    pub fn new() -> Self {
-        let mem = Arc::new(Mutex::new(VideoMem::new()));
+    pub fn new(timing: &MachineTiming) -> Self {
+        let pal = timing.region == Region::PAL;
+        let mut video_mem = VideoMem::new();
+        video_mem.set_pal(pal);
+        let mem = Arc::new(Mutex::new(video_mem));
 
         PPU {
             state:          PPUState::VBlank,
@@ -126,8 +188,23 @@ impl PPU {
             h_cycle:        0,
             v_timer:        0,
             h_irq_latch:    false,
-
-            renderer:       render::RenderThread::new(mem),
+            nmi_pending:    false,
+            overscan:       false,
+            output_overscan:    false,
+            num_scanlines:  timing.num_scanlines,
+            pal:            pal,
+
+            renderer:       render::LineRenderer::new(mem),
+            skip_render:    false,
+            mem_monitor:    None,
+
+            blank_accuracy: false,
+            line_blank:     true,
+
+            #[cfg(feature = "debug")]
+            sprite_lines:       Vec::new(),
+            #[cfg(feature = "debug")]
+            sprite_last_frame:  Vec::new(),
         }
     }
 
@@ -135,13 +212,91 @@ impl PPU {
         self.renderer.start_frame(frame);
     }
 
+    // Wait for the frame to be fully drawn.
+    // Returns the hashes of the threaded and single-threaded frames if verification found a difference.
+    pub fn end_frame(&mut self) -> Option<(u64, u64)> {
+        self.renderer.end_frame()
+    }
+
+    // Draw each frame on this thread as well as the render thread, and compare them.
+    #[cfg(feature = "std")]
+    pub fn set_render_verification(&mut self, enable: bool) {
+        self.renderer.set_verification(enable);
+    }
+
+    pub fn set_sprite_limit(&mut self, enable: bool) {
+        self.renderer.set_sprite_limit(enable);
+    }
+
+    // Fill video memory and registers with garbage, as at power on, and leave forced blank off
+    // so the first frame shows it. Also hides sprites on lines after forced blank,
+    // and restricts reading the data ports during active display.
+    pub fn set_power_on_garbage(&mut self, enable: bool, rng: &mut Rng) {
+        self.blank_accuracy = enable;
+        if enable {
+            self.randomize(rng);
+        }
+    }
+
+    // The frame continues to be emulated, but no lines are drawn.
+    #[cfg(feature = "std")]
+    pub fn set_skip_render(&mut self, skip: bool) {
+        self.skip_render = skip;
+    }
+
+    pub fn set_widescreen(&mut self, extra: usize) {
+        self.renderer.set_widescreen(extra);
+    }
+
+    // Draw the extra lines of the overscan mode. The frame must be 239 lines tall.
+    pub fn set_overscan_output(&mut self, enable: bool) {
+        self.output_overscan = enable;
+    }
+
+    pub fn overscan_output(&self) -> bool {
+        self.output_overscan
+    }
+
+    // Report a different region to the game than the one the timing is for.
+    pub fn set_region_flag(&mut self, region: Region) {
+        self.pal = region == Region::PAL;
+        self.mem.lock().set_pal(self.pal);
+    }
+
+    // Set a monitor to receive all writes to VRAM, OAM and CGRAM.
+    pub fn set_mem_monitor(&mut self, monitor: Option<Box<dyn VideoMemMonitor>>) -> Option<Box<dyn VideoMemMonitor>> {
+        core::mem::replace(&mut self.mem_monitor, monitor)
+    }
+
     // Memory access from CPU / B Bus
-    pub fn read_mem(&mut self, addr: u8) -> u8 {
-        self.mem.lock().unwrap().read(addr)
+    // Open bus is the last value on the CPU data bus.
+    // With blanking accuracy, the data ports return what the PPU is accessing during active display.
+    pub fn read_mem(&mut self, addr: u8, open_bus: u8) -> u8 {
+        let mut mem = self.mem.lock();
+        mem.set_access_cycle(self.access_cycle());
+        mem.read(addr, open_bus)
     }
 
     pub fn write_mem(&mut self, addr: u8, data: u8) {
-        self.mem.lock().unwrap().write(addr, data);
+        match addr {
+            0x00 => self.write_inidisp(data),
+            0x33 => {
+                self.overscan = test_bit!(data, 2, u8);
+                self.mem.lock().write(addr, data);
+            },
+            _ => {
+                let mut mem = self.mem.lock();
+                mem.set_access_cycle(self.access_cycle());
+                let written = mem.write(addr, data);
+                if let Some(monitor) = self.mem_monitor.as_mut() {
+                    notify_write(monitor.as_mut(), addr, written, self.scanline as u16);
+                }
+            },
+        }
+    }
+
+    fn access_cycle(&self) -> Option<usize> {
+        if self.blank_accuracy {Some(self.cycle_count)} else {None}
     }
 
     // Misc
@@ -149,12 +304,24 @@ impl PPU {
         self.status.bits()
     }
 
-    pub fn latch_hv(&mut self) -> u8 {
-        self.mem.lock().unwrap().set_latched_hv(
+    pub fn latch_hv(&mut self) {
+        self.mem.lock().set_latched_hv(
             (self.cycle_count / timing::DOT_TIME) as u16,   // H
             self.scanline as u16                            // V
         );
-        0
+    }
+
+    // Copies of video memory.
+    pub fn copy_vram(&self) -> Vec<u8> {
+        self.mem.lock().get_vram().to_vec()
+    }
+
+    pub fn copy_cgram(&self) -> Vec<u8> {
+        self.mem.lock().get_cgram().to_vec()
+    }
+
+    pub fn copy_oam(&self) -> Vec<u8> {
+        self.mem.lock().get_oam_bytes()
     }
 
     // Timing
@@ -167,9 +334,9 @@ impl PPU {
             DrawingBeforePause  if self.cycle_count >= timing::PAUSE_START  => Some(PPUTransition::CPUPause),
             DrawingAfterPause   if self.cycle_count >= timing::H_BLANK_TIME => Some(PPUTransition::EnterHBlank),
             HBlankRight         if self.cycle_count >= timing::SCANLINE     => Some(PPUTransition::NextLine),
-            HBlankLeft          if self.scanline > screen::V_RES            => Some(PPUTransition::EnterVBlank),
+            HBlankLeft          if self.scanline > self.v_res()             => Some(PPUTransition::EnterVBlank),
             HBlankLeft          if (self.cycle_count >= timing::SCANLINE_OFFSET)
-                                && (self.scanline <= screen::V_RES)         => Some(PPUTransition::ExitHBlank),
+                                && (self.scanline <= self.v_res())          => Some(PPUTransition::ExitHBlank),
             VBlank              if self.cycle_count >= timing::SCANLINE     => Some(PPUTransition::NextLine),
             _ => None
         };
@@ -180,7 +347,7 @@ impl PPU {
             PPUSignal::None
         };
 
-        if signal == PPUSignal::None {
+        let signal = if signal == PPUSignal::None {
             if self.check_x_irq() {
                 self.h_irq_latch = true;
                 self.trigger_irq()
@@ -189,12 +356,44 @@ impl PPU {
             }
         } else {
             signal
+        };
+
+        // A late NMI can go out alongside other interrupts.
+        // If another signal is emitted, it is held until the next clock.
+        match signal {
+            PPUSignal::None if self.nmi_pending => {
+                self.nmi_pending = false;
+                PPUSignal::Int(Interrupt::NMI)
+            },
+            PPUSignal::Int(i) if self.nmi_pending => {
+                self.nmi_pending = false;
+                PPUSignal::Int(i | Interrupt::NMI)
+            },
+            _ => signal
         }
     }
 
     // Interrupts
+    // Changes take effect immediately:
+    // Enabling NMI during V-Blank before $4210 is read triggers NMI straight away.
+    // Enabling H-IRQ after the H timer has passed won't trigger until the next line.
+    // Disabling both IRQs acknowledges any pending IRQ.
     pub fn set_int_enable(&mut self, data: u8) {
-        self.int_enable = IntEnable::from_bits_truncate(data);
+        let int_enable = IntEnable::from_bits_truncate(data);
+
+        let nmi_enabled = int_enable.contains(IntEnable::ENABLE_NMI) && !self.int_enable.contains(IntEnable::ENABLE_NMI);
+        if nmi_enabled && test_bit!(self.nmi_flag, 7, u8) && (self.state == PPUState::VBlank) {
+            self.nmi_pending = true;
+        } else if !int_enable.contains(IntEnable::ENABLE_NMI) {
+            self.nmi_pending = false;
+        }
+
+        if !int_enable.intersects(IntEnable::all_irq()) {
+            self.irq_flag = 0;
+        }
+        self.h_irq_latch |= self.cycle_count >= self.h_cycle;
+
+        self.int_enable = int_enable;
     }
 
     pub fn set_h_timer_lo(&mut self, data: u8) {
@@ -218,11 +417,54 @@ impl PPU {
     }
 
     pub fn get_nmi_flag(&mut self) -> u8 {
-        std::mem::replace(&mut self.nmi_flag, 0)
+        core::mem::replace(&mut self.nmi_flag, 0)
     }
 
     pub fn get_irq_flag(&mut self) -> u8 {
-        std::mem::replace(&mut self.irq_flag, 0)
+        core::mem::replace(&mut self.irq_flag, 0)
+    }
+}
+
+impl Stateful for PPU {
+    fn save_state(&self, w: &mut StateWriter) {
+        w.write(&*self.mem.lock());
+        w.write(&self.state);
+        w.write(&self.cycle_count);
+        w.write(&self.scanline);
+        w.write(&self.int_enable);
+        w.write(&self.status);
+        w.write(&self.nmi_flag);
+        w.write(&self.irq_flag);
+        w.write(&self.h_timer);
+        w.write(&self.h_cycle);
+        w.write(&self.v_timer);
+        w.write(&self.h_irq_latch);
+        w.write(&self.nmi_pending);
+        w.write(&self.line_blank);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), String> {
+        {
+            let mut mem = self.mem.lock();
+            r.read_into(&mut *mem)?;
+            mem.set_all_dirty();
+            mem.set_pal(self.pal);
+            self.overscan = mem.get_window_registers().use_overscan();
+        }
+        r.read_into(&mut self.state)?;
+        r.read_into(&mut self.cycle_count)?;
+        r.read_into(&mut self.scanline)?;
+        r.read_into(&mut self.int_enable)?;
+        r.read_into(&mut self.status)?;
+        r.read_into(&mut self.nmi_flag)?;
+        r.read_into(&mut self.irq_flag)?;
+        r.read_into(&mut self.h_timer)?;
+        r.read_into(&mut self.h_cycle)?;
+        r.read_into(&mut self.v_timer)?;
+        r.read_into(&mut self.h_irq_latch)?;
+        r.read_into(&mut self.nmi_pending)?;
+        r.read_into(&mut self.line_blank)?;
+        Ok(())
     }
 }
 
@@ -239,15 +481,32 @@ enum PPUTransition {
 
 // Internal
 impl PPU {
+    // The last visible line.
+    fn v_res(&self) -> usize {
+        if self.overscan {screen::V_RES_OVERSCAN} else {screen::V_RES}
+    }
+
+    // The last line drawn into the frame.
+    fn output_v_res(&self) -> usize {
+        if self.output_overscan {self.v_res()} else {screen::V_RES}
+    }
+
     // Transition to the appropriate state and emit a relevant signal.
     fn transition_state(&mut self, transition: PPUTransition) -> PPUSignal {
         use PPUTransition::*;
         match transition {
             ExitVBlank => {
+                self.mem.lock().toggle_field();
                 self.nmi_flag = 0;
                 self.irq_flag = 0;
+                self.nmi_pending = false;
                 self.toggle_vblank(false);
                 self.toggle_hblank(false);
+                self.line_blank = self.mem.lock().get_bg_registers().in_fblank();
+                #[cfg(feature = "debug")]
+                {
+                    self.sprite_last_frame = core::mem::take(&mut self.sprite_lines);
+                }
                 self.state = PPUState::DrawingBeforePause;
                 PPUSignal::FrameStart
             },
@@ -265,8 +524,8 @@ impl PPU {
                 self.h_irq_latch = false;
                 self.scanline += 1;
 
-                if self.scanline >= screen::NUM_SCANLINES {
-                    self.scanline -= screen::NUM_SCANLINES;
+                if self.scanline >= self.num_scanlines {
+                    self.scanline -= self.num_scanlines;
                 }
 
                 if self.state == PPUState::HBlankRight {
@@ -281,7 +540,15 @@ impl PPU {
             },
             ExitHBlank => {
                 self.toggle_hblank(false);
-                self.renderer.draw_line((self.scanline - 1) as usize);
+                if self.blank_accuracy {
+                    self.start_obj_line();
+                }
+                #[cfg(feature = "debug")]
+                self.capture_sprite_line();
+                // Overscan lines are only output if enabled.
+                if self.scanline <= self.output_v_res() && !self.skip_render {
+                    self.renderer.draw_line((self.scanline - 1) as usize);
+                }
                 self.state = PPUState::DrawingBeforePause;
                 PPUSignal::None
             },
@@ -291,10 +558,17 @@ impl PPU {
                 self.toggle_hblank(false);
 
                 {
-                    let mut mem = self.mem.lock().unwrap();
+                    let mut mem = self.mem.lock();
                     mem.oam_reset();
                 }
 
+                // The frame has room for overscan lines, but the game didn't use them.
+                if self.output_overscan && !self.overscan && !self.skip_render {
+                    for y in screen::V_RES..screen::V_RES_OVERSCAN {
+                        self.renderer.blank_line(y);
+                    }
+                }
+
                 self.state = PPUState::VBlank;
                 self.trigger_nmi()
             },
@@ -331,7 +605,7 @@ impl PPU {
     fn trigger_nmi(&mut self) -> PPUSignal {
         self.nmi_flag |= bit!(7);
         if self.int_enable.contains(IntEnable::ENABLE_NMI) {
-            PPUSignal::Int(Interrupt::NMI)
+            PPUSignal::Int(Interrupt::NMI | Interrupt::VBLANK)
         } else {
             PPUSignal::Int(Interrupt::VBLANK)
         }
@@ -345,9 +619,191 @@ impl PPU {
     // Toggle blanking modes.
     fn toggle_vblank(&mut self, vblank: bool) {
         self.status.set(PPUStatus::V_BLANK, vblank);
+        self.mem.lock().set_vblank(vblank);
+    }
+
+    // The line is drawn when it starts, so if forced blank is turned on or off part way through,
+    // the rest of the line needs to be cleared or redrawn.
+    fn write_inidisp(&mut self, data: u8) {
+        let was_blank = {
+            let mut mem = self.mem.lock();
+            let was_blank = mem.get_bg_registers().in_fblank();
+            mem.write(0x00, data);
+            was_blank
+        };
+        let blank = test_bit!(data, 7, u8);
+        self.line_blank |= blank;
+        let drawing = (self.state == PPUState::DrawingBeforePause) || (self.state == PPUState::DrawingAfterPause);
+        if drawing && !self.skip_render && (blank != was_blank) && (self.scanline >= 1) && (self.scanline <= self.output_v_res()) {
+            let y = self.scanline - 1;
+            let x = core::cmp::min(self.cycle_count.saturating_sub(timing::SCANLINE_OFFSET) / timing::DOT_TIME, screen::H_RES);
+            if blank {
+                self.renderer.blank_line_from(y, x);
+            } else {
+                self.renderer.draw_line_from(y, x);
+            }
+        }
     }
 
     fn toggle_hblank(&mut self, hblank: bool) {
         self.status.set(PPUStatus::H_BLANK, hblank);
     }
+
+    // Sprites for a line are evaluated during the line before.
+    // This doesn't happen in forced blank, so the first line after it has no sprites.
+    fn start_obj_line(&mut self) {
+        let mut mem = self.mem.lock();
+        mem.set_hide_objects(self.line_blank);
+        self.line_blank = mem.get_bg_registers().in_fblank();
+    }
+
+    // Fill VRAM, CGRAM, OAM and the PPU registers with random data.
+    fn randomize(&mut self, rng: &mut Rng) {
+        self.write_mem(0x00, 0x80);
+
+        let mut vram = vec![0; 0x10000];
+        rng.fill(&mut vram);
+        self.write_mem(0x15, 0x80);
+        self.write_mem(0x16, 0);
+        self.write_mem(0x17, 0);
+        for word in vram.chunks(2) {
+            self.write_mem(0x18, word[0]);
+            self.write_mem(0x19, word[1]);
+        }
+
+        let mut oam = vec![0; 544];
+        rng.fill(&mut oam);
+        self.write_mem(0x02, 0);
+        self.write_mem(0x03, 0);
+        for data in oam {
+            self.write_mem(0x04, data);
+        }
+
+        let mut cgram = vec![0; 512];
+        rng.fill(&mut cgram);
+        self.write_mem(0x21, 0);
+        for data in cgram {
+            self.write_mem(0x22, data);
+        }
+
+        // Skip the data ports, and $2133 which changes the frame size.
+        for addr in 0x01..=0x32 {
+            match addr {
+                0x04 | 0x18 | 0x19 | 0x22 => {},
+                _ => self.write_mem(addr, rng.next_u64() as u8),
+            }
+        }
+        self.write_mem(0x00, 0x0F);
+    }
+}
+
+// Debug
+#[cfg(feature = "debug")]
+impl PPU {
+    // Get the current position as (dot, scanline).
+    pub fn get_hv(&self) -> (u16, u16) {
+        ((self.cycle_count / timing::DOT_TIME) as u16, self.scanline as u16)
+    }
+
+    // Move to the start of the dot and scanline provided.
+    // The internal state and status flags are set to match the new position.
+    // Any pending H-IRQ latch is cleared.
+    pub fn set_hv(&mut self, dot: u16, scanline: u16) {
+        self.cycle_count = core::cmp::min((dot as usize) * timing::DOT_TIME, timing::SCANLINE - 1);
+        self.scanline = (scanline as usize) % self.num_scanlines;
+        self.h_irq_latch = false;
+
+        self.state = if self.scanline > self.v_res() {
+            PPUState::VBlank
+        } else if (self.scanline > 0) && (self.cycle_count < timing::SCANLINE_OFFSET) {
+            PPUState::HBlankLeft
+        } else if self.cycle_count < timing::PAUSE_START {
+            PPUState::DrawingBeforePause
+        } else if self.cycle_count < timing::H_BLANK_TIME {
+            PPUState::DrawingAfterPause
+        } else {
+            PPUState::HBlankRight
+        };
+
+        self.toggle_vblank(self.state == PPUState::VBlank);
+        self.toggle_hblank((self.state == PPUState::HBlankLeft) || (self.state == PPUState::HBlankRight));
+    }
+
+    // Decode an entire background map into an indexed image, using the current mode and CGRAM.
+    // bg is 0-3 for BG1-4.
+    // Returns None if the background isn't used in the current mode.
+    pub fn export_bg_map(&self, bg: usize) -> Option<IndexedImage> {
+        let bg = *BG::all().get(bg)?;
+        let mem = self.mem.lock();
+        render::export::bg_map(&mem, bg).map(|(width, height, pixels)| IndexedImage {
+            width:      width,
+            height:     height,
+            pixels:     pixels,
+            palette:    render::export::palette(&mem),
+        })
+    }
+
+    // Decode both object name tables into an indexed image, using one of the object palettes.
+    pub fn export_obj_tables(&self, palette: u8) -> IndexedImage {
+        let mem = self.mem.lock();
+        let (width, height, pixels) = render::export::obj_tables(&mem, palette);
+        IndexedImage {
+            width:      width,
+            height:     height,
+            pixels:     pixels,
+            palette:    render::export::palette(&mem),
+        }
+    }
+
+    // Force the priority bit of all tiles in a background on or off. bg is 0-3 for BG1-4.
+    pub fn set_bg_priority_override(&mut self, bg: usize, priority: Option<bool>) {
+        self.renderer.set_bg_priority_override(bg, priority);
+    }
+
+    // Composite a single pixel again, to find where its colour came from.
+    pub fn explain_pixel(&self, x: usize, y: usize) -> Option<crate::debug::PixelExplanation> {
+        let mem = self.mem.lock();
+        render::explain::explain_pixel(&mem, x, y)
+    }
+
+    // Objects evaluated for each visible line of the last complete frame, starting at line 1.
+    pub fn sprite_line_states(&self) -> Vec<crate::debug::SpriteLineState> {
+        self.sprite_last_frame.clone()
+    }
+
+    fn capture_sprite_line(&mut self) {
+        if self.scanline > self.v_res() {
+            return;
+        }
+        let mem = self.mem.lock();
+        let mut state = crate::debug::SpriteLineState::default();
+        if !mem.hide_objects() {
+            for (n, i) in mem.objects_on_line(self.scanline as u8).enumerate() {
+                if n < render::SPRITES_PER_LINE {
+                    state.selected.push(i as u8);
+                } else {
+                    state.dropped.push(i as u8);
+                }
+            }
+        }
+        self.sprite_lines.push(state);
+    }
+}
+
+// Pass the bytes written through a data port on to the monitor.
+fn notify_write(monitor: &mut dyn VideoMemMonitor, port: u8, written: PortWrite, scanline: u16) {
+    let mut write = |addr: u16, data: u8| match port {
+        0x04 => monitor.oam_write(addr, data, scanline),
+        0x18 | 0x19 => monitor.vram_write(addr, data, scanline),
+        0x22 => monitor.cgram_write(addr, data, scanline),
+        _ => {},
+    };
+    match written {
+        PortWrite::None => {},
+        PortWrite::Byte(addr, data) => write(addr, data),
+        PortWrite::Word(addr, lo, hi) => {
+            write(addr, lo);
+            write(addr + 1, hi);
+        },
+    }
 }
@@ -1,12 +1,17 @@
 // Other video registers, for BG settings
 
+use alloc::vec::Vec;
 use bitflags::bitflags;
+use serde::{
+    Serialize,
+    Deserialize
+};
 use fixed::types::I8F8;
 
 use crate::video::BG;
 
 bitflags! {
-    #[derive(Default)]
+    #[derive(Default, Serialize, Deserialize)]
     pub struct ScreenDisplay: u8 {
         const F_BLANK       = bit!(7);
         const BRIGHTNESS    = bits![3, 2, 1, 0];
@@ -14,7 +19,7 @@ bitflags! {
 }
 
 bitflags! {
-    #[derive(Default)]
+    #[derive(Default, Serialize, Deserialize)]
     struct ObjectSettings: u8 {
         const SIZE      = bits![7, 6, 5];
         const SELECT    = bits![4, 3];
@@ -23,7 +28,7 @@ bitflags! {
 }
 
 bitflags! {
-    #[derive(Default)]
+    #[derive(Default, Serialize, Deserialize)]
     pub struct BGMode: u8 {
         const BG4_TILE_SIZE = bit!(7);
         const BG3_TILE_SIZE = bit!(6);
@@ -36,7 +41,7 @@ bitflags! {
 
 // BG Register bits.
 bitflags! {
-    #[derive(Default)]
+    #[derive(Default, Serialize, Deserialize)]
     pub struct BGReg: u8 {
         const ADDR      = bits![7, 6, 5, 4, 3, 2];
         const MIRROR_Y  = bit!(1);
@@ -65,7 +70,7 @@ impl From<BGReg> for MapMirror {
 }
 
 bitflags! {
-    #[derive(Default)]
+    #[derive(Default, Serialize, Deserialize)]
     struct Mosaic: u8 {
         const PIXEL_SIZE = bits![7, 6, 5, 4];
         const BG4_ENABLE = bit!(3);
@@ -76,7 +81,7 @@ bitflags! {
 }
 
 bitflags! {
-    #[derive(Default)]
+    #[derive(Default, Serialize, Deserialize)]
     struct Mode7Settings: u8 {
         const FIELD_SIZE    = bit!(7);
         const EMPTY_FILL    = bit!(6);
@@ -105,6 +110,7 @@ impl From<Mode7Settings> for Mode7Extend {
 
 const BG_SCROLL_MASK: u16 = 0x3FF;
 
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Registers {
 
         screen_display:     ScreenDisplay,
@@ -292,6 +298,9 @@ impl Registers {
     }
 
     // Getters (CPU side)
+    // Signed matrix A * signed last byte written to matrix B.
+    // Each write puts the new byte in the top half of the register, so this is always the top byte of B.
+    // The result is derived from the registers on read, so it reflects every write immediately.
     pub fn read_mult_result_lo(&self) -> u8 {
         let a = (self.mode7_matrix_a as i16) as i32;
         let b = (hi!(self.mode7_matrix_b) as i8) as i32;
@@ -504,23 +513,23 @@ impl Registers {
     pub fn get_vram_pattern_regions(&self) -> Vec<(u16, u16)> {
         const ROW_HEIGHT_2BPP: usize = 16 * 16;
         const ROW_HEIGHT_4BPP: usize = 16 * 32;
-        const VRAM_MAX: usize = std::u16::MAX as usize;
+        const VRAM_MAX: usize = core::u16::MAX as usize;
 
         let mode = self.get_mode();
         let mut regions = Vec::new();
 
         let obj0_pattern_start = self.obj0_pattern_addr();
-        let obj0_pattern_end = std::cmp::min((obj0_pattern_start as usize) + (16 * ROW_HEIGHT_4BPP) - 1, VRAM_MAX) as u16;
+        let obj0_pattern_end = core::cmp::min((obj0_pattern_start as usize) + (16 * ROW_HEIGHT_4BPP) - 1, VRAM_MAX) as u16;
         regions.push((obj0_pattern_start, obj0_pattern_end));
         let objn_pattern_start = self.objn_pattern_addr();
-        let objn_pattern_end = std::cmp::min((objn_pattern_start as usize) + (16 * ROW_HEIGHT_4BPP) - 1, VRAM_MAX) as u16;
+        let objn_pattern_end = core::cmp::min((objn_pattern_start as usize) + (16 * ROW_HEIGHT_4BPP) - 1, VRAM_MAX) as u16;
         regions.push((objn_pattern_start, objn_pattern_end));
 
         let bg1_pattern_start = self.bg_pattern_addr(BG::_1);
         let bg1_pattern_end = match mode {
-            0 => std::cmp::min((bg1_pattern_start as usize) + (64 * ROW_HEIGHT_2BPP) - 1, VRAM_MAX) as u16,
-            1 | 2 | 5 | 6 => std::cmp::min((bg1_pattern_start as usize) + (64 * ROW_HEIGHT_4BPP) - 1, VRAM_MAX) as u16,
-            3 | 4 => std::u16::MAX,
+            0 => core::cmp::min((bg1_pattern_start as usize) + (64 * ROW_HEIGHT_2BPP) - 1, VRAM_MAX) as u16,
+            1 | 2 | 5 | 6 => core::cmp::min((bg1_pattern_start as usize) + (64 * ROW_HEIGHT_4BPP) - 1, VRAM_MAX) as u16,
+            3 | 4 => core::u16::MAX,
             _ => bg1_pattern_start,
         };
         regions.push((bg1_pattern_start, bg1_pattern_end));
@@ -528,21 +537,21 @@ impl Registers {
         if mode < 6 {
             let bg2_pattern_start = self.bg_pattern_addr(BG::_2);
             let bg2_pattern_end = match mode {
-                0 | 4 | 5 => std::cmp::min((bg2_pattern_start as usize) + (64 * ROW_HEIGHT_2BPP) - 1, VRAM_MAX) as u16,
-                _ => std::cmp::min((bg2_pattern_start as usize) + (64 * ROW_HEIGHT_4BPP) - 1, VRAM_MAX) as u16,
+                0 | 4 | 5 => core::cmp::min((bg2_pattern_start as usize) + (64 * ROW_HEIGHT_2BPP) - 1, VRAM_MAX) as u16,
+                _ => core::cmp::min((bg2_pattern_start as usize) + (64 * ROW_HEIGHT_4BPP) - 1, VRAM_MAX) as u16,
             };
             regions.push((bg2_pattern_start, bg2_pattern_end));
         }
 
         if mode < 2 {
             let bg3_pattern_start = self.bg_pattern_addr(BG::_3);
-            let bg3_pattern_end = std::cmp::min((bg3_pattern_start as usize) + (64 * ROW_HEIGHT_2BPP) - 1, VRAM_MAX) as u16;
+            let bg3_pattern_end = core::cmp::min((bg3_pattern_start as usize) + (64 * ROW_HEIGHT_2BPP) - 1, VRAM_MAX) as u16;
             regions.push((bg3_pattern_start, bg3_pattern_end));
         }
 
         if mode == 0 {
             let bg4_pattern_start = self.bg_pattern_addr(BG::_4);
-            let bg4_pattern_end = std::cmp::min((bg4_pattern_start as usize) + (64 * ROW_HEIGHT_2BPP) - 1, VRAM_MAX) as u16;
+            let bg4_pattern_end = core::cmp::min((bg4_pattern_start as usize) + (64 * ROW_HEIGHT_2BPP) - 1, VRAM_MAX) as u16;
             regions.push((bg4_pattern_start, bg4_pattern_end));
         }
 
@@ -558,17 +567,17 @@ impl Registers {
         let max_space = 0x10000 - (self.bg_pattern_addr(bg) as u32);
         (match bg {
             BG::_1 => match self.get_mode() {
-                0 => std::cmp::min(1024, max_space / TILE_SIZE_2BPP),
-                1 | 2 | 5 | 6 => std::cmp::min(1024, max_space / TILE_SIZE_4BPP),
-                3 | 4 => std::cmp::min(1024, max_space / TILE_SIZE_8BPP),
+                0 => core::cmp::min(1024, max_space / TILE_SIZE_2BPP),
+                1 | 2 | 5 | 6 => core::cmp::min(1024, max_space / TILE_SIZE_4BPP),
+                3 | 4 => core::cmp::min(1024, max_space / TILE_SIZE_8BPP),
                 _ => 0
             },
             BG::_2 => match self.get_mode() {
-                0 | 4 | 5 => std::cmp::min(1024, max_space / TILE_SIZE_2BPP),
-                _ => std::cmp::min(1024, max_space / TILE_SIZE_4BPP),
+                0 | 4 | 5 => core::cmp::min(1024, max_space / TILE_SIZE_2BPP),
+                _ => core::cmp::min(1024, max_space / TILE_SIZE_4BPP),
             },
-            BG::_3 => std::cmp::min(1024, max_space / TILE_SIZE_2BPP),
-            BG::_4 => std::cmp::min(1024, max_space / TILE_SIZE_2BPP),
+            BG::_3 => core::cmp::min(1024, max_space / TILE_SIZE_2BPP),
+            BG::_4 => core::cmp::min(1024, max_space / TILE_SIZE_2BPP),
         }) as u16
     }
 }
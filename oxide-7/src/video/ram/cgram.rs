@@ -1,5 +1,14 @@
 // CGRAM: contains palette information.
 
+use alloc::boxed::Box;
+use serde::{
+    Serialize,
+    Deserialize
+};
+
+use super::PortWrite;
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct CGRAM {
     data:       Box<[u8]>,
     addr:       u8,
@@ -28,6 +37,11 @@ impl CGRAM {
         self.hi_byte = false;
     }
 
+    // The next access is to the high byte of a colour.
+    pub fn is_hi_byte(&self) -> bool {
+        self.hi_byte
+    }
+
     pub fn read(&mut self) -> u8 {
         let addr = ((self.addr as usize) * 2) + (if self.hi_byte {1} else {0});
 
@@ -43,7 +57,16 @@ impl CGRAM {
         ret
     }
 
-    pub fn write(&mut self, data: u8) {
+    // Read while the PPU is outputting pixels.
+    // The address moves on as normal, but the data comes from the colour the PPU is using.
+    pub fn read_while_drawing(&mut self, colour: u8) -> u8 {
+        let addr = ((colour as usize) * 2) + (if self.hi_byte {1} else {0});
+        self.read();
+        self.data[addr]
+    }
+
+    // The low byte is buffered, and both bytes are written together with the high byte.
+    pub fn write(&mut self, data: u8) -> PortWrite {
         if self.hi_byte {
             let addr = (self.addr as usize) * 2;
 
@@ -57,9 +80,11 @@ impl CGRAM {
 
             self.addr = self.addr.wrapping_add(1);
             self.hi_byte = false;
+            PortWrite::Word(addr as u16, self.buffer, data)
         } else {
             self.buffer = data;
             self.hi_byte = true;
+            PortWrite::None
         }
     }
 
@@ -68,6 +93,12 @@ impl CGRAM {
         &self.data
     }
 
+    #[cfg_attr(not(feature = "std"), allow(dead_code))]
+    pub fn set_all_dirty(&mut self) {
+        self.bg_dirty = true;
+        self.obj_dirty = true;
+    }
+
     pub fn is_bg_dirty(&self) -> bool {
         self.bg_dirty
     }
@@ -6,6 +6,14 @@ mod oam;
 mod vram;
 mod windowregs;
 
+use alloc::vec::Vec;
+use serde::{
+    Serialize,
+    Deserialize
+};
+
+use crate::constants::timing;
+
 pub use bgregs::*;
 use cgram::CGRAM;
 use oam::OAM;
@@ -13,10 +21,24 @@ pub use oam::SpritePriority;
 use vram::VRAM;
 pub use windowregs::{
     Screen,
-    WindowRegisters
+    WindowRegisters,
+    WindowSpans
 };
 
+// Chip versions, reported in $213E (5C77) and $213F (5C78).
+const PPU1_VERSION: u8 = 1;
+const PPU2_VERSION: u8 = 3;
+
+// Bytes that reached VRAM, OAM or CGRAM from a write to a data port.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PortWrite {
+    None,
+    Byte(u16, u8),      // Address, data.
+    Word(u16, u8, u8),  // Address of the low byte, low byte, high byte.
+}
+
 // Struct containing OAM, CGRAM and VRAM.
+#[derive(Clone, Serialize, Deserialize)]
 pub struct VideoMem {
     bgregs:         Registers,
     windowregs:     WindowRegisters,
@@ -27,6 +49,18 @@ pub struct VideoMem {
     h_hi_byte:      bool,
     v_hi_byte:      bool,
 
+    field:          bool,   // Toggles every frame. Used for interlacing.
+    vblank:         bool,   // VRAM can only be written during V-Blank or forced blank.
+    hide_objects:   bool,   // Sprites weren't evaluated for the current line.
+
+    ppu1_open_bus:  u8,     // Last value read from a PPU1 register. Returned by some write-only registers.
+    ppu2_open_bus:  u8,     // Last value read from a PPU2 register.
+    counters_latched:   bool,   // Set when the H/V counters are latched, until $213F is read.
+    #[serde(skip)]
+    access_cycle:   Option<usize>,  // Cycle into the line of the current access, if data port reads during active display are restricted.
+    #[serde(skip)]
+    pal:            bool,   // Region flag in $213F. Set by the PPU, as it isn't part of the state.
+
     oam:            OAM,
     cgram:          CGRAM,
     vram:           VRAM
@@ -44,53 +78,52 @@ impl VideoMem {
             h_hi_byte:  false,
             v_hi_byte:  false,
 
+            field:      false,
+            vblank:     true,
+            hide_objects: false,
+
+            ppu1_open_bus:  0,
+            ppu2_open_bus:  0,
+            counters_latched:   false,
+            access_cycle:   None,
+            pal:            false,
+
             oam:        OAM::new(),
             cgram:      CGRAM::new(),
             vram:       VRAM::new()
         }
     }
 
-    pub fn read(&mut self, addr: u8) -> u8 {
+    // Read a register. Bits that aren't driven by the PPU return open bus:
+    // either the last value read from the same PPU chip, or the last value on the CPU bus.
+    pub fn read(&mut self, addr: u8, open_bus: u8) -> u8 {
         match addr {
-            0x34 => self.bgregs.read_mult_result_lo(),
-            0x35 => self.bgregs.read_mult_result_mid(),
-            0x36 => self.bgregs.read_mult_result_hi(),
-            0x38 => self.oam.read(),
-            0x39 => self.vram.read_lo(),
-            0x3A => self.vram.read_hi(),
-            0x3B => self.cgram.read(),
-            0x3C => if !self.h_hi_byte {
-                self.h_hi_byte = true;
-                lo!(self.h_pos)
-            } else {
-                self.h_hi_byte = false;
-                hi!(self.h_pos)
-            },
-            0x3D => if !self.v_hi_byte {
-                self.v_hi_byte = true;
-                lo!(self.v_pos)
-            } else {
-                self.v_hi_byte = false;
-                hi!(self.v_pos)
+            // Write-only registers on PPU1.
+            0x04..=0x06 | 0x08..=0x0A | 0x14..=0x16 |
+            0x18..=0x1A | 0x24..=0x26 | 0x28..=0x2A => self.ppu1_open_bus,
+
+            0x34..=0x36 | 0x38..=0x3A | 0x3E => {
+                self.ppu1_open_bus = self.read_ppu1(addr);
+                self.ppu1_open_bus
             },
-            0x3E => 1,  // PPU Status
-            0x3F => {   // PPU Status
-                self.h_hi_byte = false;
-                self.v_hi_byte = false;
-                2
+            0x3B..=0x3D | 0x3F => {
+                self.ppu2_open_bus = self.read_ppu2(addr);
+                self.ppu2_open_bus
             },
-            _ => unreachable!()
+            // The rest of the write-only registers, and $2137.
+            _ => open_bus
         }
     }
 
-    pub fn write(&mut self, addr: u8, data: u8) {
+    // Returns the bytes written to VRAM, OAM or CGRAM, if any.
+    pub fn write(&mut self, addr: u8, data: u8) -> PortWrite {
         match addr {
             0x00 => self.bgregs.set_screen_display(data),
 
             0x01 => self.bgregs.set_object_settings(data),
             0x02 => self.oam.set_addr_lo(data),
             0x03 => self.oam.set_addr_hi(data),
-            0x04 => self.oam.write(data),
+            0x04 => return self.oam.write(data),
 
             0x05 => self.bgregs.set_bg_mode(data),
             0x06 => self.bgregs.set_mosaic(data),
@@ -110,10 +143,10 @@ impl VideoMem {
             0x14 => self.bgregs.set_bg4_scroll_y(data),
 
             0x15 => self.vram.set_port_control(data),
-            0x16 => self.vram.set_addr_lo(data),
-            0x17 => self.vram.set_addr_hi(data),
-            0x18 => self.vram.write_lo(data),
-            0x19 => self.vram.write_hi(data),
+            0x16 => self.vram.set_addr_lo(data, self.vram_readable()),
+            0x17 => self.vram.set_addr_hi(data, self.vram_readable()),
+            0x18 => return self.vram.write_lo(data, self.vram_accessible()),
+            0x19 => return self.vram.write_hi(data, self.vram_accessible()),
 
             0x1A => self.bgregs.set_mode7_settings(data),
             0x1B => self.bgregs.set_mode7_matrix_a(data),
@@ -124,7 +157,7 @@ impl VideoMem {
             0x20 => self.bgregs.set_mode7_centre_y(data),
 
             0x21 => self.cgram.set_addr(data),
-            0x22 => self.cgram.write(data),
+            0x22 => return self.cgram.write(data),
             
             0x23 => self.windowregs.set_mask_bg1_2(data), // BG1&2 window
             0x24 => self.windowregs.set_mask_bg3_4(data), // BG3&4 window
@@ -145,12 +178,75 @@ impl VideoMem {
             0x33 => self.windowregs.set_video_select(data),
             _ => unreachable!()
         }
+        PortWrite::None
     }
 
     // Set latched h or v pos.
     pub fn set_latched_hv(&mut self, h: u16, v: u16) {
         self.h_pos = h;
         self.v_pos = v;
+        self.counters_latched = true;
+    }
+
+    fn read_ppu1(&mut self, addr: u8) -> u8 {
+        match addr {
+            0x34 => self.bgregs.read_mult_result_lo(),
+            0x35 => self.bgregs.read_mult_result_mid(),
+            0x36 => self.bgregs.read_mult_result_hi(),
+            0x38 => match self.drawing_cycle() {
+                // Each object is checked over 2 dots.
+                Some(cycle) => self.oam.read_while_drawing((cycle / (timing::DOT_TIME * 2)).min(oam::NUM_OBJECTS - 1)),
+                None => self.oam.read()
+            },
+            0x39 => self.vram.read_lo(self.vram_readable()),
+            0x3A => self.vram.read_hi(self.vram_readable()),
+            0x3E => (self.ppu1_open_bus & bit!(4)) | PPU1_VERSION, // PPU Status
+            _ => unreachable!()
+        }
+    }
+
+    fn read_ppu2(&mut self, addr: u8) -> u8 {
+        match addr {
+            0x3B => {
+                let hi_byte = self.cgram.is_hi_byte();
+                let data = match self.drawing_cycle() {
+                    // The colour of each pixel isn't kept, so the backdrop colour is used.
+                    Some(cycle) if (timing::SCANLINE_OFFSET..timing::H_BLANK_TIME).contains(&cycle) => self.cgram.read_while_drawing(0),
+                    _ => self.cgram.read()
+                };
+                if hi_byte {
+                    (self.ppu2_open_bus & bit!(7)) | (data & 0x7F)
+                } else {
+                    data
+                }
+            },
+            0x3C => if !self.h_hi_byte {
+                self.h_hi_byte = true;
+                lo!(self.h_pos)
+            } else {
+                self.h_hi_byte = false;
+                (self.ppu2_open_bus & 0xFE) | (hi!(self.h_pos) & 1)
+            },
+            0x3D => if !self.v_hi_byte {
+                self.v_hi_byte = true;
+                lo!(self.v_pos)
+            } else {
+                self.v_hi_byte = false;
+                (self.ppu2_open_bus & 0xFE) | (hi!(self.v_pos) & 1)
+            },
+            0x3F => {   // PPU Status
+                // Reading clears the counter latch flag, and resets the H/V counter byte selects.
+                self.h_hi_byte = false;
+                self.v_hi_byte = false;
+                let latched = core::mem::take(&mut self.counters_latched);
+                (if self.field {bit!(7)} else {0}) |
+                (if latched {bit!(6)} else {0}) |
+                (self.ppu2_open_bus & bit!(5)) |
+                (if self.pal {bit!(4)} else {0}) |
+                PPU2_VERSION
+            },
+            _ => unreachable!()
+        }
     }
 
     // OAM address reset that happens at V-blank
@@ -158,6 +254,46 @@ impl VideoMem {
         self.oam.reset();
     }
 
+    // Called at the start of each frame.
+    pub fn toggle_field(&mut self) {
+        self.field = !self.field;
+    }
+
+    pub fn get_field(&self) -> bool {
+        self.field
+    }
+
+    pub fn set_vblank(&mut self, vblank: bool) {
+        self.vblank = vblank;
+    }
+
+    fn vram_accessible(&self) -> bool {
+        self.vblank || self.bgregs.in_fblank()
+    }
+
+    // Set before accessing a register, with the number of master cycles into the line.
+    // If this is None, the data ports can be read at any time.
+    pub fn set_access_cycle(&mut self, cycle: Option<usize>) {
+        self.access_cycle = cycle;
+    }
+
+    pub fn set_pal(&mut self, pal: bool) {
+        self.pal = pal;
+    }
+
+    // The cycle into the line, if the PPU is drawing and the data ports return what the PPU is accessing.
+    fn drawing_cycle(&self) -> Option<usize> {
+        if self.vram_accessible() {
+            None
+        } else {
+            self.access_cycle
+        }
+    }
+
+    fn vram_readable(&self) -> bool {
+        self.drawing_cycle().is_none()
+    }
+
     // Renderer methods to get raw data.
     pub fn get_oam<'a>(&'a self) -> &'a [oam::Object] {
         self.oam.ref_data()
@@ -171,6 +307,31 @@ impl VideoMem {
         self.vram.ref_data()
     }
 
+    // Indices of the objects that cover the given line, in OAM order.
+    // The per-line limit isn't applied here.
+    pub fn objects_on_line(&self, line: u8) -> impl Iterator<Item = usize> + '_ {
+        let (small, large) = self.bgregs.obj_sizes();
+        // With OBJ interlace, objects are half height on screen.
+        let interlace = self.windowregs.use_obj_interlace();
+
+        self.get_oam().iter().enumerate().filter(move |(_, object)| {
+            let size_y = if object.large {large.1} else {small.1};
+            let screen_size_y = if interlace {size_y / 2} else {size_y};
+
+            let bottom_y = object.y.wrapping_add(screen_size_y - 1);
+
+            if bottom_y > object.y {
+                (line >= object.y) && (line <= bottom_y)
+            } else {
+                (line >= object.y) || (line <= bottom_y)
+            }
+        }).map(|(i, _)| i)
+    }
+
+    pub fn get_oam_bytes(&self) -> Vec<u8> {
+        self.oam.to_bytes()
+    }
+
     pub fn get_bg_registers<'a>(&'a self) -> &'a Registers {
         &self.bgregs
     }
@@ -203,4 +364,20 @@ impl VideoMem {
     pub fn vram_set_pattern_regions(&mut self, regions: Vec<(u16, u16)>) {
         self.vram.set_pattern_regions(regions);
     }
+
+    pub fn set_hide_objects(&mut self, hide: bool) {
+        self.hide_objects = hide;
+    }
+
+    pub fn hide_objects(&self) -> bool {
+        self.hide_objects
+    }
+
+    // Force the renderer to rebuild all of its caches.
+    // This is only needed after loading a savestate.
+    #[cfg_attr(not(feature = "std"), allow(dead_code))]
+    pub fn set_all_dirty(&mut self) {
+        self.vram.set_all_dirty();
+        self.cgram.set_all_dirty();
+    }
 }
\ No newline at end of file
@@ -1,12 +1,22 @@
 // OAM (Object Attribute Memory), contains sprite info
 
+use alloc::{
+    boxed::Box,
+    vec::Vec
+};
 use bitflags::bitflags;
+use serde::{
+    Serialize,
+    Deserialize
+};
+
+use super::PortWrite;
 
 const LO_TABLE_SIZE: usize = 512;
-const NUM_OBJECTS: usize = 128;
+pub const NUM_OBJECTS: usize = 128;
 
 bitflags!{
-    #[derive(Default)]
+    #[derive(Default, Serialize, Deserialize)]
     struct ObjectAttributes: u8 {
         const Y_FLIP        = bit!(7);
         const X_FLIP        = bit!(6);
@@ -41,7 +51,7 @@ impl From<ObjectAttributes> for SpritePriority {
 }
 
 // A single object in memory.
-#[derive(Clone, Copy, Default)]
+#[derive(Clone, Copy, Default, Serialize, Deserialize)]
 pub struct Object {
     pub x:          i16,
     pub y:          u8,
@@ -118,6 +128,7 @@ impl Object {
     }
 }
 
+#[derive(Clone, Serialize, Deserialize)]
 pub struct OAM {
     objects:    Box<[Object]>,
 
@@ -173,7 +184,15 @@ impl OAM {
         ret
     }
 
-    pub fn write(&mut self, data: u8) {
+    // Read while the PPU is checking which objects are on the next line.
+    // The address moves on as normal, but the data is the Y coordinate of the object being checked.
+    pub fn read_while_drawing(&mut self, object: usize) -> u8 {
+        self.read();
+        self.read_lo_table((object * 4) + 1)
+    }
+
+    // Writes to the high table go straight in, writes to the low table are buffered into words.
+    pub fn write(&mut self, data: u8) -> PortWrite {
         // Hi table
         if self.addr >= LO_TABLE_SIZE {
             let addr = self.addr % 32;
@@ -182,20 +201,25 @@ impl OAM {
                 
                 self.addr += 2;
                 self.hi_byte = false;
+                PortWrite::Byte((LO_TABLE_SIZE + addr + 1) as u16, data)
             } else {
                 self.write_hi_table(addr, data);
                 self.hi_byte = true;
+                PortWrite::Byte((LO_TABLE_SIZE + addr) as u16, data)
             }
         } else {
             if self.hi_byte {
-                self.write_lo_table(self.addr, self.buffer);
-                self.write_lo_table(self.addr + 1, data);
+                let addr = self.addr;
+                self.write_lo_table(addr, self.buffer);
+                self.write_lo_table(addr + 1, data);
 
                 self.addr += 2;
                 self.hi_byte = false;
+                PortWrite::Word(addr as u16, self.buffer, data)
             } else {
                 self.buffer = data;
                 self.hi_byte = true;
+                PortWrite::None
             }
         }
     }
@@ -208,6 +232,13 @@ impl OAM {
     pub fn ref_data<'a>(&'a self) -> &'a [Object] {
         &self.objects
     }
+
+    // Raw contents, in the layout used by the SNES: the low table followed by the high table.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let lo_table = (0..LO_TABLE_SIZE).map(|addr| self.read_lo_table(addr));
+        let hi_table = (0..(NUM_OBJECTS / 4)).map(|addr| self.read_hi_table(addr));
+        lo_table.chain(hi_table).collect()
+    }
 }
 
 impl OAM {
@@ -1,9 +1,19 @@
 // VRAM: background maps and pattern data.
 
+use alloc::{
+    boxed::Box,
+    vec::Vec
+};
 use bitflags::bitflags;
+use serde::{
+    Serialize,
+    Deserialize
+};
+
+use super::PortWrite;
 
 bitflags! {
-    #[derive(Default)]
+    #[derive(Default, Serialize, Deserialize)]
     struct PortControl: u8 {
         const INC =      bit!(7);
         const REMAP =    bits![3, 2];
@@ -18,6 +28,7 @@ const INC_RATE_32: u8 = 1;
 // Size of VRAM (64kB, or 2^16)
 const VRAM_SIZE: usize = 64 * 1024;
 
+#[derive(Clone, Serialize, Deserialize)]
 pub struct VRAM {
     data:           Box<[u8]>,
 
@@ -45,62 +56,77 @@ impl VRAM {
         self.port_control = PortControl::from_bits_truncate(data);
     }
 
-    pub fn set_addr_lo(&mut self, addr: u8) {
+    // The read buffer is filled from the new address.
+    // If VRAM isn't accessible, it is filled with 0.
+    pub fn set_addr_lo(&mut self, addr: u8, accessible: bool) {
         let old_word_addr = self.byte_addr / 2;
         let new_word_addr = set_lo!(old_word_addr, addr);
         self.byte_addr = new_word_addr * 2;
-        self.do_read();
+        self.do_read(accessible);
     }
 
-    pub fn set_addr_hi(&mut self, addr: u8) {
+    pub fn set_addr_hi(&mut self, addr: u8, accessible: bool) {
         let old_word_addr = self.byte_addr / 2;
         let new_word_addr = set_hi!(old_word_addr, addr);
         self.byte_addr = new_word_addr * 2;
-        self.do_read();
+        self.do_read(accessible);
     }
 
-    pub fn read_lo(&mut self) -> u8 {
+    pub fn read_lo(&mut self, accessible: bool) -> u8 {
         let ret = lo!(self.read_buffer);
 
         if !self.port_control.contains(PortControl::INC) {
-            self.do_read();
+            self.do_read(accessible);
             self.inc_addr();
         }
 
         ret
     }
 
-    pub fn read_hi(&mut self) -> u8 {
+    pub fn read_hi(&mut self, accessible: bool) -> u8 {
         let ret = hi!(self.read_buffer);
 
         if self.port_control.contains(PortControl::INC) {
-            self.do_read();
+            self.do_read(accessible);
             self.inc_addr();
         }
 
         ret
     }
 
-    pub fn write_lo(&mut self, data: u8) {
-        let addr = self.remap_addr();
-        self.data[addr as usize] = data;
+    // Writes during active display are ignored, but the address still increments.
+    pub fn write_lo(&mut self, data: u8, accessible: bool) -> PortWrite {
+        let written = if accessible {
+            let addr = self.remap_addr();
+            self.data[addr as usize] = data;
 
-        self.set_dirty(addr);
+            self.set_dirty(addr);
+            PortWrite::Byte(addr, data)
+        } else {
+            PortWrite::None
+        };
 
         if !self.port_control.contains(PortControl::INC) {
             self.inc_addr();
         }
+        written
     }
 
-    pub fn write_hi(&mut self, data: u8) {
-        let addr = self.remap_addr().wrapping_add(1);
-        self.data[addr as usize] = data;
+    pub fn write_hi(&mut self, data: u8, accessible: bool) -> PortWrite {
+        let written = if accessible {
+            let addr = self.remap_addr().wrapping_add(1);
+            self.data[addr as usize] = data;
 
-        self.set_dirty(addr);
+            self.set_dirty(addr);
+            PortWrite::Byte(addr, data)
+        } else {
+            PortWrite::None
+        };
 
         if self.port_control.contains(PortControl::INC) {
             self.inc_addr();
         }
+        written
     }
 
     // For use by renderer memory caches.
@@ -132,6 +158,13 @@ impl VRAM {
         //println!("Setting regions: {:?}", regions);
         self.pattern_regions = regions.iter().cloned().map(|r| (r, true)).collect::<Vec<_>>();
     }
+
+    #[cfg_attr(not(feature = "std"), allow(dead_code))]
+    pub fn set_all_dirty(&mut self) {
+        for (_, dirty) in self.pattern_regions.iter_mut() {
+            *dirty = true;
+        }
+    }
 }
 
 // Internal
@@ -188,10 +221,14 @@ impl VRAM {
     }
 
     // Read into the buffer.
-    fn do_read(&mut self) {
-        let remapped_addr = self.remap_addr() as usize;
-        let lo = self.data[remapped_addr];
-        let hi = self.data[remapped_addr.wrapping_add(1)];
-        self.read_buffer = make16!(hi, lo);
+    fn do_read(&mut self, accessible: bool) {
+        self.read_buffer = if accessible {
+            let remapped_addr = self.remap_addr() as usize;
+            let lo = self.data[remapped_addr];
+            let hi = self.data[remapped_addr.wrapping_add(1)];
+            make16!(hi, lo)
+        } else {
+            0
+        };
     }
 }
\ No newline at end of file
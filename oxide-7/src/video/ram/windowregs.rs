@@ -1,13 +1,27 @@
 // Video registers for colour math and window settings.
+use core::ops::Range;
+
 use bitflags::bitflags;
+use serde::{
+    Serialize,
+    Deserialize
+};
 
-use crate::video::{
-    BG,
-    render::Colour
+use crate::{
+    constants::screen::H_RES,
+    video::{
+        BG,
+        render::Colour
+    }
+};
+#[cfg(feature = "debug")]
+use crate::debug::{
+    ColourMath,
+    PixelLayer
 };
 
 bitflags! {
-    #[derive(Default)]
+    #[derive(Default, Serialize, Deserialize)]
     struct WindowMaskSettings: u8 {
         const WINDOW_2_ENABLE_HI = bit!(7);
         const WINDOW_2_INVERT_HI = bit!(6);
@@ -21,7 +35,7 @@ bitflags! {
 }
 
 bitflags! {
-    #[derive(Default)]
+    #[derive(Default, Serialize, Deserialize)]
     struct BGMaskLogic: u8 {
         const BG_4_OP = bits![7, 6];
         const BG_3_OP = bits![5, 4];
@@ -31,7 +45,7 @@ bitflags! {
 }
 
 bitflags! {
-    #[derive(Default)]
+    #[derive(Default, Serialize, Deserialize)]
     struct ObjColMaskLogic: u8 {
         const COL_OP = bits![3, 2];
         const OBJ_OP = bits![1, 0];
@@ -39,7 +53,7 @@ bitflags! {
 }
 
 bitflags! {
-    #[derive(Default)]
+    #[derive(Default, Serialize, Deserialize)]
     struct LayerDesignation: u8 {
         const OBJ = bit!(4);
         const BG4 = bit!(3);
@@ -50,7 +64,7 @@ bitflags! {
 }
 
 bitflags! {
-    #[derive(Default)]
+    #[derive(Default, Serialize, Deserialize)]
     struct ColourAddSelect: u8 {
         const CLIP_TO_BLACK = bits![7, 6];
         const PREVENT       = bits![5, 4];
@@ -60,7 +74,7 @@ bitflags! {
 }
 
 bitflags! {
-    #[derive(Default)]
+    #[derive(Default, Serialize, Deserialize)]
     struct ColourMathDesignation: u8 {
         const ADD_SUB   = bit!(7);
         const HALF      = bit!(6);
@@ -74,8 +88,9 @@ bitflags! {
 }
 
 bitflags! {
-    #[derive(Default)]
+    #[derive(Default, Serialize, Deserialize)]
     struct VideoSelect: u8 {
+        const EXT_SYNC          = bit!(7);  // Only used with external video hardware.
         const MODE_7_EXT_BG     = bit!(6);
         const PSEUDO_HIRES      = bit!(3);
         const OVERSCAN          = bit!(2);
@@ -131,6 +146,55 @@ impl WindowOp {
     }
 }
 
+// The window edges split a line into at most 5 spans.
+const MAX_SPANS: usize = 5;
+
+// A run of pixels on a line with the same window result.
+#[derive(Clone, Copy, Default)]
+pub struct WindowSpan {
+    start:      usize,
+    end:        usize,
+    pub value:  bool,   // For layers: the layer shows. For colour math: inside the colour window.
+}
+
+impl WindowSpan {
+    pub fn range(&self) -> Range<usize> {
+        self.start..self.end
+    }
+}
+
+// Spans covering a whole line, in order.
+#[derive(Default)]
+pub struct WindowSpans {
+    spans:  [WindowSpan; MAX_SPANS],
+    len:    usize,
+}
+
+impl WindowSpans {
+    // A single span for the whole line.
+    fn whole_line(value: bool) -> Self {
+        let mut spans = Self::default();
+        spans.push(0, H_RES, value);
+        spans
+    }
+
+    // Add a span after the last one, merging them if they have the same value.
+    fn push(&mut self, start: usize, end: usize, value: bool) {
+        match self.spans[..self.len].last_mut() {
+            Some(last) if last.value == value => last.end = end,
+            _ => {
+                self.spans[self.len] = WindowSpan {start: start, end: end, value: value};
+                self.len += 1;
+            }
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &WindowSpan> {
+        self.spans[..self.len].iter()
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct WindowRegisters {
     mask_bg1_2:         WindowMaskSettings,
     mask_bg3_4:         WindowMaskSettings,
@@ -262,35 +326,33 @@ impl WindowRegisters {
 
     // Getters - renderer side
 
-    // Sets the input bool slice to false if the associated pixel shouldn't be shown.
-    // Assumes an input of true bools.
-    pub fn bg_window(&self, bg: BG, screen: Screen, window_line: &mut [bool]) {
-        if self.enable_bg(bg, screen) {                 // Check if this bg is enabled for the screen.
-            if self.enable_masking_bg(bg, screen) {     // Check if masking is enabled for this background.
-                for (x, w) in window_line.iter_mut().enumerate() {
-                    *w = !self.mask_bg_pixel(bg, x as u8);
-                }
-            }
+    // Spans of the line where the background is shown on the screen.
+    pub fn bg_window_spans(&self, bg: BG, screen: Screen) -> WindowSpans {
+        if !self.enable_bg(bg, screen) {                // Check if this bg is enabled for the screen.
+            WindowSpans::whole_line(false)
+        } else if !self.enable_masking_bg(bg, screen) { // Check if masking is enabled for this background.
+            WindowSpans::whole_line(true)
         } else {
-            for w in window_line.iter_mut() {
-                *w = false;
-            }
+            self.window_spans(|win_1, win_2| !self.mask_bg(bg, win_1, win_2))
         }
     }
 
-    // Returns true if the pixel should be shown for the objects on a screen.
-    pub fn show_obj_pixel(&self, screen: Screen, x: u8) -> bool {
-        if self.enable_obj(screen) {    // Check if objects are enabled for the screen.
-            if self.enable_masking_obj(screen) {    // Check if masking is enabled for objects.
-                !self.mask_obj_pixel(x)
-            } else {
-                true
-            }
+    // Spans of the line where objects are shown on the screen.
+    pub fn obj_window_spans(&self, screen: Screen) -> WindowSpans {
+        if !self.enable_obj(screen) {                   // Check if objects are enabled for the screen.
+            WindowSpans::whole_line(false)
+        } else if !self.enable_masking_obj(screen) {    // Check if masking is enabled for objects.
+            WindowSpans::whole_line(true)
         } else {
-            false
+            self.window_spans(|win_1, win_2| !self.mask_obj(win_1, win_2))
         }
     }
 
+    // Spans of the line that are inside the colour window.
+    pub fn col_window_spans(&self) -> WindowSpans {
+        self.window_spans(|win_1, win_2| self.col_window(win_1, win_2))
+    }
+
     pub fn get_fixed_colour(&self) -> Colour {
         self.fixed_colour
     }
@@ -315,28 +377,39 @@ impl WindowRegisters {
         self.video_select.contains(VideoSelect::MODE_7_EXT_BG)
     }
 
+    // Returns true if 239 lines should be drawn instead of 224.
+    pub fn use_overscan(&self) -> bool {
+        self.video_select.contains(VideoSelect::OVERSCAN)
+    }
+
+    // Returns true if objects should be drawn at half height, using alternate lines each field.
+    pub fn use_obj_interlace(&self) -> bool {
+        self.video_select.contains(VideoSelect::OBJ_INTERLACE)
+    }
+
     // Combine colours.
-    pub fn calc_colour_math_bg(&self, main: Colour, sub: Option<Colour>, bg: BG, x: u8) -> Colour {
+    // col_window is true if the pixel is inside the colour window.
+    pub fn calc_colour_math_bg(&self, main: Colour, sub: Option<Colour>, bg: BG, col_window: bool) -> Colour {
         if self.enable_bg_colour_math(bg) {
-            self.do_colour_math(main, sub, x)
+            self.do_colour_math(main, sub, col_window)
         } else {
             main
         }
     }
 
     // Combine colours.
-    pub fn calc_colour_math_obj(&self, main: Colour, sub: Option<Colour>, x: u8) -> Colour {
+    pub fn calc_colour_math_obj(&self, main: Colour, sub: Option<Colour>, col_window: bool) -> Colour {
         if self.enable_obj_colour_math() {
-            self.do_colour_math(main, sub, x)
+            self.do_colour_math(main, sub, col_window)
         } else {
             main
         }
     }
 
     // Combine colours.
-    pub fn calc_colour_math_backdrop(&self, main: Colour, sub: Option<Colour>, x: u8) -> Colour {
+    pub fn calc_colour_math_backdrop(&self, main: Colour, sub: Option<Colour>, col_window: bool) -> Colour {
         if self.enable_backdrop_colour_math() {
-            self.do_colour_math(main, sub, x)
+            self.do_colour_math(main, sub, col_window)
         } else {
             main
         }
@@ -345,21 +418,44 @@ impl WindowRegisters {
 
 // internal helpers
 impl WindowRegisters {
-    // Returns true if the bg pixel specified is inside the mask.
-    fn mask_bg_pixel(&self, bg: BG, x: u8) -> bool {
+    // Split the line at the window edges, and find the value of each span.
+    // f is called with whether the span is inside window 1 and window 2.
+    fn window_spans(&self, f: impl Fn(bool, bool) -> bool) -> WindowSpans {
+        let mut edges = [
+            0,
+            self.window_1_left as usize,
+            (self.window_1_right as usize) + 1,
+            self.window_2_left as usize,
+            (self.window_2_right as usize) + 1,
+        ];
+        edges.sort_unstable();
+
+        let mut spans = WindowSpans::default();
+        for (i, start) in edges.iter().cloned().enumerate() {
+            let end = edges.get(i + 1).cloned().unwrap_or(H_RES);
+            if start < end {
+                let x = start as u8;
+                spans.push(start, end, f(self.test_inside_window_1(x), self.test_inside_window_2(x)));
+            }
+        }
+        spans
+    }
+
+    // Returns true if a bg pixel inside or outside each window is inside the mask.
+    fn mask_bg(&self, bg: BG, inside_1: bool, inside_2: bool) -> bool {
         let enable_1 = self.enable_window_1_bg(bg);
         let enable_2 = self.enable_window_2_bg(bg);
         match (enable_1, enable_2) {
             (true, true) => {   // Use op to combine
-                let win_1 = self.test_inside_window_1(x) != self.invert_window_1_bg(bg);
-                let win_2 = self.test_inside_window_2(x) != self.invert_window_2_bg(bg);
+                let win_1 = inside_1 != self.invert_window_1_bg(bg);
+                let win_2 = inside_2 != self.invert_window_2_bg(bg);
                 self.window_op_bg(bg).combine(win_1, win_2)
             },
             (true, false) => {  // Just use window 1
-                self.test_inside_window_1(x) != self.invert_window_1_bg(bg)
+                inside_1 != self.invert_window_1_bg(bg)
             },
             (false, true) => {  // Just use window 2
-                self.test_inside_window_2(x) != self.invert_window_2_bg(bg)
+                inside_2 != self.invert_window_2_bg(bg)
             },
             (false, false) => { // No windows enabled for bg.
                 false
@@ -367,21 +463,21 @@ impl WindowRegisters {
         }
     }
 
-    // Returns true if the obj pixel specified is inside the mask.
-    fn mask_obj_pixel(&self, x: u8) -> bool {
+    // Returns true if an obj pixel inside or outside each window is inside the mask.
+    fn mask_obj(&self, inside_1: bool, inside_2: bool) -> bool {
         let enable_1 = self.enable_window_1_obj();
         let enable_2 = self.enable_window_2_obj();
         match (enable_1, enable_2) {
             (true, true) => {   // Use op to combine
-                let win_1 = self.test_inside_window_1(x) != self.invert_window_1_obj();
-                let win_2 = self.test_inside_window_2(x) != self.invert_window_2_obj();
+                let win_1 = inside_1 != self.invert_window_1_obj();
+                let win_2 = inside_2 != self.invert_window_2_obj();
                 self.window_op_obj().combine(win_1, win_2)
             },
             (true, false) => {  // Just use window 1
-                self.test_inside_window_1(x) != self.invert_window_1_obj()
+                inside_1 != self.invert_window_1_obj()
             },
             (false, true) => {  // Just use window 2
-                self.test_inside_window_2(x) != self.invert_window_2_obj()
+                inside_2 != self.invert_window_2_obj()
             },
             (false, false) => { // No windows enabled for objects
                 false
@@ -389,21 +485,21 @@ impl WindowRegisters {
         }
     }
 
-    // Returns true if the colour math pixel specified is inside the colour mask.
-    fn col_window_pixel(&self, x: u8) -> bool {
+    // Returns true if a pixel inside or outside each window is inside the colour window.
+    fn col_window(&self, inside_1: bool, inside_2: bool) -> bool {
         let enable_1 = self.enable_window_1_col();
         let enable_2 = self.enable_window_2_col();
         match (enable_1, enable_2) {
             (true, true) => {   // Use op to combine
-                let win_1 = self.test_inside_window_1(x) != self.invert_window_1_col();
-                let win_2 = self.test_inside_window_2(x) != self.invert_window_2_col();
+                let win_1 = inside_1 != self.invert_window_1_col();
+                let win_2 = inside_2 != self.invert_window_2_col();
                 self.window_op_col().combine(win_1, win_2)
             },
             (true, false) => {  // Just use window 1
-                self.test_inside_window_1(x) != self.invert_window_1_col()
+                inside_1 != self.invert_window_1_col()
             },
             (false, true) => {  // Just use window 2
-                self.test_inside_window_2(x) != self.invert_window_2_col()
+                inside_2 != self.invert_window_2_col()
             },
             (false, false) => { // No windows enabled for colour
                 false
@@ -412,7 +508,7 @@ impl WindowRegisters {
     }
 
     // Returns true if layer is enabled for the screen
-    fn enable_bg(&self, bg: BG, screen: Screen) -> bool {
+    pub fn enable_bg(&self, bg: BG, screen: Screen) -> bool {
         match screen {
             Screen::Main => match bg {
                 BG::_1 => self.main_screen_desg.contains(LayerDesignation::BG1),
@@ -566,7 +662,7 @@ impl WindowRegisters {
     }
 
     // Returns true if main screen pixel should be clipped to black.
-    fn clip_to_black(&self, x: u8) -> bool {
+    fn clip_to_black(&self, col_window: bool) -> bool {
         const CLIP_NEVER: u8 = 0 << 6;
         const CLIP_OUTSIDE: u8 = 1 << 6;
         const CLIP_INSIDE: u8 = 2 << 6;
@@ -574,15 +670,15 @@ impl WindowRegisters {
     
         match (self.colour_add_select & ColourAddSelect::CLIP_TO_BLACK).bits() {
             CLIP_NEVER => false,
-            CLIP_OUTSIDE => !self.col_window_pixel(x),
-            CLIP_INSIDE => self.col_window_pixel(x),
+            CLIP_OUTSIDE => !col_window,
+            CLIP_INSIDE => col_window,
             CLIP_ALWAYS => true,
             _ => unreachable!()
         }
     }
 
     // Returns true if colour math should happen.
-    fn should_do_colour_math(&self, x: u8) -> bool {
+    fn should_do_colour_math(&self, col_window: bool) -> bool {
         const PREVENT_NEVER: u8 = 0 << 4;
         const PREVENT_OUTSIDE: u8 = 1 << 4;
         const PREVENT_INSIDE: u8 = 2 << 4;
@@ -590,18 +686,18 @@ impl WindowRegisters {
     
         match (self.colour_add_select & ColourAddSelect::PREVENT).bits() {
             PREVENT_NEVER => true,
-            PREVENT_OUTSIDE => self.col_window_pixel(x),
-            PREVENT_INSIDE => !self.col_window_pixel(x),
+            PREVENT_OUTSIDE => col_window,
+            PREVENT_INSIDE => !col_window,
             PREVENT_ALWAYS => false,
             _ => unreachable!()
         }
     }
 
-    fn do_colour_math(&self, main: Colour, sub: Option<Colour>, x: u8) -> Colour {
-        let clip_colour = self.clip_to_black(x);
+    fn do_colour_math(&self, main: Colour, sub: Option<Colour>, col_window: bool) -> Colour {
+        let clip_colour = self.clip_to_black(col_window);
         let main_col = if clip_colour {Colour::zero()} else {main};
         let sub_col = sub.unwrap_or(self.get_fixed_colour());
-        if self.should_do_colour_math(x) {
+        if self.should_do_colour_math(col_window) {
             let (r, g, b) = if !self.colour_math_desg.contains(ColourMathDesignation::ADD_SUB) {
                 let i_r = main_col.r as u16 + sub_col.r as u16;
                 let i_g = main_col.g as u16 + sub_col.g as u16;
@@ -635,3 +731,36 @@ impl WindowRegisters {
         }
     }
 }
+
+// Debug
+#[cfg(feature = "debug")]
+impl WindowRegisters {
+    // Find if a main screen pixel from the layer is clipped to black, and the colour math applied to it.
+    // This follows do_colour_math.
+    pub fn explain_colour_math(&self, layer: PixelLayer, sub: Option<Colour>, col_window: bool) -> (bool, Option<ColourMath>) {
+        let enabled = match layer {
+            PixelLayer::BG1 => self.enable_bg_colour_math(BG::_1),
+            PixelLayer::BG2 => self.enable_bg_colour_math(BG::_2),
+            PixelLayer::BG3 => self.enable_bg_colour_math(BG::_3),
+            PixelLayer::BG4 => self.enable_bg_colour_math(BG::_4),
+            PixelLayer::Obj => self.enable_obj_colour_math(),
+            PixelLayer::Backdrop => self.enable_backdrop_colour_math(),
+        };
+        if !enabled {
+            return (false, None);
+        }
+
+        let clip_colour = self.clip_to_black(col_window);
+        if !self.should_do_colour_math(col_window) {
+            return (clip_colour, None);
+        }
+
+        let sub_col = sub.unwrap_or(self.get_fixed_colour());
+        let sub_backdrop = self.colour_add_select.contains(ColourAddSelect::USE_SUB) && sub.is_none();
+        (clip_colour, Some(ColourMath {
+            subtract:   self.colour_math_desg.contains(ColourMathDesignation::ADD_SUB),
+            half:       self.colour_math_desg.contains(ColourMathDesignation::HALF) && !clip_colour && !sub_backdrop,
+            operand:    [sub_col.r, sub_col.g, sub_col.b],
+        }))
+    }
+}
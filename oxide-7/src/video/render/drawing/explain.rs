@@ -0,0 +1,253 @@
+// Compositing a single pixel again, to explain where its colour came from.
+
+use crate::{
+    constants::screen::{
+        H_RES,
+        V_RES,
+        V_RES_OVERSCAN
+    },
+    debug::{
+        LayerPixel,
+        LayerWindow,
+        PixelExplanation,
+        PixelLayer
+    },
+    video::{
+        BG,
+        VideoMem,
+        ram::{
+            Screen,
+            WindowSpans
+        },
+        render::{
+            VideoMode,
+            patternmem::BitsPerPixel
+        }
+    }
+};
+
+use super::{
+    Renderer,
+    lines::{
+        BGData,
+        TileAttributes
+    },
+    types::{
+        LineBuffers,
+        Pixel,
+        SpritePixel
+    }
+};
+
+const OBJ_PALETTE_OFFSET: u8 = 128;
+
+// The layers at a single position on one screen.
+struct ScreenPixels {
+    sprite:     SpritePixel,
+    bgs:        [BGData; 4],
+    mode7_bg1:  Option<u8>,
+    mode7_bg2:  u8,
+}
+
+impl ScreenPixels {
+    fn at(buffers: &LineBuffers, screen: Screen, x: usize) -> Self {
+        let (sprites, bgs, mode7_bg1, mode7_bg2) = match screen {
+            Screen::Main => (&buffers.main_sprites, &buffers.main_bgs, &buffers.main_mode7_bg1, &buffers.main_mode7_bg2),
+            Screen::Sub => (&buffers.sub_sprites, &buffers.sub_bgs, &buffers.sub_mode7_bg1, &buffers.sub_mode7_bg2),
+        };
+        ScreenPixels {
+            sprite:     sprites[x],
+            bgs:        [bgs[0][x], bgs[1][x], bgs[2][x], bgs[3][x]],
+            mode7_bg1:  mode7_bg1[x],
+            mode7_bg2:  mode7_bg2[x],
+        }
+    }
+}
+
+// Composite the pixel at x, y in the frame using the current video memory.
+// Returns None if the pixel is off screen.
+pub fn explain_pixel(mem: &VideoMem, x: usize, y: usize) -> Option<PixelExplanation> {
+    let window_regs = mem.get_window_registers();
+    let v_res = if window_regs.use_overscan() {V_RES_OVERSCAN} else {V_RES};
+    if x >= H_RES || y >= v_res {
+        return None;
+    }
+
+    let mut renderer = Renderer::from_mem(mem);
+    let mut buffers = renderer.line_buffers.take().expect("line buffers in use");
+    renderer.draw_layers(mem, &mut buffers, y);
+
+    let main_pixels = ScreenPixels::at(&buffers, Screen::Main, x);
+    let sub_pixels = ScreenPixels::at(&buffers, Screen::Sub, x);
+    let main = renderer.eval_pixel(mem, &main_pixels);
+    let sub = if window_regs.use_subscreen() {
+        renderer.eval_pixel(mem, &sub_pixels)
+    } else {
+        Pixel::None
+    };
+
+    let main_layer = renderer.layer_pixel(mem, &main_pixels, main);
+    let sub_layer = match sub {
+        Pixel::None => None,
+        _ => Some(renderer.layer_pixel(mem, &sub_pixels, sub)),
+    };
+
+    let col_window = in_window(&window_regs.col_window_spans(), x);
+    let hires_mode = renderer.mode == VideoMode::_5 || renderer.mode == VideoMode::_6;
+    let hires = hires_mode || window_regs.use_pseudo_hires();
+    let (clip_to_black, colour_math) = match main {
+        _ if hires => (false, None),
+        Pixel::ObjLo(_) => (false, None),
+        _ => window_regs.explain_colour_math(main_layer.layer, sub.any(), col_window),
+    };
+
+    let mut windows = [LayerWindow::default(); 5];
+    for (window, bg) in windows.iter_mut().zip(BG::all().iter()) {
+        window.main = in_window(&window_regs.bg_window_spans(*bg, Screen::Main), x);
+        window.sub = in_window(&window_regs.bg_window_spans(*bg, Screen::Sub), x);
+    }
+    windows[4] = LayerWindow {
+        main:   in_window(&window_regs.obj_window_spans(Screen::Main), x),
+        sub:    in_window(&window_regs.obj_window_spans(Screen::Sub), x),
+    };
+
+    // Write the pixel in the same way as the line is drawn.
+    let mut out = [0; 8];
+    if hires_mode {
+        renderer.write_hires_pixel(&mut out, main, sub.any().unwrap_or(window_regs.get_fixed_colour()));
+    } else {
+        renderer.write_pixel(window_regs, &mut out, main, sub.any(), col_window);
+    }
+
+    Some(PixelExplanation {
+        mode:           renderer.mode as u8,
+        hires:          hires,
+        main:           main_layer,
+        sub:            sub_layer,
+        windows:        windows,
+        colour_window:  col_window,
+        clip_to_black:  clip_to_black,
+        colour_math:    colour_math,
+        colour:         [out[4], out[5], out[6]],
+    })
+}
+
+impl Renderer {
+    // A renderer with the tiles and palettes decoded from video memory.
+    // The renderer that draws the frame belongs to the render thread, so a new one is made.
+    fn from_mem(mem: &VideoMem) -> Self {
+        let regs = mem.get_bg_registers();
+        let mut renderer = Renderer::new();
+        renderer.switch_mode(VideoMode::from(regs.get_mode()));
+
+        let num_bgs = renderer.num_bgs();
+        for (bg_pattern, bg) in renderer.bg_pattern_mem.iter_mut().take(num_bgs).zip(BG::all().iter().cloned()) {
+            bg_pattern.set_addr(regs.bg_pattern_addr(bg), regs.get_pattern_table_size(bg));
+            bg_pattern.make_tiles(mem.get_vram());
+        }
+
+        renderer.obj_pattern_mem[0].set_addr_obj(regs.obj0_pattern_addr());
+        renderer.obj_pattern_mem[1].set_addr_obj(regs.objn_pattern_addr());
+        for obj_pattern in renderer.obj_pattern_mem.iter_mut() {
+            obj_pattern.make_tiles(mem.get_vram());
+        }
+
+        renderer.palettes.make_bg_palette(mem);
+        renderer.palettes.make_obj_palette(mem);
+        renderer.palettes.set_brightness(regs.get_brightness());
+        renderer
+    }
+
+    // Draw each layer of the line into the buffers, as draw_line does.
+    fn draw_layers(&self, mem: &VideoMem, buffers: &mut LineBuffers, y: usize) {
+        if self.mode == VideoMode::_7 {
+            buffers.clear_mode7();
+        } else {
+            buffers.clear(self.num_bgs());
+        }
+
+        self.draw_sprites_to_line(mem, &mut buffers.main_sprites, &mut buffers.sub_sprites, &mut buffers.objects, y as u8);
+
+        match self.mode {
+            VideoMode::_7 => {
+                self.draw_mode7_bg1_to_line(mem, &mut buffers.main_mode7_bg1, &mut buffers.sub_mode7_bg1, y);
+                if mem.get_window_registers().use_ext_bg() {
+                    self.draw_mode7_bg2_to_line(mem, &mut buffers.main_mode7_bg2, &mut buffers.sub_mode7_bg2, y);
+                }
+            },
+            _ => {
+                let offset_per_tile = self.mode == VideoMode::_2 || self.mode == VideoMode::_4 || self.mode == VideoMode::_6;
+                for bg in BG::all().iter().take(self.num_bgs()) {
+                    self.draw_bg_to_line(mem, *bg, buffers, y, offset_per_tile);
+                }
+            }
+        }
+    }
+
+    // Find the frontmost pixel of one screen.
+    fn eval_pixel(&self, mem: &VideoMem, pixels: &ScreenPixels) -> Pixel {
+        let regs = mem.get_bg_registers();
+        let window_regs = mem.get_window_registers();
+        let [bg1, bg2, bg3, bg4] = pixels.bgs;
+        let sprite_pix = pixels.sprite;
+        match self.mode {
+            VideoMode::_0 => self.eval_mode_0(sprite_pix, bg1, bg2, bg3, bg4),
+            VideoMode::_1 => self.eval_mode_1(regs.get_bg3_priority(), sprite_pix, bg1, bg2, bg3),
+            VideoMode::_2 => self.eval_mode_2(sprite_pix, bg1, bg2),
+            VideoMode::_3 => self.eval_mode_3(window_regs.use_direct_colour(), sprite_pix, bg1, bg2),
+            VideoMode::_4 => self.eval_mode_4(window_regs.use_direct_colour(), sprite_pix, bg1, bg2),
+            VideoMode::_5 => self.eval_mode_5(sprite_pix, bg1, bg2),
+            VideoMode::_6 => self.eval_mode_6(sprite_pix, bg1),
+            VideoMode::_7 => self.eval_mode_7(window_regs.use_direct_colour(), window_regs.use_ext_bg(), sprite_pix, pixels.mode7_bg1, pixels.mode7_bg2),
+        }
+    }
+
+    // Find the layer and CGRAM index that a pixel came from.
+    fn layer_pixel(&self, mem: &VideoMem, pixels: &ScreenPixels, pixel: Pixel) -> LayerPixel {
+        let direct_col = mem.get_window_registers().use_direct_colour();
+        let mode_7 = self.mode == VideoMode::_7;
+        let (layer, palette_index) = match pixel {
+            Pixel::BG1(_) if mode_7 => (PixelLayer::BG1, if direct_col {None} else {pixels.mode7_bg1}),
+            Pixel::BG2(_) if mode_7 => (PixelLayer::BG2, Some(pixels.mode7_bg2 & 0x7F)),
+            Pixel::BG1(_) => (PixelLayer::BG1, self.bg_palette_index(BG::_1, pixels.bgs[0], direct_col)),
+            Pixel::BG2(_) => (PixelLayer::BG2, self.bg_palette_index(BG::_2, pixels.bgs[1], direct_col)),
+            Pixel::BG3(_) => (PixelLayer::BG3, self.bg_palette_index(BG::_3, pixels.bgs[2], direct_col)),
+            Pixel::BG4(_) => (PixelLayer::BG4, self.bg_palette_index(BG::_4, pixels.bgs[3], direct_col)),
+            Pixel::ObjHi(_) | Pixel::ObjLo(_) => (PixelLayer::Obj, obj_palette_index(pixels.sprite)),
+            Pixel::None => (PixelLayer::Backdrop, Some(0)),
+        };
+
+        let colour = pixel.any().unwrap_or(self.palettes.get_zero_colour());
+        LayerPixel {
+            layer:          layer,
+            palette_index:  palette_index,
+            colour:         [colour.r, colour.g, colour.b],
+        }
+    }
+
+    // This follows make_2bpp_pixel, make_4bpp_pixel and make_8bpp_pixel.
+    fn bg_palette_index(&self, bg: BG, data: BGData, direct_col: bool) -> Option<u8> {
+        let palette_num = (data.attrs & TileAttributes::PALETTE).bits();
+        match self.get_pattern_mem(bg).get_bits_per_pixel() {
+            BitsPerPixel::_2 if self.mode == VideoMode::_0 => Some(palette_num + ((bg as u8) * 32) + data.texel),
+            BitsPerPixel::_2 => Some(palette_num + data.texel),
+            BitsPerPixel::_4 => Some((palette_num << 2) + data.texel),
+            BitsPerPixel::_8 if direct_col => None,
+            BitsPerPixel::_8 => Some(data.texel),
+        }
+    }
+}
+
+fn obj_palette_index(sprite: SpritePixel) -> Option<u8> {
+    match sprite {
+        SpritePixel::Prio3(c) |
+        SpritePixel::Prio2(c) |
+        SpritePixel::Prio1(c) |
+        SpritePixel::Prio0(c) => Some(OBJ_PALETTE_OFFSET + c.index),
+        _ => None
+    }
+}
+
+fn in_window(spans: &WindowSpans, x: usize) -> bool {
+    spans.iter().any(|span| span.value && span.range().contains(&x))
+}
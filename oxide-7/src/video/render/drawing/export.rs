@@ -0,0 +1,146 @@
+// Decoding whole backgrounds and object tables, for exporting graphics.
+
+use crate::video::{
+    BG,
+    VideoMem,
+    render::{
+        VideoMode,
+        patternmem::{
+            BitsPerPixel,
+            PatternMem
+        },
+        palette::PaletteMem
+    }
+};
+
+use super::{
+    get_mode_7_texel,
+    lines::{
+        TileAttributes,
+        get_bg_texel
+    }
+};
+
+const MODE_7_MAP_SIZE: usize = 1024;
+const OBJ_TABLE_TILES: usize = 16;  // Width and height of an object name table, in tiles.
+const OBJ_PALETTE_OFFSET: u8 = 128;
+
+// Decoded palette indices, as (width, height, pixels).
+pub type IndexedPixels = (usize, usize, Vec<u8>);
+
+// Decode all 256 colours of CGRAM.
+pub fn palette(mem: &VideoMem) -> Vec<[u8; 3]> {
+    let mut palettes = PaletteMem::new();
+    palettes.make_bg_palette(mem);
+    palettes.make_obj_palette(mem);
+    (0..256).map(|i| {
+        let colour = palettes.get_bg_colour(i);
+        [colour.r, colour.g, colour.b]
+    }).collect()
+}
+
+// Decode an entire background map, in the current mode, into CGRAM indices.
+// Index 0 is transparent.
+// Returns None if the background isn't used in the current mode.
+pub fn bg_map(mem: &VideoMem, bg: BG) -> Option<IndexedPixels> {
+    let regs = mem.get_bg_registers();
+    let mode = VideoMode::from(regs.get_mode());
+
+    if mode == VideoMode::_7 {
+        return match bg {
+            BG::_1 => Some(mode_7_map(mem)),
+            _ => None
+        };
+    }
+
+    let bits_per_pixel = bg_bits_per_pixel(mode, bg)?;
+    let mut tiles = PatternMem::new(bits_per_pixel);
+    tiles.set_addr(regs.bg_pattern_addr(bg), regs.get_pattern_table_size(bg));
+    tiles.make_tiles(mem.get_vram());
+
+    // Mode 0 uses a different set of palettes for each background.
+    let mode_0_offset = if mode == VideoMode::_0 {
+        (bg as u8) * 32
+    } else {
+        0
+    };
+
+    let (mask_x, mask_y) = regs.bg_size_mask(bg);
+    let (width, height) = (mask_x + 1, mask_y + 1);
+    let mut pixels = vec![0; width * height];
+    for (y, row) in pixels.chunks_mut(width).enumerate() {
+        for (x, pixel) in row.iter_mut().enumerate() {
+            let data = get_bg_texel(&tiles, mem, bg, x, y);
+            let palette_num = (data.attrs & TileAttributes::PALETTE).bits();
+            *pixel = match (data.texel, bits_per_pixel) {
+                (0, _) => 0,
+                (t, BitsPerPixel::_2) => palette_num + mode_0_offset + t,
+                (t, BitsPerPixel::_4) => (palette_num << 2) + t,
+                (t, BitsPerPixel::_8) => t,
+            };
+        }
+    }
+
+    Some((width, height, pixels))
+}
+
+// Decode both object name tables into CGRAM indices, using one of the 8 object palettes.
+// The tables are stacked on top of each other, 16x16 tiles each.
+// Index 0 is transparent.
+pub fn obj_tables(mem: &VideoMem, palette: u8) -> IndexedPixels {
+    let regs = mem.get_bg_registers();
+    let palette_offset = OBJ_PALETTE_OFFSET + ((palette % 8) * 16);
+
+    let width = OBJ_TABLE_TILES * 8;
+    let height = OBJ_TABLE_TILES * 8 * 2;
+    let mut pixels = vec![0; width * height];
+
+    for (table, addr) in [regs.obj0_pattern_addr(), regs.objn_pattern_addr()].iter().enumerate() {
+        let mut tiles = PatternMem::new(BitsPerPixel::_4);
+        tiles.set_addr_obj(*addr);
+        tiles.make_tiles(mem.get_vram());
+
+        for tile_num in 0..(OBJ_TABLE_TILES * OBJ_TABLE_TILES) {
+            let tile = tiles.ref_tile(tile_num);
+            let tile_x = (tile_num % OBJ_TABLE_TILES) * 8;
+            let tile_y = ((tile_num / OBJ_TABLE_TILES) + (table * OBJ_TABLE_TILES)) * 8;
+            for y in 0..8 {
+                for x in 0..8 {
+                    let texel = tile.get_texel(x, y);
+                    pixels[((tile_y + y) * width) + tile_x + x] = if texel == 0 {0} else {palette_offset + texel};
+                }
+            }
+        }
+    }
+
+    (width, height, pixels)
+}
+
+fn mode_7_map(mem: &VideoMem) -> IndexedPixels {
+    let mut pixels = vec![0; MODE_7_MAP_SIZE * MODE_7_MAP_SIZE];
+    for (y, row) in pixels.chunks_mut(MODE_7_MAP_SIZE).enumerate() {
+        for (x, pixel) in row.iter_mut().enumerate() {
+            *pixel = get_mode_7_texel(mem.get_vram(), x, y);
+        }
+    }
+    (MODE_7_MAP_SIZE, MODE_7_MAP_SIZE, pixels)
+}
+
+fn bg_bits_per_pixel(mode: VideoMode, bg: BG) -> Option<BitsPerPixel> {
+    match (mode, bg) {
+        (VideoMode::_0, _)      => Some(BitsPerPixel::_2),
+        (VideoMode::_1, BG::_1) => Some(BitsPerPixel::_4),
+        (VideoMode::_1, BG::_2) => Some(BitsPerPixel::_4),
+        (VideoMode::_1, BG::_3) => Some(BitsPerPixel::_2),
+        (VideoMode::_2, BG::_1) => Some(BitsPerPixel::_4),
+        (VideoMode::_2, BG::_2) => Some(BitsPerPixel::_4),
+        (VideoMode::_3, BG::_1) => Some(BitsPerPixel::_8),
+        (VideoMode::_3, BG::_2) => Some(BitsPerPixel::_4),
+        (VideoMode::_4, BG::_1) => Some(BitsPerPixel::_8),
+        (VideoMode::_4, BG::_2) => Some(BitsPerPixel::_2),
+        (VideoMode::_5, BG::_1) => Some(BitsPerPixel::_4),
+        (VideoMode::_5, BG::_2) => Some(BitsPerPixel::_2),
+        (VideoMode::_6, BG::_1) => Some(BitsPerPixel::_4),
+        _ => None
+    }
+}
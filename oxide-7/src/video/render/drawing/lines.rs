@@ -45,59 +45,9 @@ pub struct BGData {
 impl Renderer {
     // Get a line (256 texels) of a background.
     pub fn get_row(&self, tiles: &PatternMem, mem: &VideoMem, bg: BG, row: &mut [BGData], y: usize, offset_per_tile: bool) {
-        let regs = mem.get_bg_registers();
-        // The mask determines which parts of the coords are used to index into the tile.
-        // The shift determines which parts of the coords are used to look for the tile num in VRAM.
-        let (tile_mask_x, tile_shift_x) = if regs.bg_large_tiles(bg) || regs.use_wide_tiles() {(LARGE_TILE_MASK, LARGE_TILE_SHIFT)} else {(SMALL_TILE_MASK, SMALL_TILE_SHIFT)};
-        let (tile_mask_y, tile_shift_y) = if regs.bg_large_tiles(bg) {(LARGE_TILE_MASK, LARGE_TILE_SHIFT)} else {(SMALL_TILE_MASK, SMALL_TILE_SHIFT)};
-        let wide_map = regs.bg_wide_map(bg);
-
-        let start_addr = regs.bg_map_addr(bg) as usize;
-
         for (x, data) in row.iter_mut().enumerate() {
             let (pix_x, pix_y) = self.calc_offsets(mem, offset_per_tile, x, y, bg);
-
-            let tile_y = pix_y & tile_mask_y;   // Y index into tile.
-            let map_y = pix_y >> tile_shift_y;  // Y index into VRAM.
-            let hi_submap = map_y >= 32;        // Hi submap: B, C or D.
-            let submap_y = map_y % 32;          // Index into submap
-
-            let tile_x = pix_x & tile_mask_x;
-            let map_x = pix_x >> tile_shift_x;
-            let right_submap = map_x >= 32;
-            let submap_x = map_x % 32;
-
-            // Find memory address of the tile we want.
-            // Offset based on the 32x32 tile submap.
-            let submap_offset = match (right_submap, hi_submap, wide_map) {
-                (false, false, _)       => SUB_MAP_A_OFFSET,
-                (true, false, _)        => SUB_MAP_B_OFFSET,
-                (false, true, false)    => SUB_MAP_B_OFFSET,
-                (false, true, true)     => SUB_MAP_C_OFFSET,
-                (true, true, _)         => SUB_MAP_D_OFFSET,
-            };
-            let inner_offset = (submap_y * SUB_MAP_LEN * 2) + (submap_x * 2);
-            let addr = (start_addr + submap_offset + inner_offset) & 0xFFFF;
-
-            let tile_num_lo = mem.get_vram()[addr];
-            let tile_attrs = TileAttributes::from_bits_truncate(mem.get_vram()[(addr + 1)]);
-
-            // Use tile data to find correct texel.
-            let mut tile_num = make16!((tile_attrs & TileAttributes::TILE_NUM).bits(), tile_num_lo) as usize;
-            let mut real_tile_x = if tile_attrs.contains(TileAttributes::X_FLIP) {tile_mask_x - tile_x} else {tile_x};
-            let mut real_tile_y = if tile_attrs.contains(TileAttributes::Y_FLIP) {tile_mask_y - tile_y} else {tile_y};
-
-            if real_tile_x >= 8 {
-                tile_num += 1;
-                real_tile_x -= 8;
-            }
-            if real_tile_y >= 8 {
-                tile_num += 16;
-                real_tile_y -= 8;
-            }
-
-            let tile = tiles.ref_tile(tile_num as usize);
-            *data = BGData{texel: tile.get_texel(real_tile_x, real_tile_y), attrs: tile_attrs};
+            *data = get_bg_texel(tiles, mem, bg, pix_x, pix_y);
         }
 
         // DEBUG
@@ -156,4 +106,58 @@ impl Renderer {
 
         (out_x & mask_x, out_y & mask_y)
     }
-}
\ No newline at end of file
+}
+
+// Get a single texel of a background, at a scrolled and masked position.
+pub fn get_bg_texel(tiles: &PatternMem, mem: &VideoMem, bg: BG, pix_x: usize, pix_y: usize) -> BGData {
+    let regs = mem.get_bg_registers();
+    // The mask determines which parts of the coords are used to index into the tile.
+    // The shift determines which parts of the coords are used to look for the tile num in VRAM.
+    let (tile_mask_x, tile_shift_x) = if regs.bg_large_tiles(bg) || regs.use_wide_tiles() {(LARGE_TILE_MASK, LARGE_TILE_SHIFT)} else {(SMALL_TILE_MASK, SMALL_TILE_SHIFT)};
+    let (tile_mask_y, tile_shift_y) = if regs.bg_large_tiles(bg) {(LARGE_TILE_MASK, LARGE_TILE_SHIFT)} else {(SMALL_TILE_MASK, SMALL_TILE_SHIFT)};
+    let wide_map = regs.bg_wide_map(bg);
+
+    let start_addr = regs.bg_map_addr(bg) as usize;
+
+    let tile_y = pix_y & tile_mask_y;   // Y index into tile.
+    let map_y = pix_y >> tile_shift_y;  // Y index into VRAM.
+    let hi_submap = map_y >= 32;        // Hi submap: B, C or D.
+    let submap_y = map_y % 32;          // Index into submap
+
+    let tile_x = pix_x & tile_mask_x;
+    let map_x = pix_x >> tile_shift_x;
+    let right_submap = map_x >= 32;
+    let submap_x = map_x % 32;
+
+    // Find memory address of the tile we want.
+    // Offset based on the 32x32 tile submap.
+    let submap_offset = match (right_submap, hi_submap, wide_map) {
+        (false, false, _)       => SUB_MAP_A_OFFSET,
+        (true, false, _)        => SUB_MAP_B_OFFSET,
+        (false, true, false)    => SUB_MAP_B_OFFSET,
+        (false, true, true)     => SUB_MAP_C_OFFSET,
+        (true, true, _)         => SUB_MAP_D_OFFSET,
+    };
+    let inner_offset = (submap_y * SUB_MAP_LEN * 2) + (submap_x * 2);
+    let addr = (start_addr + submap_offset + inner_offset) & 0xFFFF;
+
+    let tile_num_lo = mem.get_vram()[addr];
+    let tile_attrs = TileAttributes::from_bits_truncate(mem.get_vram()[(addr + 1)]);
+
+    // Use tile data to find correct texel.
+    let mut tile_num = make16!((tile_attrs & TileAttributes::TILE_NUM).bits(), tile_num_lo) as usize;
+    let mut real_tile_x = if tile_attrs.contains(TileAttributes::X_FLIP) {tile_mask_x - tile_x} else {tile_x};
+    let mut real_tile_y = if tile_attrs.contains(TileAttributes::Y_FLIP) {tile_mask_y - tile_y} else {tile_y};
+
+    if real_tile_x >= 8 {
+        tile_num += 1;
+        real_tile_x -= 8;
+    }
+    if real_tile_y >= 8 {
+        tile_num += 16;
+        real_tile_y -= 8;
+    }
+
+    let tile = tiles.ref_tile(tile_num as usize);
+    BGData{texel: tile.get_texel(real_tile_x, real_tile_y), attrs: tile_attrs}
+}
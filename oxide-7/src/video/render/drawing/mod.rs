@@ -2,6 +2,17 @@
 
 mod lines;
 mod types;
+#[cfg(feature = "debug")]
+pub mod explain;
+#[cfg(feature = "debug")]
+pub mod export;
+#[cfg(test)]
+mod tests;
+
+use alloc::{
+    boxed::Box,
+    vec::Vec
+};
 
 use crate::video::{
     BG,
@@ -10,7 +21,8 @@ use crate::video::{
         Mode7Extend,
         Screen,
         SpritePriority,
-        WindowRegisters
+        WindowRegisters,
+        WindowSpans
     },
     render::{
         Colour,
@@ -26,18 +38,28 @@ use crate::video::{
 use types::*;
 use lines::{
     BGData,
-    TileAttributes
+    TileAttributes,
+    get_bg_texel
 };
 
 use crate::constants::screen::H_RES;
 
+// Maximum number of sprites that can be drawn on a single line.
+pub const SPRITES_PER_LINE: usize = 32;
+
 pub struct Renderer {
     mode: VideoMode,
 
     bg_pattern_mem: [PatternMem; 4],
     obj_pattern_mem: [PatternMem; 2],
 
-    palettes: PaletteMem
+    palettes: PaletteMem,
+
+    sprite_limit: bool,
+    widescreen: usize,  // Extra pixels drawn on each side of the line.
+    bg_priority: [Option<bool>; 4], // Override the priority bit of each BG's tiles.
+
+    line_buffers: Option<Box<LineBuffers>>, // Only taken while drawing a line.
 }
 
 impl Renderer {
@@ -57,22 +79,55 @@ impl Renderer {
                 PatternMem::new(BitsPerPixel::_4),
             ],
 
-            palettes: PaletteMem::new()
+            palettes: PaletteMem::new(),
+
+            sprite_limit: true,
+            widescreen: 0,
+            bg_priority: [None; 4],
+
+            line_buffers: Some(Box::new(LineBuffers::new())),
+        }
+    }
+
+    pub fn set_sprite_limit(&mut self, enable: bool) {
+        self.sprite_limit = enable;
+    }
+
+    pub fn set_widescreen(&mut self, extra: usize) {
+        self.widescreen = extra;
+    }
+
+    #[cfg(feature = "debug")]
+    pub fn set_bg_priority_override(&mut self, bg: usize, priority: Option<bool>) {
+        if let Some(p) = self.bg_priority.get_mut(bg) {
+            *p = priority;
         }
     }
 
     pub fn draw_line(&mut self, mem: &VideoMem, target: &mut [u8], y: usize) {
+        let mut buffers = self.line_buffers.take().expect("line buffers in use");
         match self.mode {
-            VideoMode::_0 => self.draw_line_mode_0(mem, target, y),
-            VideoMode::_1 => self.draw_line_mode_1(mem, target, y),
-            VideoMode::_2 => self.draw_line_mode_2(mem, target, y),
-            VideoMode::_3 => self.draw_line_mode_3(mem, target, y),
-            VideoMode::_4 => self.draw_line_mode_4(mem, target, y),
-            VideoMode::_5 => self.draw_line_mode_5(mem, target, y),
-            VideoMode::_6 => self.draw_line_mode_6(mem, target, y),
-            VideoMode::_7 => self.draw_line_mode_7(mem, target, y),
+            VideoMode::_0 => self.draw_line_mode_0(mem, target, y, &mut buffers),
+            VideoMode::_1 => self.draw_line_mode_1(mem, target, y, &mut buffers),
+            VideoMode::_2 => self.draw_line_mode_2(mem, target, y, &mut buffers),
+            VideoMode::_3 => self.draw_line_mode_3(mem, target, y, &mut buffers),
+            VideoMode::_4 => self.draw_line_mode_4(mem, target, y, &mut buffers),
+            VideoMode::_5 => self.draw_line_mode_5(mem, target, y, &mut buffers),
+            VideoMode::_6 => self.draw_line_mode_6(mem, target, y, &mut buffers),
+            VideoMode::_7 => self.draw_line_mode_7(mem, target, y, &mut buffers),
+        }
+        self.line_buffers = Some(buffers);
+
+        if self.widescreen > 0 {
+            self.draw_margins(mem, target, y);
         }
     }
+
+    // Index of the first on-screen pixel of a line in the target.
+    // Each pixel is 8 bytes wide.
+    fn line_start(&self, y: usize) -> usize {
+        (y * (H_RES + (self.widescreen * 2))) + self.widescreen
+    }
 }
 
 // Caches
@@ -141,6 +196,8 @@ impl Renderer {
             self.palettes.make_obj_palette(mem);
         }
         mem.cgram_reset_dirty();
+
+        self.palettes.set_brightness(mem.get_bg_registers().get_brightness());
     }
 
     // Switch mode: setup backgrounds.
@@ -228,346 +285,361 @@ impl Renderer {
     }
 }
 
-// Apply brightness to colour component.
-macro_rules! apply_brightness {
-    ($col:expr, $brightness:expr) => {
-        (($col as usize) * ($brightness as usize) / 0xF) as u8
-    };
-}
-
 // Drawing modes
 impl Renderer {
     #[inline]
-    fn write_hires_pixel(&self, out: &mut [u8], main: Pixel, sub: Colour, brightness: u8) {
-        let main_col = main.any().unwrap_or(self.palettes.get_zero_colour());
-        out[0] = apply_brightness!(sub.r, brightness);
-        out[1] = apply_brightness!(sub.g, brightness);
-        out[2] = apply_brightness!(sub.b, brightness);
-        out[4] = apply_brightness!(main_col.r, brightness);
-        out[5] = apply_brightness!(main_col.g, brightness);
-        out[6] = apply_brightness!(main_col.b, brightness);
+    fn write_hires_pixel(&self, out: &mut [u8], main: Pixel, sub: Colour) {
+        let main_col = self.palettes.apply_brightness(main.any().unwrap_or(self.palettes.get_zero_colour()));
+        let sub_col = self.palettes.apply_brightness(sub);
+        out[0] = sub_col.r;
+        out[1] = sub_col.g;
+        out[2] = sub_col.b;
+        out[4] = main_col.r;
+        out[5] = main_col.g;
+        out[6] = main_col.b;
     }
 
     #[inline]
-    fn write_pixel(&self, window_regs: &WindowRegisters, out: &mut [u8], main: Pixel, sub: Option<Colour>, brightness: u8, x: u8) {
+    fn write_pixel(&self, window_regs: &WindowRegisters, out: &mut [u8], main: Pixel, sub: Option<Colour>, col_window: bool) {
         if window_regs.use_pseudo_hires() {
-            self.write_hires_pixel(out, main, sub.unwrap_or(window_regs.get_fixed_colour()), brightness);
+            self.write_hires_pixel(out, main, sub.unwrap_or(window_regs.get_fixed_colour()));
         } else {
             let colour = match main {
-                Pixel::BG1(c) => window_regs.calc_colour_math_bg(c, sub, BG::_1, x),
-                Pixel::BG2(c) => window_regs.calc_colour_math_bg(c, sub, BG::_2, x),
-                Pixel::BG3(c) => window_regs.calc_colour_math_bg(c, sub, BG::_3, x),
-                Pixel::BG4(c) => window_regs.calc_colour_math_bg(c, sub, BG::_4, x),
-                Pixel::ObjHi(c) => window_regs.calc_colour_math_obj(c, sub, x),
+                Pixel::BG1(c) => window_regs.calc_colour_math_bg(c, sub, BG::_1, col_window),
+                Pixel::BG2(c) => window_regs.calc_colour_math_bg(c, sub, BG::_2, col_window),
+                Pixel::BG3(c) => window_regs.calc_colour_math_bg(c, sub, BG::_3, col_window),
+                Pixel::BG4(c) => window_regs.calc_colour_math_bg(c, sub, BG::_4, col_window),
+                Pixel::ObjHi(c) => window_regs.calc_colour_math_obj(c, sub, col_window),
                 Pixel::ObjLo(c) => c,
-                Pixel::None => window_regs.calc_colour_math_backdrop(self.palettes.get_zero_colour(), sub, x),
+                Pixel::None => window_regs.calc_colour_math_backdrop(self.palettes.get_zero_colour(), sub, col_window),
             };
 
-            let r = apply_brightness!(colour.r, brightness);
-            let g = apply_brightness!(colour.g, brightness);
-            let b = apply_brightness!(colour.b, brightness);
+            let colour = self.palettes.apply_brightness(colour);
+
+            out[0] = colour.r;
+            out[1] = colour.g;
+            out[2] = colour.b;
+            out[4] = colour.r;
+            out[5] = colour.g;
+            out[6] = colour.b;
+        }
+    }
 
-            out[0] = r;
-            out[1] = g;
-            out[2] = b;
-            out[4] = r;
-            out[5] = g;
-            out[6] = b;
+    // Write a line of pixels. The colour window is found once for each span of the line.
+    // pixel returns the main and sub screen pixels at x.
+    fn write_line(&self, window_regs: &WindowRegisters, target: &mut [u8], y: usize, pixel: impl Fn(usize) -> (Pixel, Option<Colour>)) {
+        let mut line = target.chunks_mut(8).skip(self.line_start(y));
+        for span in window_regs.col_window_spans().iter() {
+            for (x, out) in span.range().zip(&mut line) {
+                let (main, sub) = pixel(x);
+                self.write_pixel(window_regs, out, main, sub, span.value);
+            }
         }
     }
 
-    fn draw_line_mode_0(&self, mem: &VideoMem, target: &mut [u8], y: usize) {
-        let brightness = mem.get_bg_registers().get_brightness();
+    fn draw_line_mode_0(&self, mem: &VideoMem, target: &mut [u8], y: usize, buffers: &mut LineBuffers) {
         let window_regs = mem.get_window_registers();
-        let target_start = y * H_RES;
-
-        let mut main_sprite_pixels = [SpritePixel::None; H_RES];
-        let mut sub_sprite_pixels = [SpritePixel::None; H_RES];
-        self.draw_sprites_to_line(mem, &mut main_sprite_pixels, &mut sub_sprite_pixels, y as u8);
-        let mut main_bg1_pixels = [BGData::default(); H_RES];
-        let mut sub_bg1_pixels = [BGData::default(); H_RES];
-        self.draw_bg_to_line(mem, BG::_1, &mut main_bg1_pixels, &mut sub_bg1_pixels, y, false);
-        let mut main_bg2_pixels = [BGData::default(); H_RES];
-        let mut sub_bg2_pixels = [BGData::default(); H_RES];
-        self.draw_bg_to_line(mem, BG::_2, &mut main_bg2_pixels, &mut sub_bg2_pixels, y, false);
-        let mut main_bg3_pixels = [BGData::default(); H_RES];
-        let mut sub_bg3_pixels = [BGData::default(); H_RES];
-        self.draw_bg_to_line(mem, BG::_3, &mut main_bg3_pixels, &mut sub_bg3_pixels, y, false);
-        let mut main_bg4_pixels = [BGData::default(); H_RES];
-        let mut sub_bg4_pixels = [BGData::default(); H_RES];
-        self.draw_bg_to_line(mem, BG::_4, &mut main_bg4_pixels, &mut sub_bg4_pixels, y, false);
 
-        for (x, out) in target.chunks_mut(8).skip(target_start).take(H_RES).enumerate() {
+        buffers.clear(4);
+        self.draw_sprites_to_line(mem, &mut buffers.main_sprites, &mut buffers.sub_sprites, &mut buffers.objects, y as u8);
+        self.draw_bg_to_line(mem, BG::_1, buffers, y, false);
+        self.draw_bg_to_line(mem, BG::_2, buffers, y, false);
+        self.draw_bg_to_line(mem, BG::_3, buffers, y, false);
+        self.draw_bg_to_line(mem, BG::_4, buffers, y, false);
+
+        let LineBuffers {main_sprites, sub_sprites, main_bgs, sub_bgs, ..} = buffers;
+        self.write_line(window_regs, target, y, |x| {
             let main = {
-                let sprite_pix = main_sprite_pixels[x];
-                let bg1_pix = main_bg1_pixels[x];
-                let bg2_pix = main_bg2_pixels[x];
-                let bg3_pix = main_bg3_pixels[x];
-                let bg4_pix = main_bg4_pixels[x];
+                let sprite_pix = main_sprites[x];
+                let bg1_pix = main_bgs[0][x];
+                let bg2_pix = main_bgs[1][x];
+                let bg3_pix = main_bgs[2][x];
+                let bg4_pix = main_bgs[3][x];
                 self.eval_mode_0(sprite_pix, bg1_pix, bg2_pix, bg3_pix, bg4_pix)
             };
             let sub = if window_regs.use_subscreen() {
-                let sprite_pix = sub_sprite_pixels[x];
-                let bg1_pix = sub_bg1_pixels[x];
-                let bg2_pix = sub_bg2_pixels[x];
-                let bg3_pix = sub_bg3_pixels[x];
-                let bg4_pix = main_bg4_pixels[x];
+                let sprite_pix = sub_sprites[x];
+                let bg1_pix = sub_bgs[0][x];
+                let bg2_pix = sub_bgs[1][x];
+                let bg3_pix = sub_bgs[2][x];
+                let bg4_pix = sub_bgs[3][x];
                 self.eval_mode_0(sprite_pix, bg1_pix, bg2_pix, bg3_pix, bg4_pix).any()
             } else {
                 None
             };
 
-            self.write_pixel(window_regs, out, main, sub, brightness, x as u8);
-        }
+            (main, sub)
+        });
     }
 
-    fn draw_line_mode_1(&self, mem: &VideoMem, target: &mut [u8], y: usize) {
-        let brightness = mem.get_bg_registers().get_brightness();
+    fn draw_line_mode_1(&self, mem: &VideoMem, target: &mut [u8], y: usize, buffers: &mut LineBuffers) {
         let window_regs = mem.get_window_registers();
-        let target_start = y * H_RES;
-
-        let mut main_sprite_pixels = [SpritePixel::None; H_RES];
-        let mut sub_sprite_pixels = [SpritePixel::None; H_RES];
-        self.draw_sprites_to_line(mem, &mut main_sprite_pixels, &mut sub_sprite_pixels, y as u8);
-        let mut main_bg1_pixels = [BGData::default(); H_RES];
-        let mut sub_bg1_pixels = [BGData::default(); H_RES];
-        self.draw_bg_to_line(mem, BG::_1, &mut main_bg1_pixels, &mut sub_bg1_pixels, y, false);
-        let mut main_bg2_pixels = [BGData::default(); H_RES];
-        let mut sub_bg2_pixels = [BGData::default(); H_RES];
-        self.draw_bg_to_line(mem, BG::_2, &mut main_bg2_pixels, &mut sub_bg2_pixels, y, false);
-        let mut main_bg3_pixels = [BGData::default(); H_RES];
-        let mut sub_bg3_pixels = [BGData::default(); H_RES];
-        self.draw_bg_to_line(mem, BG::_3, &mut main_bg3_pixels, &mut sub_bg3_pixels, y, false);
 
-        for (x, out) in target.chunks_mut(8).skip(target_start).take(H_RES).enumerate() {
+        buffers.clear(3);
+        self.draw_sprites_to_line(mem, &mut buffers.main_sprites, &mut buffers.sub_sprites, &mut buffers.objects, y as u8);
+        self.draw_bg_to_line(mem, BG::_1, buffers, y, false);
+        self.draw_bg_to_line(mem, BG::_2, buffers, y, false);
+        self.draw_bg_to_line(mem, BG::_3, buffers, y, false);
+
+        let LineBuffers {main_sprites, sub_sprites, main_bgs, sub_bgs, ..} = buffers;
+        self.write_line(window_regs, target, y, |x| {
             let main = {
-                let sprite_pix = main_sprite_pixels[x];
-                let bg1_pix = main_bg1_pixels[x];
-                let bg2_pix = main_bg2_pixels[x];
-                let bg3_pix = main_bg3_pixels[x];
+                let sprite_pix = main_sprites[x];
+                let bg1_pix = main_bgs[0][x];
+                let bg2_pix = main_bgs[1][x];
+                let bg3_pix = main_bgs[2][x];
                 self.eval_mode_1(mem.get_bg_registers().get_bg3_priority(), sprite_pix, bg1_pix, bg2_pix, bg3_pix)
             };
             let sub = if window_regs.use_subscreen() {
-                let sprite_pix = sub_sprite_pixels[x];
-                let bg1_pix = sub_bg1_pixels[x];
-                let bg2_pix = sub_bg2_pixels[x];
-                let bg3_pix = sub_bg3_pixels[x];
+                let sprite_pix = sub_sprites[x];
+                let bg1_pix = sub_bgs[0][x];
+                let bg2_pix = sub_bgs[1][x];
+                let bg3_pix = sub_bgs[2][x];
                 self.eval_mode_1(mem.get_bg_registers().get_bg3_priority(), sprite_pix, bg1_pix, bg2_pix, bg3_pix).any()
             } else {
                 None
             };
 
-            self.write_pixel(window_regs, out, main, sub, brightness, x as u8);
-        }
+            (main, sub)
+        });
     }
 
-    fn draw_line_mode_2(&self, mem: &VideoMem, target: &mut [u8], y: usize) {
-        let brightness = mem.get_bg_registers().get_brightness();
+    fn draw_line_mode_2(&self, mem: &VideoMem, target: &mut [u8], y: usize, buffers: &mut LineBuffers) {
         let window_regs = mem.get_window_registers();
-        let target_start = y * H_RES;
-
-        let mut main_sprite_pixels = [SpritePixel::None; H_RES];
-        let mut sub_sprite_pixels = [SpritePixel::None; H_RES];
-        self.draw_sprites_to_line(mem, &mut main_sprite_pixels, &mut sub_sprite_pixels, y as u8);
-        let mut main_bg1_pixels = [BGData::default(); H_RES];
-        let mut sub_bg1_pixels = [BGData::default(); H_RES];
-        self.draw_bg_to_line(mem, BG::_1, &mut main_bg1_pixels, &mut sub_bg1_pixels, y, true);
-        let mut main_bg2_pixels = [BGData::default(); H_RES];
-        let mut sub_bg2_pixels = [BGData::default(); H_RES];
-        self.draw_bg_to_line(mem, BG::_2, &mut main_bg2_pixels, &mut sub_bg2_pixels, y, true);
 
-        for (x, out) in target.chunks_mut(8).skip(target_start).take(H_RES).enumerate() {
+        buffers.clear(2);
+        self.draw_sprites_to_line(mem, &mut buffers.main_sprites, &mut buffers.sub_sprites, &mut buffers.objects, y as u8);
+        self.draw_bg_to_line(mem, BG::_1, buffers, y, true);
+        self.draw_bg_to_line(mem, BG::_2, buffers, y, true);
+
+        let LineBuffers {main_sprites, sub_sprites, main_bgs, sub_bgs, ..} = buffers;
+        self.write_line(window_regs, target, y, |x| {
             let main = {
-                let sprite_pix = main_sprite_pixels[x];
-                let bg1_pix = main_bg1_pixels[x];
-                let bg2_pix = main_bg2_pixels[x];
+                let sprite_pix = main_sprites[x];
+                let bg1_pix = main_bgs[0][x];
+                let bg2_pix = main_bgs[1][x];
                 self.eval_mode_2(sprite_pix, bg1_pix, bg2_pix)
             };
             let sub = if window_regs.use_subscreen() {
-                let sprite_pix = sub_sprite_pixels[x];
-                let bg1_pix = sub_bg1_pixels[x];
-                let bg2_pix = sub_bg2_pixels[x];
+                let sprite_pix = sub_sprites[x];
+                let bg1_pix = sub_bgs[0][x];
+                let bg2_pix = sub_bgs[1][x];
                 self.eval_mode_2(sprite_pix, bg1_pix, bg2_pix).any()
             } else {
                 None
             };
 
-            self.write_pixel(window_regs, out, main, sub, brightness, x as u8);
-        }
+            (main, sub)
+        });
     }
 
-    fn draw_line_mode_3(&self, mem: &VideoMem, target: &mut [u8], y: usize) {
-        let brightness = mem.get_bg_registers().get_brightness();
+    fn draw_line_mode_3(&self, mem: &VideoMem, target: &mut [u8], y: usize, buffers: &mut LineBuffers) {
         let window_regs = mem.get_window_registers();
-        let target_start = y * H_RES;
-
-        let mut main_sprite_pixels = [SpritePixel::None; H_RES];
-        let mut sub_sprite_pixels = [SpritePixel::None; H_RES];
-        self.draw_sprites_to_line(mem, &mut main_sprite_pixels, &mut sub_sprite_pixels, y as u8);
-        let mut main_bg1_pixels = [BGData::default(); H_RES];
-        let mut sub_bg1_pixels = [BGData::default(); H_RES];
-        self.draw_bg_to_line(mem, BG::_1, &mut main_bg1_pixels, &mut sub_bg1_pixels, y, false);
-        let mut main_bg2_pixels = [BGData::default(); H_RES];
-        let mut sub_bg2_pixels = [BGData::default(); H_RES];
-        self.draw_bg_to_line(mem, BG::_2, &mut main_bg2_pixels, &mut sub_bg2_pixels, y, false);
 
-        for (x, out) in target.chunks_mut(8).skip(target_start).take(H_RES).enumerate() {
+        buffers.clear(2);
+        self.draw_sprites_to_line(mem, &mut buffers.main_sprites, &mut buffers.sub_sprites, &mut buffers.objects, y as u8);
+        self.draw_bg_to_line(mem, BG::_1, buffers, y, false);
+        self.draw_bg_to_line(mem, BG::_2, buffers, y, false);
+
+        let LineBuffers {main_sprites, sub_sprites, main_bgs, sub_bgs, ..} = buffers;
+        self.write_line(window_regs, target, y, |x| {
             let main = {
-                let sprite_pix = main_sprite_pixels[x];
-                let bg1_pix = main_bg1_pixels[x];
-                let bg2_pix = main_bg2_pixels[x];
+                let sprite_pix = main_sprites[x];
+                let bg1_pix = main_bgs[0][x];
+                let bg2_pix = main_bgs[1][x];
                 self.eval_mode_3(window_regs.use_direct_colour(), sprite_pix, bg1_pix, bg2_pix)
             };
             let sub = if window_regs.use_subscreen() {
-                let sprite_pix = sub_sprite_pixels[x];
-                let bg1_pix = sub_bg1_pixels[x];
-                let bg2_pix = sub_bg2_pixels[x];
+                let sprite_pix = sub_sprites[x];
+                let bg1_pix = sub_bgs[0][x];
+                let bg2_pix = sub_bgs[1][x];
                 self.eval_mode_3(window_regs.use_direct_colour(), sprite_pix, bg1_pix, bg2_pix).any()
             } else {
                 None
             };
 
-            self.write_pixel(window_regs, out, main, sub, brightness, x as u8);
-        }
+            (main, sub)
+        });
     }
 
-    fn draw_line_mode_4(&self, mem: &VideoMem, target: &mut [u8], y: usize) {
-        let brightness = mem.get_bg_registers().get_brightness();
+    fn draw_line_mode_4(&self, mem: &VideoMem, target: &mut [u8], y: usize, buffers: &mut LineBuffers) {
         let window_regs = mem.get_window_registers();
-        let target_start = y * H_RES;
-
-        let mut main_sprite_pixels = [SpritePixel::None; H_RES];
-        let mut sub_sprite_pixels = [SpritePixel::None; H_RES];
-        self.draw_sprites_to_line(mem, &mut main_sprite_pixels, &mut sub_sprite_pixels, y as u8);
-        let mut main_bg1_pixels = [BGData::default(); H_RES];
-        let mut sub_bg1_pixels = [BGData::default(); H_RES];
-        self.draw_bg_to_line(mem, BG::_1, &mut main_bg1_pixels, &mut sub_bg1_pixels, y, true); // TODO: offset to bg limits?
-        let mut main_bg2_pixels = [BGData::default(); H_RES];
-        let mut sub_bg2_pixels = [BGData::default(); H_RES];
-        self.draw_bg_to_line(mem, BG::_2, &mut main_bg2_pixels, &mut sub_bg2_pixels, y, true); // TODO: offset to bg limits?
 
-        for (x, out) in target.chunks_mut(8).skip(target_start).take(H_RES).enumerate() {
+        buffers.clear(2);
+        self.draw_sprites_to_line(mem, &mut buffers.main_sprites, &mut buffers.sub_sprites, &mut buffers.objects, y as u8);
+        self.draw_bg_to_line(mem, BG::_1, buffers, y, true); // TODO: offset to bg limits?
+        self.draw_bg_to_line(mem, BG::_2, buffers, y, true); // TODO: offset to bg limits?
+
+        let LineBuffers {main_sprites, sub_sprites, main_bgs, sub_bgs, ..} = buffers;
+        self.write_line(window_regs, target, y, |x| {
             let main = {
-                let sprite_pix = main_sprite_pixels[x];
-                let bg1_pix = main_bg1_pixels[x];
-                let bg2_pix = main_bg2_pixels[x];
+                let sprite_pix = main_sprites[x];
+                let bg1_pix = main_bgs[0][x];
+                let bg2_pix = main_bgs[1][x];
                 self.eval_mode_4(window_regs.use_direct_colour(), sprite_pix, bg1_pix, bg2_pix)
             };
             let sub = if window_regs.use_subscreen() {
-                let sprite_pix = sub_sprite_pixels[x];
-                let bg1_pix = sub_bg1_pixels[x];
-                let bg2_pix = sub_bg2_pixels[x];
+                let sprite_pix = sub_sprites[x];
+                let bg1_pix = sub_bgs[0][x];
+                let bg2_pix = sub_bgs[1][x];
                 self.eval_mode_4(window_regs.use_direct_colour(), sprite_pix, bg1_pix, bg2_pix).any()
             } else {
                 None
             };
 
-            self.write_pixel(window_regs, out, main, sub, brightness, x as u8);
-        }
+            (main, sub)
+        });
     }
 
-    fn draw_line_mode_5(&self, mem: &VideoMem, target: &mut [u8], y: usize) {
-        let brightness = mem.get_bg_registers().get_brightness();
+    fn draw_line_mode_5(&self, mem: &VideoMem, target: &mut [u8], y: usize, buffers: &mut LineBuffers) {
         let window_regs = mem.get_window_registers();
-        let target_start = y * H_RES;
-
-        let mut main_sprite_pixels = [SpritePixel::None; H_RES];
-        let mut sub_sprite_pixels = [SpritePixel::None; H_RES];
-        self.draw_sprites_to_line(mem, &mut main_sprite_pixels, &mut sub_sprite_pixels, y as u8);
-        let mut main_bg1_pixels = [BGData::default(); H_RES];
-        let mut sub_bg1_pixels = [BGData::default(); H_RES];
-        self.draw_bg_to_line(mem, BG::_1, &mut main_bg1_pixels, &mut sub_bg1_pixels, y, false);
-        let mut main_bg2_pixels = [BGData::default(); H_RES];
-        let mut sub_bg2_pixels = [BGData::default(); H_RES];
-        self.draw_bg_to_line(mem, BG::_2, &mut main_bg2_pixels, &mut sub_bg2_pixels, y, false);
+        let target_start = self.line_start(y);
+
+        buffers.clear(2);
+        self.draw_sprites_to_line(mem, &mut buffers.main_sprites, &mut buffers.sub_sprites, &mut buffers.objects, y as u8);
+        self.draw_bg_to_line(mem, BG::_1, buffers, y, false);
+        self.draw_bg_to_line(mem, BG::_2, buffers, y, false);
 
+        let LineBuffers {main_sprites, sub_sprites, main_bgs, sub_bgs, ..} = buffers;
         for (x, out) in target.chunks_mut(8).skip(target_start).take(H_RES).enumerate() {
             let main = {
-                let sprite_pix = main_sprite_pixels[x];
-                let bg1_pix = main_bg1_pixels[x];
-                let bg2_pix = main_bg2_pixels[x];
+                let sprite_pix = main_sprites[x];
+                let bg1_pix = main_bgs[0][x];
+                let bg2_pix = main_bgs[1][x];
                 self.eval_mode_5(sprite_pix, bg1_pix, bg2_pix)
             };
             let sub = if window_regs.use_subscreen() {
-                let sprite_pix = sub_sprite_pixels[x];
-                let bg1_pix = sub_bg1_pixels[x];
-                let bg2_pix = sub_bg2_pixels[x];
+                let sprite_pix = sub_sprites[x];
+                let bg1_pix = sub_bgs[0][x];
+                let bg2_pix = sub_bgs[1][x];
                 self.eval_mode_5(sprite_pix, bg1_pix, bg2_pix).any()
                     .unwrap_or(window_regs.get_fixed_colour())
             } else {
                 window_regs.get_fixed_colour()
             };
 
-            self.write_hires_pixel(out, main, sub, brightness);
+            self.write_hires_pixel(out, main, sub);
         }
     }
 
-    fn draw_line_mode_6(&self, mem: &VideoMem, target: &mut [u8], y: usize) {
-        let brightness = mem.get_bg_registers().get_brightness();
+    fn draw_line_mode_6(&self, mem: &VideoMem, target: &mut [u8], y: usize, buffers: &mut LineBuffers) {
         let window_regs = mem.get_window_registers();
-        let target_start = y * H_RES;
+        let target_start = self.line_start(y);
 
-        let mut main_sprite_pixels = [SpritePixel::None; H_RES];
-        let mut sub_sprite_pixels = [SpritePixel::None; H_RES];
-        self.draw_sprites_to_line(mem, &mut main_sprite_pixels, &mut sub_sprite_pixels, y as u8);
-        let mut main_bg1_pixels = [BGData::default(); H_RES];
-        let mut sub_bg1_pixels = [BGData::default(); H_RES];
-        self.draw_bg_to_line(mem, BG::_1, &mut main_bg1_pixels, &mut sub_bg1_pixels, y, true);
+        buffers.clear(1);
+        self.draw_sprites_to_line(mem, &mut buffers.main_sprites, &mut buffers.sub_sprites, &mut buffers.objects, y as u8);
+        self.draw_bg_to_line(mem, BG::_1, buffers, y, true);
 
+        let LineBuffers {main_sprites, sub_sprites, main_bgs, sub_bgs, ..} = buffers;
         for (x, out) in target.chunks_mut(8).skip(target_start).take(H_RES).enumerate() {
             let main = {
-                let sprite_pix = main_sprite_pixels[x];
-                let bg1_pix = main_bg1_pixels[x];
+                let sprite_pix = main_sprites[x];
+                let bg1_pix = main_bgs[0][x];
                 self.eval_mode_6(sprite_pix, bg1_pix)
             };
             let sub = if window_regs.use_subscreen() {
-                let sprite_pix = sub_sprite_pixels[x];
-                let bg1_pix = sub_bg1_pixels[x];
+                let sprite_pix = sub_sprites[x];
+                let bg1_pix = sub_bgs[0][x];
                 self.eval_mode_6(sprite_pix, bg1_pix).any()
                     .unwrap_or(window_regs.get_fixed_colour())
             } else {
                 window_regs.get_fixed_colour()
             };
 
-            self.write_hires_pixel(out, main, sub, brightness);
+            self.write_hires_pixel(out, main, sub);
         }
     }
 
-    fn draw_line_mode_7(&self, mem: &VideoMem, target: &mut [u8], y: usize) {
-        let brightness = mem.get_bg_registers().get_brightness();
+    fn draw_line_mode_7(&self, mem: &VideoMem, target: &mut [u8], y: usize, buffers: &mut LineBuffers) {
         let window_regs = mem.get_window_registers();
-        let target_start = y * H_RES;
-
-        let mut main_sprite_pixels = [SpritePixel::None; H_RES];
-        let mut sub_sprite_pixels = [SpritePixel::None; H_RES];
-        self.draw_sprites_to_line(mem, &mut main_sprite_pixels, &mut sub_sprite_pixels, y as u8);
-        let mut main_bg1_pixels = [None; H_RES];
-        let mut sub_bg1_pixels = [None; H_RES];
-        self.draw_mode7_bg1_to_line(mem, &mut main_bg1_pixels, &mut sub_bg1_pixels, y);
-        let mut main_bg2_pixels = [0_u8; H_RES];
-        let mut sub_bg2_pixels = [0_u8; H_RES];
+
+        buffers.clear_mode7();
+        let LineBuffers {main_sprites, sub_sprites, main_mode7_bg1, sub_mode7_bg1, main_mode7_bg2, sub_mode7_bg2, objects, ..} = buffers;
+
+        self.draw_sprites_to_line(mem, main_sprites, sub_sprites, objects, y as u8);
+        self.draw_mode7_bg1_to_line(mem, main_mode7_bg1, sub_mode7_bg1, y);
         let ext_bg = window_regs.use_ext_bg();
         if ext_bg {
-            self.draw_mode7_bg2_to_line(mem, &mut main_bg2_pixels, &mut sub_bg2_pixels, y);
+            self.draw_mode7_bg2_to_line(mem, main_mode7_bg2, sub_mode7_bg2, y);
         }
 
-        for (x, out) in target.chunks_mut(8).skip(target_start).take(H_RES).enumerate() {
+        self.write_line(window_regs, target, y, |x| {
             let main = {
-                let sprite_pix = main_sprite_pixels[x];
-                let bg1_pix = main_bg1_pixels[x];
-                let bg2_pix = main_bg2_pixels[x];
+                let sprite_pix = main_sprites[x];
+                let bg1_pix = main_mode7_bg1[x];
+                let bg2_pix = main_mode7_bg2[x];
                 self.eval_mode_7(window_regs.use_direct_colour(), ext_bg, sprite_pix, bg1_pix, bg2_pix)
             };
             let sub = if window_regs.use_subscreen() {
-                let sprite_pix = sub_sprite_pixels[x];
-                let bg1_pix = sub_bg1_pixels[x];
-                let bg2_pix = sub_bg2_pixels[x];
+                let sprite_pix = sub_sprites[x];
+                let bg1_pix = sub_mode7_bg1[x];
+                let bg2_pix = sub_mode7_bg2[x];
                 self.eval_mode_7(window_regs.use_direct_colour(), ext_bg, sprite_pix, bg1_pix, bg2_pix).any()
             } else {
                 None
             };
 
-            self.write_pixel(window_regs, out, main, sub, brightness, x as u8);
+            (main, sub)
+        });
+    }
+}
+
+// Widescreen
+impl Renderer {
+    // Draw the backgrounds past the left and right edges of the screen.
+    // Only the main screen backgrounds in modes 0-4 are drawn, without offset-per-tile.
+    // Sprites, windows, mosaic and colour math don't extend past the edges.
+    // In other modes the margins are left black.
+    fn draw_margins(&self, mem: &VideoMem, target: &mut [u8], y: usize) {
+        let line_width = H_RES + (self.widescreen * 2);
+        let screen_x = self.widescreen..(self.widescreen + H_RES);
+
+        for (i, out) in target.chunks_mut(8).skip(y * line_width).take(line_width).enumerate() {
+            if screen_x.contains(&i) {
+                continue;
+            }
+
+            let x = (i as isize) - (self.widescreen as isize);
+            let colour = match self.mode {
+                VideoMode::_5 | VideoMode::_6 | VideoMode::_7 => Colour::zero(),
+                _ => self.eval_margin_pixel(mem, x, y).any().unwrap_or(self.palettes.get_zero_colour()),
+            };
+
+            let colour = self.palettes.apply_brightness(colour);
+
+            out[0] = colour.r;
+            out[1] = colour.g;
+            out[2] = colour.b;
+            out[4] = colour.r;
+            out[5] = colour.g;
+            out[6] = colour.b;
+        }
+    }
+
+    // Find the main screen background pixel at an off-screen x position.
+    fn eval_margin_pixel(&self, mem: &VideoMem, x: isize, y: usize) -> Pixel {
+        let regs = mem.get_bg_registers();
+        let window_regs = mem.get_window_registers();
+
+        let mut bg_pixels = [BGData::default(); 4];
+        for (bg, pixel) in BG::all().iter().take(self.num_bgs()).zip(bg_pixels.iter_mut()) {
+            if window_regs.enable_bg(*bg, Screen::Main) {
+                let (mask_x, mask_y) = regs.bg_size_mask(*bg);
+                let pix_x = regs.get_bg_scroll_x(*bg).wrapping_add(x as usize) & mask_x;
+                let pix_y = (y + 1 + regs.get_bg_scroll_y(*bg)) & mask_y;
+                *pixel = get_bg_texel(self.get_pattern_mem(*bg), mem, *bg, pix_x, pix_y);
+            }
+        }
+
+        let [bg1, bg2, bg3, bg4] = bg_pixels;
+        let sprite_pix = SpritePixel::None;
+        match self.mode {
+            VideoMode::_0 => self.eval_mode_0(sprite_pix, bg1, bg2, bg3, bg4),
+            VideoMode::_1 => self.eval_mode_1(regs.get_bg3_priority(), sprite_pix, bg1, bg2, bg3),
+            VideoMode::_2 => self.eval_mode_2(sprite_pix, bg1, bg2),
+            VideoMode::_3 => self.eval_mode_3(window_regs.use_direct_colour(), sprite_pix, bg1, bg2),
+            VideoMode::_4 => self.eval_mode_4(window_regs.use_direct_colour(), sprite_pix, bg1, bg2),
+            _ => Pixel::None
         }
     }
 }
@@ -575,36 +647,32 @@ impl Renderer {
 // Generic drawing utils.
 impl Renderer {
     // TODO: lots of cleanup here
-    fn draw_sprites_to_line(&self, mem: &VideoMem, main_line: &mut [SpritePixel], sub_line: &mut [SpritePixel], y: u8) {
+    fn draw_sprites_to_line(&self, mem: &VideoMem, main_line: &mut [SpritePixel], sub_line: &mut [SpritePixel], line_objects: &mut Vec<usize>, y: u8) {
         let (small, large) = mem.get_bg_registers().obj_sizes();
 
         let actual_y = y + 1;
 
         let window_regs = mem.get_window_registers();
-        for (x, (main, sub)) in main_line.iter_mut().zip(sub_line.iter_mut()).enumerate() {
-            if !window_regs.show_obj_pixel(Screen::Main, x as u8) {
-                *main = SpritePixel::Masked;
-            }
-            if !window_regs.show_obj_pixel(Screen::Sub, x as u8) {
-                *sub = SpritePixel::Masked;
-            }
+        // With OBJ interlace, objects are half height on screen, showing odd or even rows depending on the field.
+        let interlace = window_regs.use_obj_interlace();
+        let field = if interlace && mem.get_field() {1} else {0};
+        clear_window_spans(main_line, &window_regs.obj_window_spans(Screen::Main), SpritePixel::Masked);
+        clear_window_spans(sub_line, &window_regs.obj_window_spans(Screen::Sub), SpritePixel::Masked);
+
+        line_objects.clear();
+        if mem.hide_objects() {
+            return;
         }
 
         let objects = mem.get_oam();
-        
-        objects.iter().filter(|object| { // See if this sprite should appear on this line.
-            let size_y = if object.large {large.1} else {small.1};
-
-            let bottom_y = object.y.wrapping_add(size_y - 1);
-            
-            if bottom_y > object.y {
-                (actual_y >= object.y) && (actual_y <= bottom_y)
-            } else {
-                (actual_y >= object.y) || (actual_y <= bottom_y)
-            }   // TODO: fix sprite priorities...
-        }).take(32).collect::<Box::<[_]>>().iter().rev().for_each(|object| { // Actually do drawing.
+        let max_sprites = if self.sprite_limit {SPRITES_PER_LINE} else {objects.len()};
+
+        line_objects.extend(mem.objects_on_line(actual_y).take(max_sprites));   // TODO: fix sprite priorities...
+
+        for object in line_objects.iter().rev().map(|i| &objects[*i]) { // Actually do drawing.
             let size = if object.large {large} else {small};
-            let sprite_y = actual_y - object.y;   // TODO: deal with wraparound.
+            let sprite_y = actual_y.wrapping_sub(object.y);
+            let sprite_y = if interlace {(sprite_y * 2) + field} else {sprite_y};
             let y_pixel = if object.y_flip() {size.1 - 1 - sprite_y} else {sprite_y} as usize;
 
             for x in 0..size.0 {
@@ -624,11 +692,12 @@ impl Renderer {
                         let col_index = object.palette_offset() + texel;
                         let colour = self.palettes.get_obj_colour(col_index);
                         let col_math = col_index >= 64; // Top 4 palettes participate in colour math
+                        let index = col_index as u8;
                         let pix = match object.priority() {
-                            SpritePriority::_3 => SpritePixel::Prio3(SpriteColour{colour, col_math}),
-                            SpritePriority::_2 => SpritePixel::Prio2(SpriteColour{colour, col_math}),
-                            SpritePriority::_1 => SpritePixel::Prio1(SpriteColour{colour, col_math}),
-                            SpritePriority::_0 => SpritePixel::Prio0(SpriteColour{colour, col_math}),
+                            SpritePriority::_3 => SpritePixel::Prio3(SpriteColour{colour, col_math, index}),
+                            SpritePriority::_2 => SpritePixel::Prio2(SpriteColour{colour, col_math, index}),
+                            SpritePriority::_1 => SpritePixel::Prio1(SpriteColour{colour, col_math, index}),
+                            SpritePriority::_0 => SpritePixel::Prio0(SpriteColour{colour, col_math, index}),
                         };
                         if !main_line[line_x].is_masked() {
                             main_line[line_x] = pix;
@@ -639,10 +708,10 @@ impl Renderer {
                     }
                 }
             }   // for sprite x pixels
-        });
+        }
     }
 
-    fn draw_bg_to_line(&self, mem: &VideoMem, bg: BG, main_line: &mut [BGData], sub_line: &mut [BGData], y: usize, offset_per_tile: bool) {
+    fn draw_bg_to_line(&self, mem: &VideoMem, bg: BG, buffers: &mut LineBuffers, y: usize, offset_per_tile: bool) {
         let regs = mem.get_bg_registers();
         let window_regs = mem.get_window_registers();
 
@@ -654,34 +723,30 @@ impl Renderer {
         } else {
             0
         } as usize;
-        let mut x_mosaic_offset = 0;
 
         let y_mosaic_offset = y % (mosaic_amount + 1);
         let line_y = actual_y - y_mosaic_offset;
-        let mut bg_row = [BGData::default(); H_RES];
-        self.get_row(self.get_pattern_mem(bg), mem, bg, &mut bg_row, line_y, offset_per_tile); // TODO: merge these functions together?
-
-        let mut main_window = [true; H_RES];
-        window_regs.bg_window(bg, Screen::Main, &mut main_window);
-        let mut sub_window = [true; H_RES];
-        window_regs.bg_window(bg, Screen::Sub, &mut sub_window);
-
-        for (x, (main, sub)) in main_line.iter_mut().zip(sub_line.iter_mut()).enumerate() {
-            let bg_x = x - x_mosaic_offset;
-            if x_mosaic_offset == mosaic_amount {
-                x_mosaic_offset = 0;
-            } else {
-                x_mosaic_offset += 1;
-            }
-            if main_window[x] { // If pixel shows through main window.
-                *main = bg_row[bg_x];
+        let LineBuffers {main_bgs, sub_bgs, bg_scratch, ..} = buffers;
+        let (main_line, sub_line) = (&mut main_bgs[bg as usize], &mut sub_bgs[bg as usize]);
+        let bg_row = &mut bg_scratch.row;
+        self.get_row(self.get_pattern_mem(bg), mem, bg, bg_row, line_y, offset_per_tile); // TODO: merge these functions together?
+        if let Some(priority) = self.bg_priority[bg as usize] {
+            for data in bg_row.iter_mut() {
+                data.attrs.set(TileAttributes::PRIORITY, priority);
             }
-            if sub_window[x] {  // If pixel shows through sub window.
-                *sub = bg_row[bg_x];
+        }
+
+        if mosaic_amount > 0 {
+            for x in 0..H_RES {
+                bg_row[x] = bg_row[x - (x % (mosaic_amount + 1))];
             }
         }
+
+        copy_window_spans(bg_row, main_line, &window_regs.bg_window_spans(bg, Screen::Main));
+        copy_window_spans(bg_row, sub_line, &window_regs.bg_window_spans(bg, Screen::Sub));
     }
 
+    // The line is drawn to the main line, then copied to the sub line.
     fn draw_mode7_bg1_to_line(&self, mem: &VideoMem, main_line: &mut [Option<u8>], sub_line: &mut [Option<u8>], y: usize) {
         let vram = mem.get_vram();
         let regs = mem.get_bg_registers();
@@ -689,12 +754,7 @@ impl Renderer {
 
         let actual_y = y + 1;
 
-        let mut main_window = [true; H_RES];
-        window_regs.bg_window(BG::_1, Screen::Main, &mut main_window);
-        let mut sub_window = [true; H_RES];
-        window_regs.bg_window(BG::_1, Screen::Sub, &mut sub_window);
-
-        for (x, (main, sub)) in main_line.iter_mut().zip(sub_line.iter_mut()).enumerate() {
+        for (x, main) in main_line.iter_mut().enumerate() {
             let x_in = if regs.mode_7_flip_x() {x ^ 0xFF} else {x};
             let y_in = if regs.mode_7_flip_y() {actual_y ^ 0xFF} else {actual_y};
             let (bg_x, bg_y) = regs.calc_mode_7(x_in as i16, y_in as i16);
@@ -719,18 +779,15 @@ impl Renderer {
 
             if let (Some(x_val), Some(y_val)) = (lookup_x, lookup_y) {
                 let pix = get_mode_7_texel(vram, x_val, y_val);
-                let pix_out = if pix == 0 {None} else {Some(pix)};
-
-                if main_window[x] { // If pixel shows through main window.
-                    *main = pix_out;
-                }
-                if sub_window[x] {  // If pixel shows through sub window.
-                    *sub = pix_out;
-                }
+                *main = if pix == 0 {None} else {Some(pix)};
             }
         }
+
+        copy_window_spans(main_line, sub_line, &window_regs.bg_window_spans(BG::_1, Screen::Sub));
+        clear_window_spans(main_line, &window_regs.bg_window_spans(BG::_1, Screen::Main), None);
     }
 
+    // The line is drawn to the main line, then copied to the sub line.
     fn draw_mode7_bg2_to_line(&self, mem: &VideoMem, main_line: &mut [u8], sub_line: &mut [u8], y: usize) {
         let vram = mem.get_vram();
         let regs = mem.get_bg_registers();
@@ -738,24 +795,16 @@ impl Renderer {
 
         let actual_y = y + 1;
 
-        let mut main_window = [true; H_RES];
-        window_regs.bg_window(BG::_2, Screen::Main, &mut main_window);
-        let mut sub_window = [true; H_RES];
-        window_regs.bg_window(BG::_2, Screen::Sub, &mut sub_window);
-
         let bg_y = (actual_y + (regs.get_mode7_scroll_y() as usize)) % 1024;
 
-        for (x, (main, sub)) in main_line.iter_mut().zip(sub_line.iter_mut()).enumerate() {
+        for (x, main) in main_line.iter_mut().enumerate() {
             let bg_x = (x + (regs.get_mode7_scroll_x() as usize)) % 1024;
             // TODO: does this use reflect ?
-            let pix = get_mode_7_texel(vram, bg_x, bg_y);
-            if main_window[x] { // If pixel shows through main window.
-                *main = pix;
-            }
-            if sub_window[x] {  // If pixel shows through sub window.
-                *sub = pix;
-            }
+            *main = get_mode_7_texel(vram, bg_x, bg_y);
         }
+
+        copy_window_spans(main_line, sub_line, &window_regs.bg_window_spans(BG::_2, Screen::Sub));
+        clear_window_spans(main_line, &window_regs.bg_window_spans(BG::_2, Screen::Main), 0);
     }
 
     // Make a pixel from a texel and attributes
@@ -1018,4 +1067,18 @@ fn get_mode_7_texel(vram: &[u8], x: usize, y: usize) -> u8 {
     // Lookup pixel in vram.
     let tile_offset = tile_num * 128;
     vram[tile_offset + (tex_num * 2) + 1]
-}
\ No newline at end of file
+}
+
+// Copy the parts of a layer that show through the window.
+fn copy_window_spans<T: Copy>(src: &[T], dst: &mut [T], spans: &WindowSpans) {
+    for span in spans.iter().filter(|s| s.value) {
+        dst[span.range()].copy_from_slice(&src[span.range()]);
+    }
+}
+
+// Clear the parts of a layer that are hidden by the window.
+fn clear_window_spans<T: Copy>(line: &mut [T], spans: &WindowSpans, empty: T) {
+    for span in spans.iter().filter(|s| !s.value) {
+        line[span.range()].fill(empty);
+    }
+}
@@ -0,0 +1,573 @@
+// Renderer tests: build a scene in video memory, draw a single line, and compare it to a golden line.
+//
+// Golden lines are run-length encoded as (count, colour) pairs. Counts are in half-pixels
+// (the target has a sub and main half for each pixel, for hi-res), and colours are 0xRRGGBB.
+// If a change to the output is intended, the failure message contains the new golden line.
+use super::*;
+
+type Golden = &'static [(usize, u32)];
+
+// The line drawn in each test.
+const LINE: usize = 20;
+
+const RED: u16      = 0x001F;
+const GREEN: u16    = 0x03E0;
+const BLUE: u16     = 0x7C00;
+const WHITE: u16    = 0x7FFF;
+const GREY: u16     = 0x4210;
+
+struct Scene {
+    mem: VideoMem,
+}
+
+impl Scene {
+    // An empty scene at full brightness.
+    fn new(mode: u8) -> Self {
+        let mut mem = VideoMem::new();
+        mem.write(0x00, 0x0F);
+        mem.write(0x05, mode);
+        Scene {
+            mem: mem,
+        }
+    }
+
+    fn reg(&mut self, addr: u8, data: u8) {
+        self.mem.write(addr, data);
+    }
+
+    // Write both bytes of a double-write register, low first.
+    fn reg16(&mut self, addr: u8, data: u16) {
+        self.mem.write(addr, lo!(data));
+        self.mem.write(addr, hi!(data));
+    }
+
+    // Write words to VRAM.
+    fn vram(&mut self, word_addr: u16, data: &[u8]) {
+        self.mem.write(0x15, 0x80);
+        self.mem.write(0x16, lo!(word_addr));
+        self.mem.write(0x17, hi!(word_addr));
+        for word in data.chunks(2) {
+            self.mem.write(0x18, word[0]);
+            self.mem.write(0x19, word[1]);
+        }
+    }
+
+    // Write to the low or high byte of consecutive VRAM words.
+    fn vram_bytes(&mut self, word_addr: u16, hi_byte: bool, data: &[u8]) {
+        self.mem.write(0x15, if hi_byte {0x80} else {0x00});
+        self.mem.write(0x16, lo!(word_addr));
+        self.mem.write(0x17, hi!(word_addr));
+        for b in data {
+            self.mem.write(if hi_byte {0x19} else {0x18}, *b);
+        }
+    }
+
+    fn cgram(&mut self, index: u8, colours: &[u16]) {
+        self.mem.write(0x21, index);
+        for c in colours {
+            self.mem.write(0x22, lo!(*c));
+            self.mem.write(0x22, hi!(*c));
+        }
+    }
+
+    fn oam(&mut self, byte_addr: u16, data: &[u8]) {
+        self.mem.write(0x02, lo!(byte_addr >> 1));
+        self.mem.write(0x03, hi!(byte_addr >> 1));
+        for b in data {
+            self.mem.write(0x04, *b);
+        }
+    }
+
+    // Fill every row of a 32x32 map: columns in the range get the entry, the rest are tile 0.
+    fn map(&mut self, map_addr: u16, cols: core::ops::Range<usize>, entry: u16) {
+        let row = (0..32).map(|c| if cols.contains(&c) {entry} else {0}).collect::<Vec<_>>();
+        let bytes = (0..32).flat_map(|_| row.iter().flat_map(|e| vec![lo!(*e), hi!(*e)])).collect::<Vec<_>>();
+        self.vram(map_addr, &bytes);
+    }
+
+    fn draw_line(&mut self) -> Vec<u8> {
+        self.draw_line_with(Renderer::new())
+    }
+
+    fn draw_line_with(&mut self, mut renderer: Renderer) -> Vec<u8> {
+        self.mem.set_all_dirty();
+        renderer.setup_caches(&mut self.mem);
+        let mut target = vec![0; H_RES * 8 * (LINE + 1)];
+        renderer.draw_line(&self.mem, &mut target, LINE);
+        target.split_off(H_RES * 8 * LINE)
+    }
+}
+
+// Make a tile where the colour of each pixel in a row is given by the column.
+fn tile(bpp: usize, colour: impl Fn(usize) -> u8) -> Vec<u8> {
+    let mut data = Vec::new();
+    for plane_pair in 0..(bpp / 2) {
+        for _ in 0..8 {
+            for plane in (plane_pair * 2)..(plane_pair * 2 + 2) {
+                let bits = (0..8).fold(0, |acc, x| acc | (((colour(x) >> plane) & 1) << (7 - x)));
+                data.push(bits);
+            }
+        }
+    }
+    data
+}
+
+fn solid_tile(bpp: usize, colour: u8) -> Vec<u8> {
+    tile(bpp, |_| colour)
+}
+
+// Tile data for a set of tiles, starting at tile 0 (which is left transparent).
+fn tiles(bpp: usize, colours: &[u8]) -> Vec<u8> {
+    let mut data = solid_tile(bpp, 0);
+    for c in colours {
+        data.extend(solid_tile(bpp, *c));
+    }
+    data
+}
+
+// A map entry.
+fn entry(tile: u16, palette: u16, priority: bool) -> u16 {
+    tile | (palette << 10) | if priority {bit!(13, u16)} else {0}
+}
+
+fn encode_line(line: &[u8]) -> Vec<(usize, u32)> {
+    let mut runs: Vec<(usize, u32)> = Vec::new();
+    for half in line.chunks(4) {
+        let colour = make24!(half[0], half[1], half[2]);
+        match runs.last_mut() {
+            Some((count, c)) if *c == colour => *count += 1,
+            _ => runs.push((1, colour)),
+        }
+    }
+    runs
+}
+
+fn check_line(scene: &mut Scene, golden: Golden) {
+    let runs = encode_line(&scene.draw_line());
+    let formatted = runs.iter().map(|(n, c)| format!("({}, 0x{:06X})", n, c)).collect::<Vec<_>>().join(", ");
+    assert!(runs == golden, "line doesn't match golden. New golden:\n&[{}]", formatted);
+}
+
+// Mode 1 with BG1 on columns 2-5, BG2 on columns 4-7 and BG3 on columns 6-9, all 4bpp/4bpp/2bpp.
+fn mode_1_scene(bg3_priority: bool) -> Scene {
+    let mut scene = Scene::new(0x01 | if bg3_priority {bit!(3)} else {0});
+    scene.reg(0x07, 0x10);  // BG1 map: $1000
+    scene.reg(0x08, 0x14);  // BG2 map: $1400
+    scene.reg(0x09, 0x18);  // BG3 map: $1800
+    scene.reg(0x0B, 0x00);  // BG1&2 chars: $0000
+    scene.reg(0x0C, 0x02);  // BG3 chars: $2000
+    scene.reg(0x2C, 0x07);
+    scene.vram(0x0000, &tiles(4, &[1, 2]));
+    scene.vram(0x2000, &tiles(2, &[3]));
+    scene.map(0x1000, 2..6, entry(1, 0, false));
+    scene.map(0x1400, 4..8, entry(2, 1, false));
+    scene.map(0x1800, 6..10, entry(1, 0, true));
+    scene.cgram(0, &[GREY, RED, GREEN]);
+    scene.cgram(16, &[0, 0, BLUE]);
+    scene.cgram(3, &[WHITE]);
+    scene
+}
+
+#[test]
+fn mode_0_layers() {
+    let mut scene = Scene::new(0x00);
+    scene.reg(0x07, 0x10);
+    scene.reg(0x08, 0x14);
+    scene.reg(0x09, 0x18);
+    scene.reg(0x0A, 0x1C);
+    scene.reg(0x0B, 0x00);
+    scene.reg(0x0C, 0x00);
+    scene.reg(0x2C, 0x0F);
+    scene.vram(0x0000, &tiles(2, &[1, 2, 3]));
+    // Each BG overlaps the next, and uses its own block of 32 colours.
+    scene.map(0x1000, 0..4, entry(1, 0, false));
+    scene.map(0x1400, 2..6, entry(2, 0, false));
+    scene.map(0x1800, 4..8, entry(3, 0, false));
+    scene.map(0x1C00, 6..10, entry(1, 0, true));
+    scene.cgram(0, &[0, RED]);
+    scene.cgram(32 + 2, &[GREEN]);
+    scene.cgram(64 + 3, &[BLUE]);
+    scene.cgram(96 + 1, &[WHITE]);
+    check_line(&mut scene, &[(64, 0xFF0000), (32, 0x00FF00), (64, 0xFFFFFF), (352, 0x000000)]);
+}
+
+#[test]
+fn mode_1_layers() {
+    check_line(&mut mode_1_scene(false), &[(32, 0x848484), (64, 0xFF0000), (32, 0x0000FF), (32, 0xFFFFFF), (352, 0x848484)]);
+}
+
+// With the BG3 priority bit set, high priority BG3 tiles go in front of everything.
+#[test]
+fn mode_1_bg3_priority() {
+    check_line(&mut mode_1_scene(true), &[(32, 0x848484), (64, 0xFF0000), (64, 0xFFFFFF), (352, 0x848484)]);
+}
+
+// Forcing the BG2 priority bit on puts it in front of BG1.
+#[cfg(feature = "debug")]
+#[test]
+fn bg_priority_override() {
+    let mut renderer = Renderer::new();
+    renderer.set_bg_priority_override(1, Some(true));
+    let runs = encode_line(&mode_1_scene(false).draw_line_with(renderer));
+    assert_eq!(runs, &[(32, 0x848484), (32, 0xFF0000), (64, 0x0000FF), (32, 0xFFFFFF), (352, 0x848484)]);
+}
+
+#[test]
+fn mode_2_layers() {
+    let mut scene = Scene::new(0x02);
+    scene.reg(0x07, 0x10);
+    scene.reg(0x08, 0x14);
+    scene.reg(0x09, 0x18);  // Offset-per-tile table, left empty.
+    scene.reg(0x2C, 0x03);
+    scene.vram(0x0000, &tiles(4, &[1, 2]));
+    scene.map(0x1000, 0..3, entry(1, 0, false));
+    scene.map(0x1400, 2..5, entry(2, 0, true));
+    scene.cgram(0, &[0, RED, GREEN]);
+    check_line(&mut scene, &[(32, 0xFF0000), (48, 0x00FF00), (432, 0x000000)]);
+}
+
+// 8bpp BG1, with a gradient tile to check the pixel order.
+#[test]
+fn mode_3_8bpp() {
+    let mut scene = Scene::new(0x03);
+    scene.reg(0x07, 0x10);
+    scene.reg(0x08, 0x14);
+    scene.reg(0x0B, 0x20);  // BG2 chars: $2000
+    scene.reg(0x2C, 0x03);
+    scene.vram(0x0000, &solid_tile(8, 0));
+    scene.vram(0x0020, &tile(8, |x| 0x80 + (x as u8)));
+    scene.vram(0x2000, &tiles(4, &[1]));
+    scene.map(0x1000, 0..2, entry(1, 0, false));
+    scene.map(0x1400, 1..3, entry(1, 0, false));
+    scene.cgram(0, &[0, BLUE]);
+    let gradient = (0..8).map(|x| x * 0x0421).collect::<Vec<_>>();
+    scene.cgram(0x80, &gradient);
+    check_line(&mut scene, &[(2, 0x000000), (2, 0x080808), (2, 0x101010), (2, 0x181818), (2, 0x212121), (2, 0x292929), (2, 0x313131), (2, 0x393939), (2, 0x000000), (2, 0x080808), (2, 0x101010), (2, 0x181818), (2, 0x212121), (2, 0x292929), (2, 0x313131), (2, 0x393939), (16, 0x0000FF), (464, 0x000000)]);
+}
+
+// Direct colour: the 8bpp pixel value is the colour.
+#[test]
+fn mode_3_direct_colour() {
+    let mut scene = Scene::new(0x03);
+    scene.reg(0x07, 0x10);
+    scene.reg(0x2C, 0x01);
+    scene.reg(0x30, 0x01);
+    scene.vram(0x0000, &solid_tile(8, 0));
+    scene.vram(0x0020, &tile(8, |x| [0x07, 0x38, 0xC0, 0xFF, 0x49, 0x92, 0x24, 0x01][x]));
+    scene.map(0x1000, 0..1, entry(1, 0, false));
+    check_line(&mut scene, &[(2, 0xEE0000), (2, 0x00EE00), (2, 0x0000DB), (2, 0xEEEEDB), (2, 0x222249), (2, 0x444492), (2, 0x888800), (2, 0x220000), (496, 0x000000)]);
+}
+
+#[test]
+fn mode_4_layers() {
+    let mut scene = Scene::new(0x04);
+    scene.reg(0x07, 0x10);
+    scene.reg(0x08, 0x14);
+    scene.reg(0x0B, 0x20);
+    scene.reg(0x2C, 0x03);
+    scene.vram(0x0000, &solid_tile(8, 0));
+    scene.vram(0x0020, &solid_tile(8, 0x40));
+    scene.vram(0x2000, &tiles(2, &[3]));
+    scene.map(0x1000, 0..2, entry(1, 0, false));
+    scene.map(0x1400, 1..3, entry(1, 0, true));
+    scene.cgram(0, &[0, 0, 0, GREEN]);
+    scene.cgram(0x40, &[RED]);
+    check_line(&mut scene, &[(16, 0xFF0000), (32, 0x00FF00), (464, 0x000000)]);
+}
+
+// Hi-res: tiles are 16 pixels wide (tile N then tile N+1), and each layer is on both screens.
+// The sub screen is drawn to the left half of each pixel.
+// Tile 1 is left transparent so that tile 0 doesn't pull in tile 1.
+#[test]
+fn mode_5_hires() {
+    let mut scene = Scene::new(0x05);
+    scene.reg(0x07, 0x10);
+    scene.reg(0x08, 0x14);
+    scene.reg(0x0B, 0x20);
+    scene.reg(0x2C, 0x03);
+    scene.reg(0x2D, 0x03);
+    scene.reg(0x30, 0x02);
+    scene.vram(0x0000, &tiles(4, &[0, 1, 2]));
+    scene.vram(0x2000, &tiles(2, &[0, 3, 3]));
+    scene.map(0x1000, 0..1, entry(2, 0, false));
+    scene.map(0x1400, 1..2, entry(2, 0, false));
+    scene.cgram(0, &[0, RED, GREEN, BLUE]);
+    check_line(&mut scene, &[(16, 0xFF0000), (16, 0x00FF00), (32, 0x0000FF), (448, 0x000000)]);
+}
+
+#[test]
+fn mode_6_hires() {
+    let mut scene = Scene::new(0x06);
+    scene.reg(0x07, 0x10);
+    scene.reg(0x2C, 0x01);
+    scene.reg(0x2D, 0x01);
+    scene.reg(0x30, 0x02);
+    scene.vram(0x0000, &[tiles(4, &[0, 1]), tile(4, |x| (x as u8) & 3)].concat());
+    scene.map(0x1000, 0..2, entry(2, 0, false));
+    scene.cgram(0, &[0, RED, GREEN, BLUE]);
+    check_line(&mut scene, &[(16, 0xFF0000), (2, 0x000000), (2, 0xFF0000), (2, 0x00FF00), (2, 0x0000FF), (2, 0x000000), (2, 0xFF0000), (2, 0x00FF00), (2, 0x0000FF), (16, 0xFF0000), (2, 0x000000), (2, 0xFF0000), (2, 0x00FF00), (2, 0x0000FF), (2, 0x000000), (2, 0xFF0000), (2, 0x00FF00), (2, 0x0000FF), (448, 0x000000)]);
+}
+
+// Mode 7 with the identity matrix, and the map offset by scrolling.
+#[test]
+fn mode_7_identity() {
+    let mut scene = Scene::new(0x07);
+    scene.reg(0x2C, 0x01);
+    scene.reg16(0x1B, 0x0100);
+    scene.reg16(0x1E, 0x0100);
+    scene.reg16(0x0D, 0x0004);
+    // Map row for line 20: tiles 1 and 2 alternate.
+    let row = (0..128).map(|c| 1 + (c & 1) as u8).collect::<Vec<_>>();
+    scene.vram_bytes((LINE / 8 * 128) as u16, false, &row);
+    let tile_data = (0..64).map(|i| if (i % 8) < 4 {1} else {2})
+        .chain((0..64).map(|i| 3 + ((i % 8) as u8 & 1)))
+        .collect::<Vec<_>>();
+    scene.vram_bytes(64, true, &tile_data);
+    scene.cgram(0, &[0, RED, GREEN, BLUE, WHITE]);
+    check_line(&mut scene, &[(8, 0x00FF00), (2, 0x0000FF), (2, 0xFFFFFF), (2, 0x0000FF), (2, 0xFFFFFF), (2, 0x0000FF), (2, 0xFFFFFF), (2, 0x0000FF), (2, 0xFFFFFF), (8, 0xFF0000), (8, 0x00FF00), (2, 0x0000FF), (2, 0xFFFFFF), (2, 0x0000FF), (2, 0xFFFFFF), (2, 0x0000FF), (2, 0xFFFFFF), (2, 0x0000FF), (2, 0xFFFFFF), (8, 0xFF0000), (8, 0x00FF00), (2, 0x0000FF), (2, 0xFFFFFF), (2, 0x0000FF), (2, 0xFFFFFF), (2, 0x0000FF), (2, 0xFFFFFF), (2, 0x0000FF), (2, 0xFFFFFF), (8, 0xFF0000), (8, 0x00FF00), (2, 0x0000FF), (2, 0xFFFFFF), (2, 0x0000FF), (2, 0xFFFFFF), (2, 0x0000FF), (2, 0xFFFFFF), (2, 0x0000FF), (2, 0xFFFFFF), (8, 0xFF0000), (8, 0x00FF00), (2, 0x0000FF), (2, 0xFFFFFF), (2, 0x0000FF), (2, 0xFFFFFF), (2, 0x0000FF), (2, 0xFFFFFF), (2, 0x0000FF), (2, 0xFFFFFF), (8, 0xFF0000), (8, 0x00FF00), (2, 0x0000FF), (2, 0xFFFFFF), (2, 0x0000FF), (2, 0xFFFFFF), (2, 0x0000FF), (2, 0xFFFFFF), (2, 0x0000FF), (2, 0xFFFFFF), (8, 0xFF0000), (8, 0x00FF00), (2, 0x0000FF), (2, 0xFFFFFF), (2, 0x0000FF), (2, 0xFFFFFF), (2, 0x0000FF), (2, 0xFFFFFF), (2, 0x0000FF), (2, 0xFFFFFF), (8, 0xFF0000), (8, 0x00FF00), (2, 0x0000FF), (2, 0xFFFFFF), (2, 0x0000FF), (2, 0xFFFFFF), (2, 0x0000FF), (2, 0xFFFFFF), (2, 0x0000FF), (2, 0xFFFFFF), (8, 0xFF0000), (8, 0x00FF00), (2, 0x0000FF), (2, 0xFFFFFF), (2, 0x0000FF), (2, 0xFFFFFF), (2, 0x0000FF), (2, 0xFFFFFF), (2, 0x0000FF), (2, 0xFFFFFF), (8, 0xFF0000), (8, 0x00FF00), (2, 0x0000FF), (2, 0xFFFFFF), (2, 0x0000FF), (2, 0xFFFFFF), (2, 0x0000FF), (2, 0xFFFFFF), (2, 0x0000FF), (2, 0xFFFFFF), (8, 0xFF0000), (8, 0x00FF00), (2, 0x0000FF), (2, 0xFFFFFF), (2, 0x0000FF), (2, 0xFFFFFF), (2, 0x0000FF), (2, 0xFFFFFF), (2, 0x0000FF), (2, 0xFFFFFF), (8, 0xFF0000), (8, 0x00FF00), (2, 0x0000FF), (2, 0xFFFFFF), (2, 0x0000FF), (2, 0xFFFFFF), (2, 0x0000FF), (2, 0xFFFFFF), (2, 0x0000FF), (2, 0xFFFFFF), (8, 0xFF0000), (8, 0x00FF00), (2, 0x0000FF), (2, 0xFFFFFF), (2, 0x0000FF), (2, 0xFFFFFF), (2, 0x0000FF), (2, 0xFFFFFF), (2, 0x0000FF), (2, 0xFFFFFF), (8, 0xFF0000), (8, 0x00FF00), (2, 0x0000FF), (2, 0xFFFFFF), (2, 0x0000FF), (2, 0xFFFFFF), (2, 0x0000FF), (2, 0xFFFFFF), (2, 0x0000FF), (2, 0xFFFFFF), (8, 0xFF0000), (8, 0x00FF00), (2, 0x0000FF), (2, 0xFFFFFF), (2, 0x0000FF), (2, 0xFFFFFF), (2, 0x0000FF), (2, 0xFFFFFF), (2, 0x0000FF), (2, 0xFFFFFF), (8, 0xFF0000), (8, 0x00FF00), (2, 0x0000FF), (2, 0xFFFFFF), (2, 0x0000FF), (2, 0xFFFFFF), (2, 0x0000FF), (2, 0xFFFFFF), (2, 0x0000FF), (2, 0xFFFFFF), (8, 0xFF0000)]);
+}
+
+// Sprites go in front of or behind BGs depending on their priority.
+#[test]
+fn sprite_priority() {
+    let mut scene = mode_1_scene(false);
+    scene.reg(0x01, 0x01);  // OBJ chars: $2000 (shared with BG3)
+    scene.reg(0x2C, 0x13);
+    scene.vram(0x2000 + (4 * 16), &solid_tile(4, 1));
+    scene.cgram(128 + 16, &[0, WHITE]);
+    scene.cgram(128 + 32 + 1, &[0x5294]);
+    // Sprite 0: priority 0 at x=20 (behind BG1).
+    // Sprite 1: priority 3 at x=28 (in front of everything).
+    scene.oam(0, &[20, (LINE - 4) as u8, 4, 0x02, 28, (LINE - 4) as u8, 4, 0x34]);
+    check_line(&mut scene, &[(32, 0x848484), (24, 0xFF0000), (16, 0xA5A5A5), (24, 0xFF0000), (32, 0x0000FF), (384, 0x848484)]);
+}
+
+// A single BG, masked by windows on the main screen with each combination logic.
+fn window_scene(logic: u8) -> Scene {
+    let mut scene = Scene::new(0x01);
+    scene.reg(0x07, 0x10);
+    scene.reg(0x2C, 0x01);
+    scene.reg(0x2E, 0x01);
+    scene.vram(0x0000, &tiles(4, &[1]));
+    scene.map(0x1000, 0..32, entry(1, 0, false));
+    scene.cgram(0, &[GREY, RED]);
+    scene.reg(0x26, 32);
+    scene.reg(0x27, 95);
+    scene.reg(0x28, 64);
+    scene.reg(0x29, 159);
+    scene.reg(0x23, 0x0A);  // Window 1 and 2 enabled for BG1.
+    scene.reg(0x2A, logic);
+    scene
+}
+
+#[test]
+fn window_single() {
+    let mut scene = window_scene(0);
+    scene.reg(0x23, 0x02);
+    check_line(&mut scene, &[(64, 0xFF0000), (128, 0x848484), (320, 0xFF0000)]);
+}
+
+#[test]
+fn window_inverted() {
+    let mut scene = window_scene(0);
+    scene.reg(0x23, 0x03);
+    check_line(&mut scene, &[(64, 0x848484), (128, 0xFF0000), (320, 0x848484)]);
+}
+
+#[test]
+fn window_or() {
+    check_line(&mut window_scene(0), &[(64, 0xFF0000), (256, 0x848484), (192, 0xFF0000)]);
+}
+
+#[test]
+fn window_and() {
+    check_line(&mut window_scene(1), &[(128, 0xFF0000), (64, 0x848484), (320, 0xFF0000)]);
+}
+
+#[test]
+fn window_xor() {
+    check_line(&mut window_scene(2), &[(64, 0xFF0000), (64, 0x848484), (64, 0xFF0000), (128, 0x848484), (192, 0xFF0000)]);
+}
+
+#[test]
+fn window_xnor() {
+    check_line(&mut window_scene(3), &[(64, 0x848484), (64, 0xFF0000), (64, 0x848484), (128, 0xFF0000), (192, 0x848484)]);
+}
+
+// Window 1 reaching the right edge, and window 2 empty with its left edge past its right.
+#[test]
+fn window_edges() {
+    let mut scene = window_scene(0);
+    scene.reg(0x26, 200);
+    scene.reg(0x27, 255);
+    scene.reg(0x28, 100);
+    scene.reg(0x29, 50);
+    check_line(&mut scene, &[(400, 0xFF0000), (112, 0x848484)]);
+}
+
+// BG1 on the main screen over the left 3/4, BG2 on the sub screen over the right 3/4.
+fn colour_math_scene(cgwsel: u8, cgadsub: u8) -> Scene {
+    let mut scene = Scene::new(0x01);
+    scene.reg(0x07, 0x10);
+    scene.reg(0x08, 0x14);
+    scene.reg(0x2C, 0x01);
+    scene.reg(0x2D, 0x02);
+    scene.vram(0x0000, &tiles(4, &[1, 2]));
+    scene.map(0x1000, 0..24, entry(1, 0, false));
+    scene.map(0x1400, 8..32, entry(2, 0, false));
+    scene.cgram(0, &[0x0842, 0x4010, 0x0210]);
+    scene.reg(0x30, cgwsel);
+    scene.reg(0x31, cgadsub);
+    scene.reg(0x32, 0x20 | 0x08);   // Fixed colour: red 8
+    scene
+}
+
+#[test]
+fn colour_math_add_sub_screen() {
+    check_line(&mut colour_math_scene(0x02, 0x01), &[(128, 0xC60084), (256, 0xFF8484), (128, 0x101010)]);
+}
+
+#[test]
+fn colour_math_add_half() {
+    check_line(&mut colour_math_scene(0x02, 0x41), &[(128, 0xC60084), (256, 0x844242), (128, 0x101010)]);
+}
+
+#[test]
+fn colour_math_subtract() {
+    check_line(&mut colour_math_scene(0x02, 0x81), &[(128, 0x420084), (256, 0x000084), (128, 0x101010)]);
+}
+
+#[test]
+fn colour_math_subtract_half() {
+    check_line(&mut colour_math_scene(0x02, 0xC1), &[(128, 0x420084), (256, 0x000042), (128, 0x101010)]);
+}
+
+#[test]
+fn colour_math_fixed_colour() {
+    check_line(&mut colour_math_scene(0x00, 0x01), &[(384, 0xC60084), (128, 0x101010)]);
+}
+
+#[test]
+fn colour_math_backdrop() {
+    check_line(&mut colour_math_scene(0x02, 0x20), &[(384, 0x840084), (128, 0x949410)]);
+}
+
+// Clip to black inside the colour window, and prevent colour math outside it.
+#[test]
+fn colour_math_window() {
+    let mut scene = colour_math_scene(0x92, 0x01);
+    scene.reg(0x25, 0x20);
+    scene.reg(0x26, 32);
+    scene.reg(0x27, 127);
+    check_line(&mut scene, &[(64, 0x840084), (64, 0x420000), (128, 0x848400), (128, 0x840084), (128, 0x101010)]);
+}
+
+// Only sprites using palettes 4-7 take part in colour math.
+#[test]
+fn colour_math_sprites() {
+    let mut scene = colour_math_scene(0x00, 0x10);
+    scene.reg(0x01, 0x01);
+    scene.reg(0x2C, 0x11);
+    scene.vram(0x2000 + 16, &solid_tile(4, 1));
+    scene.cgram(128 + 1, &[GREY]);
+    scene.cgram(128 + 64 + 1, &[GREY]);
+    scene.oam(0, &[16, (LINE - 4) as u8, 1, 0x30, 64, (LINE - 4) as u8, 1, 0x38]);
+    check_line(&mut scene, &[(32, 0x840084), (16, 0x848484), (80, 0x840084), (16, 0xC68484), (240, 0x840084), (128, 0x101010)]);
+}
+
+#[test]
+fn pseudo_hires() {
+    let mut scene = colour_math_scene(0x02, 0x00);
+    scene.reg(0x33, 0x08);
+    check_line(&mut scene, &[(1, 0x420000), (1, 0x840084), (1, 0x420000), (1, 0x840084), (1, 0x420000), (1, 0x840084), (1, 0x420000), (1, 0x840084), (1, 0x420000), (1, 0x840084), (1, 0x420000), (1, 0x840084), (1, 0x420000), (1, 0x840084), (1, 0x420000), (1, 0x840084), (1, 0x420000), (1, 0x840084), (1, 0x420000), (1, 0x840084), (1, 0x420000), (1, 0x840084), (1, 0x420000), (1, 0x840084), (1, 0x420000), (1, 0x840084), (1, 0x420000), (1, 0x840084), (1, 0x420000), (1, 0x840084), (1, 0x420000), (1, 0x840084), (1, 0x420000), (1, 0x840084), (1, 0x420000), (1, 0x840084), (1, 0x420000), (1, 0x840084), (1, 0x420000), (1, 0x840084), (1, 0x420000), (1, 0x840084), (1, 0x420000), (1, 0x840084), (1, 0x420000), (1, 0x840084), (1, 0x420000), (1, 0x840084), (1, 0x420000), (1, 0x840084), (1, 0x420000), (1, 0x840084), (1, 0x420000), (1, 0x840084), (1, 0x420000), (1, 0x840084), (1, 0x420000), (1, 0x840084), (1, 0x420000), (1, 0x840084), (1, 0x420000), (1, 0x840084), (1, 0x420000), (1, 0x840084), (1, 0x420000), (1, 0x840084), (1, 0x420000), (1, 0x840084), (1, 0x420000), (1, 0x840084), (1, 0x420000), (1, 0x840084), (1, 0x420000), (1, 0x840084), (1, 0x420000), (1, 0x840084), (1, 0x420000), (1, 0x840084), (1, 0x420000), (1, 0x840084), (1, 0x420000), (1, 0x840084), (1, 0x420000), (1, 0x840084), (1, 0x420000), (1, 0x840084), (1, 0x420000), (1, 0x840084), (1, 0x420000), (1, 0x840084), (1, 0x420000), (1, 0x840084), (1, 0x420000), (1, 0x840084), (1, 0x420000), (1, 0x840084), (1, 0x420000), (1, 0x840084), (1, 0x420000), (1, 0x840084), (1, 0x420000), (1, 0x840084), (1, 0x420000), (1, 0x840084), (1, 0x420000), (1, 0x840084), (1, 0x420000), (1, 0x840084), (1, 0x420000), (1, 0x840084), (1, 0x420000), (1, 0x840084), (1, 0x420000), (1, 0x840084), (1, 0x420000), (1, 0x840084), (1, 0x420000), (1, 0x840084), (1, 0x420000), (1, 0x840084), (1, 0x420000), (1, 0x840084), (1, 0x420000), (1, 0x840084), (1, 0x420000), (1, 0x840084), (1, 0x420000), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x840084), (1, 0x848400), (1, 0x101010), (1, 0x848400), (1, 0x101010), (1, 0x848400), (1, 0x101010), (1, 0x848400), (1, 0x101010), (1, 0x848400), (1, 0x101010), (1, 0x848400), (1, 0x101010), (1, 0x848400), (1, 0x101010), (1, 0x848400), (1, 0x101010), (1, 0x848400), (1, 0x101010), (1, 0x848400), (1, 0x101010), (1, 0x848400), (1, 0x101010), (1, 0x848400), (1, 0x101010), (1, 0x848400), (1, 0x101010), (1, 0x848400), (1, 0x101010), (1, 0x848400), (1, 0x101010), (1, 0x848400), (1, 0x101010), (1, 0x848400), (1, 0x101010), (1, 0x848400), (1, 0x101010), (1, 0x848400), (1, 0x101010), (1, 0x848400), (1, 0x101010), (1, 0x848400), (1, 0x101010), (1, 0x848400), (1, 0x101010), (1, 0x848400), (1, 0x101010), (1, 0x848400), (1, 0x101010), (1, 0x848400), (1, 0x101010), (1, 0x848400), (1, 0x101010), (1, 0x848400), (1, 0x101010), (1, 0x848400), (1, 0x101010), (1, 0x848400), (1, 0x101010), (1, 0x848400), (1, 0x101010), (1, 0x848400), (1, 0x101010), (1, 0x848400), (1, 0x101010), (1, 0x848400), (1, 0x101010), (1, 0x848400), (1, 0x101010), (1, 0x848400), (1, 0x101010), (1, 0x848400), (1, 0x101010), (1, 0x848400), (1, 0x101010), (1, 0x848400), (1, 0x101010), (1, 0x848400), (1, 0x101010), (1, 0x848400), (1, 0x101010), (1, 0x848400), (1, 0x101010), (1, 0x848400), (1, 0x101010), (1, 0x848400), (1, 0x101010), (1, 0x848400), (1, 0x101010), (1, 0x848400), (1, 0x101010), (1, 0x848400), (1, 0x101010), (1, 0x848400), (1, 0x101010), (1, 0x848400), (1, 0x101010), (1, 0x848400), (1, 0x101010), (1, 0x848400), (1, 0x101010), (1, 0x848400), (1, 0x101010), (1, 0x848400), (1, 0x101010), (1, 0x848400), (1, 0x101010), (1, 0x848400), (1, 0x101010), (1, 0x848400), (1, 0x101010), (1, 0x848400), (1, 0x101010), (1, 0x848400), (1, 0x101010), (1, 0x848400), (1, 0x101010), (1, 0x848400), (1, 0x101010), (1, 0x848400), (1, 0x101010), (1, 0x848400), (1, 0x101010), (1, 0x848400), (1, 0x101010), (1, 0x848400), (1, 0x101010), (1, 0x848400), (1, 0x101010)]);
+}
+
+#[test]
+fn brightness() {
+    let mut scene = mode_1_scene(false);
+    scene.reg(0x00, 0x07);
+    check_line(&mut scene, &[(32, 0x3D3D3D), (64, 0x770000), (32, 0x000077), (32, 0x777777), (352, 0x3D3D3D)]);
+}
+
+// Explain a pixel on the line, and check it matches the colour drawn.
+#[cfg(feature = "debug")]
+fn explain(scene: &mut Scene, x: usize) -> crate::debug::PixelExplanation {
+    let line = scene.draw_line();
+    let explanation = explain::explain_pixel(&scene.mem, x, LINE).unwrap();
+    assert_eq!(explanation.colour, [line[x * 8 + 4], line[x * 8 + 5], line[x * 8 + 6]]);
+    explanation
+}
+
+#[cfg(feature = "debug")]
+#[test]
+fn explain_layers() {
+    use crate::debug::{ColourMath, PixelLayer};
+
+    let mut scene = colour_math_scene(0x02, 0x41);
+    let pixel = explain(&mut scene, 100);
+    assert_eq!(pixel.mode, 1);
+    assert_eq!(pixel.main.layer, PixelLayer::BG1);
+    assert_eq!(pixel.main.palette_index, Some(1));
+    assert_eq!(pixel.main.colour, [0x84, 0x00, 0x84]);
+    assert_eq!(pixel.sub.map(|p| (p.layer, p.palette_index)), Some((PixelLayer::BG2, Some(2))));
+    assert!(pixel.windows[0].main && !pixel.windows[0].sub);
+    assert!(!pixel.windows[1].main && pixel.windows[1].sub);
+    assert_eq!(pixel.colour_math, Some(ColourMath {subtract: false, half: true, operand: [0x84, 0x84, 0x00]}));
+
+    // The backdrop doesn't take part in colour math here.
+    let pixel = explain(&mut scene, 200);
+    assert_eq!(pixel.main.layer, PixelLayer::Backdrop);
+    assert_eq!(pixel.main.palette_index, Some(0));
+    assert_eq!(pixel.colour_math, None);
+
+    assert!(explain::explain_pixel(&scene.mem, H_RES, LINE).is_none());
+}
+
+#[cfg(feature = "debug")]
+#[test]
+fn explain_windows() {
+    use crate::debug::PixelLayer;
+
+    let mut scene = window_scene(0);
+    let pixel = explain(&mut scene, 10);
+    assert_eq!(pixel.main.layer, PixelLayer::BG1);
+    assert!(pixel.windows[0].main);
+    let pixel = explain(&mut scene, 40);
+    assert_eq!(pixel.main.layer, PixelLayer::Backdrop);
+    assert!(!pixel.windows[0].main);
+
+    // Clip to black inside the colour window, and prevent colour math outside it.
+    let mut scene = colour_math_scene(0x92, 0x01);
+    scene.reg(0x25, 0x20);
+    scene.reg(0x26, 32);
+    scene.reg(0x27, 127);
+    let pixel = explain(&mut scene, 10);
+    assert!(!pixel.colour_window && !pixel.clip_to_black);
+    assert_eq!(pixel.colour_math, None);
+    let pixel = explain(&mut scene, 40);
+    assert!(pixel.colour_window && pixel.clip_to_black);
+    assert!(pixel.colour_math.is_some());
+}
+
+// Only sprites using palettes 4-7 take part in colour math.
+#[cfg(feature = "debug")]
+#[test]
+fn explain_sprites() {
+    use crate::debug::{ColourMath, PixelLayer};
+
+    let mut scene = colour_math_scene(0x00, 0x10);
+    scene.reg(0x01, 0x01);
+    scene.reg(0x2C, 0x11);
+    scene.vram(0x2000 + 16, &solid_tile(4, 1));
+    scene.cgram(128 + 1, &[GREY]);
+    scene.cgram(128 + 64 + 1, &[GREY]);
+    scene.oam(0, &[16, (LINE - 4) as u8, 1, 0x30, 64, (LINE - 4) as u8, 1, 0x38]);
+
+    let pixel = explain(&mut scene, 20);
+    assert_eq!((pixel.main.layer, pixel.main.palette_index), (PixelLayer::Obj, Some(129)));
+    assert_eq!(pixel.colour_math, None);
+    let pixel = explain(&mut scene, 68);
+    assert_eq!((pixel.main.layer, pixel.main.palette_index), (PixelLayer::Obj, Some(193)));
+    assert_eq!(pixel.sub, None);
+    assert_eq!(pixel.colour_math, Some(ColourMath {subtract: false, half: false, operand: [0x42, 0x00, 0x00]}));
+}
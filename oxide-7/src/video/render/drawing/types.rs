@@ -1,12 +1,19 @@
 // Types to assist with drawing.
 
+use alloc::vec::Vec;
+
 use crate::video::render::Colour;
+use crate::constants::screen::H_RES;
+
+use super::lines::BGData;
 
 // A sprite colour for final compositing.
 #[derive(Clone, Copy)]
 pub struct SpriteColour {
     pub colour:     Colour, // The colour of the sprite
-    pub col_math:   bool    // Should it participate in colour math
+    pub col_math:   bool,   // Should it participate in colour math
+    #[cfg_attr(not(feature = "debug"), allow(dead_code))]
+    pub index:      u8      // Index into the object palettes
 }
 
 // A sprite pixel.
@@ -32,18 +39,20 @@ impl SpritePixel {
     }
 
     // Get the final pixel out, and test it to see if it should participate in colour math.
+    // Masked and empty pixels have no colour.
     pub fn pixel(&self) -> Pixel {
         match self {
             SpritePixel::Prio3(c) => if c.col_math {Pixel::ObjHi(c.colour)} else {Pixel::ObjLo(c.colour)},
             SpritePixel::Prio2(c) => if c.col_math {Pixel::ObjHi(c.colour)} else {Pixel::ObjLo(c.colour)},
             SpritePixel::Prio1(c) => if c.col_math {Pixel::ObjHi(c.colour)} else {Pixel::ObjLo(c.colour)},
             SpritePixel::Prio0(c) => if c.col_math {Pixel::ObjHi(c.colour)} else {Pixel::ObjLo(c.colour)},
-            _ => panic!("Don't call this on pixels with no colour value.")
+            _ => Pixel::None
         }
     }
 }
 
 // A colour value with source information.
+#[derive(Clone, Copy)]
 pub enum Pixel {
     BG1(Colour),
     BG2(Colour),
@@ -68,3 +77,70 @@ impl Pixel {
         }
     }
 }
+
+// Scratch buffers for drawing a line.
+// These are kept by the renderer and reused for each line, to avoid allocating.
+pub struct LineBuffers {
+    pub main_sprites:   [SpritePixel; H_RES],
+    pub sub_sprites:    [SpritePixel; H_RES],
+    pub main_bgs:       [[BGData; H_RES]; 4],
+    pub sub_bgs:        [[BGData; H_RES]; 4],
+    pub main_mode7_bg1: [Option<u8>; H_RES],
+    pub sub_mode7_bg1:  [Option<u8>; H_RES],
+    pub main_mode7_bg2: [u8; H_RES],
+    pub sub_mode7_bg2:  [u8; H_RES],
+
+    pub bg_scratch:     BGScratch,
+    pub objects:        Vec<usize>, // Indices of the objects that appear on the line.
+}
+
+impl LineBuffers {
+    pub fn new() -> Self {
+        Self {
+            main_sprites:   [SpritePixel::None; H_RES],
+            sub_sprites:    [SpritePixel::None; H_RES],
+            main_bgs:       [[BGData::default(); H_RES]; 4],
+            sub_bgs:        [[BGData::default(); H_RES]; 4],
+            main_mode7_bg1: [None; H_RES],
+            sub_mode7_bg1:  [None; H_RES],
+            main_mode7_bg2: [0; H_RES],
+            sub_mode7_bg2:  [0; H_RES],
+
+            bg_scratch:     BGScratch::new(),
+            objects:        Vec::with_capacity(128),
+        }
+    }
+
+    // Reset the sprite lines and the first num_bgs background lines.
+    pub fn clear(&mut self, num_bgs: usize) {
+        self.main_sprites.fill(SpritePixel::None);
+        self.sub_sprites.fill(SpritePixel::None);
+        for (main, sub) in self.main_bgs.iter_mut().zip(self.sub_bgs.iter_mut()).take(num_bgs) {
+            main.fill(BGData::default());
+            sub.fill(BGData::default());
+        }
+    }
+
+    // Reset the mode 7 background lines.
+    pub fn clear_mode7(&mut self) {
+        self.main_sprites.fill(SpritePixel::None);
+        self.sub_sprites.fill(SpritePixel::None);
+        self.main_mode7_bg1.fill(None);
+        self.sub_mode7_bg1.fill(None);
+        self.main_mode7_bg2.fill(0);
+        self.sub_mode7_bg2.fill(0);
+    }
+}
+
+// Intermediate buffers used when drawing a single background.
+pub struct BGScratch {
+    pub row:    [BGData; H_RES],
+}
+
+impl BGScratch {
+    fn new() -> Self {
+        Self {
+            row:    [BGData::default(); H_RES],
+        }
+    }
+}
@@ -1,19 +1,36 @@
-// Tools for communicating with the renderer (and its thread on certain platforms).
+// Tools for communicating with the renderer (and its thread on platforms with std).
 
 //mod bgcache;
 mod patternmem;
 mod drawing;
 mod palette;
 
-use std::sync::{
-    Arc, Mutex
+#[cfg(feature = "debug")]
+pub use drawing::{
+    explain,
+    export,
+    SPRITES_PER_LINE
 };
 
+use alloc::boxed::Box;
+#[cfg(feature = "std")]
 use crossbeam_channel::{
     bounded,
     Sender,
     Receiver
 };
+use serde::{
+    Serialize,
+    Deserialize
+};
+
+use crate::{
+    constants::screen::H_RES,
+    sync::{
+        Arc, Mutex
+    }
+};
+use super::ram::VideoMem;
 
 // Renderer trait.
 pub trait Renderable {
@@ -53,7 +70,7 @@ impl From<u8> for VideoMode {
 
 pub type RenderTarget = Arc<Mutex<Box<[u8]>>>;
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
 pub struct Colour {
     pub r: u8,
     pub g: u8,
@@ -107,20 +124,40 @@ impl Colour {
     }*/
 }
 
+// Renderer used by the PPU.
+#[cfg(feature = "std")]
+pub type LineRenderer = RenderThread;
+#[cfg(not(feature = "std"))]
+pub type LineRenderer = RenderInline;
+
 // Messages to send to the render thread.
+#[cfg(feature = "std")]
 enum RendererMessage {
     StartFrame(RenderTarget),   // Begin frame, and target the provided byte array.
     DrawLine(usize),
+    BlankLine(usize),               // Clear a whole line, including the widescreen margins.
+    BlankLineFrom(usize, usize),    // Forced blank started part way through a line: clear from x onwards.
+    DrawLineFrom(usize, usize),     // Forced blank ended part way through a line: draw from x onwards.
+    SpriteLimit(bool),          // Enable or disable the per-line sprite limit.
+    Widescreen(usize),          // Set the number of extra pixels to draw on each side of the line.
+    #[cfg(feature = "debug")]
+    BGPriority(usize, Option<bool>),    // Force the priority bit of a background.
+    Fence,                      // Reply once every message before this has been handled.
 }
 
 // Renderer for video that spawns a thread to render on.
+// If the thread stops, messages are dropped and nothing more is drawn.
+#[cfg(feature = "std")]
 pub struct RenderThread {
     sender:     Sender<RendererMessage>,
     receiver:   Receiver<()>,
+    check:      RenderCheck,
 }
 
+#[cfg(feature = "std")]
 impl RenderThread {
     pub fn new(mem: super::VRamRef) -> Self {
+        let check_mem = mem.clone();
         let (send_msg, recv_msg) = bounded(224);
         let (send_reply, recv_reply) = bounded(224);
 
@@ -128,23 +165,52 @@ impl RenderThread {
             use RendererMessage::*;
             let mut target = None;
             let mut renderer = drawing::Renderer::new();
+            let mut widescreen = 0;
 
             while let Ok(msg) = recv_msg.recv() {
                 match msg {
                     StartFrame(data) => {
                         target = Some(data);
                     },
+                    SpriteLimit(enable) => {
+                        renderer.set_sprite_limit(enable);
+                    },
+                    Widescreen(extra) => {
+                        widescreen = extra;
+                        renderer.set_widescreen(extra);
+                    },
+                    #[cfg(feature = "debug")]
+                    BGPriority(bg, priority) => {
+                        renderer.set_bg_priority_override(bg, priority);
+                    },
+                    // Lines are skipped if no frame has been started.
                     DrawLine(y) => {
-                        let mut mem = mem.lock().unwrap();
-                        send_reply.send(()).unwrap();
-                        if !mem.get_bg_registers().in_fblank() {
-                            renderer.setup_caches(&mut mem);
-                            let mut t = target.as_ref().unwrap().lock().unwrap();
-                            renderer.draw_line(&mem, &mut t, y);
-                        } else {
-                            let mut t = target.as_ref().unwrap().lock().unwrap();
-                            clear_line(&mut t, y);
+                        let mut mem = mem.lock();
+                        let _ = send_reply.send(());
+                        if let Some(target) = &target {
+                            render_line(&mut renderer, &mut mem, target, y, widescreen);
+                        }
+                    },
+                    BlankLine(y) => {
+                        if let Some(target) = &target {
+                            clear_line(&mut target.lock(), y, widescreen);
+                        }
+                    },
+                    BlankLineFrom(y, x) => {
+                        if let Some(target) = &target {
+                            let mut t = target.lock();
+                            clear_line_range(&mut t, y, x + widescreen, H_RES + (widescreen * 2), widescreen);
+                        }
+                    },
+                    DrawLineFrom(y, x) => {
+                        let mut mem = mem.lock();
+                        let _ = send_reply.send(());
+                        if let Some(target) = &target {
+                            render_line_from(&mut renderer, &mut mem, target, y, x, widescreen);
                         }
+                    },
+                    Fence => {
+                        let _ = send_reply.send(());
                     }
                 }
             }
@@ -153,30 +219,264 @@ impl RenderThread {
         RenderThread {
             sender:     send_msg,
             receiver:   recv_reply,
+            check:      RenderCheck::new(check_mem),
+        }
+    }
+
+    pub fn start_frame(&mut self, target: RenderTarget) {
+        self.check.start_frame(&target);
+        let _ = self.sender.send(RendererMessage::StartFrame(target));
+    }
+
+    // Wait for the render thread to finish everything sent so far, so the frame is complete.
+    // If verification is on, returns the hashes of the threaded and single-threaded frames when they differ.
+    pub fn end_frame(&mut self) -> Option<(u64, u64)> {
+        let _ = self.sender.send(RendererMessage::Fence);
+        let _ = self.receiver.recv();
+
+        self.check.end_frame()
+    }
+
+    pub fn set_verification(&mut self, enable: bool) {
+        self.check.set_enabled(enable);
+    }
+
+    pub fn set_sprite_limit(&mut self, enable: bool) {
+        self.check.renderer.set_sprite_limit(enable);
+        let _ = self.sender.send(RendererMessage::SpriteLimit(enable));
+    }
+
+    pub fn set_widescreen(&mut self, extra: usize) {
+        self.check.set_widescreen(extra);
+        let _ = self.sender.send(RendererMessage::Widescreen(extra));
+    }
+
+    #[cfg(feature = "debug")]
+    pub fn set_bg_priority_override(&mut self, bg: usize, priority: Option<bool>) {
+        self.check.renderer.set_bg_priority_override(bg, priority);
+        let _ = self.sender.send(RendererMessage::BGPriority(bg, priority));
+    }
+
+    pub fn draw_line(&mut self, y: usize) {
+        self.check.draw_line(y, None);
+        let _ = self.sender.send(RendererMessage::DrawLine(y));
+
+        let _ = self.receiver.recv();
+    }
+
+    pub fn blank_line(&mut self, y: usize) {
+        self.check.blank_line(y);
+        let _ = self.sender.send(RendererMessage::BlankLine(y));
+    }
+
+    pub fn blank_line_from(&mut self, y: usize, x: usize) {
+        self.check.blank_line_from(y, x);
+        let _ = self.sender.send(RendererMessage::BlankLineFrom(y, x));
+    }
+
+    pub fn draw_line_from(&mut self, y: usize, x: usize) {
+        self.check.draw_line(y, Some(x));
+        let _ = self.sender.send(RendererMessage::DrawLineFrom(y, x));
+
+        let _ = self.receiver.recv();
+    }
+}
+
+// Draws each line a second time on the calling thread, to check the render thread gives the same frame.
+// Lines are drawn from a copy of video memory taken before the render thread sees it,
+// as drawing resets the dirty flags that the caches are built from.
+#[cfg(feature = "std")]
+struct RenderCheck {
+    mem:        super::VRamRef,
+    renderer:   drawing::Renderer,
+    widescreen: usize,
+
+    enabled:    bool,
+    rebuild:    bool,   // Rebuild the caches on the next line, as they weren't kept up to date while disabled.
+    frame:      Option<RenderTarget>,   // The frame drawn by the render thread.
+    target:     RenderTarget,           // The frame drawn here.
+}
+
+#[cfg(feature = "std")]
+impl RenderCheck {
+    fn new(mem: super::VRamRef) -> Self {
+        RenderCheck {
+            mem:        mem,
+            renderer:   drawing::Renderer::new(),
+            widescreen: 0,
+
+            enabled:    false,
+            rebuild:    false,
+            frame:      None,
+            target:     Arc::new(Mutex::new(Box::new([]))),
+        }
+    }
+
+    fn set_enabled(&mut self, enable: bool) {
+        if enable && !self.enabled {
+            self.rebuild = true;
+        }
+        self.enabled = enable;
+        self.frame = None;
+    }
+
+    fn set_widescreen(&mut self, extra: usize) {
+        self.widescreen = extra;
+        self.renderer.set_widescreen(extra);
+    }
+
+    // Start from the same data as the render thread's frame, so lines that aren't drawn match.
+    fn start_frame(&mut self, frame: &RenderTarget) {
+        if self.enabled {
+            *self.target.lock() = frame.lock().clone();
+            self.frame = Some(frame.clone());
+        }
+    }
+
+    fn end_frame(&mut self) -> Option<(u64, u64)> {
+        let frame = self.frame.take()?;
+        let threaded = crate::dump::fnv1a(&frame.lock());
+        let single = crate::dump::fnv1a(&self.target.lock());
+        if threaded != single {
+            Some((threaded, single))
+        } else {
+            None
+        }
+    }
+
+    fn draw_line(&mut self, y: usize, from_x: Option<usize>) {
+        if self.frame.is_none() {
+            return;
+        }
+        let mut mem = self.mem.lock().clone();
+        if core::mem::take(&mut self.rebuild) {
+            mem.set_all_dirty();
+        }
+        match from_x {
+            Some(x) => render_line_from(&mut self.renderer, &mut mem, &self.target, y, x, self.widescreen),
+            None => render_line(&mut self.renderer, &mut mem, &self.target, y, self.widescreen),
+        }
+    }
+
+    fn blank_line(&mut self, y: usize) {
+        if self.frame.is_some() {
+            clear_line(&mut self.target.lock(), y, self.widescreen);
+        }
+    }
+
+    fn blank_line_from(&mut self, y: usize, x: usize) {
+        if self.frame.is_some() {
+            let mut t = self.target.lock();
+            clear_line_range(&mut t, y, x + self.widescreen, H_RES + (self.widescreen * 2), self.widescreen);
+        }
+    }
+}
+
+// Renderer for video that draws each line on the calling thread.
+#[cfg(not(feature = "std"))]
+pub struct RenderInline {
+    mem:        super::VRamRef,
+    target:     Option<RenderTarget>,
+    renderer:   drawing::Renderer,
+    widescreen: usize,
+}
+
+#[cfg(not(feature = "std"))]
+impl RenderInline {
+    pub fn new(mem: super::VRamRef) -> Self {
+        RenderInline {
+            mem:        mem,
+            target:     None,
+            renderer:   drawing::Renderer::new(),
+            widescreen: 0,
         }
     }
 
     pub fn start_frame(&mut self, target: RenderTarget) {
-        self.sender
-            .send(RendererMessage::StartFrame(target))
-            .expect("Couldn't send start frame message!");
+        self.target = Some(target);
     }
 
+    pub fn set_sprite_limit(&mut self, enable: bool) {
+        self.renderer.set_sprite_limit(enable);
+    }
+
+    pub fn set_widescreen(&mut self, extra: usize) {
+        self.widescreen = extra;
+        self.renderer.set_widescreen(extra);
+    }
+
+    // Lines are skipped if no frame has been started.
     pub fn draw_line(&mut self, y: usize) {
-        self.sender
-            .send(RendererMessage::DrawLine(y))
-            .expect("Couldn't send draw line message!");
+        if let Some(target) = &self.target {
+            let mut mem = self.mem.lock();
+            render_line(&mut self.renderer, &mut mem, target, y, self.widescreen);
+        }
+    }
 
-        self.receiver
-            .recv()
-            .expect("Draw line");
+    pub fn blank_line(&mut self, y: usize) {
+        if let Some(target) = &self.target {
+            clear_line(&mut target.lock(), y, self.widescreen);
+        }
+    }
+
+    pub fn blank_line_from(&mut self, y: usize, x: usize) {
+        if let Some(target) = &self.target {
+            let mut t = target.lock();
+            clear_line_range(&mut t, y, x + self.widescreen, H_RES + (self.widescreen * 2), self.widescreen);
+        }
+    }
+
+    pub fn draw_line_from(&mut self, y: usize, x: usize) {
+        if let Some(target) = &self.target {
+            let mut mem = self.mem.lock();
+            render_line_from(&mut self.renderer, &mut mem, target, y, x, self.widescreen);
+        }
     }
+
+    // Lines are drawn as they are sent, so the frame is always complete.
+    pub fn end_frame(&mut self) -> Option<(u64, u64)> {
+        None
+    }
+}
+
+// Draw a line into the target, or clear it if the screen is blanked.
+fn render_line(renderer: &mut drawing::Renderer, mem: &mut VideoMem, target: &RenderTarget, y: usize, widescreen: usize) {
+    if !mem.get_bg_registers().in_fblank() {
+        renderer.setup_caches(mem);
+        let mut t = target.lock();
+        renderer.draw_line(mem, &mut t, y);
+    } else {
+        let mut t = target.lock();
+        clear_line(&mut t, y, widescreen);
+    }
+}
+
+// Draw the line, but keep the part before x blank.
+fn render_line_from(renderer: &mut drawing::Renderer, mem: &mut VideoMem, target: &RenderTarget, y: usize, x: usize, widescreen: usize) {
+    renderer.setup_caches(mem);
+    let mut t = target.lock();
+    renderer.draw_line(mem, &mut t, y);
+    clear_line_range(&mut t, y, 0, x + widescreen, widescreen);
 }
 
-fn clear_line(target: &mut [u8], y: usize) {
-    use crate::constants::screen::H_RES;
+fn clear_line(target: &mut [u8], y: usize, widescreen: usize) {
+    clear_line_range(target, y, 0, H_RES + (widescreen * 2), widescreen);
+}
 
-    for d in target.iter_mut().skip(y * H_RES * 8).take(H_RES * 8) {
+// Clear the pixels from start to end. The range includes the widescreen margins.
+fn clear_line_range(target: &mut [u8], y: usize, start: usize, end: usize, widescreen: usize) {
+    let line_size = (H_RES + (widescreen * 2)) * 8;
+    for d in target.iter_mut().skip((y * line_size) + (start * 8)).take((end - start) * 8) {
         *d = 0;
     }
+}
+
+// Average each pair of horizontal pixels in a frame, writing into a frame of half the width.
+pub fn blend_hires(src: &[u8], dst: &mut [u8]) {
+    for (o, i) in dst.chunks_exact_mut(4).zip(src.chunks_exact(8)) {
+        let (sub, main) = i.split_at(4);
+        for (o_c, (s_c, m_c)) in o.iter_mut().zip(sub.iter().zip(main.iter())) {
+            *o_c = ((*s_c as u16 + *m_c as u16) / 2) as u8;
+        }
+    }
 }
\ No newline at end of file
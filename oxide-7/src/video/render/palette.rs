@@ -19,17 +19,47 @@ macro_rules! col15_to_col888 {
     };
 }
 
+const MAX_BRIGHTNESS: u8 = 0xF;
+
 pub struct PaletteMem {
-    colours: [Colour; 256]
+    colours: [Colour; 256],
+
+    brightness:     u8,
+    brightness_lut: [u8; 256],  // Colour channel values scaled by the brightness.
 }
 
 impl PaletteMem {
     pub fn new() -> Self {
-        PaletteMem {
+        let mut palette = PaletteMem {
             colours: [Colour::zero(); 256],
+
+            brightness:     0,
+            brightness_lut: [0; 256],
+        };
+        palette.set_brightness(MAX_BRIGHTNESS);
+        palette
+    }
+
+    // Rebuild the brightness lookup if the brightness in INIDISP has changed.
+    pub fn set_brightness(&mut self, brightness: u8) {
+        if brightness != self.brightness {
+            self.brightness = brightness;
+            for (i, out) in self.brightness_lut.iter_mut().enumerate() {
+                *out = ((i * (brightness as usize)) / (MAX_BRIGHTNESS as usize)) as u8;
+            }
         }
     }
 
+    // Apply the master brightness to a final colour.
+    #[inline]
+    pub fn apply_brightness(&self, colour: Colour) -> Colour {
+        Colour::new(
+            self.brightness_lut[colour.r as usize],
+            self.brightness_lut[colour.g as usize],
+            self.brightness_lut[colour.b as usize]
+        )
+    }
+
     pub fn make_bg_palette(&mut self, mem: &VideoMem) {
         for (d, p) in mem.get_cgram().chunks(2).take(128).zip(self.colours.iter_mut()) {
             *p = col15_to_col888!(make16!(d[1], d[0]));
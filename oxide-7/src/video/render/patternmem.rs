@@ -1,5 +1,7 @@
 // Pattern mem for a single background. Reads VRAM, outputs Texture data.
 
+use alloc::vec::Vec;
+
 pub const TILE_SIZE: usize = 8;
 const PATTERN_MEM_WIDTH_TILES: u16 = 16;
 const OBJ_PATTERN_MEM_HEIGHT_TILES: u16 = 16;
@@ -53,8 +55,8 @@ impl PatternMem {
         PatternMem {
             bits_per_pixel: bits_per_pixel,
 
-            start_addr:     std::u16::MAX,
-            end_addr:       std::u16::MAX,
+            start_addr:     core::u16::MAX,
+            end_addr:       core::u16::MAX,
 
             tiles:          Vec::new()
         }
@@ -62,14 +64,15 @@ impl PatternMem {
 
     // Set the address, plus height in TILES.
     pub fn set_addr(&mut self, start_addr: u16, num_tiles: u16) {
-        let size = num_tiles * match self.bits_per_pixel {
+        // 1024 8BPP tiles fill the whole of VRAM, so this won't fit in 16 bits.
+        let size = (num_tiles as u32) * match self.bits_per_pixel {
             BitsPerPixel::_2 => 16,
             BitsPerPixel::_4 => 32,
             BitsPerPixel::_8 => 64,
         } - 1;
 
         self.start_addr = start_addr;
-        self.end_addr = start_addr + size;
+        self.end_addr = ((start_addr as u32) + size) as u16;
 
         self.tiles.resize_with(num_tiles as usize, || Tile::new());
     }
@@ -0,0 +1,440 @@
+// PPU tests.
+use super::*;
+
+// Set up a PPU part way through V-Blank, before $4210 has been read.
+fn vblank_ppu() -> PPU {
+    let mut ppu = PPU::new(&MachineTiming::NTSC);
+    ppu.state = PPUState::VBlank;
+    ppu.scanline = screen::V_RES + 10;
+    ppu.nmi_flag = bit!(7);
+    ppu
+}
+
+// Set up a PPU part way through a visible line.
+fn drawing_ppu(cycle_count: usize) -> PPU {
+    let mut ppu = PPU::new(&MachineTiming::NTSC);
+    ppu.state = PPUState::DrawingAfterPause;
+    ppu.scanline = 100;
+    ppu.cycle_count = cycle_count;
+    ppu
+}
+
+#[test]
+fn vblank_nmi() {
+    let mut ppu = PPU::new(&MachineTiming::NTSC);
+    ppu.set_int_enable(0x80);
+    ppu.state = PPUState::HBlankLeft;
+    ppu.scanline = screen::V_RES + 1;
+
+    assert_eq!(ppu.clock(4), PPUSignal::Int(Interrupt::NMI | Interrupt::VBLANK));
+    assert_eq!(ppu.get_nmi_flag(), bit!(7));
+}
+
+#[test]
+fn vblank_no_nmi() {
+    let mut ppu = PPU::new(&MachineTiming::NTSC);
+    ppu.state = PPUState::HBlankLeft;
+    ppu.scanline = screen::V_RES + 1;
+
+    assert_eq!(ppu.clock(4), PPUSignal::Int(Interrupt::VBLANK));
+}
+
+// Enabling NMI during V-Blank fires it immediately, without starting a new frame.
+#[test]
+fn nmi_enabled_in_vblank() {
+    let mut ppu = vblank_ppu();
+
+    ppu.set_int_enable(0x80);
+
+    assert_eq!(ppu.clock(4), PPUSignal::Int(Interrupt::NMI));
+    assert_eq!(ppu.clock(4), PPUSignal::None);
+}
+
+// Once $4210 is read, enabling NMI has no effect until the next V-Blank.
+#[test]
+fn nmi_enabled_after_ack() {
+    let mut ppu = vblank_ppu();
+
+    ppu.get_nmi_flag();
+    ppu.set_int_enable(0x80);
+
+    assert_eq!(ppu.clock(4), PPUSignal::None);
+}
+
+// Writing $4200 when NMI is already enabled doesn't trigger another.
+#[test]
+fn nmi_already_enabled() {
+    let mut ppu = vblank_ppu();
+    ppu.int_enable = IntEnable::ENABLE_NMI;
+
+    ppu.set_int_enable(0x81);
+
+    assert_eq!(ppu.clock(4), PPUSignal::None);
+}
+
+// Toggling NMI off and on again during V-Blank triggers a second NMI.
+#[test]
+fn nmi_toggled_in_vblank() {
+    let mut ppu = vblank_ppu();
+    ppu.int_enable = IntEnable::ENABLE_NMI;
+
+    ppu.set_int_enable(0x00);
+    ppu.set_int_enable(0x80);
+
+    assert_eq!(ppu.clock(4), PPUSignal::Int(Interrupt::NMI));
+}
+
+// Disabling NMI before the late NMI is taken cancels it.
+#[test]
+fn nmi_enabled_then_disabled() {
+    let mut ppu = vblank_ppu();
+
+    ppu.set_int_enable(0x80);
+    ppu.set_int_enable(0x00);
+
+    assert_eq!(ppu.clock(4), PPUSignal::None);
+}
+
+// Enabling NMI outside of V-Blank waits for the next one.
+#[test]
+fn nmi_enabled_in_frame() {
+    let mut ppu = drawing_ppu(timing::PAUSE_START);
+
+    ppu.set_int_enable(0x80);
+
+    assert_eq!(ppu.clock(4), PPUSignal::None);
+}
+
+#[test]
+fn h_irq_enabled_before_timer() {
+    let mut ppu = drawing_ppu(timing::PAUSE_START);
+    ppu.set_h_timer_lo(200);
+
+    ppu.set_int_enable(0x10);
+
+    assert_eq!(ppu.clock(4), PPUSignal::None);
+    assert_eq!(ppu.clock(200 * timing::DOT_TIME - timing::PAUSE_START), PPUSignal::Int(Interrupt::IRQ));
+    assert_eq!(ppu.get_irq_flag(), bit!(7));
+}
+
+// Enabling H-IRQ after the timer has passed doesn't fire until the next line.
+#[test]
+fn h_irq_enabled_after_timer() {
+    let mut ppu = drawing_ppu(timing::PAUSE_START);
+    ppu.set_h_timer_lo(10);
+
+    ppu.set_int_enable(0x10);
+
+    assert_eq!(ppu.clock(4), PPUSignal::None);
+    assert_eq!(ppu.get_irq_flag(), 0);
+}
+
+// Disabling IRQs acknowledges a pending IRQ.
+#[test]
+fn irq_disabled() {
+    let mut ppu = drawing_ppu(timing::PAUSE_START);
+    ppu.set_h_timer_lo(10);
+    ppu.set_int_enable(0x10);
+    ppu.h_irq_latch = false;
+    assert_eq!(ppu.clock(4), PPUSignal::Int(Interrupt::IRQ));
+
+    ppu.set_int_enable(0x00);
+
+    assert_eq!(ppu.get_irq_flag(), 0);
+}
+
+// Changing which IRQs are enabled leaves a pending IRQ set.
+#[test]
+fn irq_changed() {
+    let mut ppu = drawing_ppu(timing::PAUSE_START);
+    ppu.set_h_timer_lo(10);
+    ppu.set_int_enable(0x10);
+    ppu.h_irq_latch = false;
+    assert_eq!(ppu.clock(4), PPUSignal::Int(Interrupt::IRQ));
+
+    ppu.set_int_enable(0x30);
+
+    assert_eq!(ppu.get_irq_flag(), bit!(7));
+}
+
+// Write a 16-bit value to a mode 7 register, low byte first.
+fn write_mode7(mem: &mut ram::VideoMem, addr: u8, data: u16) {
+    mem.write(addr, lo!(data));
+    mem.write(addr, hi!(data));
+}
+
+fn read_mult_result(mem: &mut ram::VideoMem) -> u32 {
+    make24!(mem.read(0x36, 0), mem.read(0x35, 0), mem.read(0x34, 0))
+}
+
+#[test]
+fn mult_result() {
+    let mut mem = ram::VideoMem::new();
+
+    write_mode7(&mut mem, 0x1B, 0x1234);
+    write_mode7(&mut mem, 0x1C, 0x5600);
+    assert_eq!(read_mult_result(&mut mem), 0x1234 * 0x56);
+
+    // Both operands are signed.
+    write_mode7(&mut mem, 0x1C, 0xFE00);
+    assert_eq!(read_mult_result(&mut mem), 0xFFDB98);
+    write_mode7(&mut mem, 0x1B, 0x8000);
+    assert_eq!(read_mult_result(&mut mem), 0x010000);
+}
+
+// The multiplier uses the last byte written to matrix B, even after only one write.
+#[test]
+fn mult_result_partial_write() {
+    let mut mem = ram::VideoMem::new();
+
+    write_mode7(&mut mem, 0x1B, 0x0100);
+    mem.write(0x1C, 0x7F);
+    assert_eq!(read_mult_result(&mut mem), 0x007F00);
+
+    // Writing matrix A again reuses the last B byte.
+    mem.write(0x1C, 0x02);
+    write_mode7(&mut mem, 0x1B, 0x0010);
+    assert_eq!(read_mult_result(&mut mem), 0x000020);
+}
+
+// In overscan mode, V-Blank starts after line 239. The extra lines aren't drawn.
+#[test]
+fn overscan_vblank() {
+    let mut ppu = PPU::new(&MachineTiming::NTSC);
+    ppu.write_mem(0x33, 0x04);
+    ppu.state = PPUState::HBlankLeft;
+    ppu.scanline = screen::V_RES + 1;
+    ppu.cycle_count = timing::SCANLINE_OFFSET;
+
+    assert_eq!(ppu.clock(4), PPUSignal::None);
+    assert_eq!(ppu.state, PPUState::DrawingBeforePause);
+
+    ppu.state = PPUState::HBlankLeft;
+    ppu.scanline = screen::V_RES_OVERSCAN + 1;
+    assert_eq!(ppu.clock(4), PPUSignal::Int(Interrupt::VBLANK));
+}
+
+// $213F bit 7 shows the field, which changes every frame.
+#[test]
+fn field_toggle() {
+    let mut ppu = vblank_ppu();
+    let field = ppu.read_mem(0x3F, 0) & bit!(7);
+
+    ppu.scanline = 0;
+    assert_eq!(ppu.clock(4), PPUSignal::FrameStart);
+    assert_eq!(ppu.read_mem(0x3F, 0) & bit!(7), field ^ bit!(7));
+}
+
+// The low bits of $213E and $213F are the chip versions.
+#[test]
+fn ppu_versions() {
+    let mut ppu = vblank_ppu();
+    assert_eq!(ppu.read_mem(0x3E, 0) & 0x0F, 1);
+    assert_eq!(ppu.read_mem(0x3F, 0) & 0x0F, 3);
+}
+
+// $213F bit 6 is set when the H/V counters are latched, and cleared by reading $213F.
+#[test]
+fn counter_latch_flag() {
+    let mut ppu = vblank_ppu();
+    assert_eq!(ppu.read_mem(0x3F, 0) & bit!(6), 0);
+
+    ppu.latch_hv();
+    assert_eq!(ppu.read_mem(0x3F, 0) & bit!(6), bit!(6));
+    assert_eq!(ppu.read_mem(0x3F, 0) & bit!(6), 0);
+}
+
+// Reading $213F resets the H/V counter reads to the low byte.
+#[test]
+fn status_resets_counter_reads() {
+    let mut ppu = vblank_ppu();
+    ppu.mem.lock().set_latched_hv(0x1AB, 0x0CD);
+    assert_eq!(ppu.read_mem(0x3C, 0), 0xAB);
+    ppu.read_mem(0x3F, 0);
+    assert_eq!(ppu.read_mem(0x3C, 0), 0xAB);
+    assert_eq!(ppu.read_mem(0x3C, 0) & 1, 1);
+}
+
+fn line_pixels(target: &RenderTarget, y: usize) -> Vec<u8> {
+    let line_size = screen::H_RES * 8;
+    target.lock()[(y * line_size)..((y + 1) * line_size)].to_vec()
+}
+
+// Turning on forced blank part way through a line clears the rest of it.
+#[test]
+fn fblank_mid_line() {
+    let target: RenderTarget = Arc::new(Mutex::new(vec![0xFF; screen::H_RES * 8 * screen::V_RES].into_boxed_slice()));
+    let mut ppu = drawing_ppu(timing::SCANLINE_OFFSET + (100 * timing::DOT_TIME));
+    ppu.start_frame(target.clone());
+
+    ppu.write_mem(0x00, 0x80);
+    // Wait for the renderer to catch up.
+    ppu.renderer.draw_line(0);
+
+    let line = line_pixels(&target, 99);
+    assert!(line[..(100 * 8)].iter().all(|b| *b == 0xFF));
+    assert!(line[(100 * 8)..].iter().all(|b| *b == 0));
+}
+
+// VRAM can only be written in V-Blank or forced blank. The address increments either way.
+#[test]
+fn vram_write_active_display() {
+    let mut ppu = vblank_ppu();
+    ppu.write_mem(0x15, 0x80);
+    ppu.write_mem(0x18, 0x12);
+    ppu.write_mem(0x19, 0x34);
+
+    ppu.toggle_vblank(false);
+    ppu.write_mem(0x18, 0x56);
+    ppu.write_mem(0x19, 0x78);
+
+    ppu.write_mem(0x00, 0x80);
+    ppu.write_mem(0x18, 0x9A);
+    ppu.write_mem(0x19, 0xBC);
+
+    assert_eq!(&ppu.copy_vram()[0..6], &[0x12, 0x34, 0x00, 0x00, 0x9A, 0xBC]);
+}
+
+// With blanking accuracy, the data ports return what the PPU is accessing during active display.
+#[test]
+fn data_port_reads_active_display() {
+    let mut ppu = vblank_ppu();
+    for (addr, data) in [(0x15, 0x80), (0x18, 0x12), (0x19, 0x34), (0x22, 0x11), (0x22, 0x22), (0x22, 0x33), (0x22, 0x44)] {
+        ppu.write_mem(addr, data);
+    }
+    for data in [0, 0x10, 0, 0, 0, 0x20, 0, 0] {
+        ppu.write_mem(0x04, data);
+    }
+
+    ppu.blank_accuracy = true;
+    ppu.toggle_vblank(false);
+    ppu.cycle_count = timing::DOT_TIME * 2;
+
+    // VRAM reads return 0, and the address still increments.
+    ppu.write_mem(0x16, 0x00);
+    ppu.write_mem(0x17, 0x00);
+    assert_eq!(ppu.read_mem(0x39, 0), 0);
+    assert_eq!(ppu.read_mem(0x3A, 0), 0);
+    ppu.write_mem(0x16, 0x00);
+    assert_eq!(ppu.read_mem(0x3A, 0), 0);
+
+    // OAM reads return the Y coordinate of the object being checked: object 1.
+    ppu.write_mem(0x02, 0x00);
+    ppu.write_mem(0x03, 0x00);
+    assert_eq!(ppu.read_mem(0x38, 0), 0x20);
+
+    // CGRAM reads while pixels are output return the backdrop colour.
+    ppu.write_mem(0x21, 0x01);
+    assert_eq!(ppu.read_mem(0x3B, 0), 0x33);
+    ppu.cycle_count = timing::SCANLINE_OFFSET;
+    assert_eq!(ppu.read_mem(0x3B, 0), 0x22);
+
+    // Reads are normal in forced blank.
+    ppu.write_mem(0x00, 0x80);
+    ppu.write_mem(0x16, 0x00);
+    assert_eq!(ppu.read_mem(0x39, 0), 0x12);
+    assert_eq!(ppu.read_mem(0x3A, 0), 0x34);
+}
+
+#[test]
+fn power_on_garbage() {
+    let mut ppu = PPU::new(&MachineTiming::NTSC);
+    ppu.set_power_on_garbage(true, &mut Rng::new(1));
+    assert!(!ppu.mem.lock().get_bg_registers().in_fblank());
+    assert!(ppu.copy_vram().iter().any(|b| *b != 0));
+    assert!(ppu.copy_cgram().iter().any(|b| *b != 0));
+
+    // The same seed always gives the same garbage.
+    let mut other = PPU::new(&MachineTiming::NTSC);
+    other.set_power_on_garbage(true, &mut Rng::new(1));
+    assert_eq!(ppu.copy_vram(), other.copy_vram());
+    assert_eq!(ppu.copy_cgram(), other.copy_cgram());
+    assert_eq!(ppu.copy_oam(), other.copy_oam());
+}
+
+// Sprites aren't evaluated in forced blank, so the line after it is released has none.
+#[test]
+fn no_sprites_after_fblank() {
+    let mut ppu = PPU::new(&MachineTiming::NTSC);
+    ppu.blank_accuracy = true;
+    ppu.skip_render = true;
+    ppu.write_mem(0x00, 0x80);
+    ppu.state = PPUState::HBlankLeft;
+    ppu.scanline = 100;
+    ppu.cycle_count = timing::SCANLINE_OFFSET;
+
+    ppu.transition_state(PPUTransition::ExitHBlank);
+    ppu.write_mem(0x00, 0x0F);
+    ppu.state = PPUState::HBlankLeft;
+    ppu.transition_state(PPUTransition::ExitHBlank);
+    assert!(ppu.mem.lock().hide_objects());
+
+    ppu.state = PPUState::HBlankLeft;
+    ppu.transition_state(PPUTransition::ExitHBlank);
+    assert!(!ppu.mem.lock().hide_objects());
+}
+
+// Objects past the 32nd on a line are listed as dropped.
+#[cfg(feature = "debug")]
+#[test]
+fn sprite_line_states() {
+    let mut ppu = PPU::new(&MachineTiming::NTSC);
+    ppu.skip_render = true;
+    ppu.write_mem(0x00, 0x80);
+    ppu.write_mem(0x02, 0x00);
+    ppu.write_mem(0x03, 0x00);
+    for i in 0..128 {
+        let y = if i < 34 {50} else {0xF0};
+        for data in [0, y, 0, 0] {
+            ppu.write_mem(0x04, data);
+        }
+    }
+    ppu.write_mem(0x00, 0x0F);
+
+    ppu.state = PPUState::VBlank;
+    ppu.transition_state(PPUTransition::ExitVBlank);
+    for line in 1..=screen::V_RES {
+        ppu.scanline = line;
+        ppu.transition_state(PPUTransition::ExitHBlank);
+    }
+    ppu.transition_state(PPUTransition::ExitVBlank);
+
+    let lines = ppu.sprite_line_states();
+    assert_eq!(lines.len(), screen::V_RES);
+    assert!(lines[48].selected.is_empty());
+    assert_eq!(lines[49].selected, (0..32).collect::<Vec<u8>>());
+    assert_eq!(lines[49].dropped, vec![32, 33]);
+    assert_eq!(lines[56], lines[49]);
+    assert_eq!(lines[57], crate::debug::SpriteLineState::default());
+}
+
+// Unused bits of the PPU2 registers return the last value read from PPU2.
+#[test]
+fn ppu2_open_bus() {
+    let mut ppu = vblank_ppu();
+    for (addr, data) in [(0x21, 0x00), (0x22, 0xFF), (0x22, 0x7F), (0x22, 0x00), (0x22, 0x7F), (0x21, 0x00)] {
+        ppu.write_mem(addr, data);
+    }
+
+    assert_eq!(ppu.read_mem(0x3B, 0), 0xFF);
+    assert_eq!(ppu.read_mem(0x3B, 0), 0xFF);
+    assert_eq!(ppu.read_mem(0x3B, 0), 0x00);
+    assert_eq!(ppu.read_mem(0x3B, 0), 0x7F);
+    assert_eq!(ppu.read_mem(0x3F, 0) & 0x7F, 0x23);
+}
+
+// Write-only registers on PPU1 return the last value read from PPU1. Others return CPU open bus.
+#[test]
+fn ppu1_open_bus() {
+    let mut ppu = vblank_ppu();
+    ppu.write_mem(0x1B, 0x08);
+    ppu.write_mem(0x1B, 0x00);
+    ppu.write_mem(0x1C, 0x02);
+
+    assert_eq!(ppu.read_mem(0x34, 0x21), 0x10);
+    assert_eq!(ppu.read_mem(0x05, 0x21), 0x10);
+    assert_eq!(ppu.read_mem(0x2A, 0x21), 0x10);
+    assert_eq!(ppu.read_mem(0x3E, 0x21), 0x11);
+    assert_eq!(ppu.read_mem(0x00, 0x21), 0x21);
+    assert_eq!(ppu.read_mem(0x33, 0x21), 0x21);
+}
@@ -0,0 +1,67 @@
+// Fast reads of a fixed set of work RAM addresses, for scripts and training agents.
+// The addresses are converted to WRAM offsets once, so each update is a plain copy out of WRAM.
+
+use alloc::{
+    string::String,
+    vec::Vec
+};
+
+/// A list of work RAM addresses, read together with SNES::update_watch.
+/// Values are packed into a buffer in the same order as the addresses.
+pub struct RamWatch {
+    offsets:    Vec<usize>, // Offsets into WRAM for each address.
+    values:     Vec<u8>,
+    changed:    Vec<usize>, // Indices of values that changed in the last update.
+    first:      bool,       // Nothing has been read yet.
+}
+
+impl RamWatch {
+    /// Create a watch for the addresses. Addresses can be in banks $7E-$7F,
+    /// or in the first 8kB of banks $00-$3F and $80-$BF, which mirror $7E0000-$7E1FFF.
+    /// Returns an error for any address that isn't work RAM.
+    pub fn new(addrs: &[u32]) -> Result<Self, String> {
+        let offsets = addrs.iter()
+            .map(|addr| wram_offset(*addr).ok_or_else(|| format!("${:06X} is not in work RAM", addr)))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(RamWatch {
+            values:     vec![0; offsets.len()],
+            changed:    Vec::with_capacity(offsets.len()),
+            offsets:    offsets,
+            first:      true,
+        })
+    }
+
+    /// Values of each address, from the last update.
+    pub fn values(&self) -> &[u8] {
+        &self.values
+    }
+
+    /// Indices of the addresses whose value changed in the last update.
+    /// On the first update, every address is included.
+    pub fn changed(&self) -> &[usize] {
+        &self.changed
+    }
+
+    pub(crate) fn update(&mut self, wram: &[u8]) {
+        self.changed.clear();
+        for (i, (value, offset)) in self.values.iter_mut().zip(self.offsets.iter()).enumerate() {
+            let new = wram[*offset];
+            if self.first || (new != *value) {
+                self.changed.push(i);
+            }
+            *value = new;
+        }
+        self.first = false;
+    }
+}
+
+fn wram_offset(addr: u32) -> Option<usize> {
+    let bank = addr >> 16;
+    let offset = addr & 0xFFFF;
+    match bank {
+        0x7E | 0x7F => Some((addr - 0x7E0000) as usize),
+        0x00..=0x3F | 0x80..=0xBF if offset < 0x2000 => Some(offset as usize),
+        _ => None,
+    }
+}
@@ -0,0 +1,24 @@
+// Check the debug access to audio RAM at the ends of ARAM.
+#![cfg(feature = "debug")]
+
+mod common;
+
+use oxide7::SNES;
+
+#[test]
+fn aram_bounds() {
+    let rom = common::lorom(&[
+        0x80, 0xFE, // $8000: BRA $8000
+    ]);
+    let mut snes = SNES::from_rom_data(&rom, "", None).unwrap();
+
+    // Writes wrap around to the start.
+    snes.write_aram(0xFFFE, &[1, 2, 3, 4]);
+    assert_eq!(snes.read_aram(0xFFFE..0x1_0000), &[1, 2]);
+    assert_eq!(snes.read_aram(0x0000..0x0002), &[3, 4]);
+
+    // Reads are cut off at the end.
+    assert_eq!(snes.read_aram(0xFFFE..0x1_0010), &[1, 2]);
+    assert!(snes.read_aram(0x2_0000..0x2_0010).is_empty());
+    assert!(snes.dump_aram(0xFFF0..0x1_0010).starts_with("$FFF0:"));
+}
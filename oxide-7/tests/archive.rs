@@ -0,0 +1,75 @@
+// Check that ROMs are loaded from inside zip archives.
+#![cfg(feature = "archive")]
+mod common;
+
+use std::io::{
+    Cursor,
+    Write
+};
+
+use zip::{
+    ZipWriter,
+    write::FileOptions
+};
+
+use oxide7::{
+    ROMFormat,
+    SNES
+};
+
+// Make a zip archive in memory, from a list of file names and contents.
+fn zip(files: &[(&str, &[u8])]) -> Vec<u8> {
+    let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+    for (name, data) in files {
+        writer.start_file(*name, FileOptions::default()).unwrap();
+        writer.write_all(data).unwrap();
+    }
+    writer.finish().unwrap().into_inner()
+}
+
+// A LoROM that writes $5A to $0000.
+fn rom() -> Vec<u8> {
+    common::lorom(&[
+        0xA9, 0x5A,                 // $8000: LDA #$5A
+        0x8D, 0x00, 0x00,           // $8002: STA $0000
+        0x80, 0xFE,                 // $8005: BRA $8005
+    ])
+}
+
+#[test]
+fn rom_in_zip() {
+    let rom = rom();
+    let archive = zip(&[
+        ("readme.txt", b"Not a ROM."),
+        ("game.sfc", &rom),
+    ]);
+
+    let mut snes = SNES::from_rom_data(&archive, "", None).unwrap();
+    let info = snes.cart_info();
+    assert!(info.name.starts_with("TEST"));
+    assert_eq!(info.format, ROMFormat::Plain);
+    assert_eq!(info.rom_size, rom.len());
+
+    let mut frame = vec![0; snes.frame_buffer_size()];
+    snes.frame(&mut frame);
+    assert_eq!(snes.wram()[0], 0x5A);
+}
+
+#[test]
+fn copier_header_in_zip() {
+    let mut smc = vec![0; 512];
+    smc[8..11].copy_from_slice(&[0xAA, 0xBB, 0x04]);
+    smc.extend_from_slice(&rom());
+    let archive = zip(&[("game.smc", &smc)]);
+
+    let snes = SNES::from_rom_data(&archive, "", None).unwrap();
+    let info = snes.cart_info();
+    assert_eq!(info.format, ROMFormat::SMC);
+    assert_eq!(info.rom_size, smc.len() - 512);
+}
+
+#[test]
+fn no_rom_in_zip() {
+    let archive = zip(&[("readme.txt", b"Not a ROM.")]);
+    assert!(SNES::from_rom_data(&archive, "", None).is_err());
+}
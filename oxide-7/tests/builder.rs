@@ -0,0 +1,153 @@
+// Check that the SNESBuilder options are applied to the SNES it creates.
+mod common;
+
+use std::{
+    env,
+    fs
+};
+
+use oxide7::{
+    Region,
+    SNES,
+    SNESBuilder
+};
+
+// A 32kB LoROM that counts up at $0000 in SRAM forever, optionally with 8kB SRAM.
+fn counter_rom(sram: bool) -> Vec<u8> {
+    let code = [
+        0x1A,                       // $8000: INC A
+        0x8F, 0x00, 0x00, 0x70,     // $8001: STA $700000
+        0x80, 0xF9,                 // $8005: BRA $8000
+    ];
+    if sram {
+        common::Cart::lorom()
+            .rom_type(0x02)     // ROM + RAM + battery
+            .sram_size(0x03)    // 8kB SRAM
+            .build(&code)
+    } else {
+        common::lorom(&code)
+    }
+}
+
+fn run_frames(snes: &mut SNES, frames: usize) {
+    let mut frame = vec![0; snes.frame_buffer_size()];
+    for _ in 0..frames {
+        snes.frame(&mut frame);
+    }
+}
+
+// Master cycles taken by the second frame.
+fn frame_cycles(region: Region) -> u64 {
+    let rom = counter_rom(false);
+    let mut snes = SNESBuilder::from_rom_data(&rom).region(region).build().unwrap();
+    run_frames(&mut snes, 1);
+    let start = snes.frame_timestamp();
+    run_frames(&mut snes, 1);
+    snes.frame_timestamp() - start
+}
+
+#[test]
+fn defaults() {
+    let rom = counter_rom(false);
+    let mut snes = SNESBuilder::from_rom_data(&rom).build().unwrap();
+    assert!(snes.rom_name().starts_with("TEST"));
+    assert_eq!(snes.frame_width(), 512);
+    run_frames(&mut snes, 2);
+}
+
+#[test]
+fn frame_options() {
+    let rom = counter_rom(false);
+    let snes = SNESBuilder::from_rom_data(&rom)
+        .hires_blending(true)
+        .build()
+        .unwrap();
+    assert_eq!(snes.frame_width(), 256);
+
+    let mut snes = SNESBuilder::from_rom_data(&rom)
+        .widescreen(48)
+        .build()
+        .unwrap();
+    assert_eq!(snes.frame_width(), 704);
+    assert_eq!(snes.frame_buffer_size(), 704 * 224 * 4);
+    run_frames(&mut snes, 2);
+}
+
+// A 32kB LoROM that shows a white backdrop, with $2133 (SETINI) set to setini.
+fn backdrop_rom(setini: u8) -> Vec<u8> {
+    common::lorom(&[
+        0xA9, 0xFF,                 // $8000: LDA #$FF
+        0x9C, 0x21, 0x21,           // $8002: STZ $2121
+        0x8D, 0x22, 0x21,           // $8005: STA $2122
+        0xA9, 0x7F,                 // $8008: LDA #$7F
+        0x8D, 0x22, 0x21,           // $800A: STA $2122
+        0xA9, 0x0F,                 // $800D: LDA #$0F
+        0x8D, 0x00, 0x21,           // $800F: STA $2100
+        0xA9, setini,               // $8012: LDA #setini
+        0x8D, 0x33, 0x21,           // $8014: STA $2133
+        0x80, 0xFE,                 // $8017: BRA $8017
+    ])
+}
+
+// Returns true if the first pixel of line y isn't black.
+fn line_drawn(frame: &[u8], y: usize) -> bool {
+    let offset = y * 512 * 4;
+    frame[offset..(offset + 3)].iter().any(|c| *c != 0)
+}
+
+#[test]
+fn overscan() {
+    for &(setini, overscan_lines) in [(0x00, false), (0x04, true)].iter() {
+        let rom = backdrop_rom(setini);
+        let mut snes = SNESBuilder::from_rom_data(&rom).build().unwrap();
+        assert_eq!(snes.frame_buffer_size(), 512 * 224 * 4);
+        run_frames(&mut snes, 2);
+
+        let mut snes = SNESBuilder::from_rom_data(&rom).overscan(true).build().unwrap();
+        assert_eq!(snes.frame_height(), 239);
+        assert_eq!(snes.frame_buffer_size(), 512 * 239 * 4);
+        let mut frame = vec![0; snes.frame_buffer_size()];
+        snes.frame(&mut frame);
+        snes.frame(&mut frame);
+        assert!(line_drawn(&frame, 100));
+        assert_eq!(line_drawn(&frame, 230), overscan_lines);
+        assert_eq!(line_drawn(&frame, 238), overscan_lines);
+    }
+}
+
+#[test]
+fn pal_frames_are_longer() {
+    let ntsc = frame_cycles(Region::NTSC) as f64;
+    let pal = frame_cycles(Region::PAL) as f64;
+    assert!(((pal / ntsc) - (312.0 / 262.0)).abs() < 0.01, "NTSC: {} PAL: {}", ntsc, pal);
+}
+
+#[test]
+fn region_from_header() {
+    let mut rom = counter_rom(false);
+    assert_eq!(SNESBuilder::from_rom_data(&rom).build().unwrap().region(), Region::NTSC);
+
+    rom[0x7FD9] = 0x02; // Europe
+    let snes = SNESBuilder::from_rom_data(&rom).build().unwrap();
+    assert_eq!(snes.cart_info().region, Region::PAL);
+    assert_eq!(snes.region(), Region::PAL);
+
+    let snes = SNESBuilder::from_rom_data(&rom).region(Region::NTSC).build().unwrap();
+    assert_eq!(snes.region(), Region::NTSC);
+}
+
+#[test]
+fn default_save_path() {
+    let dir = env::temp_dir().join("oxide7-builder");
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let cart_path = dir.join("game.v1.sfc");
+    fs::write(&cart_path, counter_rom(true)).unwrap();
+
+    let mut snes = SNESBuilder::from_file(cart_path.to_str().unwrap()).build().unwrap();
+    run_frames(&mut snes, 2);
+    drop(snes);
+    assert_eq!(fs::read(dir.join("game.v1.sav")).unwrap().len(), 0x2000);
+
+    let _ = fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,32 @@
+// Check that a ROM whose checksum doesn't match its header is reported.
+mod common;
+
+use oxide7::{
+    Event,
+    SNES
+};
+
+#[test]
+fn bad_checksum_event() {
+    // The generated ROM has a checksum of zero in its header.
+    let rom = common::lorom(&[
+        0x80, 0xFE, // $8000: BRA $8000
+    ]);
+    let mut snes = SNES::from_rom_data(&rom, "", None).unwrap();
+    let info = snes.cart_info();
+    assert_eq!(info.header_checksum, 0);
+    assert_ne!(info.checksum, 0);
+
+    let events = snes.subscribe_events();
+    let mut frame = vec![0; snes.frame_buffer_size()];
+    snes.frame(&mut frame);
+    let bad_checksum = Event::BadChecksum {
+        header: 0,
+        actual: info.checksum,
+    };
+    assert!(events.try_iter().any(|e| e == bad_checksum));
+
+    // It is only sent once.
+    snes.frame(&mut frame);
+    assert!(events.try_iter().all(|e| e != bad_checksum));
+}
@@ -0,0 +1,93 @@
+// Tiny generated ROMs for the integration tests.
+// Not every test uses every helper.
+#![allow(dead_code)]
+
+// A 32kB LoROM that runs code from $8000.
+pub fn lorom(code: &[u8]) -> Vec<u8> {
+    Cart::lorom().build(code)
+}
+
+// A 64kB HiROM that runs code from $8000.
+pub fn hirom(code: &[u8]) -> Vec<u8> {
+    Cart::hirom().build(code)
+}
+
+// The header of a generated ROM. The reset vector always points to $8000.
+pub struct Cart {
+    name:       Vec<u8>,
+    header:     usize,
+    size:       usize,
+    map_mode:   u8,
+    rom_type:   u8,
+    sram_size:  u8,
+}
+
+impl Cart {
+    pub fn lorom() -> Self {
+        Cart {
+            name:       b"TEST".to_vec(),
+            header:     0x7FC0,
+            size:       0x8000,
+            map_mode:   0x20,
+            rom_type:   0x00,
+            sram_size:  0x00,
+        }
+    }
+
+    pub fn hirom() -> Self {
+        Cart {
+            name:       b"TEST".to_vec(),
+            header:     0xFFC0,
+            size:       0x1_0000,
+            map_mode:   0x21,
+            rom_type:   0x00,
+            sram_size:  0x00,
+        }
+    }
+
+    // The name in the header, up to 21 bytes. It doesn't need to be valid UTF-8.
+    pub fn name(mut self, name: &[u8]) -> Self {
+        self.name = name.to_vec();
+        self
+    }
+
+    // Size in bytes. This must be a power of two, of at least 32kB.
+    pub fn size(mut self, size: usize) -> Self {
+        self.size = size;
+        self
+    }
+
+    pub fn map_mode(mut self, map_mode: u8) -> Self {
+        self.map_mode = map_mode;
+        self
+    }
+
+    // The chips on the cartridge, for example 0x02 for ROM + RAM + battery.
+    pub fn rom_type(mut self, rom_type: u8) -> Self {
+        self.rom_type = rom_type;
+        self
+    }
+
+    // 1kB << sram_size of SRAM.
+    pub fn sram_size(mut self, sram_size: u8) -> Self {
+        self.sram_size = sram_size;
+        self
+    }
+
+    // Make the ROM, with code at the start of bank $00.
+    pub fn build(&self, code: &[u8]) -> Vec<u8> {
+        let mut rom = vec![0; self.size];
+        let start = self.header & 0x8000;
+        rom[start..(start + code.len())].copy_from_slice(code);
+
+        let header = &mut rom[self.header..(self.header + 0x40)];
+        header[..self.name.len()].copy_from_slice(&self.name);
+        header[0x15] = self.map_mode;
+        header[0x16] = self.rom_type;
+        header[0x17] = (self.size / 1024).trailing_zeros() as u8;
+        header[0x18] = self.sram_size;
+        header[0x3C] = 0x00;    // Reset vector: $8000
+        header[0x3D] = 0x80;
+        rom
+    }
+}
@@ -0,0 +1,48 @@
+// Check that each component's state can be exported alone, to compare two systems.
+mod common;
+
+use oxide7::{
+    SNES,
+    StateComponent
+};
+
+// A 32kB LoROM that loops forever.
+fn loop_rom() -> Vec<u8> {
+    common::lorom(&[
+        0x80, 0xFE, // $8000: BRA $8000
+    ])
+}
+
+fn run_frame(snes: &mut SNES) {
+    let mut frame = vec![0; snes.frame_buffer_size()];
+    snes.frame(&mut frame);
+}
+
+// Components with different state.
+fn diverged(a: &SNES, b: &SNES) -> Vec<StateComponent> {
+    StateComponent::iter()
+        .filter(|c| a.export_component_state(*c) != b.export_component_state(*c))
+        .collect()
+}
+
+#[test]
+fn components_not_empty() {
+    let snes = SNES::from_rom_data(&loop_rom(), "", None).unwrap();
+    for component in StateComponent::iter() {
+        assert!(!snes.export_component_state(component).is_empty(), "{} is empty", component.name());
+    }
+}
+
+#[test]
+fn find_diverged_component() {
+    let mut a = SNES::from_rom_data(&loop_rom(), "", None).unwrap();
+    let mut b = SNES::from_rom_data(&loop_rom(), "", None).unwrap();
+    a.randomize_wram(1);
+    b.randomize_wram(1);
+    run_frame(&mut a);
+    run_frame(&mut b);
+    assert!(diverged(&a, &b).is_empty());
+
+    b.randomize_wram(2);
+    assert_eq!(diverged(&a, &b), vec![StateComponent::WRAM]);
+}
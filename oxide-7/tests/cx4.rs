@@ -0,0 +1,46 @@
+// Check that CX4 carts are detected, and the chip can be used by the CPU.
+mod common;
+
+use oxide7::{
+    EnhancementChip,
+    SNES
+};
+
+// A 32kB CX4 cart that multiplies $001234 by $000100, copies the middle bytes
+// of the result to $0000 in RAM, then loops forever.
+fn cx4_rom() -> Vec<u8> {
+    let mut code = Vec::new();
+    let mut write = |addr: u16, data: u8| {
+        code.extend_from_slice(&[0xA9, data, 0x8D, addr as u8, (addr >> 8) as u8]); // LDA #data; STA addr
+    };
+    write(0x7F80, 0x34);
+    write(0x7F81, 0x12);
+    write(0x7F82, 0x00);
+    write(0x7F83, 0x00);
+    write(0x7F84, 0x01);
+    write(0x7F85, 0x00);
+    write(0x7F4F, 0x25);    // Multiply
+    code.extend_from_slice(&[
+        0xAD, 0x81, 0x7F,   // LDA $7F81
+        0x8D, 0x00, 0x00,   // STA $0000
+        0xAD, 0x82, 0x7F,   // LDA $7F82
+        0x8D, 0x01, 0x00,   // STA $0001
+        0x80, 0xFE,         // BRA to self
+    ]);
+    let mut rom = common::Cart::lorom()
+        .rom_type(0xF3)     // ROM + custom chip
+        .build(&code);
+    rom[0x7FBF] = 0x10; // CX4 subtype
+    rom[0x7FDA] = 0x33; // Extended header
+    rom
+}
+
+#[test]
+fn multiply() {
+    let mut snes = SNES::from_rom_data(&cx4_rom(), "", None).unwrap();
+    assert_eq!(snes.cart_info().chip, Some(EnhancementChip::CX4));
+
+    let mut frame = vec![0; snes.frame_buffer_size()];
+    snes.frame(&mut frame);
+    assert_eq!(&snes.wram()[0..2], &[0x34, 0x12]);
+}
@@ -0,0 +1,151 @@
+// Check that emulation is deterministic: running the same inputs gives the same state,
+// including when the state is saved and loaded part way through.
+// Replays, rewind and netplay all rely on this.
+
+mod common;
+
+use oxide7::{
+    SNES,
+    Button
+};
+
+const FRAMES: usize = 40;
+const SAVE_FRAME: usize = 15;
+const SEED: u64 = 0x5EED;
+
+// A 32kB LoROM that sets up a mode 1 background, fills VRAM by DMA and CGRAM from ROM,
+// then writes the APU ports and uses the multiplier in its main loop.
+// The NMI handler scrolls the background using a frame counter and the joypad.
+fn test_rom() -> Vec<u8> {
+    let code = [
+        0x78,               // $8000: SEI
+        0x18,               // $8001: CLC
+        0xFB,               // $8002: XCE
+        0xA9, 0x8F,         // $8003: LDA #$8F
+        0x8D, 0x00, 0x21,   // $8005: STA $2100
+        0xA9, 0x01,         // $8008: LDA #$01
+        0x8D, 0x05, 0x21,   // $800A: STA $2105
+        0x8D, 0x2C, 0x21,   // $800D: STA $212C
+        0xA9, 0x80,         // $8010: LDA #$80
+        0x8D, 0x15, 0x21,   // $8012: STA $2115
+        0x9C, 0x16, 0x21,   // $8015: STZ $2116
+        0x9C, 0x17, 0x21,   // $8018: STZ $2117
+        0xA9, 0x01,         // $801B: LDA #$01
+        0x8D, 0x00, 0x43,   // $801D: STA $4300
+        0xA9, 0x18,         // $8020: LDA #$18
+        0x8D, 0x01, 0x43,   // $8022: STA $4301
+        0x9C, 0x02, 0x43,   // $8025: STZ $4302
+        0xA9, 0x80,         // $8028: LDA #$80
+        0x8D, 0x03, 0x43,   // $802A: STA $4303
+        0x9C, 0x04, 0x43,   // $802D: STZ $4304
+        0x9C, 0x05, 0x43,   // $8030: STZ $4305
+        0xA9, 0x10,         // $8033: LDA #$10
+        0x8D, 0x06, 0x43,   // $8035: STA $4306
+        0xA9, 0x01,         // $8038: LDA #$01
+        0x8D, 0x0B, 0x42,   // $803A: STA $420B
+        0x9C, 0x21, 0x21,   // $803D: STZ $2121
+        0xA2, 0x00,         // $8040: LDX #$00
+        0xBD, 0x00, 0x80,   // $8042: LDA $8000,X
+        0x8D, 0x22, 0x21,   // $8045: STA $2122
+        0xE8,               // $8048: INX
+        0xD0, 0xF7,         // $8049: BNE $8042
+        0xA9, 0x0F,         // $804B: LDA #$0F
+        0x8D, 0x00, 0x21,   // $804D: STA $2100
+        0xA9, 0x81,         // $8050: LDA #$81
+        0x8D, 0x00, 0x42,   // $8052: STA $4200
+        0x58,               // $8055: CLI
+        0xAD, 0x40, 0x21,   // $8056: LDA $2140
+        0x8D, 0x41, 0x21,   // $8059: STA $2141
+        0xE6, 0x20,         // $805C: INC $20
+        0xA5, 0x20,         // $805E: LDA $20
+        0x8D, 0x02, 0x42,   // $8060: STA $4202
+        0x8D, 0x03, 0x42,   // $8063: STA $4203
+        0xAD, 0x16, 0x42,   // $8066: LDA $4216
+        0x85, 0x21,         // $8069: STA $21
+        0x80, 0xE9,         // $806B: BRA $8056
+        0x48,               // $806D: PHA
+        0xE6, 0x10,         // $806E: INC $10
+        0xA5, 0x10,         // $8070: LDA $10
+        0x8D, 0x0D, 0x21,   // $8072: STA $210D
+        0x9C, 0x0D, 0x21,   // $8075: STZ $210D
+        0xAD, 0x18, 0x42,   // $8078: LDA $4218
+        0x85, 0x11,         // $807B: STA $11
+        0x45, 0x10,         // $807D: EOR $10
+        0x8D, 0x0E, 0x21,   // $807F: STA $210E
+        0x9C, 0x0E, 0x21,   // $8082: STZ $210E
+        0xAD, 0x10, 0x42,   // $8085: LDA $4210
+        0x68,               // $8088: PLA
+        0x40,               // $8089: RTI
+    ];
+    let mut rom = common::lorom(&code);
+    rom[0x7FEA] = 0x6D; // Native NMI vector
+    rom[0x7FEB] = 0x80;
+    rom
+}
+
+fn new_snes() -> SNES {
+    let mut snes = SNES::from_rom_data(&test_rom(), "", None).unwrap();
+    snes.randomize_wram(SEED);
+    snes
+}
+
+// Run a frame, with input that changes every frame.
+fn run_frame(snes: &mut SNES, frame_num: usize, frame: &mut [u8]) {
+    for (i, button) in Button::iter().enumerate() {
+        let pressed = ((frame_num * 7 + i * 3) % 5) < 2;
+        snes.set_button(button, pressed, 0);
+    }
+    snes.frame(frame);
+}
+
+// The state includes a thumbnail of the last frame, but compare the whole frame too.
+fn check_same(a: &SNES, a_frame: &[u8], b: &SNES, b_frame: &[u8]) {
+    assert!(a_frame == b_frame, "Frames differ");
+    assert!(a.wram() == b.wram(), "WRAM differs");
+    assert!(a.save_state() == b.save_state(), "States differ");
+}
+
+#[test]
+fn same_inputs_same_state() {
+    let mut a = new_snes();
+    let mut b = new_snes();
+    let mut a_frame = vec![0; a.frame_buffer_size()];
+    let mut b_frame = vec![0; b.frame_buffer_size()];
+    for i in 0..FRAMES {
+        run_frame(&mut a, i, &mut a_frame);
+        run_frame(&mut b, i, &mut b_frame);
+    }
+    // Make sure the NMI handler ran.
+    assert_ne!(a.wram()[0x10], 0);
+    check_same(&a, &a_frame, &b, &b_frame);
+}
+
+#[test]
+fn load_state_part_way() {
+    let mut a = new_snes();
+    let mut a_frame = vec![0; a.frame_buffer_size()];
+    for i in 0..FRAMES {
+        run_frame(&mut a, i, &mut a_frame);
+    }
+
+    // Save part way through, then continue in a new SNES.
+    let mut b = new_snes();
+    let mut b_frame = vec![0; b.frame_buffer_size()];
+    for i in 0..SAVE_FRAME {
+        run_frame(&mut b, i, &mut b_frame);
+    }
+    let state = b.save_state();
+    let mut c = SNES::from_rom_data(&test_rom(), "", None).unwrap();
+    c.load_state(&state).unwrap();
+    for i in SAVE_FRAME..FRAMES {
+        run_frame(&mut c, i, &mut b_frame);
+    }
+    check_same(&a, &a_frame, &c, &b_frame);
+
+    // Go back in the same SNES, and run again.
+    b.load_state(&state).unwrap();
+    for i in SAVE_FRAME..FRAMES {
+        run_frame(&mut b, i, &mut b_frame);
+    }
+    check_same(&a, &a_frame, &b, &b_frame);
+}
@@ -0,0 +1,73 @@
+// Check DMA channel registers and transfers started through the debug API.
+#![cfg(feature = "debug")]
+
+mod common;
+
+use oxide7::SNES;
+
+// A 32kB LoROM that loops forever, with data to transfer at $8100.
+fn data_rom() -> Vec<u8> {
+    let mut rom = common::lorom(&[
+        0x80, 0xFE,         // $8000: BRA $8000
+    ]);
+    rom[0x100..0x104].copy_from_slice(&[0x12, 0x34, 0x56, 0x78]);
+    rom
+}
+
+// Set up channel 2 to copy 4 bytes from $00:8100 to VRAM $0000.
+// A frame is run first, so the renderer has a target for any lines drawn during the transfer.
+fn setup_vram_dma(snes: &mut SNES) {
+    let mut frame = vec![0; snes.frame_buffer_size()];
+    snes.frame(&mut frame);
+    snes.set_mem_at(0x2100, 0x80);
+    snes.set_mem_at(0x2115, 0x80);
+    snes.set_mem_at(0x2116, 0x00);
+    snes.set_mem_at(0x2117, 0x00);
+    for (i, data) in [0x01, 0x18, 0x00, 0x81, 0x00, 0x04, 0x00].iter().enumerate() {
+        snes.set_mem_at(0x4320 + i as u32, *data);
+    }
+}
+
+#[test]
+fn channel_registers() {
+    let mut snes = SNES::from_rom_data(&data_rom(), "", None).unwrap();
+    setup_vram_dma(&mut snes);
+    snes.set_mem_at(0x4327, 0x7E);
+    snes.set_mem_at(0x4328, 0x34);
+    snes.set_mem_at(0x4329, 0x12);
+    snes.set_mem_at(0x432A, 0x05);
+
+    let regs = snes.get_dma_channels()[2];
+    assert_eq!(regs.control, 0x01);
+    assert_eq!(regs.b_bus_addr, 0x18);
+    assert_eq!(regs.a_bus_addr, 0x008100);
+    assert_eq!(regs.count, 4);
+    assert_eq!(regs.hdma_bank, 0x7E);
+    assert_eq!(regs.hdma_table_addr, 0x1234);
+    assert_eq!(regs.hdma_line_count, 0x05);
+    assert_eq!(regs.unused, 0xFF);
+    assert_eq!(snes.get_dma_channels()[3], oxide7::debug::DMAChannelRegs {
+        a_bus_addr: 0,
+        hdma_table_addr: 0xFF,
+        unused: 0xFF,
+        ..Default::default()
+    });
+}
+
+#[test]
+fn trigger_transfer() {
+    let mut snes = SNES::from_rom_data(&data_rom(), "", None).unwrap();
+    setup_vram_dma(&mut snes);
+
+    snes.trigger_dma(0x04);
+    assert_eq!(&snes.vram()[..4], &[0x12, 0x34, 0x56, 0x78]);
+
+    let regs = snes.get_dma_channels()[2];
+    assert_eq!(regs.a_bus_addr, 0x008104);
+    assert_eq!(regs.count, 0);
+
+    // Channels not in the mask don't run.
+    snes.set_mem_at(0x4325, 0x04);
+    snes.trigger_dma(0x01);
+    assert_eq!(snes.get_dma_channels()[2].count, 4);
+}
@@ -0,0 +1,79 @@
+// Check that the DSP ROM for the game is picked from a directory of DSP ROMs.
+
+use std::{
+    env,
+    fs,
+    path::{
+        Path,
+        PathBuf
+    }
+};
+
+use oxide7::{
+    Error,
+    SNES
+};
+
+// A 32kB LoROM with a DSP, that does nothing.
+fn dsp_rom(name: &str) -> Vec<u8> {
+    let mut rom = vec![0; 0x8000];
+    rom[0] = 0x80;      // $8000: BRA $8000
+    rom[1] = 0xFE;
+    rom[0x7FC0..0x7FD5].copy_from_slice(format!("{:21}", name).as_bytes());
+    rom[0x7FD5] = 0x20; // LoROM
+    rom[0x7FD6] = 0x03; // ROM + DSP
+    rom[0x7FD7] = 0x05;
+    rom[0x7FFC] = 0x00; // Reset vector: $8000
+    rom[0x7FFD] = 0x80;
+    rom
+}
+
+// Make an empty directory with a blank ROM for each of the names provided.
+fn dsp_dir(test_name: &str, roms: &[&str]) -> PathBuf {
+    let dir = env::temp_dir().join(format!("oxide7-{}", test_name));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    for rom in roms {
+        fs::write(dir.join(rom), vec![0; 0x2000]).unwrap();
+    }
+    dir
+}
+
+// Load the game, and get the file name of the DSP ROM that was picked.
+fn picked_rom(game_name: &str, dir: &Path) -> Option<String> {
+    let snes = SNES::from_rom_data(&dsp_rom(game_name), "", dir.to_str()).unwrap();
+    snes.cart_info().dsp_rom.map(|path| Path::new(&path).file_name().unwrap().to_string_lossy().into_owned())
+}
+
+#[test]
+fn pick_by_game_name() {
+    let dir = dsp_dir("dsp-all", &["dsp1.rom", "dsp1b.rom", "dsp2.rom", "dsp3.rom", "dsp4.rom"]);
+    assert_eq!(picked_rom("SUPER MARIO KART", &dir).as_deref(), Some("dsp1b.rom"));
+    assert_eq!(picked_rom("PILOTWINGS", &dir).as_deref(), Some("dsp1.rom"));
+    assert_eq!(picked_rom("DUNGEON MASTER", &dir).as_deref(), Some("dsp2.rom"));
+    assert_eq!(picked_rom("TOP GEAR 3000", &dir).as_deref(), Some("dsp4.rom"));
+}
+
+#[test]
+fn fall_back_to_other_revision() {
+    let dir = dsp_dir("dsp-fallback", &["dsp1.bin"]);
+    assert_eq!(picked_rom("SUPER MARIO KART", &dir).as_deref(), Some("dsp1.bin"));
+}
+
+#[test]
+fn missing_rom_in_dir() {
+    let dir = dsp_dir("dsp-missing", &["dsp1.rom"]);
+    let result = SNES::from_rom_data(&dsp_rom("DUNGEON MASTER"), "", dir.to_str());
+    assert_eq!(result.err(), Some(Error::DSPROMNotFound {
+        dir:        dir.to_str().unwrap().to_string(),
+        expected:   vec!["dsp2".to_string()],
+    }));
+}
+
+#[test]
+fn single_rom_path() {
+    let dir = dsp_dir("dsp-single", &["custom.rom"]);
+    let path = dir.join("custom.rom");
+    let snes = SNES::from_rom_data(&dsp_rom("DUNGEON MASTER"), "", path.to_str()).unwrap();
+    assert_eq!(snes.cart_info().dsp_rom.as_deref(), path.to_str());
+}
@@ -0,0 +1,147 @@
+// Check a cart chip implemented outside the crate.
+use std::sync::{
+    Arc,
+    atomic::{
+        AtomicU64,
+        Ordering
+    }
+};
+
+use oxide7::{
+    CartInfo,
+    Expansion,
+    ExpansionProvider,
+    Interrupt,
+    ROM,
+    SNES,
+    SRAM,
+    Stateful,
+    StateReader,
+    StateWriter
+};
+
+// Maps LoROM, with a single register at $2200 that returns the last value written plus one.
+// Counts the master cycles it is clocked for.
+struct TestChip {
+    rom:    ROM,
+    reg:    u8,
+    cycles: Arc<AtomicU64>,
+}
+
+impl Expansion for TestChip {
+    fn read(&mut self, bank: u8, addr: u16) -> u8 {
+        match addr {
+            0x2200 => self.reg.wrapping_add(1),
+            0x8000..=0xFFFF => self.rom.read(bank % 0x80, addr % 0x8000),
+            _ => 0,
+        }
+    }
+
+    fn write(&mut self, _bank: u8, addr: u16, data: u8) {
+        if addr == 0x2200 {
+            self.reg = data;
+        }
+    }
+
+    fn clock(&mut self, cycles: usize) -> Interrupt {
+        self.cycles.fetch_add(cycles as u64, Ordering::Relaxed);
+        Interrupt::default()
+    }
+}
+
+impl Stateful for TestChip {
+    fn save_state(&self, w: &mut StateWriter) {
+        w.write(&self.reg);
+    }
+
+    fn load_state(&mut self, r: &mut StateReader) -> Result<(), String> {
+        r.read_into(&mut self.reg)
+    }
+}
+
+#[derive(Default)]
+struct TestProvider {
+    cycles: Arc<AtomicU64>,
+}
+
+impl ExpansionProvider for TestProvider {
+    fn provides(&self, info: &CartInfo) -> bool {
+        info.name.starts_with("CHIP")
+    }
+
+    fn create(&mut self, _info: &CartInfo, rom: ROM, _sram: Box<dyn SRAM>) -> Box<dyn Expansion> {
+        assert_eq!(rom.data().len(), 0x8000);
+        Box::new(TestChip {
+            rom:    rom,
+            reg:    0,
+            cycles: self.cycles.clone(),
+        })
+    }
+}
+
+// A 32kB LoROM that writes to the chip, and copies the result into WRAM.
+fn chip_rom(name: &[u8; 4]) -> Vec<u8> {
+    let mut rom = vec![0; 0x8000];
+    rom[..12].copy_from_slice(&[
+        0xA9, 0x41,         // $8000: LDA #$41
+        0x8D, 0x00, 0x22,   // $8002: STA $2200
+        0xAD, 0x00, 0x22,   // $8005: LDA $2200
+        0x85, 0x10,         // $8008: STA $10
+        0x80, 0xFE,         // $800A: BRA $800A
+    ]);
+    rom[0x7FC0..0x7FC4].copy_from_slice(name);
+    rom[0x7FD5] = 0x20; // LoROM
+    rom[0x7FD7] = 0x05; // 32kB
+    rom[0x7FFC] = 0x00; // Reset vector
+    rom[0x7FFD] = 0x80;
+    rom
+}
+
+#[test]
+fn custom_chip() {
+    let mut snes = SNES::from_rom_data_with_expansion(&chip_rom(b"CHIP"), "", None, &mut TestProvider::default()).unwrap();
+    let mut frame = vec![0; snes.frame_buffer_size()];
+    snes.frame(&mut frame);
+    assert_eq!(snes.wram()[0x10], 0x42);
+}
+
+#[test]
+fn unsupported_cart() {
+    // Loaded as a normal LoROM: $2200 isn't mapped.
+    let mut snes = SNES::from_rom_data_with_expansion(&chip_rom(b"TEST"), "", None, &mut TestProvider::default()).unwrap();
+    let mut frame = vec![0; snes.frame_buffer_size()];
+    snes.frame(&mut frame);
+    assert_eq!(snes.wram()[0x10], 0x00);
+}
+
+#[test]
+fn chip_keeps_time() {
+    // Poll the chip register forever. Each access runs the chip ahead of the rest of the system.
+    let mut rom = chip_rom(b"CHIP");
+    rom[..5].copy_from_slice(&[
+        0xAD, 0x00, 0x22,   // $8000: LDA $2200
+        0x80, 0xFB,         // $8003: BRA $8000
+    ]);
+    let mut provider = TestProvider::default();
+    let mut snes = SNES::from_rom_data_with_expansion(&rom, "", None, &mut provider).unwrap();
+    let mut frame = vec![0; snes.frame_buffer_size()];
+    for _ in 0..3 {
+        snes.frame(&mut frame);
+    }
+
+    // The chip is never more than one register access ahead.
+    let chip_cycles = provider.cycles.load(Ordering::Relaxed);
+    let lead = chip_cycles - snes.frame_timestamp();
+    assert!(lead <= 6, "Chip is {} cycles ahead", lead);
+
+    // It stays in step after loading a state.
+    let state = snes.save_state();
+    let mut loaded_provider = TestProvider::default();
+    let mut loaded = SNES::from_rom_data_with_expansion(&rom, "", None, &mut loaded_provider).unwrap();
+    loaded.load_state(&state).unwrap();
+    loaded.frame(&mut frame);
+    snes.frame(&mut frame);
+    assert_eq!(loaded.frame_timestamp(), snes.frame_timestamp());
+    let loaded_cycles = chip_cycles + loaded_provider.cycles.load(Ordering::Relaxed);
+    assert_eq!(loaded_cycles, provider.cycles.load(Ordering::Relaxed));
+}
@@ -0,0 +1,78 @@
+// Check the APU fast boot uploads programs the same way as the IPL ROM.
+#![cfg(feature = "debug")]
+
+mod common;
+
+use oxide7::{
+    SNES,
+    debug::{
+        Condition,
+        run_until
+    }
+};
+
+// Where the CPU waits once the SPC program has started.
+const DONE: u32 = 0x00804A;
+
+// A 32kB LoROM that uploads a small program to $0200 in ARAM, starts it,
+// and waits for it to write $5A to port 0.
+fn upload_rom() -> Vec<u8> {
+    let code = [
+        0xA9, 0xAA,         // $8000: LDA #$AA
+        0xCD, 0x40, 0x21,   // $8002: CMP $2140
+        0xD0, 0xFB,         // $8005: BNE $8002
+        0xA9, 0xBB,         // $8007: LDA #$BB
+        0xCD, 0x41, 0x21,   // $8009: CMP $2141
+        0xD0, 0xFB,         // $800C: BNE $8009
+        0x9C, 0x42, 0x21,   // $800E: STZ $2142
+        0xA9, 0x02,         // $8011: LDA #$02
+        0x8D, 0x43, 0x21,   // $8013: STA $2143
+        0xA9, 0x01,         // $8016: LDA #$01
+        0x8D, 0x41, 0x21,   // $8018: STA $2141
+        0xA9, 0xCC,         // $801B: LDA #$CC
+        0x8D, 0x40, 0x21,   // $801D: STA $2140
+        0xCD, 0x40, 0x21,   // $8020: CMP $2140
+        0xD0, 0xFB,         // $8023: BNE $8020
+        0xA2, 0x00,         // $8025: LDX #$00
+        0xBD, 0x00, 0x81,   // $8027: LDA $8100,X
+        0x8D, 0x41, 0x21,   // $802A: STA $2141
+        0x8A,               // $802D: TXA
+        0x8D, 0x40, 0x21,   // $802E: STA $2140
+        0xCD, 0x40, 0x21,   // $8031: CMP $2140
+        0xD0, 0xFB,         // $8034: BNE $8031
+        0xE8,               // $8036: INX
+        0xE0, 0x05,         // $8037: CPX #$05
+        0xD0, 0xEC,         // $8039: BNE $8027
+        0x9C, 0x41, 0x21,   // $803B: STZ $2141
+        0xA9, 0x06,         // $803E: LDA #$06
+        0x8D, 0x40, 0x21,   // $8040: STA $2140
+        0xA9, 0x5A,         // $8043: LDA #$5A
+        0xCD, 0x40, 0x21,   // $8045: CMP $2140
+        0xD0, 0xFB,         // $8048: BNE $8045
+        0x80, 0xFE,         // $804A: BRA $804A
+    ];
+    let mut rom = common::lorom(&code);
+    // SPC program.
+    rom[0x100..0x105].copy_from_slice(&[
+        0x8F, 0x5A, 0xF4,   // $0200: MOV $F4, #$5A
+        0x2F, 0xFE,         // $0203: BRA $0203
+    ]);
+    rom
+}
+
+fn run_upload(fast_boot: bool) -> (SNES, usize) {
+    let mut snes = SNES::from_rom_data(&upload_rom(), "", None).unwrap();
+    snes.set_fast_apu_boot(fast_boot);
+    let steps = run_until(&mut snes, Condition::PC(DONE), 1_000_000).expect("upload didn't finish");
+    (snes, steps)
+}
+
+#[test]
+fn fast_boot_upload() {
+    let (snes, steps) = run_upload(false);
+    let (fast_snes, fast_steps) = run_upload(true);
+
+    assert_eq!(snes.read_aram(0x200..0x205), &[0x8F, 0x5A, 0xF4, 0x2F, 0xFE]);
+    assert_eq!(fast_snes.read_aram(0x200..0x205), snes.read_aram(0x200..0x205));
+    assert!(fast_steps < steps, "fast boot took {} steps, IPL ROM took {}", fast_steps, steps);
+}
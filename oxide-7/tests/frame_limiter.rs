@@ -0,0 +1,41 @@
+// Check the frame limiter keeps to the console's frame rate.
+use std::time::{
+    Duration,
+    Instant
+};
+
+use oxide7::{
+    FrameLimiter,
+    Region
+};
+
+#[test]
+fn console_frame_times() {
+    let ntsc = FrameLimiter::ntsc().frame_time().as_secs_f64();
+    assert!((ntsc - (1.0 / 60.0988)).abs() < 0.000_001, "NTSC frame time: {}", ntsc);
+    let pal = FrameLimiter::pal().frame_time().as_secs_f64();
+    assert!((pal - (1.0 / 50.0070)).abs() < 0.000_001, "PAL frame time: {}", pal);
+    assert_eq!(FrameLimiter::for_region(Region::PAL).frame_time(), FrameLimiter::pal().frame_time());
+}
+
+#[test]
+fn wait_paces_frames() {
+    let start = Instant::now();
+    let mut limiter = FrameLimiter::new(Duration::from_millis(5));
+    for _ in 0..10 {
+        limiter.wait();
+    }
+    assert!(start.elapsed() >= Duration::from_millis(50));
+}
+
+#[test]
+fn late_frames_are_made_up() {
+    let mut limiter = FrameLimiter::new(Duration::from_millis(20));
+    std::thread::sleep(Duration::from_millis(30));
+
+    // This frame was late, so the next wait is shorter to get back on time.
+    limiter.wait();
+    let start = Instant::now();
+    limiter.wait();
+    assert!(start.elapsed() < Duration::from_millis(15));
+}
@@ -0,0 +1,66 @@
+// Check frames over budget are emulated without being drawn.
+mod common;
+
+use std::time::Duration;
+
+use oxide7::SNES;
+
+// A 32kB LoROM that changes the backdrop colour every frame in the NMI handler.
+fn colour_rom() -> Vec<u8> {
+    let mut rom = common::lorom(&[
+        0xA9, 0x0F,         // $8000: LDA #$0F
+        0x8D, 0x00, 0x21,   // $8002: STA $2100
+        0xA9, 0x80,         // $8005: LDA #$80
+        0x8D, 0x00, 0x42,   // $8007: STA $4200
+        0x80, 0xFE,         // $800A: BRA $800A
+        0xE6, 0x00,         // $800C: INC $00
+        0x9C, 0x21, 0x21,   // $800E: STZ $2121
+        0xA5, 0x00,         // $8011: LDA $00
+        0x8D, 0x22, 0x21,   // $8013: STA $2122
+        0x8D, 0x22, 0x21,   // $8016: STA $2122
+        0x40,               // $8019: RTI
+    ]);
+    rom[0x7FFA] = 0x0C; // NMI vector
+    rom[0x7FFB] = 0x80;
+    rom
+}
+
+#[test]
+fn no_skip_by_default() {
+    let mut snes = SNES::from_rom_data(&colour_rom(), "", None).unwrap();
+    let mut frame = vec![0; snes.frame_buffer_size()];
+    for _ in 0..5 {
+        snes.frame(&mut frame);
+    }
+    assert!(snes.take_skipped_frames().is_empty());
+}
+
+#[test]
+fn skip_frames_over_budget() {
+    let mut snes = SNES::from_rom_data(&colour_rom(), "", None).unwrap();
+    let mut frame = vec![0; snes.frame_buffer_size()];
+    snes.frame(&mut frame);
+
+    // Every frame is over budget, so at most 2 are skipped in a row.
+    snes.set_frame_budget(Some(Duration::from_nanos(1)), 2);
+    let mut changed = Vec::new();
+    for _ in 1..10 {
+        let prev = frame.clone();
+        let timestamp = snes.frame_timestamp();
+        snes.frame(&mut frame);
+        assert!(snes.frame_timestamp() > timestamp);
+        changed.push(frame != prev);
+    }
+    let skipped = snes.take_skipped_frames();
+    assert_eq!(skipped, vec![2, 3, 5, 6, 8, 9]);
+    assert!(snes.take_skipped_frames().is_empty());
+
+    // Skipped frames leave the last frame drawn in the buffer.
+    for (i, changed) in changed.iter().enumerate() {
+        assert_eq!(*changed, !skipped.contains(&((i + 1) as u64)), "frame {}", i + 1);
+    }
+
+    snes.set_frame_budget(None, 2);
+    snes.frame(&mut frame);
+    assert!(snes.take_skipped_frames().is_empty());
+}
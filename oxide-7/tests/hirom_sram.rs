@@ -0,0 +1,44 @@
+// Check HiROM SRAM at $20-$3F:6000-7FFF, and its mirrors.
+mod common;
+
+use std::{env, fs};
+
+use oxide7::SNES;
+
+// A 64kB HiROM with 2kB SRAM, that writes to SRAM through different banks,
+// then reads it back through others into $0000-$0002.
+fn sram_rom() -> Vec<u8> {
+    let code = [
+        0xA9, 0x11,                 // $8000: LDA #$11
+        0x8F, 0x00, 0x60, 0x20,     // $8002: STA $206000
+        0xA9, 0x22,                 // $8006: LDA #$22
+        0x8F, 0x01, 0x78, 0xA0,     // $8008: STA $A07801
+        0xAF, 0x00, 0x60, 0xBF,     // $800C: LDA $BF6000
+        0x8D, 0x00, 0x00,           // $8010: STA $0000
+        0xAF, 0x01, 0x60, 0x30,     // $8013: LDA $306001
+        0x8D, 0x01, 0x00,           // $8017: STA $0001
+        0xAF, 0x00, 0x60, 0xA0,     // $801A: LDA $A06000
+        0x8D, 0x02, 0x00,           // $801E: STA $0002
+        0x80, 0xFE,                 // $8021: BRA $8021
+    ];
+    common::Cart::hirom()
+        .rom_type(0x02)     // ROM + RAM + battery
+        .sram_size(0x01)    // 2kB SRAM
+        .build(&code)
+}
+
+#[test]
+fn sram_mirrors() {
+    let save_path = env::temp_dir().join("oxide7-hirom-sram.sav");
+    let _ = fs::remove_file(&save_path);
+
+    let mut snes = SNES::from_rom_data(&sram_rom(), save_path.to_str().unwrap(), None).unwrap();
+    let mut frame = vec![0; snes.frame_buffer_size()];
+    snes.frame(&mut frame);
+
+    // Each bank has the same 8kB window, and 2kB SRAM is mirrored through it.
+    assert_eq!(&snes.wram()[..3], &[0x11, 0x22, 0x11]);
+
+    drop(snes);
+    let _ = fs::remove_file(&save_path);
+}
@@ -0,0 +1,53 @@
+// Check queued input is only applied when the joypads are read.
+mod common;
+
+use oxide7::{
+    SNES,
+    Button
+};
+
+// A 32kB LoROM that enables auto-joypad reading, then loops.
+fn auto_read_rom() -> Vec<u8> {
+    common::lorom(&[
+        0xA9, 0x01,         // $8000: LDA #$01
+        0x8D, 0x00, 0x42,   // $8002: STA $4200
+        0x80, 0xFE,         // $8005: BRA $8005
+    ])
+}
+
+#[test]
+fn input_queue() {
+    let mut snes = SNES::from_rom_data(&auto_read_rom(), "", None).unwrap();
+    let mut frame = vec![0; snes.frame_buffer_size()];
+    snes.set_input_queue(true);
+
+    snes.set_button(Button::A, true, 0);
+    assert_eq!(snes.held_buttons(0), 0);
+    snes.frame(&mut frame);
+    assert_eq!(snes.held_buttons(0), 0x0080);
+
+    // Disabling the queue applies waiting input.
+    snes.set_button(Button::A, false, 0);
+    snes.set_input_queue(false);
+    assert_eq!(snes.held_buttons(0), 0);
+    snes.set_button(Button::B, true, 1);
+    assert_eq!(snes.held_buttons(1), 0x8000);
+}
+
+#[test]
+fn queue_button_at_cycle() {
+    let mut snes = SNES::from_rom_data(&auto_read_rom(), "", None).unwrap();
+    let mut frame = vec![0; snes.frame_buffer_size()];
+    snes.frame(&mut frame);
+    let start = snes.frame_timestamp();
+    snes.frame(&mut frame);
+    let frame_len = snes.frame_timestamp() - start;
+
+    // Apply two frames from now.
+    snes.queue_button(Button::Start, true, 0, snes.frame_timestamp() + (frame_len * 2));
+    snes.frame(&mut frame);
+    assert_eq!(snes.held_buttons(0), 0);
+    snes.frame(&mut frame);
+    snes.frame(&mut frame);
+    assert_eq!(snes.held_buttons(0), 0x1000);
+}
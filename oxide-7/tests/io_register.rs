@@ -0,0 +1,44 @@
+// Check the I/O register lookup table.
+#![cfg(feature = "debug")]
+
+use oxide7::debug::{
+    io_register,
+    io_register_name
+};
+
+#[test]
+fn register_names() {
+    assert_eq!(io_register_name(0x2100), Some("INIDISP"));
+    assert_eq!(io_register_name(0x4212), Some("HVBJOY"));
+    assert_eq!(io_register_name(0x4375), Some("DAS7L"));
+    // Mirrors.
+    assert_eq!(io_register_name(0x802118), Some("VMDATAL"));
+    assert_eq!(io_register_name(0x3F4016), Some("JOYSER0"));
+    // Not I/O.
+    assert_eq!(io_register_name(0x7E2100), None);
+    assert_eq!(io_register_name(0x2144), None);
+    assert_eq!(io_register_name(0x420E), None);
+}
+
+#[test]
+fn all_registers() {
+    let registers = (0x2000..0x4400).filter_map(|addr| io_register(addr).map(|r| (addr, r))).collect::<Vec<_>>();
+    assert_eq!(registers.len(), 208);
+    for (addr, register) in registers {
+        assert_eq!(register.addr as u32, addr);
+        let mask = register.fields.iter().fold(0, |acc, (mask, _)| {
+            // Fields don't overlap.
+            assert_eq!(acc & mask, 0, "{}", register.name);
+            acc | mask
+        });
+        assert!(register.fields.is_empty() || mask != 0);
+    }
+}
+
+#[test]
+fn describe() {
+    let inidisp = io_register(0x2100).unwrap();
+    assert_eq!(inidisp.describe(0x8F), "INIDISP: Force blank=1 Brightness=F");
+    assert_eq!(io_register(0x2118).unwrap().describe(0x5A), "VMDATAL: 5A");
+    assert_eq!(io_register(0x4300).unwrap().describe(0x01), "DMAP0: B to A=0 HDMA indirect=0 A address step=0 Transfer pattern=1");
+}
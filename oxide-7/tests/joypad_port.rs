@@ -0,0 +1,66 @@
+// Check the bits read from $4016 and $4017 when reading the joypads manually.
+mod common;
+
+use oxide7::{
+    SNES,
+    Button,
+    Port,
+    Unplugged
+};
+
+// A 32kB LoROM that latches the joypads, reads $4016 24 times into $0000-$0017,
+// then reads $4017 into $0020.
+fn manual_read_rom() -> Vec<u8> {
+    let code = [
+        0xA9, 0x01,         // $8000: LDA #$01
+        0x8D, 0x16, 0x40,   // $8002: STA $4016
+        0x9C, 0x16, 0x40,   // $8005: STZ $4016
+        0xA2, 0x00,         // $8008: LDX #$00
+        0xAD, 0x16, 0x40,   // $800A: LDA $4016
+        0x9D, 0x00, 0x00,   // $800D: STA $0000,X
+        0xE8,               // $8010: INX
+        0xE0, 0x18,         // $8011: CPX #$18
+        0xD0, 0xF5,         // $8013: BNE $800A
+        0xAD, 0x17, 0x40,   // $8015: LDA $4017
+        0x8D, 0x20, 0x00,   // $8018: STA $0020
+        0x80, 0xFE,         // $801B: BRA $801B
+    ];
+    common::lorom(&code)
+}
+
+fn run(snes: &mut SNES) {
+    let mut frame = vec![0; snes.frame_buffer_size()];
+    snes.frame(&mut frame);
+}
+
+// The top bits of $4016 are open bus: the high byte of the address, from the last operand read.
+#[test]
+fn joypad_bits() {
+    let mut snes = SNES::from_rom_data(&manual_read_rom(), "", None).unwrap();
+    snes.set_button(Button::B, true, 0);
+    snes.set_button(Button::A, true, 0);
+    run(&mut snes);
+
+    let wram = snes.wram();
+    let mut expected = [0x40; 24];
+    expected[0] = 0x41;     // B
+    expected[8] = 0x41;     // A
+    // Bits 12-15 are the controller signature (0 for a standard joypad).
+    // After 16 bits, a standard joypad returns 1.
+    expected[16..].iter_mut().for_each(|b| *b = 0x41);
+    assert_eq!(&wram[..24], &expected);
+
+    // Bits 2-4 of $4017 are always set.
+    assert_eq!(wram[0x20], 0x5C);
+}
+
+#[test]
+fn unplugged_port() {
+    let mut snes = SNES::from_rom_data(&manual_read_rom(), "", None).unwrap();
+    snes.set_peripheral(Port::One, Box::new(Unplugged));
+    run(&mut snes);
+
+    assert!(snes.wram()[..24].iter().all(|b| *b == 0x40));
+    assert!(snes.remove_peripheral(Port::One).is_some());
+    assert!(snes.remove_peripheral(Port::Two).is_none());
+}
@@ -0,0 +1,65 @@
+// Check that carts that can't be loaded return an error instead of panicking.
+mod common;
+
+use std::env;
+
+use oxide7::{
+    EnhancementChip,
+    Error,
+    SNES,
+    SNESBuilder
+};
+
+// A 32kB LoROM that loops forever, with the mapping and ROM type bytes from the header provided.
+fn rom(mapping: u8, rom_type: u8) -> Vec<u8> {
+    common::Cart::lorom()
+        .map_mode(mapping)
+        .rom_type(rom_type)
+        .sram_size(0x03)    // 8kB SRAM, if the ROM type has it
+        .build(&[
+            0x80, 0xFE, // $8000: BRA $8000
+        ])
+}
+
+#[test]
+fn missing_file() {
+    let path = env::temp_dir().join("oxide7-missing").join("game.sfc");
+    match SNES::new(path.to_str().unwrap(), "", None) {
+        Err(Error::File {path: error_path, ..}) => assert_eq!(error_path, path.to_str().unwrap()),
+        other => panic!("expected file error, got {:?}", other.err()),
+    }
+}
+
+#[test]
+fn bad_save_path() {
+    let save_path = env::temp_dir().join("oxide7-missing").join("game.sav");
+    let result = SNES::from_rom_data(&rom(0x20, 0x02), save_path.to_str().unwrap(), None);
+    assert!(matches!(result.err(), Some(Error::File {..})));
+}
+
+#[test]
+fn bad_header() {
+    assert_eq!(SNES::from_rom_data(&vec![0; 0x8000], "", None).err(), Some(Error::BadHeader));
+    assert_eq!(SNES::from_rom_data(&[0; 16], "", None).err(), Some(Error::BadHeader));
+}
+
+#[test]
+fn unsupported_mapper() {
+    assert_eq!(SNES::from_rom_data(&rom(0x3A, 0x00), "", None).err(), Some(Error::UnsupportedMapper(0x3A)));
+}
+
+#[test]
+fn unsupported_chip() {
+    let obc1 = rom(0x20, 0x23);
+    assert!(SNES::from_rom_data(&obc1, "", None).is_ok());
+
+    let result = SNESBuilder::from_rom_data(&obc1)
+        .allow_unsupported_chips(false)
+        .build();
+    assert_eq!(result.err(), Some(Error::UnsupportedChip(EnhancementChip::OBC1)));
+}
+
+#[test]
+fn no_dsp_rom() {
+    assert_eq!(SNES::from_rom_data(&rom(0x20, 0x03), "", None).err(), Some(Error::NoDSPROM));
+}
@@ -0,0 +1,36 @@
+// Check that a state that can't be loaded leaves the SNES as it was.
+mod common;
+
+use oxide7::SNES;
+
+// A 32kB LoROM that counts up in RAM forever.
+fn counter_rom() -> Vec<u8> {
+    common::lorom(&[
+        0xEE, 0x10, 0x00,   // $8000: INC $0010
+        0x80, 0xFB          // $8003: BRA $8000
+    ])
+}
+
+#[test]
+fn truncated_state() {
+    let mut snes = SNES::from_rom_data(&counter_rom(), "", None).unwrap();
+    let mut frame = vec![0; snes.frame_buffer_size()];
+    snes.frame(&mut frame);
+    let state = snes.save_state();
+
+    for _ in 0..5 {
+        snes.frame(&mut frame);
+    }
+    let wram = snes.wram().to_vec();
+    let timestamp = snes.frame_timestamp();
+
+    // Work RAM comes before the end of the state, so it is read before loading fails.
+    assert!(snes.load_state(&state[..(state.len() - 16)]).is_err());
+    assert!(snes.wram() == wram.as_slice());
+    assert_eq!(snes.frame_timestamp(), timestamp);
+
+    // The SNES runs on from where it was.
+    snes.frame(&mut frame);
+    assert!(snes.wram() != wram.as_slice());
+    assert!(snes.load_state(&state).is_ok());
+}
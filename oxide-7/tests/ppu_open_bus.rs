@@ -0,0 +1,91 @@
+// Check the values read from each B bus register.
+mod common;
+
+use oxide7::SNES;
+
+// A 32kB LoROM that sets the PPU1 open bus to $06 by reading the multiply result,
+// then reads each of $2100-$21FF into $0000-$00FF.
+fn read_ports_rom() -> Vec<u8> {
+    let code = [
+        0xA9, 0x02,         // $8000: LDA #$02
+        0x8D, 0x1B, 0x21,   // $8002: STA $211B
+        0x9C, 0x1B, 0x21,   // $8005: STZ $211B
+        0xA9, 0x03,         // $8008: LDA #$03
+        0x8D, 0x1C, 0x21,   // $800A: STA $211C
+        0xAD, 0x34, 0x21,   // $800D: LDA $2134
+        0xA2, 0x00,         // $8010: LDX #$00
+        0xBD, 0x00, 0x21,   // $8012: LDA $2100,X
+        0x9D, 0x00, 0x00,   // $8015: STA $0000,X
+        0xE8,               // $8018: INX
+        0xD0, 0xF7,         // $8019: BNE $8012
+        0x80, 0xFE,         // $801B: BRA $801B
+    ];
+    common::lorom(&code)
+}
+
+#[test]
+fn read_each_port() {
+    let mut snes = SNES::from_rom_data(&read_ports_rom(), "", None).unwrap();
+    let mut frame = vec![0; snes.frame_buffer_size()];
+    snes.frame(&mut frame);
+    let wram = snes.wram();
+
+    // Write-only registers on PPU1 return the last value read from PPU1.
+    let ppu1 = [0x04, 0x05, 0x06, 0x08, 0x09, 0x0A, 0x14, 0x15, 0x16, 0x18, 0x19, 0x1A, 0x24, 0x25, 0x26, 0x28, 0x29, 0x2A];
+    for (port, data) in wram.iter().enumerate().take(0x34) {
+        // The rest return CPU open bus: the high byte of the address operand.
+        let expected = if ppu1.contains(&port) {0x06} else {0x21};
+        assert_eq!(*data, expected, "${:04X}", 0x2100 + port);
+    }
+
+    // Multiply result.
+    assert_eq!(&wram[0x34..0x37], &[0x06, 0x00, 0x00]);
+    // Latching the H/V counters returns open bus.
+    assert_eq!(wram[0x37], 0x21);
+    assert_eq!(wram[0x3E], 0x01);
+
+    // The APU ports are mirrored up to $217F.
+    for port in 0x44..=0x7F {
+        assert_eq!(wram[port], wram[0x40 + (port % 4)], "${:04X}", 0x2100 + port);
+    }
+
+    // WRAM address registers are write-only, and nothing is connected to the expansion port.
+    for (port, data) in wram.iter().enumerate().take(0x100).skip(0x81) {
+        assert_eq!(*data, 0x21, "${:04X}", 0x2100 + port);
+    }
+}
+
+// A 32kB LoROM that reads $2137 then $213F, first with WRIO bit 7 clear, then with it set,
+// into $0000-$0003.
+fn latch_rom() -> Vec<u8> {
+    let code = [
+        0x9C, 0x01, 0x42,   // $8000: STZ $4201
+        0xAD, 0x3F, 0x21,   // $8003: LDA $213F (clear the latch made by WRIO)
+        0xAD, 0x37, 0x21,   // $8006: LDA $2137
+        0x8D, 0x00, 0x00,   // $8009: STA $0000
+        0xAD, 0x3F, 0x21,   // $800C: LDA $213F
+        0x8D, 0x01, 0x00,   // $800F: STA $0001
+        0xA9, 0x80,         // $8012: LDA #$80
+        0x8D, 0x01, 0x42,   // $8014: STA $4201
+        0xAD, 0x37, 0x21,   // $8017: LDA $2137
+        0x8D, 0x02, 0x00,   // $801A: STA $0002
+        0xAD, 0x3F, 0x21,   // $801D: LDA $213F
+        0x8D, 0x03, 0x00,   // $8020: STA $0003
+        0x80, 0xFE,         // $8023: BRA $8023
+    ];
+    common::lorom(&code)
+}
+
+#[test]
+fn slhv_open_bus() {
+    let mut snes = SNES::from_rom_data(&latch_rom(), "", None).unwrap();
+    let mut frame = vec![0; snes.frame_buffer_size()];
+    snes.frame(&mut frame);
+    let wram = snes.wram();
+
+    // $2137 returns CPU open bus whether or not WRIO allows it to latch the counters.
+    assert_eq!(wram[0x00], 0x21);
+    assert_eq!(wram[0x01] & 0x40, 0x00, "Latched with WRIO bit 7 clear");
+    assert_eq!(wram[0x02], 0x21);
+    assert_eq!(wram[0x03] & 0x40, 0x40, "Not latched with WRIO bit 7 set");
+}
@@ -0,0 +1,50 @@
+// Check that watched RAM addresses are read each frame.
+mod common;
+
+use oxide7::{
+    SNES,
+    RamWatch
+};
+
+// A 32kB LoROM that writes $42 to $0010, then increments $0011 in the NMI handler.
+fn nmi_counter_rom() -> Vec<u8> {
+    let code = [
+        0xA9, 0x42,         // $8000: LDA #$42
+        0x85, 0x10,         // $8002: STA $10
+        0xA9, 0x80,         // $8004: LDA #$80
+        0x8D, 0x00, 0x42,   // $8006: STA $4200
+        0x80, 0xFE,         // $8009: BRA $8009
+        0xE6, 0x11,         // $800B: INC $11
+        0x40,               // $800D: RTI
+    ];
+    let mut rom = common::lorom(&code);
+    rom[0x7FFA] = 0x0B; // NMI vector
+    rom[0x7FFB] = 0x80;
+    rom
+}
+
+#[test]
+fn watch_values() {
+    let mut snes = SNES::from_rom_data(&nmi_counter_rom(), "", None).unwrap();
+    let mut frame = vec![0; snes.frame_buffer_size()];
+    // The same byte through bank $7E and the low RAM mirror.
+    let mut watch = RamWatch::new(&[0x7E0010, 0x000011, 0x801FFF, 0x7F0000, 0x7E0011]).unwrap();
+
+    snes.frame(&mut frame);
+    snes.update_watch(&mut watch);
+    assert_eq!(watch.changed(), &[0, 1, 2, 3, 4]);
+    let count = watch.values()[1];
+    assert_eq!(&watch.values()[..4], &[0x42, count, 0, 0]);
+    assert_eq!(watch.values()[4], count);
+
+    snes.frame(&mut frame);
+    snes.update_watch(&mut watch);
+    assert_eq!(watch.changed(), &[1, 4]);
+    assert_eq!(watch.values()[1], count.wrapping_add(1));
+}
+
+#[test]
+fn not_wram() {
+    assert!(RamWatch::new(&[0x7E0000, 0x008000]).is_err());
+    assert!(RamWatch::new(&[0x402000]).is_err());
+}
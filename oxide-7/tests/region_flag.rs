@@ -0,0 +1,60 @@
+// Check that the region in the PPU status register matches the console, or the override.
+mod common;
+
+use oxide7::{
+    Region,
+    SNES,
+    SNESBuilder
+};
+
+// A 32kB LoROM that copies $213F to $0000 in RAM forever.
+fn status_rom() -> Vec<u8> {
+    let code = [
+        0xAD, 0x3F, 0x21,   // $8000: LDA $213F
+        0x8D, 0x00, 0x00,   // $8003: STA $0000
+        0x80, 0xF8,         // $8006: BRA $8000
+    ];
+    common::lorom(&code)
+}
+
+// Run a frame, and check bit 4 of $213F.
+fn reads_pal(snes: &mut SNES) -> bool {
+    let mut frame = vec![0; snes.frame_buffer_size()];
+    snes.frame(&mut frame);
+    (snes.wram()[0] & 0x10) != 0
+}
+
+#[test]
+fn matches_region() {
+    let rom = status_rom();
+    let mut ntsc = SNESBuilder::from_rom_data(&rom).build().unwrap();
+    assert!(!reads_pal(&mut ntsc));
+
+    let mut pal = SNESBuilder::from_rom_data(&rom).region(Region::PAL).build().unwrap();
+    assert!(reads_pal(&mut pal));
+}
+
+#[test]
+fn override_region() {
+    let rom = status_rom();
+    let mut snes = SNESBuilder::from_rom_data(&rom)
+        .region(Region::PAL)
+        .region_flag(Region::NTSC)
+        .build()
+        .unwrap();
+    assert!(!reads_pal(&mut snes));
+
+    snes.set_region_flag(Region::PAL);
+    assert!(reads_pal(&mut snes));
+}
+
+#[test]
+fn override_survives_savestate() {
+    let mut snes = SNES::from_rom_data(&status_rom(), "", None).unwrap();
+    snes.set_region_flag(Region::PAL);
+    assert!(reads_pal(&mut snes));
+
+    let state = snes.save_state();
+    snes.load_state(&state).unwrap();
+    assert!(reads_pal(&mut snes));
+}
@@ -0,0 +1,106 @@
+// Check that the render thread draws the same frames as the single-threaded check.
+mod common;
+
+use oxide7::{
+    Event,
+    SNES
+};
+
+// A 32kB LoROM that fills VRAM, CGRAM and OAM with noise from $9000, shows BG1 and sprites,
+// then loops forever.
+fn noise_rom() -> Vec<u8> {
+    let mut code = Vec::new();
+    let mut write = |addr: u16, data: u8| {
+        code.extend_from_slice(&[0xA9, data, 0x8D, addr as u8, (addr >> 8) as u8]); // LDA #data; STA addr
+    };
+    // DMA channel 0 from $00:9000.
+    let dma = |write: &mut dyn FnMut(u16, u8), mode: u8, b_reg: u8, size: u16| {
+        write(0x4300, mode);
+        write(0x4301, b_reg);
+        write(0x4302, 0x00);
+        write(0x4303, 0x90);
+        write(0x4304, 0x00);
+        write(0x4305, size as u8);
+        write(0x4306, (size >> 8) as u8);
+        write(0x420B, 0x01);
+    };
+    write(0x2100, 0x80);    // Forced blank
+    write(0x2105, 0x01);    // Mode 1
+    write(0x2107, 0x00);    // BG1 map at $0000
+    write(0x210B, 0x01);    // BG1 tiles at $1000
+    write(0x2101, 0x02);    // Sprite tiles at $4000
+    write(0x2115, 0x80);
+    write(0x2116, 0x00);    // Map
+    write(0x2117, 0x00);
+    dma(&mut write, 0x01, 0x18, 0x2000);
+    write(0x2116, 0x00);    // BG tiles
+    write(0x2117, 0x10);
+    dma(&mut write, 0x01, 0x18, 0x2000);
+    write(0x2116, 0x00);    // Sprite tiles
+    write(0x2117, 0x40);
+    dma(&mut write, 0x01, 0x18, 0x2000);
+    write(0x2121, 0x00);
+    dma(&mut write, 0x00, 0x22, 0x200);
+    write(0x2102, 0x00);
+    write(0x2103, 0x00);
+    dma(&mut write, 0x00, 0x04, 0x220);
+    write(0x212C, 0x11);    // BG1 and sprites on the main screen
+    write(0x2100, 0x0F);    // Full brightness
+    code.extend_from_slice(&[0x80, 0xFE]);  // BRA to self
+    let mut rom = common::lorom(&code);
+
+    let mut seed = 1_u32;
+    for b in rom[0x1000..0x3000].iter_mut() {
+        seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+        *b = (seed >> 16) as u8;
+    }
+    rom
+}
+
+fn new_snes() -> SNES {
+    SNES::from_rom_data(&noise_rom(), "", None).unwrap()
+}
+
+fn run_frames(snes: &mut SNES, n: usize) -> Vec<u8> {
+    let mut frame = vec![0; snes.frame_buffer_size()];
+    for _ in 0..n {
+        snes.frame(&mut frame);
+    }
+    frame
+}
+
+fn is_mismatch(event: &Event) -> bool {
+    matches!(event, Event::RenderMismatch {..})
+}
+
+#[test]
+fn frames_match() {
+    let mut snes = new_snes();
+    let events = snes.subscribe_events();
+    snes.set_render_verification(true);
+
+    let frame = run_frames(&mut snes, 4);
+    assert!(frame.iter().any(|p| *p != 0));
+    assert!(!events.try_iter().any(|e| is_mismatch(&e)));
+}
+
+#[test]
+fn enable_while_running() {
+    // Video memory isn't written after the first frame, so the check has to build its caches from scratch.
+    let mut snes = new_snes();
+    let events = snes.subscribe_events();
+    run_frames(&mut snes, 2);
+
+    snes.set_render_verification(true);
+    run_frames(&mut snes, 3);
+    assert!(!events.try_iter().any(|e| is_mismatch(&e)));
+}
+
+#[test]
+fn frames_complete() {
+    // The frame is read out after the fence, so it is the same every time.
+    let first = run_frames(&mut new_snes(), 3);
+    for _ in 0..3 {
+        assert_eq!(run_frames(&mut new_snes(), 3), first);
+    }
+}
@@ -0,0 +1,92 @@
+// Check that rewinding goes back to the states that were saved.
+mod common;
+
+use oxide7::{
+    SNES,
+    THUMBNAIL_SIZE
+};
+
+// A 32kB LoROM that counts up in RAM forever.
+fn counter_rom() -> Vec<u8> {
+    common::lorom(&[
+        0xEE, 0x10, 0x00,   // $8000: INC $0010
+        0x80, 0xFB          // $8003: BRA $8000
+    ])
+}
+
+// The state without the header and thumbnail, which shows the last frame drawn rather than the state.
+// Loading a state marks caches as dirty, so this only matches once a frame has run after loading.
+fn state_body(snes: &SNES) -> Vec<u8> {
+    snes.save_state()[(6 + THUMBNAIL_SIZE)..].to_vec()
+}
+
+// Run 10 frames, saving a rewind point after each. Returns the state and WRAM at each point.
+fn run_with_rewind(snes: &mut SNES) -> Vec<(Vec<u8>, Vec<u8>)> {
+    let mut frame = vec![0; snes.frame_buffer_size()];
+    (0..10).map(|_| {
+        snes.frame(&mut frame);
+        snes.push_rewind_point();
+        (state_body(snes), snes.wram().to_vec())
+    }).collect()
+}
+
+#[test]
+fn rewind_to_earlier_points() {
+    let mut snes = SNES::from_rom_data(&counter_rom(), "", None).unwrap();
+    assert!(snes.rewind(1).is_err());
+
+    let states = run_with_rewind(&mut snes);
+    assert_eq!(snes.rewind_len(), 10);
+
+    assert_eq!(snes.rewind(3), Ok(3));
+    assert!(snes.wram() == states[6].1.as_slice());
+    assert_eq!(snes.rewind_len(), 7);
+
+    // The point rewound to is kept.
+    assert_eq!(snes.rewind(0), Ok(0));
+    assert!(snes.wram() == states[6].1.as_slice());
+
+    assert_eq!(snes.rewind(100), Ok(6));
+    assert!(snes.wram() == states[0].1.as_slice());
+    assert_eq!(snes.rewind_len(), 1);
+}
+
+#[test]
+fn run_on_after_rewind() {
+    let mut snes = SNES::from_rom_data(&counter_rom(), "", None).unwrap();
+    let states = run_with_rewind(&mut snes);
+    snes.rewind(5).unwrap();
+
+    let mut frame = vec![0; snes.frame_buffer_size()];
+    snes.frame(&mut frame);
+    assert!(state_body(&snes) == states[5].0);
+}
+
+#[test]
+fn timestamp_after_rewind() {
+    let mut snes = SNES::from_rom_data(&counter_rom(), "", None).unwrap();
+    let mut frame = vec![0; snes.frame_buffer_size()];
+    let timestamps = (0..10).map(|_| {
+        snes.frame(&mut frame);
+        snes.push_rewind_point();
+        snes.frame_timestamp()
+    }).collect::<Vec<_>>();
+
+    snes.rewind(3).unwrap();
+    assert_eq!(snes.frame_timestamp(), timestamps[6]);
+
+    // The clock carries on from the point rewound to.
+    snes.frame(&mut frame);
+    assert_eq!(snes.frame_timestamp(), timestamps[7]);
+}
+
+#[test]
+fn rewind_capacity() {
+    let mut snes = SNES::from_rom_data(&counter_rom(), "", None).unwrap();
+    snes.set_rewind_capacity(4);
+    let states = run_with_rewind(&mut snes);
+    assert_eq!(snes.rewind_len(), 4);
+
+    assert_eq!(snes.rewind(10), Ok(3));
+    assert!(snes.wram() == states[6].1.as_slice());
+}
@@ -0,0 +1,189 @@
+// Boot a directory of ROMs and compare their frames against a stored hash corpus.
+// This is ignored by default, as the ROMs need to be provided by the user.
+//
+// OXIDE7_ROM_DIR:          Directory containing the ROMs to test (required).
+// OXIDE7_ROM_CORPUS:       Corpus file. Defaults to "hashes.txt" inside the ROM directory.
+// OXIDE7_CORPUS_FRAMES:    Number of frames to run each ROM for (default 300).
+// OXIDE7_DSP_ROM:          DSP ROM, for carts that need one.
+// OXIDE7_UPDATE_CORPUS:    If set, record the current hashes instead of comparing.
+//
+// Test carts such as the SNES Burn-In Test and SNES Test Program need input to step through
+// their tests. Input is read from a file next to the ROM with the extension ".input".
+// Each line is: [frame] [button] [down|up] [joypad (default 0)]
+// Record the hash once the cart shows its results screen.
+//
+// Run with:
+// OXIDE7_ROM_DIR=[DIR] cargo test --release --test rom_corpus -- --ignored --nocapture
+
+use std::{
+    collections::BTreeMap,
+    env,
+    fs,
+    panic,
+    path::{Path, PathBuf}
+};
+
+use oxide7::{
+    SNES,
+    Button
+};
+
+const DEFAULT_FRAMES: usize = 300;
+const CORPUS_FILE_NAME: &str = "hashes.txt";
+const ROM_EXTENSIONS: [&str; 4] = ["sfc", "smc", "swc", "fig"];
+
+// Frame count and hash of the last frame, for each ROM file name.
+type Corpus = BTreeMap<String, (usize, u64)>;
+
+#[test]
+#[ignore]
+fn rom_corpus() {
+    let rom_dir = match env::var("OXIDE7_ROM_DIR") {
+        Ok(dir) => PathBuf::from(dir),
+        Err(_) => {
+            println!("OXIDE7_ROM_DIR not set: skipping ROM corpus.");
+            return;
+        }
+    };
+    let corpus_path = env::var("OXIDE7_ROM_CORPUS").map(PathBuf::from).unwrap_or_else(|_| rom_dir.join(CORPUS_FILE_NAME));
+    let frames = env::var("OXIDE7_CORPUS_FRAMES").ok()
+        .map(|n| n.parse::<usize>().expect("OXIDE7_CORPUS_FRAMES must be a number"))
+        .unwrap_or(DEFAULT_FRAMES);
+    let dsp_rom = env::var("OXIDE7_DSP_ROM").ok();
+    let update = env::var("OXIDE7_UPDATE_CORPUS").is_ok();
+
+    let stored = if corpus_path.exists() {
+        read_corpus(&corpus_path)
+    } else {
+        Corpus::new()
+    };
+    let mut results = Corpus::new();
+    let mut failures = Vec::new();
+
+    for rom in find_roms(&rom_dir) {
+        let name = rom.file_name().unwrap().to_string_lossy().into_owned();
+        let frames = match stored.get(&name) {
+            Some((stored_frames, _)) if !update => *stored_frames,
+            _ => frames
+        };
+
+        let hash = match panic::catch_unwind(|| run_rom(&rom, frames, dsp_rom.as_deref())) {
+            Ok(hash) => hash,
+            Err(_) => {
+                failures.push(format!("{}: panicked", name));
+                continue;
+            }
+        };
+
+        match stored.get(&name) {
+            _ if update => println!("{}: {:016x}", name, hash),
+            Some((_, stored_hash)) if *stored_hash == hash => println!("{}: ok", name),
+            Some((_, stored_hash)) => failures.push(format!("{}: expected {:016x}, got {:016x}", name, stored_hash, hash)),
+            None => println!("{}: not in corpus ({:016x})", name, hash),
+        }
+        results.insert(name, (frames, hash));
+    }
+
+    if update {
+        write_corpus(&corpus_path, &results);
+        println!("Wrote {} hashes to {}", results.len(), corpus_path.display());
+    }
+
+    assert!(failures.is_empty(), "ROM corpus mismatches:\n{}", failures.join("\n"));
+}
+
+fn find_roms(dir: &Path) -> Vec<PathBuf> {
+    let mut roms = fs::read_dir(dir).expect("couldn't read ROM directory")
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| ROM_EXTENSIONS.contains(&ext.to_lowercase().as_str())))
+        .collect::<Vec<_>>();
+    roms.sort();
+    roms
+}
+
+// Run the ROM from power on, and hash the last frame.
+fn run_rom(rom: &Path, frames: usize, dsp_rom: Option<&str>) -> u64 {
+    // Use a fresh save file so results don't depend on previous runs.
+    let save_path = env::temp_dir().join(format!("oxide7-corpus-{}.sav", rom.file_stem().unwrap().to_string_lossy()));
+    let _ = fs::remove_file(&save_path);
+
+    let mut snes = SNES::new(rom.to_str().unwrap(), save_path.to_str().unwrap(), dsp_rom).unwrap();
+    let mut frame = vec![0; snes.frame_buffer_size()];
+    let mut current = 0;
+    for (input_frame, button, pressed, joypad) in read_inputs(&rom.with_extension("input")) {
+        if input_frame >= frames {
+            break;
+        }
+        snes.run_frames_collect_last(input_frame - current, &mut frame);
+        snes.set_button(button, pressed, joypad);
+        current = input_frame;
+    }
+    snes.run_frames_collect_last(frames - current, &mut frame);
+
+    drop(snes);
+    let _ = fs::remove_file(&save_path);
+    fnv1a(&frame)
+}
+
+// Input events as (frame, button, pressed, joypad), in frame order.
+// Returns nothing if the file doesn't exist.
+fn read_inputs(path: &Path) -> Vec<(usize, Button, bool, usize)> {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(_) => return Vec::new(),
+    };
+    let mut inputs = text.lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            let fields = line.split_whitespace().collect::<Vec<_>>();
+            let frame = fields.first().and_then(|f| f.parse::<usize>().ok());
+            let button = fields.get(1).and_then(|b| b.parse::<Button>().ok());
+            let pressed = match fields.get(2) {
+                Some(&"down") => Some(true),
+                Some(&"up") => Some(false),
+                _ => None,
+            };
+            let joypad = fields.get(3).map_or(Some(0), |j| j.parse::<usize>().ok());
+            match (frame, button, pressed, joypad) {
+                (Some(frame), Some(button), Some(pressed), Some(joypad)) => (frame, button, pressed, joypad),
+                _ => panic!("invalid input line in {}: {}", path.display(), line)
+            }
+        })
+        .collect::<Vec<_>>();
+    inputs.sort_by_key(|input| input.0);
+    inputs
+}
+
+// A fixed hash function, so the corpus stays valid across toolchains.
+fn fnv1a(data: &[u8]) -> u64 {
+    data.iter().fold(0xcbf2_9ce4_8422_2325, |hash, b| (hash ^ (*b as u64)).wrapping_mul(0x0000_0100_0000_01B3))
+}
+
+// Each line is: [ROM file name] [frames] [hash]
+// The name is last-but-two so it can contain spaces.
+fn read_corpus(path: &Path) -> Corpus {
+    let text = fs::read_to_string(path).expect("couldn't read ROM corpus");
+    text.lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            let mut fields = line.rsplitn(3, ' ');
+            let hash = fields.next().and_then(|h| u64::from_str_radix(h, 16).ok());
+            let frames = fields.next().and_then(|n| n.parse::<usize>().ok());
+            match (fields.next(), frames, hash) {
+                (Some(name), Some(frames), Some(hash)) => (name.to_string(), (frames, hash)),
+                _ => panic!("invalid ROM corpus line: {}", line)
+            }
+        })
+        .collect()
+}
+
+fn write_corpus(path: &Path, corpus: &Corpus) {
+    let text = corpus.iter()
+        .map(|(name, (frames, hash))| format!("{} {} {:016x}\n", name, frames, hash))
+        .collect::<String>();
+    fs::write(path, text).expect("couldn't write ROM corpus");
+}
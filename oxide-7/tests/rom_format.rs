@@ -0,0 +1,95 @@
+// Check that copier headers are stripped and interleaved HiROM dumps are un-shuffled on load.
+mod common;
+
+use oxide7::{
+    ROMFormat,
+    SNES
+};
+
+// A 64kB LoROM that reads $01:8000 into $0000.
+// Only LoROM mapping puts the start of the second 32kB there.
+fn lorom_with_marker() -> Vec<u8> {
+    let code = [
+        0xAF, 0x00, 0x80, 0x01,     // $8000: LDA $018000
+        0x8D, 0x00, 0x00,           // $8004: STA $0000
+        0x80, 0xFE,                 // $8007: BRA $8007
+    ];
+    let mut rom = common::Cart::lorom().size(0x1_0000).build(&code);
+    rom[0x8000] = 0x5A;
+    rom
+}
+
+// A 512-byte copier header with bytes 4-11 set.
+fn copier_header(bytes: [u8; 8]) -> Vec<u8> {
+    let mut header = vec![0; 512];
+    header[4..12].copy_from_slice(&bytes);
+    header
+}
+
+#[test]
+fn copier_header_stripped() {
+    let headers = [
+        (ROMFormat::SMC, copier_header([0, 0, 0, 0, 0xAA, 0xBB, 0x04, 0])),
+        (ROMFormat::FIG, copier_header([0x77, 0x83, 0, 0, 0, 0, 0, 0])),
+        (ROMFormat::Copier, copier_header([0; 8])),
+    ];
+
+    for (format, header) in headers.iter() {
+        let mut rom = header.clone();
+        rom.extend_from_slice(&lorom_with_marker());
+
+        let mut snes = SNES::from_rom_data(&rom, "", None).unwrap();
+        let info = snes.cart_info();
+        assert_eq!(info.format, *format);
+        assert_eq!(info.rom_size, 0x1_0000);
+        assert!(info.name.starts_with("TEST"));
+        assert!(!info.interleaved);
+
+        let mut frame = vec![0; snes.frame_buffer_size()];
+        snes.frame(&mut frame);
+        assert_eq!(snes.wram()[0], 0x5A, "{:?}", format);
+    }
+}
+
+#[test]
+fn plain_rom() {
+    let snes = SNES::from_rom_data(&lorom_with_marker(), "", None).unwrap();
+    let info = snes.cart_info();
+    assert_eq!(info.format, ROMFormat::Plain);
+    assert_eq!(info.rom_size, 0x1_0000);
+}
+
+// Shuffle a HiROM the way an interleaved dump stores it:
+// the upper half of each bank first, then the lower half of each bank.
+fn interleave(rom: &[u8]) -> Vec<u8> {
+    let halves = rom.chunks(0x8000).collect::<Vec<_>>();
+    let upper = halves.iter().skip(1).step_by(2);
+    let lower = halves.iter().step_by(2);
+    upper.chain(lower).flat_map(|half| half.iter().cloned()).collect()
+}
+
+#[test]
+fn interleaved_hirom() {
+    // A 128kB HiROM that reads the start of both banks into $0000-$0001.
+    let code = [
+        0xAF, 0x00, 0x00, 0xC0,     // $8000: LDA $C00000
+        0x8D, 0x00, 0x00,           // $8004: STA $0000
+        0xAF, 0x00, 0x00, 0xC1,     // $8007: LDA $C10000
+        0x8D, 0x01, 0x00,           // $800B: STA $0001
+        0x80, 0xFE,                 // $800E: BRA $800E
+    ];
+    let mut rom = common::Cart::hirom().size(0x2_0000).build(&code);
+    rom[0x0_0000] = 0x11;
+    rom[0x1_0000] = 0x22;
+
+    let mut snes = SNES::from_rom_data(&interleave(&rom), "", None).unwrap();
+    let info = snes.cart_info();
+    assert!(info.interleaved);
+    assert_eq!(info.format, ROMFormat::Plain);
+    assert_eq!(info.rom_size, 0x2_0000);
+    assert!(info.name.starts_with("TEST"));
+
+    let mut frame = vec![0; snes.frame_buffer_size()];
+    snes.frame(&mut frame);
+    assert_eq!(&snes.wram()[..2], &[0x11, 0x22]);
+}
@@ -0,0 +1,21 @@
+// Check that reads past the end of a small ROM are mirrored.
+mod common;
+
+use oxide7::SNES;
+
+#[test]
+fn read_past_end_of_rom() {
+    // A 32kB LoROM. $20:8000 is 1MB into the ROM, which mirrors the start.
+    let rom = common::lorom(&[
+        0xAF, 0x00, 0x80, 0x20,     // $8000: LDA $208000
+        0x8D, 0x00, 0x00,           // $8004: STA $0000
+        0xAF, 0x01, 0x80, 0xFF,     // $8007: LDA $FF8001
+        0x8D, 0x01, 0x00,           // $800B: STA $0001
+        0x80, 0xFE,                 // $800E: BRA $800E
+    ]);
+
+    let mut snes = SNES::from_rom_data(&rom, "", None).unwrap();
+    let mut frame = vec![0; snes.frame_buffer_size()];
+    snes.frame(&mut frame);
+    assert_eq!(&snes.wram()[..2], &[0xAF, 0x00]);
+}
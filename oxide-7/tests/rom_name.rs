@@ -0,0 +1,24 @@
+// Check that ROM names that aren't valid UTF-8 can be loaded.
+mod common;
+
+use oxide7::SNES;
+
+// A 32kB LoROM with a name ending in half-width katakana, as used by Japanese games.
+fn katakana_rom() -> Vec<u8> {
+    common::Cart::lorom()
+        .name(b"TEST \xB1\xB2              ")
+        .build(&[
+            0x80, 0xFE, // $8000: BRA $8000
+        ])
+}
+
+#[test]
+fn invalid_utf8_name() {
+    let mut snes = SNES::from_rom_data(&katakana_rom(), "", None).unwrap();
+    let name = snes.rom_name();
+    assert!(name.starts_with("TEST "));
+    assert!(name.contains('\u{FFFD}'));
+
+    let mut frame = vec![0; snes.frame_buffer_size()];
+    snes.frame(&mut frame);
+}
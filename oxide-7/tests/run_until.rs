@@ -0,0 +1,73 @@
+// Check the debug run_until helper with a tiny generated ROM.
+#![cfg(feature = "debug")]
+
+mod common;
+
+use oxide7::{
+    SNES,
+    debug::{
+        Condition,
+        run_until
+    }
+};
+
+// A 32kB LoROM that increments $10 in a loop.
+fn counter_rom() -> Vec<u8> {
+    common::lorom(&[
+        0x18,       // $8000: CLC
+        0xFB,       // $8001: XCE
+        0xE6, 0x10, // $8002: INC $10
+        0x80, 0xFC, // $8004: BRA $8002
+    ])
+}
+
+#[test]
+fn run_until_pc() {
+    let mut snes = SNES::from_rom_data(&counter_rom(), "", None).unwrap();
+
+    assert_eq!(run_until(&mut snes, Condition::PC(0x008004), 100), Some(3));
+    // Already there.
+    assert_eq!(run_until(&mut snes, Condition::PC(0x008004), 100), Some(0));
+    assert_eq!(run_until(&mut snes, Condition::PC(0x009000), 100), None);
+}
+
+#[test]
+fn run_until_mem() {
+    let mut snes = SNES::from_rom_data(&counter_rom(), "", None).unwrap();
+
+    assert!(run_until(&mut snes, Condition::Mem{addr: 0x7E0010, value: 5}, 100).is_some());
+    assert_eq!(snes.wram()[0x10], 5);
+}
+
+#[test]
+fn run_until_scanline() {
+    let mut snes = SNES::from_rom_data(&counter_rom(), "", None).unwrap();
+
+    assert!(run_until(&mut snes, Condition::Scanline(100), 100_000).is_some());
+    assert_eq!(snes.get_hv_counter().1, 100);
+    // Runs into the next frame.
+    assert!(run_until(&mut snes, Condition::Scanline(50), 100_000).is_some());
+    assert_eq!(snes.get_hv_counter().1, 50);
+}
+
+#[test]
+fn cycle_profile() {
+    let mut snes = SNES::from_rom_data(&counter_rom(), "", None).unwrap();
+
+    run_until(&mut snes, Condition::PC(0x008004), 100);
+    let profile = snes.get_cycle_profile();
+    assert_eq!(profile.last_opcode, 0xE6);
+    assert_eq!(profile.counts[0xE6], 1);
+    assert_eq!(profile.cycles[0xE6], profile.last_cycles as u64);
+    // INC dp takes longer than the implied mode XCE.
+    assert!(profile.cycles[0xE6] > profile.cycles[0xFB]);
+
+    run_until(&mut snes, Condition::Mem{addr: 0x7E0010, value: 10}, 100);
+    let profile = snes.get_cycle_profile();
+    assert_eq!(profile.counts[0xE6], 10);
+    assert_eq!(profile.counts[0x80], 9);
+    assert_eq!(profile.by_total_cycles().len(), 4);
+
+    snes.reset_cycle_profile();
+    assert!(snes.get_cycle_profile().by_total_cycles().is_empty());
+}
@@ -0,0 +1,50 @@
+// Check that S-DD1 carts are detected, and DMA from compressed data is decompressed.
+mod common;
+
+use oxide7::{
+    EnhancementChip,
+    SNES
+};
+
+// A 32kB S-DD1 cart that decompresses a byte from $C0:4000 into $0000 in RAM with DMA,
+// copies $4801 to $0001, then loops forever.
+fn sdd1_rom() -> Vec<u8> {
+    let mut code = Vec::new();
+    let mut write = |addr: u16, data: u8| {
+        code.extend_from_slice(&[0xA9, data, 0x8D, addr as u8, (addr >> 8) as u8]); // LDA #data; STA addr
+    };
+    write(0x2181, 0x00);    // WRAM address $000000
+    write(0x2182, 0x00);
+    write(0x2183, 0x00);
+    write(0x4300, 0x08);    // DMA channel 0: one register, fixed address
+    write(0x4301, 0x80);    // WRAM port
+    write(0x4302, 0x00);    // From $C0:4000
+    write(0x4303, 0x40);
+    write(0x4304, 0xC0);
+    write(0x4305, 0x01);    // 1 byte
+    write(0x4306, 0x00);
+    write(0x4800, 0x01);    // Decompress channel 0
+    write(0x4801, 0x01);
+    write(0x420B, 0x01);    // Start
+    code.extend_from_slice(&[
+        0xAD, 0x01, 0x48,   // LDA $4801
+        0x8D, 0x01, 0x00,   // STA $0001
+        0x80, 0xFE,         // BRA to self
+    ]);
+    let mut rom = common::Cart::lorom()
+        .map_mode(0x32)     // Fast LoROM
+        .rom_type(0x43)     // ROM + S-DD1
+        .build(&code);
+    rom[0x4000] = 0xC8;     // 8 bits per pixel
+    rom
+}
+
+#[test]
+fn decompress_dma() {
+    let mut snes = SNES::from_rom_data(&sdd1_rom(), "", None).unwrap();
+    assert_eq!(snes.cart_info().chip, Some(EnhancementChip::SDD1));
+
+    let mut frame = vec![0; snes.frame_buffer_size()];
+    snes.frame(&mut frame);
+    assert_eq!(&snes.wram()[0..2], &[0x55, 0x00]);
+}
@@ -0,0 +1,83 @@
+// Check that patches next to the cart are applied when loading with soft patching.
+
+mod common;
+
+use std::{
+    env,
+    fs,
+    path::PathBuf
+};
+
+use oxide7::{
+    Event,
+    SNES
+};
+
+// A 32kB LoROM that does nothing, named "TEST".
+fn test_rom() -> Vec<u8> {
+    common::lorom(&[
+        0x80, 0xFE, // $8000: BRA $8000
+    ])
+}
+
+// The events sent with the first frame, which include any problems from loading.
+fn first_frame_events(snes: &mut SNES) -> Vec<Event> {
+    let events = snes.subscribe_events();
+    let mut frame = vec![0; snes.frame_buffer_size()];
+    snes.frame(&mut frame);
+    events.try_iter().collect()
+}
+
+// Write the ROM, and the patch if there is one, to a temp directory.
+fn write_files(name: &str, patch: Option<(&str, &[u8])>) -> (String, String) {
+    let dir = env::temp_dir();
+    let cart_path: PathBuf = dir.join(format!("oxide7-{}.sfc", name));
+    fs::write(&cart_path, test_rom()).unwrap();
+    for ext in ["bps", "ips"] {
+        let _ = fs::remove_file(cart_path.with_extension(ext));
+    }
+    if let Some((ext, data)) = patch {
+        fs::write(cart_path.with_extension(ext), data).unwrap();
+    }
+    let save_path = dir.join(format!("oxide7-{}.sav", name));
+    (cart_path.to_string_lossy().into_owned(), save_path.to_string_lossy().into_owned())
+}
+
+// Rename the game to "PTCH".
+fn rename_ips() -> Vec<u8> {
+    let mut patch = b"PATCH".to_vec();
+    patch.extend_from_slice(&[0x00, 0x7F, 0xC0, 0x00, 0x04]);
+    patch.extend_from_slice(b"PTCH");
+    patch.extend_from_slice(b"EOF");
+    patch
+}
+
+#[test]
+fn ips_applied() {
+    let (cart, save) = write_files("softpatch-ips", Some(("ips", &rename_ips())));
+    let mut snes = SNES::new_soft_patched(&cart, &save, None).unwrap();
+    let info = snes.cart_info();
+    assert!(info.patched);
+    assert!(info.name.starts_with("PTCH"));
+    assert!(first_frame_events(&mut snes).iter().all(|e| !matches!(e, Event::BadPatch(_))));
+
+    // The original cart file is unchanged.
+    assert_eq!(fs::read(&cart).unwrap(), test_rom());
+}
+
+#[test]
+fn not_applied_without_soft_patching() {
+    let (cart, save) = write_files("softpatch-off", Some(("ips", &rename_ips())));
+    let info = SNES::new(&cart, &save, None).unwrap().cart_info();
+    assert!(!info.patched);
+    assert!(info.name.starts_with("TEST"));
+}
+
+#[test]
+fn bad_patch_warns() {
+    let (cart, save) = write_files("softpatch-bad", Some(("bps", b"BPS1 not really a patch")));
+    let mut snes = SNES::new_soft_patched(&cart, &save, None).unwrap();
+    assert!(!snes.cart_info().patched);
+    assert!(snes.cart_info().name.starts_with("TEST"));
+    assert!(first_frame_events(&mut snes).iter().any(|e| matches!(e, Event::BadPatch(_))));
+}
@@ -0,0 +1,78 @@
+// Check that SPC7110 carts are detected, their data ROM and SRAM can be read, and the clock is saved.
+mod common;
+
+use std::{env, fs};
+
+use oxide7::{
+    EnhancementChip,
+    SNES
+};
+
+// A 2MB SPC7110 cart with an RTC: 1MB of program ROM and 1MB of data ROM.
+// It copies SRAM, the data port, bank $D0, and the decompression status into $0000-$0005, then loops forever.
+fn spc7110_rom() -> Vec<u8> {
+    let mut code = Vec::new();
+    let mut write = |addr: u16, data: u8| {
+        code.extend_from_slice(&[0xA9, data, 0x8D, addr as u8, (addr >> 8) as u8]); // LDA #data; STA addr
+    };
+    write(0x4830, 0x80);    // Enable SRAM
+    write(0x6000, 0x42);
+    write(0x4811, 0x00);    // Data port at $000000
+    write(0x4812, 0x00);
+    write(0x4813, 0x00);
+    write(0x4801, 0x00);    // Decompression table at $000100
+    write(0x4802, 0x01);
+    write(0x4803, 0x00);
+    write(0x4804, 0x00);
+    write(0x4805, 0x00);
+    write(0x4806, 0x00);
+    code.extend_from_slice(&[
+        0xAD, 0x00, 0x60,       // LDA $6000
+        0x8D, 0x00, 0x00,       // STA $0000
+        0xAD, 0x10, 0x48,       // LDA $4810
+        0x8D, 0x01, 0x00,       // STA $0001
+        0xAD, 0x10, 0x48,       // LDA $4810
+        0x8D, 0x02, 0x00,       // STA $0002
+        0xAF, 0x00, 0x00, 0xD0, // LDA $D00000
+        0x8D, 0x03, 0x00,       // STA $0003
+        0xAD, 0x0C, 0x48,       // LDA $480C
+        0x8D, 0x04, 0x00,       // STA $0004
+        0xAD, 0x00, 0x48,       // LDA $4800
+        0x8D, 0x05, 0x00,       // STA $0005
+        0x80, 0xFE,             // BRA to self
+    ]);
+    let mut rom = common::Cart::hirom()
+        .size(0x20_0000)    // 2MB
+        .map_mode(0x3A)     // SPC7110
+        .rom_type(0xF9)     // ROM + RAM + battery + RTC + custom chip
+        .sram_size(0x03)    // 8kB SRAM
+        .build(&code);
+
+    // Data ROM. The table entry is 1bpp data at $000200, which is all zeroes.
+    rom[0x10_0000] = 0x12;
+    rom[0x10_0001] = 0x34;
+    rom[0x10_0100..0x10_0104].copy_from_slice(&[0x00, 0x00, 0x02, 0x00]);
+
+    rom[0xFFBF] = 0x00; // Custom chip subtype
+    rom
+}
+
+#[test]
+fn data_rom_and_sram() {
+    let save_path = env::temp_dir().join("oxide7-spc7110.sav");
+    let rtc_path = save_path.with_extension("rtc");
+    let _ = fs::remove_file(&save_path);
+    let _ = fs::remove_file(&rtc_path);
+
+    let mut snes = SNES::from_rom_data(&spc7110_rom(), save_path.to_str().unwrap(), None).unwrap();
+    assert_eq!(snes.cart_info().chip, Some(EnhancementChip::SPC7110));
+    assert!(rtc_path.is_file());
+
+    let mut frame = vec![0; snes.frame_buffer_size()];
+    snes.frame(&mut frame);
+    assert_eq!(&snes.wram()[0..6], &[0x42, 0x12, 0x34, 0x12, 0x80, 0x00]);
+
+    drop(snes);
+    let _ = fs::remove_file(&save_path);
+    let _ = fs::remove_file(&rtc_path);
+}
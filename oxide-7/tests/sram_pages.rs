@@ -0,0 +1,118 @@
+// Check that only the pages of SRAM that changed are written to the save file.
+mod common;
+
+use std::{
+    env,
+    fs::{
+        self,
+        OpenOptions
+    },
+    io::{
+        Seek,
+        SeekFrom,
+        Write
+    },
+    path::PathBuf
+};
+
+use oxide7::{
+    SNES,
+    StateComponent
+};
+
+// A 32kB LoROM with 8kB SRAM, that writes once to $1400 in SRAM, then counts up at $0000 forever.
+fn counter_rom() -> Vec<u8> {
+    let code = [
+        0xA9, 0x5A,                 // $8000: LDA #$5A
+        0x8F, 0x00, 0x14, 0x70,     // $8002: STA $701400
+        0x1A,                       // $8006: INC A
+        0x8F, 0x00, 0x00, 0x70,     // $8007: STA $700000
+        0x80, 0xF9,                 // $800B: BRA $8006
+    ];
+    common::Cart::lorom()
+        .rom_type(0x02)     // ROM + RAM + battery
+        .sram_size(0x03)    // 8kB SRAM
+        .build(&code)
+}
+
+fn save_path(name: &str) -> PathBuf {
+    let path = env::temp_dir().join(format!("oxide7-{}.sav", name));
+    let _ = fs::remove_file(&path);
+    path
+}
+
+fn run_frames(snes: &mut SNES, frames: usize) {
+    let mut frame = vec![0; snes.frame_buffer_size()];
+    for _ in 0..frames {
+        snes.frame(&mut frame);
+    }
+}
+
+// Change a byte of the save file behind the emulator's back.
+fn poke_file(path: &PathBuf, offset: u64, data: u8) {
+    let mut file = OpenOptions::new().write(true).open(path).unwrap();
+    file.seek(SeekFrom::Start(offset)).unwrap();
+    file.write_all(&[data]).unwrap();
+}
+
+#[test]
+fn flush_changed_pages() {
+    let path = save_path("sram-pages");
+    let mut snes = SNES::from_rom_data(&counter_rom(), path.to_str().unwrap(), None).unwrap();
+    run_frames(&mut snes, 2);
+    let save = fs::read(&path).unwrap();
+    assert_eq!(save.len(), 0x2000);
+    assert_eq!(save[0x1400], 0x5A);
+
+    // Only the first page keeps changing, so the rest of the file isn't written again.
+    poke_file(&path, 0x1000, 0xAB);
+    run_frames(&mut snes, 2);
+    let save = fs::read(&path).unwrap();
+    assert_eq!(save[0x1000], 0xAB);
+    assert_ne!(save[0x0000], 0x00);
+
+    drop(snes);
+    let _ = fs::remove_file(&path);
+}
+
+#[test]
+fn savestate_marks_changed_pages() {
+    let path = save_path("sram-state");
+    let mut snes = SNES::from_rom_data(&counter_rom(), path.to_str().unwrap(), None).unwrap();
+    run_frames(&mut snes, 2);
+    let state = snes.save_state();
+
+    // Loading a state with the same data in a page doesn't write it again.
+    poke_file(&path, 0x1000, 0xAB);
+    snes.load_state(&state).unwrap();
+    run_frames(&mut snes, 2);
+    assert_eq!(fs::read(&path).unwrap()[0x1000], 0xAB);
+
+    // Pages that differ are written.
+    let other_path = save_path("sram-state-other");
+    let mut other = SNES::from_rom_data(&counter_rom(), other_path.to_str().unwrap(), None).unwrap();
+    other.load_state(&state).unwrap();
+    run_frames(&mut other, 1);
+    let save = fs::read(&other_path).unwrap();
+    assert_eq!(save[0x1400], 0x5A);
+    assert_eq!(save[0x1000], 0x00);
+
+    drop(snes);
+    drop(other);
+    let _ = fs::remove_file(&path);
+    let _ = fs::remove_file(&other_path);
+}
+
+#[test]
+fn savestate_size_is_fixed() {
+    let path = save_path("sram-state-size");
+    let mut snes = SNES::from_rom_data(&counter_rom(), path.to_str().unwrap(), None).unwrap();
+    let blank = snes.export_component_state(StateComponent::Cart);
+    run_frames(&mut snes, 1);
+    let written = snes.export_component_state(StateComponent::Cart);
+    assert_ne!(blank, written);
+    assert_eq!(blank.len(), written.len());
+
+    drop(snes);
+    let _ = fs::remove_file(&path);
+}
@@ -0,0 +1,52 @@
+// Check symbol file loading.
+#![cfg(feature = "debug")]
+
+use oxide7::debug::SymbolTable;
+
+const SYM_FILE: &str = "; wla symbolic information file
+; generated automatically by wlalink
+
+[labels]
+00:8000 Reset
+00:8010 NMI_Handler
+00:8010 VBlank
+7e:0010 counter
+
+[definitions]
+00000001 _sizeof_counter
+";
+
+#[test]
+fn parse_labels() {
+    let symbols = SymbolTable::parse(SYM_FILE).unwrap();
+
+    assert_eq!(symbols.len(), 4);
+    assert_eq!(symbols.address_of("NMI_Handler"), Some(0x008010));
+    assert_eq!(symbols.address_of("VBlank"), Some(0x008010));
+    assert_eq!(symbols.address_of("counter"), Some(0x7E0010));
+    // Definitions aren't addresses.
+    assert_eq!(symbols.address_of("_sizeof_counter"), None);
+    // The first label at an address is shown.
+    assert_eq!(symbols.label_at(0x008010), Some("NMI_Handler"));
+}
+
+#[test]
+fn format_addresses() {
+    let symbols = SymbolTable::parse(SYM_FILE).unwrap();
+
+    assert_eq!(symbols.format_addr(0x008000), "$008000 <Reset>");
+    assert_eq!(symbols.format_addr(0x008012), "$008012 <NMI_Handler+$2>");
+    // Labels in other banks aren't used.
+    assert_eq!(symbols.format_addr(0x017FFF), "$017FFF");
+    assert_eq!(symbols.resolve("Reset"), Some(0x008000));
+    assert_eq!(symbols.resolve("$C08000"), Some(0xC08000));
+}
+
+#[test]
+fn no_sections() {
+    let symbols = SymbolTable::parse("00:8000 Reset\n").unwrap();
+    assert_eq!(symbols.address_of("Reset"), Some(0x008000));
+
+    assert!(SymbolTable::parse("8000 Reset\n").is_err());
+    assert!(SymbolTable::parse("00:8000\n").is_err());
+}
@@ -0,0 +1,69 @@
+// Check the thumbnail stored in savestates.
+mod common;
+
+use oxide7::{
+    SNESBuilder,
+    THUMBNAIL_HEIGHT,
+    THUMBNAIL_WIDTH,
+    read_thumbnail
+};
+
+// A 32kB LoROM that shows a white backdrop on the top half of the screen.
+// An IRQ at line 112 turns on forced blank, and NMI turns it off again.
+fn half_white_rom() -> Vec<u8> {
+    let mut rom = common::lorom(&[
+        0xA9, 0xFF,                 // $8000: LDA #$FF
+        0x8D, 0x22, 0x21,           // $8002: STA $2122
+        0xA9, 0x7F,                 // $8005: LDA #$7F
+        0x8D, 0x22, 0x21,           // $8007: STA $2122
+        0xA9, 0x70,                 // $800A: LDA #$70
+        0x8D, 0x09, 0x42,           // $800C: STA $4209
+        0x9C, 0x0A, 0x42,           // $800F: STZ $420A
+        0xA9, 0xA0,                 // $8012: LDA #$A0
+        0x8D, 0x00, 0x42,           // $8014: STA $4200
+        0x58,                       // $8017: CLI
+        0x80, 0xFE,                 // $8018: BRA $8018
+    ]);
+    rom[0x20..0x26].copy_from_slice(&[
+        0xA9, 0x0F,                 // $8020: LDA #$0F
+        0x8D, 0x00, 0x21,           // $8022: STA $2100
+        0x40,                       // $8025: RTI
+    ]);
+    rom[0x30..0x39].copy_from_slice(&[
+        0xAD, 0x11, 0x42,           // $8030: LDA $4211
+        0xA9, 0x80,                 // $8033: LDA #$80
+        0x8D, 0x00, 0x21,           // $8035: STA $2100
+        0x40,                       // $8038: RTI
+    ]);
+    rom[0x7FFA] = 0x20; // NMI vector: $8020
+    rom[0x7FFB] = 0x80;
+    rom[0x7FFE] = 0x30; // IRQ vector: $8030
+    rom[0x7FFF] = 0x80;
+    rom
+}
+
+#[test]
+fn widescreen_thumbnail() {
+    let rom = half_white_rom();
+    for &extra in [0, 48].iter() {
+        let mut snes = SNESBuilder::from_rom_data(&rom).widescreen(extra).build().unwrap();
+        let mut frame = vec![0; snes.frame_buffer_size()];
+        for _ in 0..3 {
+            snes.frame(&mut frame);
+        }
+
+        // Each row of the thumbnail is two lines of the frame.
+        let state = snes.save_state();
+        let thumbnail = read_thumbnail(&state).unwrap();
+        for (y, row) in thumbnail.chunks_exact(THUMBNAIL_WIDTH * 4).enumerate() {
+            let colour = match y {
+                0..=54 => [0xFF; 3],
+                57..=THUMBNAIL_HEIGHT => [0; 3],
+                _ => continue,
+            };
+            for pixel in row.chunks_exact(4) {
+                assert_eq!(&pixel[..3], &colour, "widescreen: {} row: {}", extra, y);
+            }
+        }
+    }
+}
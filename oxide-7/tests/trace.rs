@@ -0,0 +1,95 @@
+// Check trace log formatting and comparison.
+#![cfg(feature = "debug")]
+
+mod common;
+
+use oxide7::{
+    SNES,
+    debug::{
+        TraceEntry,
+        compare_trace,
+        trace_line
+    }
+};
+
+// A 32kB LoROM that increments $10 in a loop in native mode.
+fn counter_rom() -> Vec<u8> {
+    common::lorom(&[
+        0x18,               // $8000: CLC
+        0xFB,               // $8001: XCE
+        0xC2, 0x20,         // $8002: REP #$20
+        0xA9, 0x34, 0x12,   // $8004: LDA #$1234
+        0xE6, 0x10,         // $8007: INC $10
+        0x80, 0xFC,         // $8009: BRA $8007
+    ])
+}
+
+// Trace the first instructions of the ROM.
+fn make_log(len: usize) -> Vec<String> {
+    let mut snes = SNES::from_rom_data(&counter_rom(), "", None).unwrap();
+    snes.start_frame();
+    (0..len).map(|_| {
+        let line = trace_line(&mut snes);
+        let count = snes.instr_count();
+        while snes.instr_count() == count {
+            snes.step();
+        }
+        line
+    }).collect()
+}
+
+#[test]
+fn format_trace() {
+    let log = make_log(5);
+    assert!(log[0].starts_with("008000 clc                  A:0000 X:0000 Y:0000 S:0100 D:0000 B:00 nvMXdIzc V:  0 "));
+    assert!(log[2].starts_with("008002 rep #$20"));
+    // 16-bit immediate after REP.
+    assert!(log[3].starts_with("008004 lda #$1234"));
+    assert!(log[4].starts_with("008007 inc $10"));
+    assert!(log[4].contains(" A:1234 "));
+
+    let entry = TraceEntry::parse(&log[4]).unwrap();
+    assert_eq!(entry.pc, 0x008007);
+    assert_eq!(entry.a, Some(0x1234));
+    assert_eq!(entry.db, Some(0));
+    // m flag is clear.
+    assert_eq!(entry.p.map(|p| p & 0x20), Some(0));
+}
+
+#[test]
+fn parse_mesen() {
+    let entry = TraceEntry::parse("00:8007 INC $10  A:1234 X:0000 Y:0000 S:01FF D:0000 DB:00 P:14 V:0 H:200").unwrap();
+    assert_eq!(entry, TraceEntry {
+        pc: 0x008007,
+        a:  Some(0x1234),
+        x:  Some(0),
+        y:  Some(0),
+        s:  Some(0x01FF),
+        d:  Some(0),
+        db: Some(0),
+        p:  Some(0x14),
+    });
+    assert!(TraceEntry::parse("[DMA] channel 0").is_none());
+    assert!(TraceEntry::parse("").is_none());
+}
+
+#[test]
+fn compare_matching() {
+    let log = make_log(20).join("\n");
+    let mut snes = SNES::from_rom_data(&counter_rom(), "", None).unwrap();
+    assert_eq!(compare_trace(&mut snes, &log), None);
+}
+
+#[test]
+fn compare_divergence() {
+    let mut log = make_log(20);
+    log[10] = log[10].replace("A:1234", "A:1235");
+    log.insert(0, String::from("trace start"));
+
+    let mut snes = SNES::from_rom_data(&counter_rom(), "", None).unwrap();
+    let divergence = compare_trace(&mut snes, &log.join("\n")).unwrap();
+    assert_eq!(divergence.line, 12);
+    assert_eq!(divergence.fields, vec!["A"]);
+    assert_eq!(divergence.expected, log[11]);
+    assert_eq!(divergence.actual.replace("A:1234", "A:1235"), log[11]);
+}
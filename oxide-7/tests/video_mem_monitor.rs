@@ -0,0 +1,119 @@
+// Check that writes to VRAM, OAM and CGRAM are passed to the video memory monitor.
+mod common;
+
+use std::{
+    cell::RefCell,
+    rc::Rc
+};
+
+use oxide7::{
+    SNES,
+    VideoMemMonitor
+};
+
+// A 32kB LoROM that writes to each video memory during forced blank, then copies 4 bytes
+// from $8100 to VRAM $0000 with DMA, then loops forever.
+fn upload_rom() -> Vec<u8> {
+    let mut code = Vec::new();
+    let mut write = |addr: u16, data: u8| {
+        code.extend_from_slice(&[0xA9, data, 0x8D, addr as u8, (addr >> 8) as u8]); // LDA #data; STA addr
+    };
+    write(0x2100, 0x80);    // Forced blank
+    write(0x2115, 0x80);    // Increment VRAM address after high byte
+    write(0x2116, 0x00);    // VRAM word address $1000
+    write(0x2117, 0x10);
+    write(0x2118, 0x12);
+    write(0x2119, 0x34);
+    write(0x2121, 0x05);    // Colour 5
+    write(0x2122, 0xAB);
+    write(0x2122, 0x7C);
+    write(0x2102, 0x00);    // OAM address 0
+    write(0x2103, 0x00);
+    write(0x2104, 0x11);
+    write(0x2104, 0x22);
+    write(0x2116, 0x00);    // VRAM word address 0
+    write(0x2117, 0x00);
+    write(0x4300, 0x01);    // DMA channel 0: two registers, $2118 and $2119
+    write(0x4301, 0x18);
+    write(0x4302, 0x00);    // From $00:8100
+    write(0x4303, 0x81);
+    write(0x4304, 0x00);
+    write(0x4305, 0x04);    // 4 bytes
+    write(0x4306, 0x00);
+    write(0x420B, 0x01);    // Start
+    code.extend_from_slice(&[0x80, 0xFE]);  // BRA to self
+    let mut rom = common::lorom(&code);
+    rom[0x100..0x104].copy_from_slice(&[0x01, 0x02, 0x03, 0x04]);
+    rom
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Mem {
+    VRAM,
+    OAM,
+    CGRAM
+}
+
+// (memory, address, data, scanline) for each write, shared with the test.
+type Writes = Rc<RefCell<Vec<(Mem, u16, u8, u16)>>>;
+
+#[derive(Clone, Default)]
+struct WriteLog(Writes);
+
+impl WriteLog {
+    fn writes(&self) -> Vec<(Mem, u16, u8)> {
+        self.0.borrow().iter().map(|(mem, addr, data, _)| (*mem, *addr, *data)).collect()
+    }
+}
+
+impl VideoMemMonitor for WriteLog {
+    fn vram_write(&mut self, addr: u16, data: u8, scanline: u16) {
+        self.0.borrow_mut().push((Mem::VRAM, addr, data, scanline));
+    }
+
+    fn oam_write(&mut self, addr: u16, data: u8, scanline: u16) {
+        self.0.borrow_mut().push((Mem::OAM, addr, data, scanline));
+    }
+
+    fn cgram_write(&mut self, addr: u16, data: u8, scanline: u16) {
+        self.0.borrow_mut().push((Mem::CGRAM, addr, data, scanline));
+    }
+}
+
+fn run_frame(snes: &mut SNES) {
+    let mut frame = vec![0; snes.frame_buffer_size()];
+    snes.frame(&mut frame);
+}
+
+#[test]
+fn port_and_dma_writes() {
+    let mut snes = SNES::from_rom_data(&upload_rom(), "", None).unwrap();
+    let log = WriteLog::default();
+    assert!(snes.set_video_mem_monitor(Box::new(log.clone())).is_none());
+    run_frame(&mut snes);
+
+    assert_eq!(log.writes(), vec![
+        (Mem::VRAM, 0x2000, 0x12),
+        (Mem::VRAM, 0x2001, 0x34),
+        (Mem::CGRAM, 0x000A, 0xAB),
+        (Mem::CGRAM, 0x000B, 0x7C),
+        (Mem::OAM, 0x0000, 0x11),
+        (Mem::OAM, 0x0001, 0x22),
+        (Mem::VRAM, 0x0000, 0x01),
+        (Mem::VRAM, 0x0001, 0x02),
+        (Mem::VRAM, 0x0002, 0x03),
+        (Mem::VRAM, 0x0003, 0x04),
+    ]);
+    assert!(log.0.borrow().iter().all(|(_, _, _, scanline)| *scanline < 262));
+    assert_eq!(&snes.vram()[0..4], &[0x01, 0x02, 0x03, 0x04]);
+}
+
+#[test]
+fn remove_monitor() {
+    let mut snes = SNES::from_rom_data(&upload_rom(), "", None).unwrap();
+    let log = WriteLog::default();
+    snes.set_video_mem_monitor(Box::new(log.clone()));
+    assert!(snes.remove_video_mem_monitor().is_some());
+    run_frame(&mut snes);
+    assert!(log.writes().is_empty());
+}